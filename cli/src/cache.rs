@@ -0,0 +1,169 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    fs,
+    hash::{Hash, Hasher},
+    io,
+    path::{Path, PathBuf},
+};
+
+use g_code::emit::FormatOptions;
+use log::debug;
+use roxmltree::{Document, ParsingOptions};
+use svg2gcode::{ConversionOptions, Settings};
+
+/// On-disk cache of converted programs, keyed by the SVG's content, the images it links to, the
+/// fonts loaded for its text, and everything else that affects the output.
+///
+/// Entries are never invalidated, a changed input or setting just produces a different key.
+/// The hasher is not stable across Rust releases, which only results in cache misses.
+#[derive(Debug)]
+pub struct ConversionCache {
+    dir: PathBuf,
+}
+
+/// Identifies a single conversion
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheKey {
+    svg_hash: u64,
+    settings_hash: u64,
+}
+
+impl CacheKey {
+    pub fn new(
+        svg: &str,
+        settings: &Settings,
+        options: &ConversionOptions,
+        format_options: &FormatOptions,
+    ) -> serde_json::Result<Self> {
+        let svg_hash = {
+            let mut hasher = DefaultHasher::new();
+            svg.hash(&mut hasher);
+            if let Some(dir) = &options.image_dir {
+                // A missing image hashes the same as an empty one, either is only warned about
+                for path in linked_images(svg, dir) {
+                    fs::read(path).unwrap_or_default().hash(&mut hasher);
+                }
+            }
+            options.fonts.faces().hash(&mut hasher);
+            // Stroke fonts contain floats, so hash how they are printed instead, like the format options
+            format!("{:?}", options.fonts.stroke_fonts()).hash(&mut hasher);
+            hasher.finish()
+        };
+
+        // Settings contain floats, so hash their serialized form instead
+        let settings_hash = {
+            let mut hasher = DefaultHasher::new();
+            serde_json::to_string(settings)?.hash(&mut hasher);
+            serde_json::to_string(options)?.hash(&mut hasher);
+            format!("{format_options:?}").hash(&mut hasher);
+            env!("CARGO_PKG_VERSION").hash(&mut hasher);
+            hasher.finish()
+        };
+
+        Ok(Self {
+            svg_hash,
+            settings_hash,
+        })
+    }
+
+    fn file_name(&self) -> String {
+        format!("{:016x}-{:016x}.gcode", self.svg_hash, self.settings_hash)
+    }
+}
+
+/// Files read for `<image>` elements that aren't embedded as `data:` URLs, like the converter does
+fn linked_images(svg: &str, dir: &Path) -> Vec<PathBuf> {
+    const XLINK_NS_URI: &str = "http://www.w3.org/1999/xlink";
+    let options = ParsingOptions {
+        allow_dtd: true,
+        ..Default::default()
+    };
+    let Ok(doc) = Document::parse_with_options(svg, options) else {
+        return vec![];
+    };
+    doc.descendants()
+        .filter(|node| node.has_tag_name("image"))
+        .filter_map(|node| {
+            node.attribute("href")
+                .or_else(|| node.attribute((XLINK_NS_URI, "href")))
+        })
+        .filter(|href| !href.trim().starts_with("data:"))
+        .map(|href| dir.join(href.strip_prefix("file://").unwrap_or(href)))
+        .collect()
+}
+
+impl ConversionCache {
+    pub fn new(dir: PathBuf) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    /// Returns the previously converted program, if there is one
+    pub fn get(&self, key: &CacheKey) -> Option<Vec<u8>> {
+        let path = self.dir.join(key.file_name());
+        match fs::read(&path) {
+            Ok(gcode) => {
+                debug!("Cache hit: {}", path.display());
+                Some(gcode)
+            }
+            Err(_) => {
+                debug!("Cache miss: {}", path.display());
+                None
+            }
+        }
+    }
+
+    pub fn insert(&self, key: &CacheKey, gcode: &[u8]) -> io::Result<()> {
+        // Write then rename so an interrupted run can't leave a truncated entry behind
+        let path = self.dir.join(key.file_name());
+        let tmp_path = path.with_extension("gcode.tmp");
+        fs::write(&tmp_path, gcode)?;
+        fs::rename(tmp_path, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use svg2gcode::{FontFace, StrokeFont};
+
+    use super::*;
+
+    #[test]
+    fn key_depends_on_fonts() {
+        let key = |options: &ConversionOptions| {
+            CacheKey::new("<svg/>", &Settings::default(), options, &Default::default()).unwrap()
+        };
+        let mut options = ConversionOptions::default();
+        let without = key(&options);
+        options.fonts.insert(FontFace {
+            family: "Foo".to_string(),
+            data: vec![1, 2, 3],
+        });
+        let with = key(&options);
+        assert_ne!(without, with);
+
+        let mut changed = ConversionOptions::default();
+        changed.fonts.insert(FontFace {
+            family: "Foo".to_string(),
+            data: vec![1, 2, 4],
+        });
+        assert_ne!(key(&changed), with);
+    }
+
+    #[test]
+    fn key_depends_on_stroke_fonts() {
+        let key = |options: &ConversionOptions| {
+            CacheKey::new("<svg/>", &Settings::default(), options, &Default::default()).unwrap()
+        };
+        let font = |jhf| StrokeFont::from_jhf("Foo", jhf, ' ').unwrap();
+        let mut options = ConversionOptions::default();
+        let without = key(&options);
+        options.fonts.insert_stroke_font(font("12345  1JZ"));
+        let with = key(&options);
+        assert_ne!(without, with);
+
+        let mut changed = ConversionOptions::default();
+        changed.fonts.insert_stroke_font(font("12345  1KZ"));
+        assert_ne!(key(&changed), with);
+    }
+}
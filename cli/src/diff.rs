@@ -0,0 +1,179 @@
+use std::fmt::{self, Display};
+
+use svg2gcode::{HoursMinutesSeconds, JobEstimate, Toolpath};
+
+/// Greatest distance in millimeters between the points compared along the paths of two profiles
+const SAMPLE_SPACING: f64 = 0.1;
+
+/// What converting an SVG with the settings of one profile produced
+pub struct ProfileOutput {
+    pub name: String,
+    pub estimate: JobEstimate,
+    pub paths: usize,
+    /// The program, written once converted
+    pub gcode: Vec<u8>,
+    /// Points along every path, at most [`SAMPLE_SPACING`] apart
+    points: Vec<[f64; 2]>,
+}
+
+impl ProfileOutput {
+    pub fn new(
+        name: String,
+        toolpaths: &[Toolpath],
+        estimate: JobEstimate,
+        tolerance: f64,
+    ) -> Self {
+        let mut points = vec![];
+        for toolpath in toolpaths {
+            let flattened = toolpath.flattened(tolerance);
+            points.extend(flattened.first().map(|p| [p.x, p.y]));
+            for (from, to) in flattened.iter().zip(flattened.iter().skip(1)) {
+                let steps = ((*to - *from).length() / SAMPLE_SPACING).ceil().max(1.) as usize;
+                points.extend((1..=steps).map(|step| {
+                    let p = from.lerp(*to, step as f64 / steps as f64);
+                    [p.x, p.y]
+                }));
+            }
+        }
+        Self {
+            name,
+            estimate,
+            paths: toolpaths.len(),
+            gcode: vec![],
+            points,
+        }
+    }
+
+    /// Bottom left and top right corners of the paths
+    fn bounds(&self) -> Option<[[f64; 2]; 2]> {
+        let first = *self.points.first()?;
+        Some(self.points.iter().fold([first, first], |[min, max], p| {
+            [
+                [min[0].min(p[0]), min[1].min(p[1])],
+                [max[0].max(p[0]), max[1].max(p[1])],
+            ]
+        }))
+    }
+}
+
+/// Report on how the output of the second profile differs from that of the first
+pub struct Diff<'a>(pub &'a ProfileOutput, pub &'a ProfileOutput);
+
+impl Display for Diff<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self(a, b) = self;
+        let width = a.name.len().max(b.name.len()).max(16) + 2;
+        let row =
+            |f: &mut fmt::Formatter<'_>, label: &str, a: String, b: String, change: String| {
+                writeln!(f, "{label:<12}{a:<width$}{b:<width$}{change}")
+            };
+        row(f, "", a.name.clone(), b.name.clone(), "change".to_string())?;
+
+        let [a_time, b_time] = [a, b].map(|output| output.estimate.duration.as_secs_f64());
+        row(
+            f,
+            "Time",
+            HoursMinutesSeconds(a.estimate.duration).to_string(),
+            HoursMinutesSeconds(b.estimate.duration).to_string(),
+            format!("{:+.1}s{}", b_time - a_time, percent(a_time, b_time)),
+        )?;
+        for (label, a, b) in [
+            (
+                "Cutting",
+                a.estimate.cutting_distance,
+                b.estimate.cutting_distance,
+            ),
+            (
+                "Travel",
+                a.estimate.rapid_distance,
+                b.estimate.rapid_distance,
+            ),
+        ] {
+            row(
+                f,
+                label,
+                format!("{a:.1} mm"),
+                format!("{b:.1} mm"),
+                format!("{:+.1} mm{}", b - a, percent(a, b)),
+            )?;
+        }
+        let lines = |output: &ProfileOutput| output.gcode.split(|c| *c == b'\n').count() - 1;
+        for (label, a, b, unit) in [
+            ("Paths", a.paths, b.paths, ""),
+            ("Lines", lines(a), lines(b), ""),
+            ("File size", a.gcode.len(), b.gcode.len(), " bytes"),
+        ] {
+            row(
+                f,
+                label,
+                format!("{a}{unit}"),
+                format!("{b}{unit}"),
+                format!(
+                    "{:+}{unit}{}",
+                    b as i64 - a as i64,
+                    percent(a as f64, b as f64)
+                ),
+            )?;
+        }
+
+        writeln!(f)?;
+        match (farthest(&b.points, &a.points), farthest(&a.points, &b.points)) {
+            (Some(b_from_a), Some(a_from_b)) => writeln!(
+                f,
+                "The paths of {} stray up to {b_from_a:.2} mm from those of {}, which stray up to {a_from_b:.2} mm from them",
+                b.name, a.name
+            )?,
+            _ => writeln!(f, "There are no paths to compare, one of the profiles drew nothing")?,
+        }
+        if let (Some([a_min, a_max]), Some([b_min, b_max])) = (a.bounds(), b.bounds()) {
+            writeln!(
+                f,
+                "The bounding box moved by {:+.2}, {:+.2} mm and changed size by {:+.2} x {:+.2} mm",
+                b_min[0] - a_min[0],
+                b_min[1] - a_min[1],
+                (b_max[0] - b_min[0]) - (a_max[0] - a_min[0]),
+                (b_max[1] - b_min[1]) - (a_max[1] - a_min[1]),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Change from `a` to `b` as a percentage, if there is anything to compare it to
+fn percent(a: f64, b: f64) -> String {
+    if a == 0. {
+        String::new()
+    } else {
+        format!(" ({:+.1}%)", (b - a) / a * 100.)
+    }
+}
+
+/// The farthest any point of `from` is from the nearest point of `to`, or `None` if there are no
+/// points to be near
+///
+/// Each point is compared starting next to where the last one was nearest and stops as soon as it
+/// can't be the farthest, which is quick when the paths are mostly the same and in the same order.
+fn farthest(from: &[[f64; 2]], to: &[[f64; 2]]) -> Option<f64> {
+    if to.is_empty() {
+        return None;
+    }
+    let mut farthest = 0f64;
+    let mut start = 0;
+    for p in from {
+        let mut nearest = f64::INFINITY;
+        let mut nearest_index = start;
+        for i in (start..to.len()).chain(0..start) {
+            let distance = (p[0] - to[i][0]).powi(2) + (p[1] - to[i][1]).powi(2);
+            if distance < nearest {
+                nearest = distance;
+                nearest_index = i;
+            }
+            if nearest <= farthest {
+                break;
+            }
+        }
+        farthest = farthest.max(nearest);
+        start = nearest_index;
+    }
+    Some(farthest.sqrt())
+}
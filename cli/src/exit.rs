@@ -0,0 +1,167 @@
+use std::{
+    fmt, io,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+use log::{LevelFilter, Log, Metadata, Record};
+use svg2gcode::LineTooLong;
+
+/// Warnings about parts of an SVG that could not be converted, logged to [`svg2gcode::UNSUPPORTED`]
+static CONVERSION_WARNINGS: AtomicUsize = AtomicUsize::new(0);
+/// Every other warning logged
+static OTHER_WARNINGS: AtomicUsize = AtomicUsize::new(0);
+/// Messages of all warnings logged, for manifests
+static WARNING_MESSAGES: Mutex<Vec<String>> = Mutex::new(vec![]);
+
+/// Why the CLI failed, which is what it exits with so scripts can tell failures apart
+///
+/// Usage errors in the arguments themselves exit with 2, like other clap programs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Failure {
+    /// Options, settings, or g-code sequences that can't be used, exits with 1
+    InvalidOptions,
+    /// An SVG, settings file, or merge data that can't be parsed, exits with 3
+    Parse,
+    /// With `--strict`, an SVG that uses something that can't be converted, like a clip path or
+    /// text with no font to draw it, exits with 4
+    Unsupported,
+    /// With `--strict`, artwork that goes past the edges of the bed, exits with 5
+    OffBed,
+    /// A file that can't be read or written, exits with 6
+    Io,
+    /// With `--strict`, anything else that was warned about, exits with 7
+    Warning,
+}
+
+impl Failure {
+    /// Failure for an error returned while running, which is [`Self::InvalidOptions`] for a line
+    /// longer than the limit, [`Self::Parse`] for invalid data, and [`Self::Io`] for anything else
+    /// unless it already is a [`Failure`]
+    pub fn of(err: &io::Error) -> Self {
+        match err.get_ref().and_then(|inner| inner.downcast_ref::<Self>()) {
+            Some(failure) => *failure,
+            None if err.get_ref().is_some_and(|inner| inner.is::<LineTooLong>()) => {
+                Self::InvalidOptions
+            }
+            None if err.kind() == io::ErrorKind::InvalidData => Self::Parse,
+            None => Self::Io,
+        }
+    }
+
+    pub fn code(self) -> u8 {
+        match self {
+            Self::InvalidOptions => 1,
+            Self::Parse => 3,
+            Self::Unsupported => 4,
+            Self::OffBed => 5,
+            Self::Io => 6,
+            Self::Warning => 7,
+        }
+    }
+
+    /// Exit right away with the code for this failure, after any errors have been logged
+    pub fn exit(self) -> ! {
+        std::process::exit(self.code().into())
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::InvalidOptions => "invalid options",
+            Self::Parse => "could not parse the input",
+            Self::Unsupported => "the SVG uses features that could not be converted (--strict)",
+            Self::OffBed => "the artwork goes past the edges of the bed (--strict)",
+            Self::Io => "could not read or write a file",
+            Self::Warning => "there were warnings (--strict)",
+        })
+    }
+}
+
+impl std::error::Error for Failure {}
+
+impl From<Failure> for io::Error {
+    fn from(failure: Failure) -> Self {
+        io::Error::other(failure)
+    }
+}
+
+/// Number of conversion and other warnings logged so far
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Warnings {
+    conversion: usize,
+    other: usize,
+    messages: usize,
+}
+
+impl Warnings {
+    pub fn now() -> Self {
+        Self {
+            conversion: CONVERSION_WARNINGS.load(Ordering::Relaxed),
+            other: OTHER_WARNINGS.load(Ordering::Relaxed),
+            messages: WARNING_MESSAGES.lock().map_or(0, |messages| messages.len()),
+        }
+    }
+
+    /// Messages of the warnings logged since `self`
+    pub fn messages(self) -> Vec<String> {
+        WARNING_MESSAGES
+            .lock()
+            .map(|messages| messages.get(self.messages..).unwrap_or_default().to_vec())
+            .unwrap_or_default()
+    }
+
+    /// The failure for `--strict` if there were any warnings since `self`, or if the artwork went
+    /// `off_bed`
+    pub fn strict(self, off_bed: bool) -> Result<(), Failure> {
+        let now = Self::now();
+        if off_bed {
+            Err(Failure::OffBed)
+        } else if now.conversion > self.conversion {
+            Err(Failure::Unsupported)
+        } else if now.other > self.other {
+            Err(Failure::Warning)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Logs like [`env_logger`], counting and keeping warnings as they go by even if they aren't shown
+struct CountingLogger(env_logger::Logger);
+
+impl Log for CountingLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::Level::Warn || self.0.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if record.level() == log::Level::Warn {
+            if record.target() == svg2gcode::UNSUPPORTED {
+                CONVERSION_WARNINGS.fetch_add(1, Ordering::Relaxed);
+            } else {
+                OTHER_WARNINGS.fetch_add(1, Ordering::Relaxed);
+            }
+            if let Ok(mut messages) = WARNING_MESSAGES.lock() {
+                messages.push(record.args().to_string());
+            }
+        }
+        if self.0.matches(record) {
+            self.0.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        self.0.flush();
+    }
+}
+
+/// Set up logging from `RUST_LOG`, counting the warnings for [`Warnings`]
+pub fn init_logger() {
+    let logger = env_logger::Builder::from_default_env().build();
+    log::set_max_level(logger.filter().max(LevelFilter::Warn));
+    log::set_boxed_logger(Box::new(CountingLogger(logger))).expect("logger is only set once");
+}
@@ -1,26 +1,53 @@
-use clap::Parser;
+use clap::{Args, Parser, Subcommand};
 use g_code::{
-    emit::{format_gcode_io, FormatOptions},
+    emit::{FormatOptions, Token},
     parse::snippet_parser,
 };
-use log::{error, info};
+use log::{error, info, warn};
 use roxmltree::ParsingOptions;
 use std::{
+    collections::BTreeMap,
     env,
-    fs::File,
+    fmt::Display,
+    fs::{self, File},
     io::{self, Read, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
+    process::ExitCode,
 };
 use svgtypes::LengthListParser;
 
 use svg2gcode::{
-    svg2program, ConversionOptions, Machine, Settings, SupportedFunctionality, Version,
+    bounding_box, lint_sequences, place, program2svg, svg2cut_order, svg2toolpaths, tile,
+    toolpaths2perimeter_check, toolpaths2program, write_gcode, Alignment, Barcode, BarcodeFill,
+    Calibration, CalibrationPattern, CalibrationTest, ConversionConfig, ConversionOptions,
+    CutDirection, Dialect, Dithering, FillPattern, FocusTest, FontFace, FontLibrary, GrayImage,
+    Halftone, HalftonePattern, Hooks, JobEstimate, KerfSide, LayerOverrides, LeadShape, Machine,
+    MachineConfig, Manifest, ManifestEntry, ManifestSource, MarkPosition, MarkShape, MaterialTest,
+    MergeData, OriginMode, PaintFilter, PassRegistry, PathOrdering, Plunge, PowerSource, Preset,
+    QrErrorCorrection, Script, Settings, ShortNames, StrokeFont, StrokeOutline,
+    SupportedFunctionality, Symbology, TestShape, Toolpaths, Version,
 };
 
+mod cache;
+mod diff;
+mod exit;
+mod set;
+mod watch;
+
+use cache::{CacheKey, ConversionCache};
+use diff::{Diff, ProfileOutput};
+use exit::{Failure, Warnings};
+use set::set_fields;
+
+/// How far apart in millimeters the measured reference points can be from the design before warning
+const ALIGNMENT_TOLERANCE: f64 = 0.5;
+
 #[derive(Debug, Parser)]
 #[command(name = "svg2gcode", version, author, about)]
 struct Opt {
     /// Curve interpolation tolerance (mm)
+    ///
+    /// The greatest distance the lines a curve is cut as may stray from it. Curves are split into as few lines as stay within it, so large ones get more lines and small ones fewer.
     #[arg(long)]
     tolerance: Option<f64>,
     /// Machine feed rate (mm/min)
@@ -30,6 +57,21 @@ struct Opt {
     /// Used for scaling visual units (pixels, points, picas, etc.)
     #[arg(long)]
     dpi: Option<f64>,
+    /// Set up the tool on, tool off, and end sequences and arcs for the firmware of the machine: grbl (in laser mode, $32=1), marlin, linuxcnc, or klipper
+    ///
+    /// Marlin and Klipper drive the laser from the fan output with M106/M107 and read dwells (G4 P) in milliseconds. On Grbl and LinuxCNC, moves leave out G0/G1/G2/G3 when it is the same as the move before. Sequences using commands the firmware doesn't support are warned about. Sequences and arc options given along with this replace what it sets up.
+    #[arg(long)]
+    dialect: Option<String>,
+    /// Start from the settings of a machine: shapeoko, x-carve, ortur-laser, axidraw, grbl-plotter, or the name of a preset saved with --save-preset
+    ///
+    /// Sets the bed size, tool and program sequences, dialect, and feedrates, keeping the rest of --settings. Saved presets replace all settings. Other options given along with this replace what it sets.
+    #[arg(long)]
+    preset: Option<String>,
+    /// Save the settings as a preset with this name for --preset, instead of converting
+    ///
+    /// Presets are saved as JSON in svg2gcode/presets in the config directory (i.e. ~/.config/svg2gcode/presets).
+    #[arg(long)]
+    save_preset: Option<String>,
     #[arg(alias = "tool_on_sequence", long = "on")]
     /// G-Code for turning on the tool
     tool_on_sequence: Option<String>,
@@ -42,6 +84,167 @@ struct Opt {
     /// G-Code for stopping/idling the machine at the end of the program
     #[arg(alias = "end_sequence", long = "end")]
     end_sequence: Option<String>,
+    /// G-Code run right before homing
+    ///
+    /// This and the other sequences below may use the placeholders {pass}, {passes}, {path}, and {paths}
+    #[arg(alias = "pre_home_sequence", long = "pre-home")]
+    pre_home_sequence: Option<String>,
+    /// G-Code for homing the machine, after units are set and before the begin sequence
+    #[arg(alias = "home_sequence", long = "home")]
+    home_sequence: Option<String>,
+    /// G-Code run right after homing
+    #[arg(alias = "post_home_sequence", long = "post-home")]
+    post_home_sequence: Option<String>,
+    /// G-Code run before each pass over all paths
+    #[arg(alias = "pass_begin_sequence", long = "pass-begin")]
+    pass_begin_sequence: Option<String>,
+    /// G-Code run after each pass over all paths
+    #[arg(alias = "pass_end_sequence", long = "pass-end")]
+    pass_end_sequence: Option<String>,
+    /// G-Code run before moving to the start of each path
+    #[arg(alias = "path_begin_sequence", long = "path-begin")]
+    path_begin_sequence: Option<String>,
+    /// G-Code run after the last cut of each path, before the tool is turned off
+    #[arg(alias = "path_end_sequence", long = "path-end")]
+    path_end_sequence: Option<String>,
+    /// Number of times to draw all paths (i.e. to cut through thicker material)
+    #[arg(long)]
+    passes: Option<usize>,
+    /// Comma-separated tool power of each pass, available in sequences as {power} (i.e. 600,1000)
+    ///
+    /// The last value is used for any remaining passes.
+    #[arg(long, value_delimiter = ',')]
+    pass_power: Option<Vec<f64>>,
+    /// Comma-separated feedrate of each pass (mm/min), instead of --feedrate (i.e. 1000,800,600)
+    ///
+    /// The last value is used for any remaining passes.
+    #[arg(long, value_delimiter = ',')]
+    pass_feedrate: Option<Vec<f64>>,
+    /// Tool library from a JSON file, to pick from with --tool or for a layer with --layers
+    ///
+    /// Each tool has a name, kind (EndMill, VBit, or Laser), diameter (mm), angle of a V-bit (degrees), max_depth_of_cut (mm), and max_feedrate (mm/min)
+    /// (i.e. [{"name": "1/8 end mill", "diameter": 3.175, "max_depth_of_cut": 1.5, "max_feedrate": 1000}]).
+    #[arg(long)]
+    tools: Option<PathBuf>,
+    /// Name of the tool in --tools to cut with, which limits the feedrate to its max_feedrate
+    #[arg(long)]
+    tool: Option<String>,
+    /// Thickness of the material (mm) for --tool to cut through, in as many passes as its max_depth_of_cut takes instead of --passes
+    ///
+    /// Each pass goes deeper by the same amount, available in sequences as {depth} below the surface and as {z} (i.e. G1 Z{z}).
+    /// Without --kerf-width, the width of the tool's cut is made up for.
+    #[arg(long)]
+    material_thickness: Option<f64>,
+    /// Z coordinate (mm) of the top of the material, which {z} is measured down from
+    #[arg(long)]
+    surface_z: Option<f64>,
+    /// How far (mm) to cut past the bottom of the material, so parts come out cleanly
+    #[arg(long)]
+    overcut: Option<f64>,
+    /// Depth (mm) to engrave into the material instead of cutting through it, 0 to cut through
+    #[arg(long)]
+    engrave_depth: Option<f64>,
+    /// Number of uncut gaps left along each closed path to keep parts attached
+    #[arg(long)]
+    tab_count: Option<usize>,
+    /// Length of each tab (mm)
+    #[arg(long)]
+    tab_width: Option<f64>,
+    /// Only leave tabs on this many of the final passes, earlier passes cut the full path
+    #[arg(long)]
+    tab_passes: Option<usize>,
+    /// Perforate closed paths with cuts of this length (mm), so parts stay attached until they are popped out
+    #[arg(long)]
+    perforation_cut: Option<f64>,
+    /// Length of the uncut gap after each perforation cut (mm)
+    #[arg(long)]
+    perforation_gap: Option<f64>,
+    /// Comma-separated layer names or stroke colors of paths to always cut after all others (i.e. Outline,#ff0000), so the outline that frees a part is never cut early
+    ///
+    /// Layers are Inkscape layers by their label, or else the outermost group by its id.
+    #[arg(long, value_delimiter = ',')]
+    cut_last: Option<Vec<String>>,
+    /// Order to draw paths in: document (default) as they appear in the SVG, nearest-neighbor to draw each after the one that ends closest to it, or optimized to improve on that further
+    #[arg(long)]
+    path_ordering: Option<String>,
+    /// Most seconds to spend improving the order for --path-ordering optimized (default 2, so stipple art with many paths still converts quickly), or 0 for no limit
+    #[arg(long)]
+    order_time_budget: Option<f64>,
+    /// Longest run of paths moved at once to between two others while improving the order, or 0 to not move any
+    #[arg(long)]
+    order_max_run: Option<usize>,
+    /// Whether runs of paths may be drawn in reverse while improving the order
+    #[arg(long)]
+    order_reverse_runs: Option<bool>,
+    /// Number of nearby paths considered for each move while improving the order
+    #[arg(long)]
+    order_neighbors: Option<usize>,
+    /// Merge points within this distance (mm) of each other, drop segments left going nowhere, and join open paths that then touch end to end
+    ///
+    /// Cleans up artwork where paths that should meet miss each other by a little, like CAD exports and traced images.
+    #[arg(long)]
+    heal_tolerance: Option<f64>,
+    /// Close paths that end within this distance (mm) of where they started, as CAD exports often leave them
+    ///
+    /// Closed paths get tabs and perforations and are never reversed when ordering. The number of paths closed is logged.
+    #[arg(long)]
+    close_tolerance: Option<f64>,
+    /// Remove the parts of lines that an earlier line already covers, to within this distance (mm), so edges that shapes share are only cut once
+    ///
+    /// Closed paths that lose an edge are opened up there, so leave this off to make up for the kerf of shapes that share edges. The length removed is logged.
+    #[arg(long)]
+    overlap_tolerance: Option<f64>,
+    /// Width of the cut (mm), like the kerf of a laser or the diameter of a router bit, to make up for by moving closed paths over by half of it
+    ///
+    /// Holes, which are paths inside an odd number of others, are moved the other way. Open paths are left as they are.
+    #[arg(long)]
+    kerf_width: Option<f64>,
+    /// Which side of closed paths to cut on for --kerf-width: outside (default) so parts come out the size they are drawn, or inside so the openings are
+    #[arg(long)]
+    kerf_side: Option<String>,
+    /// Which way to go around closed paths when milling with a spindle turning clockwise: as-drawn (default), climb to keep the part on the right of the tool, or conventional to keep it on the left
+    ///
+    /// Parts and holes are told apart like for --kerf-side, and paths that go around the other way are reversed.
+    #[arg(long)]
+    cut_direction: Option<String>,
+    /// Material (mm) to leave on the side of closed paths that --kerf-side cuts on in every pass, for a finishing pass after them to take off at full depth along the exact contour
+    ///
+    /// Edges of milled parts come out cleaner when the last cut only takes off a little.
+    #[arg(long)]
+    finish_allowance: Option<f64>,
+    /// Feedrate (mm/min) of the finishing pass of --finish-allowance, 0 for that of the last pass
+    #[arg(long)]
+    finish_feedrate: Option<f64>,
+    /// Tool power (i.e. spindle speed) of the finishing pass of --finish-allowance, 0 for that of the last pass
+    #[arg(long)]
+    finish_power: Option<f64>,
+    /// Length (mm) of a move onto the start of each closed path, tangent to it, so the tool is turned on beside the part instead of leaving a mark on it
+    ///
+    /// Leads are made on the side that --kerf-side cuts on. Paths left open by tabs or perforations get none.
+    #[arg(long)]
+    lead_in: Option<f64>,
+    /// Length (mm) of a move off of the end of each closed path, like --lead-in
+    #[arg(long)]
+    lead_out: Option<f64>,
+    /// Shape of leads: line (default) for one coming in at 45° from beside the path, or arc for a quarter circle with the lead length as its radius
+    #[arg(long)]
+    lead_shape: Option<String>,
+    /// Seconds to wait with the tool on before the first cutting move of each path, so it pierces the material before moving
+    #[arg(long)]
+    pierce_dwell: Option<f64>,
+    /// Keep the tool on and draw through to the next path if it starts within this distance (mm) of the previous one
+    ///
+    /// Saves pen lifts on dense artwork like hatching. Paths are joined before tabs and perforations are added.
+    #[arg(long)]
+    join_gap: Option<f64>,
+    /// Keep the tool down (i.e. don't retract Z or turn it off) when moving to a path that starts within this distance (mm) of the previous one
+    ///
+    /// Saves time on engraving with many tiny gaps. The tool cuts across the gap at the feedrate of the cut.
+    #[arg(long)]
+    stay_down_gap: Option<f64>,
+    /// Lift the tool anyway once it has stayed down over this many millimeters of gaps in a row, 0 for no limit
+    #[arg(long)]
+    stay_down_travel: Option<f64>,
     /// A file path to an SVG, else reads from stdin
     file: Option<PathBuf>,
     /// Output file path (overwrites old files), else writes to stdout
@@ -50,14 +253,33 @@ struct Opt {
     /// Provide settings from a JSON file. Overrides command-line arguments.
     #[arg(long)]
     settings: Option<PathBuf>,
+    /// Set any setting by its path in a settings file (i.e. conversion.tolerance=0.05 or machine.dialect=grbl), may be repeated
+    ///
+    /// Values are read as JSON, or else as text, and are set after all other options.
+    /// Unlike --dialect, setting machine.dialect does not change the tool sequences.
+    #[arg(long = "set", value_name = "KEY=VALUE")]
+    set: Vec<String>,
     /// Export current settings to a JSON file instead of converting.
     ///
     /// Use `-` to export to standard out.
     #[arg(long)]
     export: Option<PathBuf>,
-    /// Coordinates for the bottom left corner of the machine
+    /// Coordinates to place the artwork at, by the point picked with --origin-mode
     #[arg(long, allow_hyphen_values = true)]
     origin: Option<String>,
+    /// Which point of the artwork to place at --origin: bottom-left (default), top-left, center, or current-position
+    ///
+    /// current-position places the bottom left corner like bottom-left, and starts the program with G92 X0 Y0 so it runs from wherever the machine is instead of its origin, clearing it with G92.1 at the end.
+    #[arg(long)]
+    origin_mode: Option<String>,
+    /// Mirror the y-axis, for machines where it points toward the front of the bed (i.e. with the origin at the back left)
+    ///
+    /// The top of the artwork is then at the lowest y, so use --origin-mode top-left to keep it on the bed.
+    #[arg(long)]
+    invert_y: Option<bool>,
+    /// Degrees to turn the artwork counterclockwise before placing it at the origin
+    #[arg(long, allow_hyphen_values = true)]
+    rotation: Option<f64>,
     /// Override the width and height of the SVG (i.e. 210mm,297mm)
     ///
     /// Useful when the SVG does not specify these (see https://github.com/sameer/svg2gcode/pull/16)
@@ -65,11 +287,72 @@ struct Opt {
     /// Passing "210mm," or ",297mm" calculates the missing dimension to conform to the viewBox aspect ratio.
     #[arg(long)]
     dimensions: Option<String>,
+    /// Only convert the elements with these ids, along with everything inside them (i.e. part-3,part-7)
+    ///
+    /// Everything is still placed where it would be when converting the whole SVG, so a single failed part can be re-cut.
+    #[arg(long, value_delimiter = ',')]
+    only_ids: Vec<String>,
+    /// Leave out the elements with these ids, along with everything inside them
+    #[arg(long, value_delimiter = ',')]
+    skip_ids: Vec<String>,
+    /// Draw layers with their own settings from a JSON file, by the label of the Inkscape layer or the id of the outermost group
+    ///
+    /// Each layer can set its own feedrate, power, passes, tool (from --tools), engrave_depth, cut_direction (AsDrawn, Climb, or Conventional), tool_on_sequence, and tool_off_sequence (i.e. {"cut": {"passes": 3, "feedrate": 200}}).
+    /// Each of these layers is cut in all of its passes before moving on to the next.
+    #[arg(long)]
+    layers: Option<PathBuf>,
+    /// Affine correction applied to all output coordinates, as the rows of a 2x3 matrix a,b,c,d,e,f
+    ///
+    /// Moves each point (x, y) to (a x + b y + c, d x + e y + f), i.e. to correct for non-square axes or stretched belts.
+    #[arg(long, allow_hyphen_values = true, value_delimiter = ',')]
+    calibration: Option<Vec<f64>>,
+    /// Play in the x and y axes to take up whenever an axis reverses, as x,y in millimeters
+    ///
+    /// Only needed if the firmware does not compensate for backlash itself. Curves are drawn as lines when this is set.
+    #[arg(long, value_delimiter = ',')]
+    backlash: Option<Vec<f64>>,
+    /// Distance each axis moves per motor step, as x,y in millimeters
+    ///
+    /// Output coordinates are rounded onto this grid so the machine doesn't accumulate rounding error, 0 leaves an axis as is.
+    #[arg(long, value_delimiter = ',')]
+    step_size: Option<Vec<f64>>,
+    /// Longest line the controller accepts, 0 for no limit
+    ///
+    /// Comments are wrapped, and inline comments, feedrates and spindle speeds are moved onto lines of their own to fit.
+    #[arg(long)]
+    max_line_length: Option<usize>,
+    /// Height (mm) to lift the Z axis to before every rapid move, like the safe height of a router or pen up for a plotter
+    ///
+    /// The tool is plunged to --cut-z after it is turned on, or to the depth of each pass with --material-thickness.
+    #[arg(long)]
+    travel_z: Option<f64>,
+    /// Height (mm) to plunge the Z axis to for cutting with --travel-z, like the depth of a drag knife or pen down for a plotter
+    #[arg(long, allow_hyphen_values = true)]
+    cut_z: Option<f64>,
+    /// Feedrate (mm/min) to plunge at with --travel-z, 0 for the feedrate of the cut
+    #[arg(long)]
+    z_feedrate: Option<f64>,
+    /// How to go down into the material with --travel-z: straight (default), ramp back and forth along the path, or helix to spiral down
+    ///
+    /// Ramps and helixes start at the surface of the material, or the bottom of the pass before, going no steeper than --ramp-angle. Helixes go inside closed paths, and other paths are ramped into instead.
+    #[arg(long)]
+    plunge: Option<String>,
+    /// Steepest angle (degrees) from the surface to ramp down at with --plunge, 0 for 3 degrees
+    #[arg(long)]
+    ramp_angle: Option<f64>,
+    /// Radius (mm) of the circles to spiral down in with --plunge helix, a little under that of the bit, 0 to ramp instead
+    #[arg(long)]
+    helix_radius: Option<f64>,
     /// Whether to use circular arcs when generating g-code
     ///
     /// Please check if your machine supports G2/G3 commands before enabling this.
     #[arg(long)]
     circular_interpolation: Option<bool>,
+    /// Whether to write the center of arcs as an offset from their start (I/J) instead of their radius (R)
+    ///
+    /// Grbl recommends this, since the center of an arc close to a half circle is hard to tell from its radius.
+    #[arg(long)]
+    arc_center_offsets: Option<bool>,
 
     #[arg(long)]
     /// Include line numbers at the beginning of each line
@@ -87,62 +370,612 @@ struct Opt {
     /// Workaround for parsers that don't accept comments on the same line
     newline_before_comment: Option<bool>,
     #[arg(long)]
+    /// Name output files in the 8.3 form that SD cards in controllers can read (i.e. DRAWING.GCO)
+    ///
+    /// Names are upper case with only letters, digits, _, and -, ending in a number if they are too long or would be written over.
+    short_names: Option<bool>,
+    #[arg(long)]
+    /// Round numbers to this many digits after the decimal point (i.e. 3 for micrometers)
+    ///
+    /// By default, numbers are written with as many digits as it takes to read them back exactly.
+    precision: Option<usize>,
+    #[arg(long)]
     /// When printing a node name , print a extra attribute 
     ///
     /// Useful to print the label of layer on SVG generated by Inkscape
     extra_attribute_name: Option<String>,
+    #[arg(long)]
+    /// Add a comment before each path with its position in the cut order
+    number_paths: Option<bool>,
+    #[arg(long)]
+    /// Add comments that senders (i.e. LaserGRBL or OctoPrint) read to show progress and resume from: ;LAYER:n before each pass and ;PROGRESS:n for each percent done
+    sender_markers: Option<bool>,
+    #[arg(long)]
+    /// Also write an SVG of the paths numbered in the order they will be cut
+    ///
+    /// Useful for auditing the cut order before running the program
+    cut_order: Option<PathBuf>,
+    #[arg(long)]
+    /// Also write an SVG of the program as a machine would run it, with cuts in black and rapid moves dashed in gray
+    ///
+    /// Unlike --cut-order, this is drawn from the g-code that was written, sequences and all, for checking it without a machine
+    render_preview: Option<PathBuf>,
+    #[arg(long)]
+    /// Also write a program that jogs around the bounding box of the artwork with the tool off, stopping at each corner
+    ///
+    /// Useful for checking that the material covers the job before cutting it. Resume the machine to move on to the next corner.
+    perimeter_check: Option<PathBuf>,
+    #[arg(long)]
+    /// Directory of TrueType/OpenType fonts for drawing text, may be repeated
+    ///
+    /// Fonts embedded in the SVG with @font-face are preferred, followed by these directories in order, then system fonts.
+    font_dir: Vec<PathBuf>,
+    #[arg(long)]
+    /// Whether to look for fonts in the usual system font directories (default: true)
+    system_fonts: Option<bool>,
+    #[arg(long)]
+    /// Single-stroke font in the Hershey .jhf format, named by its file name, may be repeated
+    ///
+    /// Single-stroke fonts are preferred over outline fonts, since they draw each glyph only once.
+    stroke_font: Vec<PathBuf>,
+    #[arg(long)]
+    /// Use a single-stroke font in place of a font family (i.e. Arial=rowmans), may be repeated
+    ///
+    /// The bundled single-stroke fonts, sans (Hershey Roman Simplex) and serif, already stand in for sans-serif, serif,
+    /// and common families like Arial and Times New Roman when there is no other font for them.
+    font_substitution: Vec<String>,
+    #[arg(long)]
+    /// Language tag of the reader (i.e. en or fr-CA), which picks the translation to draw from a <switch> (default: en)
+    language: Option<String>,
+    #[arg(long)]
+    /// Substitute each row of a CSV file into the {{column}} placeholders of the SVG, where the first row names the columns
+    ///
+    /// {{row}} is replaced by the row number. Rows are drawn one after another in a single program, unless --merge-separate is set.
+    merge: Option<PathBuf>,
+    #[arg(long)]
+    /// Move each merged row this far from the previous one in millimeters (i.e. 0,20)
+    ///
+    /// Rows are placed relative to --origin, or 0,0 if it is not set.
+    merge_offset: Option<String>,
+    #[arg(long)]
+    /// Write one program per merged row, numbered after the output file (i.e. out-1.gcode)
+    merge_separate: Option<bool>,
+    #[arg(long)]
+    /// Draw registration marks around the artwork before the first pass, for lining up the material
+    ///
+    /// Comma-separated positions out of top-left, top, top-right, right, bottom-right, bottom, bottom-left, and left, or corners for all four corners.
+    registration_marks: Option<String>,
+    #[arg(long)]
+    /// Shape of the registration marks: cross, or corner for L-shaped crop marks (default: cross)
+    mark_shape: Option<String>,
+    #[arg(long)]
+    /// Length of each line of a registration mark in millimeters (default: 10)
+    mark_size: Option<f64>,
+    #[arg(long)]
+    /// Distance between the artwork and the registration marks in millimeters (default: 5)
+    mark_margin: Option<f64>,
+    #[arg(long)]
+    /// Tool power for drawing the registration marks, available in sequences as {power} (default: power of the first pass)
+    mark_power: Option<f64>,
+    #[arg(long)]
+    /// Write the registration marks as a program of their own, named after the output file (i.e. out-marks.gcode)
+    marks_separate: Option<bool>,
+    #[arg(long)]
+    /// Set the power of each path from its stroke with an S word: luminance so darker strokes get more power, or opacity so more opaque ones do
+    stroke_power: Option<String>,
+    #[arg(long)]
+    /// Power for a white or transparent stroke, for --stroke-power (default: 0)
+    stroke_power_min: Option<f64>,
+    #[arg(long)]
+    /// Power for a black or opaque stroke, for --stroke-power (default: 1000)
+    stroke_power_max: Option<f64>,
+    #[arg(long)]
+    /// Which shapes to draw by how they are painted: all (default), stroked, filled, or stroked-or-filled to leave out invisible ones
+    paint: Option<String>,
+    /// How to cut strokes: centerline (default) along their middle, outline around both sides at their stroke-width, or filled to also fill between the sides with --fill
+    ///
+    /// Strokes no wider than the cut are still cut along their middle.
+    #[arg(long)]
+    stroke_outline: Option<String>,
+    #[arg(long)]
+    /// Draw the inside of filled shapes: lines, crosshatch, concentric, spiral, stipple for dots, or halftone or halftone-lines to reproduce the shade of the fill color
+    fill: Option<String>,
+    #[arg(long)]
+    /// Distance between fill lines, rings, spiral turns, or dots in millimeters (default: 1)
+    fill_spacing: Option<f64>,
+    #[arg(long, allow_hyphen_values = true)]
+    /// Angle of fill lines in degrees counterclockwise from the x-axis (default: 45)
+    fill_angle: Option<f64>,
+    #[arg(long)]
+    /// Time to wait at each stipple dot in seconds, i.e. for burning dots with a laser (default: 0)
+    fill_dwell: Option<f64>,
+    #[arg(long)]
+    /// Width of the line the tool draws in millimeters, for halftone fills (default: 0.2)
+    fill_tool_width: Option<f64>,
+    #[arg(long)]
+    /// Engrave PNG and Netpbm images of <image> elements line by line, before the paths: grayscale to set the power by how dark each pixel is, or threshold, floyd-steinberg, or ordered to dither them into full power or none
+    ///
+    /// Images are read from data: URLs or from files relative to the SVG.
+    raster: Option<String>,
+    #[arg(long)]
+    /// Distance between scanlines of images in millimeters, for --raster (default: 0.1)
+    raster_line_interval: Option<f64>,
+    #[arg(long)]
+    /// Power for the lightest pixels that are engraved, for --raster (default: 0)
+    raster_min_power: Option<f64>,
+    #[arg(long)]
+    /// Power for black pixels, for --raster (default: 1000)
+    raster_max_power: Option<f64>,
+    #[arg(long)]
+    /// Feedrate along scanlines in millimeters / minute, for --raster (default: 3000)
+    raster_feedrate: Option<f64>,
+    #[arg(long)]
+    /// Engrave every other scanline backwards, for --raster (default: true)
+    raster_bidirectional: Option<bool>,
+    #[arg(long, allow_hyphen_values = true, value_parser = parse_point_pair)]
+    /// Two reference points in the design for print-and-cut, as x1,y1,x2,y2 in millimeters (i.e. the centers of two registration marks)
+    ///
+    /// The toolpaths are rotated and moved so these land on the points given by --align-measured.
+    align_design: Option<[[f64; 2]; 2]>,
+    #[arg(long, allow_hyphen_values = true, value_parser = parse_point_pair)]
+    /// Where the reference points of --align-design were found on the machine, as x1,y1,x2,y2 in millimeters
+    align_measured: Option<[[f64; 2]; 2]>,
+    #[arg(long)]
+    /// Split artwork larger than the bed into tiles, writing one program per tile numbered after the output file (i.e. out-1.gcode)
+    ///
+    /// Tiles overlap by --tile-overlap, with registration marks in the overlaps for lining up the material between tiles.
+    tile: Option<bool>,
+    #[arg(long, default_value = "10")]
+    /// Overlap between neighboring tiles in millimeters
+    tile_overlap: f64,
+    #[arg(long)]
+    /// Log an estimate of how long the program takes to run, the electricity it uses, and what it costs
+    stats: Option<bool>,
+    #[arg(long)]
+    /// Feedrate of moves between paths for estimates (mm/min)
+    rapid_feedrate: Option<f64>,
+    #[arg(long)]
+    /// Cost of running the machine for an hour, for estimates
+    hourly_rate: Option<f64>,
+    #[arg(long)]
+    /// Cost of a square meter of material, for estimates
+    ///
+    /// Charged for the bounding box of the job.
+    material_cost: Option<f64>,
+    #[arg(long)]
+    /// Power drawn by the machine while running in watts, for estimating electricity use
+    power: Option<f64>,
+    #[arg(long)]
+    /// How quickly the machine speeds up and slows down in mm/s², for estimates (default: ignored)
+    acceleration: Option<f64>,
+    #[arg(long)]
+    /// Start the program with comments stating how long it takes and how far it cuts and travels
+    estimate_header: Option<bool>,
+    #[arg(long)]
+    /// Write a JSON manifest describing each program written, with the SVG it came from and a hash of it, the settings, how long it takes, its bounding box, and any warnings
+    ///
+    /// Job management systems can read this instead of the comments in each program.
+    manifest: Option<PathBuf>,
+    #[arg(long)]
+    /// Directory for caching converted programs
+    ///
+    /// Inputs whose content and settings haven't changed since a previous run are not reconverted.
+    cache_dir: Option<PathBuf>,
+    /// Keep running and convert the SVG again whenever it is saved, i.e. from Inkscape
+    ///
+    /// When the SVG is a directory, each SVG in it is converted to a .gcode file of the same name whenever it is saved, next to it or in --out if it is a directory.
+    #[arg(long)]
+    watch: Option<bool>,
+    /// Fail instead of writing the program when anything is warned about, i.e. in build scripts
+    ///
+    /// Exits with 4 when the SVG uses something that can't be converted, 5 when the artwork goes past the edges of the bed, and 7 for any other warning. Otherwise, invalid options exit with 1, input that can't be parsed with 3, and files that can't be read or written with 6.
+    #[arg(long)]
+    strict: Option<bool>,
+    /// Run the fn path() of a Rhai script on each ordered path, to filter them, change how they are cut, comment on them, or reorder them
+    ///
+    /// The path is `this`, with the tag, id, layer, stroke, fill, and attributes of its element, settable feedrate, power, passes, comments, and order, and its length, whether it is closed, and where it starts (x and y). Returning false leaves it out.
+    #[arg(long)]
+    script: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Generate a QR code or Code 128 barcode as an SVG ready for engraving
+    Barcode(BarcodeOpt),
+    /// Convert a grayscale image into an SVG of halftone dots or lines ready for engraving
+    Halftone(HalftoneOpt),
+    /// Generate a program that draws a grid of cells across ranges of power and feedrate, for dialing in the settings for a material
+    ///
+    /// Uses the machine settings and origin like a conversion does. Each cell is drawn as its own pass, so use {power} in the pass begin sequence (i.e. M3 S{power}).
+    MaterialTest(MaterialTestOpt),
+    /// Generate a program that cuts a line while moving the Z axis across a range, for finding the height at which a laser is in focus
+    ///
+    /// Uses the machine settings, origin, and feedrate like a conversion does. The thinnest part of the line is in focus, count the ticks below it to find its height.
+    FocusTest(FocusTestOpt),
+    /// Generate a program that draws a pattern for calibrating the machine, i.e. its steps per millimeter, backlash, or kerf
+    ///
+    /// Uses the machine settings, origin, and feedrate like a conversion does, so the pattern is drawn the same way as real jobs.
+    Calibrate(CalibrateOpt),
+    /// Convert an SVG with two settings profiles and compare how long each program takes, how far it cuts and travels, its size, and how far apart their paths are
+    ///
+    /// Useful for seeing what a change to the settings does, i.e. when tuning path ordering or healing.
+    Diff(DiffOpt),
+}
+
+#[derive(Debug, Args)]
+struct BarcodeOpt {
+    /// Text to encode
+    text: String,
+    /// Generate a Code 128 barcode instead of a QR code
+    #[arg(long)]
+    code128: bool,
+    /// Width of the symbol in millimeters, excluding the quiet zone
+    #[arg(long)]
+    width: f64,
+    /// Height of the Code 128 bars in millimeters (default: a quarter of the width)
+    #[arg(long)]
+    height: Option<f64>,
+    /// QR code error correction level: L, M, Q or H (default: M)
+    #[arg(long)]
+    error_correction: Option<char>,
+    /// Fill dark modules with lines this far apart in millimeters (i.e. the tool width), else only outline them
+    #[arg(long)]
+    hatch: Option<f64>,
+    /// Output file path (overwrites old files), else writes to stdout
+    #[arg(short, long)]
+    out: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+struct MaterialTestOpt {
+    /// Tool power of the first and last column, as start,end
+    #[arg(long, value_delimiter = ',')]
+    power: Option<Vec<f64>>,
+    /// Feedrate in mm/min of the first and last row, as start,end
+    #[arg(long, value_delimiter = ',')]
+    feedrate: Option<Vec<f64>>,
+    /// Number of power steps (default: 5)
+    #[arg(long)]
+    columns: Option<usize>,
+    /// Number of feedrate steps (default: 5)
+    #[arg(long)]
+    rows: Option<usize>,
+    /// Width and height of each cell in millimeters (default: 5)
+    #[arg(long)]
+    cell_size: Option<f64>,
+    /// Space between cells in millimeters (default: 2)
+    #[arg(long)]
+    gap: Option<f64>,
+    /// What to draw in each cell: square, hatch, or line (default: hatch)
+    #[arg(long)]
+    shape: Option<String>,
+    /// Distance between the lines of a hatched cell in millimeters, i.e. the tool width (default: 0.2)
+    #[arg(long)]
+    hatch: Option<f64>,
+    /// Height of the labels in millimeters, 0 to leave them out (default: 2.5)
+    #[arg(long)]
+    label_height: Option<f64>,
+    /// Tool power for the labels (default: the power of the first column)
+    #[arg(long)]
+    label_power: Option<f64>,
+    /// Output file path (overwrites old files), else writes to stdout
+    #[arg(short, long)]
+    out: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+struct FocusTestOpt {
+    /// Height of the Z axis in millimeters at the start and end of the line, as start,end (default: -5,5)
+    ///
+    /// The Z axis is moved to the start with a rapid move, so the whole range must be clear of the material.
+    #[arg(long, allow_hyphen_values = true, value_delimiter = ',')]
+    z: Option<Vec<f64>>,
+    /// Length of the line in millimeters (default: 100)
+    #[arg(long)]
+    length: Option<f64>,
+    /// Change in height between ticks in millimeters, 0 to leave them out (default: 1)
+    #[arg(long)]
+    tick_spacing: Option<f64>,
+    /// Tool power, available in sequences as {power}
+    #[arg(long)]
+    power: Option<f64>,
+    /// Output file path (overwrites old files), else writes to stdout
+    #[arg(short, long)]
+    out: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+struct CalibrateOpt {
+    /// Pattern to draw: axis-scale, circle-diamond-square, backlash, or kerf-comb
+    ///
+    /// axis-scale draws squares a quarter, half, and all of the size wide to measure, circle-diamond-square shows whether the axes are square and move together smoothly, backlash draws rows of ticks from either side that are offset by the backlash, and kerf-comb cuts strips to push together and measure.
+    pattern: String,
+    /// Width and height of the pattern in millimeters (default: 50)
+    #[arg(long)]
+    size: Option<f64>,
+    /// Number of ticks in each backlash row, or strips of the kerf comb (default: 10)
+    #[arg(long)]
+    count: Option<usize>,
+    /// Tool power, available in sequences as {power}
+    #[arg(long)]
+    power: Option<f64>,
+    /// Output file path (overwrites old files), else writes to stdout
+    #[arg(short, long)]
+    out: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+struct DiffOpt {
+    /// A file path to an SVG
+    file: PathBuf,
+    /// Settings file of a profile to compare (i.e. from --export), given twice, the second is compared to the first
+    #[arg(long, required = true)]
+    profile: Vec<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+struct HalftoneOpt {
+    /// A PNG, PGM, or PPM (Netpbm) image
+    image: PathBuf,
+    /// Width of the image in millimeters, the height follows from its aspect ratio
+    #[arg(long)]
+    width: f64,
+    /// Draw lines that widen with the darkness instead of dots that grow with it
+    #[arg(long)]
+    lines: bool,
+    /// Distance between dots or lines in millimeters (default: 1)
+    #[arg(long)]
+    spacing: Option<f64>,
+    /// Angle of the grid of dots or lines in degrees counterclockwise from the x-axis (default: 45)
+    #[arg(long, allow_hyphen_values = true)]
+    angle: Option<f64>,
+    /// Width of the line the tool draws in millimeters, i.e. pen tip or laser spot (default: 0.2)
+    #[arg(long)]
+    tool_width: Option<f64>,
+    /// Output file path (overwrites old files), else writes to stdout
+    #[arg(short, long)]
+    out: Option<PathBuf>,
 }
 
-fn main() -> io::Result<()> {
+fn main() -> ExitCode {
     if env::var("RUST_LOG").is_err() {
         env::set_var("RUST_LOG", "svg2gcode=info")
     }
-    env_logger::init();
+    exit::init_logger();
+
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            error!("{err}");
+            ExitCode::from(Failure::of(&err).code())
+        }
+    }
+}
 
-    let opt = Opt::parse();
+fn run() -> io::Result<()> {
+    let mut opt = Opt::parse();
+
+    // Tests are drawn with the machine settings, so they are generated once those are read
+    let test = match opt.command.take() {
+        Some(Command::Barcode(barcode)) => return generate_barcode(barcode),
+        Some(Command::Halftone(halftone)) => return generate_halftone(halftone),
+        Some(Command::Diff(diff)) => return diff_profiles(diff),
+        test @ Some(Command::MaterialTest(_) | Command::FocusTest(_) | Command::Calibrate(_)) => {
+            test
+        }
+        None => None,
+    };
 
     let settings = {
-        let mut settings = if let Some(path) = opt.settings {
+        let mut settings = if let Some(path) = &opt.settings {
             serde_json::from_reader(File::open(path)?)?
         } else {
             Settings::default()
         };
+        if let Some(name) = &opt.preset {
+            match (builtin_preset(name), preset_path(name)) {
+                (Some(preset), _) => preset.apply(&mut settings),
+                (None, Some(path)) if path.is_file() => {
+                    if opt.settings.is_some() {
+                        warn!("--settings is ignored, since the saved preset {name} replaces all settings");
+                    }
+                    settings = serde_json::from_reader(File::open(path)?)?;
+                }
+                _ => {
+                    error!(
+                        "Unknown preset {name}, expected shapeoko, x-carve, ortur-laser, axidraw, grbl-plotter, or one saved with --save-preset"
+                    );
+                    Failure::InvalidOptions.exit();
+                }
+            }
+        }
 
         {
             let conversion = &mut settings.conversion;
             conversion.dpi = opt.dpi.unwrap_or(conversion.dpi);
             conversion.feedrate = opt.feedrate.unwrap_or(conversion.feedrate);
             conversion.tolerance = opt.tolerance.unwrap_or(conversion.tolerance);
+            conversion.passes = opt.passes.unwrap_or(conversion.passes);
+            if let Some(pass_power) = opt.pass_power.clone() {
+                conversion.pass_power = pass_power;
+            }
+            if let Some(pass_feedrate) = opt.pass_feedrate.clone() {
+                conversion.pass_feedrate = pass_feedrate;
+            }
+            if let Some(path) = &opt.tools {
+                conversion.tools = serde_json::from_reader(File::open(path)?)?;
+            }
+            if let Some(tool) = &opt.tool {
+                conversion.tool = Some(tool.clone());
+            }
+            let material = &mut conversion.material;
+            material.thickness = opt.material_thickness.unwrap_or(material.thickness);
+            material.surface_z = opt.surface_z.unwrap_or(material.surface_z);
+            material.overcut = opt.overcut.unwrap_or(material.overcut);
+            conversion.engrave_depth = opt.engrave_depth.unwrap_or(conversion.engrave_depth);
+            conversion.tab_count = opt.tab_count.unwrap_or(conversion.tab_count);
+            conversion.tab_width = opt.tab_width.unwrap_or(conversion.tab_width);
+            conversion.tab_passes = opt.tab_passes.unwrap_or(conversion.tab_passes);
+            conversion.perforation_cut = opt.perforation_cut.unwrap_or(conversion.perforation_cut);
+            conversion.perforation_gap = opt.perforation_gap.unwrap_or(conversion.perforation_gap);
+            conversion.heal_tolerance = opt.heal_tolerance.unwrap_or(conversion.heal_tolerance);
+            conversion.close_tolerance = opt.close_tolerance.unwrap_or(conversion.close_tolerance);
+            conversion.overlap_tolerance = opt
+                .overlap_tolerance
+                .unwrap_or(conversion.overlap_tolerance);
+            conversion.kerf_width = opt.kerf_width.unwrap_or(conversion.kerf_width);
+            conversion.finish_allowance =
+                opt.finish_allowance.unwrap_or(conversion.finish_allowance);
+            conversion.finish_feedrate = opt.finish_feedrate.unwrap_or(conversion.finish_feedrate);
+            conversion.finish_power = opt.finish_power.unwrap_or(conversion.finish_power);
+            conversion.lead_in = opt.lead_in.unwrap_or(conversion.lead_in);
+            conversion.lead_out = opt.lead_out.unwrap_or(conversion.lead_out);
+            conversion.pierce_dwell = opt.pierce_dwell.unwrap_or(conversion.pierce_dwell);
+            conversion.join_gap = opt.join_gap.unwrap_or(conversion.join_gap);
+            conversion.stay_down_gap = opt.stay_down_gap.unwrap_or(conversion.stay_down_gap);
+            conversion.stay_down_travel =
+                opt.stay_down_travel.unwrap_or(conversion.stay_down_travel);
+            if let Some(cut_last) = opt.cut_last.clone() {
+                conversion.cut_last = cut_last;
+            }
+            conversion.order_time_budget = opt
+                .order_time_budget
+                .unwrap_or(conversion.order_time_budget);
+            let improvements = &mut conversion.order_improvements;
+            improvements.max_run = opt.order_max_run.unwrap_or(improvements.max_run);
+            improvements.reverse_runs = opt.order_reverse_runs.unwrap_or(improvements.reverse_runs);
+            improvements.neighbors = opt.order_neighbors.unwrap_or(improvements.neighbors);
+            conversion.number_paths = opt.number_paths.unwrap_or(conversion.number_paths);
+            conversion.sender_markers = opt.sender_markers.unwrap_or(conversion.sender_markers);
+            if let Some(language) = opt.language.clone() {
+                conversion.language = language;
+            }
+            for substitution in &opt.font_substitution {
+                let Some((family, font)) = substitution.split_once('=') else {
+                    error!("Expected a font substitution as FAMILY=FONT but got {substitution}");
+                    Failure::InvalidOptions.exit();
+                };
+                conversion
+                    .font_substitutions
+                    .insert(family.trim().to_string(), font.trim().to_string());
+            }
+            if let Some(path) = &opt.script {
+                let source = fs::read_to_string(path)?;
+                if let Err(err) = Script::new(&source) {
+                    error!("Could not parse the script {}: {err}", path.display());
+                    Failure::Parse.exit();
+                }
+                conversion.script = Some(source);
+            }
         }
         {
             let machine = &mut settings.machine;
+            if let Some(dialect) = &opt.dialect {
+                match dialect.trim() {
+                    "grbl" => Dialect::Grbl,
+                    "marlin" => Dialect::Marlin,
+                    "linuxcnc" => Dialect::LinuxCnc,
+                    "klipper" => Dialect::Klipper,
+                    other => {
+                        error!(
+                            "Unknown dialect {other}, expected grbl, marlin, linuxcnc, or klipper"
+                        );
+                        Failure::InvalidOptions.exit();
+                    }
+                }
+                .apply(machine);
+            }
             machine.supported_functionality = SupportedFunctionality {
                 circular_interpolation: opt
                     .circular_interpolation
                     .unwrap_or(machine.supported_functionality.circular_interpolation),
+                arc_center_offsets: opt
+                    .arc_center_offsets
+                    .unwrap_or(machine.supported_functionality.arc_center_offsets),
             };
-            if let seq @ Some(_) = opt.tool_on_sequence {
+            if let seq @ Some(_) = opt.tool_on_sequence.clone() {
                 machine.tool_on_sequence = seq;
             }
-            if let seq @ Some(_) = opt.tool_off_sequence {
+            if let seq @ Some(_) = opt.tool_off_sequence.clone() {
                 machine.tool_off_sequence = seq;
             }
-            if let seq @ Some(_) = opt.begin_sequence {
+            if let seq @ Some(_) = opt.begin_sequence.clone() {
                 machine.begin_sequence = seq;
             }
-            if let seq @ Some(_) = opt.end_sequence {
+            if let seq @ Some(_) = opt.end_sequence.clone() {
                 machine.end_sequence = seq;
             }
+            if let Some(backlash) = opt.backlash.clone() {
+                for (axis, backlash) in machine.backlash.iter_mut().zip(backlash) {
+                    *axis = backlash;
+                }
+            }
+            if let Some(step_size) = opt.step_size.clone() {
+                for (axis, step_size) in machine.step_size.iter_mut().zip(step_size) {
+                    *axis = step_size;
+                }
+            }
+            if let Some(max_line_length) = opt.max_line_length {
+                machine.max_line_length = max_line_length;
+            }
+            if let Some(travel_z) = opt.travel_z {
+                machine.travel_z = Some(travel_z);
+            }
+            machine.cut_z = opt.cut_z.unwrap_or(machine.cut_z);
+            machine.z_feedrate = opt.z_feedrate.unwrap_or(machine.z_feedrate);
+            if let Some(plunge) = opt.plunge.clone() {
+                machine.plunge = match plunge.trim() {
+                    "straight" => Plunge::Straight,
+                    "ramp" => Plunge::Ramp,
+                    "helix" => Plunge::Helix,
+                    other => {
+                        error!("Unknown plunge {other}, expected straight, ramp, or helix");
+                        Failure::InvalidOptions.exit();
+                    }
+                };
+            }
+            machine.ramp_angle = opt.ramp_angle.unwrap_or(machine.ramp_angle);
+            machine.helix_radius = opt.helix_radius.unwrap_or(machine.helix_radius);
+            if let Some(calibration) = opt.calibration.clone() {
+                let [a, b, c, d, e, f] = calibration[..] else {
+                    error!(
+                        "--calibration needs the 6 values of a 2x3 matrix, got {}",
+                        calibration.len()
+                    );
+                    Failure::InvalidOptions.exit();
+                };
+                machine.calibration = Some(Calibration {
+                    matrix: [[a, b, c], [d, e, f]],
+                });
+            }
+            for (seq, setting) in [
+                (&opt.pre_home_sequence, &mut machine.pre_home_sequence),
+                (&opt.home_sequence, &mut machine.home_sequence),
+                (&opt.post_home_sequence, &mut machine.post_home_sequence),
+                (&opt.pass_begin_sequence, &mut machine.pass_begin_sequence),
+                (&opt.pass_end_sequence, &mut machine.pass_end_sequence),
+                (&opt.path_begin_sequence, &mut machine.path_begin_sequence),
+                (&opt.path_end_sequence, &mut machine.path_end_sequence),
+            ] {
+                if let seq @ Some(_) = seq {
+                    *setting = seq.clone();
+                }
+            }
         }
         {
-            if let Some(origin) = opt.origin {
+            if let Some(origin) = opt.origin.clone() {
                 for (i, dimension_origin) in origin
                     .split(',')
                     .map(|point| {
                         if point.is_empty() {
                             Default::default()
                         } else {
-                            point.parse::<f64>().expect("could not parse coordinate")
+                            point.parse::<f64>().unwrap_or_else(|err| {
+                                error!("Could not parse the origin coordinate {point}: {err}");
+                                Failure::InvalidOptions.exit();
+                            })
                         }
                     })
                     .take(2)
@@ -151,6 +984,24 @@ fn main() -> io::Result<()> {
                     settings.conversion.origin[i] = Some(dimension_origin);
                 }
             }
+            if let Some(mode) = opt.origin_mode.clone() {
+                settings.conversion.origin_mode = match mode.trim() {
+                    "bottom-left" => OriginMode::BottomLeft,
+                    "top-left" => OriginMode::TopLeft,
+                    "center" => OriginMode::Center,
+                    "current-position" => OriginMode::CurrentPosition,
+                    other => {
+                        error!("Unknown origin mode {other}, expected bottom-left, top-left, center, or current-position");
+                        Failure::InvalidOptions.exit();
+                    }
+                };
+            }
+            if let Some(invert_y) = opt.invert_y {
+                settings.conversion.invert_y = invert_y;
+            }
+            if let Some(rotation) = opt.rotation {
+                settings.conversion.rotation = rotation;
+            }
         }
 
         if let Some(line_numbers) = opt.line_numbers {
@@ -165,15 +1016,216 @@ fn main() -> io::Result<()> {
             settings.postprocess.newline_before_comment = newline_before_comment;
         }
 
-	settings.conversion.extra_attribute_name = opt.extra_attribute_name ;
+        if let Some(precision) = opt.precision {
+            settings.postprocess.precision = Some(precision);
+        }
 
-        if let Version::Unknown(ref unknown) = settings.version {
-            error!(
-                "Your settings use an unknown version. Your version: {unknown}, latest: {}. See {} to download the latest CLI version.",
+        if let Some(short_names) = opt.short_names {
+            settings.postprocess.short_file_names = short_names;
+        }
+
+        {
+            let cost = &mut settings.cost;
+            cost.rapid_feedrate = opt.rapid_feedrate.unwrap_or(cost.rapid_feedrate);
+            cost.hourly_rate = opt.hourly_rate.unwrap_or(cost.hourly_rate);
+            cost.material_cost = opt.material_cost.unwrap_or(cost.material_cost);
+            cost.power = opt.power.unwrap_or(cost.power);
+            cost.acceleration = opt.acceleration.unwrap_or(cost.acceleration);
+            cost.header = opt.estimate_header.unwrap_or(cost.header);
+        }
+
+        if let Some(positions) = opt.registration_marks.clone() {
+            let marks = settings
+                .conversion
+                .registration_marks
+                .get_or_insert_with(Default::default);
+            marks.positions = positions
+                .split(',')
+                .flat_map(parse_mark_positions)
+                .collect();
+        }
+        if let Some(marks) = &mut settings.conversion.registration_marks {
+            if let Some(shape) = opt.mark_shape.clone() {
+                marks.shape = match shape.trim() {
+                    "cross" => MarkShape::Cross,
+                    "corner" => MarkShape::Corner,
+                    other => {
+                        error!("Unknown mark shape {other}, expected cross or corner");
+                        Failure::InvalidOptions.exit();
+                    }
+                };
+            }
+            marks.size = opt.mark_size.unwrap_or(marks.size);
+            marks.margin = opt.mark_margin.unwrap_or(marks.margin);
+            if let power @ Some(_) = opt.mark_power {
+                marks.power = power;
+            }
+        }
+
+        if let Some(source) = opt.stroke_power.clone() {
+            settings
+                .conversion
+                .stroke_power
+                .get_or_insert_with(Default::default)
+                .source = match source.trim() {
+                "luminance" => PowerSource::Luminance,
+                "opacity" => PowerSource::Opacity,
+                other => {
+                    error!("Unknown stroke power {other}, expected luminance or opacity");
+                    Failure::InvalidOptions.exit();
+                }
+            };
+        }
+        if let Some(stroke_power) = &mut settings.conversion.stroke_power {
+            stroke_power.min = opt.stroke_power_min.unwrap_or(stroke_power.min);
+            stroke_power.max = opt.stroke_power_max.unwrap_or(stroke_power.max);
+        }
+
+        if let Some(side) = opt.kerf_side.clone() {
+            settings.conversion.kerf_side = match side.trim() {
+                "outside" => KerfSide::Outside,
+                "inside" => KerfSide::Inside,
+                other => {
+                    error!("Unknown kerf side {other}, expected outside or inside");
+                    Failure::InvalidOptions.exit();
+                }
+            };
+        }
+        if let Some(direction) = opt.cut_direction.clone() {
+            settings.conversion.cut_direction = match direction.trim() {
+                "as-drawn" => CutDirection::AsDrawn,
+                "climb" => CutDirection::Climb,
+                "conventional" => CutDirection::Conventional,
+                other => {
+                    error!(
+                        "Unknown cut direction {other}, expected as-drawn, climb, or conventional"
+                    );
+                    Failure::InvalidOptions.exit();
+                }
+            };
+        }
+        if let Some(shape) = opt.lead_shape.clone() {
+            settings.conversion.lead_shape = match shape.trim() {
+                "line" => LeadShape::Line,
+                "arc" => LeadShape::Arc,
+                other => {
+                    error!("Unknown lead shape {other}, expected line or arc");
+                    Failure::InvalidOptions.exit();
+                }
+            };
+        }
+        if let Some(paint) = opt.paint.clone() {
+            settings.conversion.paint_filter = match paint.trim() {
+                "all" => PaintFilter::All,
+                "stroked" => PaintFilter::Stroked,
+                "filled" => PaintFilter::Filled,
+                "stroked-or-filled" => PaintFilter::StrokedOrFilled,
+                other => {
+                    error!("Unknown paint {other}, expected all, stroked, filled, or stroked-or-filled");
+                    Failure::InvalidOptions.exit();
+                }
+            };
+        }
+        if let Some(outline) = opt.stroke_outline.clone() {
+            settings.conversion.stroke_outline = match outline.trim() {
+                "centerline" => StrokeOutline::Centerline,
+                "outline" => StrokeOutline::Outline,
+                "filled" => StrokeOutline::Filled,
+                other => {
+                    error!(
+                        "Unknown stroke outline {other}, expected centerline, outline, or filled"
+                    );
+                    Failure::InvalidOptions.exit();
+                }
+            };
+        }
+        if let Some(pattern) = opt.fill.clone() {
+            settings
+                .conversion
+                .fill
+                .get_or_insert_with(Default::default)
+                .pattern = match pattern.trim() {
+                "lines" => FillPattern::Lines,
+                "crosshatch" => FillPattern::Crosshatch,
+                "concentric" => FillPattern::Concentric,
+                "spiral" => FillPattern::Spiral,
+                "stipple" => FillPattern::Stipple,
+                "halftone" => FillPattern::Halftone(HalftonePattern::Dots),
+                "halftone-lines" => FillPattern::Halftone(HalftonePattern::Lines),
+                other => {
+                    error!("Unknown fill {other}, expected lines, crosshatch, concentric, spiral, stipple, halftone, or halftone-lines");
+                    Failure::InvalidOptions.exit();
+                }
+            };
+        }
+        if let Some(fill) = &mut settings.conversion.fill {
+            fill.spacing = opt.fill_spacing.unwrap_or(fill.spacing);
+            fill.angle = opt.fill_angle.unwrap_or(fill.angle);
+            fill.dwell = opt.fill_dwell.unwrap_or(fill.dwell);
+            fill.tool_width = opt.fill_tool_width.unwrap_or(fill.tool_width);
+        }
+        if let Some(dithering) = opt.raster.clone() {
+            settings
+                .conversion
+                .raster
+                .get_or_insert_with(Default::default)
+                .dithering = match dithering.trim() {
+                "grayscale" => Dithering::Grayscale,
+                "threshold" => Dithering::Threshold,
+                "floyd-steinberg" => Dithering::FloydSteinberg,
+                "ordered" => Dithering::Ordered,
+                other => {
+                    error!("Unknown raster dithering {other}, expected grayscale, threshold, floyd-steinberg, or ordered");
+                    Failure::InvalidOptions.exit();
+                }
+            };
+        }
+        if let Some(raster) = &mut settings.conversion.raster {
+            raster.line_interval = opt.raster_line_interval.unwrap_or(raster.line_interval);
+            raster.min_power = opt.raster_min_power.unwrap_or(raster.min_power);
+            raster.max_power = opt.raster_max_power.unwrap_or(raster.max_power);
+            raster.feedrate = opt.raster_feedrate.unwrap_or(raster.feedrate);
+            raster.bidirectional = opt.raster_bidirectional.unwrap_or(raster.bidirectional);
+        }
+
+        match (opt.align_design, opt.align_measured) {
+            (Some(design), Some(measured)) => {
+                settings.conversion.alignment = Some(Alignment { design, measured });
+            }
+            (None, None) => {}
+            _ => {
+                error!("--align-design and --align-measured must be used together");
+                Failure::InvalidOptions.exit();
+            }
+        }
+        if let Some(alignment) = &settings.conversion.alignment {
+            let distance_error = alignment.distance_error();
+            if distance_error.abs() > ALIGNMENT_TOLERANCE {
+                warn!(
+                    "The measured reference points are {distance_error:+.2} mm further apart than in the design, check that they were measured accurately"
+                );
+            }
+        }
+
+	settings.conversion.extra_attribute_name = opt.extra_attribute_name.clone() ;
+
+        if !opt.set.is_empty() {
+            settings = match set_fields(&settings, &opt.set) {
+                Ok(settings) => settings,
+                Err(err) => {
+                    error!("{err}");
+                    Failure::InvalidOptions.exit();
+                }
+            };
+        }
+
+        if let Version::Unknown(ref unknown) = settings.version {
+            error!(
+                "Your settings use an unknown version. Your version: {unknown}, latest: {}. See {} to download the latest CLI version.",
                 Version::latest(),
                 env!("CARGO_PKG_REPOSITORY"),
             );
-            std::process::exit(1);
+            Failure::InvalidOptions.exit();
         }
 
         let old_version = settings.version.clone();
@@ -183,12 +1235,85 @@ fn main() -> io::Result<()> {
                 Version::latest(),
                 env!("CARGO_PKG_REPOSITORY"),
             );
-            std::process::exit(1);
+            Failure::InvalidOptions.exit();
+        }
+
+        // A move at a feedrate of 0 never finishes, so the program couldn't be run or estimated
+        let feedrates = [
+            ("feedrate", settings.conversion.feedrate),
+            ("rapid feedrate", settings.cost.rapid_feedrate),
+        ]
+        .into_iter()
+        .chain(
+            settings
+                .conversion
+                .pass_feedrate
+                .iter()
+                .map(|feedrate| ("pass feedrate", *feedrate)),
+        )
+        .chain(
+            settings
+                .conversion
+                .raster
+                .as_ref()
+                .map(|raster| ("raster feedrate", raster.feedrate)),
+        );
+        for (name, feedrate) in feedrates {
+            if feedrate <= 0. || feedrate.is_nan() {
+                error!("The {name} must be above 0 mm/min, not {feedrate}");
+                Failure::InvalidOptions.exit();
+            }
+        }
+        if let Some(fill) = &settings.conversion.fill {
+            if fill.spacing <= 0. || fill.spacing.is_nan() {
+                error!("The fill spacing must be above 0 mm, not {}", fill.spacing);
+                Failure::InvalidOptions.exit();
+            }
+        }
+        if let Some(raster) = &settings.conversion.raster {
+            if raster.line_interval <= 0. || raster.line_interval.is_nan() {
+                error!(
+                    "The raster line interval must be above 0 mm, not {}",
+                    raster.line_interval
+                );
+                Failure::InvalidOptions.exit();
+            }
+        }
+        if let Some(calibration) = &settings.machine.calibration {
+            // A matrix without an inverse would move every point onto a line or a single spot
+            let [[a, b, _], [d, e, _]] = calibration.matrix;
+            let determinant = a * e - b * d;
+            if determinant.abs() < 1e-9 || determinant.is_nan() {
+                error!(
+                    "The calibration matrix must not flatten the drawing, its determinant is {determinant}"
+                );
+                Failure::InvalidOptions.exit();
+            }
         }
 
         settings
     };
 
+    if let Some(name) = &opt.save_preset {
+        if builtin_preset(name).is_some() {
+            error!("{name} is a built-in preset, save this one under another name");
+            Failure::InvalidOptions.exit();
+        }
+        if !is_preset_name(name) {
+            error!("Preset names can't be empty or have /, \\, or .. in them: {name}");
+            Failure::InvalidOptions.exit();
+        }
+        let Some(path) = preset_path(name) else {
+            error!("Could not find a config directory to save presets in");
+            Failure::InvalidOptions.exit();
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        info!("Saving preset {name} to {}", path.display());
+        return File::create(path)?.write_all(&serde_json::to_vec_pretty(&settings)?);
+    }
+
     if let Some(export_path) = opt.export {
         let config_json_bytes = serde_json::to_vec_pretty(&settings)?;
         if export_path.to_string_lossy() == "-" {
@@ -198,32 +1323,143 @@ fn main() -> io::Result<()> {
         }
     }
 
-    let options = {
+    match test {
+        Some(Command::MaterialTest(material_test)) => {
+            return generate_material_test(material_test, &settings)
+        }
+        Some(Command::FocusTest(focus_test)) => return generate_focus_test(focus_test, &settings),
+        Some(Command::Calibrate(calibrate)) => {
+            return generate_calibration_test(calibrate, &settings)
+        }
+        _ => {}
+    }
+
+    // Shared by every conversion, so SVGs converted while watching a directory don't get the same name
+    let mut short_paths = settings
+        .postprocess
+        .short_file_names
+        .then(ShortPaths::default);
+    if !opt.watch.unwrap_or(false) {
+        let manifest = convert(
+            &opt,
+            settings,
+            opt.file.as_deref(),
+            opt.out.clone(),
+            &mut short_paths,
+        )?;
+        return write_manifest(&opt, &manifest);
+    }
+    let Some(path) = &opt.file else {
+        error!("--watch needs an SVG file or directory to watch");
+        Failure::InvalidOptions.exit();
+    };
+    if path.is_dir() {
+        let out_dir = match &opt.out {
+            Some(out) if !out.is_dir() => {
+                error!("--out must be a directory when watching a directory");
+                Failure::InvalidOptions.exit();
+            }
+            out => out.clone(),
+        };
+        // Programs written for every SVG so far, with those converted again replaced
+        let mut manifest: Vec<ManifestEntry> = vec![];
+        watch::watch(path, |svg| {
+            let out = out_dir
+                .as_deref()
+                .unwrap_or(svg.parent().unwrap_or(Path::new("")))
+                .join(svg.with_extension("gcode").file_name().unwrap_or_default());
+            let out = ShortPaths::shorten(&mut short_paths, out);
+            let entries = convert(
+                &opt,
+                settings.clone(),
+                Some(svg),
+                Some(out),
+                &mut short_paths,
+            )?;
+            manifest.retain(|entry| entries.iter().all(|new| new.file != entry.file));
+            manifest.extend(entries);
+            write_manifest(&opt, &manifest)
+        })
+    } else {
+        watch::watch(path, |svg| {
+            let manifest = convert(
+                &opt,
+                settings.clone(),
+                Some(svg),
+                opt.out.clone(),
+                &mut short_paths,
+            )?;
+            write_manifest(&opt, &manifest)
+        })
+    }
+}
+
+/// Convert an SVG `file`, or standard input, into a program written to `out`, or standard output,
+/// along with anything else the options ask for (i.e. the cut order or a perimeter check)
+///
+/// Returns the programs written for the --manifest, which is left to the caller so a directory of
+/// SVGs goes in a single one.
+///
+/// `out` is written as given, only the files named after it (i.e. rows, tiles, and marks) get
+/// short names.
+fn convert(
+    opt: &Opt,
+    mut settings: Settings,
+    file: Option<&Path>,
+    out: Option<PathBuf>,
+    short_paths: &mut Option<ShortPaths>,
+) -> io::Result<Vec<ManifestEntry>> {
+    let warned = Warnings::now();
+    let strict = |off_bed| match opt.strict {
+        Some(true) => warned.strict(off_bed),
+        _ => Ok(()),
+    };
+    let mut options = {
         let mut dimensions = [None, None];
 
-        if let Some(dimensions_str) = opt.dimensions {
-            dimensions_str
-                .split(',')
-                .map(|dimension_str| {
-                    if dimension_str.is_empty() {
-                        None
-                    } else {
-                        LengthListParser::from(dimension_str)
-                            .next()
-                            .transpose()
-                            .expect("could not parse dimension")
+        if let Some(dimensions_str) = &opt.dimensions {
+            for (i, dimension_str) in dimensions_str.split(',').take(2).enumerate() {
+                if dimension_str.is_empty() {
+                    continue;
+                }
+                match LengthListParser::from(dimension_str).next().transpose() {
+                    Ok(dimension) => dimensions[i] = dimension,
+                    Err(err) => {
+                        error!("Could not parse the dimension {dimension_str}: {err}");
+                        return Err(Failure::InvalidOptions.into());
                     }
-                })
-                .take(2)
-                .enumerate()
-                .for_each(|(i, dimension_origin)| {
-                    dimensions[i] = dimension_origin;
-                });
+                }
+            }
+        }
+        let layer_overrides = match &opt.layers {
+            Some(path) => serde_json::from_reader(File::open(path)?)?,
+            None => Default::default(),
+        };
+        let path_ordering = match opt.path_ordering.as_deref().map(str::trim) {
+            None | Some("document") => PathOrdering::Document,
+            Some("nearest-neighbor") => PathOrdering::NearestNeighbor,
+            Some("optimized") => PathOrdering::Optimized,
+            Some(other) => {
+                error!("Unknown path ordering {other}, expected document, nearest-neighbor, or optimized");
+                return Err(Failure::InvalidOptions.into());
+            }
+        };
+        ConversionOptions {
+            dimensions,
+            only_ids: opt.only_ids.clone(),
+            skip_ids: opt.skip_ids.clone(),
+            layer_overrides,
+            path_ordering,
+            // Piped SVGs link to images relative to where they are converted from
+            image_dir: Some(
+                file.and_then(Path::parent)
+                    .map_or_else(|| PathBuf::from("."), Path::to_path_buf),
+            ),
+            ..Default::default()
         }
-        ConversionOptions { dimensions }
     };
 
-    let input = match opt.file {
+    let input = match file {
         Some(filename) => {
             let mut f = File::open(filename)?;
             let len = f.metadata()?.len();
@@ -239,73 +1475,898 @@ fn main() -> io::Result<()> {
         }
     };
 
+    let format_options = if out.is_some() {
+        FormatOptions {
+            line_numbers: settings.postprocess.line_numbers,
+            checksums: settings.postprocess.checksums,
+            ..Default::default()
+        }
+    } else {
+        FormatOptions {
+            line_numbers: settings.postprocess.line_numbers,
+            checksums: settings.postprocess.checksums,
+            newline_before_comment: settings.postprocess.newline_before_comment,
+            ..Default::default()
+        }
+    };
+
+    let machine = machine_from_config(&settings.machine)?;
+
+    // Programs written, for --manifest
+    let mut manifest = vec![];
+    let manifest_dir = opt.manifest.as_deref().and_then(Path::parent);
+    let describe = |out: Option<&Path>, program: &[Token<'_>], settings: &Settings| {
+        let name = out.map_or_else(
+            || "-".to_string(),
+            |out| {
+                manifest_dir
+                    .and_then(|dir| out.strip_prefix(dir).ok())
+                    .unwrap_or(out)
+                    .display()
+                    .to_string()
+            },
+        );
+        ManifestEntry {
+            source: file
+                .and_then(Path::file_name)
+                .map(|name| ManifestSource::new(name.to_string_lossy(), input.as_bytes())),
+            warnings: warned.messages(),
+            ..ManifestEntry::new(name, program, settings.clone())
+        }
+    };
+
+    let document = roxmltree::Document::parse_with_options(
+        &input,
+        ParsingOptions {
+            allow_dtd: true,
+            ..Default::default()
+        },
+    )
+    .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+    // Reading fonts is slow, so only do it when they will be used
+    if document.descendants().any(|node| node.has_tag_name("text")) {
+        let system_font_dirs = if opt.system_fonts.unwrap_or(true) {
+            system_font_dirs()
+        } else {
+            vec![]
+        };
+        let dirs = opt
+            .font_dir
+            .iter()
+            .chain(&system_font_dirs)
+            .cloned()
+            .collect::<Vec<_>>();
+        load_fonts(
+            &mut options.fonts,
+            &dirs,
+            &FontLibrary::requested_families(&document),
+        );
+        for path in &opt.stroke_font {
+            let name = path.file_stem().unwrap_or_default().to_string_lossy();
+            match StrokeFont::from_jhf(name, &fs::read_to_string(path)?, ' ') {
+                Some(font) => options.fonts.insert_stroke_font(font),
+                None => warn!("Could not read single-stroke font {}", path.display()),
+            }
+        }
+    }
+
+    let precision = settings.postprocess.precision;
+    let max_line_length = settings.machine.max_line_length;
+    let modal_motion = settings.machine.dialect.is_some_and(Dialect::modal_motion);
+    let mut short = |path: PathBuf| ShortPaths::shorten(short_paths, path);
+    let stats = opt.stats.unwrap_or(false);
+    let tiled = opt.tile.unwrap_or(false);
+    let marks_separate = opt.marks_separate.unwrap_or(false);
+    if marks_separate && (tiled || opt.merge.is_some()) {
+        warn!("--marks-separate is ignored with --merge and --tile, marks are drawn in each program instead");
+    }
+    // The fonts loaded for the text are part of the key, so the cache is only checked once they are.
+    // The cut order, preview, perimeter check, stats, tiles, and separate marks are only produced on conversion, so skip the cache when they are requested.
+    // Warnings aren't cached either, so --strict has to convert to find out whether there are any.
+    let cache = if let Some(cache_dir) = opt.cache_dir.clone().filter(|_| {
+        opt.strict != Some(true)
+            && opt.cut_order.is_none()
+            && opt.render_preview.is_none()
+            && opt.perimeter_check.is_none()
+            && opt.merge.is_none()
+            && !stats
+            && opt.manifest.is_none()
+            && !tiled
+            && !marks_separate
+    }) {
+        let cache = ConversionCache::new(cache_dir)?;
+        let key = CacheKey::new(&input, &settings, &options, &format_options)?;
+        if let Some(gcode) = cache.get(&key) {
+            info!("Using cached conversion");
+            return write_output(out, &gcode).map(|()| vec![]);
+        }
+        Some((cache, key))
+    } else {
+        None
+    };
+
+    if let Some(cut_order_path) = &opt.cut_order {
+        let svg = svg2cut_order(
+            &document,
+            &settings.conversion,
+            options.clone(),
+            &PassRegistry::default(),
+        );
+        File::create(cut_order_path)?.write_all(svg.as_bytes())?;
+    }
+
+    if let Some(merge_path) = &opt.merge {
+        let data = MergeData::from_csv(&fs::read_to_string(merge_path)?)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        info!("Merging {} rows of {}", data.len(), merge_path.display());
+        let merged = (0..data.len()).map(|row| data.merge(&input, row));
+
+        if opt.merge_separate.unwrap_or(false) {
+            let Some(out) = out else {
+                error!("--merge-separate needs an output file to number");
+                return Err(Failure::InvalidOptions.into());
+            };
+            for (row, svg) in merged.enumerate() {
+                let toolpaths = merged_toolpaths(&svg, &settings.conversion, &options)?;
+                let row_out = short(suffixed_path(&out, row + 1));
+                let program = program_with_header(
+                    toolpaths,
+                    &settings,
+                    &options.layer_overrides,
+                    machine.clone(),
+                );
+                strict(false)?;
+                manifest.push(describe(Some(&row_out), &program, &settings));
+                let mut gcode = vec![];
+                write_gcode(
+                    &program,
+                    &format_options,
+                    precision,
+                    max_line_length,
+                    modal_motion,
+                    &mut gcode,
+                )?;
+                write_output(Some(row_out), &gcode)?;
+            }
+            return Ok(manifest);
+        }
+
+        let offset = opt
+            .merge_offset
+            .as_ref()
+            .map(|offset| {
+                let parts = offset
+                    .split(',')
+                    .take(2)
+                    .map(|d| d.trim().parse::<f64>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|err| {
+                        error!("Could not parse --merge-offset {offset}: {err}");
+                        Failure::InvalidOptions
+                    })?;
+                Ok::<_, Failure>([
+                    parts.first().copied().unwrap_or_default(),
+                    parts.get(1).copied().unwrap_or_default(),
+                ])
+            })
+            .transpose()?;
+        let mut toolpaths = Toolpaths::default();
+        for (row, svg) in merged.enumerate() {
+            let mut config = settings.conversion.clone();
+            if let Some(offset) = offset {
+                for (origin, offset) in config.origin.iter_mut().zip(offset) {
+                    *origin = Some(origin.unwrap_or_default() + offset * row as f64);
+                }
+            }
+            let mut row_toolpaths = merged_toolpaths(&svg, &config, &options)?;
+            if let Some(first) = row_toolpaths.toolpaths.first_mut() {
+                first
+                    .comments
+                    .insert(0, format!("row {} of {}", row + 1, data.len()));
+            }
+            toolpaths.toolpaths.append(&mut row_toolpaths.toolpaths);
+            toolpaths.comments.append(&mut row_toolpaths.comments);
+            toolpaths.rasters.append(&mut row_toolpaths.rasters);
+        }
+        let program = program_with_header(toolpaths, &settings, &options.layer_overrides, machine);
+        strict(false)?;
+        if stats {
+            log_estimate(&program, &settings);
+        }
+        manifest.push(describe(out.as_deref(), &program, &settings));
+        let mut gcode = vec![];
+        write_gcode(
+            &program,
+            &format_options,
+            precision,
+            max_line_length,
+            modal_motion,
+            &mut gcode,
+        )?;
+        write_output(out, &gcode)?;
+        return Ok(manifest);
+    }
+
+    let placement = place(&document, &settings.conversion, &options);
+    let layer_overrides = options.layer_overrides.clone();
+    let mut toolpaths = svg2toolpaths(&document, &settings.conversion, options, placement);
+    toolpaths.toolpaths = PassRegistry::default().apply(toolpaths.toolpaths, &settings.conversion);
+    if let Some(perimeter_check_path) = &opt.perimeter_check {
+        let program = toolpaths2perimeter_check(&toolpaths, &settings.conversion, machine.clone());
+        let mut gcode = vec![];
+        write_gcode(
+            &program,
+            &format_options,
+            precision,
+            max_line_length,
+            modal_motion,
+            &mut gcode,
+        )?;
+        write_output(Some(short(perimeter_check_path.clone())), &gcode)?;
+    }
+
+    let bed_size = settings.conversion.bed_size;
+    if tiled {
+        let Some(out) = out else {
+            error!("--tile needs an output file to number");
+            return Err(Failure::InvalidOptions.into());
+        };
+        let tiles = tile(
+            &toolpaths.toolpaths,
+            &toolpaths.rasters,
+            bed_size,
+            opt.tile_overlap,
+            settings.conversion.tolerance,
+        );
+        info!("Splitting into {} tiles", tiles.len());
+        for (i, tile) in tiles.into_iter().enumerate() {
+            let instructions = tile.instructions();
+            let mut tile_toolpaths = Toolpaths {
+                toolpaths: tile.toolpaths,
+                comments: toolpaths.comments.clone(),
+                rasters: tile.rasters,
+            };
+            // A tile of only images has nothing to put them before
+            match tile_toolpaths.toolpaths.first_mut() {
+                Some(first) => first.comments.splice(0..0, instructions),
+                None => tile_toolpaths.comments.splice(0..0, instructions),
+            };
+            let tile_out = short(suffixed_path(&out, i + 1));
+            let program =
+                program_with_header(tile_toolpaths, &settings, &layer_overrides, machine.clone());
+            strict(false)?;
+            if stats {
+                log_estimate(&program, &settings);
+            }
+            manifest.push(describe(Some(&tile_out), &program, &settings));
+            let mut gcode = vec![];
+            write_gcode(
+                &program,
+                &format_options,
+                precision,
+                max_line_length,
+                modal_motion,
+                &mut gcode,
+            )?;
+            write_output(Some(tile_out), &gcode)?;
+        }
+        return Ok(manifest);
+    }
+    if marks_separate {
+        let Some(out) = &out else {
+            error!("--marks-separate needs an output file to name the marks after");
+            return Err(Failure::InvalidOptions.into());
+        };
+        if let Some(marks) = settings.conversion.registration_marks.take() {
+            let marks_config = ConversionConfig {
+                passes: 1,
+                pass_power: marks
+                    .power
+                    .or(settings.conversion.pass_power(1))
+                    .into_iter()
+                    .collect(),
+                pass_feedrate: vec![settings.conversion.pass_feedrate(1)],
+                tab_count: 0,
+                ..settings.conversion.clone()
+            };
+            let marks_toolpaths = Toolpaths {
+                toolpaths: marks.toolpaths(&toolpaths.toolpaths, settings.conversion.tolerance),
+                ..Default::default()
+            };
+            let program = toolpaths2program(
+                marks_toolpaths,
+                &marks_config,
+                &LayerOverrides::new(),
+                machine.clone(),
+            );
+            let mut gcode = vec![];
+            write_gcode(
+                &program,
+                &format_options,
+                precision,
+                max_line_length,
+                modal_motion,
+                &mut gcode,
+            )?;
+            write_output(Some(short(suffixed_path(out, "marks"))), &gcode)?;
+        } else {
+            warn!("--marks-separate has no effect without --registration-marks");
+        }
+    }
+
+    let tolerance = settings.conversion.tolerance;
+    let [width, height] = bounding_box(&toolpaths.toolpaths, tolerance)
+        .map_or([0., 0.], |bounds| bounds.size().to_array());
+    // Where the program runs from is only known once it is on the machine
+    let off_bed = settings.conversion.origin_mode != OriginMode::CurrentPosition
+        && !toolpaths
+            .toolpaths
+            .iter()
+            .flat_map(|toolpath| toolpath.flattened(tolerance))
+            .all(|p| {
+                (-tolerance..=bed_size[0] + tolerance).contains(&p.x)
+                    && (-tolerance..=bed_size[1] + tolerance).contains(&p.y)
+            });
+    if width > bed_size[0] || height > bed_size[1] {
+        warn!(
+            "The artwork is {width:.1} x {height:.1} mm, larger than the {} x {} mm bed. Use --tile to split it into tiles",
+            bed_size[0], bed_size[1]
+        );
+    } else if off_bed {
+        warn!(
+            "The artwork goes past the edges of the {} x {} mm bed. Use --origin to move it onto the bed",
+            bed_size[0], bed_size[1]
+        );
+    }
+    let program = program_with_header(toolpaths, &settings, &layer_overrides, machine);
+    strict(off_bed)?;
+    if stats {
+        log_estimate(&program, &settings);
+    }
+    manifest.push(describe(out.as_deref(), &program, &settings));
+    if let Some(render_preview_path) = &opt.render_preview {
+        File::create(render_preview_path)?.write_all(program2svg(&program).as_bytes())?;
+    }
+
+    let mut gcode = vec![];
+    write_gcode(
+        &program,
+        &format_options,
+        precision,
+        max_line_length,
+        modal_motion,
+        &mut gcode,
+    )?;
+
+    if let Some((cache, key)) = cache {
+        cache.insert(&key, &gcode)?;
+    }
+
+    write_output(out, &gcode)?;
+    Ok(manifest)
+}
+
+/// Parse the sequences of a [`MachineConfig`], reporting any errors and exiting if they can't be used
+fn machine_from_config(config: &MachineConfig) -> io::Result<Machine<'_>> {
     let snippets = [
-        settings
-            .machine
+        config
             .tool_on_sequence
             .as_deref()
             .map(snippet_parser)
             .transpose(),
-        settings
-            .machine
+        config
             .tool_off_sequence
             .as_deref()
             .map(snippet_parser)
             .transpose(),
-        settings
-            .machine
+        config
             .begin_sequence
             .as_deref()
             .map(snippet_parser)
             .transpose(),
-        settings
-            .machine
+        config
             .end_sequence
             .as_deref()
             .map(snippet_parser)
             .transpose(),
     ];
 
-    let machine = if let [Ok(tool_on_action), Ok(tool_off_action), Ok(program_begin_sequence), Ok(program_end_sequence)] =
-        snippets
+    let hooks = Hooks::from_config(config);
+
+    let machine = if let (
+        [Ok(tool_on_action), Ok(tool_off_action), Ok(program_begin_sequence), Ok(program_end_sequence)],
+        Ok(hooks),
+    ) = (&snippets, &hooks)
     {
         Machine::new(
-            settings.machine.supported_functionality,
-            tool_on_action,
-            tool_off_action,
-            program_begin_sequence,
-            program_end_sequence,
+            config.supported_functionality.clone(),
+            tool_on_action.clone(),
+            tool_off_action.clone(),
+            program_begin_sequence.clone(),
+            program_end_sequence.clone(),
         )
+        .with_hooks(hooks.clone())
+        .with_calibration(config.calibration.clone())
+        .with_backlash(config.backlash)
+        .with_step_size(config.step_size)
+        .with_z_moves(config.travel_z, config.cut_z, config.z_feedrate)
+        .with_plunge(config.plunge, config.ramp_angle, config.helix_radius)
+        .with_dialect(config.dialect)
     } else {
         use codespan_reporting::term::{
             emit,
             termcolor::{ColorChoice, StandardStream},
         };
         let mut writer = StandardStream::stderr(ColorChoice::Auto);
-        let config = codespan_reporting::term::Config::default();
+        let report_config = codespan_reporting::term::Config::default();
 
         for (i, (filename, gcode)) in [
-            ("tool_on_sequence", &settings.machine.tool_on_sequence),
-            ("tool_off_sequence", &settings.machine.tool_off_sequence),
-            ("begin_sequence", &settings.machine.begin_sequence),
-            ("end_sequence", &settings.machine.end_sequence),
+            ("tool_on_sequence", &config.tool_on_sequence),
+            ("tool_off_sequence", &config.tool_off_sequence),
+            ("begin_sequence", &config.begin_sequence),
+            ("end_sequence", &config.end_sequence),
         ]
         .iter()
         .enumerate()
         {
             if let Err(err) = &snippets[i] {
-                emit(
+                if let Err(emit_err) = emit(
                     &mut writer,
-                    &config,
-                    &codespan_reporting::files::SimpleFile::new(filename, gcode.as_ref().unwrap()),
+                    &report_config,
+                    &codespan_reporting::files::SimpleFile::new(
+                        filename,
+                        gcode.as_deref().unwrap_or_default(),
+                    ),
                     &g_code::parse::into_diagnostic(err),
-                )
-                .unwrap();
+                ) {
+                    error!("Could not parse the {filename}: {err}, {emit_err}");
+                }
             }
         }
-        std::process::exit(1)
+        for (filename, gcode, err) in hooks.err().into_iter().flatten() {
+            if let Err(emit_err) = emit(
+                &mut writer,
+                &report_config,
+                &codespan_reporting::files::SimpleFile::new(filename, gcode),
+                &g_code::parse::into_diagnostic(&err),
+            ) {
+                error!("Could not parse the {filename}: {err}, {emit_err}");
+            }
+        }
+        return Err(Failure::InvalidOptions.into());
     };
+    if let Err(errors) = machine.check_modes() {
+        for (name, mode) in errors {
+            error!("{name} {mode}");
+        }
+        return Err(Failure::InvalidOptions.into());
+    }
+    for warning in lint_sequences(config) {
+        warn!("{warning}");
+    }
+    Ok(machine)
+}
 
+/// Convert an SVG with placeholders substituted from a row of [`MergeData`]
+///
+/// Values with markup in them can leave the SVG unparseable, which is an error with
+/// [`io::ErrorKind::InvalidData`].
+fn merged_toolpaths(
+    svg: &str,
+    config: &ConversionConfig,
+    options: &ConversionOptions,
+) -> io::Result<Toolpaths> {
+    let document = roxmltree::Document::parse_with_options(
+        svg,
+        ParsingOptions {
+            allow_dtd: true,
+            ..Default::default()
+        },
+    )
+    .map_err(|err| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("could not parse the SVG with merged values: {err}"),
+        )
+    })?;
+    let placement = place(&document, config, options);
+    let mut toolpaths = svg2toolpaths(&document, config, options.clone(), placement);
+    toolpaths.toolpaths = PassRegistry::default().apply(toolpaths.toolpaths, config);
+    Ok(toolpaths)
+}
+
+/// Same as [`toolpaths2program`], starting with the [`JobEstimate::header`] if it is enabled
+fn program_with_header<'input>(
+    toolpaths: Toolpaths,
+    settings: &Settings,
+    layer_overrides: &LayerOverrides,
+    machine: Machine<'input>,
+) -> Vec<Token<'input>> {
+    let mut program = toolpaths2program(toolpaths, &settings.conversion, layer_overrides, machine);
+    if settings.cost.header {
+        match JobEstimate::from_program(&program, settings.machine.dialect, &settings.cost) {
+            Ok(estimate) => {
+                program.splice(0..0, estimate.header());
+            }
+            Err(err) => warn!("Could not estimate the job for the header: {err}"),
+        }
+    }
+    program
+}
+
+/// Log how long the program takes to run and what it costs, for --stats
+fn log_estimate(program: &[Token<'_>], settings: &Settings) {
+    match JobEstimate::from_program(program, settings.machine.dialect, &settings.cost) {
+        Ok(estimate) => info!("Estimate: {estimate}"),
+        Err(err) => warn!("Could not estimate the job: {err}"),
+    }
+}
+
+/// Short names for the programs written, see --short-names
+#[derive(Default)]
+struct ShortPaths {
+    names: ShortNames,
+    /// The short path given for each one asked for, which it gets again when written over
+    given: BTreeMap<PathBuf, PathBuf>,
+}
+
+impl ShortPaths {
+    /// The short path to write `path` to, or `path` itself when names aren't shortened
+    fn shorten(short_paths: &mut Option<Self>, path: PathBuf) -> PathBuf {
+        let Some(short_paths) = short_paths else {
+            return path;
+        };
+        if let Some(short) = short_paths.given.get(&path) {
+            return short.clone();
+        }
+        let name = short_paths
+            .names
+            .name(&path.file_name().unwrap_or_default().to_string_lossy());
+        let short = path.with_file_name(name);
+        short_paths.given.insert(path, short.clone());
+        short
+    }
+}
+
+/// Add a suffix to the end of a file name, before the extension (i.e. out.gcode becomes out-1.gcode)
+fn suffixed_path(path: &Path, suffix: impl Display) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    match path.extension() {
+        Some(extension) => {
+            path.with_file_name(format!("{stem}-{suffix}.{}", extension.to_string_lossy()))
+        }
+        None => path.with_file_name(format!("{stem}-{suffix}")),
+    }
+}
+
+/// Parse two points given as x1,y1,x2,y2
+fn parse_point_pair(points: &str) -> Result<[[f64; 2]; 2], String> {
+    let coordinates = points
+        .split(',')
+        .map(|d| {
+            d.trim()
+                .parse::<f64>()
+                .map_err(|err| format!("could not parse coordinate {d}: {err}"))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let [x1, y1, x2, y2] = coordinates[..] else {
+        return Err(format!(
+            "expected two points as x1,y1,x2,y2 but got {points}"
+        ));
+    };
+    Ok([[x1, y1], [x2, y2]])
+}
+
+/// Parse a registration mark position such as top-left, or corners for all four corners
+fn parse_mark_positions(name: &str) -> Vec<MarkPosition> {
+    match name.trim() {
+        "corners" => MarkPosition::CORNERS.to_vec(),
+        "top-left" => vec![MarkPosition::TopLeft],
+        "top" => vec![MarkPosition::Top],
+        "top-right" => vec![MarkPosition::TopRight],
+        "right" => vec![MarkPosition::Right],
+        "bottom-right" => vec![MarkPosition::BottomRight],
+        "bottom" => vec![MarkPosition::Bottom],
+        "bottom-left" => vec![MarkPosition::BottomLeft],
+        "left" => vec![MarkPosition::Left],
+        other => {
+            error!("Unknown registration mark position {other}");
+            Failure::InvalidOptions.exit();
+        }
+    }
+}
+
+/// Where fonts are usually installed on this platform
+fn system_font_dirs() -> Vec<PathBuf> {
+    let home = env::var_os("HOME").map(PathBuf::from);
+    if cfg!(target_os = "windows") {
+        env::var_os("WINDIR")
+            .map(|windir| PathBuf::from(windir).join("Fonts"))
+            .into_iter()
+            .collect()
+    } else if cfg!(target_os = "macos") {
+        [
+            Some(PathBuf::from("/System/Library/Fonts")),
+            Some(PathBuf::from("/Library/Fonts")),
+            home.map(|home| home.join("Library/Fonts")),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    } else {
+        [
+            Some(PathBuf::from("/usr/share/fonts")),
+            Some(PathBuf::from("/usr/local/share/fonts")),
+            home.as_ref().map(|home| home.join(".local/share/fonts")),
+            home.map(|home| home.join(".fonts")),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+}
+
+/// The built-in preset called `name`, which comes before any saved under the same name
+fn builtin_preset(name: &str) -> Option<Preset> {
+    match name.trim() {
+        "shapeoko" => Some(Preset::Shapeoko),
+        "x-carve" => Some(Preset::XCarve),
+        "ortur-laser" => Some(Preset::OrturLaser),
+        "axidraw" => Some(Preset::AxiDraw),
+        "grbl-plotter" => Some(Preset::GrblPlotter),
+        _ => None,
+    }
+}
+
+/// Whether `name` can be the file name of a saved preset, without reaching out of the presets
+/// directory
+fn is_preset_name(name: &str) -> bool {
+    let name = name.trim();
+    !name.is_empty() && !name.contains(['/', '\\']) && !name.contains("..")
+}
+
+/// Where a preset saved with --save-preset is kept, in the config directory of the platform
+///
+/// Names that aren't [`is_preset_name`] have none.
+fn preset_path(name: &str) -> Option<PathBuf> {
+    if !is_preset_name(name) {
+        return None;
+    }
+    let config_dir = if cfg!(target_os = "windows") {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Application Support"))
+    } else {
+        env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+    };
+    Some(
+        config_dir?
+            .join("svg2gcode/presets")
+            .join(format!("{}.json", name.trim())),
+    )
+}
+
+/// Add the fonts in the directories that are of the families asked for, in the order found
+///
+/// Only the family names of the other fonts are read, so they aren't kept in memory or in the cache
+/// key. When none of the families are found, the first font is added for text to fall back to.
+fn load_fonts(fonts: &mut FontLibrary, dirs: &[PathBuf], families: &[String]) {
+    let mut files = vec![];
+    for dir in dirs {
+        font_files(dir, &mut files);
+    }
+    let mut fallback = None;
+    let mut found = false;
+    for path in files {
+        let data = match fs::read(&path) {
+            Ok(data) => data,
+            Err(err) => {
+                warn!("Could not read font {}: {err}", path.display());
+                continue;
+            }
+        };
+        let Some(family) = FontLibrary::family_name(&data) else {
+            warn!("Could not read font family of {}", path.display());
+            continue;
+        };
+        if families
+            .iter()
+            .any(|wanted| wanted.eq_ignore_ascii_case(&family))
+        {
+            fonts.insert(FontFace { family, data });
+            found = true;
+        } else if fallback.is_none() {
+            fallback = Some(FontFace { family, data });
+        }
+    }
+    if let Some(fallback) = fallback.filter(|_| !found) {
+        fonts.insert(fallback);
+    }
+}
+
+/// Recursively list the TrueType/OpenType fonts in a directory
+fn font_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for path in entries.flatten().map(|entry| entry.path()) {
+        if path.is_dir() {
+            font_files(&path, files);
+        } else if path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| {
+                ["ttf", "otf", "ttc"].contains(&extension.to_ascii_lowercase().as_str())
+            })
+        {
+            files.push(path);
+        }
+    }
+}
+
+fn generate_barcode(opt: BarcodeOpt) -> io::Result<()> {
+    let symbology = if opt.code128 {
+        Symbology::Code128 {
+            height: opt.height.unwrap_or(opt.width / 4.),
+        }
+    } else {
+        Symbology::QrCode(match opt.error_correction.map(|c| c.to_ascii_uppercase()) {
+            None | Some('M') => QrErrorCorrection::Medium,
+            Some('L') => QrErrorCorrection::Low,
+            Some('Q') => QrErrorCorrection::Quartile,
+            Some('H') => QrErrorCorrection::High,
+            Some(other) => {
+                error!("Unknown error correction level {other}, expected one of L, M, Q or H");
+                Failure::InvalidOptions.exit();
+            }
+        })
+    };
+    let barcode = Barcode {
+        text: opt.text,
+        symbology,
+        width: opt.width,
+        fill: opt
+            .hatch
+            .map_or(BarcodeFill::Outline, |spacing| BarcodeFill::Hatch {
+                spacing,
+            }),
+    };
+    match barcode.to_svg() {
+        Ok(svg) => write_output(opt.out, svg.as_bytes()),
+        Err(err) => {
+            error!("Could not generate barcode: {err}");
+            Failure::InvalidOptions.exit();
+        }
+    }
+}
+
+fn generate_material_test(opt: MaterialTestOpt, settings: &Settings) -> io::Result<()> {
+    let default = MaterialTest::default();
+    let range = |values: Option<Vec<f64>>, default: [f64; 2]| match values.as_deref() {
+        None => default,
+        Some(&[start, end]) => [start, end],
+        Some(_) => {
+            error!("Expected a range as start,end");
+            Failure::InvalidOptions.exit();
+        }
+    };
+    let spacing = opt.hatch.unwrap_or(0.2);
+    if spacing <= 0. || spacing.is_nan() {
+        error!("The hatch spacing must be above 0 mm, not {spacing}");
+        Failure::InvalidOptions.exit();
+    }
+    let hatch = TestShape::Hatch { spacing };
+    let material_test = MaterialTest {
+        power: range(opt.power, default.power),
+        feedrate: range(opt.feedrate, default.feedrate),
+        columns: opt.columns.unwrap_or(default.columns),
+        rows: opt.rows.unwrap_or(default.rows),
+        cell_size: opt.cell_size.unwrap_or(default.cell_size),
+        gap: opt.gap.unwrap_or(default.gap),
+        shape: match opt.shape.as_deref().map(str::trim) {
+            None | Some("hatch") => hatch,
+            Some("square") => TestShape::Square,
+            Some("line") => TestShape::Line,
+            Some(other) => {
+                error!("Unknown cell shape {other}, expected square, hatch, or line");
+                Failure::InvalidOptions.exit();
+            }
+        },
+        label_height: opt.label_height.unwrap_or(default.label_height),
+        label_power: opt.label_power,
+    };
+    info!(
+        "Drawing {} x {} cells",
+        material_test.columns, material_test.rows
+    );
+
+    let machine = machine_from_config(&settings.machine)?;
+    let program = material_test.to_program(&settings.conversion, machine);
+    write_test_program(&program, settings, opt.out)
+}
+
+fn generate_focus_test(opt: FocusTestOpt, settings: &Settings) -> io::Result<()> {
+    let default = FocusTest::default();
+    let focus_test = FocusTest {
+        z: match opt.z.as_deref() {
+            None => default.z,
+            Some(&[start, end]) => [start, end],
+            Some(_) => {
+                error!("Expected a range as start,end");
+                Failure::InvalidOptions.exit();
+            }
+        },
+        length: opt.length.unwrap_or(default.length),
+        tick_spacing: opt.tick_spacing.unwrap_or(default.tick_spacing),
+        power: opt.power,
+        ..default
+    };
+    let machine = machine_from_config(&settings.machine)?;
+    let program = focus_test.to_program(&settings.conversion, machine);
+    write_test_program(&program, settings, opt.out)
+}
+
+fn generate_calibration_test(opt: CalibrateOpt, settings: &Settings) -> io::Result<()> {
+    let default = CalibrationTest::default();
+    let calibration_test = CalibrationTest {
+        pattern: match opt.pattern.trim() {
+            "axis-scale" => CalibrationPattern::AxisScale,
+            "circle-diamond-square" => CalibrationPattern::CircleDiamondSquare,
+            "backlash" => CalibrationPattern::Backlash,
+            "kerf-comb" => CalibrationPattern::KerfComb,
+            other => {
+                error!("Unknown calibration pattern {other}, expected axis-scale, circle-diamond-square, backlash, or kerf-comb");
+                Failure::InvalidOptions.exit();
+            }
+        },
+        size: opt.size.unwrap_or(default.size),
+        count: opt.count.unwrap_or(default.count),
+        power: opt.power,
+    };
+    if calibration_test.pattern == CalibrationPattern::KerfComb {
+        info!(
+            "Push the strips together and measure across them, the kerf is how much less than {} mm that is, divided by {}",
+            calibration_test.size, calibration_test.count
+        );
+    }
+    let machine = machine_from_config(&settings.machine)?;
+    let program = calibration_test.to_program(&settings.conversion, machine);
+    write_test_program(&program, settings, opt.out)
+}
+
+/// Write the program of a material, focus, or calibration test with the postprocessing settings
+fn write_test_program(
+    program: &[Token],
+    settings: &Settings,
+    out: Option<PathBuf>,
+) -> io::Result<()> {
+    let format_options = FormatOptions {
+        line_numbers: settings.postprocess.line_numbers,
+        checksums: settings.postprocess.checksums,
+        newline_before_comment: out.is_none() && settings.postprocess.newline_before_comment,
+        ..Default::default()
+    };
+    let mut gcode = vec![];
+    write_gcode(
+        program,
+        &format_options,
+        settings.postprocess.precision,
+        settings.machine.max_line_length,
+        settings.machine.dialect.is_some_and(Dialect::modal_motion),
+        &mut gcode,
+    )?;
+    write_output(out, &gcode)
+}
+
+fn diff_profiles(opt: DiffOpt) -> io::Result<()> {
+    let [a, b] = &opt.profile[..] else {
+        error!("--profile must be given twice, once for each profile to compare");
+        Failure::InvalidOptions.exit();
+    };
+    let input = fs::read_to_string(&opt.file)?;
     let document = roxmltree::Document::parse_with_options(
         &input,
         ParsingOptions {
@@ -313,30 +2374,108 @@ fn main() -> io::Result<()> {
             ..Default::default()
         },
     )
-    .unwrap();
+    .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    let mut options = ConversionOptions::default();
+    if document.descendants().any(|node| node.has_tag_name("text")) {
+        load_fonts(
+            &mut options.fonts,
+            &system_font_dirs(),
+            &FontLibrary::requested_families(&document),
+        );
+    }
+    let a = profile_output(a, &document, &options)?;
+    let b = profile_output(b, &document, &options)?;
+    write_output(None, Diff(&a, &b).to_string().as_bytes())
+}
 
-    let program = svg2program(&document, &settings.conversion, options, machine);
+/// Convert an SVG with the settings of a profile for `svg2gcode diff`
+fn profile_output(
+    path: &Path,
+    document: &roxmltree::Document,
+    options: &ConversionOptions,
+) -> io::Result<ProfileOutput> {
+    let mut settings: Settings = serde_json::from_reader(File::open(path)?)?;
+    let old_version = settings.version.clone();
+    if let Err(msg) = settings.try_upgrade() {
+        error!(
+            "The settings of {} can't be used: {msg}. Their version: {old_version}, latest: {}.",
+            path.display(),
+            Version::latest(),
+        );
+        Failure::InvalidOptions.exit();
+    }
+    let placement = place(document, &settings.conversion, options);
+    let mut toolpaths = svg2toolpaths(document, &settings.conversion, options.clone(), placement);
+    toolpaths.toolpaths = PassRegistry::default().apply(toolpaths.toolpaths, &settings.conversion);
+    let name = path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned();
+    let paths = toolpaths.toolpaths.clone();
+    let program = program_with_header(
+        toolpaths,
+        &settings,
+        &options.layer_overrides,
+        machine_from_config(&settings.machine)?,
+    );
+    let estimate = JobEstimate::from_program(&program, settings.machine.dialect, &settings.cost)
+        .unwrap_or_else(|err| {
+            error!("Could not estimate the job of {name}: {err}");
+            Failure::InvalidOptions.exit();
+        });
+    let mut output = ProfileOutput::new(name, &paths, estimate, settings.conversion.tolerance);
 
-    if let Some(out_path) = opt.out {
-        format_gcode_io(
-            &program,
-            FormatOptions {
-                line_numbers: settings.postprocess.line_numbers,
-                checksums: settings.postprocess.checksums,
-                ..Default::default()
-            },
-            File::create(out_path)?,
-        )
+    write_gcode(
+        &program,
+        &FormatOptions {
+            line_numbers: settings.postprocess.line_numbers,
+            checksums: settings.postprocess.checksums,
+            ..Default::default()
+        },
+        settings.postprocess.precision,
+        settings.machine.max_line_length,
+        settings.machine.dialect.is_some_and(Dialect::modal_motion),
+        &mut output.gcode,
+    )?;
+    Ok(output)
+}
+
+fn generate_halftone(opt: HalftoneOpt) -> io::Result<()> {
+    let image = match GrayImage::decode(&fs::read(&opt.image)?) {
+        Ok(image) => image,
+        Err(err) => {
+            error!("Could not read {}: {err}", opt.image.display());
+            Failure::Parse.exit();
+        }
+    };
+    let default = Halftone::default();
+    let halftone = Halftone {
+        pattern: if opt.lines {
+            HalftonePattern::Lines
+        } else {
+            HalftonePattern::Dots
+        },
+        spacing: opt.spacing.unwrap_or(default.spacing),
+        angle: opt.angle.unwrap_or(default.angle),
+        tool_width: opt.tool_width.unwrap_or(default.tool_width),
+    };
+    write_output(opt.out, halftone.image_to_svg(&image, opt.width).as_bytes())
+}
+
+/// Write the --manifest, if there is one, describing the programs written
+fn write_manifest(opt: &Opt, files: &[ManifestEntry]) -> io::Result<()> {
+    let Some(path) = &opt.manifest else {
+        return Ok(());
+    };
+    let json = serde_json::to_vec_pretty(&Manifest::new(files.to_vec()))?;
+    File::create(path)?.write_all(&json)
+}
+
+fn write_output(out: Option<PathBuf>, gcode: &[u8]) -> io::Result<()> {
+    if let Some(out_path) = out {
+        File::create(out_path)?.write_all(gcode)
     } else {
-        format_gcode_io(
-            &program,
-            FormatOptions {
-                line_numbers: settings.postprocess.line_numbers,
-                checksums: settings.postprocess.checksums,
-                newline_before_comment: settings.postprocess.newline_before_comment,
-                ..Default::default()
-            },
-            std::io::stdout(),
-        )
+        io::stdout().write_all(gcode)
     }
 }
@@ -0,0 +1,209 @@
+use serde_json::{Map, Value};
+use svg2gcode::Settings;
+
+/// Set fields of the settings by their path in a settings file, given as `KEY=VALUE` (i.e.
+/// `conversion.tolerance=0.05` or `machine.backlash.0=0.1`)
+///
+/// Values are read as JSON, or else as a string, and choices can be named in any case (i.e.
+/// `machine.dialect=grbl`). Fields are set after all other options, so they win over them.
+pub fn set_fields(settings: &Settings, assignments: &[String]) -> Result<Settings, String> {
+    let mut json = serde_json::to_value(settings).map_err(|err| err.to_string())?;
+    for assignment in assignments {
+        let Some((path, value)) = assignment.split_once('=') else {
+            return Err(format!("--set {assignment} should be KEY=VALUE"));
+        };
+        let path = path.trim().split('.').collect::<Vec<_>>();
+        let mut value = serde_json::from_str(value.trim())
+            .unwrap_or_else(|_| Value::String(value.trim().to_string()));
+        loop {
+            *field_mut(&mut json, &path).map_err(|err| format!("--set {assignment}: {err}"))? =
+                value.clone();
+            let err = match serde_json::from_value::<Settings>(json.clone()) {
+                Ok(_) => break,
+                Err(err) => err.to_string(),
+            };
+            match value.as_str().and_then(choice) {
+                Some(choice) => value = Value::String(choice.to_string()),
+                None => return Err(format!("--set {assignment}: {err}")),
+            }
+        }
+        // Fields that aren't in the settings are dropped when they are read, so they were misspelled
+        let set = serde_json::from_value::<Settings>(json.clone())
+            .and_then(serde_json::to_value)
+            .map_err(|err| err.to_string())?;
+        if field(&set, &path).is_none() {
+            return Err(format!("--set {assignment}: there is no such setting"));
+        }
+    }
+    serde_json::from_value(json).map_err(|err| err.to_string())
+}
+
+/// The field at a path, adding it if it isn't there yet, or turning an unset parent into an
+/// object to hold it
+fn field_mut<'a>(json: &'a mut Value, path: &[&str]) -> Result<&'a mut Value, String> {
+    path.iter().try_fold(json, |json, key| {
+        if json.is_null() {
+            *json = Value::Object(Map::new());
+        }
+        match json {
+            Value::Object(object) => Ok(object.entry(key.to_string()).or_insert(Value::Null)),
+            Value::Array(array) => key
+                .parse::<usize>()
+                .ok()
+                .and_then(|i| array.get_mut(i))
+                .ok_or_else(|| format!("{key} is not an index of the list")),
+            _ => Err(format!("{key} can't be set inside of a value")),
+        }
+    })
+}
+
+fn field<'a>(json: &'a Value, path: &[&str]) -> Option<&'a Value> {
+    path.iter().try_fold(json, |json, key| match json {
+        Value::Object(object) => object.get(*key),
+        Value::Array(array) => array.get(key.parse::<usize>().ok()?),
+        _ => None,
+    })
+}
+
+/// Variants of the choices in the settings, which can be named in any case
+///
+/// A variant left out here can still be set by its exact name.
+const CHOICES: &[&str] = &[
+    // OriginMode
+    "BottomLeft",
+    "TopLeft",
+    "Center",
+    "CurrentPosition",
+    // PaintFilter
+    "All",
+    "Stroked",
+    "Filled",
+    "StrokedOrFilled",
+    // StrokeOutline
+    "Centerline",
+    "Outline",
+    // Dialect
+    "Grbl",
+    "Marlin",
+    "LinuxCnc",
+    "Klipper",
+    // FillPattern and HalftonePattern
+    "Lines",
+    "Crosshatch",
+    "Concentric",
+    "Spiral",
+    "Stipple",
+    "Dots",
+    // PowerSource
+    "Luminance",
+    "Opacity",
+    // LeadShape
+    "Line",
+    "Arc",
+    // Plunge
+    "Straight",
+    "Ramp",
+    "Helix",
+    // Dithering
+    "Grayscale",
+    "Threshold",
+    "FloydSteinberg",
+    "Ordered",
+    // MarkShape
+    "Cross",
+    "Corner",
+    // MarkPosition
+    "Top",
+    "TopRight",
+    "Right",
+    "BottomRight",
+    "Bottom",
+    "Left",
+    // ToolKind
+    "EndMill",
+    "VBit",
+    "Laser",
+    // KerfSide
+    "Outside",
+    "Inside",
+    // CutDirection
+    "AsDrawn",
+    "Climb",
+    "Conventional",
+];
+
+/// The choice named like `name` in another case, if there is one
+///
+/// Letter case, dashes, and underscores are ignored, so `linux-cnc` is `LinuxCnc`.
+fn choice(name: &str) -> Option<&'static str> {
+    let normalized = |name: &str| {
+        name.chars()
+            .filter(|c| *c != '-' && *c != '_')
+            .flat_map(char::to_lowercase)
+            .collect::<String>()
+    };
+    CHOICES
+        .iter()
+        .copied()
+        .find(|choice| *choice != name && normalized(choice) == normalized(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use svg2gcode::{
+        CutDirection, Dialect, Dithering, FillPattern, HalftonePattern, KerfSide, LeadShape,
+        MarkPosition, MarkShape, OriginMode, PaintFilter, Plunge, PowerSource, StrokeOutline,
+        ToolKind,
+    };
+
+    use super::*;
+
+    #[test]
+    fn choices_are_variants() {
+        for choice in CHOICES {
+            let value = Value::String(choice.to_string());
+            assert!(
+                serde_json::from_value::<OriginMode>(value.clone()).is_ok()
+                    || serde_json::from_value::<PaintFilter>(value.clone()).is_ok()
+                    || serde_json::from_value::<StrokeOutline>(value.clone()).is_ok()
+                    || serde_json::from_value::<Dialect>(value.clone()).is_ok()
+                    || serde_json::from_value::<FillPattern>(value.clone()).is_ok()
+                    || serde_json::from_value::<HalftonePattern>(value.clone()).is_ok()
+                    || serde_json::from_value::<PowerSource>(value.clone()).is_ok()
+                    || serde_json::from_value::<LeadShape>(value.clone()).is_ok()
+                    || serde_json::from_value::<Plunge>(value.clone()).is_ok()
+                    || serde_json::from_value::<Dithering>(value.clone()).is_ok()
+                    || serde_json::from_value::<MarkShape>(value.clone()).is_ok()
+                    || serde_json::from_value::<MarkPosition>(value.clone()).is_ok()
+                    || serde_json::from_value::<ToolKind>(value.clone()).is_ok()
+                    || serde_json::from_value::<KerfSide>(value.clone()).is_ok()
+                    || serde_json::from_value::<CutDirection>(value.clone()).is_ok(),
+                "{choice} is not a variant of a choice"
+            );
+        }
+    }
+
+    #[test]
+    fn choices_are_named_in_any_case() {
+        let settings = set_fields(
+            &Settings::default(),
+            &[
+                "machine.dialect=linux-cnc".to_string(),
+                "conversion.origin_mode=top_left".to_string(),
+            ],
+        )
+        .unwrap();
+        assert_eq!(settings.machine.dialect, Some(Dialect::LinuxCnc));
+        assert_eq!(settings.conversion.origin_mode, OriginMode::TopLeft);
+    }
+
+    #[test]
+    fn unknown_choices_and_settings_are_rejected() {
+        let settings = Settings::default();
+        assert!(set_fields(&settings, &["machine.dialect=smoothie".to_string()]).is_err());
+        assert!(set_fields(&settings, &["conversion.tolerence=0.1".to_string()]).is_err());
+        assert!(set_fields(&settings, &["conversion.tolerance".to_string()]).is_err());
+        let settings = set_fields(&settings, &["conversion.tolerance=0.05".to_string()]).unwrap();
+        assert_eq!(settings.conversion.tolerance, 0.05);
+    }
+}
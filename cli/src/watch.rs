@@ -0,0 +1,112 @@
+use std::{collections::BTreeSet, io, path::Path, sync::mpsc, time::Duration};
+
+use log::{error, info};
+use notify::{EventKind, RecursiveMode, Watcher};
+
+/// How long to wait for more changes after one, since editors often write a file in several steps
+/// (i.e. to a temporary file that is then renamed over it)
+const SETTLE_TIME: Duration = Duration::from_millis(200);
+
+/// Convert the SVG at `path`, then again every time it changes, until interrupted
+///
+/// If `path` is a directory, each SVG in it is converted at first and again when it changes.
+/// Errors converting an SVG are logged and don't stop watching, since it may just have been
+/// saved halfway.
+pub fn watch(path: &Path, mut convert: impl FnMut(&Path) -> io::Result<()>) -> io::Result<()> {
+    let is_dir = path.is_dir();
+    // The directory of a file is watched instead, so it is still found after being replaced
+    let dir = if is_dir {
+        path
+    } else {
+        path.parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .unwrap_or(Path::new("."))
+    };
+    let is_watched = |changed: &Path| {
+        if is_dir {
+            changed.extension().is_some_and(|ext| ext == "svg")
+        } else {
+            changed.file_name() == path.file_name()
+        }
+    };
+
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender).map_err(io::Error::other)?;
+    watcher
+        .watch(dir, RecursiveMode::NonRecursive)
+        .map_err(io::Error::other)?;
+
+    let mut changed = if is_dir {
+        dir.read_dir()?
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|svg| is_watched(svg))
+            .collect()
+    } else {
+        BTreeSet::from([path.to_path_buf()])
+    };
+    loop {
+        if !changed.is_empty() {
+            for svg in std::mem::take(&mut changed) {
+                match convert(&svg) {
+                    Ok(()) => info!("Converted {}", svg.display()),
+                    Err(err) => error!("Could not convert {}: {err}", svg.display()),
+                }
+            }
+            info!("Watching {} for changes", path.display());
+        }
+
+        let mut event = receiver.recv().map_err(io::Error::other)?;
+        loop {
+            match event {
+                Ok(event) if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) => {
+                    changed.extend(
+                        event
+                            .paths
+                            .into_iter()
+                            .filter(|changed| is_watched(changed)),
+                    );
+                }
+                Ok(_) => {}
+                Err(err) => error!("Could not watch {}: {err}", path.display()),
+            }
+            match receiver.recv_timeout(SETTLE_TIME) {
+                Ok(next) => event = next,
+                Err(_) => break,
+            }
+        }
+        if !is_dir && !changed.is_empty() {
+            // Converted from the path as given, not as it was reported
+            changed = BTreeSet::from([path.to_path_buf()]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, thread};
+
+    use super::*;
+
+    #[test]
+    fn errors_dont_stop_watching() {
+        let dir = std::env::temp_dir().join(format!("svg2gcode-watch-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let svg = dir.join("drawing.svg");
+        fs::write(&svg, "<svg").unwrap();
+
+        let (sender, receiver) = mpsc::channel();
+        let watched = svg.clone();
+        // Never returns, so it is left running when the test ends
+        thread::spawn(move || {
+            watch(&watched, |svg| {
+                sender.send(svg.to_path_buf()).unwrap();
+                Err(io::Error::new(io::ErrorKind::InvalidData, "saved halfway"))
+            })
+        });
+
+        let timeout = Duration::from_secs(10);
+        assert_eq!(receiver.recv_timeout(timeout).unwrap(), svg);
+        fs::write(&svg, "<svg/>").unwrap();
+        assert_eq!(receiver.recv_timeout(timeout).unwrap(), svg);
+    }
+}
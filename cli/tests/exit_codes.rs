@@ -0,0 +1,131 @@
+use std::{fs, path::PathBuf, process::Command};
+
+const SQUARE: &str = r#"<svg xmlns="http://www.w3.org/2000/svg" width="10mm" height="10mm" viewBox="0 0 10 10">
+    <path d="M 0 0 L 10 0 L 10 10 L 0 10 Z"/>
+</svg>"#;
+
+/// Writes `contents` to a file named after the test in the temp directory
+fn input(name: &str, contents: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("svg2gcode-exit-{}-{name}", std::process::id()));
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+/// Exit code of the CLI run with `args`
+fn exit_code(args: &[&str]) -> i32 {
+    Command::new(env!("CARGO_BIN_EXE_svg2gcode"))
+        .args(args)
+        .env("RUST_LOG", "off")
+        .output()
+        .unwrap()
+        .status
+        .code()
+        .unwrap()
+}
+
+#[test]
+fn conversion_succeeds() {
+    let svg = input("square.svg", SQUARE);
+    let out = svg.with_extension("gcode");
+    assert_eq!(
+        exit_code(&[svg.to_str().unwrap(), "-o", out.to_str().unwrap()]),
+        0
+    );
+}
+
+#[test]
+fn invalid_options_exit_with_1() {
+    let svg = input("options.svg", SQUARE);
+    let svg = svg.to_str().unwrap();
+    assert_eq!(exit_code(&[svg, "--origin", "x"]), 1);
+    assert_eq!(exit_code(&[svg, "--dimensions", "10mm,x"]), 1);
+    assert_eq!(exit_code(&[svg, "--origin-mode", "nope"]), 1);
+    assert_eq!(exit_code(&[svg, "--max-line-length", "5"]), 1);
+    assert_eq!(exit_code(&[svg, "--calibration", "0,0,0,0,0,0"]), 1);
+    assert_eq!(exit_code(&[svg, "--calibration", "1,2,0,2,4,0"]), 1);
+}
+
+#[test]
+fn unknown_arguments_exit_with_2() {
+    assert_eq!(exit_code(&["--no-such-argument"]), 2);
+}
+
+#[test]
+fn input_that_cant_be_parsed_exits_with_3() {
+    let svg = input("broken.svg", "<svg");
+    assert_eq!(exit_code(&[svg.to_str().unwrap()]), 3);
+    let script = input("broken.rhai", "fn path() {");
+    assert_eq!(
+        exit_code(&[svg.to_str().unwrap(), "--script", script.to_str().unwrap()]),
+        3
+    );
+    let image = input("broken.png", "not an image");
+    assert_eq!(
+        exit_code(&["halftone", image.to_str().unwrap(), "--width", "10"]),
+        3
+    );
+}
+
+#[test]
+fn unsupported_svg_exits_with_4_when_strict() {
+    let svg = input(
+        "clip.svg",
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="10mm" height="10mm" viewBox="0 0 10 10">
+            <clipPath id="clip"><rect width="5" height="5"/></clipPath>
+            <path clip-path="url(#clip)" d="M 0 0 L 10 0 L 10 10 L 0 10 Z"/>
+        </svg>"#,
+    );
+    let svg = svg.to_str().unwrap();
+    assert_eq!(exit_code(&[svg, "--strict", "true"]), 4);
+    assert_eq!(exit_code(&[svg]), 0);
+}
+
+#[test]
+fn artwork_off_the_bed_exits_with_5_when_strict() {
+    let svg = input(
+        "large.svg",
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="1000mm" height="10mm" viewBox="0 0 1000 10">
+            <path d="M 0 0 L 1000 0"/>
+        </svg>"#,
+    );
+    let svg = svg.to_str().unwrap();
+    assert_eq!(exit_code(&[svg, "--strict", "true"]), 5);
+
+    // Even when a run that wasn't strict cached the program
+    let cache = std::env::temp_dir().join(format!("svg2gcode-exit-{}-cache", std::process::id()));
+    let cache = cache.to_str().unwrap();
+    assert_eq!(exit_code(&[svg, "--cache-dir", cache]), 0);
+    assert_eq!(
+        exit_code(&[svg, "--cache-dir", cache, "--strict", "true"]),
+        5
+    );
+}
+
+#[test]
+fn missing_file_exits_with_6() {
+    let svg = std::env::temp_dir().join("svg2gcode-exit-missing.svg");
+    assert_eq!(exit_code(&[svg.to_str().unwrap()]), 6);
+}
+
+#[test]
+fn other_warnings_exit_with_7_when_strict() {
+    let svg = input(
+        "layer.svg",
+        r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:inkscape="http://www.inkscape.org/namespaces/inkscape" width="10mm" height="10mm" viewBox="0 0 10 10">
+            <g inkscape:groupmode="layer" inkscape:label="cut">
+                <path d="M 0 0 L 10 0"/>
+            </g>
+        </svg>"#,
+    );
+    let layers = input("layers.json", r#"{"cut": {"passes": 0}}"#);
+    assert_eq!(
+        exit_code(&[
+            svg.to_str().unwrap(),
+            "--layers",
+            layers.to_str().unwrap(),
+            "--strict",
+            "true"
+        ]),
+        7
+    );
+}
@@ -1,3 +1,5 @@
+use alloc::{vec, vec::Vec};
+
 use euclid::Angle;
 use lyon_geom::{
     ArcFlags, CubicBezierSegment, Line, LineSegment, Point, Scalar, SvgArc, Transform, Vector,
@@ -155,6 +157,14 @@ where
         }
 
         let self_arc = self.to_arc();
+        // Like the linear check on curves, this also stops splitting arcs that are too short to
+        // find a circular arc between their tangents
+        if (self_arc.sample(S::HALF) - self.from.lerp(self.to, S::HALF)).length() < tolerance {
+            return vec![ArcOrLineSegment::Line(LineSegment {
+                from: self.from,
+                to: self.to,
+            })];
+        }
         if let Some(svg_arc) = arc_from_endpoints_and_tangents(
             self.from,
             self_arc.sample_tangent(S::ZERO),
@@ -191,16 +201,20 @@ pub trait Transformed<S> {
 }
 
 impl<S: Scalar> Transformed<S> for SvgArc<S> {
-    /// A lot of the math here is heavily borrowed from [Vitaly Putzin's svgpath](https://github.com/fontello/svgpath).
+    /// The image of an ellipse under any affine transform, including non-uniform scales and skews,
+    /// is another ellipse. Its radii are the square roots of the eigenvalues of `M * transpose(M)`,
+    /// where the columns of `M` are the transformed radii, and its x-axis rotation is the angle of
+    /// the eigenvector of the larger one.
     ///
-    /// The code is Rust-ified with only one or two changes, but I plan to understand the math here and
-    /// merge changes upstream to lyon-geom.
+    /// A lot of the math here is heavily borrowed from [Vitaly Putzin's svgpath](https://github.com/fontello/svgpath).
     fn transformed(&self, transform: &Transform<S>) -> Self {
         let from = transform.transform_point(self.from);
         let to = transform.transform_point(self.to);
 
         // Translation does not affect rotation, radii, or flags
         let [a, b, c, d, _tx, _ty] = transform.to_array();
+        // Variable names follow the notation of the referenced math
+        #[allow(non_snake_case)]
         let (x_rotation, radii) = {
             let (sin, cos) = self.x_rotation.sin_cos();
 
@@ -225,8 +239,8 @@ impl<S: Scalar> Transformed<S> for SvgArc<S> {
             // the "mean eigenvalue"
             let JK = (J + K) / S::TWO;
 
-            // check if the image is (almost) a circle
-            if D < S::EPSILON * JK {
+            // check if the image is (almost) a circle, relative to its size
+            if D < S::EPSILON * JK * JK {
                 // if it is
                 (Angle::zero(), Vector::splat(JK.sqrt()))
             } else {
@@ -251,7 +265,8 @@ impl<S: Scalar> Transformed<S> for SvgArc<S> {
                         .atan(),
                     )
                 };
-                (ax, Vector::from([l1.sqrt(), l2.sqrt()]))
+                // l2 can round to just below 0 when the image is squashed nearly flat
+                (ax, Vector::from([l1.sqrt(), l2.max(S::ZERO).sqrt()]))
             }
         };
         // A mirror transform causes this flag to be flipped
@@ -277,11 +292,50 @@ impl<S: Scalar> Transformed<S> for SvgArc<S> {
 #[cfg(test)]
 mod tests {
     use cairo::{Context, SvgSurface};
-    use lyon_geom::{point, vector, CubicBezierSegment, Point, Vector};
+    use lyon_geom::{
+        point, vector, Angle, ArcFlags, CubicBezierSegment, Point, SvgArc, Transform, Vector,
+    };
     use std::path::PathBuf;
     use svgtypes::PathParser;
 
-    use crate::arc::{ArcOrLineSegment, FlattenWithArcs};
+    use crate::arc::{ArcOrLineSegment, FlattenWithArcs, Transformed};
+
+    #[test]
+    fn arcs_stay_on_ellipse_under_skew_and_non_uniform_scale() {
+        let svg_arc = SvgArc {
+            from: point(10., 10.),
+            to: point(30., 20.),
+            radii: vector(40., 15.),
+            x_rotation: Angle::degrees(20.),
+            flags: ArcFlags {
+                large_arc: true,
+                sweep: false,
+            },
+        };
+        let arc = svg_arc.to_arc();
+        // Group transforms as exported by Illustrator, including a mirror
+        for transform in [
+            Transform::new(1., 0., 0.5, 1., 0., 0.),
+            Transform::new(0.8, 0.3, -0.7, 1.4, 12.5, -3.),
+            Transform::new(-1.5, 0., 0., 0.25, 100., 0.),
+            Transform::new(0.001, 0., 0., 0.0005, 0., 0.),
+        ] {
+            let transformed = svg_arc.transformed(&transform).to_arc();
+            let (sin, cos) = transformed.x_rotation.sin_cos();
+            for i in 0..=10 {
+                let t = i as f64 / 10.;
+                let expected = transform.transform_point(arc.sample(t));
+                let v = expected - transformed.center;
+                let (x, y) = (v.x * cos + v.y * sin, -v.x * sin + v.y * cos);
+                let on_ellipse =
+                    (x / transformed.radii.x).powi(2) + (y / transformed.radii.y).powi(2);
+                assert!((on_ellipse - 1.).abs() < 1e-9, "{transform:?}");
+                // Same side of the ellipse, up to the precision of Arc::from_svg_arc
+                let scale = transformed.radii.x;
+                assert!((transformed.sample(t) - expected).length() < 1e-3 * scale);
+            }
+        }
+    }
 
     #[test]
     #[ignore = "Creates an image file, will revise later"]
@@ -0,0 +1,128 @@
+use alloc::{vec, vec::Vec};
+
+use lyon_geom::{euclid::default::Box2D, Point};
+
+use crate::Toolpath;
+
+/// Cut a toolpath down to the parts inside a rectangle, including its edges.
+///
+/// Returns the pieces inside, which are flattened to lines. The toolpath is returned as-is if it is
+/// entirely inside.
+pub fn clip_to_rect(toolpath: &Toolpath, rect: &Box2D<f64>, tolerance: f64) -> Vec<Toolpath> {
+    let contains = |p: &Point<f64>| {
+        (rect.min.x..=rect.max.x).contains(&p.x) && (rect.min.y..=rect.max.y).contains(&p.y)
+    };
+    let points = toolpath.flattened(tolerance);
+    if points.iter().all(contains) {
+        return vec![toolpath.clone()];
+    }
+
+    let mut pieces: Vec<Vec<Point<f64>>> = vec![];
+    for (from, to) in points.iter().zip(points.iter().skip(1)) {
+        let Some((from, to)) = clip_line(*from, *to, rect) else {
+            continue;
+        };
+        match pieces.last_mut() {
+            Some(piece) if piece.last() == Some(&from) => piece.push(to),
+            _ => pieces.push(vec![from, to]),
+        }
+    }
+
+    let mut pieces = pieces
+        .iter()
+        // Skip pieces that only touch the rectangle
+        .filter(|piece| piece.iter().any(|p| *p != piece[0]))
+        .filter_map(|piece| Toolpath::from_polyline(piece))
+        .collect::<Vec<_>>();
+    if let Some(first) = pieces.first_mut() {
+        first.comments = toolpath.comments.clone();
+        first.pause = toolpath.pause;
+    }
+    pieces
+        .iter_mut()
+        .for_each(|piece| piece.source = toolpath.source.clone());
+    pieces
+}
+
+/// The part of a line inside a rectangle
+///
+/// <https://en.wikipedia.org/wiki/Liang%E2%80%93Barsky_algorithm>
+fn clip_line(
+    from: Point<f64>,
+    to: Point<f64>,
+    rect: &Box2D<f64>,
+) -> Option<(Point<f64>, Point<f64>)> {
+    let delta = to - from;
+    let (mut enter, mut exit) = (0., 1_f64);
+    for (p, q) in [
+        (-delta.x, from.x - rect.min.x),
+        (delta.x, rect.max.x - from.x),
+        (-delta.y, from.y - rect.min.y),
+        (delta.y, rect.max.y - from.y),
+    ] {
+        if p == 0. {
+            if q < 0. {
+                return None;
+            }
+        } else if p < 0. {
+            enter = f64::max(enter, q / p);
+        } else {
+            exit = exit.min(q / p);
+        }
+    }
+    if enter > exit {
+        return None;
+    }
+
+    // Keep the original points where possible so pieces stay connected
+    let at = |t: f64| {
+        if t == 0. {
+            from
+        } else if t == 1. {
+            to
+        } else {
+            from + delta * t
+        }
+    };
+    Some((at(enter), at(exit)))
+}
+
+#[cfg(test)]
+mod test {
+    use lyon_geom::point;
+
+    use super::*;
+
+    #[test]
+    fn pieces_outside_are_removed() {
+        let rect = Box2D::new(point(0., 0.), point(10., 10.));
+        let inside = Toolpath::from_polyline(&[point(1., 1.), point(9., 9.)]).unwrap();
+        assert_eq!(clip_to_rect(&inside, &rect, 0.1), [inside]);
+
+        // Leaves and comes back in
+        let zigzag = Toolpath::from_polyline(&[
+            point(5., 5.),
+            point(15., 5.),
+            point(15., 8.),
+            point(5., 8.),
+            point(5., 20.),
+        ])
+        .unwrap();
+        let pieces = clip_to_rect(&zigzag, &rect, 0.1)
+            .iter()
+            .map(|piece| piece.flattened(0.1))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            pieces,
+            [
+                vec![point(5., 5.), point(10., 5.)],
+                vec![point(10., 8.), point(5., 8.), point(5., 10.)],
+            ]
+        );
+
+        let outside = Toolpath::from_polyline(&[point(11., 0.), point(20., 20.)]).unwrap();
+        assert!(clip_to_rect(&outside, &rect, 0.1).is_empty());
+        let touching = Toolpath::from_polyline(&[point(10., 0.), point(20., -10.)]).unwrap();
+        assert!(clip_to_rect(&touching, &rect, 0.1).is_empty());
+    }
+}
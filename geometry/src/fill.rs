@@ -0,0 +1,502 @@
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    vec,
+    vec::Vec,
+};
+use core::f64::consts::TAU;
+
+use lyon_geom::{point, vector, Angle, Box2D, LineSegment, Point, Rotation};
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+use crate::Toolpath;
+
+/// Most samples taken of the distance to the outline for [`concentric`] fills
+const MAX_SAMPLES: f64 = 250_000.;
+
+/// Points of a polygon closer than this are taken to be the same point
+const SAME_POINT: f64 = 1e-9;
+
+/// Parallel lines `spacing` apart at `angle` from the x-axis, inside polygons by the even-odd rule.
+///
+/// Every other line is drawn in reverse so the tool zig-zags across the shape.
+pub fn hatch(polygons: &[Vec<Point<f64>>], spacing: f64, angle: Angle<f64>) -> Vec<Toolpath> {
+    // Rotated so the lines are horizontal
+    let to_horizontal = Rotation::new(-angle);
+    let from_horizontal = Rotation::new(angle);
+    let rotated = polygons
+        .iter()
+        .map(|polygon| {
+            polygon
+                .iter()
+                .map(|p| to_horizontal.transform_point(*p))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let Some(bounds) = bounds(&rotated) else {
+        return vec![];
+    };
+
+    let mut toolpaths = vec![];
+    let mut y = bounds.min.y + spacing / 2.;
+    let mut reverse = false;
+    while y < bounds.max.y {
+        let mut lines = crossings(&rotated, y)
+            .chunks_exact(2)
+            .map(|pair| [point(pair[0], y), point(pair[1], y)])
+            .collect::<Vec<_>>();
+        if reverse {
+            lines.reverse();
+            lines.iter_mut().for_each(|line| line.reverse());
+        }
+        toolpaths.extend(lines.into_iter().filter_map(|line| {
+            Toolpath::from_polyline(&line.map(|p| from_horizontal.transform_point(p)))
+        }));
+        reverse = !reverse;
+        y += spacing;
+    }
+    toolpaths
+}
+
+/// Rings `spacing` apart that follow the outline inwards, inside polygons by the even-odd rule.
+///
+/// The rings are traced from a sampled distance field, so they are only as accurate as the
+/// sampling, which is a quarter of the spacing for all but very large shapes.
+pub fn concentric(polygons: &[Vec<Point<f64>>], spacing: f64) -> Vec<Toolpath> {
+    let Some(bounds) = bounds(polygons) else {
+        return vec![];
+    };
+    let size = bounds.size();
+    let mut resolution = spacing / 4.;
+    let samples = (size.width / resolution) * (size.height / resolution);
+    if samples > MAX_SAMPLES {
+        resolution *= (samples / MAX_SAMPLES).sqrt();
+    }
+    // Padded by a sample on each side so every ring is closed
+    let origin = bounds.min - vector(resolution, resolution);
+    let columns = (size.width / resolution).ceil() as usize + 3;
+    let rows = (size.height / resolution).ceil() as usize + 3;
+    let grid = Grid {
+        origin,
+        resolution,
+        columns,
+    };
+
+    let outline = polygons
+        .iter()
+        .flat_map(|polygon| edges(polygon))
+        .collect::<Vec<_>>();
+    // Positive inside and negative outside
+    let field = (0..rows)
+        .flat_map(|j| (0..columns).map(move |i| (i, j)))
+        .map(|(i, j)| {
+            let p = grid.at(i, j);
+            let distance = outline
+                .iter()
+                .map(|edge| edge.distance_to_point(p))
+                .fold(f64::INFINITY, f64::min);
+            if contains_even_odd(polygons, p) {
+                distance
+            } else {
+                -distance
+            }
+        })
+        .collect::<Vec<_>>();
+    let deepest = field.iter().copied().fold(0., f64::max);
+
+    let mut toolpaths = vec![];
+    let mut level = spacing / 2.;
+    while level < deepest {
+        toolpaths.extend(
+            grid.contours(&field, rows, level)
+                .iter()
+                .filter_map(|polyline| Toolpath::from_polyline(polyline)),
+        );
+        level += spacing;
+    }
+    toolpaths
+}
+
+/// An [Archimedean spiral](https://en.wikipedia.org/wiki/Archimedean_spiral) with turns `spacing`
+/// apart from the middle of the polygons outwards, cut to the inside of the polygons by the
+/// even-odd rule.
+pub fn spiral(polygons: &[Vec<Point<f64>>], spacing: f64, tolerance: f64) -> Vec<Toolpath> {
+    let Some(bounds) = bounds(polygons) else {
+        return vec![];
+    };
+    let center = bounds.center();
+    let max_radius = (bounds.max - center).length();
+
+    let mut pieces = vec![];
+    let mut piece: Vec<Point<f64>> = vec![];
+    let mut previous = center;
+    let mut theta: f64 = 0.;
+    loop {
+        let radius = spacing * theta / TAU;
+        if radius > max_radius {
+            break;
+        }
+        let p = center + vector(theta.cos(), theta.sin()) * radius;
+        match (piece.is_empty(), contains_even_odd(polygons, p)) {
+            (true, true) => {
+                if theta > 0. {
+                    piece.push(crossing(polygons, previous, p));
+                }
+                piece.push(p);
+            }
+            (false, true) => piece.push(p),
+            (false, false) => {
+                piece.push(crossing(polygons, previous, p));
+                pieces.push(core::mem::take(&mut piece));
+            }
+            (true, false) => {}
+        }
+        previous = p;
+
+        // Stay within the tolerance of the curve, without stepping over thin parts of the shape
+        let step = if radius > tolerance {
+            (2. * (1. - tolerance / radius).acos()).min(spacing / (2. * radius))
+        } else {
+            TAU / 16.
+        };
+        theta += step.min(TAU / 16.);
+    }
+    pieces.push(piece);
+
+    pieces
+        .iter()
+        .filter(|piece| piece.len() > 1)
+        .filter_map(|piece| Toolpath::from_polyline(piece))
+        .collect()
+}
+
+/// Dots `spacing` apart on a hexagonal grid, inside polygons by the even-odd rule.
+///
+/// Each dot is a toolpath that does not move (see [`Toolpath::is_dot`]).
+pub fn stipple(polygons: &[Vec<Point<f64>>], spacing: f64) -> Vec<Toolpath> {
+    let Some(bounds) = bounds(polygons) else {
+        return vec![];
+    };
+    let row_height = spacing * 3_f64.sqrt() / 2.;
+
+    let mut toolpaths = vec![];
+    let mut y = bounds.min.y + row_height / 2.;
+    let mut row = 0;
+    while y < bounds.max.y {
+        let offset = if row % 2 == 0 { 0. } else { spacing / 2. };
+        let mut dots = vec![];
+        let mut x = bounds.min.x + spacing / 2. + offset;
+        while x < bounds.max.x {
+            let p = point(x, y);
+            if contains_even_odd(polygons, p) {
+                dots.extend(Toolpath::from_polyline(&[p, p]));
+            }
+            x += spacing;
+        }
+        if row % 2 == 1 {
+            dots.reverse();
+        }
+        toolpaths.append(&mut dots);
+        y += row_height;
+        row += 1;
+    }
+    toolpaths
+}
+
+/// Samples of a field on a grid, counting from the bottom left
+struct Grid {
+    origin: Point<f64>,
+    resolution: f64,
+    columns: usize,
+}
+
+/// Where a contour crosses the line between two neighboring samples, identified by the first
+/// sample and whether the line is horizontal
+type Crossing = (usize, usize, bool);
+
+impl Grid {
+    fn at(&self, i: usize, j: usize) -> Point<f64> {
+        self.origin + vector(i as f64, j as f64) * self.resolution
+    }
+
+    /// Polylines where the field crosses `level`, closed unless they run off the grid
+    ///
+    /// <https://en.wikipedia.org/wiki/Marching_squares>
+    fn contours(&self, field: &[f64], rows: usize, level: f64) -> Vec<Vec<Point<f64>>> {
+        let value = |i: usize, j: usize| field[j * self.columns + i];
+        let mut links: BTreeMap<Crossing, Vec<Crossing>> = BTreeMap::new();
+        let mut link = |a: Crossing, b: Crossing| {
+            links.entry(a).or_default().push(b);
+            links.entry(b).or_default().push(a);
+        };
+        for j in 0..rows - 1 {
+            for i in 0..self.columns - 1 {
+                // Counterclockwise from the bottom left, and the sides between them
+                let corners = [(i, j), (i + 1, j), (i + 1, j + 1), (i, j + 1)];
+                let sides = [
+                    (i, j, true),
+                    (i + 1, j, false),
+                    (i, j + 1, true),
+                    (i, j, false),
+                ];
+                let above = corners.map(|(i, j)| value(i, j) >= level);
+                let crossed = (0..4)
+                    .filter(|side| above[*side] != above[(side + 1) % 4])
+                    .collect::<Vec<_>>();
+                match crossed[..] {
+                    [a, b] => link(sides[a], sides[b]),
+                    // Saddle, where the middle decides which opposite corners are connected
+                    [_, _, _, _] => {
+                        let middle = corners.iter().map(|(i, j)| value(*i, *j)).sum::<f64>() / 4.;
+                        if (middle >= level) == above[0] {
+                            link(sides[0], sides[1]);
+                            link(sides[2], sides[3]);
+                        } else {
+                            link(sides[3], sides[0]);
+                            link(sides[1], sides[2]);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let point_at = |(i, j, horizontal): Crossing| {
+            let (to_i, to_j) = if horizontal { (i + 1, j) } else { (i, j + 1) };
+            let (from, to) = (value(i, j), value(to_i, to_j));
+            self.at(i, j)
+                .lerp(self.at(to_i, to_j), (level - from) / (to - from))
+        };
+        let mut visited = BTreeSet::new();
+        // Start open contours at one of their ends
+        let starts = links
+            .iter()
+            .filter(|(_, linked)| linked.len() == 1)
+            .map(|(crossing, _)| *crossing)
+            .chain(links.keys().copied())
+            .collect::<Vec<_>>();
+        let mut polylines = vec![];
+        for start in starts {
+            if !visited.insert(start) {
+                continue;
+            }
+            let mut polyline = vec![point_at(start)];
+            let mut current = start;
+            while let Some(next) = links[&current].iter().find(|next| !visited.contains(*next)) {
+                visited.insert(*next);
+                polyline.push(point_at(*next));
+                current = *next;
+            }
+            if current != start && links[&current].contains(&start) {
+                polyline.push(polyline[0]);
+            }
+            polylines.push(polyline);
+        }
+        polylines
+    }
+}
+
+/// Whether a point is inside polygons by the even-odd rule
+pub fn contains_even_odd(polygons: &[Vec<Point<f64>>], p: Point<f64>) -> bool {
+    polygons
+        .iter()
+        .flat_map(|polygon| edges(polygon))
+        .filter(|edge| {
+            (edge.from.y > p.y) != (edge.to.y > p.y)
+                && p.x
+                    < edge.from.x
+                        + (p.y - edge.from.y) / (edge.to.y - edge.from.y)
+                            * (edge.to.x - edge.from.x)
+        })
+        .count()
+        % 2
+        == 1
+}
+
+/// Points where the sides of a polygon cross each other, i.e. in a figure eight
+///
+/// The even-odd rule leaves the overlapping parts of such a polygon empty, unlike the nonzero rule
+/// SVGs are filled by unless they set `fill-rule`, so fills drawn from it won't match what a
+/// browser shows.
+pub fn self_intersections(polygon: &[Point<f64>]) -> Vec<Point<f64>> {
+    // Flattening leaves the end of a closed outline a rounding error away from its start
+    let mut points = polygon.to_vec();
+    points.dedup_by(|a, b| (*a - *b).length() <= SAME_POINT);
+    while points.len() > 1 && (points[points.len() - 1] - points[0]).length() <= SAME_POINT {
+        points.pop();
+    }
+    // Only sides that overlap along the x-axis can cross
+    let mut sides = edges(&points).enumerate().collect::<Vec<_>>();
+    sides.sort_by(|(_, a), (_, b)| a.from.x.min(a.to.x).total_cmp(&b.from.x.min(b.to.x)));
+    // Neighbouring sides share a point, which doesn't count as crossing
+    let neighbours =
+        |i: usize, j: usize| (i + 1) % points.len() == j || (j + 1) % points.len() == i;
+    let mut crossings = vec![];
+    for (k, (i, side)) in sides.iter().enumerate() {
+        let max_x = side.from.x.max(side.to.x);
+        crossings.extend(
+            sides[k + 1..]
+                .iter()
+                .take_while(|(_, other)| other.from.x.min(other.to.x) <= max_x)
+                .filter(|(j, _)| !neighbours(*i, *j))
+                .filter_map(|(_, other)| side.intersection(other)),
+        );
+    }
+    crossings
+}
+
+/// Where the line from `from` to `to` first crosses the outline of the polygons, or `to` if it doesn't
+fn crossing(polygons: &[Vec<Point<f64>>], from: Point<f64>, to: Point<f64>) -> Point<f64> {
+    let line = LineSegment { from, to };
+    polygons
+        .iter()
+        .flat_map(|polygon| edges(polygon))
+        .filter_map(|edge| line.intersection_t(&edge))
+        .map(|(t, _)| t)
+        .fold(None, |first: Option<f64>, t| {
+            Some(first.map_or(t, |first| first.min(t)))
+        })
+        .map_or(to, |t| line.sample(t))
+}
+
+/// Sides of a polygon, including the one that closes it
+/// Where a horizontal line at `y` crosses the sides of polygons, from left to right
+///
+/// Pairs of crossings are the parts of the line inside the polygons by the even-odd rule.
+pub(crate) fn crossings(polygons: &[Vec<Point<f64>>], y: f64) -> Vec<f64> {
+    let mut crossings = polygons
+        .iter()
+        .flat_map(|polygon| edges(polygon))
+        .filter(|edge| (edge.from.y > y) != (edge.to.y > y))
+        .map(|edge| {
+            edge.from.x + (y - edge.from.y) / (edge.to.y - edge.from.y) * (edge.to.x - edge.from.x)
+        })
+        .collect::<Vec<_>>();
+    crossings.sort_by(f64::total_cmp);
+    crossings
+}
+
+pub(crate) fn edges(polygon: &[Point<f64>]) -> impl Iterator<Item = LineSegment<f64>> + '_ {
+    polygon
+        .iter()
+        .zip(polygon.iter().cycle().skip(1))
+        .map(|(from, to)| LineSegment {
+            from: *from,
+            to: *to,
+        })
+}
+
+pub(crate) fn bounds(polygons: &[Vec<Point<f64>>]) -> Option<Box2D<f64>> {
+    polygons
+        .iter()
+        .flatten()
+        .fold(None, |bounds: Option<Box2D<f64>>, p| {
+            Some(match bounds {
+                None => Box2D::new(*p, *p),
+                Some(bounds) => Box2D::new(bounds.min.min(*p), bounds.max.max(*p)),
+            })
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A 10mm square with a 4mm square hole in the middle
+    fn frame() -> Vec<Vec<Point<f64>>> {
+        vec![
+            vec![
+                point(0., 0.),
+                point(10., 0.),
+                point(10., 10.),
+                point(0., 10.),
+            ],
+            vec![point(3., 3.), point(7., 3.), point(7., 7.), point(3., 7.)],
+        ]
+    }
+
+    #[test]
+    fn hatch_skips_holes() {
+        let lines = hatch(&frame(), 1., Angle::zero());
+        // One line on each of the 3 rows below and above the hole, two on each of the 4 beside it
+        assert_eq!(lines.len(), 3 + 3 + 2 * 4);
+        assert_eq!(lines[0].flattened(0.1), [point(0., 0.5), point(10., 0.5)]);
+        // Zig-zags across the shape
+        assert_eq!(lines[1].flattened(0.1), [point(10., 1.5), point(0., 1.5)]);
+        assert_eq!(lines[3].flattened(0.1), [point(10., 3.5), point(7., 3.5)]);
+
+        let vertical = hatch(&frame(), 1., Angle::degrees(90.));
+        assert_eq!(vertical.len(), lines.len());
+        assert!((vertical[0].from - point(9.5, 0.)).length() < 1e-9);
+    }
+
+    #[test]
+    fn concentric_rings_follow_outline() {
+        let square = vec![vec![
+            point(0., 0.),
+            point(10., 0.),
+            point(10., 10.),
+            point(0., 10.),
+        ]];
+        let rings = concentric(&square, 2.);
+        // 1mm, 3mm, and 5mm in, which is only the middle
+        assert!(rings.len() >= 2);
+        for (ring, inset) in rings.iter().zip([1., 3.]) {
+            assert!(ring.is_closed(1e-9));
+            assert!(ring.flattened(0.1).iter().all(|p| (p.x - inset)
+                .abs()
+                .min((p.y - inset).abs())
+                < 0.1
+                || (p.x - (10. - inset)).abs().min((p.y - (10. - inset)).abs()) < 0.1));
+        }
+    }
+
+    #[test]
+    fn self_intersections_are_found() {
+        assert!(frame()
+            .iter()
+            .all(|polygon| self_intersections(polygon).is_empty()));
+        // A rect flattened from a toolpath, which ends where it starts but for rounding errors
+        let rect = [
+            point(2., 18.000000000000004),
+            point(12., 18.000000000000004),
+            point(12.000000000000002, 8.),
+            point(2., 7.999999999999998),
+            point(2.0000000000000004, 18.000000000000004),
+            point(2., 18.000000000000004),
+        ];
+        assert!(self_intersections(&rect).is_empty());
+        let figure_eight = [
+            point(0., 0.),
+            point(10., 10.),
+            point(10., 0.),
+            point(0., 10.),
+            point(0., 0.),
+        ];
+        assert_eq!(self_intersections(&figure_eight), [point(5., 5.)]);
+    }
+
+    #[test]
+    fn spiral_is_cut_to_shape() {
+        let pieces = spiral(&frame(), 1., 0.01);
+        assert!(!pieces.is_empty());
+        for piece in &pieces {
+            for p in piece.flattened(0.01) {
+                assert!((-1e-9..=10. + 1e-9).contains(&p.x));
+                assert!(
+                    !(3. + 1e-9..7. - 1e-9).contains(&p.x)
+                        || !(3. + 1e-9..7. - 1e-9).contains(&p.y)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn stipple_dots_are_evenly_spaced() {
+        let dots = stipple(&frame(), 2.);
+        assert!(dots.iter().all(Toolpath::is_dot));
+        assert_eq!(dots[0].from, point(1., 3_f64.sqrt() / 2.));
+        assert_eq!(dots[1].from, point(3., 3_f64.sqrt() / 2.));
+        assert!(dots.iter().all(|dot| contains_even_odd(&frame(), dot.from)));
+    }
+}
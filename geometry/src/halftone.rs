@@ -0,0 +1,241 @@
+use alloc::{vec, vec::Vec};
+use core::f64::consts::{PI, SQRT_2};
+
+use lyon_geom::{point, vector, Angle, ArcFlags, LineSegment, Point, Rotation, SvgArc};
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+use crate::{
+    contains_even_odd,
+    fill::{bounds, crossings, edges},
+    Segment, Toolpath,
+};
+
+/// A grid of dots `spacing` apart at `angle`, each sized to cover the `darkness` (0 to 1) of its
+/// cell at the middle of it, inside polygons by the even-odd rule
+///
+/// Dots are filled with rings `tool_width` apart, or drawn as a single dot if the tool is wider.
+/// Dots much smaller than the tool are left out, and dots near the outline are shrunk to stay
+/// inside it.
+pub fn halftone_dots(
+    polygons: &[Vec<Point<f64>>],
+    spacing: f64,
+    angle: Angle<f64>,
+    tool_width: f64,
+    darkness: impl Fn(Point<f64>) -> f64,
+) -> Vec<Toolpath> {
+    let from_grid = Rotation::new(angle);
+    let Some(grid_bounds) = bounds(&rotated(polygons, -angle)) else {
+        return vec![];
+    };
+    let outline = polygons
+        .iter()
+        .flat_map(|polygon| edges(polygon))
+        .collect::<Vec<_>>();
+
+    let mut toolpaths = vec![];
+    let mut y = grid_bounds.min.y + spacing / 2.;
+    let mut reverse = false;
+    while y < grid_bounds.max.y {
+        let mut row = vec![];
+        let mut x = grid_bounds.min.x + spacing / 2.;
+        while x < grid_bounds.max.x {
+            let center = from_grid.transform_point(point(x, y));
+            let darkness = darkness(center).clamp(0., 1.);
+            // The area of the dot is the darkness of the cell, skipped if the tool would cover
+            // more than twice that
+            let radius = spacing * (darkness / PI).sqrt();
+            if radius >= tool_width / (2. * SQRT_2) && contains_even_odd(polygons, center) {
+                let room = outline
+                    .iter()
+                    .map(|edge| edge.distance_to_point(center))
+                    .fold(f64::INFINITY, f64::min);
+                row.push(dot(center, radius.min(room), tool_width));
+            }
+            x += spacing;
+        }
+        if reverse {
+            row.reverse();
+        }
+        toolpaths.append(&mut row);
+        reverse = !reverse;
+        y += spacing;
+    }
+    toolpaths
+}
+
+/// Lines `spacing` apart at `angle`, as wide as the `darkness` (0 to 1) of the spacing along them,
+/// inside polygons by the even-odd rule
+///
+/// Wider parts are drawn with more lines `tool_width` apart on either side of the middle one.
+pub fn halftone_lines(
+    polygons: &[Vec<Point<f64>>],
+    spacing: f64,
+    angle: Angle<f64>,
+    tool_width: f64,
+    darkness: impl Fn(Point<f64>) -> f64,
+) -> Vec<Toolpath> {
+    let from_lines = Rotation::new(angle);
+    let rotated = rotated(polygons, -angle);
+    let Some(line_bounds) = bounds(&rotated) else {
+        return vec![];
+    };
+    let samples = ((line_bounds.width() / tool_width).ceil() as usize).max(1);
+    let step = line_bounds.width() / samples as f64;
+    let max_strokes = (spacing / tool_width).round().max(1.) as usize;
+
+    let mut toolpaths = vec![];
+    let mut y = line_bounds.min.y + spacing / 2.;
+    let mut reverse = false;
+    while y < line_bounds.max.y {
+        let strokes = (0..samples)
+            .map(|i| {
+                let p = from_lines
+                    .transform_point(point(line_bounds.min.x + (i as f64 + 0.5) * step, y));
+                let width = darkness(p).clamp(0., 1.) * spacing;
+                ((width / tool_width).round() as usize).min(max_strokes)
+            })
+            .collect::<Vec<_>>();
+
+        // Alternating above and below the middle, so the line widens evenly
+        for stroke in 0..max_strokes {
+            let side = if stroke % 2 == 0 { 1. } else { -1. };
+            let stroke_y = y + side * stroke.div_ceil(2) as f64 * tool_width;
+            let inside = crossings(&rotated, stroke_y);
+            let mut runs = vec![];
+            let mut start = None;
+            for (i, count) in strokes.iter().chain([&0]).enumerate() {
+                match (start, *count > stroke) {
+                    (None, true) => start = Some(i),
+                    (Some(from), false) => {
+                        let [from, to] = [from, i].map(|i| line_bounds.min.x + i as f64 * step);
+                        // Cut to the parts of the line inside the polygons
+                        runs.extend(inside.chunks_exact(2).filter_map(|pair| {
+                            let (from, to) = (from.max(pair[0]), to.min(pair[1]));
+                            (from < to).then(|| {
+                                [from, to].map(|x| from_lines.transform_point(point(x, stroke_y)))
+                            })
+                        }));
+                        start = None;
+                    }
+                    _ => {}
+                }
+            }
+            if reverse {
+                runs.reverse();
+                runs.iter_mut().for_each(|run| run.reverse());
+            }
+            toolpaths.extend(runs.iter().filter_map(|run| Toolpath::from_polyline(run)));
+            reverse = !reverse;
+        }
+        y += spacing;
+    }
+    toolpaths
+}
+
+/// A filled circle drawn from the outside in, without lifting the tool
+fn dot(center: Point<f64>, radius: f64, tool_width: f64) -> Toolpath {
+    let outermost = radius - tool_width / 2.;
+    if outermost <= 0. {
+        return Toolpath::from_polyline(&[center, center]).expect("a dot has points");
+    }
+    let mut toolpath = Toolpath::new(center + vector(outermost, 0.));
+    let mut ring = outermost;
+    while ring > 0. {
+        let from = center + vector(ring, 0.);
+        if toolpath.to() != from {
+            toolpath.segments.push(Segment::Line(LineSegment {
+                from: toolpath.to(),
+                to: from,
+            }));
+        }
+        let opposite = center - vector(ring, 0.);
+        for (from, to) in [(from, opposite), (opposite, from)] {
+            toolpath.segments.push(Segment::Arc(SvgArc {
+                from,
+                to,
+                radii: vector(ring, ring),
+                x_rotation: Angle::zero(),
+                flags: ArcFlags {
+                    large_arc: false,
+                    sweep: true,
+                },
+            }));
+        }
+        ring -= tool_width;
+    }
+    toolpath
+}
+
+/// Polygons rotated about the origin
+fn rotated(polygons: &[Vec<Point<f64>>], angle: Angle<f64>) -> Vec<Vec<Point<f64>>> {
+    let rotation = Rotation::new(angle);
+    polygons
+        .iter()
+        .map(|polygon| {
+            polygon
+                .iter()
+                .map(|p| rotation.transform_point(*p))
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rectangle(width: f64, height: f64) -> Vec<Vec<Point<f64>>> {
+        vec![vec![
+            point(0., 0.),
+            point(width, 0.),
+            point(width, height),
+            point(0., height),
+        ]]
+    }
+
+    #[test]
+    fn darker_areas_get_bigger_dots_and_wider_lines() {
+        let polygons = rectangle(4., 2.);
+        // Black on the left half and 25% gray on the right
+        let darkness = |p: Point<f64>| if p.x < 2. { 1. } else { 0.25 };
+
+        let dots = halftone_dots(&polygons, 1., Angle::zero(), 0.1, darkness);
+        assert_eq!(dots.len(), 8);
+        // The outermost ring is inset by half the tool width
+        let radius = |dot: &Toolpath, center_x: f64| dot.from.x - center_x + 0.05;
+        assert!((radius(&dots[3], 3.5) - (0.25 / PI).sqrt()).abs() < 1e-9);
+        // Black dots would be wider than their cell, so they are shrunk to the outline
+        assert!((radius(&dots[0], 0.5) - 0.5).abs() < 1e-9);
+        assert!(dots[0].segments.len() > dots[3].segments.len());
+
+        let lines = halftone_lines(&polygons, 1., Angle::zero(), 0.25, darkness);
+        assert_eq!(lines.len(), 2 * 4);
+        // The middle line is drawn across the whole width and the rest only on the black half
+        assert!((lines[0].length(0.1) - 4.).abs() < 1e-9);
+        assert!(lines[1..4]
+            .iter()
+            .all(|line| (line.length(0.1) - 2.).abs() < 1e-9));
+    }
+
+    #[test]
+    fn toolpaths_stay_inside_the_polygons() {
+        let polygons = rectangle(10., 7.);
+        for angle in [0., 30., 45.] {
+            let angle = Angle::degrees(angle);
+            let dots = halftone_dots(&polygons, 1.5, angle, 0.2, |_| 1.);
+            let lines = halftone_lines(&polygons, 1.5, angle, 0.2, |_| 1.);
+            assert!(!dots.is_empty() && !lines.is_empty());
+            for p in dots
+                .iter()
+                .chain(&lines)
+                .flat_map(|toolpath| toolpath.flattened(0.01))
+            {
+                assert!(
+                    (-1e-9..=10. + 1e-9).contains(&p.x) && (-1e-9..=7. + 1e-9).contains(&p.y),
+                    "{p:?} is outside the rectangle at {angle:?}"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,212 @@
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    vec::Vec,
+};
+
+use lyon_geom::Point;
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+use crate::{Segment, Toolpath};
+
+/// Clean up toolpaths from messy artwork (i.e. CAD exports and traced images), so vertices within
+/// `tolerance` of each other become one:
+///
+/// 1. Vertices are snapped onto the first vertex seen within `tolerance` of them
+/// 2. Segments that no longer go anywhere are removed, which also removes duplicate points
+/// 3. Open toolpaths that touch end to end are joined into one, drawing some of them in reverse
+///
+/// Toolpaths that only plunge in place (see [`Toolpath::is_dot`]) stay dots. Toolpaths that start
+/// with a pause are never joined onto another one.
+pub fn heal(toolpaths: Vec<Toolpath>, tolerance: f64) -> Vec<Toolpath> {
+    if tolerance <= 0. {
+        return toolpaths;
+    }
+    let mut snap = Snap::new(tolerance);
+    let toolpaths = toolpaths
+        .into_iter()
+        .map(|toolpath| snap.toolpath(toolpath))
+        .collect::<Vec<_>>();
+    join_touching(toolpaths)
+}
+
+/// Vertices seen so far, by the cell of a `tolerance` sized grid they fall into
+struct Snap {
+    tolerance: f64,
+    cells: BTreeMap<(i64, i64), Vec<Point<f64>>>,
+}
+
+impl Snap {
+    fn new(tolerance: f64) -> Self {
+        Self {
+            tolerance,
+            cells: BTreeMap::new(),
+        }
+    }
+
+    fn cell(&self, p: Point<f64>) -> (i64, i64) {
+        (
+            (p.x / self.tolerance).floor() as i64,
+            (p.y / self.tolerance).floor() as i64,
+        )
+    }
+
+    /// The first vertex seen within the tolerance of `p`, or `p` if there is none
+    fn point(&mut self, p: Point<f64>) -> Point<f64> {
+        let (x, y) = self.cell(p);
+        let seen = (x - 1..=x + 1)
+            .flat_map(|x| (y - 1..=y + 1).map(move |y| (x, y)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .find(|seen| (**seen - p).length() <= self.tolerance)
+            .copied();
+        seen.unwrap_or_else(|| {
+            self.cells.entry((x, y)).or_default().push(p);
+            p
+        })
+    }
+
+    fn toolpath(&mut self, mut toolpath: Toolpath) -> Toolpath {
+        let was_dot = toolpath.is_dot();
+        toolpath.from = self.point(toolpath.from);
+        let mut at = toolpath.from;
+        let mut segments = Vec::with_capacity(toolpath.segments.len());
+        for mut segment in core::mem::take(&mut toolpath.segments) {
+            let to = self.point(segment.to());
+            match &mut segment {
+                Segment::Line(line) => {
+                    line.from = at;
+                    line.to = to;
+                }
+                Segment::Arc(arc) => {
+                    arc.from = at;
+                    arc.to = to;
+                }
+                Segment::CubicBezier(cbs) => {
+                    cbs.from = at;
+                    cbs.to = to;
+                }
+                Segment::QuadraticBezier(qbs) => {
+                    qbs.from = at;
+                    qbs.to = to;
+                }
+            }
+            // A curve that comes back to where it started can still go somewhere in between
+            let goes_nowhere = to == at
+                && segment
+                    .flattened(self.tolerance)
+                    .iter()
+                    .all(|p| (*p - at).length() <= self.tolerance);
+            if !goes_nowhere || (was_dot && segments.is_empty()) {
+                segments.push(segment);
+            }
+            at = to;
+        }
+        toolpath.segments = segments;
+        toolpath
+    }
+}
+
+/// Join open toolpaths that end where another one starts or ends, in the place of the first of them
+fn join_touching(toolpaths: Vec<Toolpath>) -> Vec<Toolpath> {
+    let key = |p: Point<f64>| (p.x.to_bits(), p.y.to_bits());
+    let is_open = |toolpath: &Toolpath| !toolpath.segments.is_empty() && !toolpath.is_closed(0.);
+    let mut ends: BTreeMap<(u64, u64), BTreeSet<usize>> = BTreeMap::new();
+    for (i, toolpath) in toolpaths.iter().enumerate() {
+        if is_open(toolpath) {
+            ends.entry(key(toolpath.from)).or_default().insert(i);
+            ends.entry(key(toolpath.to())).or_default().insert(i);
+        }
+    }
+
+    let mut toolpaths = toolpaths.into_iter().map(Some).collect::<Vec<_>>();
+    let mut joined = Vec::with_capacity(toolpaths.len());
+    for i in 0..toolpaths.len() {
+        let Some(mut chain) = toolpaths[i].take() else {
+            continue;
+        };
+        if is_open(&chain) {
+            remove_ends(&mut ends, key(chain.from), key(chain.to()), i);
+            // Forwards from its end, then backwards from its start by joining onto its reverse
+            for _ in 0..2 {
+                while !chain.is_closed(0.) {
+                    let Some(next) = ends.get(&key(chain.to())).and_then(|touching| {
+                        touching
+                            .iter()
+                            .copied()
+                            .find(|j| !toolpaths[*j].as_ref().is_some_and(|next| next.pause))
+                    }) else {
+                        break;
+                    };
+                    let next_toolpath = toolpaths[next]
+                        .take()
+                        .expect("ends are of unjoined toolpaths");
+                    remove_ends(
+                        &mut ends,
+                        key(next_toolpath.from),
+                        key(next_toolpath.to()),
+                        next,
+                    );
+                    let next_toolpath = if next_toolpath.from == chain.to() {
+                        next_toolpath
+                    } else {
+                        next_toolpath.reversed()
+                    };
+                    chain.comments.extend(next_toolpath.comments);
+                    chain.segments.extend(next_toolpath.segments);
+                }
+                chain = chain.reversed();
+            }
+        }
+        joined.push(chain);
+    }
+    joined
+}
+
+fn remove_ends(
+    ends: &mut BTreeMap<(u64, u64), BTreeSet<usize>>,
+    from: (u64, u64),
+    to: (u64, u64),
+    i: usize,
+) {
+    for end in [from, to] {
+        if let Some(touching) = ends.get_mut(&end) {
+            touching.remove(&i);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use lyon_geom::{point, LineSegment};
+
+    use super::*;
+
+    #[test]
+    fn messy_toolpaths_are_healed() {
+        let first = Toolpath::from_polyline(&[
+            point(0., 0.),
+            point(10., 0.),
+            point(10., 0.),
+            point(10.05, 0.02),
+        ])
+        .unwrap();
+        // Drawn the other way
+        let second = Toolpath::from_polyline(&[point(10., 10.), point(9.98, 0.03)]).unwrap();
+        let apart = Toolpath::from_polyline(&[point(20., 20.), point(30., 30.)]).unwrap();
+        let mut dot = Toolpath::new(point(40., 40.));
+        dot.segments.push(Segment::Line(LineSegment {
+            from: point(40., 40.),
+            to: point(40., 40.),
+        }));
+
+        let healed = heal(vec![first, second, apart.clone(), dot.clone()], 0.1);
+        assert_eq!(healed.len(), 3);
+        assert_eq!(
+            healed[0].flattened(0.1),
+            [point(0., 0.), point(10., 0.), point(10., 10.)]
+        );
+        assert_eq!(healed[1], apart);
+        assert_eq!(healed[2], dot);
+    }
+}
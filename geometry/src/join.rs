@@ -0,0 +1,67 @@
+use alloc::vec::Vec;
+
+use lyon_geom::LineSegment;
+
+use crate::{Segment, Toolpath};
+
+/// Join toolpaths that start within `max_gap` of where the previous one ended, drawing through the
+/// gap instead of lifting the tool (i.e. to save pen lifts on densely hatched artwork).
+///
+/// Comments of joined toolpaths are kept with the toolpath they were joined onto. Toolpaths that
+/// start with a pause are never joined onto the previous one.
+pub fn join_nearby(toolpaths: Vec<Toolpath>, max_gap: f64) -> Vec<Toolpath> {
+    let mut joined: Vec<Toolpath> = Vec::with_capacity(toolpaths.len());
+    for toolpath in toolpaths {
+        match joined.last_mut() {
+            Some(previous)
+                if !toolpath.pause && (toolpath.from - previous.to()).length() <= max_gap =>
+            {
+                let to = previous.to();
+                if toolpath.from != to {
+                    previous.segments.push(Segment::Line(LineSegment {
+                        from: to,
+                        to: toolpath.from,
+                    }));
+                }
+                previous.comments.extend(toolpath.comments);
+                previous.segments.extend(toolpath.segments);
+            }
+            _ => joined.push(toolpath),
+        }
+    }
+    joined
+}
+
+#[cfg(test)]
+mod test {
+    use lyon_geom::point;
+
+    use super::*;
+
+    #[test]
+    fn toolpaths_are_joined_across_small_gaps() {
+        let mut first = Toolpath::from_polyline(&[point(0., 0.), point(10., 0.)]).unwrap();
+        first.comments.push("first".to_string());
+        let mut second = Toolpath::from_polyline(&[point(10.5, 0.), point(10.5, 10.)]).unwrap();
+        second.comments.push("second".to_string());
+        let far = Toolpath::from_polyline(&[point(20., 20.), point(30., 30.)]).unwrap();
+
+        let joined = join_nearby(vec![first, second, far.clone()], 1.);
+        assert_eq!(joined.len(), 2);
+        assert_eq!(
+            joined[0].flattened(0.1),
+            [
+                point(0., 0.),
+                point(10., 0.),
+                point(10.5, 0.),
+                point(10.5, 10.)
+            ]
+        );
+        assert_eq!(joined[0].comments, ["first", "second"]);
+        assert_eq!(joined[1], far);
+
+        let mut paused = Toolpath::from_polyline(&[point(30., 30.), point(40., 40.)]).unwrap();
+        paused.pause = true;
+        assert_eq!(join_nearby(vec![far, paused], 1.).len(), 2);
+    }
+}
@@ -0,0 +1,49 @@
+//! Geometry used by svg2gcode, independent of g-code.
+//!
+//! Drawing operations are described by a [`Turtle`], placed in a coordinate system by a [`Terrarium`],
+//! and can be recorded as [`Toolpath`]s for further processing.
+//!
+//! Only `alloc` is needed without the `std` feature, so simple files can be converted on an embedded
+//! controller. Floating point math then comes from [`libm`](https://crates.io/crates/libm).
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+/// Approximate [Bézier curves](https://en.wikipedia.org/wiki/B%C3%A9zier_curve) with [Circular arcs](https://en.wikipedia.org/wiki/Circular_arc)
+mod arc;
+/// Cut toolpaths down to a region
+mod clip;
+/// Infill patterns for the inside of closed shapes
+mod fill;
+/// Dots and lines that vary in size to reproduce shades of gray
+mod halftone;
+/// Clean up messy artwork by merging vertices that nearly coincide
+mod heal;
+/// Draw through small gaps between toolpaths
+mod join;
+/// Move outlines in or out, i.e. to make up for the width of the cut
+mod offset;
+/// Order toolpaths to cut down on travel between them
+mod order;
+/// Remove lines that are drawn over again
+mod overlap;
+/// Uncut gaps that keep parts attached to the stock, i.e. tabs, perforations, and dashes
+mod tabs;
+/// Intermediate representation of drawing operations
+mod toolpath;
+/// Provides an interface for drawing lines
+/// This concept is referred to as [Turtle graphics](https://en.wikipedia.org/wiki/Turtle_graphics).
+mod turtle;
+
+pub use arc::{ArcOrLineSegment, FlattenWithArcs, Transformed};
+pub use clip::clip_to_rect;
+pub use fill::{concentric, contains_even_odd, hatch, self_intersections, spiral, stipple};
+pub use halftone::{halftone_dots, halftone_lines};
+pub use heal::heal;
+pub use join::join_nearby;
+pub use offset::{offset, stroke_outline};
+pub use order::{improve_order, order_nearest, OrderImprovements};
+pub use overlap::remove_overlaps;
+pub use tabs::{add_tabs, dash, perforate};
+pub use toolpath::{Segment, SourceElement, Toolpath};
+pub use turtle::{DpiConvertingTurtle, PreprocessTurtle, Terrarium, ToolpathTurtle, Turtle};
@@ -0,0 +1,450 @@
+use alloc::{collections::BTreeMap, vec, vec::Vec};
+use core::f64::consts::{FRAC_PI_2, PI};
+
+use lyon_geom::{LineSegment, Point, Vector};
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+use crate::{contains_even_odd, Toolpath};
+
+/// Grow the inside of polygons by the even-odd rule by `distance`, or shrink it if negative, like
+/// the path of the center of a round tool that cuts along their outline.
+///
+/// Corners on the outside of the offset are rounded, and the parts of the offset that cross over
+/// each other or come closer to the outline than `distance` are left out, so it never crosses
+/// itself even where the polygons cross themselves, and narrow parts that are shrunk away leave
+/// nothing behind. Returns the offset outlines, which are flattened to lines.
+pub fn offset(polygons: &[Vec<Point<f64>>], distance: f64, tolerance: f64) -> Vec<Toolpath> {
+    let polygons = polygons
+        .iter()
+        .map(|polygon| without_repeats(polygon, tolerance * 1e-3))
+        .filter(|polygon| polygon.len() > 2)
+        .collect::<Vec<_>>();
+    if distance == 0. || !distance.is_finite() {
+        return polygons
+            .iter()
+            .filter_map(|polygon| Toolpath::from_polyline(&closed(polygon)))
+            .collect();
+    }
+    let grow = distance > 0.;
+    outline_at(&polygons, distance.abs(), tolerance, &[1., -1.], |middle| {
+        contains_even_odd(&polygons, middle) != grow
+    })
+}
+
+/// Outline of a stroke `width` wide along polylines, with round ends and corners, like the path of
+/// the center of a round tool that cuts the whole stroke out
+///
+/// Closed polylines, which end where they start, get an outline on either side, and open ones a
+/// single outline around them. Polylines that don't go anywhere are left out.
+pub fn stroke_outline(polylines: &[Vec<Point<f64>>], width: f64, tolerance: f64) -> Vec<Toolpath> {
+    let distance = width / 2.;
+    if distance <= 0. || !distance.is_finite() {
+        return vec![];
+    }
+    let (closed, open): (Vec<_>, Vec<_>) = polylines
+        .iter()
+        .filter(|polyline| polyline.len() > 1)
+        .partition(|polyline| polyline.len() > 2 && polyline.first() == polyline.last());
+    let closed = closed.into_iter().cloned().collect::<Vec<_>>();
+    let mut outline = offset(&closed, distance, tolerance);
+    outline.append(&mut offset(&closed, -distance, tolerance));
+
+    // Going along each open polyline and back again, one side of it is both sides of the stroke,
+    // turning around the ends in half circles
+    let there_and_back = open
+        .into_iter()
+        .map(|polyline| {
+            let mut points = polyline.clone();
+            points.dedup();
+            let back = points[1..points.len().saturating_sub(1)]
+                .iter()
+                .rev()
+                .copied()
+                .collect::<Vec<_>>();
+            points.extend(back);
+            points
+        })
+        .filter(|points| points.len() > 1)
+        .collect::<Vec<_>>();
+    outline.append(&mut outline_at(
+        &there_and_back,
+        distance,
+        tolerance,
+        &[1.],
+        |_| true,
+    ));
+    outline
+}
+
+/// The outlines `distance` away from `polygons` on the `sides` (1 for the left, -1 for the right)
+/// of their edges, keeping the parts that `keep` the middle of and that aren't closer to the
+/// polygons than `distance`
+fn outline_at(
+    polygons: &[Vec<Point<f64>>],
+    distance: f64,
+    tolerance: f64,
+    sides: &[f64],
+    keep: impl Fn(Point<f64>) -> bool,
+) -> Vec<Toolpath> {
+    // Chords of the rounded corners are at most the tolerance inside of their arcs
+    let step = (2. * (1. - (tolerance / distance).min(1.)).acos()).clamp(0.01, FRAC_PI_2);
+
+    // The outline moved to both sides of every edge, joined by arcs around both sides of every
+    // corner, so it doesn't matter which way the polygons go around
+    let mut raw = vec![];
+    for polygon in polygons {
+        let normals = polygon
+            .iter()
+            .zip(polygon.iter().cycle().skip(1))
+            .map(|(from, to)| {
+                let direction = (*to - *from).normalize();
+                Vector::new(-direction.y, direction.x) * distance
+            })
+            .collect::<Vec<_>>();
+        for (i, (from, normal)) in polygon.iter().zip(&normals).enumerate() {
+            let to = polygon[(i + 1) % polygon.len()];
+            let next = normals[(i + 1) % normals.len()];
+            let mut sweep = normal.angle_to(next).radians;
+            // Where the polygon doubles back on itself, a single side goes around the front of
+            // the end, turning right on the left side
+            if let [side] = sides {
+                if normal.dot(next) < 0. && normal.cross(next).abs() < distance * distance * 1e-9 {
+                    sweep = -PI * side;
+                }
+            }
+            let steps = (sweep.abs() / step).ceil() as usize;
+            for side in sides {
+                let (normal, next) = (*normal * *side, next * *side);
+                raw.push(LineSegment {
+                    from: *from + normal,
+                    to: to + normal,
+                });
+                let mut corner = to + normal;
+                for k in 1..=steps {
+                    let along = if k == steps {
+                        to + next
+                    } else {
+                        let (sin, cos) = (sweep * k as f64 / steps as f64).sin_cos();
+                        to + Vector::new(
+                            normal.x * cos - normal.y * sin,
+                            normal.x * sin + normal.y * cos,
+                        )
+                    };
+                    raw.push(LineSegment {
+                        from: corner,
+                        to: along,
+                    });
+                    corner = along;
+                }
+            }
+        }
+    }
+
+    let outline = polygons
+        .iter()
+        .flat_map(|polygon| {
+            polygon
+                .iter()
+                .zip(polygon.iter().cycle().skip(1))
+                .map(|(from, to)| LineSegment {
+                    from: *from,
+                    to: *to,
+                })
+        })
+        .collect::<Vec<_>>();
+    let snap = tolerance * 1e-3;
+    let kept = split_at_crossings(&raw)
+        .into_iter()
+        .filter(|piece| piece.length() > snap)
+        .filter(|piece| {
+            let middle = piece.sample(0.5);
+            keep(middle)
+                && outline.iter().all(|edge| {
+                    // Chords of rounded corners are closest to the corner, and come up to the
+                    // tolerance closer than `distance`. Only the overshoot of edges past where
+                    // they cross at the inside of a corner, and the corner's arc behind it, are
+                    // closer to the middle of an edge.
+                    let along = (middle - edge.from).dot(edge.to_vector())
+                        / edge.to_vector().square_length();
+                    let slack = if along > 0. && along < 1. {
+                        snap
+                    } else {
+                        tolerance * 1.5
+                    };
+                    edge.distance_to_point(middle) > distance - slack
+                })
+        })
+        .collect::<Vec<_>>();
+    chain(&kept, snap)
+        .iter()
+        .filter_map(|polyline| Toolpath::from_polyline(polyline))
+        .collect()
+}
+
+/// A polygon without points within `snap` of the one before them, including a last point that
+/// closes it, which would leave edges with no direction to offset them in
+fn without_repeats(polygon: &[Point<f64>], snap: f64) -> Vec<Point<f64>> {
+    let mut points = polygon.to_vec();
+    points.dedup_by(|point, before| (*point - *before).length() <= snap);
+    if points.len() > 1 && (points[0] - points[points.len() - 1]).length() <= snap {
+        points.pop();
+    }
+    points
+}
+
+fn closed(polygon: &[Point<f64>]) -> Vec<Point<f64>> {
+    let mut polyline = polygon.to_vec();
+    polyline.push(polygon[0]);
+    polyline
+}
+
+/// Split segments wherever they cross another one
+fn split_at_crossings(segments: &[LineSegment<f64>]) -> Vec<LineSegment<f64>> {
+    // Only segments that overlap along the x-axis can cross
+    let mut order = (0..segments.len()).collect::<Vec<_>>();
+    let min_x = |i: &usize| segments[*i].from.x.min(segments[*i].to.x);
+    order.sort_by(|a, b| min_x(a).total_cmp(&min_x(b)));
+    let mut splits = vec![vec![]; segments.len()];
+    for (n, i) in order.iter().enumerate() {
+        let max_x = segments[*i].from.x.max(segments[*i].to.x);
+        for j in order[n + 1..].iter().take_while(|j| min_x(j) <= max_x) {
+            if let Some((t, u)) = segments[*i].intersection_t(&segments[*j]) {
+                splits[*i].push(t);
+                splits[*j].push(u);
+            }
+        }
+    }
+    segments
+        .iter()
+        .zip(splits.iter_mut())
+        .flat_map(|(segment, splits)| {
+            splits.sort_by(f64::total_cmp);
+            let ts = core::iter::once(0.)
+                .chain(splits.iter().copied())
+                .chain(core::iter::once(1.))
+                .collect::<Vec<_>>();
+            ts.windows(2)
+                .map(|t| segment.split_range(t[0]..t[1]))
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Join segments that meet end to end into polylines, which are closed where they go all the way
+/// around, no matter which way each segment goes
+fn chain(segments: &[LineSegment<f64>], snap: f64) -> Vec<Vec<Point<f64>>> {
+    // Ends that are within `snap` of each other are the same point
+    let mut points: Vec<Point<f64>> = vec![];
+    let mut cells: BTreeMap<(i64, i64), Vec<usize>> = BTreeMap::new();
+    let mut point_index = |p: Point<f64>| {
+        let cell = ((p.x / snap).floor() as i64, (p.y / snap).floor() as i64);
+        for x in cell.0 - 1..=cell.0 + 1 {
+            for y in cell.1 - 1..=cell.1 + 1 {
+                if let Some(&i) = cells
+                    .get(&(x, y))
+                    .and_then(|near| near.iter().find(|i| (points[**i] - p).length() <= snap))
+                {
+                    return i;
+                }
+            }
+        }
+        points.push(p);
+        cells.entry(cell).or_default().push(points.len() - 1);
+        points.len() - 1
+    };
+    let ends = segments
+        .iter()
+        .map(|segment| (point_index(segment.from), point_index(segment.to)))
+        .collect::<Vec<_>>();
+    let mut at: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for (i, (from, to)) in ends.iter().enumerate() {
+        at.entry(*from).or_default().push(i);
+        at.entry(*to).or_default().push(i);
+    }
+
+    let mut used = vec![false; segments.len()];
+    let mut polylines = vec![];
+    for first in 0..segments.len() {
+        if used[first] {
+            continue;
+        }
+        used[first] = true;
+        let (start, mut end) = ends[first];
+        let mut polyline = vec![points[start], points[end]];
+        while end != start {
+            let Some(&next) = at[&end].iter().find(|i| !used[**i]) else {
+                break;
+            };
+            used[next] = true;
+            end = if ends[next].0 == end {
+                ends[next].1
+            } else {
+                ends[next].0
+            };
+            polyline.push(points[end]);
+        }
+        polylines.push(polyline);
+    }
+    polylines
+}
+
+#[cfg(test)]
+mod test {
+    use lyon_geom::point;
+
+    use super::*;
+
+    fn square() -> Vec<Vec<Point<f64>>> {
+        vec![vec![
+            point(0., 0.),
+            point(10., 0.),
+            point(10., 10.),
+            point(0., 10.),
+        ]]
+    }
+
+    fn bounds(toolpath: &Toolpath) -> [f64; 4] {
+        toolpath.flattened(0.01).iter().fold(
+            [
+                f64::INFINITY,
+                f64::INFINITY,
+                f64::NEG_INFINITY,
+                f64::NEG_INFINITY,
+            ],
+            |[x0, y0, x1, y1], p| [x0.min(p.x), y0.min(p.y), x1.max(p.x), y1.max(p.y)],
+        )
+    }
+
+    fn close_to(a: [f64; 4], b: [f64; 4]) -> bool {
+        a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-6)
+    }
+
+    #[test]
+    fn offset_grows_and_shrinks_closed_outlines() {
+        let grown = offset(&square(), 1., 0.01);
+        assert_eq!(grown.len(), 1);
+        assert!(grown[0].is_closed(1e-9));
+        assert!(close_to(bounds(&grown[0]), [-1., -1., 11., 11.]));
+        // Corners are rounded
+        assert!(!grown[0].flattened(0.01).contains(&point(-1., -1.)));
+
+        let shrunk = offset(&square(), -1., 0.01);
+        assert_eq!(shrunk.len(), 1);
+        assert!(close_to(bounds(&shrunk[0]), [1., 1., 9., 9.]));
+        assert!(shrunk[0].flattened(0.01).contains(&point(1., 1.)));
+
+        assert!(offset(&square(), -6., 0.01).is_empty());
+    }
+
+    #[test]
+    fn offset_ignores_closing_points_off_by_rounding() {
+        let mut polygons = square();
+        polygons[0].push(point(0., 1e-12));
+        let grown = offset(&polygons, 1., 0.01);
+        assert_eq!(grown.len(), 1);
+        assert!(close_to(bounds(&grown[0]), [-1., -1., 11., 11.]));
+    }
+
+    #[test]
+    fn offset_shrinks_holes() {
+        let mut frame = square();
+        frame.push(vec![
+            point(3., 3.),
+            point(7., 3.),
+            point(7., 7.),
+            point(3., 7.),
+        ]);
+        let mut grown = offset(&frame, 1., 0.01)
+            .iter()
+            .map(bounds)
+            .collect::<Vec<_>>();
+        grown.sort_by(|a, b| a[0].total_cmp(&b[0]));
+        assert_eq!(grown.len(), 2);
+        assert!(close_to(grown[0], [-1., -1., 11., 11.]));
+        assert!(close_to(grown[1], [4., 4., 6., 6.]));
+    }
+
+    #[test]
+    fn offset_trims_the_inside_of_curved_corners() {
+        // A hole flattened from a circle, which has a corner on the inside at every point
+        let tolerance = 0.05;
+        let steps = 40;
+        let mut frame = square();
+        frame.push(
+            (0..steps)
+                .map(|i| {
+                    let (sin, cos) = (2. * PI * i as f64 / steps as f64).sin_cos();
+                    point(5. + 4. * cos, 5. + 4. * sin)
+                })
+                .collect(),
+        );
+        let shrunk = offset(&frame, 1., tolerance);
+        assert_eq!(shrunk.len(), 2);
+        let hole = shrunk
+            .iter()
+            .find(|toolpath| bounds(toolpath)[0] > 0.)
+            .unwrap();
+        assert!(hole.is_closed(1e-9));
+        assert_eq!(self_crossings(hole), 0);
+        // Each edge of the hole is moved 1 in, so its corners stay on the circle it is inside of
+        let inner = 4. * (PI / steps as f64).cos() - 1.;
+        let outer = 4. - 1. / (PI / steps as f64).cos();
+        for p in hole.flattened(tolerance) {
+            let radius = (p - point(5., 5.)).length();
+            assert!(
+                radius > inner - 1e-9 && radius < outer + 1e-9,
+                "{p:?} {radius}"
+            );
+            assert!((radius - 3.).abs() < tolerance);
+        }
+    }
+
+    #[test]
+    fn offset_leaves_out_crossings() {
+        // A bow tie, which crosses itself in the middle
+        let bow_tie = vec![vec![
+            point(0., 0.),
+            point(10., 10.),
+            point(10., 0.),
+            point(0., 10.),
+        ]];
+        let grown = offset(&bow_tie, 0.5, 0.01);
+        assert_eq!(grown.len(), 1);
+        assert!(grown[0].is_closed(1e-9));
+        assert!(self_crossings(&grown[0]) == 0);
+        // Both halves shrink apart
+        assert_eq!(offset(&bow_tie, -0.5, 0.01).len(), 2);
+    }
+
+    #[test]
+    fn stroke_outline_goes_around_both_sides() {
+        let line = vec![point(0., 0.), point(10., 0.)];
+        let outline = stroke_outline(&[line], 2., 0.01);
+        assert_eq!(outline.len(), 1);
+        assert!(outline[0].is_closed(1e-9));
+        // With round ends
+        assert!(close_to(bounds(&outline[0]), [-1., -1., 11., 1.]));
+
+        let corner = vec![point(0., 0.), point(10., 0.), point(10., 10.)];
+        let outline = stroke_outline(&[corner], 2., 0.01);
+        assert_eq!(outline.len(), 1);
+        assert!(close_to(bounds(&outline[0]), [-1., -1., 11., 11.]));
+        assert_eq!(self_crossings(&outline[0]), 0);
+
+        let mut square = square().remove(0);
+        square.push(square[0]);
+        let mut outline = stroke_outline(&[square], 2., 0.01)
+            .iter()
+            .map(bounds)
+            .collect::<Vec<_>>();
+        outline.sort_by(|a, b| a[0].total_cmp(&b[0]));
+        assert_eq!(outline.len(), 2);
+        assert!(close_to(outline[0], [-1., -1., 11., 11.]));
+        assert!(close_to(outline[1], [1., 1., 9., 9.]));
+    }
+
+    fn self_crossings(toolpath: &Toolpath) -> usize {
+        crate::self_intersections(&toolpath.flattened(0.01)).len()
+    }
+}
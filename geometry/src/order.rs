@@ -0,0 +1,334 @@
+use alloc::{vec, vec::Vec};
+use core::cell::Cell;
+
+use lyon_geom::Point;
+use rstar::{primitives::GeomWithData, RTree};
+
+use crate::Toolpath;
+
+/// A point tagged with an id, for finding the nearest toolpaths quickly
+type End = GeomWithData<[f64; 2], usize>;
+
+fn end(point: Point<f64>, id: usize) -> End {
+    End::new(point.to_array(), id)
+}
+
+/// Moves tried by [`improve_order`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct OrderImprovements {
+    /// Longest run of toolpaths moved at once to between two other toolpaths, or 0 to not move any
+    pub max_run: usize,
+    /// Whether runs of toolpaths may be drawn in reverse
+    pub reverse_runs: bool,
+    /// Number of nearby toolpaths considered for each move
+    pub neighbors: usize,
+}
+
+impl Default for OrderImprovements {
+    fn default() -> Self {
+        Self {
+            max_run: 3,
+            reverse_runs: true,
+            neighbors: 8,
+        }
+    }
+}
+
+/// Draw each toolpath after the one that ends closest to it, starting from `start`
+///
+/// Open toolpaths are reversed when their end is closer, while closed toolpaths always start
+/// where they were drawn from.
+pub fn order_nearest(toolpaths: Vec<Toolpath>, start: Point<f64>) -> Vec<Toolpath> {
+    // Even ids are where toolpaths start and odd ids are where they end, if they can be reversed
+    let ends = toolpaths
+        .iter()
+        .enumerate()
+        .flat_map(|(i, toolpath)| {
+            let reversible = !toolpath.is_closed(0.);
+            [end(toolpath.from, 2 * i)]
+                .into_iter()
+                .chain(reversible.then(|| end(toolpath.to(), 2 * i + 1)))
+        })
+        .collect::<Vec<_>>();
+    let mut tree = RTree::bulk_load(ends);
+
+    let mut toolpaths = toolpaths.into_iter().map(Some).collect::<Vec<_>>();
+    let mut ordered = Vec::with_capacity(toolpaths.len());
+    let mut position = start;
+    while let Some(&End { data: id, .. }) = tree.nearest_neighbor(&position.to_array()) {
+        let i = id / 2;
+        let toolpath = toolpaths[i].take().expect("each toolpath is taken once");
+        tree.remove(&end(toolpath.from, 2 * i));
+        tree.remove(&end(toolpath.to(), 2 * i + 1));
+        let toolpath = if id % 2 == 1 {
+            toolpath.reversed()
+        } else {
+            toolpath
+        };
+        position = toolpath.to();
+        ordered.push(toolpath);
+    }
+    ordered
+}
+
+/// Shorten the travel between toolpaths by moving short runs of them to between two other
+/// toolpaths nearby ([Or-opt](https://en.wikipedia.org/wiki/Or-opt)), and by drawing runs of them
+/// in reverse ([2-opt](https://en.wikipedia.org/wiki/2-opt)), until no move helps or
+/// `out_of_time` returns true
+///
+/// Which of these moves are tried, and how widely, is set by `improvements`.
+///
+/// Reversing a run draws its open toolpaths backwards, while closed toolpaths still start where
+/// they were drawn from. This is much slower than [`order_nearest`], which it improves on by a
+/// few percent.
+pub fn improve_order(
+    toolpaths: Vec<Toolpath>,
+    start: Point<f64>,
+    improvements: &OrderImprovements,
+    mut out_of_time: impl FnMut() -> bool,
+) -> Vec<Toolpath> {
+    let count = toolpaths.len();
+    let ends_tree = RTree::bulk_load(
+        toolpaths
+            .iter()
+            .enumerate()
+            .map(|(i, toolpath)| end(toolpath.to(), i))
+            .collect(),
+    );
+    // Toolpaths that end near the start of each toolpath, which could come before it
+    let mut neighbors = Vec::with_capacity(count);
+    for (i, toolpath) in toolpaths.iter().enumerate() {
+        // Finding them takes a while for many toolpaths, which counts against the time too
+        if out_of_time() {
+            return toolpaths;
+        }
+        neighbors.push(
+            ends_tree
+                .nearest_neighbor_iter(&toolpath.from.to_array())
+                .map(|end| end.data)
+                .filter(|j| *j != i)
+                .take(improvements.neighbors)
+                .collect::<Vec<_>>(),
+        );
+    }
+    // Both ends of each toolpath, since either may be where it ends once reversed
+    let both_ends_tree = RTree::bulk_load(
+        toolpaths
+            .iter()
+            .enumerate()
+            .flat_map(|(i, toolpath)| [end(toolpath.from, 2 * i), end(toolpath.to(), 2 * i + 1)])
+            .collect(),
+    );
+
+    // Closed toolpaths start and end at the same point, so reversing them changes nothing here
+    let reversed = vec![Cell::new(false); count];
+    let from = |i: usize| {
+        if reversed[i].get() {
+            toolpaths[i].to()
+        } else {
+            toolpaths[i].from
+        }
+    };
+    let to = |i: usize| {
+        if reversed[i].get() {
+            toolpaths[i].from
+        } else {
+            toolpaths[i].to()
+        }
+    };
+    // Travel from a position to the start of a toolpath, or nothing if there is none after it
+    let gap = |position: Point<f64>, next: Option<usize>| {
+        next.map_or(0., |next| (from(next) - position).length())
+    };
+    let mut order = (0..count).collect::<Vec<_>>();
+    let mut positions = order.clone();
+
+    let mut improved = true;
+    'sweeps: while improved {
+        improved = false;
+        for run in 1..=improvements.max_run.min(count.saturating_sub(1)) {
+            for at in 0..=count - run {
+                if out_of_time() {
+                    break 'sweeps;
+                }
+                let (first, last) = (order[at], order[at + run - 1]);
+                let previous = at.checked_sub(1).map_or(start, |p| to(order[p]));
+                let next = order.get(at + run).copied();
+                let saved = gap(previous, Some(first)) + gap(to(last), next) - gap(previous, next);
+
+                // After one of the neighbors, or at the very beginning
+                let best = neighbors[first]
+                    .iter()
+                    .map(|neighbor| Some(positions[*neighbor]))
+                    .chain([None])
+                    .filter(|after| match after {
+                        // Not where it already is
+                        Some(after) => *after + 1 < at || *after >= at + run,
+                        None => at > 0,
+                    })
+                    .map(|after| {
+                        let before = after.map_or(start, |after| to(order[after]));
+                        let following = order.get(after.map_or(0, |after| after + 1)).copied();
+                        let added = gap(before, Some(first)) + gap(to(last), following)
+                            - gap(before, following);
+                        (added, after)
+                    })
+                    .min_by(|a, b| a.0.total_cmp(&b.0));
+
+                if let Some((_, after)) = best.filter(|(added, _)| saved - added > 1e-9) {
+                    let moved = order.drain(at..at + run).collect::<Vec<_>>();
+                    let insert_at = match after {
+                        Some(after) if after > at => after + 1 - run,
+                        Some(after) => after + 1,
+                        None => 0,
+                    };
+                    order.splice(insert_at..insert_at, moved);
+                    for position in at.min(insert_at)..at.max(insert_at) + run {
+                        positions[order[position]] = position;
+                    }
+                    improved = true;
+                }
+            }
+        }
+
+        // Reverse the run from here to a toolpath with an end near where the last one ended
+        if improvements.reverse_runs {
+            for at in 0..count {
+                if out_of_time() {
+                    break 'sweeps;
+                }
+                let previous = at.checked_sub(1).map_or(start, |p| to(order[p]));
+                let nearby = both_ends_tree
+                    .nearest_neighbor_iter(&previous.to_array())
+                    .map(|end| end.data)
+                    .take(improvements.neighbors);
+                for id in nearby {
+                    let end = positions[id / 2];
+                    if end < at {
+                        continue;
+                    }
+                    let (first, last) = (order[at], order[end]);
+                    let next = order.get(end + 1).copied();
+                    // Travel within the run stays the same, only its ends are joined up differently
+                    let saved = gap(previous, Some(first)) + gap(to(last), next)
+                        - (to(last) - previous).length()
+                        - gap(from(first), next);
+                    if saved > 1e-9 {
+                        order[at..=end].reverse();
+                        for (position, &i) in order.iter().enumerate().take(end + 1).skip(at) {
+                            reversed[i].set(!reversed[i].get());
+                            positions[i] = position;
+                        }
+                        improved = true;
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    let mut toolpaths = toolpaths.into_iter().map(Some).collect::<Vec<_>>();
+    order
+        .into_iter()
+        .map(|i| {
+            let toolpath = toolpaths[i].take().expect("each toolpath is ordered once");
+            if reversed[i].get() && !toolpath.is_closed(0.) {
+                toolpath.reversed()
+            } else {
+                toolpath
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use lyon_geom::point;
+
+    use super::*;
+
+    fn line(from: (f64, f64), to: (f64, f64)) -> Toolpath {
+        Toolpath::from_polyline(&[point(from.0, from.1), point(to.0, to.1)]).unwrap()
+    }
+
+    fn travel(toolpaths: &[Toolpath]) -> f64 {
+        toolpaths
+            .iter()
+            .scan(point(0., 0.), |position, toolpath| {
+                let travel = (toolpath.from - *position).length();
+                *position = toolpath.to();
+                Some(travel)
+            })
+            .sum()
+    }
+
+    #[test]
+    fn nearest_toolpath_is_drawn_next() {
+        let toolpaths = vec![
+            line((10., 0.), (11., 0.)),
+            line((3., 0.), (2., 0.)),
+            line((0., 1.), (0., 0.5)),
+        ];
+        let ordered = order_nearest(toolpaths, point(0., 0.));
+        // Open toolpaths are drawn from whichever end is closer
+        assert_eq!(ordered[0], line((0., 0.5), (0., 1.)));
+        assert_eq!(ordered[1], line((2., 0.), (3., 0.)));
+        assert_eq!(ordered[2], line((10., 0.), (11., 0.)));
+        assert!(order_nearest(vec![], point(0., 0.)).is_empty());
+    }
+
+    #[test]
+    fn improved_order_travels_less() {
+        let dot = |x: f64| line((x, 0.), (x, 0.));
+        // Nearest first goes right and has to come back for the dot on the left
+        let nearest = order_nearest(vec![dot(1.), dot(-1.5), dot(3.)], point(0., 0.));
+        assert_eq!(travel(&nearest), 1. + 2. + 4.5);
+        let improved = improve_order(
+            nearest,
+            point(0., 0.),
+            &OrderImprovements::default(),
+            || false,
+        );
+        assert_eq!(travel(&improved), 1.5 + 2.5 + 2.);
+        assert_eq!(improved[0], dot(-1.5));
+    }
+
+    #[test]
+    fn improved_order_draws_runs_in_reverse() {
+        let toolpaths = vec![line((2., 0.), (1., 0.)), line((4., 0.), (3., 0.))];
+        assert_eq!(travel(&toolpaths), 2. + 3.);
+        let improved = improve_order(
+            toolpaths,
+            point(0., 0.),
+            &OrderImprovements::default(),
+            || false,
+        );
+        assert_eq!(
+            improved,
+            [line((1., 0.), (2., 0.)), line((3., 0.), (4., 0.))]
+        );
+    }
+
+    #[test]
+    fn improvements_can_be_turned_off() {
+        let toolpaths = vec![line((2., 0.), (1., 0.)), line((4., 0.), (3., 0.))];
+        let no_reversing = OrderImprovements {
+            reverse_runs: false,
+            ..Default::default()
+        };
+        let improved = improve_order(toolpaths.clone(), point(0., 0.), &no_reversing, || false);
+        assert_eq!(improved, toolpaths);
+
+        let dot = |x: f64| line((x, 0.), (x, 0.));
+        let nearest = vec![dot(1.), dot(-1.5), dot(3.)];
+        let no_moving = OrderImprovements {
+            max_run: 0,
+            reverse_runs: false,
+            ..Default::default()
+        };
+        let improved = improve_order(nearest.clone(), point(0., 0.), &no_moving, || false);
+        assert_eq!(improved, nearest);
+    }
+}
@@ -0,0 +1,208 @@
+use alloc::{vec, vec::Vec};
+
+use lyon_geom::{LineSegment, Point};
+use rstar::{primitives::Line, RTree, AABB};
+
+use crate::{Segment, Toolpath};
+
+/// Remove the parts of lines that an earlier line already covers, to within `tolerance`, so edges
+/// that shapes share (i.e. the borders of rectangles next to each other in a CAD export) are only
+/// cut once
+///
+/// Toolpaths are split where lines are removed from them, and closed toolpaths are opened up
+/// there. Curves are left as they are. Toolpaths that are removed entirely pass their pause on to
+/// the next one, so stages of the job stay apart; if there is none, an empty toolpath keeps it.
+pub fn remove_overlaps(toolpaths: Vec<Toolpath>, tolerance: f64) -> Vec<Toolpath> {
+    if tolerance <= 0. {
+        return toolpaths;
+    }
+    // Only lines near each other can overlap, so the ones seen so far are looked up by their bounds
+    let mut kept = RTree::new();
+    let mut pause = false;
+    let mut last_removed = None;
+    let mut remaining = Vec::with_capacity(toolpaths.len());
+    for toolpath in toolpaths {
+        let mut pieces: Vec<Toolpath> = vec![];
+        let mut at = None;
+        for segment in &toolpath.segments {
+            let lines = match segment {
+                Segment::Line(line) => {
+                    let uncovered = uncovered(line, &kept, tolerance);
+                    kept.insert(Line::new(line.from.to_array(), line.to.to_array()));
+                    uncovered
+                }
+                _ => vec![segment.clone()],
+            };
+            for line in lines {
+                if at != Some(line.from()) {
+                    let mut piece = Toolpath::new(line.from());
+                    piece.source = toolpath.source.clone();
+                    pieces.push(piece);
+                }
+                at = Some(line.to());
+                pieces.last_mut().unwrap().segments.push(line);
+            }
+        }
+
+        // A closed toolpath that was cut open only once is still one piece, going around from
+        // the cut
+        let rejoins = toolpath.is_closed(0.)
+            && pieces.len() > 1
+            && pieces[0].from == toolpath.from
+            && pieces.last().map(Toolpath::to) == Some(toolpath.from);
+        if rejoins {
+            let first = pieces.remove(0);
+            pieces.last_mut().unwrap().segments.extend(first.segments);
+        }
+
+        pause |= toolpath.pause;
+        if let Some(first) = pieces.first_mut() {
+            first.comments = toolpath.comments;
+            first.pause = core::mem::take(&mut pause);
+        } else {
+            last_removed = Some(toolpath);
+        }
+        remaining.append(&mut pieces);
+    }
+    if let Some(removed) = last_removed.filter(|_| pause) {
+        remaining.push(Toolpath {
+            comments: removed.comments,
+            source: removed.source,
+            pause,
+            ..Toolpath::new(removed.from)
+        });
+    }
+    remaining
+}
+
+/// The parts of `line` that none of `kept` cover, as lines that are longer than `tolerance`
+fn uncovered(
+    line: &LineSegment<f64>,
+    kept: &RTree<Line<[f64; 2]>>,
+    tolerance: f64,
+) -> Vec<Segment> {
+    let length = line.length();
+    if length <= tolerance {
+        return vec![Segment::Line(*line)];
+    }
+    let direction = (line.to - line.from) / length;
+    let along = |p: Point<f64>| direction.dot(p - line.from);
+    let beside = |p: Point<f64>| direction.cross(p - line.from).abs();
+
+    let bounds = line.bounding_box().inflate(tolerance, tolerance);
+    let mut covered = kept
+        .locate_in_envelope_intersecting(&AABB::from_corners(
+            bounds.min.to_array(),
+            bounds.max.to_array(),
+        ))
+        .map(|other| LineSegment {
+            from: Point::from(other.from),
+            to: Point::from(other.to),
+        })
+        .filter(|other| beside(other.from) <= tolerance && beside(other.to) <= tolerance)
+        .filter_map(|other| {
+            let (start, end) = (along(other.from), along(other.to));
+            let (start, end) = (start.min(end).max(0.), start.max(end).min(length));
+            (end - start > tolerance).then_some((start, end))
+        })
+        .collect::<Vec<_>>();
+    if covered.is_empty() {
+        return vec![Segment::Line(*line)];
+    }
+    covered.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let point_at = |t: f64| {
+        if t <= tolerance {
+            line.from
+        } else if t >= length - tolerance {
+            line.to
+        } else {
+            line.from + direction * t
+        }
+    };
+    let mut uncovered = vec![];
+    let mut start = 0.;
+    for (covered_start, covered_end) in covered {
+        if covered_start - start > tolerance {
+            uncovered.push(Segment::Line(LineSegment {
+                from: point_at(start),
+                to: point_at(covered_start),
+            }));
+        }
+        start = start.max(covered_end);
+    }
+    if length - start > tolerance {
+        uncovered.push(Segment::Line(LineSegment {
+            from: point_at(start),
+            to: line.to,
+        }));
+    }
+    uncovered
+}
+
+#[cfg(test)]
+mod test {
+    use lyon_geom::point;
+
+    use super::*;
+
+    #[test]
+    fn shared_edges_are_cut_once() {
+        let square = |x: f64| {
+            Toolpath::from_polyline(&[
+                point(x, 0.),
+                point(x + 10., 0.),
+                point(x + 10., 10.),
+                point(x, 10.),
+                point(x, 0.),
+            ])
+            .unwrap()
+        };
+        // Shares its left edge with the right edge of the first, a little off, in the middle
+        let second = Toolpath::from_polyline(&[
+            point(20., 0.),
+            point(20., 10.),
+            point(10.01, 10.),
+            point(10.01, 0.),
+            point(20., 0.),
+        ])
+        .unwrap();
+        let mut third = Toolpath::from_polyline(&[point(10., 2.), point(10., 8.)]).unwrap();
+        third.pause = true;
+        let apart = Toolpath::from_polyline(&[point(20., 5.), point(30., 5.)]).unwrap();
+        let last = Toolpath {
+            pause: true,
+            ..square(0.)
+        };
+
+        let remaining = remove_overlaps(vec![square(0.), second, third, apart.clone(), last], 0.05);
+        assert_eq!(remaining.len(), 4);
+        assert_eq!(remaining[0], square(0.));
+        // Opened up where the shared edge was, going around from there
+        assert_eq!(
+            remaining[1].flattened(0.1),
+            [
+                point(10.01, 0.),
+                point(20., 0.),
+                point(20., 10.),
+                point(10.01, 10.)
+            ]
+        );
+        // Removed entirely, passing on its pause
+        assert_eq!(
+            remaining[2],
+            Toolpath {
+                pause: true,
+                ..apart
+            }
+        );
+        // Removed entirely at the end, keeping its pause in an empty toolpath
+        assert_eq!(
+            remaining[3],
+            Toolpath {
+                pause: true,
+                ..Toolpath::new(point(0., 0.))
+            }
+        );
+    }
+}
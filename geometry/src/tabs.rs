@@ -0,0 +1,272 @@
+use alloc::{vec, vec::Vec};
+
+use lyon_geom::Point;
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+use crate::Toolpath;
+
+/// Leave `count` evenly spaced, uncut gaps of `width` along a closed toolpath so the part stays attached.
+///
+/// Returns the pieces to cut, which are flattened to lines. The toolpath is returned as-is if it is open
+/// or too short to fit the tabs.
+pub fn add_tabs(toolpath: &Toolpath, count: usize, width: f64, tolerance: f64) -> Vec<Toolpath> {
+    if count == 0 || width <= 0. || !toolpath.is_closed(tolerance) {
+        return vec![toolpath.clone()];
+    }
+
+    let mut points = toolpath.flattened(tolerance);
+    let mut distances = cumulative_distances(&points);
+    let length = *distances.last().unwrap();
+
+    // Require at least as much cutting as there is tab
+    if length < 2. * width * count as f64 {
+        return vec![toolpath.clone()];
+    }
+
+    // Go around twice so pieces can continue past the end of the path
+    points.extend_from_within(1..);
+    distances.extend(
+        distances[1..]
+            .iter()
+            .map(|distance| distance + length)
+            .collect::<Vec<_>>(),
+    );
+
+    // Tabs are centered between evenly spaced points so none of them start at the beginning of the path
+    let spacing = length / count as f64;
+    let tab_start = |i: usize| (i as f64 + 0.5) * spacing - width / 2.;
+
+    let mut pieces = (0..count)
+        .map(|i| {
+            let polyline =
+                polyline_between(&points, &distances, tab_start(i) + width, tab_start(i + 1));
+            Toolpath::from_polyline(&polyline).expect("piece is not empty")
+        })
+        .collect::<Vec<_>>();
+    if let Some(first) = pieces.first_mut() {
+        first.comments = toolpath.comments.clone();
+        first.pause = toolpath.pause;
+    }
+    pieces
+        .iter_mut()
+        .for_each(|piece| piece.source = toolpath.source.clone());
+    pieces
+}
+
+/// Turn a closed toolpath into a perforated cut, alternating between cutting `cut` and skipping
+/// `gap` so the part stays attached until it is popped out.
+///
+/// The lengths are stretched a little so the pattern fits evenly around the path. Returns the
+/// pieces to cut, which are flattened to lines. The toolpath is returned as-is if it is open or
+/// shorter than one cut and gap.
+pub fn perforate(toolpath: &Toolpath, cut: f64, gap: f64, tolerance: f64) -> Vec<Toolpath> {
+    if cut <= 0. || gap <= 0. || !toolpath.is_closed(tolerance) {
+        return vec![toolpath.clone()];
+    }
+
+    let points = toolpath.flattened(tolerance);
+    let distances = cumulative_distances(&points);
+    let length = *distances.last().unwrap();
+    let count = (length / (cut + gap)).round();
+    if count < 1. {
+        return vec![toolpath.clone()];
+    }
+    let period = length / count;
+    let cut = period * cut / (cut + gap);
+
+    let mut pieces = (0..count as usize)
+        .map(|i| {
+            let start = i as f64 * period;
+            let polyline = polyline_between(&points, &distances, start, start + cut);
+            Toolpath::from_polyline(&polyline).expect("piece is not empty")
+        })
+        .collect::<Vec<_>>();
+    if let Some(first) = pieces.first_mut() {
+        first.comments = toolpath.comments.clone();
+        first.pause = toolpath.pause;
+    }
+    pieces
+        .iter_mut()
+        .for_each(|piece| piece.source = toolpath.source.clone());
+    pieces
+}
+
+/// Break a toolpath into dashes like an SVG `stroke-dasharray`, drawing and skipping the lengths
+/// of `pattern` in turn from `offset` along the pattern.
+///
+/// A pattern with an odd number of lengths is repeated to make it even. Unlike perforations, the
+/// pattern isn't stretched to fit, and open toolpaths are dashed too. Returns the dashes, which are
+/// flattened to lines. The toolpath is returned as-is if the pattern is empty, has a negative
+/// length, or adds up to nothing.
+pub fn dash(toolpath: &Toolpath, pattern: &[f64], offset: f64, tolerance: f64) -> Vec<Toolpath> {
+    let period = pattern.iter().sum::<f64>() * if pattern.len() % 2 == 1 { 2. } else { 1. };
+    if toolpath.segments.is_empty()
+        || pattern.iter().any(|length| *length < 0.)
+        || period <= 0.
+        || !period.is_finite()
+    {
+        return vec![toolpath.clone()];
+    }
+
+    let points = toolpath.flattened(tolerance);
+    let distances = cumulative_distances(&points);
+    let length = *distances.last().unwrap();
+
+    let mut pieces = vec![];
+    // Same as `offset.rem_euclid(period)`, which needs std
+    let mut start = period * (offset / period).floor() - offset;
+    for (i, dash_length) in pattern.iter().cycle().enumerate() {
+        if start >= length {
+            break;
+        }
+        let end = start + dash_length;
+        // Zero length dashes would only be drawn with round or square caps
+        if i % 2 == 0 && end > 0. && end > start {
+            let polyline = polyline_between(&points, &distances, start.max(0.), end.min(length));
+            pieces.push(Toolpath::from_polyline(&polyline).expect("piece is not empty"));
+        }
+        start = end;
+    }
+    if let Some(first) = pieces.first_mut() {
+        first.comments = toolpath.comments.clone();
+        first.pause = toolpath.pause;
+    }
+    pieces
+        .iter_mut()
+        .for_each(|piece| piece.source = toolpath.source.clone());
+    pieces
+}
+
+/// Distance along a polyline to each of its points
+fn cumulative_distances(points: &[Point<f64>]) -> Vec<f64> {
+    core::iter::once(0.)
+        .chain(
+            points
+                .iter()
+                .zip(points.iter().skip(1))
+                .scan(0., |distance, (from, to)| {
+                    *distance += (*to - *from).length();
+                    Some(*distance)
+                }),
+        )
+        .collect()
+}
+
+/// Part of a polyline between two distances along it
+fn polyline_between(
+    points: &[Point<f64>],
+    distances: &[f64],
+    start: f64,
+    end: f64,
+) -> Vec<Point<f64>> {
+    // Index of the first point further than the distance, and the point at the distance
+    let at = |distance: f64| {
+        let i = distances
+            .partition_point(|d| *d <= distance)
+            .clamp(1, points.len() - 1);
+        let segment_length = distances[i] - distances[i - 1];
+        let t = if segment_length > 0. {
+            ((distance - distances[i - 1]) / segment_length).clamp(0., 1.)
+        } else {
+            0.
+        };
+        (i, points[i - 1].lerp(points[i], t))
+    };
+
+    let (start_index, start_point) = at(start);
+    let (end_index, end_point) = at(end);
+    let mut polyline = vec![start_point];
+    polyline.extend_from_slice(&points[start_index..end_index]);
+    polyline.push(end_point);
+    polyline
+}
+
+#[cfg(test)]
+mod test {
+    use lyon_geom::point;
+
+    use super::*;
+
+    fn length(toolpath: &Toolpath) -> f64 {
+        let points = toolpath.flattened(0.01);
+        points
+            .iter()
+            .zip(points.iter().skip(1))
+            .map(|(from, to)| (*to - *from).length())
+            .sum()
+    }
+
+    fn square() -> Toolpath {
+        Toolpath::from_polyline(&[
+            point(0., 0.),
+            point(10., 0.),
+            point(10., 10.),
+            point(0., 10.),
+            point(0., 0.),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn tabs_leave_gaps_on_closed_paths() {
+        let pieces = add_tabs(&square(), 4, 1., 0.01);
+        assert_eq!(pieces.len(), 4);
+        assert!(pieces.iter().all(|piece| (length(piece) - 9.).abs() < 1e-9));
+        // The first tab is centered on the bottom edge
+        assert_eq!(pieces.last().unwrap().to(), point(4.5, 0.));
+        assert_eq!(pieces.first().unwrap().from, point(5.5, 0.));
+    }
+
+    #[test]
+    fn tabs_skip_open_and_short_paths() {
+        let mut open = square();
+        open.segments.pop();
+        assert_eq!(add_tabs(&open, 4, 1., 0.01), vec![open]);
+        assert_eq!(add_tabs(&square(), 4, 6., 0.01), vec![square()]);
+    }
+
+    #[test]
+    fn perforation_alternates_cuts_and_gaps() {
+        // 40mm around, so 3mm cuts and 1.5mm gaps are stretched to fit 9 of each
+        let pieces = perforate(&square(), 3., 1.5, 0.01);
+        assert_eq!(pieces.len(), 9);
+        let period = 40. / 9.;
+        assert!(pieces
+            .iter()
+            .all(|piece| (length(piece) - period * 2. / 3.).abs() < 1e-9));
+        assert_eq!(pieces[0].from, point(0., 0.));
+        assert!((pieces[1].from - point(period, 0.)).length() < 1e-9);
+
+        let mut open = square();
+        open.segments.pop();
+        assert_eq!(perforate(&open, 3., 1.5, 0.01), vec![open]);
+        assert_eq!(perforate(&square(), 50., 50., 0.01), vec![square()]);
+    }
+
+    #[test]
+    fn dashes_follow_the_pattern_from_the_offset() {
+        let line = Toolpath::from_polyline(&[point(0., 0.), point(10., 0.)]).unwrap();
+        let ends = |pieces: Vec<Toolpath>| {
+            pieces
+                .iter()
+                .map(|piece| (piece.from.x, piece.to().x))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            ends(dash(&line, &[3., 1.], 0., 0.01)),
+            [(0., 3.), (4., 7.), (8., 10.)]
+        );
+        // Odd patterns are repeated, so the first 2 is a gap the second time around
+        assert_eq!(
+            ends(dash(&line, &[2., 1., 2.], 0., 0.01)),
+            [(0., 2.), (3., 5.), (7., 8.)]
+        );
+        assert_eq!(
+            ends(dash(&line, &[3., 1.], 2., 0.01)),
+            [(0., 1.), (2., 5.), (6., 9.)]
+        );
+        assert_eq!(dash(&line, &[0., 0.], 0., 0.01), vec![line.clone()]);
+        assert_eq!(dash(&line, &[1., -1.], 0., 0.01), vec![line]);
+    }
+}
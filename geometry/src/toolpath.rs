@@ -0,0 +1,383 @@
+use alloc::{collections::BTreeMap, string::String, vec, vec::Vec};
+
+use lyon_geom::{
+    ArcFlags, CubicBezierSegment, LineSegment, Point, QuadraticBezierSegment, SvgArc, Transform,
+    Vector,
+};
+
+use crate::{Transformed, Turtle};
+
+/// A drawing operation that continues from the end of the previous one
+#[derive(Debug, Clone, PartialEq)]
+pub enum Segment {
+    Line(LineSegment<f64>),
+    Arc(SvgArc<f64>),
+    CubicBezier(CubicBezierSegment<f64>),
+    QuadraticBezier(QuadraticBezierSegment<f64>),
+}
+
+impl Segment {
+    pub fn from(&self) -> Point<f64> {
+        match self {
+            Self::Line(line) => line.from,
+            Self::Arc(arc) => arc.from,
+            Self::CubicBezier(cbs) => cbs.from,
+            Self::QuadraticBezier(qbs) => qbs.from,
+        }
+    }
+
+    pub fn to(&self) -> Point<f64> {
+        match self {
+            Self::Line(line) => line.to,
+            Self::Arc(arc) => arc.to,
+            Self::CubicBezier(cbs) => cbs.to,
+            Self::QuadraticBezier(qbs) => qbs.to,
+        }
+    }
+
+    /// Approximate with points no further than `tolerance` from the segment, excluding [`Self::from`]
+    pub fn flattened(&self, tolerance: f64) -> Vec<Point<f64>> {
+        match self {
+            Self::Line(line) => vec![line.to],
+            Self::Arc(arc) if arc.is_straight_line() => vec![arc.to],
+            Self::Arc(arc) => arc.to_arc().flattened(tolerance).collect(),
+            Self::CubicBezier(cbs) => cbs.flattened(tolerance).collect(),
+            Self::QuadraticBezier(qbs) => qbs.flattened(tolerance).collect(),
+        }
+    }
+}
+
+/// Describes the SVG element that a [`Toolpath`] was drawn from
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SourceElement {
+    pub tag_name: String,
+    pub id: Option<String>,
+    /// Attributes of the element by local name, plus any inheritable presentation attributes
+    /// (i.e. `stroke`, `fill`) set on its ancestors, as attributes or in their `style`
+    pub attributes: BTreeMap<String, String>,
+    /// Name of the layer the element is in, if any (i.e. `inkscape:label` of an Inkscape layer)
+    pub layer: Option<String>,
+}
+
+impl SourceElement {
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.get(name).map(String::as_str)
+    }
+}
+
+/// A continuous stroke in machine units (millimeters): a rapid move to [`Toolpath::from`]
+/// followed by drawing each of the [`Toolpath::segments`].
+///
+/// This is the intermediate representation between SVG conversion and g-code emission.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Toolpath {
+    /// Comments to emit before moving to the start of this toolpath (i.e. the SVG node name)
+    pub comments: Vec<String>,
+    /// The element this was drawn from, if any
+    pub source: Option<SourceElement>,
+    /// Stop before drawing this toolpath until the operator resumes (i.e. to swap material)
+    pub pause: bool,
+    /// Whether this closed toolpath is a hole in a part, going around inside an odd number of
+    /// other closed toolpaths
+    ///
+    /// Worked out once over all of the toolpaths of a drawing, since they may be cut apart from
+    /// each other later (i.e. in layers).
+    pub hole: bool,
+    pub from: Point<f64>,
+    pub segments: Vec<Segment>,
+}
+
+impl Toolpath {
+    pub fn new(from: Point<f64>) -> Self {
+        Self {
+            comments: vec![],
+            source: None,
+            pause: false,
+            hole: false,
+            from,
+            segments: vec![],
+        }
+    }
+
+    /// Where the toolpath ends
+    pub fn to(&self) -> Point<f64> {
+        self.segments.last().map(Segment::to).unwrap_or(self.from)
+    }
+
+    /// Whether the toolpath ends within `tolerance` of where it started
+    pub fn is_closed(&self, tolerance: f64) -> bool {
+        !self.segments.is_empty() && (self.to() - self.from).length() <= tolerance
+    }
+
+    /// Draw a line back to where the toolpath started if it ends within `tolerance` of it, but
+    /// not right there (i.e. in a CAD export), returning whether it did
+    pub fn close(&mut self, tolerance: f64) -> bool {
+        let to = self.to();
+        if to == self.from || !self.is_closed(tolerance) {
+            return false;
+        }
+        self.segments.push(Segment::Line(LineSegment {
+            from: to,
+            to: self.from,
+        }));
+        true
+    }
+
+    /// Whether the toolpath plunges in one place without moving (i.e. a stipple dot)
+    pub fn is_dot(&self) -> bool {
+        !self.segments.is_empty()
+            && self
+                .segments
+                .iter()
+                .all(|segment| segment.from() == self.from && segment.to() == self.from)
+    }
+
+    /// Approximate with a polyline, starting at [`Self::from`]
+    pub fn flattened(&self, tolerance: f64) -> Vec<Point<f64>> {
+        core::iter::once(self.from)
+            .chain(
+                self.segments
+                    .iter()
+                    .flat_map(|segment| segment.flattened(tolerance)),
+            )
+            .collect()
+    }
+
+    /// Approximate length of the toolpath
+    pub fn length(&self, tolerance: f64) -> f64 {
+        let points = self.flattened(tolerance);
+        points
+            .iter()
+            .zip(points.iter().skip(1))
+            .map(|(from, to)| (*to - *from).length())
+            .sum()
+    }
+
+    /// The point at a distance along the toolpath and the unit tangent there, or [`None`] if the
+    /// distance is off the toolpath
+    pub fn point_at_length(
+        &self,
+        distance: f64,
+        tolerance: f64,
+    ) -> Option<(Point<f64>, Vector<f64>)> {
+        if distance < 0. {
+            return None;
+        }
+        let points = self.flattened(tolerance);
+        let mut remaining = distance;
+        for (from, to) in points.iter().zip(points.iter().skip(1)) {
+            let segment = *to - *from;
+            let segment_length = segment.length();
+            if segment_length > 0. && remaining <= segment_length {
+                return Some((
+                    from.lerp(*to, remaining / segment_length),
+                    segment / segment_length,
+                ));
+            }
+            remaining -= segment_length;
+        }
+        None
+    }
+
+    /// Lay out glyphs with the given advances along the toolpath, starting at `start_offset`
+    ///
+    /// Each glyph is centered on the point at the middle of its advance and rotated to the tangent
+    /// there. Glyphs whose middle is off the toolpath are not drawn, so they are [`None`].
+    ///
+    /// <https://www.w3.org/TR/SVG11/text.html#TextpathLayoutRules>
+    pub fn place_along(
+        &self,
+        start_offset: f64,
+        advances: &[f64],
+        tolerance: f64,
+    ) -> Vec<Option<(Point<f64>, Vector<f64>)>> {
+        advances
+            .iter()
+            .scan(start_offset, |offset, advance| {
+                let middle = *offset + advance / 2.;
+                *offset += advance;
+                Some(self.point_at_length(middle, tolerance))
+            })
+            .collect()
+    }
+
+    /// Build a toolpath of lines through each of the points
+    pub fn from_polyline(points: &[Point<f64>]) -> Option<Self> {
+        let (first, rest) = points.split_first()?;
+        let mut toolpath = Self::new(*first);
+        let mut from = *first;
+        for to in rest {
+            toolpath
+                .segments
+                .push(Segment::Line(LineSegment { from, to: *to }));
+            from = *to;
+        }
+        Some(toolpath)
+    }
+
+    /// Move every point of the toolpath by the same amount
+    pub fn translated(&self, by: Vector<f64>) -> Self {
+        Self {
+            from: self.from + by,
+            segments: self
+                .segments
+                .iter()
+                .map(|segment| match segment {
+                    Segment::Line(line) => Segment::Line(LineSegment {
+                        from: line.from + by,
+                        to: line.to + by,
+                    }),
+                    Segment::Arc(arc) => Segment::Arc(SvgArc {
+                        from: arc.from + by,
+                        to: arc.to + by,
+                        ..*arc
+                    }),
+                    Segment::CubicBezier(cbs) => Segment::CubicBezier(CubicBezierSegment {
+                        from: cbs.from + by,
+                        ctrl1: cbs.ctrl1 + by,
+                        ctrl2: cbs.ctrl2 + by,
+                        to: cbs.to + by,
+                    }),
+                    Segment::QuadraticBezier(qbs) => {
+                        Segment::QuadraticBezier(QuadraticBezierSegment {
+                            from: qbs.from + by,
+                            ctrl: qbs.ctrl + by,
+                            to: qbs.to + by,
+                        })
+                    }
+                })
+                .collect(),
+            ..self.clone()
+        }
+    }
+
+    /// The same toolpath drawn from the other end
+    pub fn reversed(&self) -> Self {
+        Self {
+            from: self.to(),
+            segments: self
+                .segments
+                .iter()
+                .rev()
+                .map(|segment| match segment {
+                    Segment::Line(line) => Segment::Line(line.flip()),
+                    Segment::Arc(arc) => Segment::Arc(SvgArc {
+                        from: arc.to,
+                        to: arc.from,
+                        flags: ArcFlags {
+                            sweep: !arc.flags.sweep,
+                            ..arc.flags
+                        },
+                        ..*arc
+                    }),
+                    Segment::CubicBezier(cbs) => Segment::CubicBezier(cbs.flip()),
+                    Segment::QuadraticBezier(qbs) => Segment::QuadraticBezier(qbs.flip()),
+                })
+                .collect(),
+            ..self.clone()
+        }
+    }
+
+    /// Apply an affine transform to every point of the toolpath
+    pub fn transformed(&self, transform: &Transform<f64>) -> Self {
+        Self {
+            from: transform.transform_point(self.from),
+            segments: self
+                .segments
+                .iter()
+                .map(|segment| match segment {
+                    Segment::Line(line) => Segment::Line(line.transformed(transform)),
+                    Segment::Arc(arc) => Segment::Arc(arc.transformed(transform)),
+                    Segment::CubicBezier(cbs) => Segment::CubicBezier(cbs.transformed(transform)),
+                    Segment::QuadraticBezier(qbs) => {
+                        Segment::QuadraticBezier(qbs.transformed(transform))
+                    }
+                })
+                .collect(),
+            ..self.clone()
+        }
+    }
+
+    /// Replay this toolpath onto a [`Turtle`]
+    pub fn draw(&self, turtle: &mut impl Turtle) {
+        self.draw_start(turtle);
+        self.draw_segments(turtle);
+    }
+
+    /// The first part of [`Self::draw`], up to the move to where the toolpath starts
+    pub fn draw_start(&self, turtle: &mut impl Turtle) {
+        self.comments
+            .iter()
+            .for_each(|comment| turtle.comment(comment.clone()));
+        if self.pause {
+            turtle.pause();
+        }
+        turtle.move_to(self.from);
+    }
+
+    /// The rest of [`Self::draw`] after [`Self::draw_start`]
+    pub fn draw_segments(&self, turtle: &mut impl Turtle) {
+        self.segments.iter().for_each(|segment| match segment {
+            Segment::Line(line) => turtle.line_to(line.to),
+            Segment::Arc(arc) => turtle.arc(*arc),
+            Segment::CubicBezier(cbs) => turtle.cubic_bezier(*cbs),
+            Segment::QuadraticBezier(qbs) => turtle.quadratic_bezier(*qbs),
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use lyon_geom::{point, vector, Angle};
+
+    use super::*;
+
+    #[test]
+    fn toolpath_ends_at_last_segment() {
+        let mut toolpath = Toolpath::new(point(0., 0.));
+        assert_eq!(toolpath.to(), point(0., 0.));
+        toolpath.segments.push(Segment::Line(LineSegment {
+            from: point(0., 0.),
+            to: point(1., 2.),
+        }));
+        assert_eq!(toolpath.to(), point(1., 2.));
+    }
+
+    #[test]
+    fn curves_are_flattened_within_tolerance_into_as_many_lines_as_their_size_needs() {
+        let half_circle = |radius: f64| {
+            Segment::Arc(SvgArc {
+                from: point(-radius, 0.),
+                to: point(radius, 0.),
+                radii: vector(radius, radius),
+                x_rotation: Angle::zero(),
+                flags: ArcFlags::default(),
+            })
+        };
+        let lines = |radius: f64, tolerance: f64| {
+            let points = half_circle(radius).flattened(tolerance);
+            for p in &points {
+                assert!((p.to_vector().length() - radius).abs() <= tolerance);
+            }
+            points.len()
+        };
+        assert!(lines(100., 0.01) > lines(1., 0.01));
+        assert!(lines(100., 0.01) > lines(100., 0.1));
+        assert!(lines(0.01, 0.01) <= 2);
+    }
+
+    #[test]
+    fn glyphs_are_placed_along_toolpath() {
+        let toolpath =
+            Toolpath::from_polyline(&[point(0., 0.), point(10., 0.), point(10., 10.)]).unwrap();
+        assert_eq!(toolpath.length(0.1), 20.);
+        assert_eq!(
+            toolpath.place_along(8., &[2., 2., 20.], 0.1),
+            [
+                Some((point(9., 0.), vector(1., 0.))),
+                Some((point(10., 1.), vector(0., 1.))),
+                None,
+            ]
+        );
+    }
+}
@@ -1,4 +1,5 @@
-use std::fmt::Debug;
+use alloc::string::String;
+use core::fmt::Debug;
 
 use lyon_geom::{point, vector, CubicBezierSegment, Point, QuadraticBezierSegment, SvgArc, Vector};
 use uom::si::{
@@ -6,7 +7,7 @@ use uom::si::{
     length::{inch, millimeter},
 };
 
-use crate::Turtle;
+use crate::{toolpath::SourceElement, Turtle};
 
 /// Wrapper turtle that converts from user units to millimeters at a given DPI
 #[derive(Debug)]
@@ -42,6 +43,14 @@ impl<T: Turtle> Turtle for DpiConvertingTurtle<T> {
         self.inner.comment(comment)
     }
 
+    fn source_element(&mut self, element: &SourceElement) {
+        self.inner.source_element(element)
+    }
+
+    fn pause(&mut self) {
+        self.inner.pause()
+    }
+
     fn move_to(&mut self, to: Point<f64>) {
         self.inner.move_to(self.point_to_mm(to))
     }
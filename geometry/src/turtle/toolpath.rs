@@ -0,0 +1,82 @@
+use alloc::{string::String, vec::Vec};
+
+use lyon_geom::{CubicBezierSegment, LineSegment, Point, QuadraticBezierSegment, SvgArc};
+
+use super::Turtle;
+use crate::toolpath::{Segment, SourceElement, Toolpath};
+
+/// Records draw operations as [`Toolpath`]s so they can be transformed before emitting g-code
+#[derive(Debug, Default)]
+pub struct ToolpathTurtle {
+    pub toolpaths: Vec<Toolpath>,
+    /// Comments that have not been attached to a toolpath yet
+    pub comments: Vec<String>,
+    /// Element currently being drawn
+    pub source: Option<SourceElement>,
+    /// Whether the next toolpath starts with a pause
+    pub pause: bool,
+}
+
+impl ToolpathTurtle {
+    fn push_segment(&mut self, segment: Segment) {
+        if self.comments.is_empty() && !self.pause {
+            if let Some(toolpath) = self.toolpaths.last_mut() {
+                toolpath.segments.push(segment);
+                return;
+            }
+        }
+        // Drawing without moving first
+        self.move_to(segment.from());
+        self.toolpaths.last_mut().unwrap().segments.push(segment);
+    }
+
+    fn current_position(&self) -> Point<f64> {
+        self.toolpaths
+            .last()
+            .map(Toolpath::to)
+            .unwrap_or_else(Point::zero)
+    }
+}
+
+impl Turtle for ToolpathTurtle {
+    fn begin(&mut self) {}
+
+    fn end(&mut self) {}
+
+    fn comment(&mut self, comment: String) {
+        self.comments.push(comment);
+    }
+
+    fn source_element(&mut self, element: &SourceElement) {
+        self.source = Some(element.clone());
+    }
+
+    fn pause(&mut self) {
+        self.pause = true;
+    }
+
+    fn move_to(&mut self, to: Point<f64>) {
+        let mut toolpath = Toolpath::new(to);
+        toolpath.comments = core::mem::take(&mut self.comments);
+        toolpath.source = self.source.clone();
+        toolpath.pause = core::mem::take(&mut self.pause);
+        self.toolpaths.push(toolpath);
+    }
+
+    fn line_to(&mut self, to: Point<f64>) {
+        let from = self.current_position();
+        self.push_segment(Segment::Line(LineSegment { from, to }));
+    }
+
+    fn arc(&mut self, svg_arc: SvgArc<f64>) {
+        self.push_segment(Segment::Arc(svg_arc));
+    }
+
+    fn cubic_bezier(&mut self, cbs: CubicBezierSegment<f64>) {
+        self.push_segment(Segment::CubicBezier(cbs));
+    }
+
+    fn quadratic_bezier(&mut self, qbs: QuadraticBezierSegment<f64>) {
+        self.push_segment(Segment::QuadraticBezier(qbs));
+    }
+}
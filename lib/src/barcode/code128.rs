@@ -0,0 +1,103 @@
+//! Code 128 (ISO/IEC 15417) encoding
+
+use super::BarcodeError;
+
+/// Alternating bar and space widths in modules of each symbol value
+const PATTERNS: [&str; 107] = [
+    "212222", "222122", "222221", "121223", "121322", "131222", "122213", "122312", "132212",
+    "221213", "221312", "231212", "112232", "122132", "122231", "113222", "123122", "123221",
+    "223211", "221132", "221231", "213212", "223112", "312131", "311222", "321122", "321221",
+    "312212", "322112", "322211", "212123", "212321", "232121", "111323", "131123", "131321",
+    "112313", "132113", "132311", "211313", "231113", "231311", "112133", "112331", "132131",
+    "113123", "113321", "133121", "313121", "211331", "231131", "213113", "213311", "213131",
+    "311123", "311321", "331121", "312113", "312311", "332111", "314111", "221411", "431111",
+    "111224", "111422", "121124", "121421", "141122", "141221", "112214", "112412", "122114",
+    "122411", "142112", "142211", "241211", "221114", "413111", "241112", "134111", "111242",
+    "121142", "121241", "114212", "124112", "124211", "411212", "421112", "421211", "212141",
+    "214121", "412121", "111143", "111341", "131141", "114113", "114311", "411113", "411311",
+    "113141", "114131", "311141", "411131", "211412", "211214", "211232", "2331112",
+];
+
+const START_B: usize = 104;
+const START_C: usize = 105;
+const STOP: usize = 106;
+
+/// Symbol values for the text, including start, checksum, and stop
+///
+/// Text made up of an even number of digits uses code set C, which packs two digits into each
+/// symbol. Anything else uses code set B, which covers printable ASCII.
+fn symbols(text: &str) -> Result<Vec<usize>, BarcodeError> {
+    let mut symbols = if !text.is_empty()
+        && text.len() & 1 == 0
+        && text.bytes().all(|byte| byte.is_ascii_digit())
+    {
+        std::iter::once(START_C)
+            .chain(
+                text.as_bytes()
+                    .chunks(2)
+                    .map(|pair| usize::from(pair[0] - b'0') * 10 + usize::from(pair[1] - b'0')),
+            )
+            .collect::<Vec<_>>()
+    } else {
+        std::iter::once(Ok(START_B))
+            .chain(text.chars().map(|c| match c {
+                ' '..='~' => Ok(c as usize - ' ' as usize),
+                _ => Err(BarcodeError::UnsupportedCharacter(c)),
+            }))
+            .collect::<Result<Vec<_>, _>>()?
+    };
+
+    let checksum = symbols
+        .iter()
+        .enumerate()
+        .map(|(i, value)| i.max(1) * value)
+        .sum::<usize>()
+        % 103;
+    symbols.extend([checksum, STOP]);
+    Ok(symbols)
+}
+
+/// Dark (`true`) and light modules of a Code 128 barcode, without the quiet zone
+pub fn encode(text: &str) -> Result<Vec<bool>, BarcodeError> {
+    Ok(symbols(text)?
+        .into_iter()
+        .flat_map(|value| PATTERNS[value].bytes().enumerate())
+        .flat_map(|(i, width)| (0..width - b'0').map(move |_| i % 2 == 0))
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn patterns_are_well_formed() {
+        for pattern in PATTERNS {
+            let widths = pattern.bytes().map(|width| usize::from(width - b'0'));
+            let bars = widths.clone().step_by(2).sum::<usize>();
+            assert_eq!(
+                widths.sum::<usize>(),
+                if pattern.len() == 7 { 13 } else { 11 }
+            );
+            // Bars always add up to an even number of modules
+            assert_eq!(bars % 2, 0, "{pattern}");
+        }
+    }
+
+    #[test]
+    fn checksum_is_weighted_sum() {
+        assert_eq!(
+            symbols("PJJ123C").unwrap(),
+            [START_B, 48, 42, 42, 17, 18, 19, 35, 55, STOP]
+        );
+        assert_eq!(symbols("1234").unwrap(), [START_C, 12, 34, 82, STOP]);
+        assert_eq!(symbols("é"), Err(BarcodeError::UnsupportedCharacter('é')));
+    }
+
+    #[test]
+    fn barcode_ends_with_stop_bar() {
+        let modules = encode("A").unwrap();
+        assert_eq!(modules.len(), 11 * 3 + 13);
+        assert_eq!(modules[modules.len() - 2..], [true, true]);
+    }
+}
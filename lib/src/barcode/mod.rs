@@ -0,0 +1,251 @@
+use std::fmt::{self, Display, Write};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+pub use self::qr::QrErrorCorrection;
+
+mod code128;
+mod qr;
+
+/// Modules of light space required around a Code 128 barcode
+const CODE128_QUIET_ZONE: usize = 10;
+/// Modules of light space required around a QR code
+const QR_QUIET_ZONE: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Symbology {
+    QrCode(QrErrorCorrection),
+    /// With the height of the bars in millimeters
+    Code128 {
+        height: f64,
+    },
+}
+
+/// How to draw the dark parts of a barcode
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum BarcodeFill {
+    /// Trace the outline of each run of dark modules
+    Outline,
+    /// Fill dark modules with back-and-forth lines this far apart in millimeters (i.e. the tool width)
+    Hatch { spacing: f64 },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BarcodeError {
+    /// The character cannot be encoded by the symbology
+    UnsupportedCharacter(char),
+    /// There is too much text to fit in the largest supported symbol
+    TooLong,
+}
+
+impl Display for BarcodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnsupportedCharacter(c) => write!(f, "cannot encode {c:?}"),
+            Self::TooLong => write!(f, "text is too long"),
+        }
+    }
+}
+
+impl std::error::Error for BarcodeError {}
+
+/// A barcode to generate as an engraving-ready SVG
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Barcode {
+    pub text: String,
+    pub symbology: Symbology,
+    /// Width of the symbol in millimeters, excluding the quiet zone
+    pub width: f64,
+    pub fill: BarcodeFill,
+}
+
+/// A run of dark modules as (x, y, width, height) in millimeters
+type Rect = (f64, f64, f64, f64);
+
+impl Barcode {
+    /// Draw the barcode as an SVG sized in millimeters, with a quiet zone around it
+    pub fn to_svg(&self) -> Result<String, BarcodeError> {
+        let (rects, [width, height], quiet_zone) = match self.symbology {
+            Symbology::QrCode(ecc) => {
+                let modules = qr::encode(&self.text, ecc)?;
+                let module = self.width / modules.len() as f64;
+                let rects = modules
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(y, row)| {
+                        runs(row).map(move |(x, len)| {
+                            (
+                                x as f64 * module,
+                                y as f64 * module,
+                                len as f64 * module,
+                                module,
+                            )
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                (rects, [self.width; 2], QR_QUIET_ZONE as f64 * module)
+            }
+            Symbology::Code128 { height } => {
+                let modules = code128::encode(&self.text)?;
+                let module = self.width / modules.len() as f64;
+                let rects = runs(&modules)
+                    .map(|(x, len)| (x as f64 * module, 0., len as f64 * module, height))
+                    .collect();
+                (
+                    rects,
+                    [self.width, height],
+                    CODE128_QUIET_ZONE as f64 * module,
+                )
+            }
+        };
+
+        let mut svg = String::new();
+        let _ = writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}mm" height="{h}mm" viewBox="{x} {x} {w} {h}">"#,
+            x = -quiet_zone,
+            w = width + 2. * quiet_zone,
+            h = height + 2. * quiet_zone,
+        );
+        let _ = writeln!(
+            svg,
+            r#"<title>{}</title>"#,
+            self.text
+                .replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;")
+        );
+        for (x, y, w, h) in rects {
+            let _ = match self.fill {
+                BarcodeFill::Outline => writeln!(
+                    svg,
+                    r#"<path d="M {x} {y} h {w} v {h} h {} Z" fill="none" stroke="black"/>"#,
+                    -w
+                ),
+                BarcodeFill::Hatch { spacing } => writeln!(
+                    svg,
+                    r#"<polyline points="{}" fill="none" stroke="black"/>"#,
+                    hatch((x, y, w, h), spacing)
+                        .iter()
+                        .map(|(x, y)| format!("{x},{y}"))
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                ),
+            };
+        }
+        svg += "</svg>\n";
+        Ok(svg)
+    }
+}
+
+/// Start and length of each run of dark modules
+fn runs(modules: &[bool]) -> impl Iterator<Item = (usize, usize)> + '_ {
+    modules
+        .iter()
+        .enumerate()
+        .filter(|(i, dark)| **dark && (*i == 0 || !modules[i - 1]))
+        .map(|(start, _)| {
+            let len = modules[start..].iter().take_while(|dark| **dark).count();
+            (start, len)
+        })
+}
+
+/// Back-and-forth lines along the longer side of a rectangle, inset by half the spacing so the
+/// tool stays inside it
+fn hatch((x, y, w, h): Rect, spacing: f64) -> Vec<(f64, f64)> {
+    let vertical = h > w;
+    let (across, along) = if vertical { (w, h) } else { (h, w) };
+    let inset = (spacing / 2.).min(across / 2.);
+    let lines = ((across - 2. * inset) / spacing).floor() as usize + 1;
+    let step = if lines > 1 {
+        (across - 2. * inset) / (lines - 1) as f64
+    } else {
+        0.
+    };
+
+    (0..lines)
+        .flat_map(|i| {
+            let offset = inset + i as f64 * step;
+            let ends = [inset.min(along / 2.), along - inset.min(along / 2.)];
+            let ends = if i % 2 == 0 { ends } else { [ends[1], ends[0]] };
+            ends.map(|end| {
+                if vertical {
+                    (x + offset, y + end)
+                } else {
+                    (x + end, y + offset)
+                }
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn runs_of_dark_modules() {
+        assert_eq!(
+            runs(&[true, true, false, true, false, false, true]).collect::<Vec<_>>(),
+            [(0, 2), (3, 1), (6, 1)]
+        );
+    }
+
+    #[test]
+    fn hatch_stays_inside_rect() {
+        assert_eq!(
+            hatch((0., 0., 1., 10.), 0.25),
+            [
+                (0.125, 0.125),
+                (0.125, 9.875),
+                (0.375, 9.875),
+                (0.375, 0.125),
+                (0.625, 0.125),
+                (0.625, 9.875),
+                (0.875, 9.875),
+                (0.875, 0.125),
+            ]
+        );
+        // Narrower than the tool, so it goes down the middle
+        assert_eq!(
+            hatch((0., 0., 10., 0.1), 0.25),
+            [(0.05, 0.05), (9.95, 0.05)]
+        );
+    }
+
+    #[test]
+    fn barcode_svg_is_sized_in_millimeters() {
+        let barcode = Barcode {
+            text: "1234".to_string(),
+            symbology: Symbology::Code128 { height: 10. },
+            width: 57.,
+            fill: BarcodeFill::Outline,
+        };
+        let svg = barcode.to_svg().unwrap();
+        let document = roxmltree::Document::parse(&svg).unwrap();
+        let root = document.root_element();
+        // 57 modules plus a quiet zone of 10 on either side
+        assert_eq!(root.attribute("width"), Some("77mm"));
+        assert_eq!(root.attribute("height"), Some("30mm"));
+        // Start C, 2 digit pairs, checksum each have 3 bars, stop has 4
+        assert_eq!(
+            document
+                .descendants()
+                .filter(|node| node.has_tag_name("path"))
+                .count(),
+            4 * 3 + 4
+        );
+
+        let qr = Barcode {
+            text: "SN-0001".to_string(),
+            symbology: Symbology::QrCode(QrErrorCorrection::Medium),
+            width: 21.,
+            fill: BarcodeFill::Hatch { spacing: 0.2 },
+        };
+        assert!(roxmltree::Document::parse(&qr.to_svg().unwrap()).is_ok());
+    }
+}
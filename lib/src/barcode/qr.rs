@@ -0,0 +1,559 @@
+//! QR Code (ISO/IEC 18004) encoding for versions 1 through 10
+//!
+//! Larger versions are not needed for part serialization and would only add tables.
+
+use super::BarcodeError;
+
+/// How much of a QR code can be damaged while staying readable
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum QrErrorCorrection {
+    /// ~7%
+    Low,
+    /// ~15%
+    #[default]
+    Medium,
+    /// ~25%
+    Quartile,
+    /// ~30%
+    High,
+}
+
+impl QrErrorCorrection {
+    fn index(self) -> usize {
+        match self {
+            Self::Low => 0,
+            Self::Medium => 1,
+            Self::Quartile => 2,
+            Self::High => 3,
+        }
+    }
+
+    fn format_bits(self) -> u32 {
+        match self {
+            Self::Low => 1,
+            Self::Medium => 0,
+            Self::Quartile => 3,
+            Self::High => 2,
+        }
+    }
+}
+
+const MAX_VERSION: usize = 10;
+
+/// Error correction codewords per block and the (count, data codewords) of each group of blocks
+type BlockStructure = (usize, [(usize, usize); 2]);
+
+/// [`BlockStructure`] by version and [`QrErrorCorrection`]
+const BLOCKS: [[BlockStructure; 4]; MAX_VERSION] = [
+    [
+        (7, [(1, 19), (0, 0)]),
+        (10, [(1, 16), (0, 0)]),
+        (13, [(1, 13), (0, 0)]),
+        (17, [(1, 9), (0, 0)]),
+    ],
+    [
+        (10, [(1, 34), (0, 0)]),
+        (16, [(1, 28), (0, 0)]),
+        (22, [(1, 22), (0, 0)]),
+        (28, [(1, 16), (0, 0)]),
+    ],
+    [
+        (15, [(1, 55), (0, 0)]),
+        (26, [(1, 44), (0, 0)]),
+        (18, [(2, 17), (0, 0)]),
+        (22, [(2, 13), (0, 0)]),
+    ],
+    [
+        (20, [(1, 80), (0, 0)]),
+        (18, [(2, 32), (0, 0)]),
+        (26, [(2, 24), (0, 0)]),
+        (16, [(4, 9), (0, 0)]),
+    ],
+    [
+        (26, [(1, 108), (0, 0)]),
+        (24, [(2, 43), (0, 0)]),
+        (18, [(2, 15), (2, 16)]),
+        (22, [(2, 11), (2, 12)]),
+    ],
+    [
+        (18, [(2, 68), (0, 0)]),
+        (16, [(4, 27), (0, 0)]),
+        (24, [(4, 19), (0, 0)]),
+        (28, [(4, 15), (0, 0)]),
+    ],
+    [
+        (20, [(2, 78), (0, 0)]),
+        (18, [(4, 31), (0, 0)]),
+        (18, [(2, 14), (4, 15)]),
+        (26, [(4, 13), (1, 14)]),
+    ],
+    [
+        (24, [(2, 97), (0, 0)]),
+        (22, [(2, 38), (2, 39)]),
+        (22, [(4, 18), (2, 19)]),
+        (26, [(4, 14), (2, 15)]),
+    ],
+    [
+        (30, [(2, 116), (0, 0)]),
+        (22, [(3, 36), (2, 37)]),
+        (20, [(4, 16), (4, 17)]),
+        (24, [(4, 12), (4, 13)]),
+    ],
+    [
+        (18, [(2, 68), (2, 69)]),
+        (26, [(4, 43), (1, 44)]),
+        (24, [(6, 19), (2, 20)]),
+        (28, [(6, 15), (2, 16)]),
+    ],
+];
+
+/// Centers of alignment patterns along each axis, by version
+const ALIGNMENT_PATTERNS: [&[usize]; MAX_VERSION] = [
+    &[],
+    &[6, 18],
+    &[6, 22],
+    &[6, 26],
+    &[6, 30],
+    &[6, 34],
+    &[6, 22, 38],
+    &[6, 24, 42],
+    &[6, 26, 46],
+    &[6, 28, 50],
+];
+
+/// A dark-light-dark run in 1:1:3:1:1 proportion followed by four light modules
+const FINDER_LIKE: [bool; 11] = [
+    true, false, true, true, true, false, true, false, false, false, false,
+];
+
+const ALPHANUMERIC: &str = "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ $%*+-./:";
+
+/// Dark (`true`) and light modules of a QR code by row, without the quiet zone
+pub fn encode(text: &str, ecc: QrErrorCorrection) -> Result<Vec<Vec<bool>>, BarcodeError> {
+    let (version, codewords) = (1..=MAX_VERSION)
+        .find_map(|version| Some((version, data_codewords(text, version, ecc)?)))
+        .ok_or(BarcodeError::TooLong)?;
+    let codewords = add_error_correction(&codewords, version, ecc);
+
+    let mut qr = Matrix::new(version);
+    qr.draw_function_patterns();
+    qr.draw_codewords(&codewords);
+    let mask = (0..8)
+        .min_by_key(|mask| {
+            let mut masked = qr.clone();
+            masked.apply_mask(*mask);
+            masked.draw_format(ecc, *mask);
+            masked.penalty()
+        })
+        .unwrap_or_default();
+    qr.apply_mask(mask);
+    qr.draw_format(ecc, mask);
+    Ok(qr.modules)
+}
+
+/// Total data codewords that fit in a version
+fn data_capacity(version: usize, ecc: QrErrorCorrection) -> usize {
+    let (_, groups) = BLOCKS[version - 1][ecc.index()];
+    groups.iter().map(|(count, len)| count * len).sum()
+}
+
+/// Encode the text in alphanumeric mode if possible, else in byte mode, and pad it to fill the
+/// version, or [`None`] if it does not fit
+fn data_codewords(text: &str, version: usize, ecc: QrErrorCorrection) -> Option<Vec<u8>> {
+    let mut bits = BitBuffer::default();
+    if text.chars().all(|c| ALPHANUMERIC.contains(c)) {
+        bits.push(0b0010, 4);
+        bits.push(text.len() as u32, if version < 10 { 9 } else { 11 });
+        let values = text
+            .chars()
+            .filter_map(|c| ALPHANUMERIC.find(c))
+            .map(|value| value as u32)
+            .collect::<Vec<_>>();
+        for pair in values.chunks(2) {
+            match pair {
+                [first, second] => bits.push(first * 45 + second, 11),
+                [single] => bits.push(*single, 6),
+                _ => unreachable!(),
+            }
+        }
+    } else {
+        bits.push(0b0100, 4);
+        bits.push(text.len() as u32, if version < 10 { 8 } else { 16 });
+        text.bytes().for_each(|byte| bits.push(byte.into(), 8));
+    }
+
+    let capacity = data_capacity(version, ecc) * 8;
+    if bits.len > capacity {
+        return None;
+    }
+    // Terminator, then pad to a whole byte
+    bits.push(0, (capacity - bits.len).min(4));
+    bits.push(0, (8 - bits.len % 8) % 8);
+    let mut codewords = bits.bytes;
+    for pad in [0xEC, 0x11].into_iter().cycle() {
+        if codewords.len() * 8 >= capacity {
+            break;
+        }
+        codewords.push(pad);
+    }
+    Some(codewords)
+}
+
+#[derive(Default)]
+struct BitBuffer {
+    bytes: Vec<u8>,
+    len: usize,
+}
+
+impl BitBuffer {
+    /// Append the `count` least significant bits of `value`, most significant first
+    fn push(&mut self, value: u32, count: usize) {
+        for i in (0..count).rev() {
+            if self.len == self.bytes.len() * 8 {
+                self.bytes.push(0);
+            }
+            if (value >> i) & 1 == 1 {
+                *self.bytes.last_mut().unwrap() |= 0x80 >> (self.len % 8);
+            }
+            self.len += 1;
+        }
+    }
+}
+
+/// Split data into blocks, add Reed-Solomon error correction to each, and interleave them
+fn add_error_correction(data: &[u8], version: usize, ecc: QrErrorCorrection) -> Vec<u8> {
+    let (ec_len, groups) = BLOCKS[version - 1][ecc.index()];
+    let divisor = reed_solomon_divisor(ec_len);
+
+    let mut remaining = data;
+    let blocks = groups
+        .iter()
+        .flat_map(|(count, len)| (0..*count).map(move |_| *len))
+        .map(|len| {
+            let (block, rest) = remaining.split_at(len);
+            remaining = rest;
+            (block, reed_solomon_remainder(block, &divisor))
+        })
+        .collect::<Vec<_>>();
+
+    let max_len = blocks
+        .iter()
+        .map(|(block, _)| block.len())
+        .max()
+        .unwrap_or(0);
+    let mut codewords = vec![];
+    for i in 0..max_len {
+        codewords.extend(blocks.iter().filter_map(|(block, _)| block.get(i)));
+    }
+    for i in 0..ec_len {
+        codewords.extend(blocks.iter().map(|(_, ec)| ec[i]));
+    }
+    codewords
+}
+
+/// Multiply in GF(2^8) modulo x^8 + x^4 + x^3 + x^2 + 1
+fn gf_multiply(x: u8, y: u8) -> u8 {
+    let mut z: u32 = 0;
+    for i in (0..8).rev() {
+        z = (z << 1) ^ ((z >> 7) * 0x11D);
+        z ^= ((u32::from(y) >> i) & 1) * u32::from(x);
+    }
+    z as u8
+}
+
+/// Coefficients of the generator polynomial, excluding the leading 1
+fn reed_solomon_divisor(degree: usize) -> Vec<u8> {
+    let mut divisor = vec![0; degree];
+    divisor[degree - 1] = 1;
+    let mut root = 1;
+    for _ in 0..degree {
+        for j in 0..degree {
+            divisor[j] = gf_multiply(divisor[j], root);
+            if j + 1 < degree {
+                divisor[j] ^= divisor[j + 1];
+            }
+        }
+        root = gf_multiply(root, 0x02);
+    }
+    divisor
+}
+
+fn reed_solomon_remainder(data: &[u8], divisor: &[u8]) -> Vec<u8> {
+    let mut remainder = vec![0; divisor.len()];
+    for byte in data {
+        let factor = byte ^ remainder.remove(0);
+        remainder.push(0);
+        for (r, d) in remainder.iter_mut().zip(divisor) {
+            *r ^= gf_multiply(*d, factor);
+        }
+    }
+    remainder
+}
+
+#[derive(Clone)]
+struct Matrix {
+    version: usize,
+    size: usize,
+    modules: Vec<Vec<bool>>,
+    is_function: Vec<Vec<bool>>,
+}
+
+impl Matrix {
+    fn new(version: usize) -> Self {
+        let size = version * 4 + 17;
+        Self {
+            version,
+            size,
+            modules: vec![vec![false; size]; size],
+            is_function: vec![vec![false; size]; size],
+        }
+    }
+
+    fn set_function(&mut self, x: usize, y: usize, dark: bool) {
+        self.modules[y][x] = dark;
+        self.is_function[y][x] = true;
+    }
+
+    fn draw_function_patterns(&mut self) {
+        for i in 0..self.size {
+            self.set_function(6, i, i % 2 == 0);
+            self.set_function(i, 6, i % 2 == 0);
+        }
+
+        let last = self.size - 4;
+        for (x, y) in [(3, 3), (last, 3), (3, last)] {
+            for dy in -4..=4_isize {
+                for dx in -4..=4_isize {
+                    let (Some(xx), Some(yy)) = (x.checked_add_signed(dx), y.checked_add_signed(dy))
+                    else {
+                        continue;
+                    };
+                    if xx < self.size && yy < self.size {
+                        let distance = dx.abs().max(dy.abs());
+                        self.set_function(xx, yy, distance != 2 && distance != 4);
+                    }
+                }
+            }
+        }
+
+        let positions = ALIGNMENT_PATTERNS[self.version - 1];
+        for (i, y) in positions.iter().enumerate() {
+            for (j, x) in positions.iter().enumerate() {
+                // Skip the corners with finder patterns
+                let last = positions.len() - 1;
+                if (i, j) == (0, 0) || (i, j) == (0, last) || (i, j) == (last, 0) {
+                    continue;
+                }
+                for dy in -2..=2_isize {
+                    for dx in -2..=2_isize {
+                        self.set_function(
+                            x.wrapping_add_signed(dx),
+                            y.wrapping_add_signed(dy),
+                            dx.abs().max(dy.abs()) != 1,
+                        );
+                    }
+                }
+            }
+        }
+
+        // Reserve the format areas, which are drawn once the mask is chosen
+        self.draw_format(QrErrorCorrection::Low, 0);
+
+        if self.version >= 7 {
+            let mut remainder = self.version as u32;
+            for _ in 0..12 {
+                remainder = (remainder << 1) ^ ((remainder >> 11) * 0x1F25);
+            }
+            let bits = (self.version as u32) << 12 | remainder;
+            for i in 0..18 {
+                let dark = (bits >> i) & 1 == 1;
+                let (a, b) = (self.size - 11 + i % 3, i / 3);
+                self.set_function(a, b, dark);
+                self.set_function(b, a, dark);
+            }
+        }
+    }
+
+    fn draw_format(&mut self, ecc: QrErrorCorrection, mask: usize) {
+        let data = ecc.format_bits() << 3 | mask as u32;
+        let mut remainder = data;
+        for _ in 0..10 {
+            remainder = (remainder << 1) ^ ((remainder >> 9) * 0x537);
+        }
+        let bits = (data << 10 | remainder) ^ 0x5412;
+        let bit = |i: usize| (bits >> i) & 1 == 1;
+
+        for i in 0..=5 {
+            self.set_function(8, i, bit(i));
+        }
+        self.set_function(8, 7, bit(6));
+        self.set_function(8, 8, bit(7));
+        self.set_function(7, 8, bit(8));
+        for i in 9..15 {
+            self.set_function(14 - i, 8, bit(i));
+        }
+
+        for i in 0..8 {
+            self.set_function(self.size - 1 - i, 8, bit(i));
+        }
+        for i in 8..15 {
+            self.set_function(8, self.size - 15 + i, bit(i));
+        }
+        self.set_function(8, self.size - 8, true);
+    }
+
+    /// Place codewords in the zig-zag pattern of two-module columns, from the bottom right
+    fn draw_codewords(&mut self, codewords: &[u8]) {
+        let mut i = 0;
+        let mut right = self.size - 1;
+        while right >= 1 {
+            // Skip the vertical timing pattern
+            if right == 6 {
+                right = 5;
+            }
+            for vertical in 0..self.size {
+                for j in 0..2 {
+                    let x = right - j;
+                    let upward = (right + 1) & 2 == 0;
+                    let y = if upward {
+                        self.size - 1 - vertical
+                    } else {
+                        vertical
+                    };
+                    if !self.is_function[y][x] && i < codewords.len() * 8 {
+                        self.modules[y][x] = (codewords[i / 8] >> (7 - i % 8)) & 1 == 1;
+                        i += 1;
+                    }
+                }
+            }
+            if right < 2 {
+                break;
+            }
+            right -= 2;
+        }
+    }
+
+    fn apply_mask(&mut self, mask: usize) {
+        for y in 0..self.size {
+            for x in 0..self.size {
+                let invert = match mask {
+                    0 => (x + y) % 2 == 0,
+                    1 => y % 2 == 0,
+                    2 => x % 3 == 0,
+                    3 => (x + y) % 3 == 0,
+                    4 => (x / 3 + y / 2) % 2 == 0,
+                    5 => x * y % 2 + x * y % 3 == 0,
+                    6 => (x * y % 2 + x * y % 3) % 2 == 0,
+                    _ => ((x + y) % 2 + x * y % 3) % 2 == 0,
+                };
+                if invert && !self.is_function[y][x] {
+                    self.modules[y][x] ^= true;
+                }
+            }
+        }
+    }
+
+    /// Score how hard the symbol is to read, lower is better
+    fn penalty(&self) -> usize {
+        let columns = (0..self.size)
+            .map(|x| (0..self.size).map(|y| self.modules[y][x]).collect())
+            .collect::<Vec<Vec<bool>>>();
+
+        let mut penalty = 0;
+        for line in self.modules.iter().chain(&columns) {
+            // Runs of five or more modules of the same color
+            let mut run = 1;
+            for i in 1..=line.len() {
+                if i < line.len() && line[i] == line[i - 1] {
+                    run += 1;
+                } else {
+                    if run >= 5 {
+                        penalty += run - 2;
+                    }
+                    run = 1;
+                }
+            }
+            // Patterns that look like finders
+            penalty += line
+                .windows(11)
+                .filter(|window| {
+                    window.iter().eq(FINDER_LIKE.iter())
+                        || window.iter().eq(FINDER_LIKE.iter().rev())
+                })
+                .count()
+                * 40;
+        }
+
+        // 2x2 blocks of the same color
+        for y in 1..self.size {
+            for x in 1..self.size {
+                let color = self.modules[y][x];
+                if self.modules[y - 1][x] == color
+                    && self.modules[y][x - 1] == color
+                    && self.modules[y - 1][x - 1] == color
+                {
+                    penalty += 3;
+                }
+            }
+        }
+
+        // Imbalance of dark and light modules, in steps of 5%
+        let dark = self.modules.iter().flatten().filter(|dark| **dark).count();
+        let total = self.size * self.size;
+        penalty + (dark * 20).abs_diff(total * 10) / total * 10
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn hello_world_codewords() {
+        let data = data_codewords("HELLO WORLD", 1, QrErrorCorrection::Quartile).unwrap();
+        assert_eq!(
+            data,
+            [32, 91, 11, 120, 209, 114, 220, 77, 67, 64, 236, 17, 236]
+        );
+        assert_eq!(
+            reed_solomon_remainder(&data, &reed_solomon_divisor(13)),
+            [168, 72, 22, 82, 217, 54, 156, 0, 46, 15, 180, 122, 16]
+        );
+    }
+
+    #[test]
+    fn block_table_fills_each_version() {
+        let total_codewords = [26, 44, 70, 100, 134, 172, 196, 242, 292, 346];
+        for (version, total) in (1..=MAX_VERSION).zip(total_codewords) {
+            for (ec_len, groups) in BLOCKS[version - 1] {
+                let blocks: usize = groups.iter().map(|(count, _)| count).sum();
+                let data: usize = groups.iter().map(|(count, len)| count * len).sum();
+                assert_eq!(data + blocks * ec_len, total, "version {version}");
+            }
+        }
+    }
+
+    #[test]
+    fn symbols_have_finder_patterns_and_grow_with_text() {
+        let qr = encode("SN-0001", QrErrorCorrection::Medium).unwrap();
+        assert_eq!(qr.len(), 21);
+        assert_eq!(
+            qr[0][..8],
+            [true, true, true, true, true, true, true, false]
+        );
+        assert_eq!(
+            qr[3][..8],
+            [true, false, true, true, true, false, true, false]
+        );
+        // Timing pattern
+        assert_eq!(qr[6][8..13], [true, false, true, false, true]);
+
+        let long = encode(&"a".repeat(100), QrErrorCorrection::Medium).unwrap();
+        assert_eq!(long.len(), 6 * 4 + 17);
+        assert_eq!(
+            encode(&"a".repeat(1000), QrErrorCorrection::Medium),
+            Err(BarcodeError::TooLong)
+        );
+    }
+}
@@ -0,0 +1,281 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use lyon_geom::{euclid::default::Transform2D, point, vector, Point};
+
+/// Lines the toolpaths up with graphics printed on the material beforehand (i.e. print-and-cut),
+/// from where two reference points of the design were found on the machine
+///
+/// The toolpaths are rotated and moved, but never scaled, so the first points always line up and
+/// the second ones are in the same direction.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Alignment {
+    /// Where the reference points are in the design (i.e. two registration marks), in millimeters
+    /// after placing it at [`crate::ConversionConfig::origin`]
+    pub design: [[f64; 2]; 2],
+    /// Where the reference points were found on the machine, in millimeters
+    pub measured: [[f64; 2]; 2],
+}
+
+impl Alignment {
+    /// Moves the first design point onto the first measured point, rotated so the second points
+    /// are in the same direction from it
+    pub fn transform(&self) -> Transform2D<f64> {
+        let [design_from, design_to] = self.design.map(to_point);
+        let [measured_from, measured_to] = self.measured.map(to_point);
+        let rotation = (measured_to - measured_from).angle_from_x_axis()
+            - (design_to - design_from).angle_from_x_axis();
+        Transform2D::translation(-design_from.x, -design_from.y)
+            .then_rotate(rotation)
+            .then_translate(measured_from.to_vector())
+    }
+
+    /// How much further apart the measured points are than the design points in millimeters
+    ///
+    /// This should be close to 0, otherwise the points were not measured accurately or the
+    /// material was printed at a different scale.
+    pub fn distance_error(&self) -> f64 {
+        let [design_from, design_to] = self.design.map(to_point);
+        let [measured_from, measured_to] = self.measured.map(to_point);
+        (measured_to - measured_from).length() - (design_to - design_from).length()
+    }
+}
+
+/// Where a machine reports its tool is in work coordinates, from a Grbl status report (i.e.
+/// `<Idle|WPos:10.000,20.000,0.000|FS:0,0>`) or a Marlin `M114` response (i.e.
+/// `X:10.00 Y:20.00 Z:0.00 E:0.00 Count X:800 Y:1600 Z:0`), in millimeters
+///
+/// Grbl reports that only have the machine position are moved by the work coordinate offset, if
+/// it was reported too.
+pub fn parse_position_report(report: &str) -> Option<[f64; 2]> {
+    let report = report.trim();
+    if let Some(status) = report.strip_prefix('<').and_then(|r| r.strip_suffix('>')) {
+        // Fields are separated by | since Grbl 1.1, and by , before then
+        let field = |name: &str| {
+            let start = status.find(name)? + name.len();
+            let mut values = status[start..].split([',', '|']).map(str::parse::<f64>);
+            Some([values.next()?.ok()?, values.next()?.ok()?])
+        };
+        return field("WPos:").or_else(|| {
+            let [x, y] = field("MPos:")?;
+            let [x_offset, y_offset] = field("WCO:").unwrap_or_default();
+            Some([x - x_offset, y - y_offset])
+        });
+    }
+    // Marlin's stepper counts come after the position, with the same axis names
+    let position = report.split(" Count ").next()?;
+    let axis = |name: &str| {
+        position
+            .split_whitespace()
+            .find_map(|field| field.strip_prefix(name)?.parse().ok())
+    };
+    Some([axis("X:")?, axis("Y:")?])
+}
+
+/// Corrects for a machine that does not move exactly where it is told (i.e. non-square axes or
+/// stretched belts), as an affine transform applied to all output coordinates
+///
+/// The matrix is given by rows, so `[[a, b, c], [d, e, f]]` moves `(x, y)` to
+/// `(a x + b y + c, d x + e y + f)`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Calibration {
+    pub matrix: [[f64; 3]; 2],
+}
+
+impl Default for Calibration {
+    fn default() -> Self {
+        Self {
+            matrix: [[1., 0., 0.], [0., 1., 0.]],
+        }
+    }
+}
+
+impl Calibration {
+    /// Find the correction from a calibration cut, given three points the machine was told to
+    /// go to and where they actually ended up on the material
+    ///
+    /// Returns [`None`] if either set of points is in a line.
+    pub fn from_cut(commanded: [[f64; 2]; 3], measured: [[f64; 2]; 3]) -> Option<Self> {
+        // Maps the unit square onto three points, so the points can be mapped onto each other
+        let basis = |[origin, x, y]: [Point<f64>; 3]| {
+            Transform2D::new(
+                x.x - origin.x,
+                x.y - origin.y,
+                y.x - origin.x,
+                y.y - origin.y,
+                origin.x,
+                origin.y,
+            )
+        };
+        let error = basis(commanded.map(to_point))
+            .inverse()?
+            .then(&basis(measured.map(to_point)));
+        Some(Self::from_transform(&error.inverse()?))
+    }
+
+    pub fn from_transform(transform: &Transform2D<f64>) -> Self {
+        Self {
+            matrix: [
+                [transform.m11, transform.m21, transform.m31],
+                [transform.m12, transform.m22, transform.m32],
+            ],
+        }
+    }
+
+    pub fn transform(&self) -> Transform2D<f64> {
+        let [[a, b, c], [d, e, f]] = self.matrix;
+        Transform2D::new(a, d, b, e, c, f)
+    }
+}
+
+/// Takes up the slack in worn lead screws by moving a little further whenever an axis reverses,
+/// for firmware that does not compensate for backlash itself
+///
+/// Assumes each axis last moved towards 0 (i.e. while homing).
+#[derive(Debug, Clone)]
+pub(crate) struct BacklashCompensation {
+    /// Play in each axis in millimeters
+    backlash: [f64; 2],
+    /// Last position before compensation
+    position: Option<Point<f64>>,
+    /// Whether each axis last moved away from 0
+    positive: [bool; 2],
+}
+
+impl BacklashCompensation {
+    /// Returns [`None`] if there is no backlash to compensate for
+    pub fn new(backlash: [f64; 2]) -> Option<Self> {
+        backlash.iter().any(|b| *b != 0.).then_some(Self {
+            backlash,
+            position: None,
+            positive: [false; 2],
+        })
+    }
+
+    /// Where to move instead of `to`, preceded by a move that takes up the slack if an axis reverses
+    pub fn move_to(&mut self, to: Point<f64>) -> (Option<Point<f64>>, Point<f64>) {
+        let Some(from) = self.position.replace(to) else {
+            return (None, self.compensated(to));
+        };
+        let mut reversed = false;
+        for (axis, delta) in [to.x - from.x, to.y - from.y].into_iter().enumerate() {
+            if delta != 0. && (delta > 0.) != self.positive[axis] {
+                self.positive[axis] = delta > 0.;
+                reversed |= self.backlash[axis] != 0.;
+            }
+        }
+        (
+            reversed.then(|| self.compensated(from)),
+            self.compensated(to),
+        )
+    }
+
+    fn compensated(&self, p: Point<f64>) -> Point<f64> {
+        let [x, y] = [0, 1].map(|axis| {
+            if self.positive[axis] {
+                self.backlash[axis]
+            } else {
+                0.
+            }
+        });
+        p + vector(x, y)
+    }
+}
+
+fn to_point([x, y]: [f64; 2]) -> Point<f64> {
+    point(x, y)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn alignment_rotates_about_first_point() {
+        let alignment = Alignment {
+            design: [[0., 0.], [100., 0.]],
+            measured: [[10., 20.], [10., 120.]],
+        };
+        let transform = alignment.transform();
+        for (design, measured) in [
+            ((0., 0.), (10., 20.)),
+            ((100., 0.), (10., 120.)),
+            ((0., 50.), (-40., 20.)),
+        ] {
+            let aligned = transform.transform_point(point(design.0, design.1));
+            assert!((aligned - point(measured.0, measured.1)).length() < 1e-9);
+        }
+        assert_eq!(alignment.distance_error(), 0.);
+
+        let stretched = Alignment {
+            measured: [[0., 0.], [101., 0.]],
+            ..alignment
+        };
+        assert_eq!(stretched.distance_error(), 1.);
+    }
+
+    #[test]
+    fn position_is_read_from_grbl_and_marlin_reports() {
+        for (report, position) in [
+            (
+                "<Idle|WPos:10.000,-20.500,0.000|FS:0,0>",
+                Some([10., -20.5]),
+            ),
+            (
+                "<Jog|MPos:11.000,22.000,0.000|FS:0,0|WCO:1.000,2.000,0.000>",
+                Some([10., 20.]),
+            ),
+            (
+                "<Idle,MPos:5.000,6.000,0.000,WPos:1.000,2.000,0.000>",
+                Some([1., 2.]),
+            ),
+            (
+                "X:10.00 Y:20.00 Z:0.00 E:0.00 Count X:800 Y:1600 Z:0\r",
+                Some([10., 20.]),
+            ),
+            ("ok", None),
+            ("error:20", None),
+        ] {
+            assert_eq!(parse_position_report(report), position, "{report}");
+        }
+    }
+
+    #[test]
+    fn calibration_undoes_measured_error() {
+        let commanded = [[0., 0.], [100., 0.], [0., 100.]];
+        // The x-axis is stretched by 1% and the y-axis leans to the right
+        let measured = [[0., 0.], [101., 0.], [2., 100.]];
+        let calibration = Calibration::from_cut(commanded, measured).unwrap();
+        let corrected = calibration.transform().transform_point(point(101., 0.));
+        assert!((corrected - point(100., 0.)).length() < 1e-9);
+        let corrected = calibration.transform().transform_point(point(0., 100.));
+        assert!((corrected - point(-2. / 1.01, 100.)).length() < 1e-9);
+
+        assert_eq!(
+            Calibration::from_transform(&calibration.transform()),
+            calibration
+        );
+        assert_eq!(Calibration::default().transform(), Transform2D::identity());
+        assert!(Calibration::from_cut(commanded, [[0., 0.], [1., 1.], [2., 2.]]).is_none());
+    }
+
+    #[test]
+    fn backlash_is_taken_up_when_reversing() {
+        assert!(BacklashCompensation::new([0., 0.]).is_none());
+
+        let mut backlash = BacklashCompensation::new([0.5, 0.]).unwrap();
+        assert_eq!(backlash.move_to(point(10., 10.)), (None, point(10., 10.)));
+        // Moving away from 0 reverses the x-axis, but the y-axis has no backlash
+        assert_eq!(
+            backlash.move_to(point(20., 20.)),
+            (Some(point(10.5, 10.)), point(20.5, 20.))
+        );
+        assert_eq!(backlash.move_to(point(30., 10.)), (None, point(30.5, 10.)));
+        assert_eq!(
+            backlash.move_to(point(0., 10.)),
+            (Some(point(30., 10.)), point(0., 10.))
+        );
+    }
+}
@@ -0,0 +1,358 @@
+use std::ops::Range;
+
+use log::debug;
+use roxmltree::{Document, Node};
+
+/// A declaration in a rule of a style sheet (i.e. `stroke: red`)
+#[derive(Debug, PartialEq)]
+struct Declaration<'a> {
+    name: &'a str,
+    value: &'a str,
+    important: bool,
+}
+
+impl Declaration<'_> {
+    fn to_style(&self) -> String {
+        format!("{}:{}", self.name, self.value)
+    }
+}
+
+/// A selector of elements by tag name, id, and class (i.e. `path.outline`), which may match any
+/// element if it has none of them (`*`)
+#[derive(Debug, Default, PartialEq)]
+struct Compound<'a> {
+    tag: Option<&'a str>,
+    id: Option<&'a str>,
+    classes: Vec<&'a str>,
+}
+
+impl<'a> Compound<'a> {
+    /// `None` for selectors that aren't supported, like attribute selectors, pseudo-classes, and
+    /// child or sibling combinators
+    fn parse(compound: &'a str) -> Option<Self> {
+        if compound.contains(['>', '+', '~', '[', ':', '(']) {
+            return None;
+        }
+        let mut parsed = Self::default();
+        let starts = compound
+            .match_indices(['.', '#'])
+            .map(|(i, _)| i)
+            .chain([compound.len()]);
+        let mut start = 0;
+        for end in starts {
+            let part = &compound[start..end];
+            start = end;
+            let mut chars = part.chars();
+            match (chars.next(), chars.as_str()) {
+                (None, _) => {}
+                (Some('.'), class) if !class.is_empty() => parsed.classes.push(class),
+                (Some('#'), id) if !id.is_empty() => parsed.id = Some(id),
+                (Some('*'), "") => {}
+                (Some('.' | '#' | '*'), _) => return None,
+                _ => parsed.tag = Some(part),
+            }
+        }
+        Some(parsed)
+    }
+
+    fn matches(&self, node: Node) -> bool {
+        self.tag.is_none_or(|tag| node.tag_name().name() == tag)
+            && self.id.is_none_or(|id| node.attribute("id") == Some(id))
+            && self.classes.iter().all(|class| {
+                node.attribute("class")
+                    .is_some_and(|classes| classes.split_whitespace().any(|c| c == *class))
+            })
+    }
+}
+
+/// Compound selectors from the outermost to the element itself, each inside the one before it
+/// (i.e. `#logo .outline`)
+#[derive(Debug, PartialEq)]
+struct Selector<'a>(Vec<Compound<'a>>);
+
+impl<'a> Selector<'a> {
+    fn parse(selector: &'a str) -> Option<Self> {
+        let compounds = selector
+            .split_whitespace()
+            .map(Compound::parse)
+            .collect::<Option<Vec<_>>>()?;
+        (!compounds.is_empty()).then_some(Self(compounds))
+    }
+
+    fn matches(&self, node: Node) -> bool {
+        let Some((element, ancestors)) = self.0.split_last() else {
+            return false;
+        };
+        let mut candidates = node.ancestors().skip(1).filter(Node::is_element);
+        element.matches(node)
+            && ancestors
+                .iter()
+                .rev()
+                .all(|compound| candidates.any(|ancestor| compound.matches(ancestor)))
+    }
+
+    /// Number of ids, classes, and tag names, which decides between rules setting the same property
+    fn specificity(&self) -> [usize; 3] {
+        self.0
+            .iter()
+            .fold([0; 3], |[ids, classes, tags], compound| {
+                [
+                    ids + usize::from(compound.id.is_some()),
+                    classes + compound.classes.len(),
+                    tags + usize::from(compound.tag.is_some()),
+                ]
+            })
+    }
+}
+
+/// The rules of the `<style>` sheets of a document that select elements by tag name, id, and
+/// class, which is how SVG editors and exporters write them
+#[derive(Debug, Default)]
+struct StyleSheet<'a> {
+    /// Each selector with the index of its declarations in `blocks`, in the order they appear
+    rules: Vec<(Selector<'a>, usize)>,
+    blocks: Vec<Vec<Declaration<'a>>>,
+}
+
+impl<'a> StyleSheet<'a> {
+    /// Rules with selectors that aren't supported are left out, along with at-rules like
+    /// `@font-face` and `@media`
+    fn parse(css: &'a str) -> Self {
+        let mut sheet = Self::default();
+        let mut rest = css;
+        loop {
+            rest = rest.trim_start();
+            if rest.starts_with('@') {
+                let end = match (rest.find(';'), rest.find('{')) {
+                    (Some(semicolon), brace) if brace.is_none_or(|brace| semicolon < brace) => {
+                        semicolon + 1
+                    }
+                    (_, Some(brace)) => brace + block_len(&rest[brace..]),
+                    _ => rest.len(),
+                };
+                rest = &rest[end..];
+                continue;
+            }
+            let Some((selectors, after)) = rest.split_once('{') else {
+                break;
+            };
+            let (block, after) = after.split_once('}').unwrap_or((after, ""));
+            rest = after;
+
+            let declarations = block
+                .split(';')
+                .filter_map(|declaration| declaration.split_once(':'))
+                .map(|(name, value)| {
+                    let value = value.trim();
+                    let important = value.strip_suffix("!important");
+                    Declaration {
+                        name: name.trim(),
+                        value: important.unwrap_or(value).trim(),
+                        important: important.is_some(),
+                    }
+                })
+                .collect();
+            sheet.blocks.push(declarations);
+            for selector in selectors.split(',') {
+                match Selector::parse(selector) {
+                    Some(parsed) => sheet.rules.push((parsed, sheet.blocks.len() - 1)),
+                    None => debug!("Unsupported CSS selector: {}", selector.trim()),
+                }
+            }
+        }
+        sheet
+    }
+
+    /// Declarations of the rules that match an element, from the one that wins to the one that
+    /// loses (by `!important`, specificity, then order)
+    fn declarations(&self, node: Node) -> Vec<&Declaration<'a>> {
+        let mut matched = self
+            .rules
+            .iter()
+            .enumerate()
+            .filter(|(_, (selector, _))| selector.matches(node))
+            .flat_map(|(order, (selector, block))| {
+                self.blocks[*block]
+                    .iter()
+                    .enumerate()
+                    .map(move |(i, declaration)| {
+                        (
+                            (declaration.important, selector.specificity(), order, i),
+                            declaration,
+                        )
+                    })
+            })
+            .collect::<Vec<_>>();
+        matched.sort_by_key(|(priority, _)| std::cmp::Reverse(*priority));
+        matched
+            .into_iter()
+            .map(|(_, declaration)| declaration)
+            .collect()
+    }
+}
+
+/// Length of a `{}` block at the start of `css`, including any blocks nested in it
+fn block_len(css: &str) -> usize {
+    let mut depth = 0;
+    for (i, c) in css.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return i + 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    css.len()
+}
+
+fn strip_comments(css: &str) -> String {
+    let mut stripped = String::with_capacity(css.len());
+    let mut rest = css;
+    while let Some((before, after)) = rest.split_once("/*") {
+        stripped.push_str(before);
+        rest = after.split_once("*/").map_or("", |(_, after)| after);
+    }
+    stripped.push_str(rest);
+    stripped
+}
+
+/// The SVG of a document with the declarations of its `<style>` sheets added to the `style` of
+/// each element they apply to, or `None` if there are no rules to add
+///
+/// The `style` attribute of an element wins over the rules, except for `!important` ones, just as
+/// in a browser. Presentation attributes (i.e. `stroke="red"`) lose to both.
+pub fn inline_styles(doc: &Document) -> Option<String> {
+    let css = doc
+        .descendants()
+        .filter(|node| node.has_tag_name("style"))
+        .flat_map(|style| style.children())
+        .filter_map(|text| text.text())
+        .collect::<String>();
+    let css = strip_comments(&css);
+    let sheet = StyleSheet::parse(&css);
+    if sheet.rules.is_empty() {
+        return None;
+    }
+
+    let text = doc.input_text();
+    let mut edits: Vec<(Range<usize>, String)> = vec![];
+    for node in doc.descendants().filter(Node::is_element) {
+        let declarations = sheet.declarations(node);
+        if declarations.is_empty() {
+            continue;
+        }
+        let (important, normal): (Vec<_>, Vec<_>) = declarations
+            .into_iter()
+            .partition(|declaration| declaration.important);
+        // Properties are read from the first declaration that sets them
+        let style = important
+            .iter()
+            .map(|declaration| declaration.to_style())
+            .chain(node.attribute("style").map(str::to_string))
+            .chain(normal.iter().map(|declaration| declaration.to_style()))
+            .collect::<Vec<_>>()
+            .join(";");
+        let style = format!("style=\"{}\"", escape(&style));
+        let inline = node
+            .attributes()
+            .find(|attr| attr.name() == "style" && attr.namespace().is_none());
+        match inline {
+            Some(attr) => {
+                let Some(range) = attribute_range(text, attr.position()) else {
+                    continue;
+                };
+                edits.push((range, style));
+            }
+            None => {
+                let name_start = node.range().start + 1;
+                let Some(name_len) =
+                    text[name_start..].find(|c: char| c.is_whitespace() || c == '/' || c == '>')
+                else {
+                    continue;
+                };
+                let at = name_start + name_len;
+                edits.push((at..at, format!(" {style}")));
+            }
+        }
+    }
+
+    let mut svg = String::with_capacity(text.len());
+    let mut copied = 0;
+    for (range, replacement) in edits {
+        svg.push_str(&text[copied..range.start]);
+        svg.push_str(&replacement);
+        copied = range.end;
+    }
+    svg.push_str(&text[copied..]);
+    Some(svg)
+}
+
+/// Where an attribute starting at `position` ends, after the quote closing its value
+fn attribute_range(text: &str, position: usize) -> Option<Range<usize>> {
+    let equals = position + text[position..].find('=')?;
+    let quote_at = equals + 1 + text[equals + 1..].find(['"', '\''])?;
+    let quote = text[quote_at..].chars().next()?;
+    let end = quote_at + 1 + text[quote_at + 1..].find(quote)?;
+    Some(position..end + 1)
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn style_sheet_rules_are_added_to_the_style_of_elements() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg">
+            <style><![CDATA[
+                /* Exported by a design tool */
+                @import url(fonts.css);
+                @media print { path { stroke: black } }
+                path { stroke: blue; stroke-width: 2 }
+                .cut, a:hover { stroke: red }
+                #logo .outline { fill: none !important }
+                path.engrave { stroke: green }
+            ]]></style>
+            <g id="logo">
+                <path id="outline" class="outline cut" fill="black" d="M 0 0 L 1 1"/>
+                <path class="engrave" style="stroke: purple" d="M 0 0 L 1 1"/>
+            </g>
+            <path class="outline" d="M 0 0 L 1 1"/>
+        </svg>"#;
+        let document = Document::parse(svg).unwrap();
+        let inlined = inline_styles(&document).unwrap();
+        let inlined = Document::parse(&inlined).unwrap();
+        let styles = inlined
+            .descendants()
+            .filter(|node| node.has_tag_name("path"))
+            .map(|path| path.attribute("style").unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            styles,
+            [
+                "fill:none;stroke:red;stroke-width:2;stroke:blue",
+                "stroke: purple;stroke:green;stroke-width:2;stroke:blue",
+                "stroke-width:2;stroke:blue",
+            ]
+        );
+        assert_eq!(
+            inlined
+                .descendants()
+                .find(|node| node.has_tag_name("path"))
+                .unwrap()
+                .attribute("id"),
+            Some("outline")
+        );
+        assert!(inline_styles(&Document::parse("<svg/>").unwrap()).is_none());
+    }
+}
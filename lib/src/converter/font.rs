@@ -0,0 +1,722 @@
+use std::{collections::BTreeMap, fmt::Debug};
+
+use base64::Engine;
+use euclid::default::{Point2D as Point, Transform2D, Vector2D as Vector};
+use log::warn;
+use roxmltree::{Document, Node};
+use rustybuzz::UnicodeBuffer;
+use svgtypes::PathSegment;
+use ttf_parser::{name_id, Face, GlyphId, Language, OutlineBuilder};
+
+use super::{stroke_font::StrokeFont, text::Direction, UNSUPPORTED};
+
+/// A font file and the family it provides
+#[derive(Clone, PartialEq, Hash)]
+pub struct FontFace {
+    pub family: String,
+    /// TrueType or OpenType font data
+    pub data: Vec<u8>,
+}
+
+impl Debug for FontFace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FontFace")
+            .field("family", &self.family)
+            .field("data", &format_args!("{} bytes", self.data.len()))
+            .finish()
+    }
+}
+
+/// Families drawn with one of the [`StrokeFont::bundled`] fonts when there is no other font for
+/// them
+const BUNDLED_SUBSTITUTIONS: [(&str, &str); 8] = [
+    ("sans-serif", "sans"),
+    ("Arial", "sans"),
+    ("Helvetica", "sans"),
+    ("Verdana", "sans"),
+    ("serif", "serif"),
+    ("Times New Roman", "serif"),
+    ("Times", "serif"),
+    ("Georgia", "serif"),
+];
+
+/// Fonts available for text, in order of preference
+///
+/// Faces embedded in the SVG with `@font-face` are always preferred over those added here.
+/// [`StrokeFont`]s are preferred over faces, since they draw each glyph once. Families that no
+/// font was added for fall back to the [`StrokeFont::bundled`] fonts by name, or by
+/// [`BUNDLED_SUBSTITUTIONS`] for the generic and common families.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct FontLibrary {
+    faces: Vec<FontFace>,
+    stroke_fonts: Vec<StrokeFont>,
+}
+
+impl FontLibrary {
+    /// Add a TrueType or OpenType font file, named by the family in its `name` table
+    ///
+    /// Returns the family, or [`None`] if the file could not be read.
+    pub fn load(&mut self, data: Vec<u8>) -> Option<&str> {
+        let family = font_family_name(&data)?;
+        self.faces.push(FontFace { family, data });
+        self.faces.last().map(|face| face.family.as_str())
+    }
+
+    /// The family in the `name` table of a TrueType or OpenType font file, as [`Self::load`] names
+    /// it
+    pub fn family_name(data: &[u8]) -> Option<String> {
+        font_family_name(data)
+    }
+
+    /// Every family the `<text>` elements of a document ask for, for only adding fonts that are
+    /// used
+    pub fn requested_families(doc: &Document) -> Vec<String> {
+        let mut families = vec![];
+        for family in doc
+            .descendants()
+            .filter(|node| node.has_tag_name("text"))
+            .flat_map(font_families)
+        {
+            if !families
+                .iter()
+                .any(|added: &String| added.eq_ignore_ascii_case(&family))
+            {
+                families.push(family);
+            }
+        }
+        families
+    }
+
+    pub fn insert(&mut self, face: FontFace) {
+        self.faces.push(face);
+    }
+
+    pub fn insert_stroke_font(&mut self, font: StrokeFont) {
+        self.stroke_fonts.push(font);
+    }
+
+    /// The faces added, in the order they were added
+    pub fn faces(&self) -> &[FontFace] {
+        &self.faces
+    }
+
+    /// The single-stroke fonts added, in the order they were added
+    pub fn stroke_fonts(&self) -> &[StrokeFont] {
+        &self.stroke_fonts
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.faces.is_empty() && self.stroke_fonts.is_empty()
+    }
+
+    /// Find the first of the families that has a single-stroke font, after substituting
+    /// families with the names of their single-stroke equivalents
+    ///
+    /// If none do and none of the families have a face either, the first that a bundled font
+    /// stands in for is drawn with it.
+    pub fn resolve_stroke_font(
+        &self,
+        families: &[String],
+        substitutions: &BTreeMap<String, String>,
+    ) -> Option<&StrokeFont> {
+        let substituted = |family: &String| {
+            substitutions
+                .iter()
+                .find(|(substituted, _)| substituted.eq_ignore_ascii_case(family))
+                .map(|(_, name)| name.as_str())
+        };
+        let added = families.iter().find_map(|family| {
+            let name = substituted(family).unwrap_or(family);
+            self.stroke_fonts
+                .iter()
+                .find(|font| font.name.eq_ignore_ascii_case(name))
+        });
+        let has_face = || {
+            families.iter().any(|family| {
+                self.faces
+                    .iter()
+                    .any(|face| face.family.eq_ignore_ascii_case(family))
+            })
+        };
+        if added.is_some() || has_face() {
+            return added;
+        }
+        families.iter().find_map(|family| {
+            let name = substituted(family)
+                .or_else(|| {
+                    BUNDLED_SUBSTITUTIONS
+                        .iter()
+                        .find(|(substituted, _)| substituted.eq_ignore_ascii_case(family))
+                        .map(|(_, name)| *name)
+                })
+                .unwrap_or(family);
+            StrokeFont::bundled()
+                .iter()
+                .find(|font| font.name.eq_ignore_ascii_case(name))
+        })
+    }
+
+    /// Put faces from `@font-face` rules in the document ahead of all others
+    pub(crate) fn embed(&mut self, doc: &Document) {
+        self.faces.splice(0..0, embedded_font_faces(doc));
+    }
+
+    /// Find the first of the families that is available, else fall back to the most preferred face
+    pub fn resolve(&self, families: &[String]) -> Option<&FontFace> {
+        let face = families.iter().find_map(|family| {
+            self.faces
+                .iter()
+                .find(|face| face.family.eq_ignore_ascii_case(family))
+        });
+        if face.is_some() {
+            return face;
+        }
+        let fallback = self.faces.first();
+        match fallback {
+            Some(fallback) => warn!(
+                target: UNSUPPORTED,
+                "None of the font families {families:?} are available, falling back to {}",
+                fallback.family
+            ),
+            None => warn!(
+                target: UNSUPPORTED,
+                "None of the font families {families:?} are available"
+            ),
+        }
+        fallback
+    }
+}
+
+/// The inherited `font-family` list of a node, from its attribute or style
+pub fn font_families(node: Node) -> Vec<String> {
+    node.ancestors()
+        .find_map(|ancestor| {
+            ancestor.attribute("font-family").or_else(|| {
+                ancestor
+                    .attribute("style")?
+                    .split(';')
+                    .find_map(|declaration| {
+                        let (property, value) = declaration.split_once(':')?;
+                        (property.trim() == "font-family").then_some(value)
+                    })
+            })
+        })
+        .map(|list| {
+            list.split(',')
+                .map(|family| family.trim().trim_matches(['"', '\'']).to_string())
+                .filter(|family| !family.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Glyphs of the font resolved for a `<text>` element, measured in ems with the y-axis pointing
+/// down
+pub enum Glyphs<'a> {
+    Stroke(&'a StrokeFont),
+    Outline(Box<rustybuzz::Face<'a>>),
+}
+
+/// A glyph of [`Glyphs`] to draw
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Glyph {
+    /// The glyph of a character in a single-stroke font
+    Char(char),
+    /// A glyph of a font face, picked by shaping
+    Id(u16),
+}
+
+/// A glyph shaped from a run of characters
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShapedGlyph {
+    /// [`None`] if the font has no glyph for the character
+    pub glyph: Option<Glyph>,
+    /// Index in the run of the first character the glyph was shaped from
+    pub cluster: usize,
+    /// Horizontal distance to the next glyph
+    pub advance: f64,
+    /// Shift from where the glyph would otherwise be drawn, with the y-axis pointing down
+    pub offset: Vector<f64>,
+}
+
+/// One glyph for each character of a run, from left to right, without kerning or ligatures
+pub fn unshaped(
+    run: &[char],
+    direction: Direction,
+    advance: impl Fn(char) -> Option<f64>,
+) -> Vec<ShapedGlyph> {
+    let mut glyphs = run
+        .iter()
+        .enumerate()
+        .map(|(cluster, c)| {
+            let advance = advance(*c);
+            ShapedGlyph {
+                glyph: advance.map(|_| Glyph::Char(*c)),
+                cluster,
+                advance: advance.unwrap_or(0.),
+                offset: Vector::zero(),
+            }
+        })
+        .collect::<Vec<_>>();
+    if direction == Direction::RightToLeft {
+        glyphs.reverse();
+    }
+    glyphs
+}
+
+impl<'a> Glyphs<'a> {
+    /// Single-stroke fonts are preferred, as in [`FontLibrary`]
+    pub fn resolve(
+        fonts: &'a FontLibrary,
+        families: &[String],
+        substitutions: &BTreeMap<String, String>,
+    ) -> Option<Self> {
+        if let Some(font) = fonts.resolve_stroke_font(families, substitutions) {
+            return Some(Self::Stroke(font));
+        }
+        let face = fonts.resolve(families)?;
+        match Face::parse(&face.data, 0) {
+            Ok(parsed) => Some(Self::Outline(Box::new(rustybuzz::Face::from_face(parsed)))),
+            Err(err) => {
+                warn!(target: UNSUPPORTED, "Could not read font {}: {err}", face.family);
+                None
+            }
+        }
+    }
+
+    /// Glyphs of a run of characters at the same embedding level, from left to right, in ems
+    ///
+    /// Font faces are shaped for their kerning, ligatures, and the forms of joining scripts,
+    /// while single-stroke fonts have a glyph for each character.
+    pub fn shape(&self, run: &[char], direction: Direction) -> Vec<ShapedGlyph> {
+        let face = match self {
+            Self::Stroke(font) => return unshaped(run, direction, |c| font.advance(c)),
+            Self::Outline(face) => face,
+        };
+        let text = run.iter().collect::<String>();
+        let mut buffer = UnicodeBuffer::new();
+        buffer.push_str(&text);
+        buffer.set_direction(match direction {
+            Direction::LeftToRight => rustybuzz::Direction::LeftToRight,
+            Direction::RightToLeft => rustybuzz::Direction::RightToLeft,
+        });
+        let shaped = rustybuzz::shape(face, &[], buffer);
+        // Clusters are byte offsets into the text
+        let starts = text.char_indices().map(|(at, _)| at).collect::<Vec<_>>();
+        let em = f64::from(face.units_per_em());
+        shaped
+            .glyph_infos()
+            .iter()
+            .zip(shaped.glyph_positions())
+            .map(|(info, position)| ShapedGlyph {
+                // Characters missing from the font are shaped as the .notdef glyph
+                glyph: (info.glyph_id != 0).then_some(Glyph::Id(info.glyph_id as u16)),
+                cluster: starts
+                    .binary_search(&(info.cluster as usize))
+                    .unwrap_or_else(|i| i.saturating_sub(1)),
+                advance: f64::from(position.x_advance) / em,
+                offset: Vector::new(f64::from(position.x_offset), -f64::from(position.y_offset))
+                    / em,
+            })
+            .collect()
+    }
+
+    /// Path drawing a glyph, mapped from ems to user units by `transform`
+    ///
+    /// Single-stroke glyphs are open polylines, while outlines are closed and can be filled.
+    pub fn path(&self, glyph: Glyph, transform: &Transform2D<f64>) -> Vec<PathSegment> {
+        match (self, glyph) {
+            (Self::Stroke(font), Glyph::Char(c)) => font
+                .strokes(c, transform)
+                .into_iter()
+                .flat_map(|stroke| {
+                    stroke.into_iter().enumerate().map(|(i, p)| {
+                        if i == 0 {
+                            PathSegment::MoveTo {
+                                abs: true,
+                                x: p.x,
+                                y: p.y,
+                            }
+                        } else {
+                            PathSegment::LineTo {
+                                abs: true,
+                                x: p.x,
+                                y: p.y,
+                            }
+                        }
+                    })
+                })
+                .collect(),
+            (Self::Outline(face), Glyph::Id(id)) => {
+                let mut outline = Outline {
+                    transform: Transform2D::scale(
+                        1. / f64::from(face.units_per_em()),
+                        -1. / f64::from(face.units_per_em()),
+                    )
+                    .then(transform),
+                    path: vec![],
+                };
+                face.outline_glyph(GlyphId(id), &mut outline);
+                outline.path
+            }
+            _ => vec![],
+        }
+    }
+}
+
+/// Collects the outline of a glyph in font units as a path in user units
+struct Outline {
+    transform: Transform2D<f64>,
+    path: Vec<PathSegment>,
+}
+
+impl Outline {
+    fn point(&self, x: f32, y: f32) -> Point<f64> {
+        self.transform
+            .transform_point(Point::new(f64::from(x), f64::from(y)))
+    }
+}
+
+impl OutlineBuilder for Outline {
+    fn move_to(&mut self, x: f32, y: f32) {
+        let p = self.point(x, y);
+        self.path.push(PathSegment::MoveTo {
+            abs: true,
+            x: p.x,
+            y: p.y,
+        });
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        let p = self.point(x, y);
+        self.path.push(PathSegment::LineTo {
+            abs: true,
+            x: p.x,
+            y: p.y,
+        });
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        let (p1, p) = (self.point(x1, y1), self.point(x, y));
+        self.path.push(PathSegment::Quadratic {
+            abs: true,
+            x1: p1.x,
+            y1: p1.y,
+            x: p.x,
+            y: p.y,
+        });
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        let (p1, p2, p) = (self.point(x1, y1), self.point(x2, y2), self.point(x, y));
+        self.path.push(PathSegment::CurveTo {
+            abs: true,
+            x1: p1.x,
+            y1: p1.y,
+            x2: p2.x,
+            y2: p2.y,
+            x: p.x,
+            y: p.y,
+        });
+    }
+
+    fn close(&mut self) {
+        self.path.push(PathSegment::ClosePath { abs: true });
+    }
+}
+
+/// Fonts embedded as base64 `data:` URLs in `@font-face` rules of `<style>` elements
+fn embedded_font_faces(doc: &Document) -> Vec<FontFace> {
+    doc.descendants()
+        .filter(|node| node.has_tag_name("style"))
+        .filter_map(|style| style.text())
+        .flat_map(|css| css.split("@font-face").skip(1))
+        .filter_map(|rule| {
+            let block = rule.split_once('{')?.1.split_once('}')?.0;
+            let family = block
+                .split_once("font-family")?
+                .1
+                .split_once(':')?
+                .1
+                .split(';')
+                .next()?
+                .trim()
+                .trim_matches(['"', '\''])
+                .to_string();
+            let url = block.split_once("url(")?.1.split_once(')')?.0;
+            let encoded = url
+                .trim()
+                .trim_matches(['"', '\''])
+                .strip_prefix("data:")?
+                .split_once(";base64,")?
+                .1;
+            match base64::engine::general_purpose::STANDARD.decode(encoded.trim()) {
+                Ok(data) => Some(FontFace { family, data }),
+                Err(err) => {
+                    warn!(target: UNSUPPORTED, "Could not decode embedded font {family}: {err}");
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// Read the family name from the `name` table of a TrueType/OpenType font, or the first font of
+/// a collection
+///
+/// The typographic family is preferred, since it groups more than the four basic styles. Names in
+/// US English are preferred over other languages, and those not encoded in Unicode are skipped.
+///
+/// <https://learn.microsoft.com/en-us/typography/opentype/spec/name>
+fn font_family_name(data: &[u8]) -> Option<String> {
+    let face = Face::parse(data, 0).ok()?;
+    [name_id::TYPOGRAPHIC_FAMILY, name_id::FAMILY]
+        .into_iter()
+        .find_map(|wanted| {
+            let names = face
+                .names()
+                .into_iter()
+                .filter(|name| name.name_id == wanted && name.is_unicode())
+                .filter_map(|name| Some((name.language(), name.to_string()?)))
+                .filter(|(_, name)| !name.is_empty())
+                .collect::<Vec<_>>();
+            names
+                .iter()
+                .find(|(language, _)| *language == Language::English_UnitedStates)
+                .or(names.first())
+                .map(|(_, name)| name.clone())
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn be(values: &[u16]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_be_bytes()).collect()
+    }
+
+    /// A font file with a table directory for the tables, sorted by tag
+    fn font_file(tables: Vec<(&[u8; 4], Vec<u8>)>) -> Vec<u8> {
+        let mut data = be(&[1, 0, tables.len() as u16, 0, 0, 0]);
+        let mut offset = 12 + 16 * tables.len();
+        for (tag, table) in &tables {
+            data.extend(*tag);
+            data.extend([0; 4]);
+            data.extend((offset as u32).to_be_bytes());
+            data.extend((table.len() as u32).to_be_bytes());
+            offset += table.len();
+        }
+        for (_, table) in tables {
+            data.extend(table);
+        }
+        data
+    }
+
+    /// The `head`, `hhea`, and `maxp` tables every font needs, for a font of `glyphs` glyphs
+    fn required_tables(glyphs: u16) -> [(&'static [u8; 4], Vec<u8>); 3] {
+        let mut head = vec![0; 54];
+        head[0..4].copy_from_slice(&0x10000u32.to_be_bytes());
+        head[18..20].copy_from_slice(&1000u16.to_be_bytes());
+        let mut hhea = vec![0; 36];
+        hhea[0..4].copy_from_slice(&0x10000u32.to_be_bytes());
+        hhea[34..36].copy_from_slice(&glyphs.to_be_bytes());
+        [
+            (b"head", head),
+            (b"hhea", hhea),
+            (b"maxp", be(&[0x0000, 0x5000, glyphs])),
+        ]
+    }
+
+    /// A font without glyphs for the family "Test Sans", which is named "Testschrift" in German
+    fn font() -> Vec<u8> {
+        let names = [(0x0407, "Testschrift"), (0x0409, "Test Sans")]
+            .map(|(language, name)| (language, name.encode_utf16().collect::<Vec<_>>()));
+        // Family names (name ID 1) on the Windows platform in Unicode BMP
+        let mut name = be(&[0, names.len() as u16, 6 + 12 * names.len() as u16]);
+        let mut offset = 0;
+        for (language, family) in &names {
+            name.extend(be(&[3, 1, *language, 1, 2 * family.len() as u16, offset]));
+            offset += 2 * family.len() as u16;
+        }
+        for (_, family) in &names {
+            name.extend(be(family));
+        }
+        let [head, hhea, maxp] = required_tables(1);
+        font_file(vec![head, hhea, maxp, (b"name", name)])
+    }
+
+    #[test]
+    fn font_family_is_read_from_name_table() {
+        let mut library = FontLibrary::default();
+        // The US English name is preferred over the German one listed before it
+        assert_eq!(library.load(font()), Some("Test Sans"));
+        assert_eq!(library.load(vec![0; 4]), None);
+    }
+
+    #[test]
+    fn embedded_fonts_are_preferred() {
+        let svg = format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg">
+                <style>@font-face {{ font-family: "Embedded"; src: url("data:font/ttf;base64,{}"); }}</style>
+                <g style="fill:none;font-family: 'Missing', Test Sans, serif"><text/></g>
+            </svg>"#,
+            base64::engine::general_purpose::STANDARD.encode(font())
+        );
+        let document = Document::parse(&svg).unwrap();
+        let text = document
+            .descendants()
+            .find(|node| node.has_tag_name("text"))
+            .unwrap();
+
+        let families = font_families(text);
+        assert_eq!(families, ["Missing", "Test Sans", "serif"]);
+        assert_eq!(FontLibrary::requested_families(&document), families);
+
+        let mut library = FontLibrary::default();
+        assert_eq!(library.resolve(&families), None);
+        library.load(font());
+        library.embed(&document);
+        assert_eq!(
+            library.resolve(&families).map(|face| face.family.as_str()),
+            Some("Test Sans")
+        );
+        assert_eq!(
+            library
+                .resolve(&["Other".to_string()])
+                .map(|face| face.family.as_str()),
+            Some("Embedded")
+        );
+    }
+
+    #[test]
+    fn families_are_substituted_with_stroke_fonts() {
+        let mut library = FontLibrary::default();
+        library.load(font());
+        library.insert_stroke_font(StrokeFont::from_jhf("rowmans", "12345  1JZ", ' ').unwrap());
+        let substitutions = BTreeMap::from([("arial".to_string(), "rowmans".to_string())]);
+
+        assert_eq!(
+            library
+                .resolve_stroke_font(&["Test Sans".to_string()], &substitutions)
+                .map(|font| font.name.as_str()),
+            None
+        );
+        assert_eq!(
+            library
+                .resolve_stroke_font(
+                    &["Test Sans".to_string(), "Arial".to_string()],
+                    &substitutions
+                )
+                .map(|font| font.name.as_str()),
+            Some("rowmans")
+        );
+        assert_eq!(
+            library
+                .resolve_stroke_font(&["RowmanS".to_string()], &BTreeMap::new())
+                .map(|font| font.name.as_str()),
+            Some("rowmans")
+        );
+    }
+
+    #[test]
+    fn bundled_stroke_fonts_stand_in_for_common_families() {
+        let mut library = FontLibrary::default();
+        let resolve = |library: &FontLibrary, family: &str, substitutions: &[(&str, &str)]| {
+            let substitutions = substitutions
+                .iter()
+                .map(|(family, font)| (family.to_string(), font.to_string()))
+                .collect();
+            library
+                .resolve_stroke_font(&[family.to_string()], &substitutions)
+                .map(|font| font.name.clone())
+        };
+
+        assert_eq!(resolve(&library, "Arial", &[]).as_deref(), Some("sans"));
+        assert_eq!(resolve(&library, "serif", &[]).as_deref(), Some("serif"));
+        assert_eq!(resolve(&library, "Comic Sans", &[]), None);
+        assert_eq!(
+            resolve(&library, "Comic Sans", &[("comic sans", "serif")]).as_deref(),
+            Some("serif")
+        );
+
+        // A face for the family is drawn instead
+        library.insert(FontFace {
+            family: "Arial".to_string(),
+            data: font(),
+        });
+        assert_eq!(resolve(&library, "Arial", &[]), None);
+    }
+
+    /// A font face with glyphs for "AVfi", where "AV" is kerned and "fi" is a ligature
+    fn shaping_font() -> Vec<u8> {
+        // .notdef, A, V, f, i, and the fi ligature
+        let [head, hhea, maxp] = required_tables(6);
+        let hmtx = be(&[500, 0, 600, 0, 600, 0, 300, 0, 250, 0, 500, 0]);
+        // Format 12 subtable for the Windows Unicode full repertoire
+        let mut cmap = be(&[0, 1, 3, 10, 0, 12, 12, 0, 0, 64, 0, 0, 0, 4]);
+        for (c, id) in [('A', 1), ('V', 2), ('f', 3), ('i', 4)] {
+            cmap.extend(be(&[0, c as u16, 0, c as u16, 0, id]));
+        }
+        // Format 0 subtable moving V 80 units closer to A
+        let kern = be(&[0, 1, 0, 20, 0x0001, 1, 6, 0, 0, 1, 2, (-80i16) as u16]);
+        // A default script with one `liga` feature for the lookup substituting f, i
+        let gsub = be(&[
+            1, 0, 10, 30, 44, // header
+            1, 0x4446, 0x4c54, 8, 4, 0, 0, 0xFFFF, 1, 0, // scripts
+            1, 0x6c69, 0x6761, 8, 0, 1, 0, // features
+            1, 4, 4, 0, 1, 8, // lookups
+            1, 8, 1, 14, 1, 1, 3, 1, 4, 5, 2, 4, // ligature substitution
+        ]);
+        font_file(vec![
+            (b"GSUB", gsub),
+            (b"cmap", cmap),
+            head,
+            hhea,
+            (b"hmtx", hmtx),
+            (b"kern", kern),
+            maxp,
+        ])
+    }
+
+    #[test]
+    fn font_faces_are_kerned_and_ligated() {
+        let data = shaping_font();
+        let glyphs = Glyphs::Outline(Box::new(rustybuzz::Face::from_face(
+            Face::parse(&data, 0).unwrap(),
+        )));
+        let shaped = |text: &str, direction| {
+            glyphs
+                .shape(&text.chars().collect::<Vec<_>>(), direction)
+                .into_iter()
+                .map(|shaped| {
+                    (
+                        shaped.glyph,
+                        shaped.cluster,
+                        shaped.advance,
+                        shaped.offset.x,
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // The kerning is split between the pair, so V is 0.08 closer to A
+        assert_eq!(
+            shaped("AVfi", Direction::LeftToRight),
+            [
+                (Some(Glyph::Id(1)), 0, 0.56, 0.),
+                (Some(Glyph::Id(2)), 1, 0.56, -0.04),
+                (Some(Glyph::Id(5)), 2, 0.5, 0.),
+            ]
+        );
+        // Right-to-left glyphs are still given from left to right
+        assert_eq!(
+            shaped("iAx", Direction::RightToLeft),
+            [
+                (None, 2, 0.5, 0.),
+                (Some(Glyph::Id(1)), 1, 0.6, 0.),
+                (Some(Glyph::Id(4)), 0, 0.25, 0.),
+            ]
+        );
+    }
+}
@@ -0,0 +1,264 @@
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    fmt::{self, Display},
+};
+
+use g_code::{
+    emit::Token,
+    parse::{snippet_parser, ParseError},
+};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use log::warn;
+
+use super::ConversionConfig;
+use crate::{CutDirection, SourceElement, Toolpath};
+
+/// Settings for the paths of one layer that differ from the rest of the document, i.e. to engrave
+/// one layer and cut through another
+///
+/// Anything left unset is the same as for the rest of the document.
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct LayerOverride {
+    /// Feedrate in millimeters / minute of every pass, instead of [`ConversionConfig::feedrate`]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub feedrate: Option<f64>,
+    /// Tool power of every pass, instead of [`ConversionConfig::pass_power`]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub power: Option<f64>,
+    /// Number of times to draw the layer, instead of [`ConversionConfig::passes`]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub passes: Option<usize>,
+    /// Name of the tool in [`ConversionConfig::tools`] to cut the layer with, instead of
+    /// [`ConversionConfig::tool`]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub tool: Option<String>,
+    /// Depth in millimeters to engrave the layer into the material, instead of
+    /// [`ConversionConfig::engrave_depth`], 0 to cut through it
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub engrave_depth: Option<f64>,
+    /// Which way to go around closed paths in the layer, instead of
+    /// [`ConversionConfig::cut_direction`]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub cut_direction: Option<CutDirection>,
+    /// Turns the tool on for the layer, instead of [`crate::MachineConfig::tool_on_sequence`]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub tool_on_sequence: Option<String>,
+    /// Turns the tool off for the layer, instead of [`crate::MachineConfig::tool_off_sequence`]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub tool_off_sequence: Option<String>,
+}
+
+/// Settings of each layer that has its own, by the name of the layer, see
+/// [`crate::SourceElement::layer`]
+pub type LayerOverrides = BTreeMap<String, LayerOverride>;
+
+/// Attributes of an element with the settings it asks for, see [`LayerOverride::from_element`]
+const ELEMENT_ATTRIBUTES: [&str; 3] = ["data-feedrate", "data-power", "data-passes"];
+
+fn is_positive(value: &f64) -> bool {
+    value.is_finite() && *value > 0.
+}
+
+fn is_non_negative(value: &f64) -> bool {
+    value.is_finite() && *value >= 0.
+}
+
+/// 0 passes would leave the paths out of the program
+fn is_positive_count(value: &usize) -> bool {
+    *value > 0
+}
+
+impl LayerOverride {
+    /// Settings an element asks for with `data-feedrate`, `data-power`, and `data-passes`
+    /// attributes, on it or a group it is in, which take precedence over those of its layer and
+    /// its tool
+    ///
+    /// Values that can't be read, or would draw nothing (i.e. 0 passes), are warned about and
+    /// left unset.
+    pub(crate) fn from_element(source: &SourceElement) -> Self {
+        fn parse<T: std::str::FromStr>(
+            source: &SourceElement,
+            name: &str,
+            is_valid: impl Fn(&T) -> bool,
+            expected: &str,
+        ) -> Option<T> {
+            let value = source.attribute(name)?;
+            let parsed = value.trim().parse().ok().filter(is_valid);
+            if parsed.is_none() {
+                warn!("Ignoring {name}=\"{value}\", which is not {expected}");
+            }
+            parsed
+        }
+        let [feedrate, power, passes] = ELEMENT_ATTRIBUTES;
+        Self {
+            feedrate: parse(source, feedrate, is_positive, "a number over 0"),
+            power: parse(source, power, is_non_negative, "a number of at least 0"),
+            passes: parse(source, passes, is_positive_count, "a whole number over 0"),
+            ..Default::default()
+        }
+    }
+
+    /// The settings of the layer `name` that can be used, warning about and leaving out those
+    /// that can't, i.e. 0 passes, which would leave the layer out of the program
+    pub(crate) fn checked(&self, name: &str) -> Self {
+        let mut checked = self.clone();
+        let ignore = |setting: &str, value: &dyn Display, expected: &str| {
+            warn!("Ignoring the {setting} of layer {name}, {value}, which is not {expected}");
+        };
+        if let Some(feedrate) = self.feedrate.filter(|f| !is_positive(f)) {
+            ignore("feedrate", &feedrate, "a number over 0");
+            checked.feedrate = None;
+        }
+        if let Some(power) = self.power.filter(|p| !is_non_negative(p)) {
+            ignore("power", &power, "a number of at least 0");
+            checked.power = None;
+        }
+        if let Some(passes) = self.passes.filter(|p| !is_positive_count(p)) {
+            ignore("passes", &passes, "a whole number over 0");
+            checked.passes = None;
+        }
+        if let Some(depth) = self.engrave_depth.filter(|d| !is_non_negative(d)) {
+            ignore("engrave depth", &depth, "a number of at least 0");
+            checked.engrave_depth = None;
+        }
+        for (setting, sequence) in [
+            ("tool on sequence", &mut checked.tool_on_sequence),
+            ("tool off sequence", &mut checked.tool_off_sequence),
+        ] {
+            if let Some(Err(err)) = sequence.as_deref().map(parse_layer_sequence) {
+                warn!("Using the machine's {setting} for layer {name} instead of its own, which {err}");
+                *sequence = None;
+            }
+        }
+        checked
+    }
+
+    /// The config for drawing the layer
+    pub(crate) fn apply(&self, config: &ConversionConfig) -> ConversionConfig {
+        let mut config = config.clone();
+        if let Some(feedrate) = self.feedrate {
+            config.feedrate = feedrate;
+            config.pass_feedrate.clear();
+        }
+        if let Some(power) = self.power {
+            config.pass_power = vec![power];
+        }
+        if let Some(passes) = self.passes {
+            config.passes = passes;
+        }
+        if let Some(tool) = &self.tool {
+            config.tool = Some(tool.clone());
+        }
+        if let Some(engrave_depth) = self.engrave_depth {
+            config.engrave_depth = engrave_depth;
+        }
+        if let Some(cut_direction) = self.cut_direction {
+            config.cut_direction = cut_direction;
+        }
+        config
+    }
+}
+
+/// Why a tool sequence of a layer can't be used, see [`parse_layer_sequence`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum LayerSequenceError {
+    Parse(ParseError),
+    /// A word with no value (i.e. the `X` of `G28 X`), which can only be written out while the
+    /// sequence it was read from is still around
+    Flag(String),
+}
+
+impl Display for LayerSequenceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Parse(err) => write!(f, "could not be parsed: {err}"),
+            Self::Flag(letter) => write!(f, "has {letter} with no value, which layers can't use"),
+        }
+    }
+}
+
+/// Parse a tool sequence of a layer into tokens that don't borrow from it, since the settings of
+/// the layer may be gone before the program is written
+pub fn parse_layer_sequence(source: &str) -> Result<Vec<Token<'static>>, LayerSequenceError> {
+    snippet_parser(source)
+        .map_err(LayerSequenceError::Parse)?
+        .iter_emit_tokens()
+        .map(|token| match token {
+            Token::Field(field) => Ok(Token::Field(field.into_owned())),
+            Token::Flag(flag) => Err(LayerSequenceError::Flag(flag.letter.into_owned())),
+            Token::Comment { is_inline, inner } => Ok(Token::Comment {
+                is_inline,
+                inner: Cow::Owned(inner.into_owned()),
+            }),
+        })
+        .collect()
+}
+
+/// Name and settings of a layer in [`LayerOverrides`]
+pub(crate) type NamedOverride<'a> = (&'a String, &'a LayerOverride);
+
+/// Toolpaths drawn with the same settings: those of their layer, if it has its own, and then
+/// those the elements they were drawn from ask for, see [`LayerOverride::from_element`]
+pub(crate) type Group<'a> = (Option<NamedOverride<'a>>, LayerOverride, Vec<Toolpath>);
+
+/// The config for drawing a [`Group`]: `config` with the settings of its layer, then those of the
+/// tool it picks, if [`ConversionConfig::tools`] has it, and then those of its elements
+pub(crate) fn group_config<'a>(
+    config: &'a ConversionConfig,
+    layer: Option<NamedOverride<'_>>,
+    element: &LayerOverride,
+) -> Cow<'a, ConversionConfig> {
+    let config = layer.map_or(Cow::Borrowed(config), |(_, layer)| {
+        Cow::Owned(layer.apply(config))
+    });
+    let config = match config.tool() {
+        Some(tool) => Cow::Owned(tool.apply(&config)),
+        None => config,
+    };
+    if *element == LayerOverride::default() {
+        config
+    } else {
+        Cow::Owned(element.apply(&config))
+    }
+}
+
+/// Split toolpaths into the layers that have their own settings, in the order each is first
+/// drawn, and those into runs of toolpaths whose elements ask for the same settings
+///
+/// Toolpaths of every other layer are kept together, with no override. Within a layer, toolpaths
+/// stay in the order they were given (i.e. holes before the outline around them).
+pub(crate) fn by_layer(toolpaths: Vec<Toolpath>, overrides: &LayerOverrides) -> Vec<Group<'_>> {
+    let mut layers: Vec<Group<'_>> = vec![];
+    // Read once for each set of values, so those that can't be are only warned about once
+    let mut elements = BTreeMap::<[Option<String>; 3], LayerOverride>::new();
+    for toolpath in toolpaths {
+        let layer = toolpath
+            .source
+            .as_ref()
+            .and_then(|source| source.layer.as_ref())
+            .and_then(|layer| overrides.get_key_value(layer));
+        let element = match &toolpath.source {
+            Some(source) => elements
+                .entry(ELEMENT_ATTRIBUTES.map(|name| source.attribute(name).map(str::to_string)))
+                .or_insert_with(|| LayerOverride::from_element(source))
+                .clone(),
+            None => LayerOverride::default(),
+        };
+        let last_of_layer = layers
+            .iter()
+            .rposition(|(other, _, _)| other.map(|(name, _)| name) == layer.map(|(name, _)| name));
+        match last_of_layer {
+            Some(last) if layers[last].1 == element => layers[last].2.push(toolpath),
+            Some(last) => layers.insert(last + 1, (layer, element, vec![toolpath])),
+            None => layers.push((layer, element, vec![toolpath])),
+        }
+    }
+    if layers.is_empty() {
+        layers.push((None, LayerOverride::default(), vec![]));
+    }
+    layers
+}
@@ -1,29 +1,79 @@
-use std::fmt::Debug;
+#[cfg(feature = "raster")]
+use std::path::PathBuf;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::{Duration, Instant};
+use std::{borrow::Cow, collections::BTreeMap, fmt::Debug, str::FromStr};
 
 use g_code::emit::Token;
-use lyon_geom::euclid::default::Transform2D;
-use roxmltree::{Document, Node};
+use log::{info, warn};
+use lyon_geom::{
+    euclid::{
+        default::{Box2D, Transform2D},
+        Angle,
+    },
+    point, vector, Point,
+};
+use roxmltree::{Document, Node, NodeId};
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
-use svgtypes::Length;
+pub use svg2gcode_geometry::OrderImprovements;
+use svg2gcode_geometry::{
+    add_tabs, dash, heal, improve_order, join_nearby, order_nearest, perforate, remove_overlaps,
+    stroke_outline,
+};
+use svgtypes::{Color, Length, PathSegment};
 use uom::si::f64::Length as UomLength;
 use uom::si::length::{inch, millimeter};
 
-use self::units::CSS_DEFAULT_DPI;
-use crate::{turtle::*, Machine};
+#[cfg(feature = "text")]
+pub use self::font::{FontFace, FontLibrary};
+use self::layers::{by_layer, group_config};
+pub use self::layers::{parse_layer_sequence, LayerOverride, LayerOverrides, LayerSequenceError};
+pub use self::paint::{PaintFilter, StrokeOutline};
+use self::path::apply_path;
+pub use self::stroke_font::{StrokeFont, StrokeGlyph};
+use self::visit::drawn_ancestors;
+#[cfg(feature = "raster")]
+use crate::RasterImage;
+use crate::{
+    calibration::BacklashCompensation,
+    fill::property,
+    kerf::{compensate_kerf, find_holes, set_cut_direction},
+    lead::add_leads,
+    toolpath::{bounding_box, SourceElement, Toolpath},
+    turtle::*,
+    Alignment, Calibration, CostConfig, CutDirection, EstimateError, Fill, JobEstimate, KerfSide,
+    LeadShape, Machine, Material, PassRegistry, RasterConfig, RegistrationMarks, StrokePower, Tool,
+};
 
+mod css;
+#[cfg(feature = "text")]
+mod font;
+mod layers;
 #[cfg(feature = "serde")]
 mod length_serde;
+mod order;
+mod paint;
 mod path;
+mod stroke_font;
+#[cfg(feature = "text")]
+mod text;
 mod transform;
 mod units;
 mod visit;
 
+/// Log target of the warnings about parts of an SVG that can't be converted as drawn, like a clip
+/// path or text with no font to draw it
+pub const UNSUPPORTED: &str = "svg2gcode::unsupported";
+
 /// High-level output configuration
 #[derive(Debug, Clone, PartialEq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ConversionConfig {
-    /// Curve interpolation tolerance in millimeters
+    /// Greatest distance in millimeters that the lines curves are cut as may stray from them
+    ///
+    /// Curves are split into as few lines as stay within it, so large ones get more lines and
+    /// small ones fewer.
     pub tolerance: f64,
     /// Feedrate in millimeters / minute
     pub feedrate: f64,
@@ -32,11 +82,233 @@ pub struct ConversionConfig {
     /// Set the origin point in millimeters for this conversion
     #[cfg_attr(feature = "serde", serde(default = "zero_origin"))]
     pub origin: [Option<f64>; 2],
+    /// Degrees to turn the artwork counterclockwise before placing it at [`Self::origin`], i.e. to
+    /// fit it into a fixture on the bed
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub rotation: f64,
+    /// Which point of the artwork is placed at [`Self::origin`]
+    ///
+    /// This is here rather than in [`ConversionOptions`], which isn't part of the saved
+    /// [`Settings`](crate::Settings), so that it's kept with the origin it goes with.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub origin_mode: OriginMode,
+    /// Mirror the y-axis, for machines where it points toward the front of the bed (i.e. with the
+    /// origin at the back left), so the artwork isn't cut upside down
+    ///
+    /// Like [`Self::origin_mode`], this depends on the machine rather than the document, so it's
+    /// saved along with the settings.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub invert_y: bool,
     /// Set extra attribute to add when printing node name
     pub extra_attribute_name: Option<String>,
     /// Plotter bed size in millimeters (width, height)
     #[cfg_attr(feature = "serde", serde(default = "default_bed_size"))]
     pub bed_size: [f64; 2],
+    /// Number of times to draw all paths (i.e. to cut through thicker material)
+    #[cfg_attr(feature = "serde", serde(default = "default_passes"))]
+    pub passes: usize,
+    /// Tool power of each pass, available in sequences as `{power}` (i.e. `M3 S{power}`)
+    ///
+    /// The last value is used for any remaining passes.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub pass_power: Vec<f64>,
+    /// Feedrate in millimeters / minute of each pass, instead of [`Self::feedrate`]
+    ///
+    /// The last value is used for any remaining passes.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub pass_feedrate: Vec<f64>,
+    /// Tools to pick from with [`Self::tool`] and [`LayerOverride::tool`]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub tools: Vec<Tool>,
+    /// Name of the tool in [`Self::tools`] to cut with, which sets the passes for the depth of
+    /// [`Self::material`] and limits the feedrate, see [`Tool`]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub tool: Option<String>,
+    /// The stock being cut, which [`Self::tool`] cuts through in as many passes as it takes,
+    /// instead of [`Self::passes`]
+    ///
+    /// Each pass goes deeper by the same amount, available in sequences as `{depth}` below the
+    /// surface and as `{z}`, see [`Self::pass_depth`].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub material: Material,
+    /// Depth in millimeters to engrave into [`Self::material`] instead of cutting through it, 0 to
+    /// cut through
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub engrave_depth: f64,
+    /// Number of uncut gaps left along each closed path to keep parts attached, 0 to disable
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub tab_count: usize,
+    /// Length of each tab in millimeters
+    #[cfg_attr(feature = "serde", serde(default = "default_tab_width"))]
+    pub tab_width: f64,
+    /// Tabs are only left on this many of the final passes, earlier passes cut the full path
+    #[cfg_attr(feature = "serde", serde(default = "default_tab_passes"))]
+    pub tab_passes: usize,
+    /// Length of each cut in millimeters when perforating closed paths, so parts stay attached
+    /// until they are popped out, 0 to disable
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub perforation_cut: f64,
+    /// Length of the uncut gap after each perforation cut in millimeters
+    #[cfg_attr(feature = "serde", serde(default = "default_perforation_gap"))]
+    pub perforation_gap: f64,
+    /// Merge vertices within this many millimeters of each other, drop segments that are left
+    /// going nowhere, and join open paths that then touch end to end, 0 to disable
+    ///
+    /// Cleans up artwork where paths that should meet miss each other by a little, like CAD
+    /// exports and traced images. Done before [`Self::close_tolerance`].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub heal_tolerance: f64,
+    /// Close paths that end within this many millimeters of where they started, 0 to disable
+    ///
+    /// Paths from CAD exports often miss their start by a little, so they would be drawn with a
+    /// gap, left without tabs, and reversed when ordering like open paths.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub close_tolerance: f64,
+    /// Remove the parts of lines that an earlier line already covers, to within this many
+    /// millimeters, 0 to disable
+    ///
+    /// Shapes from CAD exports often share edges, like rectangles side by side, which would be cut
+    /// twice. Closed paths that lose an edge are opened up there. Done after
+    /// [`Self::close_tolerance`] and before [`Self::kerf_width`], so leave it off to make up for
+    /// the kerf of shapes that share edges.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub overlap_tolerance: f64,
+    /// Width of the cut in millimeters, like the kerf of a laser or the diameter of a router bit,
+    /// 0 to disable, or to use the width of [`Self::tool`] at [`Self::depth`]
+    ///
+    /// Closed paths are moved over by half of it to [`Self::kerf_side`], so the cut lands beside
+    /// the outline instead of on it. Done after [`Self::close_tolerance`] and before fills, with
+    /// the width of the tool of each layer in [`ConversionOptions::layer_overrides`].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub kerf_width: f64,
+    /// Which side of closed paths to cut on when compensating for [`Self::kerf_width`]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub kerf_side: KerfSide,
+    /// Which way to go around closed paths when milling, so the part is on the same side of the
+    /// tool in every cut, see [`LayerOverride::cut_direction`]
+    ///
+    /// Paths that go around the other way are reversed, telling parts from holes like
+    /// [`Self::kerf_side`] does. Done before tabs and leads are added.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub cut_direction: CutDirection,
+    /// Material in millimeters to leave on the side of closed paths that [`Self::kerf_side`]
+    /// cuts on in every pass, for a finishing pass after them to take off at full depth along
+    /// the exact contour, 0 to disable
+    ///
+    /// Edges of milled parts come out cleaner when the last cut only takes off a little.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub finish_allowance: f64,
+    /// Feedrate in millimeters / minute of the finishing pass, 0 for that of the last pass
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub finish_feedrate: f64,
+    /// Tool power (i.e. spindle speed) of the finishing pass, 0 for that of the last pass
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub finish_power: f64,
+    /// Length in millimeters of a move onto the start of each closed path, tangent to it, so the
+    /// tool is turned on beside the part instead of leaving a mark on it, 0 to disable
+    ///
+    /// Leads are made on the side that [`Self::kerf_side`] cuts on. Paths left open by tabs or
+    /// perforations get none.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub lead_in: f64,
+    /// Length in millimeters of a move off of the end of each closed path, like [`Self::lead_in`]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub lead_out: f64,
+    /// Whether leads are lines or arcs, which have the lead length as their radius
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub lead_shape: LeadShape,
+    /// Seconds to wait with the tool on before the first cutting move of each path, so it pierces
+    /// the material before moving, 0 to disable
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub pierce_dwell: f64,
+    /// Keep the tool on and draw through to the next path if it starts within this many
+    /// millimeters of where the previous one ended (i.e. to save pen lifts), 0 to disable
+    ///
+    /// Paths are joined before tabs and perforations are added, which skip the joined paths
+    /// unless they are closed.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub join_gap: f64,
+    /// Move to the next path without lifting the tool (i.e. retracting Z or turning it off) if it
+    /// starts within this many millimeters of where the previous one ended, 0 to disable
+    ///
+    /// Unlike [`Self::join_gap`], the paths are kept apart, but the tool cuts across the gap at
+    /// the feedrate of the cut. Gaps left by tabs and perforations count too, so keep this shorter
+    /// than them.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub stay_down_gap: f64,
+    /// Lift the tool anyway once it has stayed down over this many millimeters of gaps in a row,
+    /// 0 for no limit
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub stay_down_travel: f64,
+    /// Draw the inside of shapes with a `fill`, not just their outline
+    ///
+    /// The line spacing suits the tool more than any one document, so this is saved with the
+    /// settings instead of being one of the [`ConversionOptions`].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub fill: Option<Fill>,
+    /// Engrave `<image>` elements line by line, instead of leaving them out
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub raster: Option<RasterConfig>,
+    /// Set the tool power of each path from its stroke, instead of only from the tool on sequence
+    ///
+    /// It takes the place of [`Self::pass_power`] for each path, so it is set here with it rather
+    /// than in [`MachineConfig`](crate::MachineConfig), which only describes the machine.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub stroke_power: Option<StrokePower>,
+    /// Which shapes to draw, by whether they have a stroke or a fill
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub paint_filter: PaintFilter,
+    /// Cut around both sides of strokes at their `stroke-width` instead of along their middle, so
+    /// wide strokes come out the size they are drawn
+    ///
+    /// Strokes no wider than [`Self::cut_width`] are still cut along their middle, since the cut
+    /// is about that wide already. Ends and corners of the outline are rounded, and the shapes
+    /// that are outlined are drawn by their stroke alone, without their fill.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub stroke_outline: StrokeOutline,
+    /// Layer names or stroke colors (i.e. `Outline` or `#ff0000`) of paths to always cut after all
+    /// others, like the outline that frees a part from the sheet
+    ///
+    /// Paths are matched by the name of the layer they are in, which is the `inkscape:label` of an
+    /// Inkscape layer or the id of the outermost group, or by their stroke color in any notation.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub cut_last: Vec<String>,
+    /// Most seconds to spend improving on the nearest-first order for
+    /// [`PathOrdering::Optimized`], or 0 for no limit
+    ///
+    /// This is 2 seconds unless set, so that a drawing with many paths (i.e. stipple art) is still
+    /// converted quickly.
+    #[cfg_attr(feature = "serde", serde(default = "default_order_time_budget"))]
+    pub order_time_budget: f64,
+    /// Which moves to try while improving the order for [`PathOrdering::Optimized`]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub order_improvements: OrderImprovements,
+    /// Add a comment before each toolpath with its position in the cut order (i.e. `path 3 of 12`)
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub number_paths: bool,
+    /// Add comments that senders (i.e. LaserGRBL or OctoPrint) read to show progress and resume
+    /// from, marking each pass as a layer (`;LAYER:0`) and each whole percent of the cut length
+    /// done (`;PROGRESS:42`)
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub sender_markers: bool,
+    /// Name of a single-stroke font to use in place of a font family (i.e. `Arial` to `rowmans`),
+    /// which can also be one of the bundled fonts, `sans` or `serif`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub font_substitutions: BTreeMap<String, String>,
+    /// Language tag of the user (i.e. `en` or `fr-CA`), for picking which elements with a
+    /// `systemLanguage` to draw, like the translations in a `<switch>`
+    #[cfg_attr(feature = "serde", serde(default = "default_language"))]
+    pub language: String,
+    /// Marks to draw around the artwork before the first pass, for lining up the material
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub registration_marks: Option<RegistrationMarks>,
+    /// Rotate and move the toolpaths onto graphics printed on the material beforehand
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub alignment: Option<Alignment>,
+    /// Source of a [`crate::Script`] to run on each toolpath once they are ordered, to filter
+    /// them, change how they are cut, comment on them, or reorder them
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub script: Option<String>,
 }
 
 const fn zero_origin() -> [Option<f64>; 2] {
@@ -47,6 +319,30 @@ const fn default_bed_size() -> [f64; 2] {
     [300.0, 300.0]
 }
 
+const fn default_passes() -> usize {
+    1
+}
+
+const fn default_tab_width() -> f64 {
+    2.
+}
+
+const fn default_tab_passes() -> usize {
+    1
+}
+
+const fn default_perforation_gap() -> f64 {
+    1.
+}
+
+const fn default_order_time_budget() -> f64 {
+    2.
+}
+
+fn default_language() -> String {
+    "en".to_string()
+}
+
 impl Default for ConversionConfig {
     fn default() -> Self {
         Self {
@@ -54,12 +350,198 @@ impl Default for ConversionConfig {
             feedrate: 300.0,
             dpi: 96.0,
             origin: zero_origin(),
+            rotation: 0.,
+            origin_mode: OriginMode::BottomLeft,
+            invert_y: false,
 	    extra_attribute_name : None,
             bed_size: default_bed_size(),
+            passes: default_passes(),
+            pass_power: vec![],
+            pass_feedrate: vec![],
+            tools: vec![],
+            tool: None,
+            material: Material::default(),
+            engrave_depth: 0.,
+            tab_count: 0,
+            tab_width: default_tab_width(),
+            tab_passes: default_tab_passes(),
+            perforation_cut: 0.,
+            perforation_gap: default_perforation_gap(),
+            heal_tolerance: 0.,
+            close_tolerance: 0.,
+            overlap_tolerance: 0.,
+            kerf_width: 0.,
+            kerf_side: KerfSide::Outside,
+            cut_direction: CutDirection::AsDrawn,
+            finish_allowance: 0.,
+            finish_feedrate: 0.,
+            finish_power: 0.,
+            lead_in: 0.,
+            lead_out: 0.,
+            lead_shape: LeadShape::Line,
+            pierce_dwell: 0.,
+            join_gap: 0.,
+            stay_down_gap: 0.,
+            stay_down_travel: 0.,
+            fill: None,
+            raster: None,
+            stroke_power: None,
+            paint_filter: PaintFilter::All,
+            stroke_outline: StrokeOutline::Centerline,
+            cut_last: vec![],
+            order_time_budget: default_order_time_budget(),
+            order_improvements: OrderImprovements::default(),
+            number_paths: false,
+            sender_markers: false,
+            font_substitutions: BTreeMap::new(),
+            language: default_language(),
+            registration_marks: None,
+            alignment: None,
+            script: None,
+        }
+    }
+}
+
+impl ConversionConfig {
+    /// Tool power for a pass, counting from 1
+    pub fn pass_power(&self, pass: usize) -> Option<f64> {
+        if self.is_finishing_pass(pass) && self.finish_power > 0. {
+            return Some(self.finish_power);
+        }
+        scheduled(&self.pass_power, pass)
+    }
+
+    /// Feedrate for a pass, counting from 1
+    pub fn pass_feedrate(&self, pass: usize) -> f64 {
+        if self.is_finishing_pass(pass) && self.finish_feedrate > 0. {
+            return self.finish_feedrate;
+        }
+        scheduled(&self.pass_feedrate, pass).unwrap_or(self.feedrate)
+    }
+
+    /// Passes over each path, counting the finishing pass of [`Self::finish_allowance`]
+    pub fn total_passes(&self) -> usize {
+        self.passes + usize::from(self.finish_allowance > 0.)
+    }
+
+    /// Whether a pass, counting from 1, is the finishing pass after [`Self::passes`]
+    pub fn is_finishing_pass(&self, pass: usize) -> bool {
+        self.finish_allowance > 0. && pass > self.passes
+    }
+
+    /// The tool in [`Self::tools`] named by [`Self::tool`], if there is one
+    pub fn tool(&self) -> Option<&Tool> {
+        let name = self.tool.as_deref()?;
+        self.tools.iter().find(|tool| tool.name == name)
+    }
+
+    /// Depth below the surface in millimeters that the last pass goes down to, see
+    /// [`Material::depth`]
+    pub fn depth(&self) -> f64 {
+        self.material.depth(self.engrave_depth)
+    }
+
+    /// Depth below the surface in millimeters that a pass goes down to, counting from 1, with
+    /// [`Self::depth`] split evenly between the passes, or 0 without a material
+    ///
+    /// The finishing pass goes all the way down, like the last pass.
+    ///
+    /// Rounded to a nanometer, so sequences don't get floating point noise in them.
+    pub fn pass_depth(&self, pass: usize) -> f64 {
+        let depth = self.depth() * pass.min(self.passes) as f64 / self.passes.max(1) as f64;
+        (depth * 1e6).round() / 1e6
+    }
+
+    /// Z coordinate that a pass goes down to, counting from 1, see [`Self::pass_depth`]
+    pub fn pass_z(&self, pass: usize) -> f64 {
+        (self.material.z(self.pass_depth(pass)) * 1e6).round() / 1e6
+    }
+
+    /// Width of the cut to make up for: [`Self::kerf_width`] if it is set, or else that of
+    /// [`Self::tool`] at [`Self::depth`]
+    pub fn cut_width(&self) -> f64 {
+        if self.kerf_width > 0. {
+            return self.kerf_width;
+        }
+        self.tool().map_or(0., |tool| tool.cut_width(self.depth()))
+    }
+
+    /// Whether a toolpath is one of those to cut after all others, see [`Self::cut_last`]
+    pub fn cuts_last(&self, toolpath: &Toolpath) -> bool {
+        let Some(source) = &toolpath.source else {
+            return false;
+        };
+        let stroke = property(source, "stroke").and_then(|stroke| Color::from_str(stroke).ok());
+        self.cut_last.iter().map(|name| name.trim()).any(|name| {
+            source.layer.as_deref() == Some(name)
+                || stroke.is_some_and(|stroke| Color::from_str(name).ok() == Some(stroke))
+        })
+    }
+
+    /// Whether tabs are left on a pass, counting from 1
+    pub fn pass_has_tabs(&self, pass: usize) -> bool {
+        self.tab_count > 0 && pass + self.tab_passes > self.passes
+    }
+}
+
+/// Which point of the bounding box of the artwork is placed at [`ConversionConfig::origin`], as it
+/// is seen on the bed
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum OriginMode {
+    #[default]
+    BottomLeft,
+    TopLeft,
+    Center,
+    /// The bottom left, with the program starting wherever the machine is instead of at its
+    /// origin, by setting the position there to 0,0 first (`G92`) and clearing it at the end
+    /// (`G92.1`)
+    CurrentPosition,
+}
+
+impl OriginMode {
+    /// The point of `bounds` (in g-code coordinates) to place at the origin
+    fn anchor(&self, bounds: &Box2D<f64>, invert_y: bool) -> Point<f64> {
+        let [bottom, top] = if invert_y {
+            [bounds.max.y, bounds.min.y]
+        } else {
+            [bounds.min.y, bounds.max.y]
+        };
+        match self {
+            Self::BottomLeft | Self::CurrentPosition => point(bounds.min.x, bottom),
+            Self::TopLeft => point(bounds.min.x, top),
+            Self::Center => bounds.center(),
         }
     }
 }
 
+fn scheduled(schedule: &[f64], pass: usize) -> Option<f64> {
+    schedule
+        .get(pass.saturating_sub(1))
+        .or_else(|| schedule.last())
+        .copied()
+}
+
+/// The order paths are drawn in
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum PathOrdering {
+    /// In the order they appear in the SVG
+    #[default]
+    Document,
+    /// Each after the one that ends closest to it, starting from the machine origin
+    NearestNeighbor,
+    /// Nearest first, then improved on by moving and reversing runs of paths within
+    /// [`ConversionConfig::order_time_budget`]
+    Optimized,
+}
+
+impl PathOrdering {
+    fn is_document(&self) -> bool {
+        *self == Self::Document
+    }
+}
+
 /// Options are specific to this conversion.
 ///
 /// This is separate from [ConversionConfig] to support bulk processing in the web interface.
@@ -71,6 +553,44 @@ pub struct ConversionOptions {
     /// Useful when an SVG does not have a set width and height or you want to override it.
     #[cfg_attr(feature = "serde", serde(with = "length_serde"))]
     pub dimensions: [Option<Length>; 2],
+    /// Fonts for drawing text, after any embedded in the SVG
+    #[cfg(feature = "text")]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub fonts: FontLibrary,
+    /// Directory to read `<image>` elements that link to a file from, usually the one the SVG is
+    /// in, or [`None`] to only read images embedded in the SVG as `data:` URLs
+    #[cfg(feature = "raster")]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub image_dir: Option<PathBuf>,
+    /// Ids of the elements to draw along with everything inside them, or every element if empty
+    ///
+    /// Useful to re-cut a single part of a document without editing it.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    pub only_ids: Vec<String>,
+    /// Ids of the elements to leave out along with everything inside them
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    pub skip_ids: Vec<String>,
+    /// Settings of layers that are drawn differently from the rest of the document, by the label
+    /// of the Inkscape layer or the id of the outermost group they are in
+    ///
+    /// Each of these layers is drawn in all of its passes before moving on to the next.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "BTreeMap::is_empty")
+    )]
+    pub layer_overrides: LayerOverrides,
+    /// Reorder paths to cut down on travel between them
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "PathOrdering::is_document")
+    )]
+    pub path_ordering: PathOrdering,
 }
 
 /// Maps SVG [`Node`]s and their attributes into operations on a [`Terrarium`]
@@ -82,6 +602,15 @@ struct ConversionVisitor<'a, T: Turtle> {
     viewport_dim_stack: Vec<[f64; 2]>,
     _config: &'a ConversionConfig,
     options: ConversionOptions,
+    /// How many of the elements currently entered are, or are inside, one in [`ConversionOptions::only_ids`]
+    selected_depth: usize,
+    /// Same as `selected_depth`, for [`ConversionOptions::skip_ids`]
+    skipped_depth: usize,
+    /// The `<use>` elements currently entered, innermost last, see [`visit::drawn_ancestors`]
+    uses: Vec<NodeId>,
+    /// Images to engrave, see [`ConversionConfig::raster`]
+    #[cfg(feature = "raster")]
+    rasters: Vec<RasterImage>,
 }
 
 impl<'a, T: Turtle> ConversionVisitor<'a, T> {
@@ -94,6 +623,116 @@ impl<'a, T: Turtle> ConversionVisitor<'a, T> {
         comment += &node_name(node,&self._config.extra_attribute_name);
 
         self.terrarium.turtle.comment(comment);
+        self.terrarium
+            .turtle
+            .source_element(&source_element(&drawn_ancestors(*node, &self.uses)));
+    }
+
+    /// Draw the path of an element, broken into dashes if its stroke is dashed, and around both
+    /// sides of its stroke for [`ConversionConfig::stroke_outline`]
+    ///
+    /// Dashes and outlines are measured in the user units of the element, before it is
+    /// transformed, as a browser would draw them.
+    fn draw(&mut self, node: &Node, path: impl IntoIterator<Item = PathSegment>) {
+        let ancestors = drawn_ancestors(*node, &self.uses);
+        let dashes = paint::stroke_dashes(&ancestors, |l, hint| self.length_to_user_units(l, hint));
+        let outline_width = self.stroke_outline_width(&ancestors);
+        if dashes.is_none() && outline_width.is_none() {
+            apply_path(&mut self.terrarium, path);
+            return;
+        }
+        let tolerance = self.user_units_tolerance();
+        let mut terrarium = Terrarium::new(ToolpathTurtle::default());
+        apply_path(&mut terrarium, path);
+        let mut toolpaths = terrarium.turtle.toolpaths;
+        if let Some((pattern, offset)) = dashes {
+            toolpaths = toolpaths
+                .iter()
+                .flat_map(|toolpath| dash(toolpath, &pattern, offset, tolerance))
+                .collect();
+        }
+        if let Some(width) = outline_width {
+            let polylines = toolpaths
+                .iter()
+                .map(|toolpath| toolpath.flattened(tolerance))
+                .collect::<Vec<_>>();
+            toolpaths = stroke_outline(&polylines, width, tolerance);
+        }
+        if outline_width.is_some() {
+            let filled = self._config.stroke_outline == StrokeOutline::Filled;
+            self.terrarium
+                .turtle
+                .source_element(&stroke_outline_source(&ancestors, filled));
+        }
+        let path = toolpaths
+            .iter()
+            .flat_map(|toolpath| {
+                toolpath
+                    .flattened(tolerance)
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, p)| match i {
+                        0 => PathSegment::MoveTo {
+                            abs: true,
+                            x: p.x,
+                            y: p.y,
+                        },
+                        _ => PathSegment::LineTo {
+                            abs: true,
+                            x: p.x,
+                            y: p.y,
+                        },
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        apply_path(&mut self.terrarium, path);
+        if outline_width.is_some() {
+            self.terrarium
+                .turtle
+                .source_element(&source_element(&ancestors));
+        }
+    }
+
+    /// [`ConversionConfig::tolerance`] in the user units of the element being drawn, so paths
+    /// flattened before they are transformed stay as close to their curves as the rest
+    fn user_units_tolerance(&self) -> f64 {
+        let scale = self
+            .terrarium
+            .current_transform()
+            .determinant()
+            .abs()
+            .sqrt();
+        let tolerance =
+            UomLength::new::<millimeter>(self._config.tolerance).get::<inch>() * self._config.dpi;
+        if scale > 0. {
+            tolerance / scale
+        } else {
+            tolerance
+        }
+    }
+
+    /// Width in user units of the stroke of an element to cut around, given it followed by its
+    /// ancestors as it is drawn, see [`ConversionConfig::stroke_outline`]
+    fn stroke_outline_width(&self, ancestors: &[Node]) -> Option<f64> {
+        if self._config.stroke_outline == StrokeOutline::Centerline {
+            return None;
+        }
+        let width = paint::stroke_width(ancestors, |l, hint| self.length_to_user_units(l, hint))?;
+        let scale = self
+            .terrarium
+            .current_transform()
+            .determinant()
+            .abs()
+            .sqrt();
+        let millimeters =
+            UomLength::new::<inch>(width * scale / self._config.dpi).get::<millimeter>();
+        (millimeters > self._config.cut_width()).then_some(width)
+    }
+
+    /// Whether the element currently entered is drawn, see [`ConversionOptions::only_ids`]
+    fn is_selected(&self) -> bool {
+        self.skipped_depth == 0 && (self.options.only_ids.is_empty() || self.selected_depth > 0)
     }
 
     fn begin(&mut self) {
@@ -115,6 +754,85 @@ pub fn svg2program<'a, 'input: 'a>(
     options: ConversionOptions,
     machine: Machine<'input>,
 ) -> Vec<Token<'input>> {
+    svg2program_with_passes(doc, config, options, machine, &PassRegistry::default())
+}
+
+/// Same as [`svg2program`], but runs the [`PassRegistry`]'s passes on the toolpaths before emitting g-code
+///
+/// This chains the conversion stages, which can also be called individually:
+///
+/// 1. [`place`] the drawing according to the configured origin
+/// 2. [`svg2toolpaths`] to convert it into millimeters
+/// 3. [`PassRegistry::apply`] to transform and order the toolpaths
+/// 4. [`toolpaths2program`] to emit g-code
+pub fn svg2program_with_passes<'a, 'input: 'a>(
+    doc: &'a Document,
+    config: &ConversionConfig,
+    options: ConversionOptions,
+    machine: Machine<'input>,
+    passes: &PassRegistry,
+) -> Vec<Token<'input>> {
+    let placement = place(doc, config, &options);
+    let layer_overrides = options.layer_overrides.clone();
+    let mut toolpaths = svg2toolpaths(doc, config, options, placement);
+    toolpaths.toolpaths = passes.apply(toolpaths.toolpaths, config);
+    toolpaths2program(toolpaths, config, &layer_overrides, machine)
+}
+
+/// Draw the toolpaths of an SVG [`Document`] as a new SVG, numbered in the order they will be cut
+///
+/// Useful for auditing the cut order without running the program on a machine.
+pub fn svg2cut_order(
+    doc: &Document,
+    config: &ConversionConfig,
+    options: ConversionOptions,
+    passes: &PassRegistry,
+) -> String {
+    let placement = place(doc, config, &options);
+    let toolpaths = svg2toolpaths(doc, config, options, placement).toolpaths;
+    order::toolpaths2svg(&passes.apply(toolpaths, config), config.tolerance)
+}
+
+/// Estimate how long converting an SVG [`Document`] would take to run and what it would cost,
+/// see [`JobEstimate::from_program`]
+pub fn svg2estimate(
+    doc: &Document,
+    config: &ConversionConfig,
+    options: ConversionOptions,
+    machine: Machine,
+    passes: &PassRegistry,
+    cost: &CostConfig,
+) -> Result<JobEstimate, EstimateError> {
+    let dialect = machine.dialect();
+    let program = svg2program_with_passes(doc, config, options, machine, passes);
+    JobEstimate::from_program(&program, dialect, cost)
+}
+
+/// Intermediate result of [`svg2toolpaths`]
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Toolpaths {
+    /// In the order they will be drawn, in millimeters
+    pub toolpaths: Vec<Toolpath>,
+    /// Comments that came after the last toolpath
+    pub comments: Vec<String>,
+    /// Images to engrave before the toolpaths, in millimeters, see [`ConversionConfig::raster`]
+    #[cfg(feature = "raster")]
+    pub rasters: Vec<RasterImage>,
+}
+
+/// Find the transform that moves an SVG [`Document`] to [`ConversionConfig::origin`], in user units
+///
+/// This requires converting the document once to find its bounding box, unless the origin is unset.
+/// The transform also turns the document by [`ConversionConfig::rotation`] and mirrors it for
+/// [`ConversionConfig::invert_y`].
+pub fn place(
+    doc: &Document,
+    config: &ConversionConfig,
+    options: &ConversionOptions,
+) -> Transform2D<f64> {
+    // Still counterclockwise as seen on the bed once mirrored, since the y-axis points the other way
+    let rotation = Transform2D::rotation(Angle::degrees(config.rotation))
+        .then_scale(1., if config.invert_y { -1. } else { 1. });
     let bounding_box_generator = || {
         let mut visitor = ConversionVisitor {
             terrarium: Terrarium::new(DpiConvertingTurtle {
@@ -122,64 +840,729 @@ pub fn svg2program<'a, 'input: 'a>(
                 dpi: config.dpi,
             }),
             _config: config,
-            options: options.clone(),
+            // Placed the same as the whole document, so a part is re-cut where it was before
+            options: ConversionOptions {
+                only_ids: vec![],
+                skip_ids: vec![],
+                ..options.clone()
+            },
             name_stack: vec![],
             viewport_dim_stack: vec![],
+            selected_depth: 0,
+            skipped_depth: 0,
+            uses: vec![],
+            #[cfg(feature = "raster")]
+            rasters: vec![],
         };
 
+        visitor.terrarium.push_transform(rotation);
         visitor.begin();
-        visit::depth_first_visit(doc, &mut visitor);
+        visit::depth_first_visit(doc, &config.language, &mut visitor);
         visitor.end();
 
+        #[cfg(feature = "raster")]
+        for raster in &visitor.rasters {
+            let bounding_box = &mut visitor.terrarium.turtle.inner.bounding_box;
+            *bounding_box = bounding_box.union(&raster.bounding_box());
+        }
         visitor.terrarium.turtle.inner.bounding_box
     };
 
-    // Convert from millimeters to user units
-    let origin = config
-        .origin
-        .map(|dim| dim.map(|d| UomLength::new::<millimeter>(d).get::<inch>() * CSS_DEFAULT_DPI));
-
-    let origin_transform = match origin {
-        [None, Some(origin_y)] => {
-            let bb = bounding_box_generator();
-            Transform2D::translation(0., origin_y - bb.min.y)
-        }
-        [Some(origin_x), None] => {
-            let bb = bounding_box_generator();
-            Transform2D::translation(origin_x - bb.min.x, 0.)
-        }
-        [Some(origin_x), Some(origin_y)] => {
-            let bb = bounding_box_generator();
-            Transform2D::translation(origin_x - bb.min.x, origin_y - bb.min.y)
+    // The bounding box is in millimeters, and the transform is in user units
+    let to_user_units = |mm: f64| UomLength::new::<millimeter>(mm).get::<inch>() * config.dpi;
+    match config.origin {
+        [None, None] => rotation,
+        origin => {
+            let anchor = config
+                .origin_mode
+                .anchor(&bounding_box_generator(), config.invert_y);
+            let [x, y] = [(origin[0], anchor.x), (origin[1], anchor.y)]
+                .map(|(origin, anchor)| origin.map_or(0., |origin| to_user_units(origin - anchor)));
+            rotation.then_translate(vector(x, y))
         }
-        [None, None] => Transform2D::identity(),
+    }
+}
+
+/// Convert an SVG [`Document`] into [`Toolpath`]s in millimeters, after applying the `placement` from [`place`]
+pub fn svg2toolpaths(
+    doc: &Document,
+    config: &ConversionConfig,
+    options: ConversionOptions,
+    placement: Transform2D<f64>,
+) -> Toolpaths {
+    #[cfg(feature = "text")]
+    let options = {
+        let mut options = options;
+        options.fonts.embed(doc);
+        options
     };
+    let layer_overrides = options.layer_overrides.clone();
+    let path_ordering = options.path_ordering;
 
     let mut conversion_visitor = ConversionVisitor {
         terrarium: Terrarium::new(DpiConvertingTurtle {
-            inner: GCodeTurtle {
-                machine,
-                tolerance: config.tolerance,
-                feedrate: config.feedrate,
-                program: vec![],
-            },
+            inner: ToolpathTurtle::default(),
             dpi: config.dpi,
         }),
         _config: config,
         options,
         name_stack: vec![],
         viewport_dim_stack: vec![],
+        selected_depth: 0,
+        skipped_depth: 0,
+        uses: vec![],
+        #[cfg(feature = "raster")]
+        rasters: vec![],
     };
 
-    conversion_visitor
-        .terrarium
-        .push_transform(origin_transform);
+    conversion_visitor.terrarium.push_transform(placement);
     conversion_visitor.begin();
-    visit::depth_first_visit(doc, &mut conversion_visitor);
+    visit::depth_first_visit(doc, &config.language, &mut conversion_visitor);
     conversion_visitor.end();
     conversion_visitor.terrarium.pop_transform();
 
-    conversion_visitor.terrarium.turtle.inner.program
+    #[cfg(feature = "raster")]
+    let rasters = conversion_visitor.rasters;
+    let ToolpathTurtle {
+        toolpaths,
+        comments,
+        ..
+    } = conversion_visitor.terrarium.turtle.inner;
+    let mut toolpaths = heal(toolpaths, config.heal_tolerance);
+    if config.close_tolerance > 0. {
+        let closed = toolpaths
+            .iter_mut()
+            .map(|toolpath| toolpath.close(config.close_tolerance))
+            .filter(|closed| *closed)
+            .count();
+        if closed > 0 {
+            info!(
+                "Closed {closed} paths that ended within {} mm of where they started",
+                config.close_tolerance
+            );
+        }
+    }
+    if config.overlap_tolerance > 0. {
+        let length = |toolpaths: &[Toolpath]| {
+            toolpaths
+                .iter()
+                .map(|toolpath| toolpath.length(config.tolerance))
+                .sum::<f64>()
+        };
+        let before = length(&toolpaths);
+        toolpaths = remove_overlaps(toolpaths, config.overlap_tolerance);
+        let removed = before - length(&toolpaths);
+        if removed > config.overlap_tolerance {
+            info!("Removed {removed:.1} mm of lines that were drawn over again");
+        }
+    }
+    // Before the toolpaths are split up into layers, which may not have the parts of their holes
+    find_holes(&mut toolpaths, config.tolerance);
+    // With the tool of each layer, which may cut wider or narrower than that of the others
+    let kerf_width = |layer| group_config(config, layer, &LayerOverride::default()).cut_width();
+    let layer_kerf_widths = layer_overrides
+        .iter()
+        .map(|layer| (layer.0, kerf_width(Some(layer))))
+        .collect::<BTreeMap<_, _>>();
+    let default_kerf_width = kerf_width(None);
+    let toolpaths = toolpaths
+        .into_iter()
+        .flat_map(|toolpath| {
+            let kerf_width = toolpath
+                .source
+                .as_ref()
+                .and_then(|source| layer_kerf_widths.get(source.layer.as_ref()?))
+                .copied()
+                .unwrap_or(default_kerf_width);
+            if kerf_width > 0. {
+                compensate_kerf(
+                    vec![toolpath],
+                    kerf_width,
+                    config.kerf_side,
+                    config.tolerance,
+                )
+            } else {
+                vec![toolpath]
+            }
+        })
+        .collect::<Vec<_>>();
+    let toolpaths = match &config.fill {
+        Some(fill) => fill.apply(toolpaths, config.tolerance),
+        None => toolpaths,
+    };
+    // Kept out of the reordering, so the paths that free the workpiece are never cut early
+    let (mut toolpaths, last): (Vec<_>, Vec<_>) = toolpaths
+        .into_iter()
+        .partition(|toolpath| !config.cuts_last(toolpath));
+    if path_ordering.is_document() {
+        toolpaths.extend(last);
+    } else {
+        toolpaths = optimize_order(toolpaths, point(0., 0.), path_ordering, config);
+        let start = toolpaths.last().map_or(point(0., 0.), Toolpath::to);
+        toolpaths.append(&mut optimize_order(last, start, path_ordering, config));
+    }
+    let toolpaths = run_script(toolpaths, config);
+    Toolpaths {
+        toolpaths,
+        comments,
+        #[cfg(feature = "raster")]
+        rasters,
+    }
+}
+
+/// Order toolpaths to cut down on travel, keeping those between pauses together so each stage of
+/// the job is still drawn in between the same pauses
+fn optimize_order(
+    toolpaths: Vec<Toolpath>,
+    start: Point<f64>,
+    path_ordering: PathOrdering,
+    config: &ConversionConfig,
+) -> Vec<Toolpath> {
+    // Seconds left to improve the order in, shared by the stages
+    let mut budget = config.order_time_budget;
+    let mut ordered: Vec<Toolpath> = Vec::with_capacity(toolpaths.len());
+    let mut toolpaths = toolpaths.into_iter().peekable();
+    while let Some(mut first) = toolpaths.next() {
+        let pause = std::mem::take(&mut first.pause);
+        let mut stage = vec![first];
+        while let Some(next) = toolpaths.next_if(|next| !next.pause) {
+            stage.push(next);
+        }
+
+        let start = ordered.last().map_or(start, Toolpath::to);
+        let stage = order_nearest(stage, start);
+        let mut stage = match path_ordering {
+            PathOrdering::Optimized => {
+                let deadline = (config.order_time_budget > 0.).then(|| Deadline::after(budget));
+                let stage = improve_order(stage, start, &config.order_improvements, || {
+                    deadline.as_ref().is_some_and(Deadline::has_passed)
+                });
+                budget = deadline.map_or(budget, |deadline| deadline.remaining());
+                stage
+            }
+            _ => stage,
+        };
+        stage[0].pause = pause;
+        ordered.append(&mut stage);
+    }
+    ordered
+}
+
+/// When the [`ConversionConfig::order_time_budget`] runs out
+#[cfg(not(target_arch = "wasm32"))]
+struct Deadline(Instant);
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Deadline {
+    fn after(seconds: f64) -> Self {
+        Self(Instant::now() + Duration::from_secs_f64(seconds))
+    }
+
+    fn has_passed(&self) -> bool {
+        Instant::now() >= self.0
+    }
+
+    /// Seconds until the deadline, or 0 once it has passed
+    fn remaining(&self) -> f64 {
+        self.0
+            .saturating_duration_since(Instant::now())
+            .as_secs_f64()
+    }
+}
+
+/// When the [`ConversionConfig::order_time_budget`] runs out, in milliseconds since the epoch
+///
+/// [`std::time::Instant`] panics in the browser, so this reads the JavaScript clock instead.
+#[cfg(target_arch = "wasm32")]
+struct Deadline(f64);
+
+#[cfg(target_arch = "wasm32")]
+impl Deadline {
+    fn after(seconds: f64) -> Self {
+        Self(js_sys::Date::now() + seconds * 1000.)
+    }
+
+    fn has_passed(&self) -> bool {
+        js_sys::Date::now() >= self.0
+    }
+
+    /// Seconds until the deadline, or 0 once it has passed
+    fn remaining(&self) -> f64 {
+        ((self.0 - js_sys::Date::now()) / 1000.).max(0.)
+    }
+}
+
+/// Run the [`ConversionConfig::script`] on the toolpaths, if there is one that compiles
+#[cfg(feature = "script")]
+fn run_script(toolpaths: Vec<Toolpath>, config: &ConversionConfig) -> Vec<Toolpath> {
+    use crate::{Script, ToolpathPass};
+
+    match config.script.as_deref().map(Script::new) {
+        Some(Ok(script)) => script.apply(toolpaths, config),
+        Some(Err(err)) => {
+            warn!("Not running the script: {err}");
+            toolpaths
+        }
+        None => toolpaths,
+    }
+}
+
+#[cfg(not(feature = "script"))]
+fn run_script(toolpaths: Vec<Toolpath>, config: &ConversionConfig) -> Vec<Toolpath> {
+    if config.script.is_some() {
+        warn!("Not running the script, since this build has no script support");
+    }
+    toolpaths
+}
+
+/// Emit g-code for [`Toolpaths`], drawing them [`ConversionConfig::passes`] times
+///
+/// Layers in `layer_overrides` are drawn with their own settings, one after another, see
+/// [`ConversionOptions::layer_overrides`].
+pub fn toolpaths2program<'input>(
+    Toolpaths {
+        toolpaths,
+        comments,
+        #[cfg(feature = "raster")]
+        rasters,
+    }: Toolpaths,
+    config: &ConversionConfig,
+    layer_overrides: &LayerOverrides,
+    machine: Machine<'input>,
+) -> Vec<Token<'input>> {
+    let mut turtle = GCodeTurtle {
+        backlash: BacklashCompensation::new(machine.backlash()),
+        machine,
+        tolerance: config.tolerance,
+        feedrate: config.feedrate,
+        program: vec![],
+        stay_down: StayDown::new(config.stay_down_gap, config.stay_down_travel),
+        power: None,
+        path_ahead: vec![],
+    };
+    let dwell = config.fill.as_ref().map_or(0., |fill| fill.dwell);
+    turtle.begin();
+    if config.origin_mode == OriginMode::CurrentPosition {
+        turtle.zero_here();
+    }
+    let mut marks_toolpaths = config
+        .registration_marks
+        .as_ref()
+        .map(|marks| (marks, marks.toolpaths(&toolpaths, config.tolerance)));
+    // Marks are moved along with the artwork, so they land on those printed on the material
+    let transform = machine_transform(config, &turtle.machine);
+    let toolpaths = if let Some(transform) = transform {
+        let transformed = |toolpaths: &[Toolpath]| {
+            toolpaths
+                .iter()
+                .map(|toolpath| toolpath.transformed(&transform))
+                .collect::<Vec<_>>()
+        };
+        if let Some((_, marks_toolpaths)) = &mut marks_toolpaths {
+            *marks_toolpaths = transformed(marks_toolpaths);
+        }
+        transformed(&toolpaths)
+    } else {
+        toolpaths
+    };
+    #[cfg(feature = "raster")]
+    let rasters = match transform {
+        Some(transform) => rasters
+            .iter()
+            .map(|raster| raster.transformed(&transform))
+            .collect(),
+        None => rasters,
+    };
+    let toolpaths = if config.join_gap > 0. {
+        join_nearby(toolpaths, config.join_gap)
+    } else {
+        toolpaths
+    };
+    // Perforated on every pass, unlike tabs
+    let toolpaths = if config.perforation_cut > 0. {
+        toolpaths
+            .iter()
+            .flat_map(|toolpath| {
+                perforate(
+                    toolpath,
+                    config.perforation_cut,
+                    config.perforation_gap,
+                    config.tolerance,
+                )
+            })
+            .collect()
+    } else {
+        toolpaths
+    };
+    if let Some((marks, marks_toolpaths)) = marks_toolpaths {
+        // Drawn as pass 0, so sequences can tell it apart from the passes over the artwork
+        turtle.feedrate = config.pass_feedrate(1);
+        turtle.pass_begin(
+            0,
+            config.total_passes(),
+            marks.power.or(config.pass_power(1)),
+        );
+        for (i, toolpath) in marks_toolpaths.iter().enumerate() {
+            turtle.path_begin(i + 1, marks_toolpaths.len());
+            toolpath.draw(&mut turtle);
+            turtle.path_end();
+        }
+        turtle.pass_end();
+    }
+    #[cfg(feature = "raster")]
+    if let Some(raster) = &config.raster {
+        for (i, image) in rasters.iter().enumerate() {
+            turtle.comment(format!("image {} of {}", i + 1, rasters.len()));
+            turtle.feedrate = raster.feedrate;
+            for scanline in image.scanlines(raster) {
+                turtle.power = None;
+                turtle.move_to(scanline.from);
+                for (to, power) in scanline.runs {
+                    turtle.power = Some(power);
+                    turtle.line_to(to);
+                }
+            }
+            turtle.power = None;
+        }
+    }
+    let layer_overrides = layer_overrides
+        .iter()
+        .map(|(name, layer)| (name.clone(), layer.checked(name)))
+        .collect();
+    // Layers with their own settings are each drawn in all of their passes before the next
+    let layers = by_layer(toolpaths, &layer_overrides)
+        .into_iter()
+        .map(|(layer, element, toolpaths)| {
+            let config = group_config(config, layer, &element);
+            if let (None, Some(name)) = (config.tool(), &config.tool) {
+                warn!("There is no tool named {name}, cutting without one");
+            }
+            let toolpaths = set_cut_direction(
+                toolpaths,
+                config.cut_direction,
+                config.kerf_side,
+                config.tolerance,
+            );
+            // Tabs and leads are added to the toolpaths of each pass
+            let with_tabs_and_leads = |toolpaths: Vec<Toolpath>| {
+                let tabbed_toolpaths = if config.tab_count > 0 {
+                    toolpaths
+                        .iter()
+                        .flat_map(|toolpath| {
+                            add_tabs(
+                                toolpath,
+                                config.tab_count,
+                                config.tab_width,
+                                config.tolerance,
+                            )
+                        })
+                        .collect()
+                } else {
+                    vec![]
+                };
+                if config.lead_in > 0. || config.lead_out > 0. {
+                    let leads = |toolpaths| {
+                        add_leads(
+                            toolpaths,
+                            config.lead_in,
+                            config.lead_out,
+                            config.lead_shape,
+                            config.kerf_side,
+                            config.tolerance,
+                        )
+                    };
+                    (leads(toolpaths), leads(tabbed_toolpaths))
+                } else {
+                    (toolpaths, tabbed_toolpaths)
+                }
+            };
+            let rough_toolpaths = (config.finish_allowance > 0.).then(|| {
+                with_tabs_and_leads(compensate_kerf(
+                    toolpaths.clone(),
+                    2. * config.finish_allowance,
+                    config.kerf_side,
+                    config.tolerance,
+                ))
+            });
+            let (toolpaths, tabbed_toolpaths) = with_tabs_and_leads(toolpaths);
+            // Those that can't be parsed were left out when the layers were checked
+            let tool_sequences = layer.map_or([None, None], |(_, layer)| {
+                [&layer.tool_on_sequence, &layer.tool_off_sequence]
+                    .map(|sequence| parse_layer_sequence(sequence.as_deref()?).ok())
+            });
+            Layer {
+                config,
+                toolpaths,
+                tabbed_toolpaths,
+                rough_toolpaths,
+                tool_sequences,
+            }
+        })
+        .collect::<Vec<_>>();
+    let mut progress = config.sender_markers.then(|| {
+        let length = |toolpaths: &Vec<Toolpath>| {
+            toolpaths
+                .iter()
+                .map(|toolpath| toolpath.length(config.tolerance))
+                .sum::<f64>()
+        };
+        let passes = layers
+            .iter()
+            .map(|layer| layer.config.total_passes())
+            .sum::<usize>();
+        turtle.comment(format!("LAYER_COUNT:{passes}"));
+        SenderProgress {
+            total: layers
+                .iter()
+                .flat_map(|layer| {
+                    (1..=layer.config.total_passes()).map(|pass| length(layer.pass_toolpaths(pass)))
+                })
+                .sum(),
+            done: 0.,
+            percent: 0,
+        }
+    });
+    let mut passes_done = 0;
+    for layer in &layers {
+        let config = layer.config.as_ref();
+        turtle.set_tool_sequences(layer.tool_sequences.clone());
+        let passes = config.total_passes();
+        for pass in 1..=passes {
+            let toolpaths = layer.pass_toolpaths(pass);
+            turtle.feedrate = config.pass_feedrate(pass);
+            if progress.is_some() {
+                turtle.comment(format!("LAYER:{passes_done}"));
+            }
+            let (depth, z) = (config.pass_depth(pass), config.pass_z(pass));
+            if config.is_finishing_pass(pass) {
+                turtle.comment(format!("finishing pass to {depth} mm deep, Z{z}"));
+            } else if depth > 0. {
+                turtle.comment(format!(
+                    "pass {pass} of {} to {depth} mm deep, Z{z}",
+                    config.passes
+                ));
+            }
+            turtle.machine.set_depth(depth, z);
+            turtle.pass_begin(pass, passes, config.pass_power(pass));
+            for (i, toolpath) in toolpaths.iter().enumerate() {
+                if let Some(percent) = progress.as_mut().and_then(SenderProgress::next_percent) {
+                    turtle.comment(format!("PROGRESS:{percent}"));
+                }
+                if config.number_paths {
+                    turtle.comment(format!("path {} of {}", i + 1, toolpaths.len()));
+                }
+                turtle.path_begin(i + 1, toolpaths.len());
+                if let Some(stroke_power) = &config.stroke_power {
+                    turtle.power = Some(stroke_power.power(toolpath.source.as_ref()));
+                }
+                if turtle.machine.ramps() {
+                    turtle.path_ahead = toolpath.flattened(config.tolerance);
+                }
+                if config.pierce_dwell > 0. && !toolpath.is_dot() {
+                    toolpath.draw_start(&mut turtle);
+                    turtle.pierce(config.pierce_dwell);
+                    toolpath.draw_segments(&mut turtle);
+                } else {
+                    toolpath.draw(&mut turtle);
+                }
+                if dwell > 0. && toolpath.is_dot() {
+                    turtle.dwell(dwell);
+                }
+                turtle.path_end();
+                if let Some(progress) = &mut progress {
+                    progress.done += toolpath.length(config.tolerance);
+                }
+            }
+            turtle.pass_end();
+            passes_done += 1;
+        }
+    }
+    if progress.is_some() {
+        turtle.comment("PROGRESS:100".to_string());
+    }
+    comments
+        .into_iter()
+        .for_each(|comment| turtle.comment(comment));
+    turtle.end();
+
+    turtle.program
+}
+
+/// Toolpaths drawn with the same settings, see [`ConversionOptions::layer_overrides`]
+struct Layer<'a> {
+    config: Cow<'a, ConversionConfig>,
+    toolpaths: Vec<Toolpath>,
+    /// Same as `toolpaths` with tabs left in them, for the passes that have tabs
+    tabbed_toolpaths: Vec<Toolpath>,
+    /// Same as `toolpaths` and `tabbed_toolpaths` with [`ConversionConfig::finish_allowance`]
+    /// left on, for the passes before the finishing pass
+    rough_toolpaths: Option<(Vec<Toolpath>, Vec<Toolpath>)>,
+    /// See [`Machine::set_tool_sequences`]
+    tool_sequences: [Option<Vec<Token<'static>>>; 2],
+}
+
+impl Layer<'_> {
+    fn pass_toolpaths(&self, pass: usize) -> &Vec<Toolpath> {
+        let (toolpaths, tabbed_toolpaths) = match &self.rough_toolpaths {
+            Some((toolpaths, tabbed_toolpaths)) if !self.config.is_finishing_pass(pass) => {
+                (toolpaths, tabbed_toolpaths)
+            }
+            _ => (&self.toolpaths, &self.tabbed_toolpaths),
+        };
+        if self.config.pass_has_tabs(pass) {
+            tabbed_toolpaths
+        } else {
+            toolpaths
+        }
+    }
+}
+
+/// How far along a program is, for [`ConversionConfig::sender_markers`]
+struct SenderProgress {
+    /// Length of all paths over all passes in millimeters
+    total: f64,
+    /// Length of the paths drawn so far in millimeters
+    done: f64,
+    /// Last whole percent that was marked
+    percent: u32,
+}
+
+impl SenderProgress {
+    /// The whole percent done, if it went up since it was last marked
+    fn next_percent(&mut self) -> Option<u32> {
+        let percent = if self.total > 0. {
+            (100. * self.done / self.total).floor() as u32
+        } else {
+            0
+        };
+        (percent > self.percent).then(|| {
+            self.percent = percent;
+            percent
+        })
+    }
+}
+
+/// Jog around the bounding box of [`Toolpaths`] with the tool off, stopping at each corner until
+/// the operator resumes, to check that the material covers the job before cutting it
+///
+/// Registration marks are included in the bounding box, since they are cut too.
+pub fn toolpaths2perimeter_check<'input>(
+    toolpaths: &Toolpaths,
+    config: &ConversionConfig,
+    machine: Machine<'input>,
+) -> Vec<Token<'input>> {
+    let corners = perimeter(toolpaths, config, &machine);
+    let mut turtle = GCodeTurtle {
+        backlash: BacklashCompensation::new(machine.backlash()),
+        machine,
+        tolerance: config.tolerance,
+        feedrate: config.feedrate,
+        program: vec![],
+        stay_down: None,
+        power: None,
+        path_ahead: vec![],
+    };
+    turtle.begin();
+    if config.origin_mode == OriginMode::CurrentPosition {
+        turtle.zero_here();
+    }
+    for (i, corner) in corners.iter().enumerate() {
+        turtle.comment(format!("corner {} of {}", i + 1, corners.len()));
+        turtle.move_to(*corner);
+        turtle.pause();
+    }
+    turtle.end();
+
+    turtle.program
+}
+
+/// Grbl jog commands (`$J=`) that trace the bounding box of [`Toolpaths`] at `feedrate` and come
+/// back to the first corner, to frame the job on the material before cutting it
+///
+/// Unlike the moves of a program, jogs can be stopped right away with Grbl's jog cancel command
+/// (`0x85`) without resetting the controller. They are not affected by backlash compensation.
+///
+/// With [`OriginMode::CurrentPosition`], the jogs are relative to where the machine is when they
+/// start, which is the origin of the program, and end back there.
+pub fn toolpaths2frame_jogs(
+    toolpaths: &Toolpaths,
+    config: &ConversionConfig,
+    machine: &Machine,
+    feedrate: f64,
+) -> Vec<String> {
+    let corners = perimeter(toolpaths, config, machine);
+    if corners.is_empty() {
+        return vec![];
+    }
+    // Rounded to a micron, which is finer than any machine moves
+    let round = |value: f64| (value * 1000.).round() / 1000.;
+    let jog = |mode: &str, to: Point<f64>| {
+        format!(
+            "$J={mode} G21 X{} Y{} F{}",
+            round(to.x),
+            round(to.y),
+            round(feedrate)
+        )
+    };
+    let corners = corners
+        .iter()
+        .chain(corners.first())
+        .map(|corner| machine.snap_to_steps(*corner));
+    if config.origin_mode == OriginMode::CurrentPosition {
+        let mut at = Point::origin();
+        corners
+            .chain([Point::origin()])
+            .map(|corner| {
+                let by = corner - at;
+                at = corner;
+                jog("G91", by.to_point())
+            })
+            .collect()
+    } else {
+        corners.map(|corner| jog("G90", corner)).collect()
+    }
+}
+
+/// Corners of the bounding box of [`Toolpaths`] and their registration marks, in the order they
+/// are traced, where the machine should move to them
+fn perimeter(
+    toolpaths: &Toolpaths,
+    config: &ConversionConfig,
+    machine: &Machine,
+) -> Vec<Point<f64>> {
+    let marks_toolpaths = config
+        .registration_marks
+        .as_ref()
+        .map(|marks| marks.toolpaths(&toolpaths.toolpaths, config.tolerance))
+        .unwrap_or_default();
+    let all_toolpaths = [toolpaths.toolpaths.as_slice(), &marks_toolpaths].concat();
+    let Some(bounds) = bounding_box(&all_toolpaths, config.tolerance) else {
+        return vec![];
+    };
+    let transform = machine_transform(config, machine);
+    [
+        bounds.min,
+        point(bounds.max.x, bounds.min.y),
+        bounds.max,
+        point(bounds.min.x, bounds.max.y),
+    ]
+    .map(|corner| transform.map_or(corner, |transform| transform.transform_point(corner)))
+    .to_vec()
+}
+
+/// Where the machine should draw each point, to line up with the material and correct for the
+/// machine's inaccuracies
+pub(crate) fn machine_transform(
+    config: &ConversionConfig,
+    machine: &Machine,
+) -> Option<Transform2D<f64>> {
+    [
+        config.alignment.as_ref().map(Alignment::transform),
+        machine.calibration().map(Calibration::transform),
+    ]
+    .into_iter()
+    .flatten()
+    .reduce(|first, then| first.then(&then))
 }
 
 fn node_name(node: &Node , attr_to_print :  &Option<String> ) -> String {
@@ -200,9 +1583,96 @@ fn node_name(node: &Node , attr_to_print :  &Option<String> ) -> String {
     name
 }
 
+const INKSCAPE_NAMESPACE: &str = "http://www.inkscape.org/namespaces/inkscape";
+
+/// Presentation attributes that are inherited by descendants
+///
+/// <https://www.w3.org/TR/SVG/propidx.html>
+const INHERITED_ATTRIBUTES: [&str; 15] = [
+    "color",
+    "fill",
+    "fill-opacity",
+    "fill-rule",
+    "stroke",
+    "stroke-dasharray",
+    "stroke-dashoffset",
+    "stroke-linecap",
+    "stroke-linejoin",
+    "stroke-opacity",
+    "stroke-width",
+    "visibility",
+    // Settings set on a group are used for the paths in it, see `LayerOverride::from_element`
+    "data-feedrate",
+    "data-passes",
+    "data-power",
+];
+
+/// The source of an element, given it followed by its ancestors as it is drawn (see
+/// [`visit::drawn_ancestors`]), with the attributes it inherits from them
+fn source_element(ancestors: &[Node]) -> SourceElement {
+    let node = ancestors[0];
+    let mut attributes: BTreeMap<String, String> = node
+        .attributes()
+        .map(|attr| (attr.name().to_string(), attr.value().to_string()))
+        .collect();
+    // Whichever is closest of the element and its ancestors to set each one, in its `style`
+    // (i.e. from a CSS rule) or as an attribute
+    for name in INHERITED_ATTRIBUTES {
+        if let Some(value) = paint::property(ancestors, name) {
+            attributes.insert(name.to_string(), value.to_string());
+        }
+    }
+
+    SourceElement {
+        tag_name: node.tag_name().name().to_string(),
+        id: node.attribute("id").map(str::to_string),
+        attributes,
+        layer: layer(&ancestors[1..]),
+    }
+}
+
+/// The source of the outline of an element's stroke, which is only filled between its sides by
+/// [`ConversionConfig::fill`] if it is `filled`, like a shape painted with the color of the stroke
+fn stroke_outline_source(ancestors: &[Node], filled: bool) -> SourceElement {
+    let mut source = source_element(ancestors);
+    let fill = if filled {
+        property(&source, "stroke").unwrap_or("black").to_string()
+    } else {
+        "none".to_string()
+    };
+    if let Some(style) = source.attributes.get_mut("style") {
+        *style = style
+            .split(';')
+            .filter(|declaration| {
+                declaration
+                    .split_once(':')
+                    .is_none_or(|(property, _)| property.trim() != "fill")
+            })
+            .collect::<Vec<_>>()
+            .join(";");
+    }
+    source.attributes.insert("fill".to_string(), fill);
+    source
+}
+
+/// Label of the closest Inkscape layer of the `ancestors` of an element, or else the id of its
+/// outermost group
+fn layer(ancestors: &[Node]) -> Option<String> {
+    let mut groups = ancestors
+        .iter()
+        .filter(|ancestor| ancestor.tag_name().name() == "g");
+    groups
+        .clone()
+        .find(|group| group.attribute((INKSCAPE_NAMESPACE, "groupmode")) == Some("layer"))
+        .and_then(|layer| layer.attribute((INKSCAPE_NAMESPACE, "label")))
+        .or_else(|| groups.next_back()?.attribute("id"))
+        .map(str::to_string)
+}
+
 #[cfg(all(test, feature = "serde"))]
 mod test {
     use super::*;
+    use crate::{Plunge, SupportedFunctionality, ToolKind};
     use svgtypes::LengthUnit;
 
     #[test]
@@ -238,17 +1708,19 @@ mod test {
 
     #[test]
     fn serde_conversion_options_with_both_dimensions_is_correct() {
-        let mut r#struct = ConversionOptions::default();
-        r#struct.dimensions = [
-            Some(Length {
-                number: 4.,
-                unit: LengthUnit::Mm,
-            }),
-            Some(Length {
-                number: 10.5,
-                unit: LengthUnit::In,
-            }),
-        ];
+        let r#struct = ConversionOptions {
+            dimensions: [
+                Some(Length {
+                    number: 4.,
+                    unit: LengthUnit::Mm,
+                }),
+                Some(Length {
+                    number: 10.5,
+                    unit: LengthUnit::In,
+                }),
+            ],
+            ..Default::default()
+        };
         let json = r#"{"dimensions":[{"number":4.0,"unit":"Mm"},{"number":10.5,"unit":"In"}]}"#;
 
         assert_eq!(serde_json::to_string(&r#struct).unwrap(), json);
@@ -257,4 +1729,1370 @@ mod test {
             r#struct
         );
     }
+
+    #[test]
+    fn pass_schedule_repeats_last_value() {
+        let config = ConversionConfig {
+            feedrate: 500.,
+            pass_power: vec![60., 100.],
+            ..Default::default()
+        };
+        assert_eq!(
+            (1..=4)
+                .map(|pass| config.pass_power(pass))
+                .collect::<Vec<_>>(),
+            [Some(60.), Some(100.), Some(100.), Some(100.)]
+        );
+        assert_eq!(config.pass_feedrate(3), 500.);
+    }
+
+    #[test]
+    fn perimeter_check_stops_at_each_corner() {
+        let toolpaths = Toolpaths {
+            toolpaths: vec![Toolpath::from_polyline(&[point(10., 20.), point(40., 5.)]).unwrap()],
+            ..Default::default()
+        };
+        let machine = Machine::new(Default::default(), None, None, None, None);
+        let program = toolpaths2perimeter_check(&toolpaths, &ConversionConfig::default(), machine);
+        let mut gcode = String::new();
+        g_code::emit::format_gcode_fmt(&program, Default::default(), &mut gcode).unwrap();
+        assert_eq!(gcode.matches("M0").count(), 4);
+        assert!(!gcode.contains("G1"));
+        for corner in ["G0 X10 Y5", "G0 X40 Y5", "G0 X40 Y20", "G0 X10 Y20"] {
+            assert!(gcode.contains(corner), "{corner} missing from {gcode}");
+        }
+    }
+
+    #[test]
+    fn frame_jogs_trace_bounding_box() {
+        let toolpaths = Toolpaths {
+            toolpaths: vec![Toolpath::from_polyline(&[point(10., 20.), point(40., 5.)]).unwrap()],
+            ..Default::default()
+        };
+        let machine = Machine::new(Default::default(), None, None, None, None);
+        assert_eq!(
+            toolpaths2frame_jogs(&toolpaths, &ConversionConfig::default(), &machine, 3000.),
+            [
+                "$J=G90 G21 X10 Y5 F3000",
+                "$J=G90 G21 X40 Y5 F3000",
+                "$J=G90 G21 X40 Y20 F3000",
+                "$J=G90 G21 X10 Y20 F3000",
+                "$J=G90 G21 X10 Y5 F3000",
+            ]
+        );
+    }
+
+    #[test]
+    fn origin_mode_picks_the_point_placed_at_the_origin() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="20mm" height="10mm" viewBox="0 0 20 10">
+            <path d="M 0 0 L 20 0 L 20 10"/>
+        </svg>"#;
+        let document = Document::parse(svg).unwrap();
+        let machine = || Machine::new(Default::default(), None, None, None, None);
+        let bounds = |origin_mode, invert_y| {
+            let config = ConversionConfig {
+                origin: [Some(0.), Some(0.)],
+                origin_mode,
+                invert_y,
+                ..Default::default()
+            };
+            let placement = place(&document, &config, &Default::default());
+            let toolpaths = svg2toolpaths(&document, &config, Default::default(), placement);
+            let bounds = bounding_box(&toolpaths.toolpaths, config.tolerance).unwrap();
+            let start = toolpaths.toolpaths[0].from;
+            let round = |value: f64| value.round() as i64;
+            (
+                [bounds.min.x, bounds.min.y, bounds.max.x, bounds.max.y].map(round),
+                [start.x, start.y].map(round),
+            )
+        };
+        assert_eq!(
+            bounds(OriginMode::BottomLeft, false),
+            ([0, 0, 20, 10], [0, 10])
+        );
+        assert_eq!(
+            bounds(OriginMode::TopLeft, false),
+            ([0, -10, 20, 0], [0, 0])
+        );
+        assert_eq!(
+            bounds(OriginMode::Center, false),
+            ([-10, -5, 10, 5], [-10, 5])
+        );
+        // The top of the artwork is toward the front of the bed, where y is higher
+        assert_eq!(
+            bounds(OriginMode::BottomLeft, true),
+            ([0, -10, 20, 0], [0, -10])
+        );
+        assert_eq!(bounds(OriginMode::TopLeft, true), ([0, 0, 20, 10], [0, 0]));
+
+        let config = ConversionConfig {
+            origin_mode: OriginMode::CurrentPosition,
+            ..Default::default()
+        };
+        let placement = place(&document, &config, &Default::default());
+        let toolpaths = svg2toolpaths(&document, &config, Default::default(), placement);
+        let mut gcode = String::new();
+        let program = toolpaths2program(
+            toolpaths.clone(),
+            &config,
+            &LayerOverrides::new(),
+            machine(),
+        );
+        g_code::emit::format_gcode_fmt(&program, Default::default(), &mut gcode).unwrap();
+        assert!(gcode.contains("G92 X0 Y0"));
+        assert!(gcode.contains("G92.1"));
+        assert_eq!(
+            toolpaths2frame_jogs(&toolpaths, &config, &machine(), 3000.),
+            [
+                "$J=G91 G21 X0 Y0 F3000",
+                "$J=G91 G21 X20 Y0 F3000",
+                "$J=G91 G21 X0 Y10 F3000",
+                "$J=G91 G21 X-20 Y0 F3000",
+                "$J=G91 G21 X0 Y-10 F3000",
+                "$J=G91 G21 X0 Y0 F3000",
+            ]
+        );
+    }
+
+    #[test]
+    fn nearly_closed_paths_are_closed() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="10mm" height="10mm" viewBox="0 0 10 10">
+            <path d="M 1 1 L 9 1 L 9 9 L 1.05 1"/>
+            <path d="M 1 2 L 5 2"/>
+        </svg>"#;
+        let document = Document::parse(svg).unwrap();
+        let closed = |close_tolerance| {
+            let config = ConversionConfig {
+                close_tolerance,
+                ..Default::default()
+            };
+            svg2toolpaths(
+                &document,
+                &config,
+                Default::default(),
+                Transform2D::identity(),
+            )
+            .toolpaths
+            .iter()
+            .map(|toolpath| toolpath.is_closed(0.))
+            .collect::<Vec<_>>()
+        };
+        assert_eq!(closed(0.), [false, false]);
+        assert_eq!(closed(0.1), [true, false]);
+    }
+
+    #[test]
+    fn kerf_moves_outlines_out_and_holes_in() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="20mm" height="20mm" viewBox="0 0 20 20">
+            <rect x="2" y="2" width="16" height="16"/>
+            <rect x="8" y="8" width="4" height="4"/>
+            <path d="M 0 0 L 5 0"/>
+        </svg>"#;
+        let document = Document::parse(svg).unwrap();
+        let sizes = |kerf_side| {
+            let config = ConversionConfig {
+                kerf_width: 1.,
+                kerf_side,
+                ..Default::default()
+            };
+            svg2toolpaths(
+                &document,
+                &config,
+                Default::default(),
+                Transform2D::identity(),
+            )
+            .toolpaths
+            .iter()
+            .map(|toolpath| {
+                let bounds = bounding_box(std::slice::from_ref(toolpath), 0.01).unwrap();
+                (bounds.width() * 1e3).round() / 1e3
+            })
+            .collect::<Vec<_>>()
+        };
+        assert_eq!(sizes(KerfSide::Outside), [17., 3., 5.]);
+        assert_eq!(sizes(KerfSide::Inside), [15., 5., 5.]);
+    }
+
+    #[test]
+    fn kerf_is_made_up_for_with_the_tool_of_each_layer() {
+        let document = Document::parse(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:inkscape="http://www.inkscape.org/namespaces/inkscape" width="20mm" height="20mm" viewBox="0 0 20 20">
+                <g inkscape:groupmode="layer" inkscape:label="pockets">
+                    <rect x="8" y="8" width="4" height="4"/>
+                </g>
+                <rect x="2" y="2" width="16" height="16"/>
+            </svg>"#,
+        )
+        .unwrap();
+        let tool = |name: &str, diameter| Tool {
+            name: name.to_string(),
+            kind: ToolKind::EndMill,
+            diameter,
+            angle: 0.,
+            max_depth_of_cut: 1.,
+            max_feedrate: 0.,
+        };
+        let config = ConversionConfig {
+            tools: vec![tool("wide", 2.), tool("narrow", 0.5)],
+            tool: Some("wide".to_string()),
+            ..Default::default()
+        };
+        let options = ConversionOptions {
+            layer_overrides: LayerOverrides::from([(
+                "pockets".to_string(),
+                LayerOverride {
+                    tool: Some("narrow".to_string()),
+                    ..Default::default()
+                },
+            )]),
+            ..Default::default()
+        };
+        let sizes = svg2toolpaths(&document, &config, options, Transform2D::identity())
+            .toolpaths
+            .iter()
+            .map(|toolpath| {
+                let bounds = bounding_box(std::slice::from_ref(toolpath), 0.01).unwrap();
+                (bounds.width() * 1e3).round() / 1e3
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(sizes, [3.5, 18.]);
+    }
+
+    #[test]
+    fn finishing_pass_takes_off_the_allowance_at_full_depth() {
+        let document = Document::parse(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="10mm" height="10mm" viewBox="0 0 10 10">
+                <rect x="2" y="2" width="6" height="6"/>
+            </svg>"#,
+        )
+        .unwrap();
+        let config = ConversionConfig {
+            passes: 2,
+            material: Material {
+                thickness: 2.,
+                ..Default::default()
+            },
+            finish_allowance: 0.5,
+            finish_feedrate: 100.,
+            ..Default::default()
+        };
+        assert_eq!(config.total_passes(), 3);
+        assert_eq!(config.pass_feedrate(2), 300.);
+        assert_eq!(config.pass_feedrate(3), 100.);
+        assert_eq!(config.pass_depth(3), 2.);
+
+        let machine =
+            Machine::new(Default::default(), None, None, None, None).with_step_size([0.01; 2]);
+        let program = svg2program(&document, &config, Default::default(), machine);
+        let mut gcode = String::new();
+        g_code::emit::format_gcode_fmt(&program, Default::default(), &mut gcode).unwrap();
+        let (rough, finish) = gcode
+            .split_once("finishing pass to 2 mm deep, Z-2")
+            .unwrap();
+        assert_eq!(rough.matches("pass ").count(), 2);
+        // Each side is left half a millimeter farther out, then cut on the outline
+        assert!(rough.contains("G1 X8.5 Y2\n"));
+        assert!(finish.contains("G1 X8 Y2\n") && !finish.contains("8.5"));
+        assert!(finish.contains("F100"));
+    }
+
+    #[test]
+    fn holes_are_left_rough_from_inside_in_layers_of_their_own() {
+        let document = Document::parse(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:inkscape="http://www.inkscape.org/namespaces/inkscape" width="10mm" height="10mm" viewBox="0 0 10 10">
+                <g inkscape:groupmode="layer" inkscape:label="outline">
+                    <rect x="1" y="1" width="8" height="8"/>
+                </g>
+                <g inkscape:groupmode="layer" inkscape:label="holes">
+                    <rect x="4" y="4" width="2" height="2"/>
+                </g>
+            </svg>"#,
+        )
+        .unwrap();
+        let config = ConversionConfig {
+            finish_allowance: 0.5,
+            ..Default::default()
+        };
+        let options = ConversionOptions {
+            layer_overrides: LayerOverrides::from(["outline", "holes"].map(|name| {
+                (
+                    name.to_string(),
+                    LayerOverride {
+                        feedrate: Some(100.),
+                        ..Default::default()
+                    },
+                )
+            })),
+            ..Default::default()
+        };
+        let machine =
+            Machine::new(Default::default(), None, None, None, None).with_step_size([0.01; 2]);
+        let program = svg2program(&document, &config, options, machine);
+        let mut gcode = String::new();
+        g_code::emit::format_gcode_fmt(&program, Default::default(), &mut gcode).unwrap();
+        // The hole is left smaller and the outline bigger, so both leave material on the part
+        assert!(gcode.contains("X5.5 Y5.5"));
+        assert!(!gcode.contains("X6.5"));
+        assert!(gcode.contains("G1 X0.5 Y9\n"));
+    }
+
+    #[test]
+    fn holes_are_climb_milled_the_other_way_in_layers_of_their_own() {
+        let document = Document::parse(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:inkscape="http://www.inkscape.org/namespaces/inkscape" width="10mm" height="10mm" viewBox="0 0 10 10">
+                <g inkscape:groupmode="layer" inkscape:label="outline">
+                    <rect x="1" y="1" width="8" height="8"/>
+                </g>
+                <g inkscape:groupmode="layer" inkscape:label="holes">
+                    <rect x="4" y="4" width="2" height="2"/>
+                </g>
+            </svg>"#,
+        )
+        .unwrap();
+        let config = ConversionConfig {
+            cut_direction: CutDirection::Climb,
+            ..Default::default()
+        };
+        let options = ConversionOptions {
+            layer_overrides: LayerOverrides::from(["outline", "holes"].map(|name| {
+                (
+                    name.to_string(),
+                    LayerOverride {
+                        feedrate: Some(100.),
+                        ..Default::default()
+                    },
+                )
+            })),
+            ..Default::default()
+        };
+        let machine =
+            Machine::new(Default::default(), None, None, None, None).with_step_size([0.01; 2]);
+        let program = svg2program(&document, &config, options, machine);
+        let mut gcode = String::new();
+        g_code::emit::format_gcode_fmt(&program, Default::default(), &mut gcode).unwrap();
+        // Clockwise around the outline, keeping the part on the right
+        assert!(gcode.contains("G1 X9 Y1\nG1 X1 Y1\nG1 X1 Y9\n"));
+        // Counterclockwise around the hole, though it is in another layer
+        assert!(gcode.contains("G1 X4 Y4\nG1 X6 Y4\nG1 X6 Y6\n"));
+    }
+
+    #[test]
+    fn paths_are_cut_last_by_layer_or_stroke() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:inkscape="http://www.inkscape.org/namespaces/inkscape" width="10mm" height="10mm" viewBox="0 0 10 10">
+            <g inkscape:groupmode="layer" inkscape:label="Outline">
+                <path id="outline" d="M 0 0 L 10 0"/>
+            </g>
+            <path id="red" stroke="red" d="M 0 9 L 1 9"/>
+            <path id="inside" d="M 0 8 L 1 8"/>
+        </svg>"#;
+        let document = Document::parse(svg).unwrap();
+        let config = ConversionConfig {
+            cut_last: vec!["Outline".to_string(), "#f00".to_string()],
+            ..Default::default()
+        };
+        let order = |path_ordering| {
+            let options = ConversionOptions {
+                path_ordering,
+                ..Default::default()
+            };
+            svg2toolpaths(&document, &config, options, Transform2D::identity())
+                .toolpaths
+                .iter()
+                .filter_map(|toolpath| toolpath.source.as_ref()?.id.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(order(PathOrdering::Document), ["inside", "outline", "red"]);
+        // The outline starts closest to the origin, but is still cut after the rest
+        assert_eq!(order(PathOrdering::NearestNeighbor)[0], "inside");
+    }
+
+    #[test]
+    fn optimized_order_stops_within_the_time_budget() {
+        // Short strokes scattered by a linear congruential generator, more than the moves can be
+        // tried on in time
+        let mut seed = 1u64;
+        let mut random = || {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 11) as f64 / (1u64 << 53) as f64
+        };
+        let toolpaths = (0..5000)
+            .map(|_| {
+                let from = point(random(), random()) * 1000.;
+                Toolpath::from_polyline(&[from, from + vector(random(), random())]).unwrap()
+            })
+            .collect::<Vec<_>>();
+        let config = ConversionConfig {
+            order_time_budget: 0.1,
+            ..Default::default()
+        };
+        let time = |path_ordering| {
+            let started = Instant::now();
+            let ordered = optimize_order(toolpaths.clone(), point(0., 0.), path_ordering, &config);
+            assert_eq!(ordered.len(), toolpaths.len());
+            started.elapsed()
+        };
+        let nearest = time(PathOrdering::NearestNeighbor);
+        // Improving on the nearest-first order stops once the budget runs out
+        assert!(time(PathOrdering::Optimized) < nearest + Duration::from_secs_f64(0.1 + 0.5));
+    }
+
+    #[test]
+    fn dashes_are_measured_before_transforming() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="10mm" height="10mm" viewBox="0 0 10 10">
+            <path id="dashed" stroke="black" stroke-dasharray="4" transform="scale(0.5)" d="M 0 0 L 20 0"/>
+            <path id="filled" stroke-dasharray="4" d="M 0 5 L 10 5"/>
+        </svg>"#;
+        let document = Document::parse(svg).unwrap();
+        let toolpaths = svg2toolpaths(
+            &document,
+            &ConversionConfig::default(),
+            Default::default(),
+            Transform2D::identity(),
+        )
+        .toolpaths;
+        let lengths = toolpaths
+            .iter()
+            .map(|toolpath| {
+                let id = toolpath
+                    .source
+                    .as_ref()
+                    .and_then(|source| source.id.clone());
+                (id.unwrap(), (toolpath.length(0.01) * 1e6).round() / 1e6)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lengths,
+            [
+                ("dashed".to_string(), 2.),
+                ("dashed".to_string(), 2.),
+                ("dashed".to_string(), 2.),
+                ("filled".to_string(), 10.)
+            ]
+        );
+    }
+
+    #[test]
+    fn dashes_are_as_smooth_at_any_view_box_scale() {
+        let points = |scale: f64| {
+            let svg = format!(
+                r#"<svg xmlns="http://www.w3.org/2000/svg" width="10mm" height="10mm" viewBox="0 0 {size} {size}">
+                    <circle stroke="black" stroke-dasharray="{dash}" cx="{center}" cy="{center}" r="{center}"/>
+                </svg>"#,
+                size = 10. * scale,
+                center = 5. * scale,
+                dash = 2. * scale,
+            );
+            let document = Document::parse(&svg).unwrap();
+            svg2toolpaths(
+                &document,
+                &ConversionConfig::default(),
+                Default::default(),
+                Transform2D::identity(),
+            )
+            .toolpaths
+            .iter()
+            .map(|toolpath| toolpath.flattened(1.).len())
+            .sum::<usize>()
+        };
+        assert_eq!(points(0.1), points(1.));
+        assert_eq!(points(10.), points(1.));
+    }
+
+    #[test]
+    fn wide_strokes_are_cut_around_both_sides() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="10mm" height="10mm" viewBox="0 0 10 10">
+            <path id="wide" stroke="black" stroke-width="2" transform="scale(0.5)" d="M 2 10 L 18 10"/>
+            <rect id="ring" x="2" y="2" width="6" height="6" stroke="red" stroke-width="1" fill="none"/>
+            <path id="thin" stroke="black" stroke-width="0.1" d="M 0 0 L 10 0"/>
+        </svg>"#;
+        let document = Document::parse(svg).unwrap();
+        let toolpaths = |stroke_outline| {
+            let config = ConversionConfig {
+                stroke_outline,
+                kerf_width: 0.2,
+                kerf_side: KerfSide::Outside,
+                fill: Some(Fill::default()),
+                ..Default::default()
+            };
+            svg2toolpaths(
+                &document,
+                &config,
+                Default::default(),
+                Transform2D::identity(),
+            )
+            .toolpaths
+        };
+        let sizes = |toolpaths: &[Toolpath]| {
+            toolpaths
+                .iter()
+                .map(|toolpath| {
+                    let id = toolpath
+                        .source
+                        .as_ref()
+                        .and_then(|source| source.id.clone());
+                    let bounds = bounding_box(std::slice::from_ref(toolpath), 0.01).unwrap();
+                    // Rounded ends are flattened, so they come up a little short
+                    let round = |length: f64| (length * 10.).round() / 10.;
+                    (id.unwrap(), round(bounds.width()), round(bounds.height()))
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // With the width of the cut made up for on the outside of the outline
+        let outlined = toolpaths(StrokeOutline::Outline);
+        assert_eq!(
+            sizes(&outlined),
+            [
+                ("wide".to_string(), 9.2, 1.2),
+                ("ring".to_string(), 7.2, 7.2),
+                ("ring".to_string(), 4.8, 4.8),
+                ("thin".to_string(), 10., 0.)
+            ]
+        );
+
+        // Filled between the sides like a shape filled with the stroke
+        let filled = toolpaths(StrokeOutline::Filled);
+        assert!(filled.len() > outlined.len());
+        let ring = filled
+            .iter()
+            .filter(|toolpath| toolpath.source.as_ref().unwrap().id.as_deref() == Some("ring"))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            property(ring[0].source.as_ref().unwrap(), "fill"),
+            Some("red")
+        );
+        assert!(ring.len() > 2);
+
+        assert_eq!(toolpaths(StrokeOutline::Centerline).len(), 3);
+    }
+
+    #[test]
+    fn tool_stays_down_over_short_gaps() {
+        let line = |from: (f64, f64), to: (f64, f64)| {
+            Toolpath::from_polyline(&[point(from.0, from.1), point(to.0, to.1)]).unwrap()
+        };
+        let gcode = |config: &ConversionConfig| {
+            let toolpaths = Toolpaths {
+                toolpaths: vec![
+                    line((0., 0.), (1., 0.)),
+                    line((1.5, 0.), (2., 0.)),
+                    line((2.5, 0.), (3., 0.)),
+                    line((10., 0.), (11., 0.)),
+                ],
+                ..Default::default()
+            };
+            let tool_off = g_code::parse::snippet_parser("M5").unwrap();
+            let machine = Machine::new(Default::default(), None, Some(tool_off), None, None);
+            let program = toolpaths2program(toolpaths, config, &LayerOverrides::new(), machine);
+            let mut gcode = String::new();
+            g_code::emit::format_gcode_fmt(&program, Default::default(), &mut gcode).unwrap();
+            gcode
+        };
+        let tool_offs = |config: &ConversionConfig| gcode(config).matches("M5").count();
+        // Before each path and at the end
+        assert_eq!(tool_offs(&ConversionConfig::default()), 5);
+        let config = ConversionConfig {
+            stay_down_gap: 0.5,
+            ..Default::default()
+        };
+        assert_eq!(tool_offs(&config), 3);
+        // Cut across at the feedrate of the cut, not rushed over with the tool on
+        let stayed_down = gcode(&config);
+        assert!(stayed_down.contains("G1 X1.5 Y0\n"));
+        assert_eq!(stayed_down.matches("G0").count(), 2);
+        let config = ConversionConfig {
+            stay_down_gap: 0.5,
+            stay_down_travel: 0.75,
+            ..Default::default()
+        };
+        assert_eq!(tool_offs(&config), 4);
+    }
+
+    #[test]
+    fn layers_are_drawn_with_their_own_settings() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:inkscape="http://www.inkscape.org/namespaces/inkscape" width="10mm" height="10mm" viewBox="0 0 10 10">
+            <g inkscape:groupmode="layer" inkscape:label="cut">
+                <path d="M 0 0 L 10 0"/>
+            </g>
+            <g inkscape:groupmode="layer" inkscape:label="engrave">
+                <path d="M 0 5 L 10 5"/>
+            </g>
+        </svg>"#;
+        let document = Document::parse(svg).unwrap();
+        let options = ConversionOptions {
+            layer_overrides: LayerOverrides::from([(
+                "cut".to_string(),
+                LayerOverride {
+                    feedrate: Some(100.),
+                    passes: Some(2),
+                    tool_on_sequence: Some("M3 S1000".to_string()),
+                    ..Default::default()
+                },
+            )]),
+            ..Default::default()
+        };
+        let tool_on = g_code::parse::snippet_parser("M4 S200").unwrap();
+        let machine = Machine::new(Default::default(), Some(tool_on), None, None, None);
+        let program = svg2program(&document, &ConversionConfig::default(), options, machine);
+        let mut gcode = String::new();
+        g_code::emit::format_gcode_fmt(&program, Default::default(), &mut gcode).unwrap();
+        assert_eq!(gcode.matches("M3 S1000").count(), 2);
+        assert!(gcode.contains("F100"));
+        // The engraving is drawn once, after both passes over the cut
+        assert_eq!(gcode.matches("M4 S200").count(), 1);
+        assert!(gcode.rfind("M3 S1000") < gcode.find("M4 S200"));
+        assert!(gcode.find("M4 S200") < gcode.find("F300"));
+    }
+
+    #[test]
+    fn layer_settings_that_cant_be_used_are_left_out() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:inkscape="http://www.inkscape.org/namespaces/inkscape" width="10mm" height="10mm" viewBox="0 0 10 10">
+            <g inkscape:groupmode="layer" inkscape:label="cut">
+                <path d="M 0 0 L 10 0"/>
+            </g>
+            <path d="M 0 5 L 10 5"/>
+        </svg>"#;
+        let document = Document::parse(svg).unwrap();
+        let options = ConversionOptions {
+            layer_overrides: LayerOverrides::from([(
+                "cut".to_string(),
+                LayerOverride {
+                    feedrate: Some(-100.),
+                    passes: Some(0),
+                    tool_on_sequence: Some("G28 X".to_string()),
+                    ..Default::default()
+                },
+            )]),
+            ..Default::default()
+        };
+        assert!(matches!(
+            parse_layer_sequence("G28 X"),
+            Err(LayerSequenceError::Flag(letter)) if letter == "X"
+        ));
+        let tool_on = g_code::parse::snippet_parser("M4 S200").unwrap();
+        let machine = Machine::new(Default::default(), Some(tool_on), None, None, None);
+        let program = svg2program(&document, &ConversionConfig::default(), options, machine);
+        let mut gcode = String::new();
+        g_code::emit::format_gcode_fmt(&program, Default::default(), &mut gcode).unwrap();
+        // Still drawn once at the usual feedrate, turning the tool on as the machine does
+        assert_eq!(gcode.matches("G1 X10").count(), 2);
+        assert_eq!(gcode.matches("M4 S200").count(), 2);
+        assert!(!gcode.contains("G28") && !gcode.contains("F-100"));
+    }
+
+    #[test]
+    fn elements_are_drawn_with_the_settings_they_ask_for() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="10mm" height="10mm" viewBox="0 0 10 10">
+            <g data-power="250">
+                <path data-feedrate="123" data-passes="3" d="M 0 0 L 10 0"/>
+            </g>
+            <path data-passes="many" d="M 0 5 L 10 5"/>
+        </svg>"#;
+        let document = Document::parse(svg).unwrap();
+        let config = ConversionConfig::default();
+        let toolpaths = svg2toolpaths(
+            &document,
+            &config,
+            ConversionOptions::default(),
+            Transform2D::identity(),
+        );
+        let settings = by_layer(toolpaths.toolpaths, &LayerOverrides::new())
+            .iter()
+            .map(|(_, element, _)| element.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            settings,
+            [
+                LayerOverride {
+                    feedrate: Some(123.),
+                    power: Some(250.),
+                    passes: Some(3),
+                    ..Default::default()
+                },
+                LayerOverride::default(),
+            ]
+        );
+
+        let tool_on = g_code::parse::snippet_parser("M4").unwrap();
+        let machine = Machine::new(Default::default(), Some(tool_on), None, None, None);
+        let program = svg2program(&document, &config, Default::default(), machine);
+        let mut gcode = String::new();
+        g_code::emit::format_gcode_fmt(&program, Default::default(), &mut gcode).unwrap();
+        assert_eq!(gcode.matches("M4").count(), 4);
+        assert!(gcode.contains("F123"));
+    }
+
+    #[test]
+    fn element_settings_keep_the_cut_order() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="20mm" height="20mm" viewBox="0 0 20 20">
+            <rect x="8" y="8" width="4" height="4"/>
+            <rect data-passes="2" x="2" y="2" width="16" height="16"/>
+            <rect data-passes="0" x="14" y="14" width="2" height="2"/>
+        </svg>"#;
+        let document = Document::parse(svg).unwrap();
+        let config = ConversionConfig::default();
+        let toolpaths = svg2toolpaths(
+            &document,
+            &config,
+            ConversionOptions::default(),
+            Transform2D::identity(),
+        );
+        // The hole is still cut before the outline, and 0 passes is ignored instead of dropping
+        // the last rect
+        let settings = by_layer(toolpaths.toolpaths.clone(), &LayerOverrides::new())
+            .iter()
+            .map(|(_, element, toolpaths)| (element.passes, toolpaths.len()))
+            .collect::<Vec<_>>();
+        assert_eq!(settings, [(None, 1), (Some(2), 1), (None, 1)]);
+
+        // The estimate is of the program, so it counts the extra pass
+        let machine = Machine::new(Default::default(), None, None, None, None);
+        let program = toolpaths2program(toolpaths, &config, &LayerOverrides::new(), machine);
+        let estimate = JobEstimate::from_program(&program, None, &CostConfig::default()).unwrap();
+        assert!((estimate.cutting_distance - (16. + 2. * 64. + 8.)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn element_settings_take_precedence_over_the_tool() {
+        let config = ConversionConfig {
+            tools: vec![Tool {
+                name: "bit".to_string(),
+                kind: ToolKind::EndMill,
+                diameter: 3.,
+                angle: 0.,
+                max_depth_of_cut: 1.,
+                max_feedrate: 500.,
+            }],
+            tool: Some("bit".to_string()),
+            material: Material {
+                thickness: 3.,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let element = LayerOverride {
+            feedrate: Some(800.),
+            passes: Some(2),
+            ..Default::default()
+        };
+        let config = group_config(&config, None, &element);
+        assert_eq!((config.feedrate, config.passes), (800., 2));
+    }
+
+    #[test]
+    fn z_axis_is_lifted_for_rapid_moves_and_plunged_to_each_pass() {
+        let document = Document::parse(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="10mm" height="10mm" viewBox="0 0 10 10">
+                <path d="M 1 1 L 9 1"/>
+                <path d="M 1 5 L 9 5"/>
+            </svg>"#,
+        )
+        .unwrap();
+        let config = ConversionConfig {
+            passes: 2,
+            material: Material {
+                thickness: 2.,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let machine = Machine::new(Default::default(), None, None, None, None).with_z_moves(
+            Some(5.),
+            0.,
+            100.,
+        );
+        let program = svg2program(&document, &config, Default::default(), machine);
+        let mut gcode = String::new();
+        g_code::emit::format_gcode_fmt(&program, Default::default(), &mut gcode).unwrap();
+        let lines = gcode.lines().collect::<Vec<_>>();
+        for (i, line) in lines.iter().enumerate() {
+            if line.starts_with("G0 X") {
+                assert!(lines[..i].iter().rev().find(|line| line.contains('Z')) == Some(&"G0 Z5"));
+            }
+        }
+        assert_eq!(gcode.matches("G1 Z-1 F100").count(), 2);
+        assert_eq!(gcode.matches("G1 Z-2 F100").count(), 2);
+        assert!(lines.last().unwrap().starts_with("G0 Z5"));
+    }
+
+    #[test]
+    fn rapid_moves_are_lifted_when_staying_down_over_gaps() {
+        let document = Document::parse(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="10mm" height="10mm" viewBox="0 0 10 10">
+                <path d="M 1 1 L 4 1"/>
+                <path d="M 4.5 1 L 9 1"/>
+                <path d="M 1 5 L 9 5"/>
+            </svg>"#,
+        )
+        .unwrap();
+        let config = ConversionConfig {
+            stay_down_gap: 1.,
+            ..Default::default()
+        };
+        let machine = Machine::new(Default::default(), None, None, None, None).with_z_moves(
+            Some(5.),
+            0.,
+            100.,
+        );
+        let program = svg2program(&document, &config, Default::default(), machine);
+        let mut gcode = String::new();
+        g_code::emit::format_gcode_fmt(&program, Default::default(), &mut gcode).unwrap();
+        let lines = gcode.lines().collect::<Vec<_>>();
+        for (i, line) in lines.iter().enumerate() {
+            if line.starts_with("G0 X") {
+                assert!(lines[..i].iter().rev().find(|line| line.contains('Z')) == Some(&"G0 Z5"));
+            }
+        }
+        // Once for each of the paths that aren't close to the last one
+        assert_eq!(gcode.matches("G0 X").count(), 2);
+        assert_eq!(gcode.matches("G1 Z0 F100").count(), 2);
+        // Before each of them and at the end
+        assert_eq!(gcode.matches("G0 Z5").count(), 3);
+    }
+
+    #[test]
+    fn tool_ramps_and_spirals_down_from_the_surface() {
+        let line = r#"<svg xmlns="http://www.w3.org/2000/svg" width="10mm" height="10mm" viewBox="0 0 10 10">
+                <path d="M 1 1 L 9 1"/>
+            </svg>"#;
+        let square = r#"<svg xmlns="http://www.w3.org/2000/svg" width="10mm" height="10mm" viewBox="0 0 10 10">
+                <path d="M 5 1 L 9 1 L 9 9 L 1 9 L 1 1 Z"/>
+            </svg>"#;
+        let gcode = |svg, plunge, circular_interpolation, ramp_angle, thickness| {
+            let document = Document::parse(svg).unwrap();
+            let config = ConversionConfig {
+                material: Material {
+                    thickness,
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            let functionality = SupportedFunctionality {
+                circular_interpolation,
+                arc_center_offsets: true,
+            };
+            let machine = Machine::new(functionality, None, None, None, None)
+                .with_z_moves(Some(5.), 0., 100.)
+                .with_plunge(plunge, ramp_angle, 1.)
+                .with_step_size([0.01; 2]);
+            let program = svg2program(&document, &config, Default::default(), machine);
+            let mut gcode = String::new();
+            g_code::emit::format_gcode_fmt(&program, Default::default(), &mut gcode).unwrap();
+            gcode
+        };
+
+        // Down to the surface, then along the path and back to its start
+        let ramp = gcode(line, Plunge::Ramp, false, 45., 1.);
+        let lines = ramp.lines().collect::<Vec<_>>();
+        let surface = lines.iter().position(|line| *line == "G1 Z0 F100").unwrap();
+        assert_eq!(
+            lines[surface + 1..surface + 3],
+            ["G1 X2 Y9 Z-1", "G1 X1 Y9 Z-1"]
+        );
+        assert!(!ramp.contains("G1 Z-1"));
+
+        // A full circle inside the path, touching it where it starts, then another at the bottom
+        let helix = gcode(square, Plunge::Helix, true, 45., 1.);
+        assert_eq!(helix.matches("G3 X5 Y9 Z-1 I0 J-1").count(), 2);
+        let lines = gcode(square, Plunge::Helix, false, 45., 1.);
+        assert_eq!(lines.matches("G1 X").count(), 2 * 16 + 5);
+        // Open paths have no inside to spiral down in
+        assert_eq!(
+            gcode(line, Plunge::Helix, true, 45., 1.),
+            gcode(line, Plunge::Ramp, true, 45., 1.)
+        );
+
+        assert!(gcode(line, Plunge::Straight, false, 45., 1.).contains("G1 Z-1 F100"));
+        // Without an angle, ramps are no steeper than 3 degrees instead of going straight down
+        let shallow = gcode(line, Plunge::Ramp, false, 0., 0.5);
+        assert!(!shallow.contains("G1 Z-0.5 F100"));
+        assert!(shallow.contains("Z-0.5\n"));
+    }
+
+    #[test]
+    fn later_passes_go_straight_down_to_the_one_before() {
+        let document = Document::parse(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="100mm" height="10mm" viewBox="0 0 100 10">
+                <path d="M 0 1 L 100 1"/>
+            </svg>"#,
+        )
+        .unwrap();
+        let config = ConversionConfig {
+            passes: 3,
+            material: Material {
+                thickness: 3.,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let machine = |plunge| {
+            Machine::new(Default::default(), None, None, None, None)
+                .with_z_moves(Some(5.), 0., 100.)
+                .with_plunge(plunge, 45., 0.)
+                .with_step_size([0.01; 2])
+        };
+        let program = svg2program(
+            &document,
+            &config,
+            Default::default(),
+            machine(Plunge::Ramp),
+        );
+        let mut gcode = String::new();
+        g_code::emit::format_gcode_fmt(&program, Default::default(), &mut gcode).unwrap();
+        for (floor, bottom) in [(0, -1), (-1, -2), (-2, -3)] {
+            assert!(
+                gcode.contains(&format!("G1 Z{floor} F100\nG1 X1 Y9 Z{bottom}")),
+                "{gcode}"
+            );
+        }
+
+        // The ramps take longer than going straight down
+        let estimate = |plunge| {
+            let program = svg2program(&document, &config, Default::default(), machine(plunge));
+            JobEstimate::from_program(&program, None, &CostConfig::default())
+                .unwrap()
+                .duration
+        };
+        assert!(estimate(Plunge::Ramp) > estimate(Plunge::Straight));
+    }
+
+    #[test]
+    #[cfg(feature = "raster")]
+    fn images_are_engraved_before_paths() {
+        use base64::{engine::general_purpose::STANDARD, Engine};
+
+        // Black, black, white, and black pixels in a row
+        let image = STANDARD.encode("P2 4 1 2\n0 0 2 0\n");
+        let svg = format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="10mm" height="10mm" viewBox="0 0 10 10">
+                <path d="M 0 9 L 10 9"/>
+                <image x="0" y="0" width="4" height="1" href="data:image/x-portable-graymap;base64,{image}"/>
+            </svg>"#
+        );
+        let document = Document::parse(&svg).unwrap();
+        let lines = |raster| {
+            let config = ConversionConfig {
+                raster,
+                ..Default::default()
+            };
+            let machine = Machine::new(Default::default(), None, None, None, None);
+            let program = svg2program(&document, &config, Default::default(), machine);
+            let mut gcode = String::new();
+            g_code::emit::format_gcode_fmt(&program, Default::default(), &mut gcode).unwrap();
+            gcode.lines().map(str::to_string).collect::<Vec<_>>()
+        };
+        assert!(!lines(None).iter().any(|line| line.contains('S')));
+
+        let lines = lines(Some(RasterConfig {
+            line_interval: 0.5,
+            max_power: 500.,
+            ..Default::default()
+        }));
+        let position = |pattern: &str| lines.iter().position(|line| line.ends_with(pattern));
+        assert!(position("S500").is_some());
+        assert!(position("S0").is_some());
+        assert!(position("S500") < position("F300"));
+        assert!(lines.iter().any(|line| line.contains("F3000")));
+    }
+
+    #[test]
+    fn arc_centers_are_written_as_offsets() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="10mm" height="10mm" viewBox="0 0 10 10">
+            <path d="M 10 5 A 5 5 0 0 1 5 10"/>
+        </svg>"#;
+        let document = Document::parse(svg).unwrap();
+        let config = ConversionConfig {
+            origin: [Some(0.), Some(0.)],
+            ..Default::default()
+        };
+        let arc = |arc_center_offsets| {
+            let machine = Machine::new(
+                crate::SupportedFunctionality {
+                    circular_interpolation: true,
+                    arc_center_offsets,
+                },
+                None,
+                None,
+                None,
+                None,
+            );
+            let program = svg2program(&document, &config, Default::default(), machine);
+            let mut gcode = vec![];
+            crate::write_gcode(&program, &Default::default(), Some(3), 0, false, &mut gcode)
+                .unwrap();
+            String::from_utf8(gcode)
+                .unwrap()
+                .lines()
+                .find(|line| line.starts_with("G2 "))
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(arc(false), "G2 X5 Y0 R5 F300");
+        assert_eq!(arc(true), "G2 X5 Y0 I-5 J0 F300");
+    }
+
+    #[test]
+    fn sender_markers_mark_passes_and_progress() {
+        let toolpaths = Toolpaths {
+            toolpaths: vec![
+                Toolpath::from_polyline(&[point(0., 0.), point(10., 0.)]).unwrap(),
+                Toolpath::from_polyline(&[point(0., 5.), point(10., 5.)]).unwrap(),
+            ],
+            ..Default::default()
+        };
+        let config = ConversionConfig {
+            passes: 2,
+            sender_markers: true,
+            ..Default::default()
+        };
+        let machine = Machine::new(Default::default(), None, None, None, None);
+        let program = toolpaths2program(toolpaths, &config, &LayerOverrides::new(), machine);
+        let mut gcode = String::new();
+        g_code::emit::format_gcode_fmt(&program, Default::default(), &mut gcode).unwrap();
+        let markers = gcode
+            .lines()
+            .filter_map(|line| line.split_once(';').map(|(_, comment)| comment))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            markers,
+            [
+                "LAYER_COUNT:2",
+                "LAYER:0",
+                "PROGRESS:25",
+                "LAYER:1",
+                "PROGRESS:50",
+                "PROGRESS:75",
+                "PROGRESS:100"
+            ]
+        );
+    }
+
+    #[test]
+    fn switch_draws_first_matching_child() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="10mm" height="10mm" viewBox="0 0 10 10">
+            <switch>
+                <path systemLanguage="de" d="M 0 0 L 1 0"/>
+                <path systemLanguage="fr, en-US" d="M 0 0 L 2 0"/>
+                <path d="M 0 0 L 3 0"/>
+            </switch>
+            <path requiredExtensions="http://ns.adobe.com/AdobeIllustrator/10.0/" d="M 0 0 L 4 0"/>
+            <path requiredFeatures="" d="M 0 0 L 5 0"/>
+        </svg>"#;
+        let document = Document::parse(svg).unwrap();
+        let lengths = |language: &str| {
+            let config = ConversionConfig {
+                language: language.to_string(),
+                ..Default::default()
+            };
+            svg2toolpaths(
+                &document,
+                &config,
+                Default::default(),
+                Transform2D::identity(),
+            )
+            .toolpaths
+            .iter()
+            .map(|toolpath| toolpath.length(config.tolerance).round())
+            .collect::<Vec<_>>()
+        };
+        assert_eq!(lengths("en"), [2.]);
+        assert_eq!(lengths("DE"), [1.]);
+        assert_eq!(lengths("es"), [3.]);
+    }
+
+    #[test]
+    fn use_draws_referenced_elements_in_place() {
+        let svg = r##"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="10mm" height="10mm" viewBox="0 0 10 10">
+            <defs>
+                <path id="tick" d="M 0 0 L 1 0"/>
+                <symbol id="box" viewBox="0 0 10 10"><path d="M 0 0 L 10 0"/></symbol>
+            </defs>
+            <use href="#tick" x="2" y="3"/>
+            <use xlink:href="#tick" x="2" y="3" transform="translate(1 0)"/>
+            <use href="#box" x="5" y="5" width="2" height="2"/>
+            <g id="loop"><use href="#loop"/></g>
+        </svg>"##;
+        let document = Document::parse(svg).unwrap();
+        let config = ConversionConfig::default();
+        let toolpaths = svg2toolpaths(
+            &document,
+            &config,
+            Default::default(),
+            Transform2D::identity(),
+        )
+        .toolpaths;
+        let drawn = toolpaths
+            .iter()
+            .map(|toolpath| {
+                [
+                    toolpath.from.x,
+                    toolpath.from.y,
+                    toolpath.length(config.tolerance),
+                ]
+                .map(|d| (d * 1e6).round() / 1e6)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(drawn, [[2., 7., 1.], [3., 7., 1.], [5., 5., 2.]]);
+    }
+
+    #[cfg(feature = "text")]
+    #[test]
+    fn text_is_drawn_along_its_text_path() {
+        let svg = r##"<svg xmlns="http://www.w3.org/2000/svg" width="10mm" height="10mm" viewBox="0 0 10 10">
+            <path id="down" d="M 5 0 L 5 10" fill="none"/>
+            <text font-family="sans" font-size="2"><textPath href="#down" startOffset="20%">II</textPath></text>
+        </svg>"##;
+        let document = Document::parse(svg).unwrap();
+        let config = ConversionConfig::default();
+        let toolpaths = svg2toolpaths(
+            &document,
+            &config,
+            Default::default(),
+            Transform2D::identity(),
+        )
+        .toolpaths;
+        let strokes = toolpaths[1..]
+            .iter()
+            .map(|toolpath| {
+                [toolpath.from, toolpath.to()].map(|p| [p.x, p.y].map(|d| (d * 1e3).round() / 1e3))
+            })
+            .collect::<Vec<_>>();
+        // Each I is turned to go down the path from 2 mm along it, with its top to the left of
+        // the path and its bottom on it
+        assert_eq!(
+            strokes,
+            [[[6.313, 7.75], [5., 7.75]], [[6.313, 7.25], [5., 7.25]]]
+        );
+    }
+
+    #[test]
+    fn styles_are_inherited_from_groups() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="10mm" height="10mm" viewBox="0 0 10 10">
+            <g style="stroke: white; fill: none" stroke="black">
+                <path id="inherited" d="M 0 0 L 1 0"/>
+                <path id="own" stroke="red" d="M 0 1 L 1 1"/>
+            </g>
+        </svg>"#;
+        let document = Document::parse(svg).unwrap();
+        let toolpaths = svg2toolpaths(
+            &document,
+            &ConversionConfig::default(),
+            Default::default(),
+            Transform2D::identity(),
+        )
+        .toolpaths;
+        let stroke = |i: usize| {
+            let source = toolpaths[i].source.as_ref().unwrap();
+            (property(source, "stroke"), property(source, "fill"))
+        };
+        assert_eq!(stroke(0), (Some("white"), Some("none")));
+        assert_eq!(stroke(1), (Some("red"), Some("none")));
+    }
+
+    #[test]
+    fn styles_of_css_rules_are_inherited() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="10mm" height="10mm" viewBox="0 0 10 10">
+            <style>.cut { stroke: red } #outline { stroke-width: 2 }</style>
+            <g class="cut" id="outline">
+                <path d="M 0 0 L 1 0"/>
+            </g>
+        </svg>"#;
+        let document = Document::parse(svg).unwrap();
+        let toolpaths = svg2toolpaths(
+            &document,
+            &ConversionConfig::default(),
+            Default::default(),
+            Transform2D::identity(),
+        )
+        .toolpaths;
+        let source = toolpaths[0].source.as_ref().unwrap();
+        assert_eq!(property(source, "stroke"), Some("red"));
+        assert_eq!(property(source, "stroke-width"), Some("2"));
+    }
+
+    #[test]
+    fn stroke_power_is_set_by_the_stroke_of_groups() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="10mm" height="10mm" viewBox="0 0 10 10">
+            <g style="stroke: white">
+                <path d="M 0 0 L 1 0"/>
+            </g>
+        </svg>"#;
+        let document = Document::parse(svg).unwrap();
+        let config = ConversionConfig {
+            stroke_power: Some(StrokePower {
+                min: 100.,
+                max: 500.,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let program = svg2program(
+            &document,
+            &config,
+            Default::default(),
+            Machine::new(Default::default(), None, None, None, None),
+        );
+        let mut gcode = String::new();
+        g_code::emit::format_gcode_fmt(&program, Default::default(), &mut gcode).unwrap();
+        assert!(gcode.contains("S100"), "{gcode}");
+        assert!(!gcode.contains("S500"));
+    }
+
+    #[test]
+    fn stroke_power_is_set_again_after_the_tool_off_sequence_sets_it() {
+        let svg = r##"<svg xmlns="http://www.w3.org/2000/svg" width="10mm" height="10mm" viewBox="0 0 10 10">
+            <path d="M 0 0 L 1 0" stroke="#808080"/>
+            <path d="M 5 5 L 6 5" stroke="#808080"/>
+        </svg>"##;
+        let document = Document::parse(svg).unwrap();
+        let config = ConversionConfig {
+            stroke_power: Some(StrokePower::default()),
+            ..Default::default()
+        };
+        let program = svg2program(
+            &document,
+            &config,
+            Default::default(),
+            Machine::new(
+                Default::default(),
+                Some(g_code::parse::snippet_parser("M4").unwrap()),
+                Some(g_code::parse::snippet_parser("M5 S0").unwrap()),
+                None,
+                None,
+            ),
+        );
+        let mut gcode = String::new();
+        g_code::emit::format_gcode_fmt(&program, Default::default(), &mut gcode).unwrap();
+        // Each path is cut after the tool off sequence left the power at 0
+        let cuts_with_power = gcode
+            .lines()
+            .filter(|line| line.starts_with("G1") && line.contains(" S"))
+            .count();
+        assert_eq!(cuts_with_power, 2, "{gcode}");
+    }
+
+    #[test]
+    fn used_elements_inherit_from_where_they_are_used() {
+        let svg = r##"<svg xmlns="http://www.w3.org/2000/svg" xmlns:inkscape="http://www.inkscape.org/namespaces/inkscape" width="10mm" height="10mm" viewBox="0 0 10 10">
+            <defs>
+                <path id="tick" fill="none" d="M 0 0 L 1 0"/>
+            </defs>
+            <g inkscape:groupmode="layer" inkscape:label="engrave">
+                <use href="#tick" stroke="red"/>
+            </g>
+        </svg>"##;
+        let document = Document::parse(svg).unwrap();
+        let config = ConversionConfig {
+            paint_filter: PaintFilter::Stroked,
+            ..Default::default()
+        };
+        let toolpaths = svg2toolpaths(
+            &document,
+            &config,
+            Default::default(),
+            Transform2D::identity(),
+        )
+        .toolpaths;
+        assert_eq!(toolpaths.len(), 1);
+        let source = toolpaths[0].source.as_ref().unwrap();
+        assert_eq!(source.id.as_deref(), Some("tick"));
+        assert_eq!(source.layer.as_deref(), Some("engrave"));
+        assert_eq!(source.attribute("stroke"), Some("red"));
+    }
+
+    #[test]
+    fn only_selected_ids_are_drawn_in_place() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="10mm" height="10mm" viewBox="0 0 10 10">
+            <path d="M -1 1 L 0 1"/>
+            <g id="part">
+                <path d="M 5 1 L 7 1"/>
+                <path id="hole" d="M 5 2 L 8 2"/>
+            </g>
+        </svg>"#;
+        let document = Document::parse(svg).unwrap();
+        let config = ConversionConfig {
+            origin: [Some(0.), Some(0.)],
+            ..Default::default()
+        };
+        let options = ConversionOptions {
+            only_ids: vec!["part".to_string()],
+            skip_ids: vec!["hole".to_string()],
+            ..Default::default()
+        };
+        let convert = |options: ConversionOptions| {
+            let placement = place(&document, &config, &options);
+            svg2toolpaths(&document, &config, options, placement).toolpaths
+        };
+        let toolpaths = convert(options);
+        assert_eq!(toolpaths.len(), 1);
+        assert_eq!(toolpaths[0].length(config.tolerance).round(), 2.);
+        // Placed where it is when the whole document is drawn
+        assert_eq!(toolpaths[0], convert(Default::default())[1]);
+    }
+
+    #[test]
+    fn rotation_turns_artwork_before_placing_it() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+            <path d="M 10 10 L 90 10"/>
+        </svg>"#;
+        let document = Document::parse(svg).unwrap();
+        let config = ConversionConfig {
+            rotation: 180.,
+            ..Default::default()
+        };
+        let placement = place(&document, &config, &Default::default());
+        let toolpaths = svg2toolpaths(&document, &config, Default::default(), placement).toolpaths;
+        let length = 80. * 25.4 / 96.;
+        assert!((toolpaths[0].from - point(length, 0.)).length() < 1e-9);
+        assert!((toolpaths[0].to() - point(0., 0.)).length() < 1e-9);
+    }
+
+    #[test]
+    fn pause_markers_stop_before_their_stage() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="10mm" height="10mm" viewBox="0 0 10 10">
+            <path d="M 9 9 L 9 8"/>
+            <g class="inlay pause"/>
+            <path d="M 1 1 L 2 1"/>
+            <path id="pause-2" d="M 0 0 L 0 1"/>
+        </svg>"#;
+        let document = Document::parse(svg).unwrap();
+        let options = ConversionOptions {
+            path_ordering: PathOrdering::NearestNeighbor,
+            ..Default::default()
+        };
+        let machine = Machine::new(Default::default(), None, None, None, None);
+        let program = svg2program(&document, &Default::default(), options, machine);
+        let mut gcode = String::new();
+        g_code::emit::format_gcode_fmt(&program, Default::default(), &mut gcode).unwrap();
+        // The paths closer to the origin are still drawn after the pauses before them
+        let position = |pattern: &str| gcode.find(pattern).unwrap();
+        assert_eq!(gcode.matches("M0").count(), 2);
+        assert!(position("Y1") < position("M0"));
+        assert!(position("M0") < position("X1 Y9"));
+        assert!(position("X1 Y9") < gcode.rfind("M0").unwrap());
+    }
+
+    #[test]
+    fn attributes_that_cant_be_parsed_are_ignored() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="10mm" height="10mm" viewBox="0 0 10 10">
+            <svg viewBox="0 0 ten 10" preserveAspectRatio="nope">
+                <path transform="rotate(" d="M 0 0 L 10 0 L 10 x 10"/>
+            </svg>
+        </svg>"#;
+        let document = Document::parse(svg).unwrap();
+        let machine = Machine::new(Default::default(), None, None, None, None);
+        let program = svg2program(
+            &document,
+            &ConversionConfig::default(),
+            Default::default(),
+            machine,
+        );
+        let mut gcode = String::new();
+        g_code::emit::format_gcode_fmt(&program, Default::default(), &mut gcode).unwrap();
+        // Drawn up to where the path stops making sense
+        assert!(gcode.contains("G1 X10 Y10"));
+        assert!(!gcode.contains("Y0"));
+    }
 }
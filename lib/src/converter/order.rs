@@ -0,0 +1,93 @@
+use std::fmt::Write;
+
+use lyon_geom::{Box2D, Point};
+
+use crate::Toolpath;
+
+/// Radius in millimeters of the marker drawn at the start of each toolpath
+const MARKER_RADIUS: f64 = 1.;
+
+/// Draw toolpaths as an SVG in millimeters, numbering each one at its start in the order it is cut
+///
+/// Rapid moves between toolpaths are drawn as dashed lines.
+pub fn toolpaths2svg(toolpaths: &[Toolpath], tolerance: f64) -> String {
+    let polylines = toolpaths
+        .iter()
+        .map(|toolpath| {
+            let mut points = vec![toolpath.from];
+            points.extend(toolpath.flattened(tolerance));
+            points
+        })
+        .collect::<Vec<_>>();
+
+    let bounding_box = Box2D::from_points(polylines.iter().flatten())
+        .inflate(MARKER_RADIUS * 2., MARKER_RADIUS * 2.);
+    // G-Code coordinates are y-up, SVG coordinates are y-down
+    let flip = |point: &Point<f64>| (point.x, bounding_box.max.y - point.y + bounding_box.min.y);
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}mm" height="{h}mm" viewBox="{x} {y} {w} {h}">"#,
+        x = bounding_box.min.x,
+        y = bounding_box.min.y,
+        w = bounding_box.width(),
+        h = bounding_box.height(),
+    );
+
+    let mut previous_end = None;
+    for (i, polyline) in polylines.iter().enumerate() {
+        let (x, y) = flip(&polyline[0]);
+        if let Some((from_x, from_y)) = previous_end {
+            let _ = writeln!(
+                svg,
+                r#"<line x1="{from_x}" y1="{from_y}" x2="{x}" y2="{y}" stroke="gray" stroke-width="0.2" stroke-dasharray="1"/>"#
+            );
+        }
+        let points = polyline
+            .iter()
+            .map(|point| {
+                let (x, y) = flip(point);
+                format!("{x},{y}")
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        let _ = writeln!(
+            svg,
+            r#"<polyline points="{points}" fill="none" stroke="black" stroke-width="0.3"/>"#
+        );
+        let _ = writeln!(
+            svg,
+            r#"<circle cx="{x}" cy="{y}" r="{MARKER_RADIUS}" fill="red"/><text x="{x}" y="{y}" dx="{MARKER_RADIUS}" font-size="3" fill="red">{number}</text>"#,
+            number = i + 1,
+        );
+        previous_end = polyline.last().map(flip);
+    }
+    svg += "</svg>\n";
+    svg
+}
+
+#[cfg(test)]
+mod test {
+    use lyon_geom::point;
+
+    use super::*;
+
+    #[test]
+    fn toolpaths_are_numbered_in_cut_order() {
+        let toolpaths = [
+            Toolpath::from_polyline(&[point(0., 0.), point(10., 0.)]).unwrap(),
+            Toolpath::from_polyline(&[point(10., 10.), point(0., 10.)]).unwrap(),
+        ];
+        let svg = toolpaths2svg(&toolpaths, 0.1);
+
+        assert_eq!(svg.matches("<polyline").count(), 2);
+        assert_eq!(svg.matches("<line").count(), 1);
+        let first = svg.find(">1</text>").unwrap();
+        let second = svg.find(">2</text>").unwrap();
+        assert!(first < second);
+        // The first toolpath is at the bottom of the machine, so it is drawn at the bottom of the SVG
+        assert!(svg.contains(r#"<circle cx="0" cy="10""#));
+        assert!(roxmltree::Document::parse(&svg).is_ok());
+    }
+}
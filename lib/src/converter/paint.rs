@@ -0,0 +1,231 @@
+use std::str::FromStr;
+
+use roxmltree::Node;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use svgtypes::{Length, LengthListParser, LengthUnit};
+
+use super::units::{font_size, DimensionHint};
+
+/// Elements that can be referenced as a paint (i.e. `fill="url(#gradient)"`)
+const PAINT_SERVER_TAG_NAMES: [&str; 3] = ["linearGradient", "radialGradient", "pattern"];
+
+/// Which shapes are drawn, by how they are painted in the SVG
+///
+/// Shapes have a black fill and no stroke unless set otherwise, like in a browser.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum PaintFilter {
+    /// Every shape, whether it is painted or not
+    #[default]
+    All,
+    /// Only shapes with a stroke
+    Stroked,
+    /// Only shapes with a fill
+    Filled,
+    /// Shapes with a stroke, a fill, or both, leaving out ones that are invisible
+    StrokedOrFilled,
+}
+
+impl PaintFilter {
+    /// Whether a shape element is drawn, given it followed by its ancestors as it is drawn (see
+    /// [`super::visit::drawn_ancestors`])
+    pub(super) fn draws(self, ancestors: &[Node]) -> bool {
+        match self {
+            Self::All => true,
+            Self::Stroked => is_stroked(ancestors),
+            Self::Filled => is_painted(ancestors, "fill"),
+            Self::StrokedOrFilled => is_stroked(ancestors) || is_painted(ancestors, "fill"),
+        }
+    }
+}
+
+/// How strokes are cut, see [`crate::ConversionConfig::stroke_outline`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum StrokeOutline {
+    /// Along the middle of the stroke, however wide it is
+    #[default]
+    Centerline,
+    /// Around both sides of the stroke, so it comes out as wide as it is drawn
+    Outline,
+    /// Around both sides of the stroke, with the inside filled by [`crate::ConversionConfig::fill`]
+    /// like a shape filled with the color of the stroke
+    Filled,
+}
+
+/// The `stroke-dasharray` of a stroked element in user units, and its `stroke-dashoffset`, or
+/// [`None`] if its stroke is solid
+///
+/// Lengths in `em` are relative to the font size of the element, and percentages to the diagonal
+/// of the viewport. A list that has a negative length or doesn't parse is ignored, as in a browser.
+/// Properties are inherited from `ancestors` like for [`PaintFilter::draws`].
+pub(super) fn stroke_dashes(
+    ancestors: &[Node],
+    to_user_units: impl Fn(Length, DimensionHint) -> f64,
+) -> Option<(Vec<f64>, f64)> {
+    if !is_stroked(ancestors) {
+        return None;
+    }
+    let resolve = |length: Length| resolve_length(ancestors[0], length, &to_user_units);
+    let pattern = LengthListParser::from(property(ancestors, "stroke-dasharray")?)
+        .map(|length| length.map(resolve))
+        .collect::<Result<Vec<_>, _>>()
+        .ok()?;
+    if pattern.is_empty() || pattern.iter().any(|length| *length < 0.) {
+        return None;
+    }
+    let offset = property(ancestors, "stroke-dashoffset")
+        .and_then(|offset| Length::from_str(offset).ok())
+        .map_or(0., resolve);
+    Some((pattern, offset))
+}
+
+/// The `stroke-width` of a stroked element in user units, 1 unless it is set, or [`None`] if it
+/// isn't stroked
+///
+/// Lengths are resolved like those of [`stroke_dashes`].
+pub(super) fn stroke_width(
+    ancestors: &[Node],
+    to_user_units: impl Fn(Length, DimensionHint) -> f64,
+) -> Option<f64> {
+    if !is_stroked(ancestors) {
+        return None;
+    }
+    Some(
+        property(ancestors, "stroke-width")
+            .and_then(|width| Length::from_str(width).ok())
+            .map_or(1., |width| {
+                resolve_length(ancestors[0], width, &to_user_units)
+            }),
+    )
+}
+
+/// A length of a stroke property in user units, relative to the font size of the element for `em`
+/// and `ex`, and to the diagonal of the viewport for percentages
+fn resolve_length(
+    node: Node,
+    length: Length,
+    to_user_units: &impl Fn(Length, DimensionHint) -> f64,
+) -> f64 {
+    match length.unit {
+        LengthUnit::Em => length.number * font_size(node, to_user_units),
+        LengthUnit::Ex => length.number * font_size(node, to_user_units) / 2.,
+        _ => to_user_units(length, DimensionHint::Other),
+    }
+}
+
+/// A stroke that is 0 wide isn't drawn
+fn is_stroked(ancestors: &[Node]) -> bool {
+    is_painted(ancestors, "stroke")
+        && property(ancestors, "stroke-width")
+            .and_then(|width| svgtypes::Length::from_str(width).ok())
+            .is_none_or(|width| width.number > 0.)
+}
+
+/// Whether the `fill` or `stroke` of an element paints anything, resolving references to
+/// gradients and patterns
+fn is_painted(ancestors: &[Node], name: &str) -> bool {
+    let Some(paint) = property(ancestors, name) else {
+        return name == "fill";
+    };
+    match paint.strip_prefix("url(") {
+        Some(reference) => {
+            let (id, fallback) = reference.split_once(')').unwrap_or((reference, ""));
+            let id = id.trim().trim_matches(['\'', '"']).trim_start_matches('#');
+            let resolved = ancestors[0].document().descendants().any(|element| {
+                element.attribute("id") == Some(id)
+                    && PAINT_SERVER_TAG_NAMES.contains(&element.tag_name().name())
+            });
+            // A reference that doesn't resolve falls back to the paint after it, or none
+            resolved || !matches!(fallback.trim(), "" | "none")
+        }
+        None => paint != "none",
+    }
+}
+
+/// The value of an inherited property, from the element's `style` or attribute, or from the
+/// closest of its `ancestors` that sets it
+pub(super) fn property<'a>(ancestors: &[Node<'a, '_>], name: &str) -> Option<&'a str> {
+    ancestors
+        .iter()
+        .filter(|node| node.is_element())
+        .filter_map(|element| {
+            let style = element.attribute("style").and_then(|style| {
+                style
+                    .split(';')
+                    .filter_map(|declaration| declaration.split_once(':'))
+                    .find(|(property, _)| property.trim() == name)
+                    .map(|(_, value)| value.trim())
+            });
+            style.or(element.attribute(name).map(str::trim))
+        })
+        .find(|value| *value != "inherit")
+}
+
+#[cfg(test)]
+mod test {
+    use roxmltree::Document;
+
+    use super::*;
+
+    #[test]
+    fn shapes_are_drawn_by_how_they_are_painted() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg">
+            <linearGradient id="gradient"/>
+            <path id="filled" d="M 0 0 L 1 1"/>
+            <g stroke="red">
+                <path id="stroked" fill="none" d="M 0 0 L 1 1"/>
+            </g>
+            <path id="gradient-filled" fill="url(#gradient)" stroke="url(#missing)" d="M 0 0 L 1 1"/>
+            <path id="fallback-stroked" style="fill: none" stroke="url(#missing) blue" d="M 0 0 L 1 1"/>
+            <path id="invisible" fill="none" stroke="red" stroke-width="0" d="M 0 0 L 1 1"/>
+        </svg>"#;
+        let document = Document::parse(svg).unwrap();
+        let drawn = |filter: PaintFilter| {
+            document
+                .descendants()
+                .filter(|node| {
+                    node.has_tag_name("path") && filter.draws(&node.ancestors().collect::<Vec<_>>())
+                })
+                .filter_map(|node| node.attribute("id"))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(drawn(PaintFilter::All).len(), 5);
+        assert_eq!(drawn(PaintFilter::Stroked), ["stroked", "fallback-stroked"]);
+        assert_eq!(drawn(PaintFilter::Filled), ["filled", "gradient-filled"]);
+        assert_eq!(
+            drawn(PaintFilter::StrokedOrFilled),
+            ["filled", "stroked", "gradient-filled", "fallback-stroked"]
+        );
+    }
+
+    #[test]
+    fn dashes_are_resolved_in_user_units() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg">
+            <g stroke="black" stroke-dasharray="1mm, 2" font-size="10">
+                <path id="inherited" style="stroke-dashoffset: 0.5em" d="M 0 0 L 1 1"/>
+                <path id="filled" stroke="none" d="M 0 0 L 1 1"/>
+                <path id="solid" stroke-dasharray="none" d="M 0 0 L 1 1"/>
+                <path id="negative" stroke-dasharray="1 -1" d="M 0 0 L 1 1"/>
+            </g>
+        </svg>"#;
+        let document = Document::parse(svg).unwrap();
+        let dashes = |id: &str| {
+            let node = document
+                .descendants()
+                .find(|node| node.attribute("id") == Some(id))
+                .unwrap();
+            let ancestors = node.ancestors().collect::<Vec<_>>();
+            // Millimeters as 4 user units each
+            stroke_dashes(&ancestors, |length, _| match length.unit {
+                LengthUnit::Mm => length.number * 4.,
+                _ => length.number,
+            })
+        };
+        assert_eq!(dashes("inherited"), Some((vec![4., 2.], 5.)));
+        assert_eq!(dashes("filled"), None);
+        assert_eq!(dashes("solid"), None);
+        assert_eq!(dashes("negative"), None);
+    }
+}
@@ -0,0 +1,189 @@
+use std::{collections::BTreeMap, sync::LazyLock};
+
+use lyon_geom::{euclid::default::Transform2D, point, Point};
+
+/// A glyph drawn with single lines instead of outlines
+#[derive(Debug, Clone, PartialEq)]
+pub struct StrokeGlyph {
+    /// Horizontal distance to the next glyph, in font units
+    pub advance: f64,
+    /// Polylines relative to the glyph origin on the baseline, with the y-axis pointing down
+    pub strokes: Vec<Vec<Point<f64>>>,
+}
+
+/// Single-stroke fonts that come with svg2gcode: `sans` is Hershey's public domain Roman Simplex,
+/// and `serif` was drawn for svg2gcode in the same format
+static BUNDLED: LazyLock<Vec<StrokeFont>> = LazyLock::new(|| {
+    [
+        ("sans", include_str!("../../fonts/sans.jhf")),
+        ("serif", include_str!("../../fonts/serif.jhf")),
+    ]
+    .into_iter()
+    .filter_map(|(name, jhf)| StrokeFont::from_jhf(name, jhf, ' '))
+    .collect()
+});
+
+/// A single-stroke (engraving) font, which draws each glyph once instead of tracing its outline
+#[derive(Debug, Clone, PartialEq)]
+pub struct StrokeFont {
+    pub name: String,
+    glyphs: BTreeMap<char, StrokeGlyph>,
+}
+
+impl StrokeFont {
+    /// Size of the em square in font units, which caps are 21 units tall in
+    pub const EM: f64 = 32.;
+
+    /// Read a font in the Hershey `.jhf` format, where glyphs are assigned to consecutive
+    /// characters starting at `first`
+    ///
+    /// Each glyph is moved so its origin is on the baseline at its left edge, since `.jhf`
+    /// coordinates are from the middle of the glyph with the baseline 9 units down.
+    ///
+    /// Returns [`None`] if any glyph is malformed.
+    ///
+    /// <https://paulbourke.net/dataformats/hershey/>
+    pub fn from_jhf(name: impl Into<String>, jhf: &str, first: char) -> Option<Self> {
+        let mut records: Vec<String> = vec![];
+        for line in jhf.lines().filter(|line| !line.trim().is_empty()) {
+            // Long glyphs continue onto the next line
+            match records.last_mut() {
+                Some(record) if record.len() < record_len(record)? => record.push_str(line),
+                _ => records.push(line.to_string()),
+            }
+        }
+
+        let glyphs = records
+            .iter()
+            .zip(first..)
+            .map(|(record, c)| Some((c, parse_jhf_glyph(record)?)))
+            .collect::<Option<_>>()?;
+        Some(Self {
+            name: name.into(),
+            glyphs,
+        })
+    }
+
+    pub fn glyph(&self, c: char) -> Option<&StrokeGlyph> {
+        self.glyphs.get(&c)
+    }
+
+    /// Horizontal distance to the next glyph in ems, or [`None`] if there is no glyph for `c`
+    pub fn advance(&self, c: char) -> Option<f64> {
+        self.glyph(c).map(|glyph| glyph.advance / Self::EM)
+    }
+
+    /// Polylines drawing the glyph for `c`, mapped from ems, with the y-axis pointing down, by
+    /// `transform`
+    pub fn strokes(&self, c: char, transform: &Transform2D<f64>) -> Vec<Vec<Point<f64>>> {
+        self.glyph(c)
+            .into_iter()
+            .flat_map(|glyph| &glyph.strokes)
+            .map(|stroke| {
+                stroke
+                    .iter()
+                    .map(|p| transform.transform_point(*p / Self::EM))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// The single-stroke fonts that come with svg2gcode, `sans` (Hershey Roman Simplex) and
+    /// `serif`, covering printable ASCII
+    pub fn bundled() -> &'static [StrokeFont] {
+        &BUNDLED
+    }
+}
+
+/// Expected length of a `.jhf` record from its vertex count
+fn record_len(record: &str) -> Option<usize> {
+    let vertices: usize = record.get(5..8)?.trim().parse().ok()?;
+    Some(8 + vertices * 2)
+}
+
+/// Distance of the baseline below the origin of `.jhf` coordinates
+const JHF_BASELINE: f64 = 9.;
+
+fn parse_jhf_glyph(record: &str) -> Option<StrokeGlyph> {
+    let coordinate = |c: u8| f64::from(c) - f64::from(b'R');
+    let pairs = record.get(8..record_len(record)?)?.as_bytes().chunks(2);
+
+    let mut pairs = pairs.map(|pair| (pair[0], pair[1]));
+    let (left, right) = pairs.next()?;
+    let mut strokes = vec![vec![]];
+    for (x, y) in pairs {
+        if (x, y) == (b' ', b'R') {
+            strokes.push(vec![]);
+        } else {
+            strokes.last_mut()?.push(point(
+                coordinate(x) - coordinate(left),
+                coordinate(y) - JHF_BASELINE,
+            ));
+        }
+    }
+    strokes.retain(|stroke| !stroke.is_empty());
+
+    Some(StrokeGlyph {
+        advance: coordinate(right) - coordinate(left),
+        strokes,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn jhf_glyphs_are_parsed() {
+        let font = StrokeFont::from_jhf(
+            "simplex",
+            "12345  1JZ\n12345  9I[RFJ[ RRFZ[ RMTWT\n12345  6\nJZNFNM RVFVM\n",
+            ' ',
+        )
+        .unwrap();
+
+        let space = font.glyph(' ').unwrap();
+        assert_eq!(space.advance, 16.);
+        assert!(space.strokes.is_empty());
+
+        let a = font.glyph('!').unwrap();
+        assert_eq!(a.advance, 18.);
+        assert_eq!(
+            a.strokes,
+            [
+                vec![point(9., -21.), point(1., 0.)],
+                vec![point(9., -21.), point(17., 0.)],
+                vec![point(4., -7.), point(14., -7.)],
+            ]
+        );
+
+        // The third glyph continues onto the next line
+        assert_eq!(font.glyph('"').unwrap().strokes.len(), 2);
+        assert_eq!(font.glyph('#'), None);
+
+        assert_eq!(StrokeFont::from_jhf("broken", "12345  9MW", ' '), None);
+    }
+
+    #[test]
+    fn bundled_fonts_cover_printable_ascii() {
+        let bundled = StrokeFont::bundled();
+        assert_eq!(
+            bundled
+                .iter()
+                .map(|font| font.name.as_str())
+                .collect::<Vec<_>>(),
+            ["sans", "serif"]
+        );
+        for font in bundled {
+            for c in ' '..='~' {
+                let glyph = font.glyph(c).unwrap();
+                assert!(glyph.advance > 0., "{} {c:?}", font.name);
+                // Glyphs start at their origin and stand on the baseline
+                assert!(glyph.strokes.iter().flatten().all(|p| p.x >= 0.));
+                assert_eq!(glyph.strokes.is_empty(), c == ' ', "{} {c:?}", font.name);
+            }
+            let h = font.glyph('H').unwrap().strokes.iter().flatten();
+            assert_eq!(h.fold(f64::MIN, |lowest, p| lowest.max(p.y)), 0.);
+        }
+    }
+}
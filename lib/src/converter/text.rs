@@ -0,0 +1,611 @@
+use roxmltree::{Node, NS_XML_URI};
+use std::{ops::Range, str::FromStr};
+
+use lyon_geom::{Point, Vector};
+use svgtypes::{Length, LengthListParser, LengthUnit, NumberListParser};
+use unicode_bidi::{get_base_direction, BidiInfo, Level};
+
+use super::{
+    font::{Glyph, ShapedGlyph},
+    units::DimensionHint,
+    visit::XLINK_NS_URI,
+};
+use crate::Toolpath;
+
+/// Elements whose character data is part of the rendered text
+const TEXT_CONTENT_TAG_NAMES: [&str; 4] = ["text", "tspan", "textPath", "a"];
+
+/// Whether whitespace is preserved for character data under this node
+///
+/// <https://www.w3.org/TR/SVG11/text.html#WhiteSpace>
+fn preserves_space(node: Node) -> bool {
+    node.ancestors()
+        .find_map(|ancestor| ancestor.attribute((NS_XML_URI, "space")))
+        == Some("preserve")
+}
+
+/// Character data of a `<text>` element and its descendants, after applying the `xml:space` rules
+///
+/// With `xml:space="default"`, newlines are removed, tabs become spaces, leading and trailing
+/// spaces are stripped, and consecutive spaces are collapsed into one, even across elements.
+/// With `xml:space="preserve"`, newlines and tabs become spaces and nothing else changes.
+pub fn text_content(text: Node) -> String {
+    collapsed_chars(text).into_iter().map(|(c, _)| c).collect()
+}
+
+/// Same as [`text_content`], paired with the text node each character came from
+fn collapsed_chars<'a, 'input>(text: Node<'a, 'input>) -> Vec<(char, Node<'a, 'input>)> {
+    let mut content: Vec<(char, Node)> = vec![];
+    let mut trailing_default_space = false;
+    for node in text.descendants().filter(|node| {
+        node.is_text()
+            && node
+                .ancestors()
+                .skip(1)
+                .take_while(|ancestor| *ancestor != text)
+                .all(|ancestor| TEXT_CONTENT_TAG_NAMES.contains(&ancestor.tag_name().name()))
+    }) {
+        let chars = node.text().unwrap_or_default().chars();
+        if preserves_space(node) {
+            content.extend(chars.map(|c| match c {
+                '\n' | '\r' | '\t' => (' ', node),
+                c => (c, node),
+            }));
+            trailing_default_space = false;
+        } else {
+            for c in chars.filter(|c| !matches!(c, '\n' | '\r')) {
+                let c = if c == '\t' { ' ' } else { c };
+                if c == ' ' && matches!(content.last(), None | Some((' ', _))) {
+                    continue;
+                }
+                content.push((c, node));
+            }
+            trailing_default_space = content.last().is_some_and(|(last, _)| *last == ' ');
+        }
+    }
+    if trailing_default_space {
+        content.pop();
+    }
+    content
+}
+
+/// Elements from the parent of a text node up to and including the `<text>` element
+fn containing_elements<'a, 'input: 'a>(
+    source: Node<'a, 'input>,
+    text: Node<'a, 'input>,
+) -> impl Iterator<Item = Node<'a, 'input>> {
+    source
+        .ancestors()
+        .skip(1)
+        .take_while(move |ancestor| *ancestor != text)
+        .chain(std::iter::once(text))
+}
+
+/// Inline base direction of text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    LeftToRight,
+    RightToLeft,
+}
+
+/// Base direction for laying out the text content of a `<text>` element
+///
+/// Uses the inherited `direction` property if set, otherwise the direction of the first strong
+/// character as in the [Unicode Bidirectional Algorithm](https://unicode.org/reports/tr9/#P2).
+/// Text with no strong characters (i.e. only digits) is left-to-right.
+pub fn direction(text: Node, content: &str) -> Direction {
+    match text
+        .ancestors()
+        .find_map(|ancestor| ancestor.attribute("direction"))
+    {
+        Some("rtl") => Direction::RightToLeft,
+        Some("ltr") => Direction::LeftToRight,
+        _ => match get_base_direction(content) {
+            unicode_bidi::Direction::Rtl => Direction::RightToLeft,
+            _ => Direction::LeftToRight,
+        },
+    }
+}
+
+/// Embedding level of each character by the
+/// [Unicode Bidirectional Algorithm](https://unicode.org/reports/tr9/), starting from the base
+/// `direction`, where odd levels are right-to-left
+pub fn bidi_levels(chars: &[PositionedChar], direction: Direction) -> Vec<Level> {
+    let text = chars.iter().map(|c| c.c).collect::<String>();
+    let base = match direction {
+        Direction::LeftToRight => Level::ltr(),
+        Direction::RightToLeft => Level::rtl(),
+    };
+    let info = BidiInfo::new(&text, Some(base));
+    text.char_indices().map(|(i, _)| info.levels[i]).collect()
+}
+
+/// A character of text content and its explicit position from the `x`, `y`, `dx`, `dy`, and
+/// `rotate` attributes of the elements containing it, in user units and degrees
+#[derive(Debug, Clone, PartialEq)]
+pub struct PositionedChar {
+    pub c: char,
+    /// Absolute position, else continues from the previous character
+    pub x: Option<f64>,
+    pub y: Option<f64>,
+    /// Shift relative to the absolute or continued position
+    pub dx: f64,
+    pub dy: f64,
+    pub rotate: f64,
+}
+
+/// Resolve the positioning attributes of a `<text>` element and its nested `<tspan>`s for each
+/// character of its [`text_content`]
+///
+/// Each value in an attribute's list applies to the next character within that element, and
+/// values of nested elements take precedence. The last `rotate` value also applies to any
+/// remaining characters.
+///
+/// <https://www.w3.org/TR/SVG11/text.html#TSpanElement>
+pub fn positioned_chars(
+    text: Node,
+    to_user_units: impl Fn(Length, DimensionHint) -> f64,
+) -> Vec<PositionedChar> {
+    let chars = collapsed_chars(text);
+    // Index of the first character within an element
+    let first_char = |element: Node| {
+        chars
+            .iter()
+            .position(|(_, source)| source.ancestors().any(|ancestor| ancestor == element))
+            .unwrap_or_default()
+    };
+    let lengths = |element: Node, attr: &str, hint: DimensionHint| -> Vec<f64> {
+        element
+            .attribute(attr)
+            .map(|list| {
+                LengthListParser::from(list)
+                    .map_while(Result::ok)
+                    .map(|length| to_user_units(length, hint))
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+
+    chars
+        .iter()
+        .enumerate()
+        .map(|(i, (c, source))| {
+            let find = |attr: &str, hint: DimensionHint| {
+                containing_elements(*source, text).find_map(|element| {
+                    lengths(element, attr, hint)
+                        .get(i - first_char(element))
+                        .copied()
+                })
+            };
+            let rotate = containing_elements(*source, text).find_map(|element| {
+                let rotate = element
+                    .attribute("rotate")
+                    .map(NumberListParser::from)?
+                    .map_while(Result::ok)
+                    .collect::<Vec<_>>();
+                rotate
+                    .get(i - first_char(element))
+                    .or_else(|| rotate.last())
+                    .copied()
+            });
+            PositionedChar {
+                c: *c,
+                x: find("x", DimensionHint::Horizontal),
+                y: find("y", DimensionHint::Vertical),
+                dx: find("dx", DimensionHint::Horizontal).unwrap_or(0.),
+                dy: find("dy", DimensionHint::Vertical).unwrap_or(0.),
+                rotate: rotate.unwrap_or(0.),
+            }
+        })
+        .collect()
+}
+
+/// Ranges of characters laid out together, each starting at a character with an absolute
+/// position
+fn chunks(chars: &[PositionedChar]) -> Vec<Range<usize>> {
+    let mut starts = (1..chars.len())
+        .filter(|i| chars[*i].x.is_some())
+        .collect::<Vec<_>>();
+    starts.insert(0, 0);
+    starts
+        .iter()
+        .zip(starts.iter().skip(1).chain([&chars.len()]))
+        .map(|(start, end)| *start..*end)
+        .filter(|chunk| !chunk.is_empty())
+        .collect()
+}
+
+/// Index of each character in the order they are laid out from left to right
+///
+/// Each chunk is reordered on its own, so runs of right-to-left text within it go from right to
+/// left.
+pub fn visual_order(chars: &[PositionedChar], levels: &[Level]) -> Vec<usize> {
+    chunks(chars)
+        .into_iter()
+        .flat_map(|chunk| {
+            BidiInfo::reorder_visual(&levels[chunk.clone()])
+                .into_iter()
+                .map(move |i| chunk.start + i)
+        })
+        .collect()
+}
+
+/// A glyph placed for drawing, with its origin on the baseline in user units
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlacedGlyph {
+    pub glyph: Glyph,
+    pub origin: Point<f64>,
+    /// Clockwise rotation around the origin, in degrees
+    pub rotate: f64,
+    /// Distance along the baseline to the next glyph
+    pub advance: f64,
+}
+
+/// Place the glyphs of a `<text>` element after the one to their left, moving each chunk so its
+/// inherited `text-anchor` is at the chunk's absolute position
+///
+/// `shape` gives the glyphs of a run of characters at the same embedding level in its
+/// direction, from left to right, in user units. Runs are laid out in their [`visual_order`],
+/// and each glyph is positioned by the first character it was shaped from.
+///
+/// <https://www.w3.org/TR/SVG11/text.html#TextLayout>
+pub fn layout(
+    text: Node,
+    chars: &[PositionedChar],
+    levels: &[Level],
+    direction: Direction,
+    shape: impl Fn(&[char], Direction) -> Vec<ShapedGlyph>,
+) -> Vec<PlacedGlyph> {
+    let anchor = text
+        .ancestors()
+        .find_map(|ancestor| ancestor.attribute("text-anchor"));
+    let order = visual_order(chars, levels);
+    let mut order = order.as_slice();
+    let mut pen = Point::zero();
+    let mut placed = Vec::with_capacity(chars.len());
+    for chunk in chunks(chars) {
+        let (chunk_order, rest) = order.split_at(chunk.len());
+        order = rest;
+        // Characters at the same level next to each other stay together in the visual order
+        let mut runs: Vec<Range<usize>> = vec![];
+        for i in chunk_order {
+            match runs.last_mut() {
+                Some(run) if levels[*i] == levels[run.start] => {
+                    *run = run.start.min(*i)..run.end.max(*i + 1);
+                }
+                _ => runs.push(*i..*i + 1),
+            }
+        }
+        pen.x = chars[chunk.start].x.unwrap_or(pen.x);
+        let start = pen.x;
+        let first = placed.len();
+        for run in runs {
+            let run_chars = chars[run.clone()].iter().map(|c| c.c).collect::<Vec<_>>();
+            let run_direction = if levels[run.start].is_rtl() {
+                Direction::RightToLeft
+            } else {
+                Direction::LeftToRight
+            };
+            let mut positioned = vec![false; run.len()];
+            for shaped in shape(&run_chars, run_direction) {
+                let c = &chars[run.start + shaped.cluster];
+                if !std::mem::replace(&mut positioned[shaped.cluster], true) {
+                    pen.y = c.y.unwrap_or(pen.y);
+                    pen += Vector::new(c.dx, c.dy);
+                }
+                if let Some(glyph) = shaped.glyph {
+                    placed.push(PlacedGlyph {
+                        glyph,
+                        origin: pen + shaped.offset,
+                        rotate: c.rotate,
+                        advance: shaped.advance,
+                    });
+                }
+                pen.x += shaped.advance;
+            }
+        }
+        let width = pen.x - start;
+        let shift = match (anchor, direction) {
+            (Some("middle"), _) => width / 2.,
+            (Some("end"), Direction::LeftToRight)
+            | (None | Some("start"), Direction::RightToLeft) => width,
+            _ => 0.,
+        };
+        for placed in &mut placed[first..] {
+            placed.origin.x -= shift;
+        }
+        pen.x -= shift;
+    }
+    placed
+}
+
+/// The `<path>` element a `<textPath>` lays out its text along
+pub fn text_path_reference<'a, 'input>(text_path: Node<'a, 'input>) -> Option<Node<'a, 'input>> {
+    let id = text_path
+        .attribute("href")
+        .or_else(|| text_path.attribute((XLINK_NS_URI, "href")))?
+        .strip_prefix('#')?;
+    text_path
+        .document()
+        .descendants()
+        .find(|node| node.attribute("id") == Some(id))
+        .filter(|node| node.has_tag_name("path"))
+}
+
+/// Move glyphs that [`layout`] placed on a baseline onto the path of a `<textPath>`, given as
+/// toolpaths in user units
+///
+/// A glyph's x on the baseline is its distance along the path after `start_offset`, and its y
+/// how far it is moved off of the path, to the right of the way it goes. Each glyph is centered on the point at the middle of its advance and turned with the path there, and those
+/// whose middle is off the path are left out.
+///
+/// <https://www.w3.org/TR/SVG11/text.html#TextpathLayoutRules>
+pub fn along_path(
+    placed: Vec<PlacedGlyph>,
+    path: &[Toolpath],
+    start_offset: f64,
+    tolerance: f64,
+) -> Vec<PlacedGlyph> {
+    let lengths = path
+        .iter()
+        .map(|toolpath| toolpath.length(tolerance))
+        .collect::<Vec<_>>();
+    // Subpaths are laid along one after the other
+    let point_at = |mut distance: f64| {
+        for (toolpath, length) in path.iter().zip(&lengths) {
+            if distance <= *length {
+                return toolpath.point_at_length(distance, tolerance);
+            }
+            distance -= length;
+        }
+        None
+    };
+    placed
+        .into_iter()
+        .filter_map(|placed| {
+            let (middle, tangent) = point_at(start_offset + placed.origin.x + placed.advance / 2.)?;
+            let normal = Vector::new(-tangent.y, tangent.x);
+            Some(PlacedGlyph {
+                origin: middle - tangent * (placed.advance / 2.) + normal * placed.origin.y,
+                rotate: placed.rotate + tangent.y.atan2(tangent.x).to_degrees(),
+                ..placed
+            })
+        })
+        .collect()
+}
+
+/// Distance along the referenced path to start the text of a `<textPath>` at, in user units
+pub fn start_offset(
+    text_path: Node,
+    path_length: f64,
+    to_user_units: impl Fn(Length, DimensionHint) -> f64,
+) -> f64 {
+    match text_path
+        .attribute("startOffset")
+        .map(Length::from_str)
+        .and_then(Result::ok)
+    {
+        Some(Length {
+            number,
+            unit: LengthUnit::Percent,
+        }) => number / 100. * path_length,
+        Some(length) => to_user_units(length, DimensionHint::Other),
+        None => 0.,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use roxmltree::Document;
+
+    use super::{super::font::unshaped, *};
+
+    fn text_content_of(svg: &str) -> String {
+        let document = Document::parse(svg).unwrap();
+        let text = document
+            .descendants()
+            .find(|node| node.has_tag_name("text"))
+            .unwrap();
+        text_content(text)
+    }
+
+    #[test]
+    fn default_space_is_collapsed_across_elements() {
+        assert_eq!(
+            text_content_of(
+                r#"<svg xmlns="http://www.w3.org/2000/svg"><text>
+                    Hello,	<tspan> world </tspan>
+                    <title>ignored</title>again
+                </text></svg>"#
+            ),
+            "Hello, world again"
+        );
+    }
+
+    #[test]
+    fn preserved_space_is_kept() {
+        assert_eq!(
+            text_content_of(
+                r#"<svg xmlns="http://www.w3.org/2000/svg" xml:space="preserve"><text>  a
+	b  <tspan xml:space="default">  c  </tspan></text></svg>"#
+            ),
+            "  a  b  c"
+        );
+    }
+
+    #[test]
+    fn nested_positions_take_precedence() {
+        let document = Document::parse(
+            r#"<svg xmlns="http://www.w3.org/2000/svg"><text x="1 2 3 4" dy="5" rotate="10 20">ab<tspan x="7" rotate="30">cd</tspan></text></svg>"#,
+        )
+        .unwrap();
+        let text = document
+            .descendants()
+            .find(|node| node.has_tag_name("text"))
+            .unwrap();
+        let chars = positioned_chars(text, |length, _| length.number);
+
+        assert_eq!(
+            chars
+                .iter()
+                .map(|c| (c.c, c.x, c.dy, c.rotate))
+                .collect::<Vec<_>>(),
+            [
+                ('a', Some(1.), 5., 10.),
+                ('b', Some(2.), 0., 20.),
+                ('c', Some(7.), 0., 30.),
+                ('d', Some(4.), 0., 30.),
+            ]
+        );
+    }
+
+    #[test]
+    fn text_path_references_path() {
+        let document = Document::parse(
+            r##"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink">
+                <path id="curve" d="M 0 0 L 10 0"/>
+                <rect id="box" width="1" height="1"/>
+                <text><textPath xlink:href="#curve" startOffset="25%">a</textPath></text>
+                <text><textPath href="#box" startOffset="3">b</textPath></text>
+            </svg>"##,
+        )
+        .unwrap();
+        let text_paths = document
+            .descendants()
+            .filter(|node| node.has_tag_name("textPath"))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            text_path_reference(text_paths[0]).and_then(|path| path.attribute("id")),
+            Some("curve")
+        );
+        assert_eq!(text_path_reference(text_paths[1]), None);
+        assert_eq!(
+            start_offset(text_paths[0], 10., |length, _| length.number),
+            2.5
+        );
+        assert_eq!(
+            start_offset(text_paths[1], 10., |length, _| length.number),
+            3.
+        );
+    }
+
+    #[test]
+    fn direction_follows_first_strong_character() {
+        let document = Document::parse(
+            r#"<svg xmlns="http://www.w3.org/2000/svg"><g direction="ltr"><text/></g></svg>"#,
+        )
+        .unwrap();
+        let text = document
+            .descendants()
+            .find(|node| node.has_tag_name("text"))
+            .unwrap();
+        let svg = document.root_element();
+
+        assert_eq!(direction(svg, "42 שלום"), Direction::RightToLeft);
+        assert_eq!(direction(svg, "مرحبا world"), Direction::RightToLeft);
+        assert_eq!(direction(svg, "hello שלום"), Direction::LeftToRight);
+        assert_eq!(direction(text, "שלום"), Direction::LeftToRight);
+    }
+
+    #[test]
+    fn right_to_left_runs_are_laid_out_from_right_to_left() {
+        let document = Document::parse(
+            r#"<svg xmlns="http://www.w3.org/2000/svg"><text x="10">ab אבג<tspan x="0">שלום 12</tspan></text></svg>"#,
+        )
+        .unwrap();
+        let text = document
+            .descendants()
+            .find(|node| node.has_tag_name("text"))
+            .unwrap();
+        let chars = positioned_chars(text, |length, _| length.number);
+        let content = text_content(text);
+        let direction = direction(text, &content);
+        assert_eq!(direction, Direction::LeftToRight);
+        let levels = bidi_levels(&chars, direction);
+
+        // The Hebrew after "ab " goes right to left, and so does the second chunk, where the
+        // number stays left to right but comes before the word it follows
+        let order = visual_order(&chars, &levels);
+        assert_eq!(order, [0, 1, 2, 5, 4, 3, 11, 12, 10, 9, 8, 7, 6]);
+    }
+
+    #[test]
+    fn chunks_are_placed_at_their_anchor() {
+        let document = Document::parse(
+            r#"<svg xmlns="http://www.w3.org/2000/svg"><text x="10" y="20" text-anchor="middle">ab<tspan x="30" dy="5" rotate="45">c</tspan></text></svg>"#,
+        )
+        .unwrap();
+        let text = document
+            .descendants()
+            .find(|node| node.has_tag_name("text"))
+            .unwrap();
+        let chars = positioned_chars(text, |length, _| length.number);
+        let levels = bidi_levels(&chars, Direction::LeftToRight);
+        let placed = layout(
+            text,
+            &chars,
+            &levels,
+            Direction::LeftToRight,
+            |run, direction| unshaped(run, direction, |_| Some(4.)),
+        );
+
+        assert_eq!(
+            placed
+                .iter()
+                .map(|placed| (placed.glyph, placed.origin, placed.rotate))
+                .collect::<Vec<_>>(),
+            [
+                (Glyph::Char('a'), Point::new(6., 20.), 0.),
+                (Glyph::Char('b'), Point::new(10., 20.), 0.),
+                (Glyph::Char('c'), Point::new(28., 25.), 45.),
+            ]
+        );
+    }
+
+    #[test]
+    fn characters_follow_the_path_from_its_start_offset() {
+        let document = Document::parse(
+            r#"<svg xmlns="http://www.w3.org/2000/svg"><text><textPath>ab<tspan dy="1">c</tspan>d</textPath></text></svg>"#,
+        )
+        .unwrap();
+        let text_path = document
+            .descendants()
+            .find(|node| node.has_tag_name("textPath"))
+            .unwrap();
+        let chars = positioned_chars(text_path, |length, _| length.number);
+        let levels = bidi_levels(&chars, Direction::LeftToRight);
+        let placed = layout(
+            text_path,
+            &chars,
+            &levels,
+            Direction::LeftToRight,
+            |run, direction| unshaped(run, direction, |_| Some(4.)),
+        );
+        // Right 10 and then down 10
+        let path = Toolpath::from_polyline(&[
+            Point::new(0., 0.),
+            Point::new(10., 0.),
+            Point::new(10., 10.),
+        ])
+        .unwrap();
+        let placed = along_path(placed, &[path], 1., 1e-3);
+
+        let rounded = |p: Point<f64>| Point::new(p.x.round(), p.y.round());
+        assert_eq!(
+            placed
+                .iter()
+                .map(|placed| (placed.glyph, rounded(placed.origin), placed.rotate.round()))
+                .collect::<Vec<_>>(),
+            [
+                (Glyph::Char('a'), Point::new(1., 0.), 0.),
+                (Glyph::Char('b'), Point::new(5., 0.), 0.),
+                // Past the corner, turned down the path and moved off to the right of it
+                (Glyph::Char('c'), Point::new(9., -1.), 90.),
+                (Glyph::Char('d'), Point::new(9., 3.), 90.),
+            ]
+        );
+        // Off the end of the path
+        assert!(along_path(placed, &[], 0., 1e-3).is_empty());
+    }
+}
@@ -1,16 +1,21 @@
+use std::str::FromStr;
+
 use log::warn;
 use roxmltree::Node;
-use svgtypes::{Length, LengthListParser};
+use svgtypes::{Length, LengthListParser, LengthUnit};
 
 use crate::Turtle;
 
-use super::ConversionVisitor;
+use super::{ConversionVisitor, UNSUPPORTED};
 
 /// The DPI assumed by CSS is 96.
 ///
 /// <https://www.w3.org/TR/css3-values/#absolute-lengths>
 pub const CSS_DEFAULT_DPI: f64 = 96.;
 
+/// `font-size` of text that doesn't set one, as in browsers
+const DEFAULT_FONT_SIZE: f64 = 16.;
+
 /// Used to compute percentages correctly
 ///
 /// <https://www.w3.org/TR/SVG/coords.html#Units>
@@ -21,6 +26,33 @@ pub enum DimensionHint {
     Other,
 }
 
+/// Inherited `font-size` of a node in user units, which is 16 unless set
+///
+/// Sizes in `em` or percent are relative to the size of the parent. Keywords (i.e. `larger`)
+/// are ignored.
+pub fn font_size(node: Node, to_user_units: impl Fn(Length, DimensionHint) -> f64) -> f64 {
+    let sizes = node
+        .ancestors()
+        .filter_map(|ancestor| {
+            let style = ancestor.attribute("style").and_then(|style| {
+                style.split(';').find_map(|declaration| {
+                    let (property, value) = declaration.split_once(':')?;
+                    (property.trim() == "font-size").then_some(value)
+                })
+            });
+            Length::from_str(style.or(ancestor.attribute("font-size"))?.trim()).ok()
+        })
+        .collect::<Vec<_>>();
+    sizes
+        .into_iter()
+        .rev()
+        .fold(DEFAULT_FONT_SIZE, |parent, size| match size.unit {
+            LengthUnit::Em => size.number * parent,
+            LengthUnit::Percent => size.number / 100. * parent,
+            _ => to_user_units(size, DimensionHint::Other),
+        })
+}
+
 impl<'a, T: Turtle> ConversionVisitor<'a, T> {
     /// Convenience function for converting a length attribute to user units
     pub fn length_attr_to_user_units(&self, node: &Node, attr: &str) -> Option<f64> {
@@ -61,7 +93,7 @@ impl<'a, T: Turtle> ConversionVisitor<'a, T> {
             // https://www.w3.org/TR/SVG/coords.html#ViewportSpace says None should be treated as Px
             Px | None => l.number,
             Em | Ex => {
-                warn!("Converting from em/ex to millimeters assumes 1em/ex = 16px");
+                warn!(target: UNSUPPORTED, "Converting from em/ex to millimeters assumes 1em/ex = 16px");
                 16. * l.number
             }
             // https://www.w3.org/TR/SVG/coords.html#Units
@@ -76,7 +108,7 @@ impl<'a, T: Turtle> ConversionVisitor<'a, T> {
                     };
                     l.number / 100. * scale
                 } else {
-                    warn!("A percentage without an established viewport is not valid!");
+                    warn!(target: UNSUPPORTED, "A percentage without an established viewport is not valid!");
                     l.number / 100.
                 }
             }
@@ -1,17 +1,43 @@
 use std::str::FromStr;
 
+#[cfg(feature = "raster")]
+use base64::{engine::general_purpose::STANDARD, Engine};
 use euclid::default::Transform2D;
+#[cfg(feature = "text")]
+use euclid::Angle;
 use log::{debug, warn};
-use roxmltree::{Document, Node};
+use roxmltree::{Document, Node, NodeId, ParsingOptions};
 use svgtypes::{AspectRatio, PathParser, PathSegment, PointsParser, TransformListParser, ViewBox};
+#[cfg(feature = "raster")]
+use uom::si::{
+    f64::Length as UomLength,
+    length::{inch, millimeter},
+};
 
 use super::{
-    path::apply_path,
+    css::inline_styles,
     transform::{get_viewport_transform, svg_transform_into_euclid_transform},
     units::DimensionHint,
-    ConversionVisitor,
+    ConversionVisitor, UNSUPPORTED,
+};
+#[cfg(feature = "text")]
+use super::{
+    font::{font_families, Glyphs, ShapedGlyph},
+    path::apply_path,
+    text::{
+        along_path, bidi_levels, direction, layout, positioned_chars, start_offset, text_content,
+        text_path_reference, PlacedGlyph,
+    },
+    units::font_size,
 };
 use crate::{converter::node_name, Turtle};
+#[cfg(feature = "raster")]
+use crate::{GrayImage, RasterImage};
+#[cfg(feature = "text")]
+use crate::{
+    turtle::{Terrarium, ToolpathTurtle},
+    Toolpath,
+};
 
 const SVG_TAG_NAME: &str = "svg";
 const CLIP_PATH_TAG_NAME: &str = "clipPath";
@@ -27,6 +53,15 @@ const DEFS_TAG_NAME: &str = "defs";
 const USE_TAG_NAME: &str = "use";
 const MARKER_TAG_NAME: &str = "marker";
 const SYMBOL_TAG_NAME: &str = "symbol";
+const TEXT_TAG_NAME: &str = "text";
+const IMAGE_TAG_NAME: &str = "image";
+#[cfg(feature = "text")]
+const TEXT_PATH_TAG_NAME: &str = "textPath";
+
+pub(super) const XLINK_NS_URI: &str = "http://www.w3.org/1999/xlink";
+const SWITCH_TAG_NAME: &str = "switch";
+/// Class or id of elements that the job pauses before
+const PAUSE_MARKER: &str = "pause";
 
 pub trait XmlVisitor {
     fn visit_enter(&mut self, node: Node);
@@ -38,26 +73,353 @@ fn should_render_node(node: Node) -> bool {
     node.is_element()
         && !node
             .attribute("style")
-            .map_or(false, |style| style.contains("display:none"))
+            .is_some_and(|style| style.contains("display:none"))
         // - Defs are not rendered
         // - Markers are not directly rendered
         // - Symbols are not directly rendered
         && !matches!(node.tag_name().name(), DEFS_TAG_NAME | MARKER_TAG_NAME | SYMBOL_TAG_NAME)
 }
 
-pub fn depth_first_visit(doc: &Document, visitor: &mut impl XmlVisitor) {
-    fn visit_node(node: Node, visitor: &mut impl XmlVisitor) {
-        if !should_render_node(node) {
+/// Whether the job should pause before drawing an element (i.e. to insert an inlay), which is
+/// marked with a `pause` class or an id of `pause` or starting with `pause-`
+///
+/// The element is drawn as usual, so an empty one (i.e. `<g class="pause"/>`) only pauses.
+fn is_pause_marker(node: &Node) -> bool {
+    node.attribute("class")
+        .is_some_and(|class| class.split_whitespace().any(|class| class == PAUSE_MARKER))
+        || node.attribute("id").is_some_and(|id| {
+            id.strip_prefix(PAUSE_MARKER)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('-'))
+        })
+}
+
+/// Whether the [conditional processing](https://www.w3.org/TR/SVG11/struct.html#ConditionalProcessing)
+/// attributes of an element all hold for a user that reads `language`
+///
+/// No extensions are supported (i.e. Illustrator's private data), and every feature is, as
+/// browsers do since SVG 2 dropped `requiredFeatures`, except that an empty list never holds.
+fn passes_conditions(node: Node, language: &str) -> bool {
+    node.attribute("requiredExtensions").is_none()
+        && node
+            .attribute("requiredFeatures")
+            .is_none_or(|features| !features.trim().is_empty())
+        && node.attribute("systemLanguage").is_none_or(|tags| {
+            tags.split(',').map(str::trim).any(|tag| {
+                // The user's language can also be a prefix of a more specific tag (i.e. en of en-US)
+                tag.get(..language.len())
+                    .is_some_and(|prefix| prefix.eq_ignore_ascii_case(language))
+                    && matches!(tag.as_bytes().get(language.len()), None | Some(b'-'))
+            })
+        })
+}
+
+/// The element a `<use>` draws a copy of
+fn use_reference<'a, 'input>(node: Node<'a, 'input>) -> Option<Node<'a, 'input>> {
+    let id = node
+        .attribute("href")
+        .or_else(|| node.attribute((XLINK_NS_URI, "href")))?
+        .strip_prefix('#')?;
+    node.document()
+        .descendants()
+        .find(|node| node.attribute("id") == Some(id))
+}
+
+/// An element followed by its ancestors as it is drawn
+///
+/// Elements drawn through the `<use>` elements in `uses`, innermost last, go on to the `<use>` and
+/// its ancestors once they reach the element it references, so they inherit styles and layers
+/// from where they are used rather than where they are defined.
+pub(super) fn drawn_ancestors<'a, 'input>(
+    node: Node<'a, 'input>,
+    uses: &[NodeId],
+) -> Vec<Node<'a, 'input>> {
+    let mut uses = uses
+        .iter()
+        .rev()
+        .filter_map(|id| node.document().get_node(*id));
+    let mut used = uses.next();
+    let mut ancestors = vec![];
+    let mut next = Some(node);
+    while let Some(element) = next {
+        ancestors.push(element);
+        next = match used {
+            Some(use_node) if use_reference(use_node) == Some(element) => {
+                used = uses.next();
+                Some(use_node)
+            }
+            _ => element.parent(),
+        };
+    }
+    ancestors
+}
+
+/// Visit the elements of a document that are rendered for a user that reads `language`
+///
+/// Of the children of a `<switch>`, only the first one that passes its conditions is visited.
+/// The element referenced by a `<use>` is visited as its only child, even if it is in `<defs>`
+/// or is a `<symbol>`, so it is drawn once for each time it is used. Rules of `<style>` sheets are
+/// added to the `style` of the elements they apply to before visiting them.
+pub fn depth_first_visit(doc: &Document, language: &str, visitor: &mut impl XmlVisitor) {
+    /// `used` are the references of the `<use>` elements being visited, to catch ones that
+    /// reference themselves
+    fn visit_node(
+        node: Node,
+        language: &str,
+        visitor: &mut impl XmlVisitor,
+        used: &mut Vec<NodeId>,
+    ) {
+        if !should_render_node(node) || !passes_conditions(node, language) {
             return;
         }
         visitor.visit_enter(node);
-        node.children().for_each(|child| visit_node(child, visitor));
+        if node.has_tag_name(SWITCH_TAG_NAME) {
+            if let Some(child) = node
+                .children()
+                .find(|child| child.is_element() && passes_conditions(*child, language))
+            {
+                visit_node(child, language, visitor, used);
+            }
+        } else if node.has_tag_name(USE_TAG_NAME) {
+            match use_reference(node) {
+                Some(reference)
+                    if used.contains(&reference.id())
+                        || node.ancestors().any(|ancestor| ancestor == reference) =>
+                {
+                    warn!(target: UNSUPPORTED, "There is a use node that references itself: {node:?}");
+                }
+                // Symbols are only ever drawn through a use
+                Some(reference) if reference.has_tag_name(SYMBOL_TAG_NAME) => {
+                    used.push(reference.id());
+                    visitor.visit_enter(reference);
+                    reference
+                        .children()
+                        .for_each(|child| visit_node(child, language, visitor, used));
+                    visitor.visit_exit(reference);
+                    used.pop();
+                }
+                Some(reference) => {
+                    used.push(reference.id());
+                    visit_node(reference, language, visitor, used);
+                    used.pop();
+                }
+                None => warn!(
+                    target: UNSUPPORTED,
+                    "There is a use node that does not reference an element: {node:?}"
+                ),
+            }
+        } else {
+            node.children()
+                .for_each(|child| visit_node(child, language, visitor, used));
+        }
         visitor.visit_exit(node);
     }
 
+    let styled = inline_styles(doc);
+    let styled = styled.as_deref().and_then(|svg| {
+        Document::parse_with_options(
+            svg,
+            ParsingOptions {
+                allow_dtd: true,
+                ..Default::default()
+            },
+        )
+        .ok()
+    });
+    let doc = styled.as_ref().unwrap_or(doc);
+
     doc.root()
         .children()
-        .for_each(|child| visit_node(child, visitor));
+        .for_each(|child| visit_node(child, language, visitor, &mut vec![]));
+}
+
+#[cfg(feature = "text")]
+impl<'a, T: Turtle> ConversionVisitor<'a, T> {
+    /// Lay out the characters of a `<text>` element and draw their glyphs
+    ///
+    /// Text in `<textPath>`s is laid out along the paths they reference instead.
+    fn draw_text(&mut self, node: Node) {
+        let content = text_content(node);
+        let Some(glyphs) = Glyphs::resolve(
+            &self.options.fonts,
+            &font_families(node),
+            &self._config.font_substitutions,
+        ) else {
+            warn!(target: UNSUPPORTED, "There is no font to draw text with: {content:?}");
+            return;
+        };
+        let text_paths = node
+            .descendants()
+            .filter(|descendant| descendant.has_tag_name(TEXT_PATH_TAG_NAME))
+            .collect::<Vec<_>>();
+        let path = if text_paths.is_empty() {
+            self.place_glyphs(node, &glyphs, |placed, _| placed)
+        } else {
+            let visible = |content: String| content.chars().filter(|c| !c.is_whitespace()).count();
+            if visible(content.clone())
+                > text_paths
+                    .iter()
+                    .map(|text_path| visible(text_content(*text_path)))
+                    .sum()
+            {
+                warn!(target: UNSUPPORTED, "Only the text in a textPath is drawn: {content:?}");
+            }
+            text_paths
+                .into_iter()
+                .flat_map(|text_path| {
+                    let Some(path) = text_path_reference(text_path) else {
+                        warn!(target: UNSUPPORTED, "There is a textPath node that does not reference a path: {text_path:?}");
+                        return vec![];
+                    };
+                    let path = self.path_toolpaths(path);
+                    let length = path
+                        .iter()
+                        .map(|toolpath| toolpath.length(self.user_units_tolerance()))
+                        .sum();
+                    let start_offset = start_offset(text_path, length, |l, hint| {
+                        self.length_to_user_units(l, hint)
+                    });
+                    self.place_glyphs(text_path, &glyphs, |placed, tolerance| {
+                        along_path(placed, &path, start_offset, tolerance)
+                    })
+                })
+                .collect()
+        };
+        self.comment(&node);
+        self.draw(&node, path);
+    }
+
+    /// Lay out the characters of a text content element, move them with `place`, and return the
+    /// path drawing their glyphs
+    fn place_glyphs(
+        &self,
+        text: Node,
+        glyphs: &Glyphs,
+        place: impl FnOnce(Vec<PlacedGlyph>, f64) -> Vec<PlacedGlyph>,
+    ) -> Vec<PathSegment> {
+        let content = text_content(text);
+        let direction = direction(text, &content);
+        let size = font_size(text, |l, hint| self.length_to_user_units(l, hint));
+        let chars = positioned_chars(text, |l, hint| self.length_to_user_units(l, hint));
+        let levels = bidi_levels(&chars, direction);
+        let placed = layout(text, &chars, &levels, direction, |run, direction| {
+            glyphs
+                .shape(run, direction)
+                .into_iter()
+                .map(|shaped| {
+                    if shaped.glyph.is_none() {
+                        let c = run[shaped.cluster];
+                        warn!(target: UNSUPPORTED, "The font has no glyph for {c:?} in text: {content:?}");
+                    }
+                    ShapedGlyph {
+                        advance: shaped.advance * size,
+                        offset: shaped.offset * size,
+                        ..shaped
+                    }
+                })
+                .collect()
+        });
+        place(placed, self.user_units_tolerance())
+            .iter()
+            .flat_map(|placed| {
+                let transform = Transform2D::scale(size, size)
+                    .then_rotate(Angle::degrees(placed.rotate))
+                    .then_translate(placed.origin.to_vector());
+                glyphs.path(placed.glyph, &transform)
+            })
+            .collect()
+    }
+
+    /// Toolpaths of a `<path>` in the user units of its parent, for laying out text along
+    fn path_toolpaths(&self, path: Node) -> Vec<Toolpath> {
+        let mut terrarium = Terrarium::new(ToolpathTurtle::default());
+        apply_path(
+            &mut terrarium,
+            PathParser::from(path.attribute("d").unwrap_or_default()).map_while(Result::ok),
+        );
+        let transform = path
+            .attribute("transform")
+            .map(|transform| {
+                TransformListParser::from(transform)
+                    .map_while(Result::ok)
+                    .map(svg_transform_into_euclid_transform)
+                    .fold(Transform2D::identity(), |acc, t| t.then(&acc))
+            })
+            .unwrap_or_else(Transform2D::identity);
+        terrarium
+            .turtle
+            .toolpaths
+            .iter()
+            .map(|toolpath| toolpath.transformed(&transform))
+            .collect()
+    }
+}
+
+#[cfg(feature = "raster")]
+impl<'a, T: Turtle> ConversionVisitor<'a, T> {
+    /// Read the image of an `<image>` element and place it where the element is, to engrave later
+    fn draw_image(&mut self, node: Node) {
+        if self._config.raster.is_none() {
+            warn!(target: UNSUPPORTED, "Images are only engraved with raster settings: {node:?}");
+            return;
+        }
+        let Some(href) = node
+            .attribute("href")
+            .or_else(|| node.attribute((XLINK_NS_URI, "href")))
+        else {
+            warn!(target: UNSUPPORTED, "There is an image node without an href: {node:?}");
+            return;
+        };
+        let bytes = match href.trim().strip_prefix("data:") {
+            Some(data) => match data.split_once(";base64,") {
+                Some((_, encoded)) => STANDARD
+                    .decode(encoded.split_whitespace().collect::<String>())
+                    .map_err(|err| err.to_string()),
+                None => Err("only base64 data URLs are supported".to_string()),
+            },
+            None => match &self.options.image_dir {
+                Some(dir) => std::fs::read(dir.join(href.strip_prefix("file://").unwrap_or(href)))
+                    .map_err(|err| err.to_string()),
+                None => Err("there is no directory to read linked images from".to_string()),
+            },
+        };
+        let image = match bytes
+            .and_then(|bytes| GrayImage::decode(&bytes).map_err(|err| err.to_string()))
+        {
+            Ok(image) => image,
+            Err(err) => {
+                warn!(target: UNSUPPORTED, "Could not read the image of {node:?}: {err}");
+                return;
+            }
+        };
+
+        // Sized by the image itself, in pixels, unless the element sets a width or height
+        // https://www.w3.org/TR/SVG/embedded.html#ImageElement
+        let [width, height] = [image.width, image.height].map(|len| len as f64);
+        let size = match ["width", "height"].map(|attr| self.length_attr_to_user_units(&node, attr))
+        {
+            [Some(w), Some(h)] => [w, h],
+            [Some(w), None] => [w, w * height / width],
+            [None, Some(h)] => [h * width / height, h],
+            [None, None] => [width, height],
+        };
+        let position = ["x", "y"].map(|attr| self.length_attr_to_user_units(&node, attr));
+        let preserve_aspect_ratio = node
+            .attribute("preserveAspectRatio")
+            .and_then(|attr| AspectRatio::from_str(attr).ok());
+        if preserve_aspect_ratio.is_some_and(|aspect_ratio| aspect_ratio.slice) {
+            warn!(target: UNSUPPORTED, "Images are not cut off to fit for preserveAspectRatio slice: {node:?}");
+        }
+        let to_mm = UomLength::new::<inch>(1. / self._config.dpi).get::<millimeter>();
+        let transform = get_viewport_transform(
+            ViewBox::new(0., 0., width, height),
+            preserve_aspect_ratio,
+            size,
+            position,
+        )
+        .then(&self.terrarium.current_transform())
+        .then_scale(to_mm, to_mm);
+        self.rasters.push(RasterImage { image, transform });
+    }
 }
 
 impl<'a, T: Turtle> XmlVisitor for ConversionVisitor<'a, T> {
@@ -65,21 +427,42 @@ impl<'a, T: Turtle> XmlVisitor for ConversionVisitor<'a, T> {
         use PathSegment::*;
 
         if node.tag_name().name() == CLIP_PATH_TAG_NAME {
-            warn!("Clip paths are not supported: {:?}", node);
+            warn!(target: UNSUPPORTED, "Clip paths are not supported: {:?}", node);
+        }
+
+        let has_id_in = |ids: &[String]| {
+            node.attribute("id")
+                .is_some_and(|id| ids.iter().any(|i| i == id))
+        };
+        if self.selected_depth > 0 || has_id_in(&self.options.only_ids) {
+            self.selected_depth += 1;
+        }
+        if self.skipped_depth > 0 || has_id_in(&self.options.skip_ids) {
+            self.skipped_depth += 1;
+        }
+
+        if is_pause_marker(&node) && self.is_selected() {
+            self.terrarium.turtle.pause();
         }
 
         // TODO: https://www.w3.org/TR/css-transforms-1/#transform-origin-property
         if let Some(mut origin) = node.attribute("transform-origin").map(PointsParser::from) {
             let _origin = origin.next();
-            warn!("transform-origin not supported yet");
+            warn!(target: UNSUPPORTED, "transform-origin not supported yet");
         }
 
         let mut flattened_transform = if let Some(transform) = node.attribute("transform") {
             // https://stackoverflow.com/questions/18582935/the-applying-order-of-svg-transforms
-            TransformListParser::from(transform)
-                .map(|token| token.expect("could not parse a transform in a list of transforms"))
-                .map(svg_transform_into_euclid_transform)
-                .fold(Transform2D::identity(), |acc, t| t.then(&acc))
+            match TransformListParser::from(transform).collect::<Result<Vec<_>, _>>() {
+                Ok(transforms) => transforms
+                    .into_iter()
+                    .map(svg_transform_into_euclid_transform)
+                    .fold(Transform2D::identity(), |acc, t| t.then(&acc)),
+                Err(err) => {
+                    warn!("Ignoring a transform that could not be parsed, {transform:?}: {err}");
+                    Transform2D::identity()
+                }
+            }
         } else {
             Transform2D::identity()
         };
@@ -88,19 +471,31 @@ impl<'a, T: Turtle> XmlVisitor for ConversionVisitor<'a, T> {
         if node.has_tag_name(SVG_TAG_NAME) {
             let view_box = node
                 .attribute("viewBox")
-                .map(ViewBox::from_str)
-                .transpose()
-                .expect("could not parse viewBox")
+                .and_then(|view_box| {
+                    ViewBox::from_str(view_box)
+                        .map_err(|err| {
+                            warn!(
+                                "Ignoring a viewBox that could not be parsed, {view_box:?}: {err}"
+                            )
+                        })
+                        .ok()
+                })
                 .filter(|view_box| {
                     if view_box.w <= 0. || view_box.h <= 0. {
-                        warn!("Invalid viewBox: {view_box:?}");
+                        warn!(target: UNSUPPORTED, "Invalid viewBox: {view_box:?}");
                         false
                     } else {
                         true
                     }
                 });
-            let preserve_aspect_ratio = node.attribute("preserveAspectRatio").map(|attr| {
-                AspectRatio::from_str(attr).expect("could not parse preserveAspectRatio")
+            let preserve_aspect_ratio = node.attribute("preserveAspectRatio").and_then(|attr| {
+                AspectRatio::from_str(attr)
+                    .map_err(|err| {
+                        warn!(
+                            "Ignoring a preserveAspectRatio that can't be parsed, {attr:?}: {err}"
+                        )
+                    })
+                    .ok()
             });
             let mut viewport_size =
                 ["width", "height"].map(|attr| self.length_attr_to_user_units(&node, attr));
@@ -109,10 +504,7 @@ impl<'a, T: Turtle> XmlVisitor for ConversionVisitor<'a, T> {
                 .options
                 .dimensions
                 .map(|l| l.map(|l| self.length_to_user_units(l, DimensionHint::Horizontal)));
-            for (original_dim, override_dim) in viewport_size
-                .iter_mut()
-                .zip(dimensions_override.into_iter())
-            {
+            for (original_dim, override_dim) in viewport_size.iter_mut().zip(dimensions_override) {
                 *original_dim = override_dim.or(*original_dim);
             }
 
@@ -144,6 +536,7 @@ impl<'a, T: Turtle> XmlVisitor for ConversionVisitor<'a, T> {
             };
 
             let viewport_pos = ["x", "y"].map(|attr| self.length_attr_to_user_units(&node, attr));
+            let outermost = self.viewport_dim_stack.is_empty();
 
             self.viewport_dim_stack
                 .push(match (view_box.as_ref(), &viewport_size) {
@@ -159,29 +552,85 @@ impl<'a, T: Turtle> XmlVisitor for ConversionVisitor<'a, T> {
                     viewport_pos,
                 );
                 flattened_transform = flattened_transform.then(&viewport_transform);
+            } else if !outermost {
+                flattened_transform = flattened_transform.then(&Transform2D::translation(
+                    viewport_pos[0].unwrap_or(0.),
+                    viewport_pos[1].unwrap_or(0.),
+                ));
+            }
+            // Part 2 of converting from SVG to GCode coordinates, once the drawing is in the
+            // coordinates of the outermost viewport
+            if outermost {
+                flattened_transform = flattened_transform.then(&Transform2D::translation(
+                    0.,
+                    -(viewport_size[1] + viewport_pos[1].unwrap_or(0.)),
+                ));
             }
-            // Part 2 of converting from SVG to GCode coordinates
-            flattened_transform = flattened_transform.then(&Transform2D::translation(
-                0.,
-                -(viewport_size[1] + viewport_pos[1].unwrap_or(0.)),
-            ));
-        } else if node.has_attribute("viewBox") {
-            warn!("View box is not supported on a {}", node.tag_name().name());
+        } else if node.has_tag_name(USE_TAG_NAME) {
+            // https://www.w3.org/TR/SVG/struct.html#UseElement
+            let position = ["x", "y"].map(|attr| self.length_attr_to_user_units(&node, attr));
+            let symbol =
+                use_reference(node).filter(|reference| reference.has_tag_name(SYMBOL_TAG_NAME));
+            let view_box = symbol
+                .and_then(|symbol| symbol.attribute("viewBox"))
+                .and_then(|view_box| ViewBox::from_str(view_box).ok())
+                .filter(|view_box| view_box.w > 0. && view_box.h > 0.);
+            let reference_transform = match (symbol, view_box) {
+                (Some(symbol), Some(view_box)) => {
+                    // Sized by the use, or else by the symbol, or else it fills the viewport
+                    let viewport = self
+                        .viewport_dim_stack
+                        .last()
+                        .copied()
+                        .unwrap_or([view_box.w, view_box.h]);
+                    let viewport_size =
+                        [("width", viewport[0]), ("height", viewport[1])].map(|(attr, default)| {
+                            self.length_attr_to_user_units(&node, attr)
+                                .or_else(|| self.length_attr_to_user_units(&symbol, attr))
+                                .unwrap_or(default)
+                        });
+                    let preserve_aspect_ratio = symbol
+                        .attribute("preserveAspectRatio")
+                        .and_then(|attr| AspectRatio::from_str(attr).ok());
+                    get_viewport_transform(view_box, preserve_aspect_ratio, viewport_size, position)
+                }
+                _ => Transform2D::translation(position[0].unwrap_or(0.), position[1].unwrap_or(0.)),
+            };
+            flattened_transform = reference_transform.then(&flattened_transform);
+        } else if node.has_attribute("viewBox") && !node.has_tag_name(SYMBOL_TAG_NAME) {
+            warn!(target: UNSUPPORTED, "View box is not supported on a {}", node.tag_name().name());
         }
 
         self.terrarium.push_transform(flattened_transform);
 
         match node.tag_name().name() {
+            // Elements outside of the selection are still entered to place the ones inside it
+            _ if !self.is_selected() => {}
+            PATH_TAG_NAME | POLYLINE_TAG_NAME | POLYGON_TAG_NAME | RECT_TAG_NAME
+            | CIRCLE_TAG_NAME | ELLIPSE_TAG_NAME | LINE_TAG_NAME | TEXT_TAG_NAME
+                if !self
+                    ._config
+                    .paint_filter
+                    .draws(&drawn_ancestors(node, &self.uses)) =>
+            {
+                debug!("Not painted as needed to be drawn: {node:?}");
+            }
             PATH_TAG_NAME => {
                 if let Some(d) = node.attribute("d") {
                     self.comment(&node);
-                    apply_path(
-                        &mut self.terrarium,
-                        PathParser::from(d)
-                            .map(|segment| segment.expect("could not parse path segment")),
+                    self.draw(
+                        &node,
+                        // Drawn up to the first error, like browsers do
+                        PathParser::from(d).map_while(|segment| {
+                            segment
+                                .map_err(|err| {
+                                    warn!("Only part of the path could be parsed, {node:?}: {err}")
+                                })
+                                .ok()
+                        }),
                     );
                 } else {
-                    warn!("There is a path node containing no actual path: {node:?}");
+                    warn!(target: UNSUPPORTED, "There is a path node containing no actual path: {node:?}");
                 }
             }
             name @ (POLYLINE_TAG_NAME | POLYGON_TAG_NAME) => {
@@ -204,9 +653,9 @@ impl<'a, T: Turtle> XmlVisitor for ConversionVisitor<'a, T> {
                             },
                         );
 
-                    apply_path(&mut self.terrarium, path);
+                    self.draw(&node, path);
                 } else {
-                    warn!("There is a {name} node containing no actual path: {node:?}");
+                    warn!(target: UNSUPPORTED, "There is a {name} node containing no actual path: {node:?}");
                 }
             }
             RECT_TAG_NAME => {
@@ -221,8 +670,8 @@ impl<'a, T: Turtle> XmlVisitor for ConversionVisitor<'a, T> {
                 match (width, height) {
                     (Some(width), Some(height)) => {
                         self.comment(&node);
-                        apply_path(
-                            &mut self.terrarium,
+                        self.draw(
+                            &node,
                             [
                                 MoveTo {
                                     abs: true,
@@ -292,7 +741,7 @@ impl<'a, T: Turtle> XmlVisitor for ConversionVisitor<'a, T> {
                         )
                     }
                     _other => {
-                        warn!("Invalid rectangle node: {node:?}");
+                        warn!(target: UNSUPPORTED, "Invalid rectangle node: {node:?}");
                     }
                 }
             }
@@ -304,8 +753,8 @@ impl<'a, T: Turtle> XmlVisitor for ConversionVisitor<'a, T> {
                 let ry = self.length_attr_to_user_units(&node, "ry").unwrap_or(r);
                 if rx > 0. && ry > 0. {
                     self.comment(&node);
-                    apply_path(
-                        &mut self.terrarium,
+                    self.draw(
+                        &node,
                         std::iter::once(MoveTo {
                             abs: true,
                             x: cx + rx,
@@ -328,7 +777,7 @@ impl<'a, T: Turtle> XmlVisitor for ConversionVisitor<'a, T> {
                         .chain(std::iter::once(ClosePath { abs: true })),
                     );
                 } else {
-                    warn!("Invalid {} node: {node:?}", node.tag_name().name());
+                    warn!(target: UNSUPPORTED, "Invalid {} node: {node:?}", node.tag_name().name());
                 }
             }
             LINE_TAG_NAME => {
@@ -339,8 +788,8 @@ impl<'a, T: Turtle> XmlVisitor for ConversionVisitor<'a, T> {
                 match (x1, y1, x2, y2) {
                     (Some(x1), Some(y1), Some(x2), Some(y2)) => {
                         self.comment(&node);
-                        apply_path(
-                            &mut self.terrarium,
+                        self.draw(
+                            &node,
                             [
                                 MoveTo {
                                     abs: true,
@@ -356,26 +805,46 @@ impl<'a, T: Turtle> XmlVisitor for ConversionVisitor<'a, T> {
                         );
                     }
                     _other => {
-                        warn!("Invalid line node: {node:?}");
+                        warn!(target: UNSUPPORTED, "Invalid line node: {node:?}");
                     }
                 }
             }
-            USE_TAG_NAME => {
-                warn!("Unsupported node: {node:?}");
+            #[cfg(feature = "text")]
+            TEXT_TAG_NAME => self.draw_text(node),
+            #[cfg(not(feature = "text"))]
+            TEXT_TAG_NAME => {
+                warn!(
+                    target: UNSUPPORTED,
+                    "Text is only drawn with the text feature, convert it to paths first: {node:?}"
+                );
+            }
+            #[cfg(feature = "raster")]
+            IMAGE_TAG_NAME => self.draw_image(node),
+            #[cfg(not(feature = "raster"))]
+            IMAGE_TAG_NAME => {
+                warn!(target: UNSUPPORTED, "Images are only engraved with the raster feature: {node:?}");
             }
             // No-op tags
-            SVG_TAG_NAME | GROUP_TAG_NAME => {}
+            SVG_TAG_NAME | GROUP_TAG_NAME | SWITCH_TAG_NAME | USE_TAG_NAME | SYMBOL_TAG_NAME => {}
             _ => {
                 debug!("Unknown node: {}", node.tag_name().name());
             }
         }
 
         self.name_stack.push(node_name(&node,&self._config.extra_attribute_name));
+        if node.has_tag_name(USE_TAG_NAME) {
+            self.uses.push(node.id());
+        }
     }
 
     fn visit_exit(&mut self, node: Node) {
         self.terrarium.pop_transform();
         self.name_stack.pop();
+        if node.has_tag_name(USE_TAG_NAME) {
+            self.uses.pop();
+        }
+        self.selected_depth = self.selected_depth.saturating_sub(1);
+        self.skipped_depth = self.skipped_depth.saturating_sub(1);
         if node.tag_name().name() == SVG_TAG_NAME {
             self.viewport_dim_stack.pop();
         }
@@ -0,0 +1,150 @@
+use std::fmt::{self, Display};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{MachineConfig, SupportedFunctionality};
+
+/// Firmware a machine runs, which sets up the sequences and supported functionality of a
+/// [`MachineConfig`] to drive a laser with it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Dialect {
+    /// [Grbl](https://github.com/gnea/grbl) in laser mode (`$32=1`), which turns the laser off
+    /// during rapid moves and scales its power with the speed of cuts (M4)
+    Grbl,
+    /// [Marlin](https://marlinfw.org) with the laser on the fan output (M106/M107), waiting for
+    /// moves to finish before switching it, since fan commands don't wait on their own
+    Marlin,
+    /// [LinuxCNC](https://linuxcnc.org) with the laser or spindle on M3/M5
+    LinuxCnc,
+    /// [Klipper](https://www.klipper3d.org) with the laser on the fan output (M106/M107), without
+    /// arcs since they need `[gcode_arcs]` in its config
+    Klipper,
+}
+
+impl Dialect {
+    /// Set the tool on, tool off, and end sequences and the supported functionality of `config`
+    /// for this firmware, replacing what was there
+    pub fn apply(self, config: &mut MachineConfig) {
+        let (tool_on, tool_off, end) = match self {
+            Self::Grbl => ("M4 S1000", "M5", Some("M2")),
+            Self::Marlin => ("M400\nM106 S255", "M400\nM107", None),
+            Self::LinuxCnc => ("M3 S1000", "M5", Some("M2")),
+            Self::Klipper => ("M106 S255", "M107", None),
+        };
+        config.dialect = Some(self);
+        config.tool_on_sequence = Some(tool_on.to_string());
+        config.tool_off_sequence = Some(tool_off.to_string());
+        config.end_sequence = end.map(str::to_string);
+        config.supported_functionality = SupportedFunctionality {
+            circular_interpolation: self != Self::Klipper,
+            arc_center_offsets: self != Self::Klipper,
+        };
+    }
+
+    /// Whether comments can be written in parentheses, which Marlin and Klipper read as g-code
+    pub fn parenthesized_comments(self) -> bool {
+        matches!(self, Self::Grbl | Self::LinuxCnc)
+    }
+
+    /// Whether the P word of a dwell (G4) is in milliseconds, as on Marlin and Klipper, instead
+    /// of seconds
+    pub fn dwell_in_milliseconds(self) -> bool {
+        matches!(self, Self::Marlin | Self::Klipper)
+    }
+
+    /// Whether a move can leave out its G0, G1, G2, or G3 when it is the same as the one before,
+    /// as on Grbl and LinuxCNC
+    ///
+    /// Marlin and Klipper only move with a G word on the line.
+    pub fn modal_motion(self) -> bool {
+        matches!(self, Self::Grbl | Self::LinuxCnc)
+    }
+
+    /// Whether the firmware understands the commands of a modal group, as it is set up by
+    /// [`Self::apply`]
+    ///
+    /// Marlin and Klipper drive the laser from the fan output and leave out the CNC features
+    /// that need extra configuration.
+    pub fn supports(self, group: ModalGroup) -> bool {
+        match self {
+            Self::Grbl | Self::LinuxCnc => true,
+            Self::Marlin | Self::Klipper => !matches!(
+                group,
+                ModalGroup::Plane
+                    | ModalGroup::FeedrateMode
+                    | ModalGroup::CoordinateSystem
+                    | ModalGroup::Spindle
+            ),
+        }
+    }
+}
+
+/// Commands that stay in effect until another command of the same group replaces them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModalGroup {
+    /// G17, G18, and G19, which plane arcs are drawn in
+    Plane,
+    /// G93, G94, and G95, whether feedrates are per minute, inverse time, or per revolution
+    FeedrateMode,
+    /// G54 to G59, which work coordinate system coordinates are in
+    CoordinateSystem,
+    /// M3, M4, and M5, which turn the spindle or laser on and off
+    Spindle,
+}
+
+impl ModalGroup {
+    /// The group a command belongs to, if it is one of these
+    pub fn of(letter: &str, number: f64) -> Option<Self> {
+        let number = number as u32;
+        match letter {
+            "G" => match number {
+                17..=19 => Some(Self::Plane),
+                93..=95 => Some(Self::FeedrateMode),
+                54..=59 => Some(Self::CoordinateSystem),
+                _ => None,
+            },
+            "M" => matches!(number, 3..=5).then_some(Self::Spindle),
+            _ => None,
+        }
+    }
+}
+
+impl Display for ModalGroup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Plane => write!(f, "plane selection (G17-G19)"),
+            Self::FeedrateMode => write!(f, "feedrate modes (G93-G95)"),
+            Self::CoordinateSystem => write!(f, "work coordinate systems (G54-G59)"),
+            Self::Spindle => write!(f, "spindle control (M3-M5)"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::lint_sequences;
+
+    #[test]
+    fn dialects_set_up_sequences_without_warnings() {
+        for dialect in [
+            Dialect::Grbl,
+            Dialect::Marlin,
+            Dialect::LinuxCnc,
+            Dialect::Klipper,
+        ] {
+            let mut config = MachineConfig {
+                begin_sequence: Some("G28".to_string()),
+                ..Default::default()
+            };
+            dialect.apply(&mut config);
+            assert_eq!(config.dialect, Some(dialect));
+            assert!(config.tool_on_sequence.is_some());
+            // Sequences that have nothing to do with the firmware are kept
+            assert_eq!(config.begin_sequence.as_deref(), Some("G28"));
+            assert!(lint_sequences(&config).is_empty(), "{dialect:?}");
+        }
+    }
+}
@@ -0,0 +1,397 @@
+use std::{
+    borrow::Cow,
+    fmt::{self, Display},
+    time::Duration,
+};
+
+use g_code::emit::Token;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{follow_program, program::cut_bounds, Dialect, ProgramStep};
+
+/// Rates for estimating how long a job takes, what it uses, and what it costs
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CostConfig {
+    /// Feedrate of moves between paths in millimeters / minute
+    #[cfg_attr(feature = "serde", serde(default = "default_rapid_feedrate"))]
+    pub rapid_feedrate: f64,
+    /// Cost of running the machine for an hour
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub hourly_rate: f64,
+    /// Cost of a square meter of material, charged for the bounding box of the job
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub material_cost: f64,
+    /// Power drawn by the machine while running in watts, for estimating electricity use
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub power: f64,
+    /// How quickly the machine speeds up and slows down in millimeters / second², or 0 to ignore
+    /// it
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub acceleration: f64,
+    /// Start programs with comments stating how long they take and how far they cut and travel,
+    /// see [`JobEstimate::header`]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub header: bool,
+}
+
+const fn default_rapid_feedrate() -> f64 {
+    3000.
+}
+
+impl Default for CostConfig {
+    fn default() -> Self {
+        Self {
+            rapid_feedrate: default_rapid_feedrate(),
+            hourly_rate: 0.,
+            material_cost: 0.,
+            power: 0.,
+            acceleration: 0.,
+            header: false,
+        }
+    }
+}
+
+/// How long a job takes, what it uses, and what it costs
+///
+/// With a [`CostConfig::acceleration`], the machine is taken to stop whenever the tool is turned
+/// on or off or the feedrate changes (i.e. at the ends of paths), while going around the corners
+/// in between at full speed.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct JobEstimate {
+    /// Distance travelled with the tool on (i.e. pen-down distance), in millimeters
+    pub cutting_distance: f64,
+    /// Distance travelled with the tool off (i.e. between paths), in millimeters
+    pub rapid_distance: f64,
+    pub duration: Duration,
+    /// Time spent with the tool on (i.e. laser tube on-time)
+    pub tool_on_duration: Duration,
+    /// Electricity used over the whole job in kilowatt-hours, from [`CostConfig::power`]
+    pub energy: f64,
+    /// Width and height of the bounding box of all cuts, in millimeters
+    pub size: [f64; 2],
+    /// From [`CostConfig::hourly_rate`] and [`CostConfig::material_cost`]
+    pub cost: f64,
+}
+
+/// Why a program can't be estimated
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EstimateError {
+    /// A feed move (G1, G2, or G3) comes before any feedrate is set
+    NoFeedrate,
+    /// A move goes at this feedrate, which isn't above 0, so it would never finish
+    InvalidFeedrate(f64),
+}
+
+impl Display for EstimateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoFeedrate => write!(f, "a move comes before any feedrate is set"),
+            Self::InvalidFeedrate(feedrate) => write!(
+                f,
+                "a move goes at {feedrate} mm/min, which would never finish, feedrates must be above 0"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for EstimateError {}
+
+impl JobEstimate {
+    /// Estimate running a program, following it like a machine would, see [`follow_program`]
+    ///
+    /// Everything the program does is counted: each pass and layer at its own feedrate, moves of
+    /// the Z axis, plunges and ramps, images, and dwells. Rapid moves (G0) go at
+    /// [`CostConfig::rapid_feedrate`].
+    pub fn from_program(
+        program: &[Token<'_>],
+        dialect: Option<Dialect>,
+        cost: &CostConfig,
+    ) -> Result<Self, EstimateError> {
+        let valid = |feedrate: f64| {
+            if feedrate > 0. {
+                Ok(feedrate)
+            } else {
+                Err(EstimateError::InvalidFeedrate(feedrate))
+            }
+        };
+        let rapid_feedrate = valid(cost.rapid_feedrate)?;
+
+        let mut estimate = Self::default();
+        // With the tool off and on
+        let mut minutes = [0.; 2];
+        // Moves that go one after the other at the same feedrate, which the machine doesn't stop
+        // between: whether they cut, their feedrate, and how far they go
+        let mut run: Option<(bool, f64, f64)> = None;
+        let end_run = |minutes: &mut [f64; 2], run: Option<(bool, f64, f64)>| {
+            if let Some((cuts, feedrate, distance)) = run {
+                minutes[usize::from(cuts)] += move_minutes(distance, feedrate, cost.acceleration);
+            }
+        };
+        let steps = follow_program(program, dialect);
+        for step in &steps {
+            let program_move = match step {
+                ProgramStep::Move(program_move) => program_move,
+                ProgramStep::Dwell { seconds, tool_on } => {
+                    end_run(&mut minutes, run.take());
+                    minutes[usize::from(*tool_on)] += seconds.max(0.) / 60.;
+                    continue;
+                }
+            };
+            let feedrate = if program_move.rapid {
+                rapid_feedrate
+            } else {
+                valid(program_move.feedrate.ok_or(EstimateError::NoFeedrate)?)?
+            };
+            let length = program_move.length();
+            if program_move.cuts {
+                estimate.cutting_distance += length;
+            } else {
+                estimate.rapid_distance += length;
+            }
+            match &mut run {
+                Some((cuts, run_feedrate, distance))
+                    if *cuts == program_move.cuts && *run_feedrate == feedrate =>
+                {
+                    *distance += length;
+                }
+                _ => end_run(
+                    &mut minutes,
+                    run.replace((program_move.cuts, feedrate, length)),
+                ),
+            }
+        }
+        end_run(&mut minutes, run);
+
+        let duration =
+            |minutes: f64| Duration::try_from_secs_f64(minutes * 60.).unwrap_or(Duration::MAX);
+        estimate.tool_on_duration = duration(minutes[1]);
+        estimate.duration = duration(minutes[0] + minutes[1]);
+        estimate.energy = cost.power * estimate.duration.as_secs_f64() / 3600. / 1000.;
+        if let Some(bounds) = cut_bounds(&steps) {
+            estimate.size = bounds.size().to_array();
+        }
+        estimate.cost = estimate.duration.as_secs_f64() / 3600. * cost.hourly_rate
+            + estimate.area() * cost.material_cost;
+        Ok(estimate)
+    }
+
+    /// Area of the bounding box in square meters
+    pub fn area(&self) -> f64 {
+        self.size[0] * self.size[1] / 1E6
+    }
+
+    /// Comments for the start of a program, stating how long it takes and how far it cuts and
+    /// travels
+    pub fn header<'input>(&self) -> Vec<Token<'input>> {
+        [
+            format!(
+                "Estimated time: {}, with the tool on for {}",
+                HoursMinutesSeconds(self.duration),
+                HoursMinutesSeconds(self.tool_on_duration)
+            ),
+            format!("Cut length: {:.0} mm", self.cutting_distance),
+            format!("Travel length: {:.0} mm", self.rapid_distance),
+        ]
+        .into_iter()
+        .map(|comment| Token::Comment {
+            is_inline: false,
+            inner: Cow::Owned(comment),
+        })
+        .collect()
+    }
+}
+
+/// Minutes to move `distance` at `feedrate`, speeding up from a stop and slowing down to one at
+/// `acceleration`, which is instant if it is 0
+///
+/// Short moves never reach the feedrate, so they speed up for half of the way and slow down for
+/// the rest.
+fn move_minutes(distance: f64, feedrate: f64, acceleration: f64) -> f64 {
+    if acceleration.is_nan() || acceleration <= 0. {
+        return distance / feedrate;
+    }
+    let speed = feedrate / 60.;
+    // Covered while speeding up and slowing down
+    let ramps = speed * speed / acceleration;
+    let seconds = if distance >= ramps {
+        distance / speed + speed / acceleration
+    } else {
+        2. * (distance / acceleration).sqrt()
+    };
+    seconds / 60.
+}
+
+impl Display for JobEstimate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:.0} mm cut, {:.0} mm between paths, about {} with the tool on for {}, over {:.1} x {:.1} mm, using {:.2} kWh, costing {:.2}",
+            self.cutting_distance,
+            self.rapid_distance,
+            HoursMinutesSeconds(self.duration),
+            HoursMinutesSeconds(self.tool_on_duration),
+            self.size[0],
+            self.size[1],
+            self.energy,
+            self.cost
+        )
+    }
+}
+
+/// Formats a duration as hours, minutes, and seconds, i.e. `1h 02m 03s`
+pub struct HoursMinutesSeconds(pub Duration);
+
+impl Display for HoursMinutesSeconds {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let seconds = self.0.as_secs_f64().round() as u64;
+        write!(
+            f,
+            "{}h {:02}m {:02}s",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use g_code::parse::snippet_parser;
+    use lyon_geom::point;
+
+    use super::*;
+    use crate::{
+        toolpaths2program, ConversionConfig, LayerOverrides, Machine, Toolpath, Toolpaths,
+    };
+
+    fn program(toolpaths: Vec<Toolpath>, config: &ConversionConfig) -> Vec<Token<'static>> {
+        let machine = Machine::new(Default::default(), None, None, None, None);
+        let toolpaths = Toolpaths {
+            toolpaths,
+            ..Default::default()
+        };
+        toolpaths2program(toolpaths, config, &LayerOverrides::new(), machine)
+    }
+
+    #[test]
+    fn estimate_includes_passes_and_moves_between_paths() {
+        let toolpaths = vec![
+            Toolpath::from_polyline(&[point(0., 0.), point(100., 0.)]).unwrap(),
+            Toolpath::from_polyline(&[point(100., 50.), point(0., 50.)]).unwrap(),
+        ];
+        let config = ConversionConfig {
+            passes: 2,
+            pass_feedrate: vec![200., 100.],
+            ..Default::default()
+        };
+        let cost = CostConfig {
+            rapid_feedrate: 1000.,
+            hourly_rate: 60.,
+            material_cost: 100.,
+            power: 500.,
+            ..Default::default()
+        };
+        let estimate =
+            JobEstimate::from_program(&program(toolpaths, &config), None, &cost).unwrap();
+
+        assert_eq!(estimate.cutting_distance, 400.);
+        // Across to the second path, then back to the start, then across again
+        assert_eq!(estimate.rapid_distance, 50. + 50. + 50.);
+        // 1 minute cutting on the first pass, 2 on the second, 0.15 moving between paths
+        assert!((estimate.duration.as_secs_f64() - 189.).abs() < 1E-6);
+        assert!((estimate.tool_on_duration.as_secs_f64() - 180.).abs() < 1E-6);
+        assert!((estimate.energy - 0.02625).abs() < 1E-9);
+        assert_eq!(estimate.size, [100., 50.]);
+        assert!((estimate.cost - (3.15 + 0.5)).abs() < 1E-9);
+        assert_eq!(
+            estimate.to_string(),
+            "400 mm cut, 150 mm between paths, about 0h 03m 09s with the tool on for 0h 03m 00s, over 100.0 x 50.0 mm, using 0.03 kWh, costing 3.65"
+        );
+
+        assert_eq!(
+            JobEstimate::from_program(&program(vec![], &config), None, &cost),
+            Ok(JobEstimate::default())
+        );
+    }
+
+    #[test]
+    fn acceleration_slows_down_short_moves() {
+        let toolpaths = vec![
+            Toolpath::from_polyline(&[point(0., 0.), point(100., 0.)]).unwrap(),
+            Toolpath::from_polyline(&[point(100., 1.), point(0., 1.)]).unwrap(),
+        ];
+        let config = ConversionConfig {
+            feedrate: 600.,
+            ..Default::default()
+        };
+        let cost = CostConfig {
+            rapid_feedrate: 600.,
+            acceleration: 100.,
+            ..Default::default()
+        };
+        let estimate =
+            JobEstimate::from_program(&program(toolpaths, &config), None, &cost).unwrap();
+
+        // 10 mm/s is reached after 0.5 mm on the cuts, adding 0.1 s to each, while the 1 mm move
+        // between them only reaches it as it slows down again
+        assert!((estimate.tool_on_duration.as_secs_f64() - 20.2).abs() < 1E-6);
+        assert!((estimate.duration.as_secs_f64() - 20.4).abs() < 1E-6);
+        assert_eq!(
+            estimate
+                .header()
+                .iter()
+                .map(|token| match token {
+                    Token::Comment { inner, .. } => inner.as_ref(),
+                    _ => unreachable!(),
+                })
+                .collect::<Vec<_>>(),
+            [
+                "Estimated time: 0h 00m 20s, with the tool on for 0h 00m 20s",
+                "Cut length: 200 mm",
+                "Travel length: 1 mm",
+            ]
+        );
+    }
+
+    #[test]
+    fn z_moves_and_dwells_take_time_and_feedrates_must_be_set() {
+        let parse = |program| {
+            snippet_parser(program)
+                .unwrap()
+                .iter_emit_tokens()
+                .collect::<Vec<_>>()
+        };
+        let cost = CostConfig::default();
+        let estimate = JobEstimate::from_program(
+            &parse("G0 X0 Y0\nG0 Z5\nG1 Z-1 F60\nG1 X10 F600\nG4 P1\nG0 Z5\n"),
+            None,
+            &cost,
+        )
+        .unwrap();
+        assert_eq!(estimate.cutting_distance, 16.);
+        assert_eq!(estimate.rapid_distance, 6.);
+        // 6 s plunging, 1 s cutting, and 1 s waiting with the tool on, then 0.12 s lifting it
+        assert!((estimate.tool_on_duration.as_secs_f64() - 8.).abs() < 1E-6);
+        assert!((estimate.duration.as_secs_f64() - 8.12).abs() < 1E-6);
+
+        assert_eq!(
+            JobEstimate::from_program(&parse("G0 X0 Y0\nG1 X1"), None, &cost),
+            Err(EstimateError::NoFeedrate)
+        );
+        assert_eq!(
+            JobEstimate::from_program(&parse("G0 X0 Y0\nG1 X1 F0"), None, &cost),
+            Err(EstimateError::InvalidFeedrate(0.))
+        );
+        let stopped = CostConfig {
+            rapid_feedrate: 0.,
+            ..Default::default()
+        };
+        assert_eq!(
+            JobEstimate::from_program(&parse("G0 X0 Y0"), None, &stopped),
+            Err(EstimateError::InvalidFeedrate(0.))
+        );
+    }
+}
@@ -0,0 +1,351 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use std::str::FromStr;
+
+use log::warn;
+use lyon_geom::{Angle, Box2D, Point};
+use svg2gcode_geometry::{concentric, hatch, self_intersections, spiral, stipple};
+use svgtypes::Color;
+
+use crate::{
+    halftone::{default_tool_width, Halftone, HalftonePattern},
+    SourceElement, Toolpath,
+};
+
+/// How the inside of filled shapes is drawn
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum FillPattern {
+    /// Parallel lines at [`Fill::angle`]
+    Lines,
+    /// Parallel lines at [`Fill::angle`], then again at a right angle to them
+    Crosshatch,
+    /// Rings that follow the outline inwards
+    Concentric,
+    /// A spiral from the middle of the shape outwards
+    Spiral,
+    /// Dots on a hexagonal grid, i.e. for pointillism with a pen or a dwelling laser
+    Stipple,
+    /// Dots or lines sized to reproduce the shade of gray of the fill color, see [`Halftone`]
+    Halftone(HalftonePattern),
+}
+
+/// Infill for shapes with a `fill`, drawn before their outline
+///
+/// Shapes are filled by the even-odd rule, so holes are left empty.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Fill {
+    #[cfg_attr(feature = "serde", serde(default = "default_pattern"))]
+    pub pattern: FillPattern,
+    /// Distance between lines, rings, turns, or dots in millimeters
+    ///
+    /// Nothing is filled unless this is above 0.
+    #[cfg_attr(feature = "serde", serde(default = "default_spacing"))]
+    pub spacing: f64,
+    /// Angle of lines in degrees counterclockwise from the x-axis
+    #[cfg_attr(feature = "serde", serde(default = "default_angle"))]
+    pub angle: f64,
+    /// Time to wait at each dot in seconds, for [`FillPattern::Stipple`]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub dwell: f64,
+    /// Width of the line the tool draws in millimeters, for [`FillPattern::Halftone`]
+    #[cfg_attr(feature = "serde", serde(default = "default_tool_width"))]
+    pub tool_width: f64,
+}
+
+const fn default_pattern() -> FillPattern {
+    FillPattern::Lines
+}
+
+const fn default_spacing() -> f64 {
+    1.
+}
+
+const fn default_angle() -> f64 {
+    45.
+}
+
+impl Default for Fill {
+    fn default() -> Self {
+        Self {
+            pattern: default_pattern(),
+            spacing: default_spacing(),
+            angle: default_angle(),
+            dwell: 0.,
+            tool_width: default_tool_width(),
+        }
+    }
+}
+
+impl Fill {
+    /// Add infill before the toolpaths of each filled element
+    ///
+    /// Toolpaths drawn from the same element must be next to each other, as they are after
+    /// conversion.
+    pub fn apply(&self, toolpaths: Vec<Toolpath>, tolerance: f64) -> Vec<Toolpath> {
+        if self.spacing <= 0. || self.spacing.is_nan() {
+            warn!(
+                "Shapes are not filled, since the fill spacing is {} mm instead of above 0",
+                self.spacing
+            );
+            return toolpaths;
+        }
+        let mut filled = Vec::with_capacity(toolpaths.len());
+        let mut toolpaths = toolpaths.into_iter().peekable();
+        while let Some(first) = toolpaths.next() {
+            let mut element = vec![first];
+            while let Some(next) =
+                toolpaths.next_if(|next| next.source.is_some() && next.source == element[0].source)
+            {
+                element.push(next);
+            }
+
+            if is_filled(&element[0]) {
+                let mut infill = self.infill(&element, tolerance);
+                if let Some(first) = infill.first_mut() {
+                    first.comments = std::mem::take(&mut element[0].comments);
+                    first.pause = std::mem::take(&mut element[0].pause);
+                    first.comments.push("fill".to_string());
+                }
+                filled.append(&mut infill);
+            }
+            filled.append(&mut element);
+        }
+        filled
+    }
+
+    fn infill(&self, element: &[Toolpath], tolerance: f64) -> Vec<Toolpath> {
+        // Subpaths are closed implicitly when filled, so only lines and points have no inside
+        let polygons = element
+            .iter()
+            .map(|toolpath| toolpath.flattened(tolerance))
+            .filter(|polygon| polygon.len() > 2)
+            .collect::<Vec<_>>();
+        report_self_intersections(element, &polygons);
+        let spacing = self.capped_spacing(&polygons);
+        let angle = Angle::degrees(self.angle);
+        match self.pattern {
+            FillPattern::Lines => hatch(&polygons, spacing, angle),
+            FillPattern::Crosshatch => {
+                let mut lines = hatch(&polygons, spacing, angle);
+                lines.append(&mut hatch(&polygons, spacing, angle + Angle::frac_pi_2()));
+                lines
+            }
+            FillPattern::Concentric => concentric(&polygons, spacing),
+            FillPattern::Spiral => spiral(&polygons, spacing, tolerance),
+            FillPattern::Stipple => stipple(&polygons, spacing),
+            FillPattern::Halftone(pattern) => {
+                let darkness = element[0].source.as_ref().map_or(1., darkness);
+                Halftone {
+                    pattern,
+                    spacing,
+                    angle: self.angle,
+                    tool_width: self.tool_width,
+                }
+                .toolpaths(&polygons, |_| darkness)
+            }
+        }
+        .into_iter()
+        .map(|mut toolpath| {
+            toolpath.source = element[0].source.clone();
+            toolpath
+        })
+        .collect()
+    }
+
+    /// The spacing, widened if it would fill the polygons with more than [`MAX_INFILL`] lines,
+    /// rings, turns, or dots
+    fn capped_spacing(&self, polygons: &[Vec<Point<f64>>]) -> f64 {
+        let size = Box2D::from_points(polygons.iter().flatten()).size();
+        let widest = match self.pattern {
+            // Stipples are packed hexagonally, closer than a square grid would
+            FillPattern::Stipple | FillPattern::Halftone(HalftonePattern::Dots) => {
+                (2. * size.area() / MAX_INFILL).sqrt()
+            }
+            _ => size.width.hypot(size.height) / MAX_INFILL,
+        };
+        if self.spacing >= widest {
+            return self.spacing;
+        }
+        warn!(
+            "The fill spacing of {} mm would take too long to draw, filling with {widest} mm instead",
+            self.spacing
+        );
+        widest
+    }
+}
+
+/// Most lines, rings, turns, or dots a single shape is filled with, since a tiny spacing would
+/// otherwise take practically forever to generate
+const MAX_INFILL: f64 = 100_000.;
+
+/// Most crossings listed when reporting a shape that crosses itself
+const MAX_REPORTED_CROSSINGS: usize = 5;
+
+/// Warn about outlines that cross themselves, since their fill won't match the SVG
+fn report_self_intersections(element: &[Toolpath], polygons: &[Vec<Point<f64>>]) {
+    let crossings = polygons
+        .iter()
+        .flat_map(|polygon| self_intersections(polygon))
+        .collect::<Vec<_>>();
+    if crossings.is_empty() {
+        return;
+    }
+    let name = element[0]
+        .source
+        .as_ref()
+        .map_or("A shape".to_string(), |source| match &source.id {
+            Some(id) => format!("<{} id=\"{id}\">", source.tag_name),
+            None => format!("A <{}>", source.tag_name),
+        });
+    let mut at = crossings
+        .iter()
+        .take(MAX_REPORTED_CROSSINGS)
+        .map(|p| format!("({:.2}, {:.2})", p.x, p.y))
+        .collect::<Vec<_>>()
+        .join(", ");
+    if crossings.len() > MAX_REPORTED_CROSSINGS {
+        at += &format!(" and {} more", crossings.len() - MAX_REPORTED_CROSSINGS);
+    }
+    warn!("{name} crosses itself at {at} mm, so its fill leaves the overlapping parts empty");
+}
+
+/// Whether the element a toolpath was drawn from has a fill, which is black unless set otherwise
+fn is_filled(toolpath: &Toolpath) -> bool {
+    toolpath
+        .source
+        .as_ref()
+        .is_some_and(|source| property(source, "fill") != Some("none"))
+}
+
+/// How dark the fill of an element is, from 0 for white or transparent to 1 for black
+fn darkness(source: &SourceElement) -> f64 {
+    let color = property(source, "fill")
+        .and_then(|fill| Color::from_str(fill).ok())
+        .unwrap_or_else(Color::black);
+    let opacity = ["fill-opacity", "opacity"]
+        .iter()
+        .filter_map(|name| property(source, name)?.parse::<f64>().ok())
+        .product::<f64>()
+        * color.alpha as f64
+        / 255.;
+    // https://en.wikipedia.org/wiki/Relative_luminance
+    let lightness =
+        (0.2126 * color.red as f64 + 0.7152 * color.green as f64 + 0.0722 * color.blue as f64)
+            / 255.;
+    (1. - lightness) * opacity.clamp(0., 1.)
+}
+
+/// A presentation attribute, which can be overridden by the element's own `style`
+pub(crate) fn property<'a>(source: &'a SourceElement, name: &str) -> Option<&'a str> {
+    let style = source.attribute("style").and_then(|style| {
+        style
+            .split(';')
+            .filter_map(|declaration| declaration.split_once(':'))
+            .find(|(property, _)| property.trim() == name)
+            .map(|(_, value)| value.trim())
+    });
+    style.or(source.attribute(name))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use lyon_geom::point;
+
+    fn square(fill: Option<&str>) -> Toolpath {
+        let mut toolpath = Toolpath::from_polyline(&[
+            point(0., 0.),
+            point(10., 0.),
+            point(10., 10.),
+            point(0., 10.),
+            point(0., 0.),
+        ])
+        .unwrap();
+        toolpath.comments.push("path".to_string());
+        toolpath.source = Some(SourceElement {
+            tag_name: "path".to_string(),
+            attributes: fill
+                .map(|fill| [("fill".to_string(), fill.to_string())].into())
+                .unwrap_or_default(),
+            ..Default::default()
+        });
+        toolpath
+    }
+
+    #[test]
+    fn fill_is_drawn_before_outline() {
+        let fill = Fill {
+            angle: 0.,
+            ..Default::default()
+        };
+        let toolpaths = fill.apply(vec![square(None)], 0.1);
+        assert_eq!(toolpaths.len(), 10 + 1);
+        assert_eq!(toolpaths[0].comments, ["path", "fill"]);
+        assert_eq!(toolpaths[0].from, point(0., 0.5));
+        assert!(toolpaths[10].comments.is_empty());
+        assert_eq!(toolpaths[10].to(), point(0., 0.));
+
+        assert_eq!(fill.apply(vec![square(Some("none"))], 0.1).len(), 1);
+        let unspaced = Fill {
+            spacing: 0.,
+            ..fill.clone()
+        };
+        assert_eq!(unspaced.apply(vec![square(None)], 0.1).len(), 1);
+        let crosshatch = Fill {
+            pattern: FillPattern::Crosshatch,
+            ..fill
+        };
+        assert_eq!(
+            crosshatch.apply(vec![square(Some("red"))], 0.1).len(),
+            20 + 1
+        );
+
+        // Lighter fills get smaller dots
+        let halftone = Fill {
+            pattern: FillPattern::Halftone(HalftonePattern::Dots),
+            ..fill
+        };
+        let black = halftone.apply(vec![square(None)], 0.1);
+        let gray = halftone.apply(vec![square(Some("#808080"))], 0.1);
+        assert_eq!(black.len(), gray.len());
+        assert!(black[0].length(0.01) > gray[0].length(0.01));
+        assert_eq!(halftone.apply(vec![square(Some("white"))], 0.1).len(), 1);
+    }
+
+    #[test]
+    fn tiny_spacing_is_widened() {
+        for pattern in [FillPattern::Lines, FillPattern::Stipple] {
+            let fill = Fill {
+                pattern,
+                spacing: 1e-9,
+                ..Default::default()
+            };
+            let toolpaths = fill.apply(vec![square(None)], 0.1);
+            assert!(toolpaths.len() > 1000 && toolpaths.len() <= MAX_INFILL as usize + 1);
+        }
+    }
+
+    #[test]
+    fn halftone_stays_inside_the_shape() {
+        for pattern in [HalftonePattern::Dots, HalftonePattern::Lines] {
+            let fill = Fill {
+                pattern: FillPattern::Halftone(pattern),
+                ..Default::default()
+            };
+            let toolpaths = fill.apply(vec![square(None)], 0.1);
+            assert!(toolpaths.len() > 1);
+            for p in toolpaths
+                .iter()
+                .flat_map(|toolpath| toolpath.flattened(0.01))
+            {
+                assert!(
+                    (-1e-9..=10. + 1e-9).contains(&p.x) && (-1e-9..=10. + 1e-9).contains(&p.y),
+                    "{pattern:?} reaches {p:?}, outside the square"
+                );
+            }
+        }
+    }
+}
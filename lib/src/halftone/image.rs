@@ -0,0 +1,276 @@
+use std::fmt::{self, Display, Write};
+
+use lyon_geom::point;
+
+use super::Halftone;
+use crate::Segment;
+
+impl Halftone {
+    /// Draw an image `width` millimeters wide as an SVG sized in millimeters, ready for engraving
+    pub fn image_to_svg(&self, image: &GrayImage, width: f64) -> String {
+        let pixel = width / image.width as f64;
+        let height = pixel * image.height as f64;
+        // Drawn in SVG coordinates, so the first row of pixels is at the top
+        let frame = [
+            point(0., 0.),
+            point(width, 0.),
+            point(width, height),
+            point(0., height),
+        ];
+        let toolpaths = self.toolpaths(&[frame.to_vec()], |p| {
+            let x = (p.x / pixel).floor();
+            let y = (p.y / pixel).floor();
+            if x < 0. || y < 0. {
+                return 0.;
+            }
+            image
+                .lightness(x as usize, y as usize)
+                .map_or(0., |lightness| 1. - lightness)
+        });
+
+        let mut svg = String::new();
+        let _ = writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}mm" height="{height}mm" viewBox="0 0 {width} {height}">"#,
+        );
+        svg += r#"<path fill="none" stroke="black" d=""#;
+        for toolpath in &toolpaths {
+            let _ = write!(svg, "M {} {}", toolpath.from.x, toolpath.from.y);
+            for segment in &toolpath.segments {
+                let _ = match segment {
+                    Segment::Arc(arc) => write!(
+                        svg,
+                        " A {} {} {} {} {} {} {}",
+                        arc.radii.x,
+                        arc.radii.y,
+                        arc.x_rotation.to_degrees(),
+                        arc.flags.large_arc as u8,
+                        arc.flags.sweep as u8,
+                        arc.to.x,
+                        arc.to.y
+                    ),
+                    // Halftones are only made of lines and arcs
+                    other => write!(svg, " L {} {}", other.to().x, other.to().y),
+                };
+            }
+            svg += "\n";
+        }
+        svg += "\"/>\n</svg>\n";
+        svg
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImageError {
+    /// Only PNG images, Netpbm graymaps (P2, P5), and Netpbm pixmaps (P3, P6) can be read
+    UnsupportedFormat,
+    /// The PNG image could not be decoded
+    InvalidPng(String),
+    /// The width, height, or maximum value is missing or not a positive number
+    InvalidHeader,
+    /// There are fewer pixels than the header says
+    Truncated,
+}
+
+impl Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnsupportedFormat => write!(f, "only PNG, PGM, and PPM images are supported"),
+            Self::InvalidPng(err) => write!(f, "invalid PNG image: {err}"),
+            Self::InvalidHeader => write!(f, "invalid image header"),
+            Self::Truncated => write!(f, "image is missing pixels"),
+        }
+    }
+}
+
+impl std::error::Error for ImageError {}
+
+/// A grayscale raster image
+#[derive(Debug, Clone, PartialEq)]
+pub struct GrayImage {
+    pub width: usize,
+    pub height: usize,
+    /// Lightness of each pixel from 0 for black to 1 for white, row by row from the top left
+    pub pixels: Vec<f64>,
+}
+
+impl GrayImage {
+    /// Read a PNG or [Netpbm](https://en.wikipedia.org/wiki/Netpbm) image by its format, converting
+    /// color to gray
+    pub fn decode(bytes: &[u8]) -> Result<Self, ImageError> {
+        if bytes.starts_with(PNG_SIGNATURE) {
+            Self::from_png(bytes)
+        } else {
+            Self::from_netpbm(bytes)
+        }
+    }
+
+    /// Read a PNG image, converting color to gray and drawing transparent pixels over white
+    pub fn from_png(bytes: &[u8]) -> Result<Self, ImageError> {
+        let invalid = |err: png::DecodingError| ImageError::InvalidPng(err.to_string());
+        let mut decoder = png::Decoder::new(bytes);
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info().map_err(invalid)?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let frame = reader.next_frame(&mut buffer).map_err(invalid)?;
+        let channels = frame.color_type.samples();
+        let pixels = buffer[..frame.buffer_size()]
+            .chunks_exact(frame.line_size)
+            .flat_map(|line| line.chunks_exact(channels).take(frame.width as usize))
+            .map(|pixel| {
+                let value = |i: usize| pixel[i] as f64 / 255.;
+                let (lightness, alpha) = match channels {
+                    1 => (value(0), 1.),
+                    2 => (value(0), value(1)),
+                    3 => (luminance([value(0), value(1), value(2)]), 1.),
+                    _ => (luminance([value(0), value(1), value(2)]), value(3)),
+                };
+                1. - alpha * (1. - lightness)
+            })
+            .collect();
+        Ok(Self {
+            width: frame.width as usize,
+            height: frame.height as usize,
+            pixels,
+        })
+    }
+
+    /// Read a [Netpbm](https://en.wikipedia.org/wiki/Netpbm) graymap or pixmap, which most image
+    /// editors can export, converting color to gray
+    pub fn from_netpbm(bytes: &[u8]) -> Result<Self, ImageError> {
+        let (channels, binary) = match bytes.get(..2) {
+            Some(b"P2") => (1, false),
+            Some(b"P5") => (1, true),
+            Some(b"P3") => (3, false),
+            Some(b"P6") => (3, true),
+            _ => return Err(ImageError::UnsupportedFormat),
+        };
+
+        let mut rest = &bytes[2..];
+        let mut header = [0; 3];
+        for value in &mut header {
+            *value = next_number(&mut rest)
+                .filter(|value| *value > 0)
+                .ok_or(ImageError::InvalidHeader)?;
+        }
+        let [width, height, max] = header;
+        // A header this large asks for more pixels than any image could have
+        let samples = width
+            .checked_mul(height)
+            .and_then(|pixels| pixels.checked_mul(channels))
+            .ok_or(ImageError::Truncated)?;
+        let values = if binary {
+            // A single whitespace character separates the header from the pixels
+            let data = rest.get(1..).unwrap_or_default();
+            if max < 256 {
+                data.iter().take(samples).map(|b| *b as usize).collect()
+            } else {
+                data.chunks_exact(2)
+                    .take(samples)
+                    .map(|pair| u16::from_be_bytes([pair[0], pair[1]]) as usize)
+                    .collect()
+            }
+        } else {
+            std::iter::from_fn(|| next_number(&mut rest))
+                .take(samples)
+                .collect::<Vec<_>>()
+        };
+        if values.len() < samples {
+            return Err(ImageError::Truncated);
+        }
+
+        let pixels = values
+            .chunks_exact(channels)
+            .map(|channels| {
+                luminance(
+                    match channels {
+                        [gray] => [*gray; 3],
+                        [red, green, blue] => [*red, *green, *blue],
+                        _ => unreachable!("images have one or three channels"),
+                    }
+                    .map(|value| value.min(max) as f64 / max as f64),
+                )
+            })
+            .collect();
+        Ok(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    /// Lightness of a pixel, or [`None`] if it is outside the image
+    pub fn lightness(&self, x: usize, y: usize) -> Option<f64> {
+        (x < self.width && y < self.height).then(|| self.pixels[y * self.width + x])
+    }
+}
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// <https://en.wikipedia.org/wiki/Relative_luminance>
+fn luminance([red, green, blue]: [f64; 3]) -> f64 {
+    0.2126 * red + 0.7152 * green + 0.0722 * blue
+}
+
+/// Parse the next whitespace-separated number, skipping comments
+fn next_number(bytes: &mut &[u8]) -> Option<usize> {
+    loop {
+        match bytes.first()? {
+            b'#' => {
+                let end = bytes
+                    .iter()
+                    .position(|b| *b == b'\n')
+                    .unwrap_or(bytes.len());
+                *bytes = &bytes[end..];
+            }
+            b if b.is_ascii_whitespace() => *bytes = &bytes[1..],
+            _ => break,
+        }
+    }
+    let end = bytes
+        .iter()
+        .position(|b| !b.is_ascii_digit())
+        .unwrap_or(bytes.len());
+    let number = std::str::from_utf8(&bytes[..end]).ok()?.parse().ok();
+    *bytes = &bytes[end..];
+    number
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn netpbm_images_are_read_as_gray() {
+        let ascii = GrayImage::from_netpbm(b"P2\n# a comment\n2 1\n255\n0 255\n").unwrap();
+        assert_eq!(ascii.pixels, [0., 1.]);
+        let binary = GrayImage::from_netpbm(b"P6 1 1 255\n\xff\x00\x00").unwrap();
+        assert!((binary.pixels[0] - 0.2126).abs() < 1e-9);
+        assert_eq!(
+            GrayImage::from_netpbm(b"P5 2 2 255\n\x00"),
+            Err(ImageError::Truncated)
+        );
+        assert_eq!(
+            GrayImage::from_netpbm(b"P6 4294967296 4294967296 255\n\x00"),
+            Err(ImageError::Truncated)
+        );
+        assert_eq!(
+            GrayImage::from_netpbm(b"\x89PNG"),
+            Err(ImageError::UnsupportedFormat)
+        );
+
+        // Only the black pixel on the left gets dots
+        let svg = Halftone {
+            angle: 0.,
+            ..Default::default()
+        }
+        .image_to_svg(&ascii, 4.);
+        let document = roxmltree::Document::parse(&svg).unwrap();
+        assert_eq!(document.root_element().attribute("width"), Some("4mm"));
+        let d = document
+            .descendants()
+            .find_map(|node| node.attribute("d"))
+            .unwrap();
+        assert_eq!(d.matches('M').count(), 2 * 2);
+    }
+}
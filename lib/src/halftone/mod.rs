@@ -0,0 +1,93 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use lyon_geom::{Angle, Point};
+use svg2gcode_geometry::{halftone_dots, halftone_lines};
+
+use crate::Toolpath;
+
+#[cfg(feature = "raster")]
+pub use self::image::{GrayImage, ImageError};
+
+/// Reads raster images to halftone
+#[cfg(feature = "raster")]
+mod image;
+
+/// Shapes that reproduce shades of gray
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum HalftonePattern {
+    /// Dots on a grid that grow with the darkness
+    Dots,
+    /// Parallel lines that widen with the darkness
+    Lines,
+}
+
+/// Reproduces shades of gray with toolpaths for photo-like engraving, without varying the tool
+/// power for each pixel
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Halftone {
+    #[cfg_attr(feature = "serde", serde(default = "default_pattern"))]
+    pub pattern: HalftonePattern,
+    /// Distance between dots or lines in millimeters, where each is as big or as wide as this
+    /// when black
+    #[cfg_attr(feature = "serde", serde(default = "default_spacing"))]
+    pub spacing: f64,
+    /// Angle of the grid of dots or lines in degrees counterclockwise from the x-axis
+    #[cfg_attr(feature = "serde", serde(default = "default_angle"))]
+    pub angle: f64,
+    /// Width of the line the tool draws in millimeters (i.e. pen tip or laser spot)
+    #[cfg_attr(feature = "serde", serde(default = "default_tool_width"))]
+    pub tool_width: f64,
+}
+
+const fn default_pattern() -> HalftonePattern {
+    HalftonePattern::Dots
+}
+
+const fn default_spacing() -> f64 {
+    1.
+}
+
+const fn default_angle() -> f64 {
+    45.
+}
+
+pub(crate) const fn default_tool_width() -> f64 {
+    0.2
+}
+
+impl Default for Halftone {
+    fn default() -> Self {
+        Self {
+            pattern: default_pattern(),
+            spacing: default_spacing(),
+            angle: default_angle(),
+            tool_width: default_tool_width(),
+        }
+    }
+}
+
+impl Halftone {
+    /// Toolpaths covering the `darkness` (0 for white to 1 for black) at each point inside
+    /// polygons by the even-odd rule
+    pub fn toolpaths(
+        &self,
+        polygons: &[Vec<Point<f64>>],
+        darkness: impl Fn(Point<f64>) -> f64,
+    ) -> Vec<Toolpath> {
+        if self.spacing <= 0. || self.tool_width <= 0. {
+            return vec![];
+        }
+        let angle = Angle::degrees(self.angle);
+        match self.pattern {
+            HalftonePattern::Dots => {
+                halftone_dots(polygons, self.spacing, angle, self.tool_width, darkness)
+            }
+            HalftonePattern::Lines => {
+                halftone_lines(polygons, self.spacing, angle, self.tool_width, darkness)
+            }
+        }
+    }
+}
@@ -0,0 +1,193 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use lyon_geom::Point;
+use svg2gcode_geometry::{contains_even_odd, offset};
+
+use crate::Toolpath;
+
+/// Which side of closed paths to cut on when compensating for [`crate::ConversionConfig::kerf_width`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum KerfSide {
+    /// Cut around the outside of shapes and the inside of holes in them, so parts come out the
+    /// size they are drawn
+    #[default]
+    Outside,
+    /// Cut along the inside of shapes and the outside of holes in them, so the openings left in
+    /// the sheet (i.e. in a stencil or an inlay pocket) are the size they are drawn
+    Inside,
+}
+
+/// Which way to go around closed paths when milling, see [`crate::ConversionConfig::cut_direction`]
+///
+/// For a spindle turning clockwise as seen from above (i.e. `M3`).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum CutDirection {
+    /// Go around each path the way it is drawn
+    #[default]
+    AsDrawn,
+    /// Keep the part on the right of the tool, so each tooth starts its cut at the thickest chip
+    /// and pushes it behind, for a cleaner edge on rigid machines
+    Climb,
+    /// Keep the part on the left of the tool, so the cut pulls away from it, for machines with
+    /// backlash that climb milling would make the tool grab and dig in
+    Conventional,
+}
+
+/// Reverse closed toolpaths that go around the wrong way for `direction`, leaving open ones as
+/// they are
+///
+/// The part is on the other side of each path from the one [`compensate_kerf`] moves it to:
+/// inside of shapes and outside of the holes [`find_holes`] found in them for
+/// [`KerfSide::Outside`].
+pub(crate) fn set_cut_direction(
+    toolpaths: Vec<Toolpath>,
+    direction: CutDirection,
+    side: KerfSide,
+    tolerance: f64,
+) -> Vec<Toolpath> {
+    if direction == CutDirection::AsDrawn {
+        return toolpaths;
+    }
+    toolpaths
+        .into_iter()
+        .map(|toolpath| {
+            if !toolpath.is_closed(tolerance) {
+                return toolpath;
+            }
+            let polygon = toolpath.flattened(tolerance);
+            let part_inside = toolpath.hole != (side == KerfSide::Outside);
+            // The inside of a clockwise path is on its right
+            let part_right = part_inside == (signed_area(&polygon) < 0.);
+            if part_right == (direction == CutDirection::Climb) {
+                toolpath
+            } else {
+                toolpath.reversed()
+            }
+        })
+        .collect()
+}
+
+/// Mark the closed toolpaths inside an odd number of other closed ones as holes, see
+/// [`Toolpath::hole`]
+pub(crate) fn find_holes(toolpaths: &mut [Toolpath], tolerance: f64) {
+    let polygons = toolpaths
+        .iter()
+        .map(|toolpath| {
+            toolpath
+                .is_closed(tolerance)
+                .then(|| toolpath.flattened(tolerance))
+        })
+        .collect::<Vec<_>>();
+    for (i, toolpath) in toolpaths.iter_mut().enumerate() {
+        toolpath.hole = polygons[i].as_ref().is_some_and(|polygon| {
+            let around = polygons
+                .iter()
+                .enumerate()
+                .filter_map(|(j, other)| other.as_ref().filter(|_| j != i))
+                .filter(|other| contains_even_odd(std::slice::from_ref(*other), polygon[0]))
+                .count();
+            around % 2 == 1
+        });
+    }
+}
+
+/// Move closed toolpaths by half the `width` of the cut to the `side` it is made on, leaving open
+/// ones as they are
+///
+/// Holes found by [`find_holes`] are moved the other way. Each toolpath keeps the direction it
+/// went around in, though it may be split into several where it is narrower than the cut, or left
+/// out if it is too small to cut at all.
+pub(crate) fn compensate_kerf(
+    toolpaths: Vec<Toolpath>,
+    width: f64,
+    side: KerfSide,
+    tolerance: f64,
+) -> Vec<Toolpath> {
+    toolpaths
+        .into_iter()
+        .flat_map(|toolpath| {
+            if !toolpath.is_closed(tolerance) {
+                return vec![toolpath];
+            }
+            let polygon = toolpath.flattened(tolerance);
+            let grow = toolpath.hole != (side == KerfSide::Outside);
+            let distance = if grow { width / 2. } else { -width / 2. };
+            let clockwise = signed_area(&polygon) < 0.;
+            let mut pieces = offset(std::slice::from_ref(&polygon), distance, tolerance)
+                .into_iter()
+                .map(|mut piece| {
+                    if (signed_area(&piece.flattened(tolerance)) < 0.) != clockwise {
+                        piece = piece.reversed();
+                    }
+                    piece.source = toolpath.source.clone();
+                    piece.hole = toolpath.hole;
+                    piece
+                })
+                .collect::<Vec<_>>();
+            if let Some(first) = pieces.first_mut() {
+                first.comments = toolpath.comments.clone();
+                first.pause = toolpath.pause;
+            }
+            pieces
+        })
+        .collect()
+}
+
+/// Twice the area of a polygon, which is negative if it goes around the other way
+pub(crate) fn signed_area(polygon: &[Point<f64>]) -> f64 {
+    polygon
+        .iter()
+        .zip(polygon.iter().cycle().skip(1))
+        .map(|(from, to)| from.to_vector().cross(to.to_vector()))
+        .sum()
+}
+
+#[cfg(test)]
+mod test {
+    use lyon_geom::point;
+
+    use super::*;
+
+    #[test]
+    fn climb_and_conventional_go_around_parts_and_holes_opposite_ways() {
+        let square = |min: f64, max: f64| {
+            Toolpath::from_polyline(&[
+                point(min, min),
+                point(max, min),
+                point(max, max),
+                point(min, max),
+                point(min, min),
+            ])
+            .unwrap()
+        };
+        // Both counterclockwise, the outer one a part and the inner one a hole in it
+        let mut toolpaths = vec![square(0., 10.), square(4., 6.)];
+        find_holes(&mut toolpaths, 0.01);
+        let clockwise = |direction, side| {
+            set_cut_direction(toolpaths.clone(), direction, side, 0.01)
+                .iter()
+                .map(|toolpath| signed_area(&toolpath.flattened(0.01)) < 0.)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            clockwise(CutDirection::AsDrawn, KerfSide::Outside),
+            [false, false]
+        );
+        assert_eq!(
+            clockwise(CutDirection::Climb, KerfSide::Outside),
+            [true, false]
+        );
+        assert_eq!(
+            clockwise(CutDirection::Conventional, KerfSide::Outside),
+            [false, true]
+        );
+        // Cutting inside, the tool is in the opening the outer one leaves
+        assert_eq!(
+            clockwise(CutDirection::Climb, KerfSide::Inside),
+            [false, true]
+        );
+    }
+}
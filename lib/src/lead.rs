@@ -0,0 +1,177 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use lyon_geom::{euclid::Angle, vector, ArcFlags, LineSegment, Point, SvgArc, Vector};
+use svg2gcode_geometry::Segment;
+
+use crate::{kerf::signed_area, KerfSide, Toolpath};
+
+/// How the tool is brought onto and off of closed paths, see [`crate::ConversionConfig::lead_in`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum LeadShape {
+    /// A straight line as long as the lead, coming in at 45° from beside the path so it starts off
+    /// of the line that is cut
+    #[default]
+    Line,
+    /// A quarter circle with the length of the lead as its radius, curving onto the path
+    Arc,
+}
+
+/// Extend closed toolpaths with a lead-in move onto where they start and a lead-out move off of
+/// where they end, each tangent to the path there, leaving open ones as they are
+///
+/// Leads are made on the waste side of the cut, which is the same side that
+/// [`crate::kerf::compensate_kerf`] moves paths to: outside of shapes and inside of holes in them
+/// (see [`Toolpath::hole`]) for [`KerfSide::Outside`], so the marks left where the tool starts and
+/// stops aren't on the part.
+pub(crate) fn add_leads(
+    toolpaths: Vec<Toolpath>,
+    lead_in: f64,
+    lead_out: f64,
+    shape: LeadShape,
+    side: KerfSide,
+    tolerance: f64,
+) -> Vec<Toolpath> {
+    toolpaths
+        .into_iter()
+        .map(|mut toolpath| {
+            if !toolpath.is_closed(tolerance) {
+                return toolpath;
+            }
+            let clockwise = signed_area(&toolpath.flattened(tolerance)) < 0.;
+            // Leads from a corner would run into the part along the edge before it
+            if let Some(Segment::Line(line)) = toolpath.segments.first().cloned() {
+                let middle = line.sample(0.5);
+                toolpath.from = middle;
+                toolpath.segments[0] = Segment::Line(LineSegment {
+                    from: middle,
+                    ..line
+                });
+                toolpath
+                    .segments
+                    .push(Segment::Line(LineSegment { to: middle, ..line }));
+            }
+            let (Some((start, start_tangent)), Some((end, end_tangent))) = (
+                toolpath.point_at_length(0., tolerance),
+                toolpath.reversed().point_at_length(0., tolerance),
+            ) else {
+                return toolpath;
+            };
+            let waste_outside = toolpath.hole != (side == KerfSide::Outside);
+            // The inside of a counterclockwise path is on its left
+            let waste_left = waste_outside == clockwise;
+            if lead_in > 0. {
+                let lead = lead(start, start_tangent, lead_in, shape, waste_left, true);
+                toolpath.from = lead.from();
+                toolpath.segments.insert(0, lead);
+            }
+            if lead_out > 0. {
+                let lead = lead(end, -end_tangent, lead_out, shape, waste_left, false);
+                toolpath.segments.push(lead);
+            }
+            toolpath
+        })
+        .collect()
+}
+
+/// A move of `length` onto (`onto`) or off of `at`, where the path goes in the unit `direction`
+fn lead(
+    at: Point<f64>,
+    direction: Vector<f64>,
+    length: f64,
+    shape: LeadShape,
+    left: bool,
+    onto: bool,
+) -> Segment {
+    let ends = |far: Point<f64>| if onto { (far, at) } else { (at, far) };
+    let normal = if left {
+        vector(-direction.y, direction.x)
+    } else {
+        vector(direction.y, -direction.x)
+    };
+    let along = if onto { -direction } else { direction };
+    match shape {
+        LeadShape::Line => {
+            let (from, to) = ends(at + (normal + along).normalize() * length);
+            Segment::Line(LineSegment { from, to })
+        }
+        LeadShape::Arc => {
+            let (from, to) = ends(at + (normal + along) * length);
+            Segment::Arc(SvgArc {
+                from,
+                to,
+                radii: vector(length, length),
+                x_rotation: Angle::zero(),
+                flags: ArcFlags {
+                    large_arc: false,
+                    sweep: left,
+                },
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use lyon_geom::point;
+
+    use super::*;
+    use crate::kerf::find_holes;
+
+    #[test]
+    fn leads_are_tangent_and_on_the_waste_side() {
+        let square = |min: f64, max: f64| {
+            Toolpath::from_polyline(&[
+                point(min, min),
+                point(max, min),
+                point(max, max),
+                point(min, max),
+                point(min, min),
+            ])
+            .unwrap()
+        };
+        let mut toolpaths = vec![square(0., 10.), square(4., 6.).reversed()];
+        find_holes(&mut toolpaths, 0.01);
+        let toolpaths = add_leads(toolpaths, 1., 1., LeadShape::Arc, KerfSide::Outside, 0.01);
+        let outline = &toolpaths[0];
+        assert_eq!(outline.from, point(4., -1.));
+        assert_eq!(outline.to(), point(6., -1.));
+        let leads = [&outline.segments[0], outline.segments.last().unwrap()];
+        for point in leads.iter().flat_map(|lead| lead.flattened(0.01)) {
+            assert!(((point - lyon_geom::point(5., -1.)).length() - 1.).abs() < 1e-6);
+            assert!(point.y <= 1e-9);
+        }
+        let hole = &toolpaths[1];
+        assert_eq!(hole.from, point(5., 4.));
+        for point in hole.flattened(0.01) {
+            assert!((4. ..=6.).contains(&point.x) && (4. ..=6.).contains(&point.y));
+        }
+    }
+
+    #[test]
+    fn straight_leads_come_in_from_the_waste_side() {
+        let square = Toolpath::from_polyline(&[
+            point(0., 0.),
+            point(10., 0.),
+            point(10., 10.),
+            point(0., 10.),
+            point(0., 0.),
+        ])
+        .unwrap();
+        let toolpaths = add_leads(
+            vec![square],
+            2.,
+            2.,
+            LeadShape::Line,
+            KerfSide::Outside,
+            0.01,
+        );
+        let outline = &toolpaths[0];
+        // Off of the edge it starts halfway along, which is cut from (5, 0) to (10, 0)
+        let offset = 2. * std::f64::consts::FRAC_1_SQRT_2;
+        assert!((outline.from - point(5. - offset, -offset)).length() < 1e-9);
+        assert!((outline.to() - point(5. + offset, -offset)).length() < 1e-9);
+        assert_eq!(outline.segments[0].to(), point(5., 0.));
+    }
+}
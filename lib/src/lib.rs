@@ -1,19 +1,110 @@
-/// Approximate [Bézier curves](https://en.wikipedia.org/wiki/B%C3%A9zier_curve) with [Circular arcs](https://en.wikipedia.org/wiki/Circular_arc)
-mod arc;
+/// Generates barcodes and QR codes as SVGs for engraving
+#[cfg(feature = "barcode")]
+mod barcode;
+/// Lines up output with the material and corrects for machine inaccuracies
+mod calibration;
 /// Converts an SVG to an internal representation
 mod converter;
+/// Sets up machines for the firmware they run
+mod dialect;
+/// Estimates how long a job takes and what it costs
+mod estimate;
+/// Draws the inside of filled shapes
+mod fill;
+/// Reproduces shades of gray with dots and lines
+mod halftone;
+/// Makes up for the width of the cut by moving closed paths to one side of it
+mod kerf;
+/// Brings the tool onto and off of closed paths beside them, away from the part
+mod lead;
+/// Finds likely mistakes in user-provided g-code sequences
+mod lint;
 /// Emulates the state of an arbitrary machine that can run G-Code
 mod machine;
+/// Describes the programs written for a job, for job management systems
+mod manifest;
+/// Draws registration marks around the artwork for lining up material
+mod marks;
+/// The stock being cut and the depths of cuts into it
+mod material;
+/// Generates test patterns for dialing in the settings for a material
+mod material_test;
+/// Substitutes rows of data into SVG templates for batch jobs
+mod merge;
+/// Ramps and spirals the tool down into the material
+mod plunge;
 /// Operations that are easier to implement while/after G-Code is generated, or would
 /// otherwise over-complicate SVG conversion
 mod postprocess;
+/// Sets the tool power of each path from how it is painted
+mod power;
+/// Settings for common machines
+mod preset;
+/// Follows programs like a machine would, for drawing and estimating them
+mod program;
+/// Engraves images line by line, setting the tool power for each pixel
+mod raster;
+/// Draws programs back as SVGs, for checking them without a machine
+mod render;
+/// Runs user scripts on each toolpath
+#[cfg(feature = "script")]
+mod script;
+/// Names files for the FAT file systems of SD cards in controllers
+mod short_name;
+/// Cutting tools and the passes they take to cut through material
+mod tool;
+/// Passes that transform toolpaths before they are emitted as G-Code
+mod toolpath;
 /// Provides an interface for drawing lines in G-Code
 /// This concept is referred to as [Turtle graphics](https://en.wikipedia.org/wiki/Turtle_graphics).
 mod turtle;
 
-pub use converter::{svg2program, ConversionConfig, ConversionOptions};
-pub use machine::{Machine, MachineConfig, SupportedFunctionality};
-pub use postprocess::PostprocessConfig;
+#[cfg(feature = "barcode")]
+pub use barcode::{Barcode, BarcodeError, BarcodeFill, QrErrorCorrection, Symbology};
+pub use calibration::{parse_position_report, Alignment, Calibration};
+pub use converter::{
+    parse_layer_sequence, place, svg2cut_order, svg2estimate, svg2program, svg2program_with_passes,
+    svg2toolpaths, toolpaths2frame_jogs, toolpaths2perimeter_check, toolpaths2program,
+    ConversionConfig, ConversionOptions, LayerOverride, LayerOverrides, LayerSequenceError,
+    OrderImprovements, OriginMode, PaintFilter, PathOrdering, StrokeFont, StrokeGlyph,
+    StrokeOutline, Toolpaths, UNSUPPORTED,
+};
+#[cfg(feature = "text")]
+pub use converter::{FontFace, FontLibrary};
+pub use dialect::{Dialect, ModalGroup};
+pub use estimate::{CostConfig, EstimateError, HoursMinutesSeconds, JobEstimate};
+pub use fill::{Fill, FillPattern};
+#[cfg(feature = "raster")]
+pub use halftone::{GrayImage, ImageError};
+pub use halftone::{Halftone, HalftonePattern};
+pub use kerf::{CutDirection, KerfSide};
+pub use lead::LeadShape;
+pub use lint::{lint_sequences, SequenceWarning};
+pub use machine::{
+    unexpected_mode, Hooks, Machine, MachineConfig, SnippetTemplate, SupportedFunctionality,
+    UnexpectedMode,
+};
+pub use manifest::{Manifest, ManifestEntry, ManifestSource};
+pub use marks::{MarkPosition, MarkShape, RegistrationMarks};
+pub use material::Material;
+pub use material_test::{CalibrationPattern, CalibrationTest, FocusTest, MaterialTest, TestShape};
+pub use merge::{MergeData, MergeError};
+pub use plunge::Plunge;
+pub use postprocess::{write_gcode, LineTooLong, PostprocessConfig};
+pub use power::{PowerSource, StrokePower};
+pub use preset::Preset;
+pub use program::{follow_program, ProgramMove, ProgramStep};
+#[cfg(feature = "raster")]
+pub use raster::RasterImage;
+pub use raster::{Dithering, RasterConfig};
+pub use render::program2svg;
+#[cfg(feature = "script")]
+pub use script::{Script, ScriptError};
+pub use short_name::ShortNames;
+pub use tool::{Tool, ToolKind};
+pub use toolpath::{
+    bounding_box, tile, PassRegistry, Region, Segment, SourceElement, Tile, Toolpath, ToolpathPass,
+};
 pub use turtle::Turtle;
 
 /// A cross-platform type used to store all configuration types.
@@ -23,6 +114,8 @@ pub struct Settings {
     pub conversion: ConversionConfig,
     pub machine: MachineConfig,
     pub postprocess: PostprocessConfig,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub cost: CostConfig,
     #[cfg_attr(feature = "serde", serde(default = "Version::unknown"))]
     pub version: Version,
 }
@@ -112,7 +205,10 @@ mod test {
         dimensions: [Option<Length>; 2],
     ) -> Vec<Token<'_>> {
         let config = ConversionConfig::default();
-        let options = ConversionOptions { dimensions };
+        let options = ConversionOptions {
+            dimensions,
+            ..Default::default()
+        };
         let document = roxmltree::Document::parse_with_options(
             input,
             ParsingOptions {
@@ -125,6 +221,7 @@ mod test {
         let machine = Machine::new(
             SupportedFunctionality {
                 circular_interpolation,
+                ..Default::default()
             },
             None,
             None,
@@ -290,6 +387,316 @@ mod test {
         assert_close(actual, expected)
     }
 
+    /// Slack for the metrics, which differ slightly between debug and release builds
+    const METRICS_TOLERANCE: f64 = 1E-6;
+
+    /// SVGs that path ordering is measured on, by their name in `tests/metrics.json`
+    const METRICS_CORPUS: [(&str, &str); 1] = [(
+        "scattered_strokes.svg",
+        include_str!("../tests/scattered_strokes.svg"),
+    )];
+
+    /// Ordering paths must never travel further or take longer than it did when the metrics in
+    /// `tests/metrics.json` were recorded, nor than not ordering them at all
+    ///
+    /// Run with `RECORD_METRICS=1` to record them again once an improvement is made.
+    #[test]
+    fn ordering_does_not_regress_from_recorded_metrics() {
+        let recorded: serde_json::Value =
+            serde_json::from_str(include_str!("../tests/metrics.json")).unwrap();
+        let record = std::env::var_os("RECORD_METRICS").is_some();
+        let mut metrics = serde_json::Map::new();
+        for (name, svg) in METRICS_CORPUS {
+            let document = roxmltree::Document::parse(svg).unwrap();
+            let measure = |path_ordering| {
+                // Long enough for the improvements to run until none help, so they are the same
+                // on any machine
+                let config = ConversionConfig {
+                    order_time_budget: 60.,
+                    ..Default::default()
+                };
+                let options = ConversionOptions {
+                    path_ordering,
+                    ..Default::default()
+                };
+                let machine = Machine::new(Default::default(), None, None, None, None);
+                let program = svg2program(&document, &config, options, machine);
+                let estimate =
+                    JobEstimate::from_program(&program, None, &CostConfig::default()).unwrap();
+                (estimate.rapid_distance, estimate.duration.as_secs_f64())
+            };
+            let (travel, time) = measure(PathOrdering::Optimized);
+            let (nearest_travel, _) = measure(PathOrdering::NearestNeighbor);
+            let (unordered_travel, unordered_time) = measure(PathOrdering::Document);
+            assert!(
+                travel <= nearest_travel,
+                "Optimizing the order of {name} travels {travel:.3} mm, more than the {nearest_travel:.3} mm of nearest first"
+            );
+            assert!(
+                travel <= unordered_travel && time <= unordered_time,
+                "Ordering {name} travels {travel:.3} mm in {time:.3} s, more than the {unordered_travel:.3} mm in {unordered_time:.3} s without it"
+            );
+            if record {
+                metrics.insert(
+                    name.to_string(),
+                    serde_json::json!({ "travel": travel, "time": time }),
+                );
+                continue;
+            }
+            let [recorded_travel, recorded_time] = ["travel", "time"].map(|metric| {
+                recorded[name][metric].as_f64().unwrap_or_else(|| {
+                    panic!("No {metric} is recorded for {name}, run with RECORD_METRICS=1")
+                })
+            });
+            assert!(
+                travel <= recorded_travel + METRICS_TOLERANCE,
+                "Ordering {name} travels {travel:.3} mm, more than the {recorded_travel:.3} mm recorded"
+            );
+            assert!(
+                time <= recorded_time + METRICS_TOLERANCE,
+                "Ordering {name} takes {time:.3} s, longer than the {recorded_time:.3} s recorded"
+            );
+        }
+        if record {
+            std::fs::write(
+                concat!(env!("CARGO_MANIFEST_DIR"), "/tests/metrics.json"),
+                serde_json::to_string_pretty(&metrics).unwrap() + "\n",
+            )
+            .unwrap();
+        }
+    }
+
+    /// Millimeters in a pixel at the default DPI
+    const MM_PER_PX: f32 = 25.4 / 96.;
+    /// Pixels in a millimeter of the renders that are compared
+    const RENDER_SCALE: f32 = 8.;
+    /// Width in millimeters that the paths of both renders are drawn with
+    const RENDER_STROKE_WIDTH: f32 = 1.;
+    /// Least overlap, as intersection over union, of the pixels drawn in both renders
+    const MIN_RENDER_IOU: f64 = 0.97;
+
+    /// SVGs that conversion is compared against resvg on, which only use what both support
+    const RENDER_CORPUS: [(&str, &str); 2] = [
+        ("curves.svg", include_str!("../tests/curves.svg")),
+        (
+            "scattered_strokes.svg",
+            include_str!("../tests/scattered_strokes.svg"),
+        ),
+    ];
+
+    /// Paths of an SVG as resvg parses them, in millimeters with the y-axis pointing up
+    ///
+    /// The transforms of groups are combined here, as the `abs_transform` of paths in a transformed
+    /// `<use>` has its transform twice in this version of resvg.
+    fn resvg_paths(svg: &str) -> Vec<resvg::tiny_skia::Path> {
+        use resvg::{tiny_skia::Transform, usvg::Node};
+
+        fn visit(
+            group: &resvg::usvg::Group,
+            parent: Transform,
+            paths: &mut Vec<resvg::tiny_skia::Path>,
+        ) {
+            let transform = parent.pre_concat(group.transform());
+            for node in group.children() {
+                match node {
+                    Node::Group(group) => visit(group, transform, paths),
+                    Node::Path(path) if path.is_visible() => paths.extend(
+                        path.data()
+                            .clone()
+                            .transform(transform.post_scale(MM_PER_PX, -MM_PER_PX)),
+                    ),
+                    _ => {}
+                }
+            }
+        }
+        let tree = resvg::usvg::Tree::from_str(svg, &Default::default()).unwrap();
+        let mut paths = vec![];
+        visit(tree.root(), Transform::identity(), &mut paths);
+        paths
+    }
+
+    /// Cutting moves of a program as a path, with arcs split into lines about a pixel long
+    fn gcode_path(program: &[Token<'_>]) -> Option<resvg::tiny_skia::Path> {
+        let mut commands: Vec<Vec<(&str, f32)>> = vec![];
+        for token in program {
+            if let Token::Field(field) = token {
+                if field.letters == "G" || field.letters == "M" || commands.is_empty() {
+                    commands.push(vec![]);
+                }
+                let value = field.value.as_f64().unwrap_or_default() as f32;
+                commands.last_mut().unwrap().push((&field.letters, value));
+            }
+        }
+
+        let mut builder = resvg::tiny_skia::PathBuilder::new();
+        let mut at = [0f32; 2];
+        let mut motion = 0;
+        for command in commands {
+            let mut to = at;
+            let mut center = None;
+            let mut radius = None;
+            for (letters, value) in command {
+                match letters {
+                    "G" if value <= 3. => motion = value as u8,
+                    "X" => to[0] = value,
+                    "Y" => to[1] = value,
+                    "I" => center.get_or_insert(at)[0] += value,
+                    "J" => center.get_or_insert(at)[1] += value,
+                    "R" => radius = Some(value),
+                    _ => {}
+                }
+            }
+            // Arcs given by radius are centered to the left of the line to where they end when
+            // counterclockwise, and the other way for the larger of the two with a negative radius
+            if let (None, Some(radius)) = (center, radius) {
+                let chord = [to[0] - at[0], to[1] - at[1]];
+                let half = chord[0].hypot(chord[1]) / 2.;
+                let offset = (radius * radius - half * half).max(0.).sqrt()
+                    / half.max(f32::EPSILON)
+                    * radius.signum()
+                    * if motion == 3 { 1. } else { -1. };
+                center = Some([
+                    at[0] + chord[0] / 2. - chord[1] * offset / 2.,
+                    at[1] + chord[1] / 2. + chord[0] * offset / 2.,
+                ]);
+            }
+            match (motion, center) {
+                (0, _) if to != at => builder.move_to(to[0], to[1]),
+                (1, _) if to != at => builder.line_to(to[0], to[1]),
+                (2 | 3, Some(center)) => {
+                    let angle = |p: [f32; 2]| (p[1] - center[1]).atan2(p[0] - center[0]);
+                    let radius = (at[0] - center[0]).hypot(at[1] - center[1]);
+                    let mut sweep = angle(to) - angle(at);
+                    if motion == 3 && sweep <= 0. {
+                        sweep += std::f32::consts::TAU;
+                    } else if motion == 2 && sweep >= 0. {
+                        sweep -= std::f32::consts::TAU;
+                    }
+                    let steps = (sweep.abs() * radius * RENDER_SCALE).ceil().max(1.) as usize;
+                    for step in 1..steps {
+                        let (sin, cos) = (angle(at) + sweep * step as f32 / steps as f32).sin_cos();
+                        builder.line_to(center[0] + radius * cos, center[1] + radius * sin);
+                    }
+                    builder.line_to(to[0], to[1]);
+                }
+                _ => {}
+            }
+            at = to;
+        }
+        builder.finish()
+    }
+
+    /// Draws both sets of paths on the same canvas, each with the bottom left of its bounding box
+    /// in the same place, so they line up wherever the artwork is placed on the machine
+    fn render(paths: [&[resvg::tiny_skia::Path]; 2]) -> [resvg::tiny_skia::Pixmap; 2] {
+        use resvg::tiny_skia::{LineCap, LineJoin, Paint, Pixmap, Stroke, Transform};
+
+        let bounds = paths.map(|paths| {
+            paths
+                .iter()
+                .filter_map(|path| path.compute_tight_bounds())
+                .fold(
+                    [
+                        f32::INFINITY,
+                        f32::INFINITY,
+                        f32::NEG_INFINITY,
+                        f32::NEG_INFINITY,
+                    ],
+                    |[left, top, right, bottom], bounds| {
+                        [
+                            left.min(bounds.left()),
+                            top.min(bounds.top()),
+                            right.max(bounds.right()),
+                            bottom.max(bounds.bottom()),
+                        ]
+                    },
+                )
+        });
+        let margin = RENDER_STROKE_WIDTH * RENDER_SCALE;
+        let [width, height] = [0, 1].map(|axis| {
+            let size = bounds
+                .iter()
+                .map(|bounds| bounds[axis + 2] - bounds[axis])
+                .fold(0f32, f32::max);
+            (size * RENDER_SCALE + 2. * margin).ceil() as u32
+        });
+        let paint = Paint {
+            anti_alias: false,
+            ..Default::default()
+        };
+        // Round, so closing a path draws the same as drawing a line back to where it started
+        let stroke = Stroke {
+            width: RENDER_STROKE_WIDTH,
+            line_cap: LineCap::Round,
+            line_join: LineJoin::Round,
+            ..Default::default()
+        };
+        [0, 1].map(|i| {
+            let mut pixmap = Pixmap::new(width, height).unwrap();
+            let transform = Transform::from_row(
+                RENDER_SCALE,
+                0.,
+                0.,
+                RENDER_SCALE,
+                margin - bounds[i][0] * RENDER_SCALE,
+                margin - bounds[i][1] * RENDER_SCALE,
+            );
+            for path in paths[i] {
+                pixmap.stroke_path(path, &paint, &stroke, transform, None);
+            }
+            pixmap
+        })
+    }
+
+    /// What the program for each SVG cuts must cover the same pixels as resvg renders of it, as an
+    /// end-to-end check on parsing, transforms, and flattening
+    ///
+    /// Renders that differ are saved next to each other in the temporary directory.
+    #[test]
+    fn conversion_draws_the_same_shapes_as_resvg() {
+        for (name, svg) in RENDER_CORPUS {
+            let expected = resvg_paths(svg);
+            for circular_interpolation in [false, true] {
+                let actual = gcode_path(&get_actual(svg, circular_interpolation, [None; 2]))
+                    .into_iter()
+                    .collect::<Vec<_>>();
+                let renders = render([&expected, &actual]);
+                let [expected_pixels, actual_pixels] = renders.each_ref().map(|pixmap| {
+                    pixmap
+                        .pixels()
+                        .iter()
+                        .map(|pixel| pixel.alpha() > 0)
+                        .collect::<Vec<_>>()
+                });
+                let (intersection, union) = expected_pixels.iter().zip(&actual_pixels).fold(
+                    (0, 0),
+                    |(intersection, union), (expected, actual)| {
+                        (
+                            intersection + usize::from(*expected && *actual),
+                            union + usize::from(*expected || *actual),
+                        )
+                    },
+                );
+                let iou = intersection as f64 / union.max(1) as f64;
+                if iou < MIN_RENDER_IOU {
+                    for (render, which) in renders.iter().zip(["resvg", "svg2gcode"]) {
+                        let path = std::env::temp_dir().join(format!(
+                            "{name}.{which}{}.png",
+                            if circular_interpolation { ".arcs" } else { "" }
+                        ));
+                        render.save_png(&path).unwrap();
+                    }
+                }
+                assert!(
+                    iou >= MIN_RENDER_IOU,
+                    "{name} converted with circular interpolation {circular_interpolation} only overlaps {:.1}% of the resvg render, see {}",
+                    iou * 100.,
+                    std::env::temp_dir().display()
+                );
+            }
+        }
+    }
+
     #[test]
     #[cfg(feature = "serde")]
     fn deserialize_v1_config_succeeds() {
@@ -0,0 +1,208 @@
+use std::fmt::{self, Display};
+
+use std::io;
+
+use g_code::{
+    emit::{FormatOptions, Token},
+    parse::snippet_parser,
+};
+
+use crate::{write_gcode, MachineConfig, ModalGroup, SnippetTemplate};
+
+/// A likely mistake in the sequences of a [`MachineConfig`], which still produces a program
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SequenceWarning {
+    /// A sequence starts the spindle or laser (M3/M4), but none stops it (M5)
+    ToolNeverStopped,
+    /// A sequence other than the end sequence ends the program (M2/M30)
+    EndsProgramEarly(&'static str),
+    /// A sequence draws arcs (G2/G3), but the machine is set up without circular interpolation
+    UnsupportedArc(&'static str),
+    /// A sequence has a line longer than [`MachineConfig::max_line_length`], even once split up
+    LineTooLong(&'static str),
+    /// A sequence has a comment in parentheses, which the [`MachineConfig::dialect`] doesn't
+    /// support
+    ParenthesizedComment(&'static str),
+    /// A sequence has a command of a modal group the [`MachineConfig::dialect`] doesn't support
+    UnsupportedModalGroup(&'static str, ModalGroup),
+}
+
+impl Display for SequenceWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ToolNeverStopped => write!(
+                f,
+                "the tool is started with M3 or M4 but never stopped, add M5 to the tool off or end sequence"
+            ),
+            Self::EndsProgramEarly(name) => {
+                write!(f, "{name} ends the program with M2 or M30 before it is done")
+            }
+            Self::UnsupportedArc(name) => write!(
+                f,
+                "{name} uses G2 or G3, but circular interpolation is not enabled for this machine"
+            ),
+            Self::LineTooLong(name) => {
+                write!(f, "{name} has a line too long for this machine, even once split up")
+            }
+            Self::ParenthesizedComment(name) => write!(
+                f,
+                "{name} has a comment in parentheses, which this firmware reads as g-code, use ; instead"
+            ),
+            Self::UnsupportedModalGroup(name, group) => {
+                write!(f, "{name} uses {group}, which this firmware doesn't support")
+            }
+        }
+    }
+}
+
+/// Look for likely mistakes in the sequences of a machine, skipping any that don't parse
+pub fn lint_sequences(config: &MachineConfig) -> Vec<SequenceWarning> {
+    let sequences = [
+        ("tool_on_sequence", &config.tool_on_sequence, false),
+        ("tool_off_sequence", &config.tool_off_sequence, false),
+        ("begin_sequence", &config.begin_sequence, false),
+        ("end_sequence", &config.end_sequence, false),
+        ("pre_home_sequence", &config.pre_home_sequence, true),
+        ("home_sequence", &config.home_sequence, true),
+        ("post_home_sequence", &config.post_home_sequence, true),
+        ("pass_begin_sequence", &config.pass_begin_sequence, true),
+        ("pass_end_sequence", &config.pass_end_sequence, true),
+        ("path_begin_sequence", &config.path_begin_sequence, true),
+        ("path_end_sequence", &config.path_end_sequence, true),
+    ]
+    .into_iter()
+    .filter_map(|(name, source, template)| {
+        let source = source.as_deref()?;
+        let tokens = if template {
+            SnippetTemplate::parse(source).ok()?.zeroed()
+        } else {
+            snippet_parser(source).ok()?.iter_emit_tokens().collect()
+        };
+        // Placeholders are zeroed, so a line can still end up too long once they are filled in
+        let too_long = config.max_line_length > 0
+            && write_gcode(
+                &tokens,
+                &FormatOptions::default(),
+                None,
+                config.max_line_length,
+                false,
+                io::sink(),
+            )
+            .is_err();
+        let words = tokens
+            .iter()
+            .filter_map(|token| match token {
+                Token::Field(field) => Some((
+                    field.letters.to_ascii_uppercase(),
+                    field.value.as_f64().unwrap_or(f64::NAN),
+                )),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let parenthesized = tokens.iter().any(|token| {
+            matches!(
+                token,
+                Token::Comment {
+                    is_inline: true,
+                    ..
+                }
+            )
+        });
+        Some((name, words, too_long, parenthesized))
+    })
+    .collect::<Vec<_>>();
+    let uses = |words: &[(String, f64)], letter: &str, numbers: &[f64]| {
+        words
+            .iter()
+            .any(|(l, number)| l == letter && numbers.contains(number))
+    };
+
+    let mut warnings = vec![];
+    if sequences
+        .iter()
+        .any(|(_, words, ..)| uses(words, "M", &[3., 4.]))
+        && !sequences
+            .iter()
+            .any(|(_, words, ..)| uses(words, "M", &[5.]))
+    {
+        warnings.push(SequenceWarning::ToolNeverStopped);
+    }
+    let parenthesized_comments = config
+        .dialect
+        .is_none_or(|dialect| dialect.parenthesized_comments());
+    for (name, words, too_long, parenthesized) in &sequences {
+        if *name != "end_sequence" && uses(words, "M", &[2., 30.]) {
+            warnings.push(SequenceWarning::EndsProgramEarly(name));
+        }
+        if !config.supported_functionality.circular_interpolation && uses(words, "G", &[2., 3.]) {
+            warnings.push(SequenceWarning::UnsupportedArc(name));
+        }
+        if *too_long {
+            warnings.push(SequenceWarning::LineTooLong(name));
+        }
+        if *parenthesized && !parenthesized_comments {
+            warnings.push(SequenceWarning::ParenthesizedComment(name));
+        }
+        if let Some(dialect) = config.dialect {
+            let mut groups = words
+                .iter()
+                .filter_map(|(letter, number)| ModalGroup::of(letter, *number))
+                .filter(|group| !dialect.supports(*group))
+                .collect::<Vec<_>>();
+            groups.dedup();
+            warnings.extend(
+                groups
+                    .into_iter()
+                    .map(|group| SequenceWarning::UnsupportedModalGroup(name, group)),
+            );
+        }
+    }
+    warnings
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Dialect;
+
+    #[test]
+    fn common_sequence_mistakes_are_found() {
+        let config = MachineConfig {
+            tool_on_sequence: Some("M3 S1000".to_string()),
+            tool_off_sequence: Some("M2".to_string()),
+            pass_begin_sequence: Some("G2 X1 Y1 R{pass}".to_string()),
+            path_begin_sequence: Some("G0 X100 Y100 Z100 A100 B100 C100".to_string()),
+            max_line_length: 24,
+            ..Default::default()
+        };
+        assert_eq!(
+            lint_sequences(&config),
+            [
+                SequenceWarning::ToolNeverStopped,
+                SequenceWarning::EndsProgramEarly("tool_off_sequence"),
+                SequenceWarning::UnsupportedArc("pass_begin_sequence"),
+                SequenceWarning::LineTooLong("path_begin_sequence"),
+            ]
+        );
+
+        let config = MachineConfig {
+            tool_on_sequence: Some("M3".to_string()),
+            tool_off_sequence: Some("M5".to_string()),
+            end_sequence: Some("M30 (done)".to_string()),
+            ..Default::default()
+        };
+        assert!(lint_sequences(&config).is_empty());
+        let config = MachineConfig {
+            dialect: Some(Dialect::Marlin),
+            ..config
+        };
+        assert_eq!(
+            lint_sequences(&config),
+            [
+                SequenceWarning::UnsupportedModalGroup("tool_on_sequence", ModalGroup::Spindle),
+                SequenceWarning::UnsupportedModalGroup("tool_off_sequence", ModalGroup::Spindle),
+                SequenceWarning::ParenthesizedComment("end_sequence"),
+            ]
+        );
+    }
+}
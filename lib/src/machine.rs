@@ -1,11 +1,22 @@
+use std::{
+    borrow::Cow,
+    fmt::{self, Display},
+};
+
 use g_code::{
     command,
     emit::Token,
-    parse::{ast::Snippet, snippet_parser},
+    parse::{ast::Snippet, snippet_parser, ParseError},
 };
+use lyon_geom::{point, Point};
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    plunge::{helix_center, helix_lines, helix_turns, ramp},
+    Calibration, Dialect, ModalGroup, Plunge,
+};
+
 /// Whether the tool is active (i.e. cutting)
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum Tool {
@@ -27,22 +38,307 @@ pub struct Machine<'input> {
     supported_functionality: SupportedFunctionality,
     tool_state: Option<Tool>,
     distance_mode: Option<Distance>,
+    feedrate: Option<f64>,
+    /// Tool power set by the last `S` word
+    power: Option<f64>,
     tool_on_sequence: Snippet<'input>,
     tool_off_sequence: Snippet<'input>,
+    /// Used instead of the tool on and off sequences when set, see [`Self::set_tool_sequences`]
+    tool_sequence_overrides: [Option<Vec<Token<'static>>>; 2],
     program_begin_sequence: Snippet<'input>,
     program_end_sequence: Snippet<'input>,
-    /// Empty snippet used to provide the same iterator type when a sequence must be empty
-    empty_snippet: Snippet<'input>,
+    hooks: Hooks<'input>,
+    calibration: Option<Calibration>,
+    backlash: [f64; 2],
+    step_size: [f64; 2],
+    /// See [`MachineConfig::travel_z`], [`MachineConfig::cut_z`], and
+    /// [`MachineConfig::z_feedrate`]
+    travel_z: Option<f64>,
+    cut_z: f64,
+    z_feedrate: f64,
+    /// See [`MachineConfig::plunge`], [`MachineConfig::ramp_angle`], and
+    /// [`MachineConfig::helix_radius`]
+    plunge: Plunge,
+    ramp_angle: f64,
+    helix_radius: f64,
+    /// Height of the Z axis, if it has been moved
+    z: Option<f64>,
+    /// Z the pass before went down to, which the tool can go straight back down to, if this
+    /// isn't the first pass over the paths
+    floor: Option<f64>,
+    progress: Progress,
+    /// See [`MachineConfig::dialect`]
+    dialect: Option<Dialect>,
+    /// Whether [`Self::zero_here`] moved the origin, so it is moved back at the end
+    zeroed_here: bool,
 }
 
 #[derive(Debug, Default, Clone, PartialEq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct MachineConfig {
+    /// Firmware the machine runs, if the sequences were set up for it with [`Dialect::apply`]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub dialect: Option<Dialect>,
     pub supported_functionality: SupportedFunctionality,
     pub tool_on_sequence: Option<String>,
     pub tool_off_sequence: Option<String>,
     pub begin_sequence: Option<String>,
     pub end_sequence: Option<String>,
+    /// Runs right before [`Self::home_sequence`]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub pre_home_sequence: Option<String>,
+    /// Homes the machine, after the units and distance mode are set and before [`Self::begin_sequence`]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub home_sequence: Option<String>,
+    /// Runs right after [`Self::home_sequence`]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub post_home_sequence: Option<String>,
+    /// Runs before each pass over all paths
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub pass_begin_sequence: Option<String>,
+    /// Runs after each pass over all paths
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub pass_end_sequence: Option<String>,
+    /// Runs before moving to the start of each path
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub path_begin_sequence: Option<String>,
+    /// Runs after the last cut of each path, before the tool is turned off
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub path_end_sequence: Option<String>,
+    /// Correction applied to all output coordinates, measured from a calibration cut
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub calibration: Option<Calibration>,
+    /// Play in the x and y axes in millimeters, taken up with an extra move whenever an axis reverses
+    ///
+    /// Only needed if the firmware does not compensate for backlash itself.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub backlash: [f64; 2],
+    /// Distance the x and y axes move per motor step in millimeters (i.e. 0.00625 for a typical
+    /// belt drive), which output coordinates are rounded to, 0 to leave an axis as is
+    ///
+    /// Rounding drops movement too small for the machine to make, so inputs that look the same
+    /// give the same output.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub step_size: [f64; 2],
+    /// Longest line the controller accepts (i.e. 80 characters), 0 for no limit
+    ///
+    /// Comments are wrapped and long lines are split up to fit, see [`crate::write_gcode`].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub max_line_length: usize,
+    /// Height in millimeters to lift the Z axis to before every rapid move (i.e. the safe height
+    /// of a router, or pen up for a plotter), `None` to leave lifting the tool to the sequences
+    ///
+    /// When set, the tool is plunged to [`Self::cut_z`] after it is turned on, or to the
+    /// [`crate::ConversionConfig::pass_z`] of each pass when there is a material.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub travel_z: Option<f64>,
+    /// Height in millimeters to plunge the Z axis to for cutting (i.e. the depth of a drag knife,
+    /// or pen down for a plotter), see [`Self::travel_z`]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub cut_z: f64,
+    /// Feedrate in millimeters / minute to plunge at, 0 for the feedrate of the cut
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub z_feedrate: f64,
+    /// How the tool goes down into the material, see [`Self::travel_z`]
+    ///
+    /// Ramps and helixes start from the top of the material, or the bottom of the pass before, so
+    /// the tool still comes down to it quickly.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub plunge: Plunge,
+    /// Steepest angle in degrees from the surface to ramp down at (i.e. 3 for a small end mill),
+    /// 0 for 3 degrees
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub ramp_angle: f64,
+    /// Radius in millimeters of the circles a [`Plunge::Helix`] goes down in, which is best a
+    /// little under that of the bit so the middle is cut too, 0 to ramp instead
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub helix_radius: f64,
+}
+
+/// Steps are rounded to a nanometer
+const STEP_ROUNDING: f64 = 1e6;
+
+/// Angle in degrees that ramps and helixes go down at when [`MachineConfig::ramp_angle`] is 0,
+/// which small end mills take well
+const DEFAULT_RAMP_ANGLE: f64 = 3.;
+
+/// Placeholders available in a [`SnippetTemplate`]
+const PLACEHOLDERS: [&str; 7] = [
+    "{passes}", "{pass}", "{paths}", "{path}", "{power}", "{depth}", "{z}",
+];
+
+/// A g-code snippet that may contain placeholders, which are filled in each time it is emitted:
+///
+/// - `{pass}`: the current pass, counting from 1
+/// - `{passes}`: the total number of passes
+/// - `{path}`: the current path within the pass, counting from 1
+/// - `{paths}`: the total number of paths in a pass
+/// - `{power}`: tool power of the current pass, from [`crate::ConversionConfig::pass_power`]
+/// - `{depth}`: depth below the surface in millimeters of the current pass, from
+///   [`crate::ConversionConfig::pass_depth`]
+/// - `{z}`: Z coordinate the current pass goes down to, from
+///   [`crate::ConversionConfig::pass_z`]
+///
+/// Placeholders outside of a pass or path are 0.
+#[derive(Debug, Clone)]
+pub struct SnippetTemplate<'input> {
+    source: &'input str,
+    /// Parsed ahead of time if there are no placeholders
+    snippet: Option<Snippet<'input>>,
+}
+
+impl<'input> SnippetTemplate<'input> {
+    pub fn parse(source: &'input str) -> Result<Self, ParseError> {
+        if PLACEHOLDERS.iter().any(|p| source.contains(p)) {
+            // Placeholders are always replaced by numbers,
+            // so zeroes of the same width will have errors at the same positions
+            snippet_parser(&fill_placeholders(source, |p| "0".repeat(p.len())))?;
+            Ok(Self {
+                source,
+                snippet: None,
+            })
+        } else {
+            Ok(Self {
+                source,
+                snippet: Some(snippet_parser(source)?),
+            })
+        }
+    }
+
+    /// Detach a token from the filled in source
+    fn detach(&self, token: Token) -> Token<'input> {
+        match token {
+            Token::Field(field) => Token::Field(field.into_owned()),
+            Token::Flag(flag) => {
+                // Flags can't be constructed outside of g-code, but they are never changed by placeholders,
+                // so the same letter can be parsed from the template itself
+                let start = self
+                    .source
+                    .find(flag.letter.as_ref())
+                    .expect("flag is in the template");
+                snippet_parser(&self.source[start..start + flag.letter.len()])
+                    .expect("flag is a valid snippet")
+                    .iter_emit_tokens()
+                    .next()
+                    .expect("snippet has one token")
+            }
+            Token::Comment { is_inline, inner } => Token::Comment {
+                is_inline,
+                inner: Cow::Owned(inner.into_owned()),
+            },
+        }
+    }
+
+    /// Whether the snippet fills in `placeholder`, i.e. `{power}`
+    pub fn uses(&self, placeholder: &str) -> bool {
+        self.source.contains(placeholder)
+    }
+
+    /// The first mode the snippet leaves active that the rest of the program doesn't expect, see
+    /// [`Machine::check_modes`]
+    pub fn unexpected_mode(&self) -> Option<UnexpectedMode> {
+        unexpected_mode(&self.zeroed())
+    }
+
+    /// Tokens with every placeholder filled in as 0, for checking what the snippet does
+    pub(crate) fn zeroed(&self) -> Vec<Token<'input>> {
+        self.render(&Progress::default())
+    }
+
+    fn render(&self, progress: &Progress) -> Vec<Token<'input>> {
+        match &self.snippet {
+            Some(snippet) => snippet.iter_emit_tokens().collect(),
+            None => {
+                let filled = fill_placeholders(self.source, |p| progress.value(p));
+                snippet_parser(&filled)
+                    .expect("template was checked when parsed")
+                    .iter_emit_tokens()
+                    .map(|token| self.detach(token))
+                    .collect()
+            }
+        }
+    }
+}
+
+fn fill_placeholders(source: &str, value: impl Fn(&str) -> String) -> String {
+    PLACEHOLDERS
+        .iter()
+        .fold(source.to_string(), |acc, p| acc.replace(p, &value(p)))
+}
+
+/// Where the machine is in the program, used to fill in [`SnippetTemplate`]s
+#[derive(Debug, Default, Clone, Copy)]
+struct Progress {
+    pass: usize,
+    passes: usize,
+    path: usize,
+    paths: usize,
+    power: f64,
+    depth: f64,
+    z: f64,
+}
+
+impl Progress {
+    fn value(&self, placeholder: &str) -> String {
+        match placeholder {
+            "{pass}" => self.pass.to_string(),
+            "{passes}" => self.passes.to_string(),
+            "{path}" => self.path.to_string(),
+            "{paths}" => self.paths.to_string(),
+            "{power}" => self.power.to_string(),
+            "{depth}" => self.depth.to_string(),
+            "{z}" => self.z.to_string(),
+            _ => unreachable!("unknown placeholder {placeholder}"),
+        }
+    }
+}
+
+/// Optional g-code emitted at specific points of the program, see [`MachineConfig`] for when each runs
+#[derive(Debug, Default, Clone)]
+pub struct Hooks<'input> {
+    pub pre_home: Option<SnippetTemplate<'input>>,
+    pub home: Option<SnippetTemplate<'input>>,
+    pub post_home: Option<SnippetTemplate<'input>>,
+    pub pass_begin: Option<SnippetTemplate<'input>>,
+    pub pass_end: Option<SnippetTemplate<'input>>,
+    pub path_begin: Option<SnippetTemplate<'input>>,
+    pub path_end: Option<SnippetTemplate<'input>>,
+}
+
+impl<'input> Hooks<'input> {
+    /// Parse all hook sequences in the config.
+    ///
+    /// On failure, returns every sequence that could not be parsed along with its field name.
+    pub fn from_config(
+        config: &'input MachineConfig,
+    ) -> Result<Self, Vec<(&'static str, &'input str, ParseError)>> {
+        let mut errors = vec![];
+        let mut parse = |name: &'static str, sequence: &'input Option<String>| {
+            sequence
+                .as_deref()
+                .and_then(|source| match SnippetTemplate::parse(source) {
+                    Ok(template) => Some(template),
+                    Err(err) => {
+                        errors.push((name, source, err));
+                        None
+                    }
+                })
+        };
+        let hooks = Self {
+            pre_home: parse("pre_home_sequence", &config.pre_home_sequence),
+            home: parse("home_sequence", &config.home_sequence),
+            post_home: parse("post_home_sequence", &config.post_home_sequence),
+            pass_begin: parse("pass_begin_sequence", &config.pass_begin_sequence),
+            pass_end: parse("pass_end_sequence", &config.pass_end_sequence),
+            path_begin: parse("path_begin_sequence", &config.path_begin_sequence),
+            path_end: parse("path_end_sequence", &config.path_end_sequence),
+        };
+        if errors.is_empty() {
+            Ok(hooks)
+        } else {
+            Err(errors)
+        }
+    }
 }
 
 #[derive(Debug, Default, Clone, PartialEq)]
@@ -52,6 +348,13 @@ pub struct SupportedFunctionality {
     ///
     /// Most modern machines support this. Old ones like early MakerBot 3D printers do not.
     pub circular_interpolation: bool,
+    /// Write the center of G2/G3 arcs as an offset from where they start (I/J) instead of their
+    /// radius (R)
+    ///
+    /// Controllers like Grbl recommend this, since the center of an arc close to a half circle is
+    /// hard to tell from its radius.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub arc_center_offsets: bool,
 }
 
 impl<'input> Machine<'input> {
@@ -67,36 +370,360 @@ impl<'input> Machine<'input> {
             supported_functionality,
             tool_on_sequence: tool_on_sequence.unwrap_or_else(|| empty_snippet.clone()),
             tool_off_sequence: tool_off_sequence.unwrap_or_else(|| empty_snippet.clone()),
+            tool_sequence_overrides: Default::default(),
             program_begin_sequence: program_begin_sequence.unwrap_or_else(|| empty_snippet.clone()),
-            program_end_sequence: program_end_sequence.unwrap_or_else(|| empty_snippet.clone()),
-            empty_snippet,
+            program_end_sequence: program_end_sequence.unwrap_or(empty_snippet),
+            hooks: Hooks::default(),
+            calibration: None,
+            backlash: [0.; 2],
+            step_size: [0.; 2],
+            travel_z: None,
+            cut_z: 0.,
+            z_feedrate: 0.,
+            plunge: Plunge::Straight,
+            ramp_angle: 0.,
+            helix_radius: 0.,
+            z: None,
+            floor: None,
+            progress: Progress::default(),
+            dialect: None,
+            zeroed_here: false,
             tool_state: Default::default(),
             distance_mode: Default::default(),
+            feedrate: Default::default(),
+            power: Default::default(),
         }
     }
 
+    /// Use these hooks in addition to the program begin and end sequences
+    pub fn with_hooks(mut self, hooks: Hooks<'input>) -> Self {
+        self.hooks = hooks;
+        self
+    }
+
+    /// Correct all output coordinates with this calibration
+    pub fn with_calibration(mut self, calibration: Option<Calibration>) -> Self {
+        self.calibration = calibration;
+        self
+    }
+
+    pub fn calibration(&self) -> Option<&Calibration> {
+        self.calibration.as_ref()
+    }
+
+    /// Compensate for play in the x and y axes, see [`MachineConfig::backlash`]
+    pub fn with_backlash(mut self, backlash: [f64; 2]) -> Self {
+        self.backlash = backlash;
+        self
+    }
+
+    pub fn backlash(&self) -> [f64; 2] {
+        self.backlash
+    }
+
+    /// Round output coordinates onto the steps of each axis, see [`MachineConfig::step_size`]
+    pub fn with_step_size(mut self, step_size: [f64; 2]) -> Self {
+        self.step_size = step_size;
+        self
+    }
+
+    /// Write commands the way this firmware expects them, see [`MachineConfig::dialect`]
+    pub fn with_dialect(mut self, dialect: Option<Dialect>) -> Self {
+        self.dialect = dialect;
+        self
+    }
+
+    pub fn dialect(&self) -> Option<Dialect> {
+        self.dialect
+    }
+
+    /// Lift the Z axis to `travel_z` before every rapid move and plunge it to `cut_z` at
+    /// `z_feedrate` for cutting, see [`MachineConfig::travel_z`]
+    pub fn with_z_moves(mut self, travel_z: Option<f64>, cut_z: f64, z_feedrate: f64) -> Self {
+        self.travel_z = travel_z;
+        self.cut_z = cut_z;
+        self.z_feedrate = z_feedrate;
+        self
+    }
+
+    /// Go down into the material at an angle instead of straight down, see
+    /// [`MachineConfig::plunge`]
+    pub fn with_plunge(mut self, plunge: Plunge, ramp_angle: f64, helix_radius: f64) -> Self {
+        self.plunge = plunge;
+        self.ramp_angle = ramp_angle;
+        self.helix_radius = helix_radius;
+        self
+    }
+
+    /// Whether plunges go along the path about to be cut, which [`Self::plunge`] then needs
+    pub fn ramps(&self) -> bool {
+        self.travel_z.is_some() && self.plunge != Plunge::Straight && self.ramp_angle < 90.
+    }
+
+    /// Tangent of the angle to ramp down at, see [`MachineConfig::ramp_angle`]
+    fn ramp_slope(&self) -> f64 {
+        let angle = if self.ramp_angle > 0. {
+            self.ramp_angle
+        } else {
+            DEFAULT_RAMP_ANGLE
+        };
+        angle.to_radians().tan()
+    }
+
+    /// Output a rapid move up to the travel height, if there is one and the Z axis isn't already
+    /// there
+    pub fn retract(&mut self) -> Vec<Token<'input>> {
+        match self.travel_z {
+            Some(travel_z) if self.z != Some(travel_z) => {
+                self.z = Some(travel_z);
+                command!(RapidPositioning { Z: travel_z }).into_token_vec()
+            }
+            _ => vec![],
+        }
+    }
+
+    /// Output a move down to the height of the cut, at the plunge feedrate or else `feedrate`, if
+    /// there is a travel height and the Z axis isn't already there
+    ///
+    /// With a depth set by [`Self::set_depth`], the cut goes down to its Z instead of the one
+    /// configured. When [`Self::ramps`], the tool goes straight down to the surface, or the bottom
+    /// of the pass before, and then ramps the rest of the way along `path`, the points of the path
+    /// about to be cut, ending at its start.
+    pub fn plunge(&mut self, feedrate: f64, path: &[Point<f64>]) -> Vec<Token<'input>> {
+        if self.travel_z.is_none() {
+            return vec![];
+        }
+        let cut_z = if self.progress.depth > 0. {
+            self.progress.z
+        } else {
+            self.cut_z
+        };
+        if self.z == Some(cut_z) {
+            return vec![];
+        }
+        let feedrate = if self.z_feedrate > 0. {
+            self.z_feedrate
+        } else {
+            feedrate
+        };
+        let surface = self.progress.z + self.progress.depth;
+        let top = self
+            .floor
+            .filter(|floor| *floor < surface)
+            .unwrap_or(surface);
+        let ramp = if self.ramps() && cut_z < top {
+            self.ramp(path, top, cut_z)
+        } else {
+            vec![]
+        };
+        self.z = Some(cut_z);
+        self.feedrate = Some(feedrate);
+        let mut tokens = command!(LinearInterpolation {
+            Z: if ramp.is_empty() { cut_z } else { top },
+            F: feedrate,
+        })
+        .into_token_vec();
+        tokens.extend(ramp);
+        tokens
+    }
+
+    /// Moves from `from_z` down to `to_z` along `path` at the current feedrate, or nothing if the
+    /// tool should go straight down instead
+    fn ramp(&self, path: &[Point<f64>], from_z: f64, to_z: f64) -> Vec<Token<'input>> {
+        let slope = self.ramp_slope();
+        let helix_center = match self.plunge {
+            Plunge::Helix => helix_center(path, self.helix_radius),
+            _ => None,
+        };
+        let points = match (self.plunge, helix_center) {
+            (Plunge::Straight, _) => vec![],
+            // Open paths and those too small for the helix are ramped into instead
+            (Plunge::Ramp, _) | (Plunge::Helix, None) => ramp(path, from_z, to_z, slope),
+            (Plunge::Helix, Some(center)) => {
+                let turns = helix_turns(from_z, to_z, self.helix_radius, slope);
+                let arcs = self.supported_functionality.circular_interpolation
+                    && self.supported_functionality.arc_center_offsets
+                    && self.backlash == [0.; 2];
+                if arcs {
+                    // Full circles, which only an offset to the center can describe
+                    let start = self.snap_to_steps(path[0]);
+                    let offset = center - start;
+                    return turns
+                        .into_iter()
+                        .flat_map(|z| {
+                            command!(CounterclockwiseCircularInterpolation {
+                                X: start.x,
+                                Y: start.y,
+                                Z: (z * STEP_ROUNDING).round() / STEP_ROUNDING,
+                                I: (offset.x * STEP_ROUNDING).round() / STEP_ROUNDING,
+                                J: (offset.y * STEP_ROUNDING).round() / STEP_ROUNDING,
+                            })
+                            .into_token_vec()
+                        })
+                        .collect();
+                }
+                helix_lines(path[0], center, from_z, &turns)
+            }
+        };
+        points
+            .into_iter()
+            .flat_map(|(point, z)| {
+                let point = self.snap_to_steps(point);
+                command!(LinearInterpolation {
+                    X: point.x,
+                    Y: point.y,
+                    Z: (z * STEP_ROUNDING).round() / STEP_ROUNDING,
+                })
+                .into_token_vec()
+            })
+            .collect()
+    }
+
+    /// Record that the Z axis was moved to `z` some other way, i.e. along a focus ramp
+    pub fn moved_z(&mut self, z: f64) {
+        self.z = Some(z);
+    }
+
+    /// Round a point onto the steps of each axis
+    pub fn snap_to_steps(&self, p: Point<f64>) -> Point<f64> {
+        let [x, y] = [(p.x, self.step_size[0]), (p.y, self.step_size[1])].map(|(value, step)| {
+            if step > 0. {
+                // Rounded again to clear up noise from multiplying, so the same step is always
+                // written the same way
+                ((value / step).round() * step * STEP_ROUNDING).round() / STEP_ROUNDING
+            } else {
+                value
+            }
+        });
+        point(x, y)
+    }
+
     pub fn supported_functionality(&self) -> &SupportedFunctionality {
         &self.supported_functionality
     }
 
+    /// Check that no sequence leaves the machine in a mode the rest of the program doesn't expect
+    ///
+    /// The program end sequence is not checked, since nothing runs after it.
+    /// On failure, returns every sequence that does along with its field name.
+    pub fn check_modes(&self) -> Result<(), Vec<(&'static str, UnexpectedMode)>> {
+        let progress = Progress::default();
+        let hooks = [
+            ("pre_home_sequence", &self.hooks.pre_home),
+            ("home_sequence", &self.hooks.home),
+            ("post_home_sequence", &self.hooks.post_home),
+            ("pass_begin_sequence", &self.hooks.pass_begin),
+            ("pass_end_sequence", &self.hooks.pass_end),
+            ("path_begin_sequence", &self.hooks.path_begin),
+            ("path_end_sequence", &self.hooks.path_end),
+        ]
+        .into_iter()
+        .filter_map(|(name, hook)| Some((name, hook.as_ref()?.render(&progress))));
+        let errors = [
+            ("tool_on_sequence", &self.tool_on_sequence),
+            ("tool_off_sequence", &self.tool_off_sequence),
+            ("begin_sequence", &self.program_begin_sequence),
+        ]
+        .into_iter()
+        .map(|(name, snippet)| (name, snippet.iter_emit_tokens().collect::<Vec<_>>()))
+        .chain(hooks)
+        .filter_map(|(name, tokens)| Some((name, unexpected_mode(&tokens)?)))
+        .collect::<Vec<_>>();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Record the feedrate of the next move, returning whether it differs from the machine's
+    /// current feedrate and so needs to be output
+    pub fn feedrate(&mut self, feedrate: f64) -> bool {
+        let changed = self.feedrate != Some(feedrate);
+        self.feedrate = Some(feedrate);
+        changed
+    }
+
+    /// Record the tool power of the next cut, returning whether it differs from the machine's
+    /// current power and so needs to be output
+    pub fn power(&mut self, power: f64) -> bool {
+        let changed = self.power != Some(power);
+        self.power = Some(power);
+        changed
+    }
+
     /// Output gcode to turn the tool on.
-    pub fn tool_on(&mut self) -> impl Iterator<Item = Token<'input>> + '_ {
-        if self.tool_state == Some(Tool::Off) || self.tool_state.is_none() {
+    pub fn tool_on(&mut self) -> impl Iterator<Item = Token<'input>> {
+        let tokens = if self.tool_state == Some(Tool::Off) || self.tool_state.is_none() {
             self.tool_state = Some(Tool::On);
-            self.tool_on_sequence.iter_emit_tokens()
+            self.tool_sequence(0)
         } else {
-            self.empty_snippet.iter_emit_tokens()
-        }
+            vec![]
+        };
+        tokens.into_iter()
     }
 
     /// Output gcode to turn the tool off.
-    pub fn tool_off(&mut self) -> impl Iterator<Item = Token<'input>> + '_ {
-        if self.tool_state == Some(Tool::On) || self.tool_state.is_none() {
+    pub fn tool_off(&mut self) -> impl Iterator<Item = Token<'input>> {
+        let tokens = if self.tool_state == Some(Tool::On) || self.tool_state.is_none() {
             self.tool_state = Some(Tool::Off);
-            self.tool_off_sequence.iter_emit_tokens()
+            self.tool_sequence(1)
         } else {
-            self.empty_snippet.iter_emit_tokens()
+            vec![]
+        };
+        tokens.into_iter()
+    }
+
+    /// The tool on (0) or off (1) sequence in use, recording the feedrate and power it sets
+    fn tool_sequence(&mut self, i: usize) -> Vec<Token<'input>> {
+        let tokens = match &self.tool_sequence_overrides[i] {
+            Some(tokens) => tokens.clone(),
+            None => [&self.tool_on_sequence, &self.tool_off_sequence][i]
+                .iter_emit_tokens()
+                .collect(),
+        };
+        self.feedrate = feedrate_set_by(tokens.iter().cloned()).or(self.feedrate);
+        if sets_power(&tokens) {
+            self.power = None;
         }
+        tokens
+    }
+
+    /// Turn the tool on and off with these sequences instead of the configured ones, i.e. for a
+    /// layer cut with a different tool, or go back to the configured ones where `None`
+    pub fn set_tool_sequences(&mut self, sequences: [Option<Vec<Token<'static>>>; 2]) {
+        self.tool_sequence_overrides = sequences;
+    }
+
+    /// The sequences set by [`Self::set_tool_sequences`]
+    pub fn tool_sequences(&self) -> &[Option<Vec<Token<'static>>>; 2] {
+        &self.tool_sequence_overrides
+    }
+
+    /// Make wherever the machine is the origin (`G92 X0 Y0`), so the program runs from there
+    pub fn zero_here(&mut self) -> Vec<Token<'input>> {
+        self.zeroed_here = true;
+        snippet_parser("G92 X0 Y0")
+            .expect("G92 is a valid snippet")
+            .iter_emit_tokens()
+            .collect()
+    }
+
+    /// Clear the origin set by [`Self::zero_here`] (`G92.1`), so it doesn't stay in effect after
+    /// the program
+    ///
+    /// Marlin and Klipper don't have `G92.1`, and keep the origin until they are homed.
+    pub fn clear_zero(&mut self) -> Vec<Token<'input>> {
+        let supported = self
+            .dialect
+            .is_none_or(|dialect| dialect.supports(ModalGroup::CoordinateSystem));
+        if !std::mem::take(&mut self.zeroed_here) || !supported {
+            return vec![];
+        }
+        snippet_parser("G92.1")
+            .expect("G92.1 is a valid snippet")
+            .iter_emit_tokens()
+            .collect()
     }
 
     /// Output user-defined setup gcode
@@ -109,6 +736,108 @@ impl<'input> Machine<'input> {
         self.program_end_sequence.iter_emit_tokens()
     }
 
+    /// Output user-defined homing gcode, including what runs before and after it
+    pub fn home(&self) -> Vec<Token<'input>> {
+        [
+            &self.hooks.pre_home,
+            &self.hooks.home,
+            &self.hooks.post_home,
+        ]
+        .into_iter()
+        .flatten()
+        .flat_map(|template| template.render(&self.progress))
+        .collect()
+    }
+
+    /// Whether a pass or path sequence fills in `{power}`, without which the power of each pass
+    /// is left out of the program
+    pub fn uses_power(&self) -> bool {
+        [
+            &self.hooks.pass_begin,
+            &self.hooks.pass_end,
+            &self.hooks.path_begin,
+            &self.hooks.path_end,
+        ]
+        .into_iter()
+        .flatten()
+        .any(|template| template.uses("{power}"))
+    }
+
+    /// Output user-defined gcode for the start of a pass, counting from 1.
+    ///
+    /// Power is 0 if it is not set.
+    pub fn pass_begin(
+        &mut self,
+        pass: usize,
+        passes: usize,
+        power: Option<f64>,
+    ) -> Vec<Token<'input>> {
+        self.progress.pass = pass;
+        self.progress.passes = passes;
+        // Nothing has been cut yet on the first pass over the paths, i.e. of the next layer
+        if pass <= 1 {
+            self.floor = None;
+        }
+        self.progress.power = power.unwrap_or_default();
+        self.render_hook(|hooks| &hooks.pass_begin)
+    }
+
+    /// Set the depth below the surface and the Z coordinate of the passes that follow, for
+    /// `{depth}` and `{z}` in sequences
+    ///
+    /// Unless the next pass is the first, see [`Self::pass_begin`], plunges go straight down to
+    /// the Z of the one before.
+    pub fn set_depth(&mut self, depth: f64, z: f64) {
+        self.floor = (self.progress.depth > 0.).then_some(self.progress.z);
+        self.progress.depth = depth;
+        self.progress.z = z;
+    }
+
+    /// Output user-defined gcode for the end of the current pass
+    pub fn pass_end(&mut self) -> Vec<Token<'input>> {
+        let tokens = self.render_hook(|hooks| &hooks.pass_end);
+        self.progress.path = 0;
+        self.progress.paths = 0;
+        tokens
+    }
+
+    /// Output user-defined gcode for the start of a path, counting from 1
+    pub fn path_begin(&mut self, path: usize, paths: usize) -> Vec<Token<'input>> {
+        self.progress.path = path;
+        self.progress.paths = paths;
+        self.render_hook(|hooks| &hooks.path_begin)
+    }
+
+    /// Output user-defined gcode for the end of the current path
+    pub fn path_end(&mut self) -> Vec<Token<'input>> {
+        self.render_hook(|hooks| &hooks.path_end)
+    }
+
+    fn render_hook(
+        &mut self,
+        hook: impl for<'h> Fn(&'h Hooks<'input>) -> &'h Option<SnippetTemplate<'input>>,
+    ) -> Vec<Token<'input>> {
+        let tokens = hook(&self.hooks)
+            .as_ref()
+            .map(|template| template.render(&self.progress))
+            .unwrap_or_default();
+        self.feedrate = feedrate_set_by(tokens.iter().cloned()).or(self.feedrate);
+        if sets_power(&tokens) {
+            self.power = None;
+        }
+        tokens
+    }
+
+    /// Output a dwell (G4) of `seconds`, in the unit the dialect reads its P word in
+    pub fn dwell(&self, seconds: f64) -> Vec<Token<'input>> {
+        let p = if self.dialect.is_some_and(Dialect::dwell_in_milliseconds) {
+            seconds * 1000.
+        } else {
+            seconds
+        };
+        command!(Dwell { P: p }).into_token_vec()
+    }
+
     /// Output absolute distance field if mode was relative or unknown.
     pub fn absolute(&mut self) -> Vec<Token<'input>> {
         if self.distance_mode == Some(Distance::Relative) || self.distance_mode.is_none() {
@@ -129,3 +858,249 @@ impl<'input> Machine<'input> {
         }
     }
 }
+
+/// The last feedrate set by a sequence, if any
+fn feedrate_set_by<'a>(tokens: impl IntoIterator<Item = Token<'a>>) -> Option<f64> {
+    tokens
+        .into_iter()
+        .filter_map(|token| match token {
+            Token::Field(field) if field.letters.eq_ignore_ascii_case("F") => field.value.as_f64(),
+            _ => None,
+        })
+        .last()
+}
+
+/// Whether a sequence sets the tool power, after which the power of the next cut has to be
+/// output again
+fn sets_power(tokens: &[Token]) -> bool {
+    tokens.iter().any(
+        |token| matches!(token, Token::Field(field) if field.letters.eq_ignore_ascii_case("S")),
+    )
+}
+
+/// A mode that a sequence leaves active, which the rest of the program doesn't expect
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnexpectedMode {
+    /// G91, moves would be taken as relative to the current position
+    RelativeDistance,
+    /// G20, coordinates would be taken as inches
+    Inches,
+    /// G18 or G19, arcs would be drawn in the wrong plane
+    NotXyPlane,
+}
+
+impl Display for UnexpectedMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::RelativeDistance => {
+                write!(
+                    f,
+                    "leaves relative distance mode (G91) active, end it with G90"
+                )
+            }
+            Self::Inches => write!(f, "leaves inch units (G20) active, end it with G21"),
+            Self::NotXyPlane => write!(
+                f,
+                "leaves the XZ or YZ plane (G18/G19) active, end it with G17"
+            ),
+        }
+    }
+}
+
+/// The first mode that a sequence leaves active that the rest of the program doesn't expect
+pub fn unexpected_mode<'a>(
+    tokens: impl IntoIterator<Item = &'a Token<'a>>,
+) -> Option<UnexpectedMode> {
+    let (mut distance, mut units, mut plane) = (None, None, None);
+    for token in tokens {
+        let Token::Field(field) = token else {
+            continue;
+        };
+        if !field.letters.eq_ignore_ascii_case("G") {
+            continue;
+        }
+        match field.value.as_f64() {
+            Some(90.) => distance = Some(false),
+            Some(91.) => distance = Some(true),
+            Some(20.) => units = Some(true),
+            Some(21.) => units = Some(false),
+            Some(17.) => plane = Some(false),
+            Some(18. | 19.) => plane = Some(true),
+            _ => {}
+        }
+    }
+    [
+        (distance, UnexpectedMode::RelativeDistance),
+        (units, UnexpectedMode::Inches),
+        (plane, UnexpectedMode::NotXyPlane),
+    ]
+    .into_iter()
+    .find_map(|(unexpected, mode)| (unexpected == Some(true)).then_some(mode))
+}
+
+#[cfg(test)]
+mod test {
+    use g_code::emit::{format_gcode_fmt, FormatOptions};
+
+    use super::*;
+
+    fn format(tokens: &[Token]) -> String {
+        let mut out = String::new();
+        format_gcode_fmt(tokens, FormatOptions::default(), &mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn coordinates_are_rounded_onto_steps() {
+        let machine = Machine::new(Default::default(), None, None, None, None);
+        assert_eq!(machine.snap_to_steps(point(0.1234, 5.)), point(0.1234, 5.));
+        let machine = machine.with_step_size([0.00625, 0.]);
+        assert_eq!(
+            machine.snap_to_steps(point(0.3012, 0.1234)),
+            point(0.3, 0.1234)
+        );
+        assert_eq!(machine.snap_to_steps(point(-10.0033, 0.)).x, -10.00625);
+    }
+
+    #[test]
+    fn dwells_are_in_the_unit_of_the_dialect() {
+        let machine = Machine::new(Default::default(), None, None, None, None);
+        assert_eq!(format(&machine.dwell(0.25)), "G4 P0.25\n");
+        let machine = machine.with_dialect(Some(Dialect::Marlin));
+        assert_eq!(format(&machine.dwell(0.25)), "G4 P250\n");
+    }
+
+    #[test]
+    fn snippet_template_fills_in_placeholders() {
+        let template = SnippetTemplate::parse(
+            "M117 P{pass} Q{passes} X\nG4 P{path} Q{paths}\nM3 S{power}\nG1 Z{z} ; {depth}",
+        )
+        .unwrap();
+        let progress = Progress {
+            pass: 2,
+            passes: 3,
+            path: 10,
+            paths: 42,
+            power: 62.5,
+            depth: 1.5,
+            z: 8.5,
+        };
+        assert_eq!(
+            format(&template.render(&progress)),
+            "M117 P2 Q3 X\nG4 P10 Q42\nM3 S62.5\nG1 Z8.5; 1.5\n"
+        );
+    }
+
+    #[test]
+    fn snippet_template_rejects_unknown_placeholders() {
+        assert!(SnippetTemplate::parse("G4 P{pass}").is_ok());
+        assert!(SnippetTemplate::parse("G4 P{unknown}").is_err());
+    }
+
+    #[test]
+    fn machine_tracks_progress_for_hooks() {
+        let config = MachineConfig {
+            pass_begin_sequence: Some("M117 P{pass} Q{passes} S{power}".to_string()),
+            path_begin_sequence: Some("M117 P{path} Q{paths}".to_string()),
+            pass_end_sequence: Some("M117 P{path}".to_string()),
+            ..Default::default()
+        };
+        let mut machine = Machine::new(SupportedFunctionality::default(), None, None, None, None)
+            .with_hooks(Hooks::from_config(&config).unwrap());
+        assert!(machine.home().is_empty());
+        assert_eq!(
+            format(&machine.pass_begin(1, 2, Some(600.))),
+            "M117 P1 Q2 S600\n"
+        );
+        assert_eq!(format(&machine.path_begin(3, 4)), "M117 P3 Q4\n");
+        assert!(machine.path_end().is_empty());
+        assert_eq!(format(&machine.pass_end()), "M117 P3\n");
+        assert_eq!(format(&machine.pass_begin(2, 2, None)), "M117 P2 Q2 S0\n");
+    }
+
+    #[test]
+    fn sequences_leaving_unexpected_modes_are_rejected() {
+        let parse = |source| snippet_parser(source).unwrap();
+        let tokens = |source| parse(source).iter_emit_tokens().collect::<Vec<_>>();
+        assert_eq!(unexpected_mode(&tokens("G91 G0 Z5 G90")), None);
+        assert_eq!(
+            unexpected_mode(&tokens("G90 G0 Z5 G91")),
+            Some(UnexpectedMode::RelativeDistance)
+        );
+        assert_eq!(
+            unexpected_mode(&tokens("g20")),
+            Some(UnexpectedMode::Inches)
+        );
+
+        let config = MachineConfig {
+            path_begin_sequence: Some("G18 G2 X1 Z1 R1".to_string()),
+            ..Default::default()
+        };
+        let machine = Machine::new(
+            SupportedFunctionality::default(),
+            Some(parse("M3 G91")),
+            None,
+            None,
+            Some(parse("G91")),
+        )
+        .with_hooks(Hooks::from_config(&config).unwrap());
+        assert_eq!(
+            machine.check_modes(),
+            Err(vec![
+                ("tool_on_sequence", UnexpectedMode::RelativeDistance),
+                ("path_begin_sequence", UnexpectedMode::NotXyPlane)
+            ])
+        );
+    }
+
+    #[test]
+    fn feedrate_is_only_output_when_it_changes() {
+        let mut machine = Machine::new(
+            SupportedFunctionality::default(),
+            Some(snippet_parser("M3 G1 F100").unwrap()),
+            None,
+            None,
+            None,
+        );
+        assert!(machine.feedrate(300.));
+        assert!(!machine.feedrate(300.));
+        // The tool on sequence changes it behind the program's back
+        assert_eq!(
+            format(&machine.tool_on().collect::<Vec<_>>()),
+            "M3\nG1 F100\n"
+        );
+        assert!(machine.feedrate(300.));
+        assert!(machine.feedrate(500.));
+    }
+
+    #[test]
+    fn power_is_output_again_after_a_sequence_sets_it() {
+        let mut machine = Machine::new(
+            SupportedFunctionality::default(),
+            Some(snippet_parser("M4").unwrap()),
+            Some(snippet_parser("M5 S0").unwrap()),
+            None,
+            None,
+        );
+        machine.tool_on().for_each(drop);
+        assert!(machine.power(500.));
+        assert!(!machine.power(500.));
+        // The tool off sequence leaves the power at 0
+        machine.tool_off().for_each(drop);
+        machine.tool_on().for_each(drop);
+        assert!(machine.power(500.));
+    }
+
+    #[test]
+    fn origin_set_here_is_cleared_where_the_firmware_can() {
+        let mut machine = Machine::new(SupportedFunctionality::default(), None, None, None, None);
+        assert!(machine.clear_zero().is_empty());
+        assert_eq!(format(&machine.zero_here()), "G92 X0 Y0\n");
+        assert_eq!(format(&machine.clear_zero()), "G92.1\n");
+        assert!(machine.clear_zero().is_empty());
+
+        let mut machine = machine.with_dialect(Some(Dialect::Klipper));
+        machine.zero_here();
+        assert!(machine.clear_zero().is_empty());
+    }
+}
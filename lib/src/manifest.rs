@@ -0,0 +1,123 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use g_code::emit::Token;
+
+use crate::{follow_program, program::cut_bounds, JobEstimate, Settings};
+
+/// Describes each program written for a job, so job management systems can take in batches of
+/// them without reading the comments in the g-code
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Manifest {
+    /// Name and version of what wrote the programs, i.e. `svg2gcode 0.3.2`
+    pub generator: String,
+    pub files: Vec<ManifestEntry>,
+}
+
+impl Manifest {
+    pub fn new(files: Vec<ManifestEntry>) -> Self {
+        Self {
+            generator: format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+            files,
+        }
+    }
+}
+
+/// A program in a [`Manifest`]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ManifestEntry {
+    /// Path of the program, relative to the manifest when they are written together
+    pub file: String,
+    /// SVG the program was converted from, if it was read from a file
+    pub source: Option<ManifestSource>,
+    /// Settings the program was converted with
+    pub settings: Settings,
+    /// How long the program takes to run in seconds, see [`JobEstimate::duration`], or `None` if
+    /// it can't be estimated
+    pub duration: Option<f64>,
+    /// Corners with the least and greatest coordinates of the cuts in millimeters, as the machine
+    /// makes them
+    pub bounding_box: Option<[[f64; 2]; 2]>,
+    /// Messages of what went wrong while converting, i.e. elements that could not be drawn
+    pub warnings: Vec<String>,
+}
+
+impl ManifestEntry {
+    /// Describe a program converted with `settings`, with no source or warnings
+    pub fn new(file: impl Into<String>, program: &[Token<'_>], settings: Settings) -> Self {
+        let estimate =
+            JobEstimate::from_program(program, settings.machine.dialect, &settings.cost).ok();
+        let bounds = cut_bounds(&follow_program(program, settings.machine.dialect));
+        Self {
+            file: file.into(),
+            source: None,
+            duration: estimate.map(|estimate| estimate.duration.as_secs_f64()),
+            bounding_box: bounds.map(|bounds| [bounds.min.to_array(), bounds.max.to_array()]),
+            settings,
+            warnings: vec![],
+        }
+    }
+}
+
+/// An SVG a program in a [`Manifest`] was converted from
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ManifestSource {
+    /// File name of the SVG
+    pub name: String,
+    /// 64-bit [FNV-1a](https://en.wikipedia.org/wiki/Fowler%E2%80%93Noll%E2%80%93Vo_hash_function)
+    /// hash of the SVG in hexadecimal, for telling whether it has changed
+    ///
+    /// This is not a cryptographic hash, so it doesn't show that the SVG wasn't tampered with.
+    pub hash: String,
+}
+
+impl ManifestSource {
+    pub fn new(name: impl Into<String>, content: &[u8]) -> Self {
+        Self {
+            name: name.into(),
+            hash: format!("{:016x}", fnv1a(content)),
+        }
+    }
+}
+
+/// Unlike [`std::collections::hash_map::DefaultHasher`], this stays the same between Rust versions
+fn fnv1a(bytes: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+    bytes.iter().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(PRIME)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use lyon_geom::point;
+
+    use super::*;
+    use crate::{toolpaths2program, LayerOverrides, Machine, Toolpath, Toolpaths};
+
+    #[test]
+    fn entries_describe_the_program_and_its_source() {
+        // https://datatracker.ietf.org/doc/html/draft-eastlake-fnv#appendix-C
+        assert_eq!(ManifestSource::new("a.svg", b"a").hash, "af63dc4c8601ec8c");
+
+        let toolpaths = Toolpaths {
+            toolpaths: vec![Toolpath::from_polyline(&[point(1., 2.), point(11., 7.)]).unwrap()],
+            ..Default::default()
+        };
+        let settings = Settings::default();
+        let machine = Machine::new(Default::default(), None, None, None, None);
+        let program = toolpaths2program(
+            toolpaths,
+            &settings.conversion,
+            &LayerOverrides::new(),
+            machine,
+        );
+        let entry = ManifestEntry::new("a.gcode", &program, settings);
+        assert_eq!(entry.bounding_box, Some([[1., 2.], [11., 7.]]));
+        assert!(entry.duration.unwrap() > 0.);
+    }
+}
@@ -0,0 +1,190 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use lyon_geom::vector;
+
+use crate::{toolpath::bounding_box, Toolpath};
+
+/// Shape of each registration mark
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum MarkShape {
+    /// Two lines crossing at the mark
+    Cross,
+    /// An L with its arms running along the artwork (i.e. crop marks)
+    ///
+    /// Marks in the middle of a side are a single line pointing away from the artwork.
+    Corner,
+}
+
+/// Where a registration mark goes around the artwork
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum MarkPosition {
+    TopLeft,
+    Top,
+    TopRight,
+    Right,
+    BottomRight,
+    Bottom,
+    BottomLeft,
+    Left,
+}
+
+impl MarkPosition {
+    pub const CORNERS: [Self; 4] = [
+        Self::TopLeft,
+        Self::TopRight,
+        Self::BottomRight,
+        Self::BottomLeft,
+    ];
+
+    /// -1 for the left or bottom, 0 for the middle, and 1 for the right or top
+    fn sides(self) -> [f64; 2] {
+        match self {
+            Self::TopLeft => [-1., 1.],
+            Self::Top => [0., 1.],
+            Self::TopRight => [1., 1.],
+            Self::Right => [1., 0.],
+            Self::BottomRight => [1., -1.],
+            Self::Bottom => [0., -1.],
+            Self::BottomLeft => [-1., -1.],
+            Self::Left => [-1., 0.],
+        }
+    }
+}
+
+/// Marks drawn around the artwork for lining up the material, i.e. for print-and-cut or when a
+/// job is run over several sessions
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RegistrationMarks {
+    #[cfg_attr(feature = "serde", serde(default = "default_shape"))]
+    pub shape: MarkShape,
+    #[cfg_attr(feature = "serde", serde(default = "default_positions"))]
+    pub positions: Vec<MarkPosition>,
+    /// Length of each line of a mark in millimeters
+    #[cfg_attr(feature = "serde", serde(default = "default_size"))]
+    pub size: f64,
+    /// Distance between the bounding box of the artwork and the marks in millimeters
+    #[cfg_attr(feature = "serde", serde(default = "default_margin"))]
+    pub margin: f64,
+    /// Tool power for drawing the marks (i.e. low enough to only mark the surface), available in
+    /// sequences as `{power}`
+    ///
+    /// Falls back on the power of the first pass.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub power: Option<f64>,
+}
+
+const fn default_shape() -> MarkShape {
+    MarkShape::Cross
+}
+
+fn default_positions() -> Vec<MarkPosition> {
+    MarkPosition::CORNERS.to_vec()
+}
+
+const fn default_size() -> f64 {
+    10.
+}
+
+const fn default_margin() -> f64 {
+    5.
+}
+
+impl Default for RegistrationMarks {
+    fn default() -> Self {
+        Self {
+            shape: default_shape(),
+            positions: default_positions(),
+            size: default_size(),
+            margin: default_margin(),
+            power: None,
+        }
+    }
+}
+
+impl RegistrationMarks {
+    /// Toolpaths for the marks around the artwork, or none if there is no artwork
+    pub fn toolpaths(&self, artwork: &[Toolpath], tolerance: f64) -> Vec<Toolpath> {
+        let Some(bounds) = bounding_box(artwork, tolerance) else {
+            return vec![];
+        };
+        let bounds = bounds.inflate(self.margin, self.margin);
+        let center = bounds.center();
+        let half_size = bounds.size() / 2.;
+
+        self.positions
+            .iter()
+            .flat_map(|position| {
+                let [x, y] = position.sides();
+                let at = center + vector(x * half_size.width, y * half_size.height);
+                let lines = match self.shape {
+                    MarkShape::Cross => vec![
+                        vec![
+                            at - vector(self.size / 2., 0.),
+                            at + vector(self.size / 2., 0.),
+                        ],
+                        vec![
+                            at - vector(0., self.size / 2.),
+                            at + vector(0., self.size / 2.),
+                        ],
+                    ],
+                    MarkShape::Corner if x != 0. && y != 0. => vec![vec![
+                        at - vector(x * self.size, 0.),
+                        at,
+                        at - vector(0., y * self.size),
+                    ]],
+                    MarkShape::Corner => vec![vec![at, at + vector(x, y) * self.size]],
+                };
+                lines
+                    .into_iter()
+                    .filter_map(|line| Toolpath::from_polyline(&line))
+                    .enumerate()
+                    .map(|(i, mut toolpath)| {
+                        if i == 0 {
+                            toolpath.comments.push("registration mark".to_string());
+                        }
+                        toolpath
+                    })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use lyon_geom::point;
+
+    #[test]
+    fn marks_surround_artwork() {
+        let artwork = [Toolpath::from_polyline(&[point(0., 0.), point(100., 50.)]).unwrap()];
+        let crosses = RegistrationMarks::default().toolpaths(&artwork, 0.1);
+        // Two lines for each corner
+        assert_eq!(crosses.len(), 8);
+        assert_eq!(crosses[0].from, point(-10., 55.));
+        assert_eq!(crosses[0].to(), point(0., 55.));
+        assert_eq!(crosses[0].comments, ["registration mark"]);
+        assert!(crosses[1].comments.is_empty());
+
+        let corners = RegistrationMarks {
+            shape: MarkShape::Corner,
+            positions: vec![MarkPosition::BottomLeft, MarkPosition::Right],
+            margin: 0.,
+            ..Default::default()
+        }
+        .toolpaths(&artwork, 0.1);
+        assert_eq!(
+            corners[0].flattened(0.1),
+            [point(10., 0.), point(0., 0.), point(0., 10.)]
+        );
+        assert_eq!(
+            corners[1].flattened(0.1),
+            [point(100., 25.), point(110., 25.)]
+        );
+
+        assert!(RegistrationMarks::default().toolpaths(&[], 0.1).is_empty());
+    }
+}
@@ -0,0 +1,79 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The stock being cut, which the depth of every pass and the Z it goes down to are worked out
+/// from, see [`crate::ConversionConfig::material`]
+///
+/// Sequences get the results as `{depth}` and `{z}`, so they don't need a Z written into each of
+/// them by hand for every material.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Material {
+    /// Thickness in millimeters, 0 when it isn't known, which leaves depths out
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub thickness: f64,
+    /// Z coordinate of the top of the material in millimeters, where depths are measured from
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub surface_z: f64,
+    /// How far in millimeters to cut past the bottom of the material, so parts come out cleanly
+    /// when the material isn't perfectly flat
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub overcut: f64,
+}
+
+impl Material {
+    /// Final depth below the surface in millimeters: `engrave_depth` for engraving, which is
+    /// never deeper than the material, or the thickness and overcut for cutting through when it
+    /// is 0
+    pub fn depth(&self, engrave_depth: f64) -> f64 {
+        if engrave_depth > 0. {
+            if self.thickness > 0. {
+                engrave_depth.min(self.thickness)
+            } else {
+                engrave_depth
+            }
+        } else if self.thickness > 0. {
+            self.thickness + self.overcut.max(0.)
+        } else {
+            0.
+        }
+    }
+
+    /// Z coordinate `depth` millimeters below the surface
+    pub fn z(&self, depth: f64) -> f64 {
+        self.surface_z - depth
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ConversionConfig;
+
+    #[test]
+    fn cuts_go_through_and_engravings_stop_partway() {
+        let material = Material {
+            thickness: 6.,
+            surface_z: 10.,
+            overcut: 0.5,
+        };
+        assert_eq!(material.depth(0.), 6.5);
+        assert_eq!(material.depth(1.), 1.);
+        assert_eq!(material.depth(8.), 6.);
+        assert_eq!(Material::default().depth(0.), 0.);
+
+        let config = ConversionConfig {
+            material,
+            passes: 2,
+            ..Default::default()
+        };
+        assert_eq!([config.pass_depth(1), config.pass_depth(2)], [3.25, 6.5]);
+        assert_eq!(config.pass_z(2), 3.5);
+
+        let engrave = ConversionConfig {
+            engrave_depth: 1.,
+            ..config
+        };
+        assert_eq!([engrave.pass_z(1), engrave.pass_z(2)], [9.5, 9.]);
+    }
+}
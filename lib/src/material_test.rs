@@ -0,0 +1,778 @@
+use g_code::emit::Token;
+use log::warn;
+use lyon_geom::{euclid::default::Transform2D, point, vector, Angle, ArcFlags, Point, SvgArc};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    calibration::BacklashCompensation,
+    converter::machine_transform,
+    turtle::{GCodeTurtle, StayDown, Turtle},
+    ConversionConfig, Machine, Segment, StrokeFont, Toolpath,
+};
+
+/// What is drawn in each cell of a [`MaterialTest`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum TestShape {
+    /// Outline of a square
+    Square,
+    /// Square filled with back-and-forth lines this far apart in millimeters (i.e. the tool width)
+    ///
+    /// A spacing of 0 or less draws a single line along the bottom of the cell.
+    Hatch { spacing: f64 },
+    /// A single line across the middle of the cell
+    Line,
+}
+
+/// A grid of cells, each drawn with its own tool power and feedrate, for dialing in the settings
+/// for a new material
+///
+/// Power goes up from left to right and feedrate from bottom to top. Each row and column is
+/// labeled with its value, so the cell that came out best can be read off the material.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MaterialTest {
+    /// Tool power of the first and last column, available in sequences as `{power}`
+    pub power: [f64; 2],
+    /// Feedrate in millimeters / minute of the first and last row
+    pub feedrate: [f64; 2],
+    pub columns: usize,
+    pub rows: usize,
+    /// Width and height of each cell in millimeters
+    pub cell_size: f64,
+    /// Space between cells and labels in millimeters
+    pub gap: f64,
+    pub shape: TestShape,
+    /// Height of the label text in millimeters, 0 to leave out the labels
+    pub label_height: f64,
+    /// Tool power for engraving the labels (i.e. low enough to only mark the surface)
+    ///
+    /// Falls back on the power of the first column.
+    pub label_power: Option<f64>,
+}
+
+impl Default for MaterialTest {
+    fn default() -> Self {
+        Self {
+            power: [10., 100.],
+            feedrate: [300., 3000.],
+            columns: 5,
+            rows: 5,
+            cell_size: 5.,
+            gap: 2.,
+            shape: TestShape::Hatch { spacing: 0.2 },
+            label_height: 2.5,
+            label_power: None,
+        }
+    }
+}
+
+/// A cell of a [`MaterialTest`] with the toolpaths to draw it with
+struct Cell {
+    power: f64,
+    feedrate: f64,
+    toolpaths: Vec<Toolpath>,
+}
+
+impl MaterialTest {
+    /// Emit g-code for the grid, with its bottom left corner at [`ConversionConfig::origin`]
+    ///
+    /// Each cell is drawn as its own pass, so sequences can use its `{power}`. The labels are
+    /// drawn first as pass 0 at [`ConversionConfig::feedrate`].
+    pub fn to_program<'input>(
+        &self,
+        config: &ConversionConfig,
+        machine: Machine<'input>,
+    ) -> Vec<Token<'input>> {
+        let origin = vector(
+            config.origin[0].unwrap_or_default(),
+            config.origin[1].unwrap_or_default(),
+        );
+        let transform = machine_transform(config, &machine);
+        if !machine.uses_power() && self.power[0] != self.power[1] {
+            warn!("No pass or path sequence uses {{power}}, so every column is drawn at the same power");
+        }
+        let draw = |toolpath: &Toolpath| {
+            let toolpath = toolpath.translated(origin);
+            match transform {
+                Some(transform) => toolpath.transformed(&transform),
+                None => toolpath,
+            }
+        };
+
+        let mut turtle = GCodeTurtle {
+            backlash: BacklashCompensation::new(machine.backlash()),
+            machine,
+            tolerance: config.tolerance,
+            feedrate: config.feedrate,
+            program: vec![],
+            stay_down: StayDown::new(config.stay_down_gap, config.stay_down_travel),
+            power: None,
+            path_ahead: vec![],
+        };
+        turtle.begin();
+        let (labels, cells) = self.layout();
+        if !labels.is_empty() {
+            turtle.comment("labels".to_string());
+            turtle.pass_begin(0, cells.len(), self.label_power.or(Some(self.power[0])));
+            for (i, label) in labels.iter().enumerate() {
+                turtle.path_begin(i + 1, labels.len());
+                draw(label).draw(&mut turtle);
+                turtle.path_end();
+            }
+            turtle.pass_end();
+        }
+        for (pass, cell) in cells.iter().enumerate() {
+            turtle.comment(format!(
+                "power {}, feedrate {}",
+                label_value(cell.power),
+                label_value(cell.feedrate)
+            ));
+            turtle.feedrate = cell.feedrate;
+            turtle.pass_begin(pass + 1, cells.len(), Some(cell.power));
+            for (i, toolpath) in cell.toolpaths.iter().enumerate() {
+                turtle.path_begin(i + 1, cell.toolpaths.len());
+                draw(toolpath).draw(&mut turtle);
+                turtle.path_end();
+            }
+            turtle.pass_end();
+        }
+        turtle.end();
+
+        turtle.program
+    }
+
+    /// Toolpaths for the labels and each cell, from the bottom left row by row, relative to the
+    /// bottom left corner of the grid and its labels
+    fn layout(&self) -> (Vec<Toolpath>, Vec<Cell>) {
+        let font = &StrokeFont::bundled()[0];
+        let power_labels = (0..self.columns)
+            .map(|column| format!("S{}", label_value(step(self.power, column, self.columns))))
+            .collect::<Vec<_>>();
+        let feedrate_labels = (0..self.rows)
+            .map(|row| format!("F{}", label_value(step(self.feedrate, row, self.rows))))
+            .collect::<Vec<_>>();
+
+        // Feedrates are labeled to the left of the rows, and powers below the columns
+        let labeled = self.label_height > 0.;
+        let grid_corner = if labeled {
+            let widest = feedrate_labels
+                .iter()
+                .map(|label| text_width(font, label, self.label_height))
+                .fold(0., f64::max);
+            point(widest + self.gap, self.label_height + self.gap)
+        } else {
+            point(0., 0.)
+        };
+        let pitch = self.cell_size + self.gap;
+        let cell_corner =
+            |row: usize, column: usize| grid_corner + vector(column as f64, row as f64) * pitch;
+
+        let mut labels = vec![];
+        if labeled {
+            for (row, label) in feedrate_labels.iter().enumerate() {
+                let baseline = (self.cell_size - self.label_height) / 2.;
+                labels.extend(text(
+                    font,
+                    label,
+                    point(0., cell_corner(row, 0).y + baseline),
+                    self.label_height,
+                ));
+            }
+            for (column, label) in power_labels.iter().enumerate() {
+                let centering = (self.cell_size - text_width(font, label, self.label_height)) / 2.;
+                labels.extend(text(
+                    font,
+                    label,
+                    point(cell_corner(0, column).x + centering, 0.),
+                    self.label_height,
+                ));
+            }
+        }
+
+        let cells = (0..self.rows)
+            .flat_map(|row| (0..self.columns).map(move |column| (row, column)))
+            .map(|(row, column)| Cell {
+                power: step(self.power, column, self.columns),
+                feedrate: step(self.feedrate, row, self.rows),
+                toolpaths: self.shape_toolpaths(cell_corner(row, column)),
+            })
+            .collect();
+        (labels, cells)
+    }
+
+    fn shape_toolpaths(&self, corner: Point<f64>) -> Vec<Toolpath> {
+        let size = self.cell_size;
+        let polylines = match self.shape {
+            TestShape::Square => vec![vec![
+                corner,
+                corner + vector(size, 0.),
+                corner + vector(size, size),
+                corner + vector(0., size),
+                corner,
+            ]],
+            TestShape::Hatch { spacing } => {
+                let lines = if spacing > 0. {
+                    (size / spacing).floor().max(0.) as usize + 1
+                } else {
+                    1
+                };
+                let spacing = if lines > 1 {
+                    size / (lines - 1) as f64
+                } else {
+                    0.
+                };
+                // Back and forth, so the whole cell is one path
+                vec![(0..lines)
+                    .flat_map(|i| {
+                        let y = i as f64 * spacing;
+                        let ends = [corner + vector(0., y), corner + vector(size, y)];
+                        if i % 2 == 0 {
+                            ends
+                        } else {
+                            [ends[1], ends[0]]
+                        }
+                    })
+                    .collect()]
+            }
+            TestShape::Line => vec![vec![
+                corner + vector(0., size / 2.),
+                corner + vector(size, size / 2.),
+            ]],
+        };
+        polylines
+            .iter()
+            .filter_map(|polyline| Toolpath::from_polyline(polyline))
+            .collect()
+    }
+}
+
+/// Distance between the ramp of a [`FocusTest`] and its ticks in millimeters, so they don't blur
+/// into it
+const TICK_GAP: f64 = 1.;
+
+/// A line cut while the Z axis moves across a range, for finding the height at which a laser is
+/// in focus
+///
+/// The line is thinnest where the laser is in focus. Ticks are cut below it at regular heights,
+/// each at the height it marks, so the height can be counted off the material.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FocusTest {
+    /// Height of the Z axis in millimeters at the start and end of the line
+    pub z: [f64; 2],
+    /// Length of the line in millimeters
+    pub length: f64,
+    /// Change in height between ticks in millimeters, 0 to leave out the ticks
+    ///
+    /// Every fifth tick is twice as long, to make them easier to count.
+    pub tick_spacing: f64,
+    /// Length of each tick in millimeters
+    pub tick_length: f64,
+    /// Tool power, available in sequences as `{power}`
+    pub power: Option<f64>,
+}
+
+impl Default for FocusTest {
+    fn default() -> Self {
+        Self {
+            z: [-5., 5.],
+            length: 100.,
+            tick_spacing: 1.,
+            tick_length: 2.,
+            power: None,
+        }
+    }
+}
+
+impl FocusTest {
+    /// Emit g-code for the ramp, starting at [`ConversionConfig::origin`] and going right at
+    /// [`ConversionConfig::feedrate`]
+    ///
+    /// The Z axis is moved to the start of the range with a rapid move, so the whole range must be
+    /// clear of the material and anything on it.
+    pub fn to_program<'input>(
+        &self,
+        config: &ConversionConfig,
+        machine: Machine<'input>,
+    ) -> Vec<Token<'input>> {
+        let origin = point(
+            config.origin[0].unwrap_or_default(),
+            config.origin[1].unwrap_or_default(),
+        );
+        let transform = machine_transform(config, &machine);
+        let place = |p: Point<f64>| transform.map_or(p, |transform| transform.transform_point(p));
+
+        let mut turtle = GCodeTurtle {
+            backlash: BacklashCompensation::new(machine.backlash()),
+            machine,
+            tolerance: config.tolerance,
+            feedrate: config.feedrate,
+            program: vec![],
+            stay_down: None,
+            power: None,
+            path_ahead: vec![],
+        };
+        turtle.begin();
+        turtle.pass_begin(1, 1, self.power);
+        let [start, end] = self.z;
+        turtle.comment(format!("ramp from Z{start} to Z{end}"));
+        turtle.move_to(place(origin));
+        turtle.move_z_to(start);
+        turtle.line_z_to(place(origin + vector(self.length, 0.)), Some(end));
+
+        for (i, (along, z)) in self.ticks().into_iter().enumerate() {
+            let length = if i % 5 == 0 {
+                2. * self.tick_length
+            } else {
+                self.tick_length
+            };
+            let top = origin + vector(along, -TICK_GAP);
+            turtle.comment(format!("tick at Z{}", label_value(z)));
+            turtle.move_to(place(top));
+            turtle.move_z_to(z);
+            turtle.line_to(place(top - vector(0., length)));
+        }
+        turtle.pass_end();
+        turtle.end();
+
+        turtle.program
+    }
+
+    /// Distance along the line and height of each tick
+    fn ticks(&self) -> Vec<(f64, f64)> {
+        let [start, end] = self.z;
+        let range = end - start;
+        if self.tick_spacing <= 0. {
+            return vec![];
+        }
+        if range == 0. {
+            return vec![(0., start)];
+        }
+        // Leeway for rounding error, so a tick lands on the end of the range
+        let count = (range.abs() / self.tick_spacing + 1e-9).floor() as usize + 1;
+        (0..count)
+            .map(|i| {
+                let z = start + range.signum() * i as f64 * self.tick_spacing;
+                (self.length * (z - start) / range, z)
+            })
+            .collect()
+    }
+}
+
+/// What a [`CalibrationTest`] draws
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum CalibrationPattern {
+    /// Squares a quarter, half, and all of the size wide, sharing their bottom left corner, for
+    /// measuring how far each axis really moves (i.e. to set its steps per millimeter)
+    AxisScale,
+    /// A square, a diamond touching the middle of its sides, and a circle inside the diamond,
+    /// for checking that the axes are square to each other and move together smoothly
+    CircleDiamondSquare,
+    /// Two rows of ticks along each axis, one drawn moving forwards and the other backwards,
+    /// which are offset from each other by the backlash of the axis
+    ///
+    /// The first tick of each row may be reached from either side, so compare the ones after it.
+    Backlash,
+    /// A comb of strips of the same width cut out of a square
+    ///
+    /// Each cut takes away its kerf, so pushed together, the strips are narrower than the size by
+    /// the kerf times the number of strips.
+    KerfComb,
+}
+
+/// A pattern for calibrating a machine, drawn in one pass with the same settings as a conversion
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CalibrationTest {
+    pub pattern: CalibrationPattern,
+    /// Width and height of the pattern in millimeters
+    pub size: f64,
+    /// Number of ticks in each row of [`CalibrationPattern::Backlash`], or strips of
+    /// [`CalibrationPattern::KerfComb`]
+    pub count: usize,
+    /// Tool power, available in sequences as `{power}`
+    pub power: Option<f64>,
+}
+
+impl Default for CalibrationTest {
+    fn default() -> Self {
+        Self {
+            pattern: CalibrationPattern::CircleDiamondSquare,
+            size: 50.,
+            count: 10,
+            power: None,
+        }
+    }
+}
+
+impl CalibrationTest {
+    /// Emit g-code for the pattern, with its bottom left corner at [`ConversionConfig::origin`]
+    /// and drawn at [`ConversionConfig::feedrate`]
+    ///
+    /// Toolpaths are drawn in the order they are laid out, without staying down between them, so
+    /// each is reached from the side the pattern needs.
+    pub fn to_program<'input>(
+        &self,
+        config: &ConversionConfig,
+        machine: Machine<'input>,
+    ) -> Vec<Token<'input>> {
+        let origin = vector(
+            config.origin[0].unwrap_or_default(),
+            config.origin[1].unwrap_or_default(),
+        );
+        let transform = machine_transform(config, &machine);
+
+        let mut turtle = GCodeTurtle {
+            backlash: BacklashCompensation::new(machine.backlash()),
+            machine,
+            tolerance: config.tolerance,
+            feedrate: config.feedrate,
+            program: vec![],
+            stay_down: None,
+            power: None,
+            path_ahead: vec![],
+        };
+        turtle.begin();
+        turtle.pass_begin(1, 1, self.power);
+        let toolpaths = self.toolpaths();
+        for (i, toolpath) in toolpaths.iter().enumerate() {
+            let toolpath = toolpath.translated(origin);
+            let toolpath = match transform {
+                Some(transform) => toolpath.transformed(&transform),
+                None => toolpath,
+            };
+            turtle.path_begin(i + 1, toolpaths.len());
+            toolpath.draw(&mut turtle);
+            turtle.path_end();
+        }
+        turtle.pass_end();
+        turtle.end();
+
+        turtle.program
+    }
+
+    /// Toolpaths of the pattern in the order they are drawn, relative to its bottom left corner
+    fn toolpaths(&self) -> Vec<Toolpath> {
+        let size = self.size;
+        let square = |size: f64| {
+            vec![
+                point(0., 0.),
+                point(size, 0.),
+                point(size, size),
+                point(0., size),
+                point(0., 0.),
+            ]
+        };
+        let commented = |polyline: &[Point<f64>], comment: String| {
+            Toolpath::from_polyline(polyline).map(|mut toolpath| {
+                toolpath.comments.push(comment);
+                toolpath
+            })
+        };
+
+        match self.pattern {
+            CalibrationPattern::AxisScale => [0.25, 0.5, 1.]
+                .into_iter()
+                .filter_map(|part| {
+                    let side = size * part;
+                    commented(
+                        &square(side),
+                        format!("square {} mm wide", label_value(side)),
+                    )
+                })
+                .collect(),
+            CalibrationPattern::CircleDiamondSquare => {
+                let half = size / 2.;
+                let center = point(half, half);
+                let radius = half / 2f64.sqrt();
+                let diamond = [
+                    point(half, 0.),
+                    point(size, half),
+                    point(half, size),
+                    point(0., half),
+                    point(half, 0.),
+                ];
+                let mut circle = Toolpath::new(center + vector(radius, 0.));
+                circle.comments.push("circle".to_string());
+                let opposite = center - vector(radius, 0.);
+                for (from, to) in [(circle.from, opposite), (opposite, circle.from)] {
+                    circle.segments.push(Segment::Arc(SvgArc {
+                        from,
+                        to,
+                        radii: vector(radius, radius),
+                        x_rotation: Angle::zero(),
+                        flags: ArcFlags {
+                            large_arc: false,
+                            sweep: true,
+                        },
+                    }));
+                }
+                commented(&square(size), "square".to_string())
+                    .into_iter()
+                    .chain(commented(&diamond, "diamond".to_string()))
+                    .chain((radius > 0.).then_some(circle))
+                    .collect()
+            }
+            CalibrationPattern::Backlash => {
+                // The X axis rows along the bottom, then the Y axis rows above them on the left
+                let length = size / 10.;
+                let pitch = size / self.count.saturating_sub(1).max(1) as f64;
+                let along = |i: usize| i as f64 * pitch;
+                let mut toolpaths = vec![];
+                for (axis, forwards) in [("X", true), ("X", false), ("Y", true), ("Y", false)] {
+                    let row = if forwards { length } else { 0. };
+                    let mut ticks = (0..self.count).collect::<Vec<_>>();
+                    if !forwards {
+                        ticks.reverse();
+                    }
+                    let direction = if forwards { "forwards" } else { "backwards" };
+                    for (i, tick) in ticks.into_iter().enumerate() {
+                        let line = if axis == "X" {
+                            [point(along(tick), row), point(along(tick), row + length)]
+                        } else {
+                            let y = 3. * length + along(tick);
+                            [point(row, y), point(row + length, y)]
+                        };
+                        let Some(mut toolpath) = Toolpath::from_polyline(&line) else {
+                            continue;
+                        };
+                        if i == 0 {
+                            toolpath
+                                .comments
+                                .push(format!("{axis} axis moving {direction}"));
+                        }
+                        toolpaths.push(toolpath);
+                    }
+                }
+                toolpaths
+            }
+            CalibrationPattern::KerfComb => {
+                let strips = self.count.max(1);
+                let width = size / strips as f64;
+                let height = size / 2.;
+                let outline = [
+                    point(0., 0.),
+                    point(size, 0.),
+                    point(size, height),
+                    point(0., height),
+                    point(0., 0.),
+                ];
+                // Up and down in turn, so each divider starts next to where the last one ended
+                commented(
+                    &outline,
+                    format!("{strips} strips {} mm wide", label_value(width)),
+                )
+                .into_iter()
+                .chain((1..strips).filter_map(|i| {
+                    let x = i as f64 * width;
+                    let ends = [point(x, 0.), point(x, height)];
+                    Toolpath::from_polyline(&if i % 2 == 0 { [ends[1], ends[0]] } else { ends })
+                }))
+                .collect()
+            }
+        }
+    }
+}
+
+/// Value of the `i`th of `n` steps from the start to the end of a range
+fn step([start, end]: [f64; 2], i: usize, n: usize) -> f64 {
+    if n > 1 {
+        start + (end - start) * i as f64 / (n - 1) as f64
+    } else {
+        start
+    }
+}
+
+/// Rounded to a tenth, which is as fine as power and feedrate are usually set
+fn label_value(value: f64) -> f64 {
+    (value * 10.).round() / 10.
+}
+
+/// Font size in millimeters, so capitals are `height` tall
+fn font_size(font: &StrokeFont, height: f64) -> f64 {
+    let cap_height = font
+        .strokes('H', &Transform2D::identity())
+        .iter()
+        .flatten()
+        .fold(0., |top, p| p.y.min(top));
+    if cap_height < 0. {
+        height / -cap_height
+    } else {
+        0.
+    }
+}
+
+fn text_width(font: &StrokeFont, text: &str, height: f64) -> f64 {
+    let size = font_size(font, height);
+    text.chars().filter_map(|c| font.advance(c)).sum::<f64>() * size
+}
+
+/// Single-stroke toolpaths for a line of text starting at `origin` on its baseline
+fn text(font: &StrokeFont, text: &str, origin: Point<f64>, height: f64) -> Vec<Toolpath> {
+    let size = font_size(font, height);
+    let mut x = origin.x;
+    let mut toolpaths = vec![];
+    for c in text.chars() {
+        let Some(advance) = font.advance(c) else {
+            continue;
+        };
+        // Glyphs point down, millimeters point up
+        let transform = Transform2D::scale(size, -size).then_translate(vector(x, origin.y));
+        toolpaths.extend(
+            font.strokes(c, &transform)
+                .iter()
+                .filter_map(|stroke| Toolpath::from_polyline(stroke)),
+        );
+        x += advance * size;
+    }
+    toolpaths
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Hooks, SnippetTemplate, SupportedFunctionality};
+
+    #[test]
+    fn each_cell_is_drawn_with_its_own_power_and_feedrate() {
+        let test = MaterialTest {
+            power: [20., 60.],
+            feedrate: [1000., 2000.],
+            columns: 3,
+            rows: 2,
+            shape: TestShape::Square,
+            ..Default::default()
+        };
+        let (labels, cells) = test.layout();
+        assert!(!labels.is_empty());
+        assert_eq!(
+            cells
+                .iter()
+                .map(|cell| (cell.power, cell.feedrate))
+                .collect::<Vec<_>>(),
+            [
+                (20., 1000.),
+                (40., 1000.),
+                (60., 1000.),
+                (20., 2000.),
+                (40., 2000.),
+                (60., 2000.)
+            ]
+        );
+        // Labels stay clear of the cells
+        let label_top = labels
+            .iter()
+            .flat_map(|label| label.flattened(0.1))
+            .filter(|p| p.x >= cells[0].toolpaths[0].from.x)
+            .fold(f64::MIN, |top, p| top.max(p.y));
+        assert!(label_top < cells[0].toolpaths[0].from.y);
+
+        let machine = Machine::new(Default::default(), None, None, None, None).with_hooks(Hooks {
+            pass_begin: Some(SnippetTemplate::parse("M3 S{power}").unwrap()),
+            ..Default::default()
+        });
+        let program = test.to_program(&ConversionConfig::default(), machine);
+        let mut gcode = String::new();
+        g_code::emit::format_gcode_fmt(&program, Default::default(), &mut gcode).unwrap();
+        for power in ["M3 S20", "M3 S40", "M3 S60"] {
+            assert_eq!(
+                gcode.matches(power).count(),
+                2 + (power == "M3 S20") as usize
+            );
+        }
+        assert!(gcode.contains("F2000"));
+    }
+
+    #[test]
+    fn hatch_without_spacing_is_a_single_line() {
+        let test = MaterialTest {
+            shape: TestShape::Hatch { spacing: 0. },
+            ..Default::default()
+        };
+        let toolpaths = test.shape_toolpaths(point(0., 0.));
+        assert_eq!(toolpaths.len(), 1);
+        assert_eq!(toolpaths[0].segments.len(), 1);
+
+        let machine = Machine::new(Default::default(), None, None, None, None);
+        assert!(!machine.uses_power());
+        let machine = machine.with_hooks(Hooks {
+            path_begin: Some(SnippetTemplate::parse("M4 S{power}").unwrap()),
+            ..Default::default()
+        });
+        assert!(machine.uses_power());
+    }
+
+    #[test]
+    fn focus_ramp_moves_z_along_the_line() {
+        let test = FocusTest {
+            z: [2., -3.],
+            length: 50.,
+            ..Default::default()
+        };
+        assert_eq!(
+            test.ticks(),
+            [
+                (0., 2.),
+                (10., 1.),
+                (20., 0.),
+                (30., -1.),
+                (40., -2.),
+                (50., -3.)
+            ]
+        );
+
+        let machine = Machine::new(Default::default(), None, None, None, None);
+        let program = test.to_program(&ConversionConfig::default(), machine);
+        let mut gcode = String::new();
+        g_code::emit::format_gcode_fmt(&program, Default::default(), &mut gcode).unwrap();
+        assert!(gcode.contains("G0 Z2\nG1 X50 Y0 Z-3 F300"), "{gcode}");
+        // The first tick is twice as long
+        assert!(gcode.contains("G0 X0 Y-1\nG0 Z2\nG1 X0 Y-5"), "{gcode}");
+        assert!(gcode.contains("G0 X10 Y-1\nG0 Z1\nG1 X10 Y-3"), "{gcode}");
+    }
+
+    #[test]
+    fn calibration_patterns_are_drawn_in_the_order_they_need() {
+        let toolpaths = |pattern| {
+            CalibrationTest {
+                pattern,
+                count: 3,
+                ..Default::default()
+            }
+            .toolpaths()
+        };
+        // The second row of ticks along each axis is reached from the other side
+        let backlash = toolpaths(CalibrationPattern::Backlash);
+        assert_eq!(
+            backlash[..6]
+                .iter()
+                .map(|tick| tick.from.x)
+                .collect::<Vec<_>>(),
+            [0., 25., 50., 50., 25., 0.]
+        );
+        assert!(backlash[6..9]
+            .windows(2)
+            .all(|ticks| ticks[0].from.y < ticks[1].from.y));
+        // An outline and the cuts between 3 strips
+        assert_eq!(toolpaths(CalibrationPattern::KerfComb).len(), 3);
+
+        let machine = Machine::new(
+            SupportedFunctionality {
+                circular_interpolation: true,
+                ..Default::default()
+            },
+            None,
+            None,
+            None,
+            None,
+        );
+        let program = CalibrationTest::default().to_program(&ConversionConfig::default(), machine);
+        let mut gcode = String::new();
+        g_code::emit::format_gcode_fmt(&program, Default::default(), &mut gcode).unwrap();
+        assert!(gcode.contains("G1 X50 Y25"), "{gcode}");
+        assert!(gcode.contains("G2"), "{gcode}");
+    }
+}
@@ -0,0 +1,225 @@
+use std::fmt::{self, Display};
+
+use log::warn;
+
+/// Placeholder for the 1-based row number, unless the data has a column of the same name
+const ROW_NUMBER_PLACEHOLDER: &str = "row";
+
+/// Values to substitute into `{{column}}` placeholders of an SVG template, one set per row
+///
+/// Used for engraving batches of name tags, serial numbers, and the like.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MergeData {
+    columns: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MergeError {
+    /// A quoted field starting on this line (1-based) is never closed
+    UnterminatedQuote { line: usize },
+    /// A row (1-based, excluding the header) has a different number of fields than the header
+    RowLength {
+        row: usize,
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl Display for MergeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnterminatedQuote { line } => {
+                write!(f, "quoted field starting on line {line} is never closed")
+            }
+            Self::RowLength {
+                row,
+                expected,
+                found,
+            } => write!(f, "row {row} has {found} fields, expected {expected}"),
+        }
+    }
+}
+
+impl std::error::Error for MergeError {}
+
+impl MergeData {
+    /// Read comma-separated values, where the first record names the columns
+    ///
+    /// Fields may be quoted to contain commas, newlines, or (doubled) quotes. Blank lines are
+    /// skipped.
+    ///
+    /// <https://www.rfc-editor.org/rfc/rfc4180>
+    pub fn from_csv(csv: &str) -> Result<Self, MergeError> {
+        let mut records = parse_csv(csv)?.into_iter();
+        let columns = records
+            .next()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|column| column.trim().to_string())
+            .collect::<Vec<_>>();
+        let rows = records.collect::<Vec<_>>();
+        if let Some((i, row)) = rows
+            .iter()
+            .enumerate()
+            .find(|(_, row)| row.len() != columns.len())
+        {
+            return Err(MergeError::RowLength {
+                row: i + 1,
+                expected: columns.len(),
+                found: row.len(),
+            });
+        }
+        Ok(Self { columns, rows })
+    }
+
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    /// Number of rows, excluding the header
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Replace the `{{column}}` placeholders of a template with the values of a row, escaped for XML
+    ///
+    /// `{{row}}` is the 1-based row number unless there is a column of that name. Placeholders for
+    /// unknown columns are left as they are.
+    pub fn merge(&self, template: &str, row: usize) -> String {
+        let mut merged = String::with_capacity(template.len());
+        let mut rest = template;
+        while let Some(start) = rest.find("{{") {
+            let Some(len) = rest[start..].find("}}") else {
+                break;
+            };
+            let placeholder = &rest[start..start + len + 2];
+            let name = placeholder[2..placeholder.len() - 2].trim();
+            merged += &rest[..start];
+            match self.value(name, row) {
+                Some(value) => merged += &escape_xml(&value),
+                None => {
+                    warn!("There is no column for the placeholder {placeholder}");
+                    merged += placeholder;
+                }
+            }
+            rest = &rest[start + len + 2..];
+        }
+        merged += rest;
+        merged
+    }
+
+    fn value(&self, name: &str, row: usize) -> Option<String> {
+        match self.columns.iter().position(|column| column == name) {
+            Some(column) => Some(self.rows[row][column].clone()),
+            None => (name == ROW_NUMBER_PLACEHOLDER).then(|| (row + 1).to_string()),
+        }
+    }
+}
+
+fn parse_csv(csv: &str) -> Result<Vec<Vec<String>>, MergeError> {
+    let mut records = vec![];
+    let mut record = vec![];
+    let mut field = String::new();
+    let mut chars = csv.chars().peekable();
+    let mut line = 1;
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if field.is_empty() => {
+                let start = line;
+                loop {
+                    match chars.next() {
+                        Some('"') if chars.peek() == Some(&'"') => {
+                            chars.next();
+                            field.push('"');
+                        }
+                        Some('"') => break,
+                        Some(c) => {
+                            if c == '\n' {
+                                line += 1;
+                            }
+                            field.push(c);
+                        }
+                        None => return Err(MergeError::UnterminatedQuote { line: start }),
+                    }
+                }
+            }
+            ',' => record.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                line += 1;
+                record.push(std::mem::take(&mut field));
+                let finished = std::mem::take(&mut record);
+                // Skip blank lines
+                if finished != [""] {
+                    records.push(finished);
+                }
+            }
+            c => field.push(c),
+        }
+    }
+    record.push(field);
+    if record != [""] {
+        records.push(record);
+    }
+    Ok(records)
+}
+
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn csv_fields_can_be_quoted() {
+        let data = MergeData::from_csv(
+            "name, serial\r\n\"Doe, Jane\",0001\n\n\"Say \"\"hi\"\"\nthere\",0002",
+        )
+        .unwrap();
+        assert_eq!(data.columns(), ["name", "serial"]);
+        assert_eq!(
+            data.rows,
+            [["Doe, Jane", "0001"], ["Say \"hi\"\nthere", "0002"]]
+        );
+
+        assert_eq!(
+            MergeData::from_csv("a,b\n1\n"),
+            Err(MergeError::RowLength {
+                row: 1,
+                expected: 2,
+                found: 1
+            })
+        );
+        assert_eq!(
+            MergeData::from_csv("a\n1\n\"2\n"),
+            Err(MergeError::UnterminatedQuote { line: 3 })
+        );
+    }
+
+    #[test]
+    fn placeholders_are_replaced() {
+        let data = MergeData::from_csv("name,serial\nA&B,0001\nC,0002\n").unwrap();
+        let template = "<text>{{ name }} #{{serial}} ({{row}} of 2) {{missing}}</text>";
+        assert_eq!(
+            data.merge(template, 0),
+            "<text>A&amp;B #0001 (1 of 2) {{missing}}</text>"
+        );
+        assert_eq!(
+            data.merge(template, 1),
+            "<text>C #0002 (2 of 2) {{missing}}</text>"
+        );
+        assert_eq!(data.merge("{{name", 0), "{{name");
+    }
+}
@@ -0,0 +1,227 @@
+use std::f64::consts::TAU;
+
+use lyon_geom::{vector, LineSegment, Point};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Lines a full turn of a helix is drawn with when arcs aren't supported
+const HELIX_LINES_PER_TURN: usize = 16;
+/// Ramps along a path shorter than this plunge straight down instead, since they would go back and
+/// forth in place
+const MIN_RAMP_LENGTH: f64 = 0.01;
+/// Leeway in millimeters for a path to count as closed, and for a helix to fit inside it where it
+/// touches the path
+const SAME_POINT: f64 = 1e-6;
+
+/// How the tool goes down into the material at the start of a cut, see
+/// [`crate::MachineConfig::plunge`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Plunge {
+    /// Straight down, which small end mills can break on since they don't cut well at the tip
+    #[default]
+    Straight,
+    /// Down at an angle along the path it is about to cut, back and forth until it is deep enough,
+    /// ending back where the path starts
+    Ramp,
+    /// Down in circles inside a closed path, touching it where it starts, like a drill, for
+    /// pockets and holes
+    ///
+    /// Paths that are open, or too small for the circles, are ramped into instead.
+    Helix,
+}
+
+/// Points, each with its Z, of a ramp from `from_z` down to `to_z` that goes back and forth along
+/// the start of `path` at `slope` (the tangent of the ramp angle), ending where it started
+///
+/// Returns nothing if the path is too short to ramp along.
+pub(crate) fn ramp(
+    path: &[Point<f64>],
+    from_z: f64,
+    to_z: f64,
+    slope: f64,
+) -> Vec<(Point<f64>, f64)> {
+    let depth = from_z - to_z;
+    let Some(start) = path.first() else {
+        return vec![];
+    };
+    let leg = along(path, depth / slope);
+    let length = leg
+        .windows(2)
+        .map(|pair| (pair[1] - pair[0]).length())
+        .sum::<f64>();
+    if depth <= 0. || length < MIN_RAMP_LENGTH {
+        return vec![];
+    }
+
+    let mut points = vec![];
+    let mut z = from_z;
+    let mut at = *start;
+    let back = leg.iter().rev().copied().collect::<Vec<_>>();
+    // Going down the whole way, then back to the start at the bottom
+    while z > to_z || at != *start {
+        let leg = if at == *start { &leg } else { &back };
+        for point in &leg[1..] {
+            z = (z - (*point - at).length() * slope).max(to_z);
+            at = *point;
+            points.push((at, z));
+        }
+    }
+    points
+}
+
+/// The start of `path`, up to `length` along it
+fn along(path: &[Point<f64>], length: f64) -> Vec<Point<f64>> {
+    let mut points = path.first().copied().into_iter().collect::<Vec<_>>();
+    let mut left = length;
+    for pair in path.windows(2) {
+        let step = (pair[1] - pair[0]).length();
+        if step >= left {
+            points.push(pair[0].lerp(pair[1], left / step));
+            break;
+        }
+        left -= step;
+        points.push(pair[1]);
+    }
+    points
+}
+
+/// Center of a helix of `radius` inside the closed `path`, touching it where it starts, or
+/// nothing if the path isn't closed or the helix doesn't fit inside it
+pub(crate) fn helix_center(path: &[Point<f64>], radius: f64) -> Option<Point<f64>> {
+    let (start, end) = (*path.first()?, *path.last()?);
+    if radius <= 0. || (end - start).length() > SAME_POINT {
+        return None;
+    }
+    let direction = path
+        .iter()
+        .map(|point| *point - start)
+        .find(|direction| direction.length() > SAME_POINT)?
+        .normalize();
+    // Counterclockwise paths have their inside on the left
+    let area = path
+        .windows(2)
+        .map(|pair| pair[0].to_vector().cross(pair[1].to_vector()))
+        .sum::<f64>();
+    let inward = if area > 0. {
+        vector(-direction.y, direction.x)
+    } else {
+        vector(direction.y, -direction.x)
+    };
+    let center = start + inward * radius;
+    path.windows(2)
+        .all(|pair| {
+            let side = LineSegment {
+                from: pair[0],
+                to: pair[1],
+            };
+            side.distance_to_point(center) >= radius - SAME_POINT
+        })
+        .then_some(center)
+}
+
+/// Z at the end of each full turn of a helix of `radius` from `from_z` down to `to_z`, going down
+/// by the same amount in each turn no steeper than `slope`, and a last turn at the bottom to clear
+/// it out
+pub(crate) fn helix_turns(from_z: f64, to_z: f64, radius: f64, slope: f64) -> Vec<f64> {
+    let depth = from_z - to_z;
+    if depth <= 0. {
+        return vec![];
+    }
+    let turns = (depth / (TAU * radius * slope)).ceil().max(1.) as usize;
+    (1..=turns)
+        .map(|turn| from_z - depth * turn as f64 / turns as f64)
+        .chain([to_z])
+        .collect()
+}
+
+/// Points, each with its Z, of a helix around `center` that starts and ends at `start`, as lines
+/// for machines that can't draw arcs
+pub(crate) fn helix_lines(
+    start: Point<f64>,
+    center: Point<f64>,
+    from_z: f64,
+    turns: &[f64],
+) -> Vec<(Point<f64>, f64)> {
+    let radius = start - center;
+    let mut z = from_z;
+    let mut points = vec![];
+    for end_z in turns {
+        for i in 1..=HELIX_LINES_PER_TURN {
+            let fraction = i as f64 / HELIX_LINES_PER_TURN as f64;
+            let (sin, cos) = (TAU * fraction).sin_cos();
+            let offset = vector(
+                radius.x * cos - radius.y * sin,
+                radius.x * sin + radius.y * cos,
+            );
+            let point = if i == HELIX_LINES_PER_TURN {
+                start
+            } else {
+                center + offset
+            };
+            points.push((point, z + (end_z - z) * fraction));
+        }
+        z = *end_z;
+    }
+    points
+}
+
+#[cfg(test)]
+mod test {
+    use lyon_geom::point;
+
+    use super::*;
+
+    #[test]
+    fn ramps_go_back_and_forth_no_steeper_than_the_angle() {
+        let path = [point(0., 0.), point(10., 0.), point(10., 10.)];
+        let slope = 0.1;
+        // 2 mm down takes 20 mm, around the corner and back
+        let points = ramp(&path, 0., -2., slope);
+        assert_eq!(
+            points,
+            [
+                (point(10., 0.), -1.),
+                (point(10., 10.), -2.),
+                (point(10., 0.), -2.),
+                (point(0., 0.), -2.)
+            ]
+        );
+        // The path is shorter than the ramp, so it goes back and forth along all of it
+        let points = ramp(&path[..2], 0., -3., slope);
+        assert_eq!(points.last(), Some(&(point(0., 0.), -3.)));
+        assert_eq!(points.len(), 4);
+        assert!(ramp(&[point(0., 0.)], 0., -3., slope).is_empty());
+    }
+
+    #[test]
+    fn helixes_go_inside_closed_paths() {
+        let square = [
+            point(5., 0.),
+            point(10., 0.),
+            point(10., 10.),
+            point(0., 10.),
+            point(0., 0.),
+            point(5., 0.),
+        ];
+        let clockwise = square.iter().rev().copied().collect::<Vec<_>>();
+        assert_eq!(helix_center(&square, 1.), Some(point(5., 1.)));
+        assert_eq!(helix_center(&clockwise, 1.), Some(point(5., 1.)));
+        // Open paths have no inside, and the helix has to fit
+        assert_eq!(helix_center(&square[..4], 1.), None);
+        assert_eq!(helix_center(&square, 6.), None);
+        assert_eq!(helix_center(&square, 0.), None);
+
+        let center = point(5., 1.);
+        let turns = helix_turns(0., -1., 1., 0.1);
+        assert_eq!(turns.len(), 3);
+        assert_eq!(turns[1..], [-1., -1.]);
+        let lines = helix_lines(square[0], center, 0., &turns);
+        assert_eq!(lines.len(), 3 * HELIX_LINES_PER_TURN);
+        // Counterclockwise, so right first, and halfway around on the other side of the center
+        let (halfway, _) = lines[HELIX_LINES_PER_TURN / 2 - 1];
+        assert!((halfway - point(5., 2.)).length() < 1e-9);
+        assert!(lines[HELIX_LINES_PER_TURN / 4 - 1].0.x > 5.);
+        assert_eq!(lines.last(), Some(&(point(5., 0.), -1.)));
+    }
+}
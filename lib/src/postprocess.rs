@@ -1,6 +1,15 @@
+use std::{
+    fmt::{self, Display},
+    io::{self, Write},
+};
+
+use g_code::emit::{FormatOptions, Token, Value};
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
+/// Bytes collected before they are written out, which is much faster than writing each field
+const BUFFER_SIZE: usize = 1 << 16;
+
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Debug, Default, Clone, PartialEq)]
 pub struct PostprocessConfig {
@@ -13,4 +22,569 @@ pub struct PostprocessConfig {
     /// Convenience field for [g_code::emit::FormatOptions] field
     #[cfg_attr(feature = "serde", serde(default))]
     pub newline_before_comment: bool,
+    /// Round numbers to this many digits after the decimal point, instead of writing as many as
+    /// it takes to read back the exact same number
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub precision: Option<usize>,
+    /// Name output files in the 8.3 form that SD cards in controllers can read, see
+    /// [`crate::ShortNames`]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub short_file_names: bool,
+}
+
+/// A line of g-code that can't be split to fit in the most characters a line may have, see
+/// [`write_gcode`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineTooLong {
+    pub line: String,
+    pub max_line_length: usize,
+}
+
+impl Display for LineTooLong {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} does not fit in {} characters",
+            self.line, self.max_line_length
+        )
+    }
+}
+
+impl std::error::Error for LineTooLong {}
+
+/// Write g-code the same way as [`g_code::emit::format_gcode_io`], rounding numbers to
+/// `precision` digits if set
+///
+/// If `max_line_length` isn't 0, no line is longer than that, see [`fit_line`]. Fails with
+/// [`io::ErrorKind::InvalidInput`] wrapping a [`LineTooLong`] if a line can't be made to fit.
+///
+/// With `modal_motion`, a move leaves out its G0, G1, G2, or G3 when it is the same as the one
+/// before, see [`crate::Dialect::modal_motion`].
+///
+/// Numbers are written with [ryu] and [itoa] instead of [`std::fmt`], which took up a large
+/// part of the time spent writing big programs.
+pub fn write_gcode(
+    program: &[Token<'_>],
+    options: &FormatOptions,
+    precision: Option<usize>,
+    max_line_length: usize,
+    modal_motion: bool,
+    w: impl Write,
+) -> io::Result<()> {
+    if max_line_length > 0 {
+        return write_fitted(
+            program,
+            options,
+            precision,
+            max_line_length,
+            modal_motion,
+            w,
+        );
+    }
+    let mut writer = LineWriter {
+        buffer: Vec::with_capacity(BUFFER_SIZE),
+        line_start: 0,
+        downstream: w,
+    };
+    let mut preceded_by_newline = true;
+    let mut line_number = 0usize;
+    // G0, G1, G2, or G3 of the last move, if no other command of their group came after it
+    let mut motion = None;
+
+    if options.delimit_with_percent {
+        writer.buffer.extend_from_slice(b"%\n");
+        writer.line_start = writer.buffer.len();
+    }
+
+    for token in program {
+        if let Token::Field(field) = token {
+            // Can't handle user-provided line numbers
+            if preceded_by_newline && field.letters == "N" {
+                continue;
+            }
+            if modal_motion && field.letters.eq_ignore_ascii_case("G") {
+                let code = field.value.as_f64().unwrap_or(f64::NAN);
+                if [0., 1., 2., 3.].contains(&code) {
+                    if motion.replace(code) == Some(code) {
+                        // Still starts a new line, for the words of the move
+                        if !preceded_by_newline {
+                            if options.checksums {
+                                writer.checksum();
+                            }
+                            line_number += 1;
+                            writer.newline()?;
+                            preceded_by_newline = true;
+                        }
+                        continue;
+                    }
+                } else if (38. ..39.).contains(&code) || (80. ..90.).contains(&code) {
+                    // Probing and canned cycles are in the same group
+                    motion = None;
+                }
+            }
+        }
+
+        if options.line_numbers && preceded_by_newline {
+            writer.line_number(line_number);
+        }
+
+        match token {
+            Token::Field(field) => {
+                if !preceded_by_newline {
+                    if matches!(field.letters.as_ref(), "G" | "g" | "M" | "m" | "D" | "d") {
+                        if options.checksums {
+                            writer.checksum();
+                        }
+                        line_number += 1;
+                        writer.newline()?;
+                        if options.line_numbers {
+                            writer.line_number(line_number);
+                        }
+                    } else {
+                        writer.buffer.push(b' ');
+                    }
+                }
+                writer.buffer.extend_from_slice(field.letters.as_bytes());
+                write_value(&mut writer.buffer, &field.value, precision);
+                preceded_by_newline = false;
+            }
+            Token::Flag(flag) => {
+                if !preceded_by_newline {
+                    writer.buffer.push(b' ');
+                }
+                writer.buffer.extend_from_slice(flag.letter.as_bytes());
+            }
+            Token::Comment {
+                is_inline: true,
+                inner,
+            } => {
+                writer.buffer.push(b'(');
+                writer.buffer.extend_from_slice(inner.as_bytes());
+                writer.buffer.push(b')');
+                preceded_by_newline = false;
+            }
+            Token::Comment {
+                is_inline: false,
+                inner,
+            } => {
+                if options.checksums {
+                    writer.checksum();
+                }
+                if !preceded_by_newline && options.newline_before_comment {
+                    line_number += 1;
+                    writer.newline()?;
+                    if options.line_numbers {
+                        writer.line_number(line_number);
+                    }
+                    if options.checksums {
+                        writer.checksum();
+                    }
+                }
+                line_number += 1;
+                writer.buffer.push(b';');
+                writer.buffer.extend_from_slice(inner.as_bytes());
+                writer.newline()?;
+                preceded_by_newline = true;
+            }
+        }
+    }
+    // Ensure presence of trailing newline
+    if !preceded_by_newline {
+        if options.checksums {
+            writer.checksum();
+        }
+        writer.newline()?;
+    }
+    if options.delimit_with_percent {
+        writer.buffer.push(b'%');
+    }
+    writer.downstream.write_all(&writer.buffer)
+}
+
+/// Write the program without line numbers or checksums first, then fit its lines and add them
+fn write_fitted(
+    program: &[Token<'_>],
+    options: &FormatOptions,
+    precision: Option<usize>,
+    max_line_length: usize,
+    modal_motion: bool,
+    w: impl Write,
+) -> io::Result<()> {
+    let mut plain = vec![];
+    let plain_options = FormatOptions {
+        newline_before_comment: true,
+        ..Default::default()
+    };
+    write_gcode(
+        program,
+        &plain_options,
+        precision,
+        0,
+        modal_motion,
+        &mut plain,
+    )?;
+    let plain =
+        String::from_utf8(plain).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+    // Every line keeps at least a character of the program, so there are no more lines than that
+    let mut reserved = 0;
+    if options.line_numbers {
+        reserved += "N ".len() + itoa::Buffer::new().format(plain.len()).len();
+    }
+    if options.checksums {
+        reserved += "*255".len();
+    }
+    let max = max_line_length.saturating_sub(reserved);
+
+    let mut writer = LineWriter {
+        buffer: Vec::with_capacity(BUFFER_SIZE),
+        line_start: 0,
+        downstream: w,
+    };
+    if options.delimit_with_percent {
+        writer.buffer.extend_from_slice(b"%\n");
+        writer.line_start = writer.buffer.len();
+    }
+    let mut line_number = 0usize;
+    for line in plain.lines() {
+        let lines = fit_line(line, max).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                LineTooLong {
+                    line: line.to_string(),
+                    max_line_length,
+                },
+            )
+        })?;
+        for line in lines {
+            if options.line_numbers {
+                writer.line_number(line_number);
+            }
+            // Same as write_gcode, the checksum goes before a comment
+            match line.strip_prefix(';') {
+                Some(comment) => {
+                    if options.checksums {
+                        writer.checksum();
+                    }
+                    writer.buffer.push(b';');
+                    writer.buffer.extend_from_slice(comment.as_bytes());
+                }
+                None => {
+                    writer.buffer.extend_from_slice(line.as_bytes());
+                    if options.checksums {
+                        writer.checksum();
+                    }
+                }
+            }
+            line_number += 1;
+            writer.newline()?;
+        }
+    }
+    if options.delimit_with_percent {
+        writer.buffer.push(b'%');
+    }
+    writer.downstream.write_all(&writer.buffer)
+}
+
+/// Split a line into lines of at most `max` characters, or [`None`] if it can't be
+///
+/// Comments are wrapped onto as many lines as they need, and inline comments are moved onto
+/// lines of their own before the rest. If that isn't enough, so are feedrates and spindle
+/// speeds (F/S), which take effect the same on the line before. Other words always stay
+/// together, since splitting up a move would change where it goes.
+fn fit_line(line: &str, max: usize) -> Option<Vec<String>> {
+    if line.len() <= max {
+        return Some(vec![line.to_string()]);
+    }
+    if let Some(comment) = line.strip_prefix(';') {
+        return wrap_comment(comment, max);
+    }
+
+    let mut lines = vec![];
+    let mut words = vec![];
+    let mut rest = line;
+    while let Some((before, after)) = rest.split_once('(') {
+        words.extend(before.split_whitespace());
+        let (comment, after) = after.split_once(')').unwrap_or((after, ""));
+        lines.extend(wrap_comment(comment, max)?);
+        rest = after;
+    }
+    words.extend(rest.split_whitespace());
+
+    if words.join(" ").len() > max {
+        let (first, others) = words.split_first()?;
+        let (modal, others): (Vec<&str>, Vec<&str>) = others
+            .iter()
+            .partition(|word| word.starts_with(['F', 'f', 'S', 's']));
+        lines.extend(modal.into_iter().map(str::to_string));
+        words = [*first].into_iter().chain(others).collect();
+    }
+    if !words.is_empty() {
+        lines.push(words.join(" "));
+    }
+    lines.iter().all(|line| line.len() <= max).then_some(lines)
+}
+
+/// Wrap a comment onto `;` lines of at most `max` characters, between words where it can
+fn wrap_comment(comment: &str, max: usize) -> Option<Vec<String>> {
+    let width = max.checked_sub(1).filter(|width| *width > 0)?;
+    let mut lines = vec![];
+    let mut line = String::new();
+    for word in comment.split_whitespace() {
+        if !line.is_empty() {
+            if line.len() + 1 + word.len() > width {
+                lines.push(format!(";{line}"));
+                line.clear();
+            } else {
+                line.push(' ');
+            }
+        }
+        for c in word.chars() {
+            if line.len() + c.len_utf8() > width {
+                lines.push(format!(";{line}"));
+                line.clear();
+            }
+            line.push(c);
+        }
+    }
+    lines.push(format!(";{line}"));
+    Some(lines)
+}
+
+/// Buffers output a line at a time, so checksums can be taken over the current line
+struct LineWriter<W> {
+    buffer: Vec<u8>,
+    /// Where the current line starts in the buffer
+    line_start: usize,
+    downstream: W,
+}
+
+impl<W: Write> LineWriter<W> {
+    fn line_number(&mut self, line_number: usize) {
+        self.buffer.push(b'N');
+        self.buffer
+            .extend_from_slice(itoa::Buffer::new().format(line_number).as_bytes());
+        self.buffer.push(b' ');
+    }
+
+    /// Append the checksum of the current line so far
+    fn checksum(&mut self) {
+        let checksum = self.buffer[self.line_start..]
+            .iter()
+            .fold(0u8, |acc, b| acc ^ b);
+        self.buffer.push(b'*');
+        self.buffer
+            .extend_from_slice(itoa::Buffer::new().format(checksum).as_bytes());
+    }
+
+    fn newline(&mut self) -> io::Result<()> {
+        self.buffer.push(b'\n');
+        if self.buffer.len() >= BUFFER_SIZE {
+            self.downstream.write_all(&self.buffer)?;
+            self.buffer.clear();
+        }
+        self.line_start = self.buffer.len();
+        Ok(())
+    }
+}
+
+fn write_value(buffer: &mut Vec<u8>, value: &Value, precision: Option<usize>) {
+    match value {
+        Value::Float(float) => write_float(buffer, *float, precision),
+        Value::Integer(integer) => {
+            buffer.extend_from_slice(itoa::Buffer::new().format(*integer).as_bytes())
+        }
+        other => write!(buffer, "{other}").expect("writing to a Vec never fails"),
+    }
+}
+
+fn write_float(buffer: &mut Vec<u8>, float: f64, precision: Option<usize>) {
+    match precision {
+        // Fits into an integer of the smallest unit, with room to spare
+        Some(digits) if digits <= 15 && (float * 10f64.powi(digits as i32)).abs() < 1e17 => {
+            let unit = 10u64.pow(digits as u32);
+            let scaled = (float * unit as f64).round() as i64;
+            if scaled < 0 {
+                buffer.push(b'-');
+            }
+            let (whole, fraction) = (scaled.unsigned_abs() / unit, scaled.unsigned_abs() % unit);
+            buffer.extend_from_slice(itoa::Buffer::new().format(whole).as_bytes());
+            if fraction != 0 {
+                let mut fraction_buffer = itoa::Buffer::new();
+                let fraction = fraction_buffer.format(fraction);
+                buffer.push(b'.');
+                buffer.resize(buffer.len() + digits - fraction.len(), b'0');
+                buffer.extend_from_slice(fraction.trim_end_matches('0').as_bytes());
+            }
+        }
+        Some(digits) => {
+            let formatted = format!("{float:.digits$}");
+            let formatted = if formatted.contains('.') {
+                formatted.trim_end_matches('0').trim_end_matches('.')
+            } else {
+                &formatted
+            };
+            buffer.extend_from_slice(formatted.as_bytes());
+        }
+        // ryu switches to scientific notation outside of this range, which g-code doesn't have
+        None if float == 0. || (1e-5..1e16).contains(&float.abs()) => {
+            let mut ryu_buffer = ryu::Buffer::new();
+            let formatted = ryu_buffer.format_finite(float);
+            let formatted = formatted.strip_suffix(".0").unwrap_or(formatted);
+            buffer.extend_from_slice(formatted.as_bytes());
+        }
+        None => write!(buffer, "{float}").expect("writing to a Vec never fails"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::borrow::Cow;
+
+    use g_code::{command, emit::format_gcode_io, parse::snippet_parser};
+
+    use super::*;
+
+    #[test]
+    fn output_matches_g_code_formatter() {
+        let mut program = snippet_parser("G21 G90 ; units\nM3 S1000 (inline) G0 X1 Y-2")
+            .unwrap()
+            .iter_emit_tokens()
+            .collect::<Vec<_>>();
+        for (x, y) in [(0.1 + 0.2, -1e-7), (12345.678901, 1e16), (-0., 2. / 3.)] {
+            program.extend(
+                command!(LinearInterpolation {
+                    X: x,
+                    Y: y,
+                    F: 300.
+                })
+                .into_token_vec(),
+            );
+            program.push(Token::Comment {
+                is_inline: false,
+                inner: Cow::Borrowed("between"),
+            });
+        }
+        program.extend(command!(RapidPositioning { X: 0. }).into_token_vec());
+
+        for (checksums, line_numbers, delimit_with_percent, newline_before_comment) in [
+            (false, false, false, false),
+            (true, true, true, true),
+            (true, false, false, true),
+        ] {
+            let options = FormatOptions {
+                checksums,
+                line_numbers,
+                delimit_with_percent,
+                newline_before_comment,
+            };
+            let mut expected = vec![];
+            format_gcode_io(&program, options.clone(), &mut expected).unwrap();
+            let mut written = vec![];
+            write_gcode(&program, &options, None, 0, false, &mut written).unwrap();
+            assert_eq!(
+                String::from_utf8(written).unwrap(),
+                String::from_utf8(expected).unwrap()
+            );
+        }
+
+        let mut written = vec![];
+        write_gcode(
+            &program,
+            &FormatOptions::default(),
+            Some(3),
+            0,
+            false,
+            &mut written,
+        )
+        .unwrap();
+        let written = String::from_utf8(written).unwrap();
+        assert!(written.contains("G1 X0.3 Y0 F300"), "{written}");
+        assert!(written.contains("G1 X12345.679 Y10000000000000000 F300"));
+        assert!(written.contains("G1 X0 Y0.667 F300"));
+    }
+
+    #[test]
+    fn lines_are_fit_into_max_line_length() {
+        let program = snippet_parser(
+            "G21 G90 ; units and distance mode, wrapped onto lines\nG1 X100.123 Y200.456 F3000 (move)",
+        )
+        .unwrap()
+        .iter_emit_tokens()
+        .collect::<Vec<_>>();
+        let options = FormatOptions {
+            checksums: true,
+            line_numbers: true,
+            ..Default::default()
+        };
+
+        let mut written = vec![];
+        write_gcode(&program, &options, None, 32, false, &mut written).unwrap();
+        let written = String::from_utf8(written).unwrap();
+        assert!(written.lines().all(|line| line.len() <= 32), "{written}");
+        let lines = written
+            .lines()
+            .map(|line| line.split_once(' ').unwrap().1)
+            .map(|line| match line.find(';') {
+                Some(comment) => &line[comment..],
+                None => line.split('*').next().unwrap(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            [
+                "G21",
+                "G90",
+                ";units and distance",
+                ";mode, wrapped onto",
+                ";lines",
+                ";move",
+                "F3000",
+                "G1 X100.123 Y200.456"
+            ]
+        );
+
+        let err = write_gcode(&program, &options, None, 16, false, &mut vec![]).unwrap_err();
+        assert!(err.get_ref().is_some_and(|inner| inner.is::<LineTooLong>()));
+    }
+
+    #[test]
+    fn repeated_motion_is_left_out() {
+        let program = snippet_parser(
+            "G0 X0 Y0\nG1 X1 F300\nG1 Y1\nM5\nG1 X0\nG2 X1 Y0 I1\nG2 X0 I-0.5\nG80\nG2 X1 I1",
+        )
+        .unwrap()
+        .iter_emit_tokens()
+        .collect::<Vec<_>>();
+        let options = FormatOptions {
+            checksums: true,
+            line_numbers: true,
+            ..Default::default()
+        };
+        let mut written = vec![];
+        write_gcode(&program, &options, None, 0, true, &mut written).unwrap();
+        let written = String::from_utf8(written).unwrap();
+        let lines = written
+            .lines()
+            .map(|line| line.split_once(' ').unwrap().1)
+            .map(|line| line.split('*').next().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            [
+                "G0 X0 Y0",
+                "G1 X1 F300",
+                "Y1",
+                "M5",
+                "X0",
+                "G2 X1 Y0 I1",
+                "X0 I-0.5",
+                "G80",
+                "G2 X1 I1"
+            ]
+        );
+        // Line numbers and checksums are still counted over the lines as written
+        assert!(written.starts_with("N0 G0"));
+        assert!(written.contains("N2 Y1*"));
+    }
 }
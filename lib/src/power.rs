@@ -0,0 +1,126 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use std::str::FromStr;
+
+use svgtypes::Color;
+
+use crate::{fill::property, SourceElement};
+
+/// What sets the tool power of a path, see [`StrokePower`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum PowerSource {
+    /// How dark the `stroke` color is, from white at the least power to black at the most
+    #[default]
+    Luminance,
+    /// The `stroke-opacity` times the `opacity`, from transparent at the least power to opaque at
+    /// the most
+    Opacity,
+}
+
+/// Scale the tool power of each path by its stroke (i.e. so darker strokes burn deeper with a
+/// laser), output as an `S` word on the first cut of a path with a different power
+///
+/// Any `S` word in the tool on sequence is left out, since the power is set by the cuts.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct StrokePower {
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub source: PowerSource,
+    /// Power for a white or transparent stroke
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub min: f64,
+    /// Power for a black or opaque stroke, and for paths that weren't drawn from an element
+    #[cfg_attr(feature = "serde", serde(default = "default_max"))]
+    pub max: f64,
+}
+
+const fn default_max() -> f64 {
+    1000.
+}
+
+impl Default for StrokePower {
+    fn default() -> Self {
+        Self {
+            source: PowerSource::default(),
+            min: 0.,
+            max: default_max(),
+        }
+    }
+}
+
+impl StrokePower {
+    /// Power for a path drawn from `source`
+    ///
+    /// Strokes are black and opaque unless set otherwise, so shapes without one (i.e. filled
+    /// ones) get the most power.
+    pub fn power(&self, source: Option<&SourceElement>) -> f64 {
+        let scale = source.map_or(1., |source| match self.source {
+            PowerSource::Luminance => {
+                let color = property(source, "stroke")
+                    .and_then(|stroke| Color::from_str(stroke).ok())
+                    .unwrap_or_else(Color::black);
+                // https://en.wikipedia.org/wiki/Relative_luminance
+                1. - (0.2126 * color.red as f64
+                    + 0.7152 * color.green as f64
+                    + 0.0722 * color.blue as f64)
+                    / 255.
+            }
+            PowerSource::Opacity => ["stroke-opacity", "opacity"]
+                .iter()
+                .filter_map(|name| property(source, name)?.parse::<f64>().ok())
+                .product::<f64>()
+                .clamp(0., 1.),
+        });
+        self.min + (self.max - self.min) * scale
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn source(attributes: &[(&str, &str)]) -> SourceElement {
+        SourceElement {
+            tag_name: "path".to_string(),
+            attributes: attributes
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn power_is_scaled_by_stroke() {
+        let assert_power = |stroke_power: &StrokePower, attributes: &[(&str, &str)], power: f64| {
+            let actual = stroke_power.power(Some(&source(attributes)));
+            assert!((actual - power).abs() < 1e-9, "{actual} != {power}");
+        };
+        let luminance = StrokePower {
+            source: PowerSource::Luminance,
+            min: 100.,
+            max: 500.,
+        };
+        assert_eq!(luminance.power(None), 500.);
+        assert_power(&luminance, &[], 500.);
+        assert_power(&luminance, &[("stroke", "white")], 100.);
+        assert_power(
+            &luminance,
+            &[("style", "stroke: #808080")],
+            500. - 400. * 128. / 255.,
+        );
+
+        let opacity = StrokePower {
+            source: PowerSource::Opacity,
+            ..luminance
+        };
+        assert_power(&opacity, &[("stroke", "white")], 500.);
+        assert_power(
+            &opacity,
+            &[("stroke-opacity", "0.5"), ("opacity", "0.5")],
+            200.,
+        );
+    }
+}
@@ -0,0 +1,88 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{Dialect, Settings, SupportedFunctionality};
+
+/// A machine with settings known to work on it, see [`Preset::apply`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Preset {
+    /// Carbide 3D Shapeoko 3 or 4 with a spindle or router switched by Grbl (M3/M5)
+    Shapeoko,
+    /// Inventables X-Carve (1000 mm) with a spindle or router switched by Grbl (M3/M5)
+    XCarve,
+    /// Ortur Laser Master 2 or 3, running Grbl in laser mode
+    OrturLaser,
+    /// Evil Mad Scientist AxiDraw V3 running g-code firmware, i.e. through a Grbl board, with the
+    /// pen lifted by a servo on the spindle output
+    AxiDraw,
+    /// A pen plotter running Grbl, with the pen lifted by a servo on the spindle output
+    GrblPlotter,
+}
+
+impl Preset {
+    pub const ALL: [Self; 5] = [
+        Self::Shapeoko,
+        Self::XCarve,
+        Self::OrturLaser,
+        Self::AxiDraw,
+        Self::GrblPlotter,
+    ];
+
+    /// Set the bed size, tool and program sequences, dialect, and feedrates of `settings` for this
+    /// machine, keeping everything else
+    pub fn apply(self, settings: &mut Settings) {
+        let (bed_size, feedrate, rapid_feedrate) = match self {
+            Self::Shapeoko => ([425., 425.], 1000., 5000.),
+            Self::XCarve => ([750., 750.], 1000., 8000.),
+            Self::OrturLaser => ([400., 430.], 3000., 6000.),
+            Self::AxiDraw => ([300., 218.], 3000., 6000.),
+            Self::GrblPlotter => ([300., 300.], 2000., 5000.),
+        };
+        settings.conversion.bed_size = bed_size;
+        settings.conversion.feedrate = feedrate;
+        settings.conversion.pass_feedrate = vec![];
+        settings.cost.rapid_feedrate = rapid_feedrate;
+
+        let machine = &mut settings.machine;
+        // A begin sequence written for another machine could do anything on this one
+        machine.begin_sequence = None;
+        match self {
+            Self::AxiDraw => {
+                machine.dialect = None;
+                machine.supported_functionality = SupportedFunctionality::default();
+                machine.end_sequence = None;
+            }
+            _ => Dialect::Grbl.apply(machine),
+        }
+        // Routers get up to speed before cutting, and servos lift or lower the pen before moving
+        let (tool_on, tool_off) = match self {
+            Self::Shapeoko | Self::XCarve => ("M3 S10000\nG4 P3", "M5"),
+            Self::OrturLaser => return,
+            Self::AxiDraw | Self::GrblPlotter => ("M3 S90\nG4 P0.2", "M5\nG4 P0.2"),
+        };
+        machine.tool_on_sequence = Some(tool_on.to_string());
+        machine.tool_off_sequence = Some(tool_off.to_string());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::lint_sequences;
+
+    #[test]
+    fn presets_set_up_the_machine_without_warnings() {
+        for preset in Preset::ALL {
+            let mut settings = Settings::default();
+            settings.conversion.dpi = 72.;
+            settings.machine.begin_sequence = Some("M106 S255".to_string());
+            preset.apply(&mut settings);
+            assert!(settings.machine.tool_on_sequence.is_some());
+            assert_eq!(settings.machine.begin_sequence, None, "{preset:?}");
+            assert!(lint_sequences(&settings.machine).is_empty(), "{preset:?}");
+            // Settings that have nothing to do with the machine are kept
+            assert_eq!(settings.conversion.dpi, 72.);
+        }
+    }
+}
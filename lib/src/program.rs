@@ -0,0 +1,412 @@
+use g_code::emit::Token;
+use lyon_geom::{point, vector, Angle, ArcFlags, Box2D, LineSegment, Point, SvgArc};
+
+use crate::{Dialect, Segment};
+
+const MILLIMETERS_PER_INCH: f64 = 25.4;
+
+/// What the machine does for a command of a program, see [`follow_program`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProgramStep {
+    Move(ProgramMove),
+    /// Waiting in place (G4)
+    Dwell {
+        seconds: f64,
+        /// Whether the tool is on while waiting, as for [`ProgramMove::cuts`]
+        tool_on: bool,
+    },
+}
+
+/// A move of a program in millimeters
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProgramMove {
+    /// Path of the X and Y axes, or `None` if only the Z axis moves
+    pub segment: Option<Segment>,
+    /// Height of the Z axis before and after the move, if it changes
+    pub z: Option<[f64; 2]>,
+    /// A rapid move (G0), made as fast as the machine goes, instead of at the
+    /// [`Self::feedrate`] (G1, G2, and G3)
+    pub rapid: bool,
+    /// In millimeters / minute, if one was set
+    pub feedrate: Option<f64>,
+    /// Whether the tool is on, which is taken to be the case for feed moves unless the tool was
+    /// turned off (M5, M107, or M106 S0)
+    pub cuts: bool,
+}
+
+impl ProgramMove {
+    /// Distance travelled by all axes together, in millimeters
+    pub fn length(&self) -> f64 {
+        let across = match &self.segment {
+            Some(Segment::Arc(arc)) => {
+                let arc = arc.to_arc();
+                arc.sweep_angle.radians.abs() * arc.radii.x
+            }
+            Some(segment) => (segment.to() - segment.from()).length(),
+            None => 0.,
+        };
+        let up = self.z.map_or(0., |[from, to]| to - from);
+        across.hypot(up)
+    }
+}
+
+/// Bounding box of the moves that cut
+pub(crate) fn cut_bounds(steps: &[ProgramStep]) -> Option<Box2D<f64>> {
+    let points = steps
+        .iter()
+        .filter_map(|step| match step {
+            ProgramStep::Move(ProgramMove {
+                segment: Some(segment),
+                cuts: true,
+                ..
+            }) => Some(extremes(segment)),
+            _ => None,
+        })
+        .flatten()
+        .collect::<Vec<_>>();
+    (!points.is_empty()).then(|| Box2D::from_points(points))
+}
+
+/// Ends of a move, and where an arc is furthest left, right, up, or down, which bound it
+pub(crate) fn extremes(segment: &Segment) -> Vec<Point<f64>> {
+    let mut points = vec![segment.from(), segment.to()];
+    if let Segment::Arc(arc) = segment {
+        let arc = arc.to_arc();
+        let sweep = arc.sweep_angle.radians;
+        let directions = [
+            vector(1., 0.),
+            vector(0., 1.),
+            vector(-1., 0.),
+            vector(0., -1.),
+        ];
+        points.extend(directions.into_iter().filter_map(|direction| {
+            let angle = direction.angle_from_x_axis();
+            let along = if sweep < 0. {
+                arc.start_angle - angle
+            } else {
+                angle - arc.start_angle
+            };
+            (along.positive().radians <= sweep.abs()).then(|| arc.center + direction * arc.radii.x)
+        }));
+    }
+    points
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Motion {
+    Rapid,
+    Linear,
+    Clockwise,
+    Counterclockwise,
+}
+
+/// Follow a program like a machine would, in millimeters
+///
+/// Inches (G20) and relative distance mode (G91) are followed, and arcs can have either a center
+/// (I and J) or a radius (R). Where the machine is before the first move isn't known, so that
+/// move only tells where it is. The `dialect` sets what unit dwells are in, see
+/// [`Dialect::dwell_in_milliseconds`].
+pub fn follow_program(program: &[Token<'_>], dialect: Option<Dialect>) -> Vec<ProgramStep> {
+    let mut steps = vec![];
+    let mut motion = None;
+    let mut inches = false;
+    let mut relative = false;
+    let mut feedrate = None;
+    // Unknown until the program turns it on or off
+    let mut tool_on: Option<bool> = None;
+    let mut position: Option<Point<f64>> = None;
+    let mut height: Option<f64> = None;
+
+    // Each command starts a block with the words after it, like a line of g-code
+    let mut blocks: Vec<Vec<(char, f64)>> = vec![vec![]];
+    for token in program {
+        let Token::Field(field) = token else {
+            continue;
+        };
+        let (Some(letter), Some(value)) = (field.letters.chars().next(), field.value.as_f64())
+        else {
+            continue;
+        };
+        let letter = letter.to_ascii_uppercase();
+        if matches!(letter, 'G' | 'M') {
+            blocks.push(vec![]);
+        }
+        blocks.last_mut().unwrap().push((letter, value));
+    }
+
+    for block in blocks {
+        let word = |letter: char| {
+            block
+                .iter()
+                .find(|(l, _)| *l == letter)
+                .map(|(_, value)| *value)
+        };
+        let scale = if inches { MILLIMETERS_PER_INCH } else { 1. };
+        if let Some(f) = word('F') {
+            feedrate = Some(f * scale);
+        }
+        // Axis words of other commands (i.e. G92 or G28) don't move anything
+        let mut moves_axes = true;
+        match block.first() {
+            Some(('G', code)) => match *code {
+                0. => motion = Some(Motion::Rapid),
+                1. => motion = Some(Motion::Linear),
+                2. => motion = Some(Motion::Clockwise),
+                3. => motion = Some(Motion::Counterclockwise),
+                4. => {
+                    let milliseconds = dialect.is_some_and(Dialect::dwell_in_milliseconds);
+                    let seconds = match (word('P'), word('S')) {
+                        (Some(p), _) if milliseconds => p / 1000.,
+                        (Some(p), _) => p,
+                        (None, Some(s)) => s,
+                        (None, None) => 0.,
+                    };
+                    steps.push(ProgramStep::Dwell {
+                        seconds,
+                        tool_on: tool_on != Some(false),
+                    });
+                    continue;
+                }
+                20. => inches = true,
+                21. => inches = false,
+                90. => relative = false,
+                91. => relative = true,
+                _ => moves_axes = false,
+            },
+            Some(('M', code)) => {
+                match *code {
+                    3. | 4. => tool_on = Some(true),
+                    106. => tool_on = Some(word('S') != Some(0.)),
+                    2. | 5. | 30. | 107. => tool_on = Some(false),
+                    _ => {}
+                }
+                continue;
+            }
+            _ => {}
+        }
+        let (Some(motion), true) = (motion, moves_axes) else {
+            continue;
+        };
+        let [x, y, z] = ['X', 'Y', 'Z'].map(|letter| word(letter).map(|value| value * scale));
+
+        let z = z.and_then(|z| {
+            let to = if relative {
+                height.unwrap_or_default() + z
+            } else {
+                z
+            };
+            height.replace(to).map(|from| [from, to])
+        });
+        let z = z.filter(|[from, to]| from != to);
+        let line = if x.is_some() || y.is_some() {
+            let base = position.unwrap_or_default();
+            let to = if relative {
+                base + vector(x.unwrap_or_default(), y.unwrap_or_default())
+            } else {
+                point(x.unwrap_or(base.x), y.unwrap_or(base.y))
+            };
+            position.replace(to).map(|from| (from, to))
+        } else {
+            None
+        };
+
+        let clockwise = motion == Motion::Clockwise;
+        let arc = |from: Point<f64>, to: Point<f64>, radius: f64, large_arc: bool| {
+            Segment::Arc(SvgArc {
+                from,
+                to,
+                radii: vector(radius, radius),
+                x_rotation: Angle::zero(),
+                flags: ArcFlags {
+                    large_arc,
+                    sweep: !clockwise,
+                },
+            })
+        };
+        let segments = match (motion, line) {
+            (_, None) => vec![],
+            (Motion::Rapid | Motion::Linear, Some((from, to))) => (from != to)
+                .then_some(Segment::Line(LineSegment { from, to }))
+                .into_iter()
+                .collect(),
+            (Motion::Clockwise | Motion::Counterclockwise, Some((from, to))) => {
+                if let Some(radius) = word('R') {
+                    // A negative radius is for arcs that go more than halfway around
+                    (from != to)
+                        .then(|| arc(from, to, radius.abs() * scale, radius < 0.))
+                        .into_iter()
+                        .collect()
+                } else {
+                    let [i, j] = ['I', 'J'].map(|letter| word(letter).unwrap_or_default() * scale);
+                    let center = from + vector(i, j);
+                    let radius = (from - center).length();
+                    if radius == 0. {
+                        vec![]
+                    } else if from == to {
+                        // SVG arcs can't go all the way around, so full circles are made of halves
+                        let opposite = center + vector(i, j);
+                        vec![
+                            arc(from, opposite, radius, false),
+                            arc(opposite, to, radius, false),
+                        ]
+                    } else {
+                        let angle = |p: Point<f64>| (p - center).angle_from_x_axis();
+                        let swept = if clockwise {
+                            angle(from) - angle(to)
+                        } else {
+                            angle(to) - angle(from)
+                        };
+                        vec![arc(
+                            from,
+                            to,
+                            radius,
+                            swept.positive().radians > std::f64::consts::PI,
+                        )]
+                    }
+                }
+            }
+        };
+        let mut step = |segment: Option<Segment>, z: Option<[f64; 2]>| {
+            steps.push(ProgramStep::Move(ProgramMove {
+                segment,
+                z,
+                rapid: motion == Motion::Rapid,
+                feedrate,
+                cuts: motion != Motion::Rapid && tool_on != Some(false),
+            }));
+        };
+        if segments.is_empty() {
+            if z.is_some() {
+                step(None, z);
+            }
+            continue;
+        }
+        // The Z axis moves evenly along the way, i.e. for a helix made of two halves
+        let count = segments.len() as f64;
+        for (k, segment) in segments.into_iter().enumerate() {
+            let z = z.map(|[from, to]| {
+                let at = |k: f64| from + (to - from) * k / count;
+                [at(k as f64), at(k as f64 + 1.)]
+            });
+            step(Some(segment), z);
+        }
+    }
+    steps
+}
+
+#[cfg(test)]
+mod test {
+    use g_code::parse::snippet_parser;
+
+    use super::*;
+
+    fn follow(program: &str, dialect: Option<Dialect>) -> Vec<ProgramStep> {
+        let program = snippet_parser(program)
+            .unwrap()
+            .iter_emit_tokens()
+            .collect::<Vec<_>>();
+        follow_program(&program, dialect)
+    }
+
+    fn moves(steps: &[ProgramStep]) -> Vec<&ProgramMove> {
+        steps
+            .iter()
+            .filter_map(|step| match step {
+                ProgramStep::Move(program_move) => Some(program_move),
+                ProgramStep::Dwell { .. } => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn moves_follow_modes_and_arcs() {
+        let steps = follow(
+            "G21 G90\nG0 X0 Y0\nG1 X10 Y0 F300\nG2 X10 Y10 I0 J5\nG3 X10 Y10 I-5 J0\nG91\nG0 Y-10\nG20\nG1 Y0.5\n",
+            None,
+        );
+        assert_eq!(
+            moves(&steps)
+                .iter()
+                .map(|program_move| (
+                    program_move.cuts,
+                    program_move.segment.as_ref().unwrap().to()
+                ))
+                .collect::<Vec<_>>(),
+            [
+                (true, point(10., 0.)),
+                (true, point(10., 10.)),
+                // A full circle in two halves
+                (true, point(0., 10.)),
+                (true, point(10., 10.)),
+                (false, point(10., 0.)),
+                (true, point(10., 12.7)),
+            ]
+        );
+        let lengths = moves(&steps)
+            .iter()
+            .map(|program_move| program_move.length())
+            .collect::<Vec<_>>();
+        assert_eq!(lengths[0], 10.);
+        assert!((lengths[1] - 5. * std::f64::consts::PI).abs() < 1E-9);
+        assert_eq!(moves(&steps)[5].feedrate, Some(300.));
+    }
+
+    #[test]
+    fn moves_with_the_tool_off_do_not_cut() {
+        let steps = follow(
+            "G0 X0 Y0 Z5\nM3 S100\nG1 Z-1 F100\nG4 P0.5\nG1 X3 Y4 Z-2\nM5\nG1 X0 Y0\n",
+            None,
+        );
+        assert_eq!(
+            steps,
+            [
+                ProgramStep::Move(ProgramMove {
+                    segment: None,
+                    z: Some([5., -1.]),
+                    rapid: false,
+                    feedrate: Some(100.),
+                    cuts: true,
+                }),
+                ProgramStep::Dwell {
+                    seconds: 0.5,
+                    tool_on: true
+                },
+                ProgramStep::Move(ProgramMove {
+                    segment: Some(Segment::Line(LineSegment {
+                        from: point(0., 0.),
+                        to: point(3., 4.)
+                    })),
+                    z: Some([-1., -2.]),
+                    rapid: false,
+                    feedrate: Some(100.),
+                    cuts: true,
+                }),
+                ProgramStep::Move(ProgramMove {
+                    segment: Some(Segment::Line(LineSegment {
+                        from: point(3., 4.),
+                        to: point(0., 0.)
+                    })),
+                    z: None,
+                    rapid: false,
+                    feedrate: Some(100.),
+                    cuts: false,
+                }),
+            ]
+        );
+        assert!((moves(&steps)[1].length() - 26f64.sqrt()).abs() < 1E-9);
+
+        assert_eq!(
+            follow("M106 S255\nG4 P250\nM106 S0\nG4 S2", Some(Dialect::Marlin)),
+            [
+                ProgramStep::Dwell {
+                    seconds: 0.25,
+                    tool_on: true
+                },
+                ProgramStep::Dwell {
+                    seconds: 2.,
+                    tool_on: false
+                },
+            ]
+        );
+    }
+}
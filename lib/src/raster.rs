@@ -0,0 +1,380 @@
+#[cfg(feature = "raster")]
+use lyon_geom::{euclid::default::Transform2D, point, vector, Box2D, Point};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "raster")]
+use crate::GrayImage;
+
+/// How shades of gray are turned into tool power, see [`RasterConfig::dithering`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Dithering {
+    /// Power in proportion to how dark each pixel is, for machines that can burn shades
+    #[default]
+    Grayscale,
+    /// Full power for pixels darker than middle gray, none for the rest
+    Threshold,
+    /// Full power or none, spreading the difference onto the next pixels so areas keep their shade
+    FloydSteinberg,
+    /// Full power or none by a repeating 4x4 pattern, which burns more evenly than
+    /// [`Self::FloydSteinberg`] at the cost of showing the pattern
+    Ordered,
+}
+
+/// Engraving of `<image>` elements line by line, with the tool power set for each pixel
+///
+/// Images are only read with the `raster` feature, and are engraved before any paths are drawn.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RasterConfig {
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub dithering: Dithering,
+    /// Distance between scanlines in millimeters, usually the width of the line the tool burns
+    ///
+    /// Nothing is engraved unless this is above 0.
+    #[cfg_attr(feature = "serde", serde(default = "default_line_interval"))]
+    pub line_interval: f64,
+    /// Power for the lightest pixels that are engraved, white ones are skipped
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub min_power: f64,
+    /// Power for black pixels
+    #[cfg_attr(feature = "serde", serde(default = "default_max_power"))]
+    pub max_power: f64,
+    /// Feedrate along scanlines in millimeters / minute, instead of
+    /// [`crate::ConversionConfig::feedrate`]
+    #[cfg_attr(feature = "serde", serde(default = "default_feedrate"))]
+    pub feedrate: f64,
+    /// Engrave every other scanline backwards, instead of moving back to the same side for each
+    #[cfg_attr(feature = "serde", serde(default = "default_bidirectional"))]
+    pub bidirectional: bool,
+}
+
+const fn default_line_interval() -> f64 {
+    0.1
+}
+
+const fn default_max_power() -> f64 {
+    1000.
+}
+
+const fn default_feedrate() -> f64 {
+    3000.
+}
+
+const fn default_bidirectional() -> bool {
+    true
+}
+
+impl Default for RasterConfig {
+    fn default() -> Self {
+        Self {
+            dithering: Dithering::default(),
+            line_interval: default_line_interval(),
+            min_power: 0.,
+            max_power: default_max_power(),
+            feedrate: default_feedrate(),
+            bidirectional: default_bidirectional(),
+        }
+    }
+}
+
+/// An image to engrave, read from an `<image>` element
+#[cfg(feature = "raster")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct RasterImage {
+    pub image: GrayImage,
+    /// From the pixels of the image, each a unit square with the top left one at the origin, to
+    /// millimeters
+    pub transform: Transform2D<f64>,
+}
+
+/// A line across an image, starting with a move to `from`
+#[cfg(feature = "raster")]
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Scanline {
+    pub from: Point<f64>,
+    /// Where each stretch of pixels with the same power ends, and the power, which is 0 for
+    /// those that are skipped
+    pub runs: Vec<(Point<f64>, f64)>,
+}
+
+#[cfg(feature = "raster")]
+impl RasterImage {
+    pub fn bounding_box(&self) -> Box2D<f64> {
+        let [width, height] = [self.image.width, self.image.height].map(|len| len as f64);
+        Box2D::from_points(
+            [(0., 0.), (width, 0.), (0., height), (width, height)]
+                .map(|(x, y)| self.transform.transform_point(point(x, y))),
+        )
+    }
+
+    /// Apply an affine transform to where the image is engraved
+    pub fn transformed(&self, transform: &Transform2D<f64>) -> Self {
+        Self {
+            image: self.image.clone(),
+            transform: self.transform.then(transform),
+        }
+    }
+
+    /// The part of the image inside `bounds`, or [`None`] if none of it is dark
+    ///
+    /// Pixels are kept whole, those with their center outside of `bounds` are made white.
+    pub fn clipped(&self, bounds: &Box2D<f64>) -> Option<Self> {
+        let GrayImage { width, height, .. } = self.image;
+        let inverse = self.transform.inverse()?;
+        let pixel_bounds = Box2D::from_points(
+            [
+                bounds.min,
+                point(bounds.max.x, bounds.min.y),
+                point(bounds.min.x, bounds.max.y),
+                bounds.max,
+            ]
+            .map(|corner| inverse.transform_point(corner)),
+        );
+        let [x0, y0] =
+            [pixel_bounds.min.x, pixel_bounds.min.y].map(|min| min.floor().max(0.) as usize);
+        let x1 = (pixel_bounds.max.x.ceil().max(0.) as usize).min(width);
+        let y1 = (pixel_bounds.max.y.ceil().max(0.) as usize).min(height);
+        if x0 >= x1 || y0 >= y1 {
+            return None;
+        }
+
+        let pixels = (y0..y1)
+            .flat_map(|y| (x0..x1).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let center = self
+                    .transform
+                    .transform_point(point(x as f64 + 0.5, y as f64 + 0.5));
+                if bounds.contains(center) {
+                    self.image.lightness(x, y).unwrap_or(1.)
+                } else {
+                    1.
+                }
+            })
+            .collect::<Vec<_>>();
+        if pixels.iter().all(|lightness| *lightness >= 1.) {
+            return None;
+        }
+        Some(Self {
+            image: GrayImage {
+                width: x1 - x0,
+                height: y1 - y0,
+                pixels,
+            },
+            transform: Transform2D::translation(x0 as f64, y0 as f64).then(&self.transform),
+        })
+    }
+
+    /// Scanlines along the rows of the image, [`RasterConfig::line_interval`] apart, leaving out
+    /// the white pixels at the ends of each
+    pub(crate) fn scanlines(&self, config: &RasterConfig) -> Vec<Scanline> {
+        if config.line_interval <= 0. || config.line_interval.is_nan() {
+            return vec![];
+        }
+        let GrayImage { width, height, .. } = self.image;
+        let length = self
+            .transform
+            .transform_vector(vector(0., height as f64))
+            .length();
+        let rows = ((length / config.line_interval).round() as usize).max(1);
+        let row_height = height as f64 / rows as f64;
+
+        // How dark each pixel of each scanline is
+        let mut levels = (0..rows)
+            .map(|row| {
+                let y = ((row as f64 + 0.5) * row_height) as usize;
+                (0..width)
+                    .map(|x| 1. - self.image.lightness(x, y).unwrap_or(1.))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        dither(&mut levels, config.dithering);
+
+        let mut scanlines = vec![];
+        for (row, levels) in levels.iter().enumerate() {
+            let Some(first) = levels.iter().position(|level| *level > 0.) else {
+                continue;
+            };
+            let last = levels.iter().rposition(|level| *level > 0.).unwrap();
+            let y = (row as f64 + 0.5) * row_height;
+            let at = |x: usize| self.transform.transform_point(point(x as f64, y));
+            let power = |level: f64| {
+                if level > 0. {
+                    config.min_power + (config.max_power - config.min_power) * level
+                } else {
+                    0.
+                }
+            };
+            // Pixel boundaries where the power changes, with the power up to each
+            let mut runs: Vec<(usize, f64)> = vec![];
+            for (x, level) in (first..).zip(&levels[first..=last]) {
+                match runs.last_mut() {
+                    Some((end, previous)) if *previous == power(*level) => *end = x + 1,
+                    _ => runs.push((x + 1, power(*level))),
+                }
+            }
+            let backwards = config.bidirectional && scanlines.len() % 2 == 1;
+            let scanline = if backwards {
+                let starts = std::iter::once(first)
+                    .chain(runs.iter().map(|(end, _)| *end))
+                    .collect::<Vec<_>>();
+                Scanline {
+                    from: at(last + 1),
+                    runs: starts
+                        .into_iter()
+                        .zip(runs.iter())
+                        .rev()
+                        .map(|(start, (_, power))| (at(start), *power))
+                        .collect(),
+                }
+            } else {
+                Scanline {
+                    from: at(first),
+                    runs: runs.iter().map(|(end, power)| (at(*end), *power)).collect(),
+                }
+            };
+            scanlines.push(scanline);
+        }
+        scanlines
+    }
+}
+
+/// Turn levels from 0 to 1 into only 0 or 1, unless engraving in [`Dithering::Grayscale`]
+#[cfg(feature = "raster")]
+fn dither(levels: &mut [Vec<f64>], dithering: Dithering) {
+    // https://en.wikipedia.org/wiki/Ordered_dithering
+    const BAYER: [[f64; 4]; 4] = [
+        [0., 8., 2., 10.],
+        [12., 4., 14., 6.],
+        [3., 11., 1., 9.],
+        [15., 7., 13., 5.],
+    ];
+    let on = |level: f64, threshold: f64| if level > threshold { 1. } else { 0. };
+    match dithering {
+        Dithering::Grayscale => {}
+        Dithering::Threshold => levels
+            .iter_mut()
+            .flatten()
+            .for_each(|level| *level = on(*level, 0.5)),
+        Dithering::Ordered => {
+            for (y, row) in levels.iter_mut().enumerate() {
+                for (x, level) in row.iter_mut().enumerate() {
+                    *level = on(*level, (BAYER[y % 4][x % 4] + 0.5) / 16.);
+                }
+            }
+        }
+        // https://en.wikipedia.org/wiki/Floyd%E2%80%93Steinberg_dithering
+        Dithering::FloydSteinberg => {
+            for y in 0..levels.len() {
+                for x in 0..levels[y].len() {
+                    let level = levels[y][x];
+                    let dithered = on(level, 0.5);
+                    levels[y][x] = dithered;
+                    let error = level - dithered;
+                    let mut spread = |y: usize, x: Option<usize>, weight: f64| {
+                        if let Some(level) = levels.get_mut(y).and_then(|row| row.get_mut(x?)) {
+                            *level += error * weight;
+                        }
+                    };
+                    spread(y, x.checked_add(1), 7. / 16.);
+                    spread(y + 1, x.checked_sub(1), 3. / 16.);
+                    spread(y + 1, Some(x), 5. / 16.);
+                    spread(y + 1, x.checked_add(1), 1. / 16.);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "raster"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn scanlines_set_the_power_of_each_run_of_pixels() {
+        // Rows of white, gray, black, and white pixels, then a blank row
+        let image = GrayImage::from_netpbm(b"P2 4 2 2\n2 1 0 2\n2 2 2 2\n").unwrap();
+        let raster = RasterImage {
+            image,
+            transform: Transform2D::scale(1., -1.).then_translate(vector(0., 2.)),
+        };
+        let config = RasterConfig {
+            line_interval: 0.5,
+            min_power: 100.,
+            max_power: 500.,
+            ..Default::default()
+        };
+        let scanlines = raster.scanlines(&config);
+        // Two scanlines cross the first row of pixels, the second one backwards
+        assert_eq!(
+            scanlines,
+            [
+                Scanline {
+                    from: point(1., 1.75),
+                    runs: vec![(point(2., 1.75), 300.), (point(3., 1.75), 500.)],
+                },
+                Scanline {
+                    from: point(3., 1.25),
+                    runs: vec![(point(2., 1.25), 500.), (point(1., 1.25), 300.)],
+                },
+            ]
+        );
+
+        let dithered = |dithering| {
+            raster
+                .scanlines(&RasterConfig {
+                    dithering,
+                    ..config.clone()
+                })
+                .iter()
+                .flat_map(|scanline| scanline.runs.iter().map(|(_, power)| *power))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(dithered(Dithering::Threshold), [500., 500.]);
+        for dithering in [Dithering::FloydSteinberg, Dithering::Ordered] {
+            assert!(dithered(dithering)
+                .iter()
+                .all(|power| *power == 0. || *power == 500.));
+        }
+
+        for line_interval in [0., -1., f64::NAN] {
+            assert!(raster
+                .scanlines(&RasterConfig {
+                    line_interval,
+                    ..config.clone()
+                })
+                .is_empty());
+        }
+    }
+
+    #[test]
+    fn clipping_keeps_the_pixels_inside() {
+        // Two black pixels then a gray one, each 2mm wide
+        let image = GrayImage::from_netpbm(b"P2 3 1 2\n0 0 1\n").unwrap();
+        let raster = RasterImage {
+            image,
+            transform: Transform2D::scale(2., 2.).then_translate(vector(10., 0.)),
+        };
+
+        // Cutting through the middle pixel right of its center leaves it in, but white
+        let right = raster
+            .clipped(&Box2D::new(point(13.5, -1.), point(20., 5.)))
+            .unwrap();
+        assert_eq!(right.image.width, 2);
+        assert_eq!(right.image.pixels, [1., 0.5]);
+        assert_eq!(
+            right.bounding_box(),
+            Box2D::new(point(12., 0.), point(16., 2.))
+        );
+
+        assert_eq!(
+            raster.clipped(&Box2D::new(point(0., 0.), point(9., 9.))),
+            None
+        );
+        assert_eq!(
+            raster.clipped(&Box2D::new(point(0., 0.), point(100., 100.))),
+            Some(raster)
+        );
+    }
+}
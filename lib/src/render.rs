@@ -0,0 +1,101 @@
+use std::fmt::Write;
+
+use g_code::emit::Token;
+use lyon_geom::{Box2D, Point};
+
+use crate::{follow_program, program::extremes, ProgramStep, Segment};
+
+/// Space left around the moves in millimeters, so lines on the edges aren't cut in half
+const MARGIN: f64 = 1.;
+
+/// Draw a program as an SVG in millimeters, following its moves like a machine would: cuts as
+/// solid lines and moves with the tool off as dashed ones, see [`follow_program`]
+///
+/// Useful for checking the program that was written, with its sequences, without running it on
+/// a machine. Only the X and Y axes are drawn. Where the machine is before the first move isn't
+/// known, so nothing is drawn up to there.
+pub fn program2svg(program: &[Token<'_>]) -> String {
+    let moves = follow_program(program, None)
+        .into_iter()
+        .filter_map(|step| match step {
+            ProgramStep::Move(program_move) => Some((program_move.cuts, program_move.segment?)),
+            ProgramStep::Dwell { .. } => None,
+        })
+        .collect::<Vec<_>>();
+
+    let bounding_box = Box2D::from_points(moves.iter().flat_map(|(_, segment)| extremes(segment)))
+        .inflate(MARGIN, MARGIN);
+    // G-Code coordinates are y-up, SVG coordinates are y-down
+    let flip = |point: Point<f64>| (point.x, bounding_box.max.y - point.y + bounding_box.min.y);
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}mm" height="{h}mm" viewBox="{x} {y} {w} {h}">"#,
+        x = bounding_box.min.x,
+        y = bounding_box.min.y,
+        w = bounding_box.width(),
+        h = bounding_box.height(),
+    );
+    for (cut, style) in [
+        (
+            false,
+            r#"stroke="gray" stroke-width="0.2" stroke-dasharray="1""#,
+        ),
+        (true, r#"stroke="black" stroke-width="0.3""#),
+    ] {
+        let mut d = String::new();
+        let mut end = None;
+        for (_, segment) in moves.iter().filter(|(is_cut, _)| *is_cut == cut) {
+            if end != Some(segment.from()) {
+                let (x, y) = flip(segment.from());
+                let _ = write!(d, "M{x},{y}");
+            }
+            let (x, y) = flip(segment.to());
+            match segment {
+                // Flipping the y-axis turns counterclockwise arcs clockwise
+                Segment::Arc(arc) => {
+                    let _ = write!(
+                        d,
+                        "A{rx},{ry} 0 {large} {sweep} {x},{y}",
+                        rx = arc.radii.x,
+                        ry = arc.radii.y,
+                        large = u8::from(arc.flags.large_arc),
+                        sweep = u8::from(!arc.flags.sweep),
+                    );
+                }
+                _ => {
+                    let _ = write!(d, "L{x},{y}");
+                }
+            }
+            end = Some(segment.to());
+        }
+        if !d.is_empty() {
+            let _ = writeln!(svg, r#"<path d="{d}" fill="none" {style}/>"#);
+        }
+    }
+    svg += "</svg>\n";
+    svg
+}
+
+#[cfg(test)]
+mod test {
+    use g_code::parse::snippet_parser;
+
+    use super::*;
+
+    #[test]
+    fn cuts_and_rapid_moves_are_drawn_apart() {
+        let program = "G21 G90\nG0 X0 Y0\nG1 X10 Y0 F300\nG2 X10 Y10 I0 J5\nG3 X10 Y10 I-5 J0\nG91\nG0 Y-10\nG20\nG1 Y0.5\n";
+        let program = snippet_parser(program)
+            .unwrap()
+            .iter_emit_tokens()
+            .collect::<Vec<_>>();
+        let svg = program2svg(&program);
+        // The full circle reaches up to Y15
+        assert!(svg.contains(r#"viewBox="-1 -1 12 17""#), "{svg}");
+        assert!(svg.contains("A5,5 0 0 1 10,5"), "{svg}");
+        assert_eq!(svg.matches("<path").count(), 2);
+        assert!(roxmltree::Document::parse(&svg).is_ok());
+    }
+}
@@ -0,0 +1,412 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display},
+};
+
+use log::{info, warn};
+use rhai::{Array, CallFnOptions, Dynamic, Engine, Map, Scope, AST, FLOAT, INT};
+
+use crate::{fill::property, ConversionConfig, SourceElement, Toolpath, ToolpathPass};
+
+/// Operations a script may take on one toolpath before it is stopped, so a script that never
+/// finishes can't hang the conversion
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+/// Fields of a toolpath in a script that set how its element is cut, with the attribute each is
+/// kept in, see [`crate::LayerOverride`]
+const SETTINGS: [(&str, &str); 3] = [
+    ("feedrate", "data-feedrate"),
+    ("power", "data-power"),
+    ("passes", "data-passes"),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScriptError {
+    /// The script can't be parsed, with where and why
+    Syntax(String),
+    /// The script doesn't define `fn path()`
+    NoPathFunction,
+}
+
+impl Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Syntax(message) => f.write_str(message),
+            Self::NoPathFunction => f.write_str("the script has no fn path()"),
+        }
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+/// A [Rhai](https://rhai.rs/book/) script that runs on each toolpath once they are ordered, to
+/// filter them, change how they are cut, comment on them, or reorder them
+///
+/// The script defines `fn path()`, which is called with `this` set to a map of the toolpath:
+///
+/// - `tag`, `id`, `layer`: of the element it was drawn from, `()` if unknown
+/// - `stroke`, `fill`: the paint of the element, from its style or attributes
+/// - `attributes`: those of the element, see [`SourceElement::attributes`]
+/// - `feedrate`, `power`, `passes`: from the element's `data-feedrate`, `data-power`, and
+///   `data-passes`, which take precedence over those of its layer, `()` if unset
+/// - `comments`: emitted before the toolpath
+/// - `order`: the toolpaths between pauses are cut in order of this, 0 by default
+/// - `length`, `closed`, `x`, `y`: how long the toolpath is in millimeters, whether it ends where
+///   it starts, and where it starts
+///
+/// Changes to `attributes`, the settings, `comments`, and `order` are kept, and the toolpath is
+/// left out if the function returns `false`. A toolpath the script fails on is kept as it was,
+/// with a warning. Anything the script prints is logged.
+///
+/// ```rhai
+/// fn path() {
+///     if this.stroke == "blue" {
+///         return false;
+///     }
+///     if this.layer == "Outline" {
+///         this.order = 1;
+///     }
+///     this.power = if this.stroke == "red" { 500 } else { 1000 };
+///     this.comments.push(`${this.length} mm`);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+}
+
+impl Script {
+    /// Compile a script, checking that it can be parsed and defines `fn path()`
+    pub fn new(source: &str) -> Result<Self, ScriptError> {
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        // The g-code may be written to stdout
+        engine.on_print(|text| info!("{text}"));
+        engine.on_debug(|text, _, position| info!("{position}: {text}"));
+        let ast = engine
+            .compile(source)
+            .map_err(|err| ScriptError::Syntax(err.to_string()))?;
+        if !ast
+            .iter_functions()
+            .any(|function| function.name == "path" && function.params.is_empty())
+        {
+            return Err(ScriptError::NoPathFunction);
+        }
+        Ok(Self { engine, ast })
+    }
+
+    /// Run `fn path()` on a toolpath, returning its order and the changed toolpath, unless it is
+    /// left out
+    fn run(&self, mut toolpath: Toolpath, tolerance: f64) -> Option<(f64, Toolpath)> {
+        let before = describe(&toolpath, tolerance);
+        let mut this = Dynamic::from_map(before.clone());
+        let result = self.engine.call_fn_with_options::<Dynamic>(
+            CallFnOptions::new().bind_this_ptr(&mut this),
+            &mut Scope::new(),
+            &self.ast,
+            "path",
+            (),
+        );
+        let name = toolpath
+            .source
+            .as_ref()
+            .map_or("toolpath", |source| {
+                source.id.as_deref().unwrap_or(&source.tag_name)
+            })
+            .to_string();
+        match result {
+            Ok(keep) if keep.as_bool() == Ok(false) => return None,
+            Ok(_) => {}
+            Err(err) => {
+                warn!("Script failed on {name}, which is left as it was: {err}");
+                return Some((0., toolpath));
+            }
+        }
+        let Some(this) = this.try_cast::<Map>() else {
+            warn!("Script replaced this of {name}, which is left as it was");
+            return Some((0., toolpath));
+        };
+        match update(&mut toolpath, &before, this) {
+            Ok(order) => Some((order, toolpath)),
+            Err(message) => {
+                warn!("Script {message} for {name}, which is left as it was");
+                Some((0., toolpath))
+            }
+        }
+    }
+}
+
+impl ToolpathPass for Script {
+    fn apply(&self, toolpaths: Vec<Toolpath>, config: &ConversionConfig) -> Vec<Toolpath> {
+        // Toolpaths kept in each stage between pauses, with whether the stage starts with one
+        let mut stages: Vec<(bool, Vec<(f64, Toolpath)>)> = vec![];
+        for mut toolpath in toolpaths {
+            let pause = std::mem::take(&mut toolpath.pause);
+            if pause || stages.is_empty() {
+                stages.push((pause, vec![]));
+            }
+            if let (Some((_, stage)), Some(kept)) =
+                (stages.last_mut(), self.run(toolpath, config.tolerance))
+            {
+                stage.push(kept);
+            }
+        }
+
+        let mut scripted = vec![];
+        // A pause before toolpaths that are all left out still comes before the next stage
+        let mut pause = false;
+        for (stage_pause, mut stage) in stages {
+            pause |= stage_pause;
+            // Stable, so toolpaths of the same order stay in the order they were in
+            stage.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+            for (_, mut toolpath) in stage {
+                toolpath.pause = std::mem::take(&mut pause);
+                scripted.push(toolpath);
+            }
+        }
+        scripted
+    }
+}
+
+/// The toolpath as the script sees it
+fn describe(toolpath: &Toolpath, tolerance: f64) -> Map {
+    let source = toolpath.source.clone().unwrap_or_default();
+    let text = |text: Option<&str>| text.map_or(Dynamic::UNIT, |text| text.to_string().into());
+    let mut this = Map::new();
+    this.insert("tag".into(), text(Some(&source.tag_name)));
+    this.insert("id".into(), text(source.id.as_deref()));
+    this.insert("layer".into(), text(source.layer.as_deref()));
+    this.insert("stroke".into(), text(property(&source, "stroke")));
+    this.insert("fill".into(), text(property(&source, "fill")));
+    for (field, attribute) in SETTINGS {
+        let number = source
+            .attribute(attribute)
+            .and_then(|value| value.trim().parse::<FLOAT>().ok());
+        this.insert(field.into(), number.map_or(Dynamic::UNIT, Dynamic::from));
+    }
+    this.insert(
+        "attributes".into(),
+        Dynamic::from_map(
+            source
+                .attributes
+                .into_iter()
+                .map(|(name, value)| (name.into(), value.into()))
+                .collect(),
+        ),
+    );
+    this.insert(
+        "comments".into(),
+        Dynamic::from_array(
+            toolpath
+                .comments
+                .iter()
+                .cloned()
+                .map(Dynamic::from)
+                .collect(),
+        ),
+    );
+    this.insert("order".into(), Dynamic::from(0 as INT));
+    this.insert("length".into(), Dynamic::from(toolpath.length(tolerance)));
+    this.insert(
+        "closed".into(),
+        Dynamic::from(toolpath.is_closed(tolerance)),
+    );
+    this.insert("x".into(), Dynamic::from(toolpath.from.x));
+    this.insert("y".into(), Dynamic::from(toolpath.from.y));
+    this
+}
+
+/// Keep the changes the script made to the toolpath, returning its order, or what the script set
+/// that can't be used
+fn update(toolpath: &mut Toolpath, before: &Map, mut this: Map) -> Result<f64, String> {
+    let number = |value: &Dynamic| {
+        value
+            .as_float()
+            .ok()
+            .or_else(|| value.as_int().ok().map(|int| int as FLOAT))
+    };
+    let order = match this.get("order") {
+        Some(order) => number(order).ok_or("set an order that is not a number")?,
+        None => 0.,
+    };
+    let Some(Some(comments)) = this.remove("comments").map(Dynamic::try_cast::<Array>) else {
+        return Err("set comments that are not an array".to_string());
+    };
+    let Some(Some(attributes)) = this.remove("attributes").map(Dynamic::try_cast::<Map>) else {
+        return Err("set attributes that are not a map".to_string());
+    };
+    let mut attributes = attributes
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect::<BTreeMap<_, _>>();
+    for (field, attribute) in SETTINGS {
+        let value = this.get(field).cloned().unwrap_or(Dynamic::UNIT);
+        // Only those the script changed, so ones that aren't numbers are still warned about
+        if before.get(field).map(Dynamic::to_string) == Some(value.to_string()) {
+            continue;
+        }
+        if value.is_unit() {
+            attributes.remove(attribute);
+        } else {
+            let value =
+                number(&value).ok_or_else(|| format!("set a {field} that is not a number"))?;
+            attributes.insert(attribute.to_string(), value.to_string());
+        }
+    }
+    toolpath.comments = comments.into_iter().map(|c| c.to_string()).collect();
+    toolpath
+        .source
+        .get_or_insert_with(SourceElement::default)
+        .attributes = attributes;
+    Ok(order)
+}
+
+#[cfg(test)]
+mod test {
+    use lyon_geom::point;
+
+    use super::*;
+    use crate::{svg2program, Machine};
+
+    fn line(stroke: &str, x: f64) -> Toolpath {
+        let mut toolpath = Toolpath::from_polyline(&[point(x, 0.), point(x, 10.)]).unwrap();
+        toolpath.source = Some(SourceElement {
+            tag_name: "path".to_string(),
+            attributes: [("stroke".to_string(), stroke.to_string())].into(),
+            ..Default::default()
+        });
+        toolpath
+    }
+
+    fn run(script: &str, toolpaths: Vec<Toolpath>) -> Vec<Toolpath> {
+        Script::new(script)
+            .unwrap()
+            .apply(toolpaths, &ConversionConfig::default())
+    }
+
+    #[test]
+    fn scripts_filter_change_and_reorder_toolpaths() {
+        let script = r#"
+            fn path() {
+                if this.stroke == "blue" {
+                    return false;
+                }
+                if this.x < 1 {
+                    this.order = 1;
+                }
+                this.power = if this.stroke == "red" { 500 } else { 250.5 };
+                this.comments.push(`${this.length} mm`);
+            }
+        "#;
+        let toolpaths = run(
+            script,
+            vec![line("red", 0.), line("blue", 1.), line("green", 2.)],
+        );
+
+        assert_eq!(toolpaths.len(), 2);
+        assert_eq!(toolpaths[0].from.x, 2.);
+        assert_eq!(toolpaths[1].from.x, 0.);
+        let power = |toolpath: &Toolpath| {
+            toolpath
+                .source
+                .as_ref()
+                .and_then(|source| source.attribute("data-power"))
+                .map(str::to_string)
+        };
+        assert_eq!(power(&toolpaths[0]).as_deref(), Some("250.5"));
+        assert_eq!(power(&toolpaths[1]).as_deref(), Some("500"));
+        assert_eq!(toolpaths[1].comments, ["10.0 mm"]);
+    }
+
+    #[test]
+    fn toolpaths_are_only_reordered_between_pauses() {
+        let mut toolpaths = vec![line("red", 0.), line("blue", 1.), line("green", 2.)];
+        toolpaths[1].pause = true;
+        let script = r#"
+            fn path() {
+                if this.stroke == "blue" {
+                    return false;
+                }
+                this.order = -this.x;
+            }
+        "#;
+        let toolpaths = run(script, toolpaths);
+
+        assert_eq!(toolpaths.len(), 2);
+        assert_eq!(toolpaths[0].from.x, 0.);
+        // The pause before the left out toolpath is kept
+        assert!(!toolpaths[0].pause);
+        assert!(toolpaths[1].pause);
+    }
+
+    #[test]
+    fn toolpaths_the_script_fails_on_are_left_as_they_were() {
+        let toolpaths = vec![line("red", 0.), line("blue", 1.)];
+        let script = r#"
+            fn path() {
+                this.comments.push("seen");
+                if this.stroke == "blue" {
+                    throw "no blue";
+                }
+                this.power = "high";
+            }
+        "#;
+        assert_eq!(run(script, toolpaths.clone()), toolpaths);
+        // Scripts that never finish are stopped
+        assert_eq!(run("fn path() { loop {} }", toolpaths.clone()), toolpaths);
+    }
+
+    #[test]
+    fn toolpaths_given_values_that_cant_be_used_are_left_as_they_were() {
+        let toolpaths = vec![line("red", 0.)];
+        for change in [
+            "this.order = \"first\";",
+            "this.comments = \"seen\";",
+            "this.attributes = [];",
+            "this = 1;",
+        ] {
+            let script = format!("fn path() {{ this.feedrate = 100; {change} }}");
+            assert_eq!(run(&script, toolpaths.clone()), toolpaths, "{change}");
+        }
+    }
+
+    #[test]
+    fn scripts_that_dont_compile_are_not_run() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="10mm" height="10mm" viewBox="0 0 10 10">
+            <path d="M 0 0 L 1 0"/>
+            <path d="M 5 5 L 6 5"/>
+        </svg>"#;
+        let document = roxmltree::Document::parse(svg).unwrap();
+        let convert = |script: Option<&str>| {
+            let config = ConversionConfig {
+                script: script.map(str::to_string),
+                ..Default::default()
+            };
+            let program = svg2program(
+                &document,
+                &config,
+                Default::default(),
+                Machine::new(Default::default(), None, None, None, None),
+            );
+            let mut gcode = String::new();
+            g_code::emit::format_gcode_fmt(program.iter(), Default::default(), &mut gcode).unwrap();
+            gcode
+        };
+        assert_eq!(convert(Some("fn path() {")), convert(None));
+        assert_eq!(convert(Some("fn other() { false }")), convert(None));
+        assert_ne!(convert(Some("fn path() { false }")), convert(None));
+    }
+
+    #[test]
+    fn scripts_must_parse_and_define_path() {
+        assert!(matches!(
+            Script::new("fn path() {"),
+            Err(ScriptError::Syntax(_))
+        ));
+        assert_eq!(
+            Script::new("fn other() {}").unwrap_err(),
+            ScriptError::NoPathFunction
+        );
+    }
+}
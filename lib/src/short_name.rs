@@ -0,0 +1,81 @@
+use std::collections::BTreeSet;
+
+/// Characters kept in short names, besides letters and digits, which the SD card readers of
+/// controllers accept along with FAT
+const ALLOWED_SYMBOLS: [char; 2] = ['_', '-'];
+const STEM_LENGTH: usize = 8;
+const EXTENSION_LENGTH: usize = 3;
+
+/// Names files in the 8.3 form (i.e. `DRAWING.GCO`) that FAT file systems without long file
+/// names and the SD card readers of controllers like Grbl and Marlin can open, see
+/// [`crate::PostprocessConfig::short_file_names`]
+///
+/// Names are upper case, with only letters, digits, `_`, and `-`. Those that are too long, or
+/// would be the same as one given out before, end in a number instead (i.e. `DRAWIN_1.GCO`).
+#[derive(Debug, Default, Clone)]
+pub struct ShortNames {
+    taken: BTreeSet<String>,
+}
+
+impl ShortNames {
+    /// A short name for a file called `name`, different from any given out before
+    pub fn name(&mut self, name: &str) -> String {
+        let (stem, extension) = match name.rsplit_once('.') {
+            Some((stem, extension)) if !stem.is_empty() => (stem, extension),
+            _ => (name, ""),
+        };
+        let mut stem = sanitized(stem);
+        if stem.is_empty() {
+            stem = "FILE".to_string();
+        }
+        let extension = sanitized(extension)
+            .chars()
+            .take(EXTENSION_LENGTH)
+            .collect::<String>();
+        let with_extension = |stem: &str| {
+            if extension.is_empty() {
+                stem.to_string()
+            } else {
+                format!("{stem}.{extension}")
+            }
+        };
+
+        let mut short = with_extension(&stem);
+        let mut number = 0;
+        while stem.len() > STEM_LENGTH || self.taken.contains(&short) {
+            number += 1;
+            let tail = format!("_{number}");
+            let kept = stem.len().min(STEM_LENGTH.saturating_sub(tail.len()));
+            short = with_extension(&format!("{}{tail}", &stem[..kept]));
+            if !self.taken.contains(&short) {
+                break;
+            }
+        }
+        self.taken.insert(short.clone());
+        short
+    }
+}
+
+fn sanitized(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_ascii_alphanumeric() || ALLOWED_SYMBOLS.contains(c))
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn names_are_short_unique_and_plain() {
+        let mut names = ShortNames::default();
+        assert_eq!(names.name("part.gcode"), "PART.GCO");
+        assert_eq!(names.name("Part.gcode"), "PART_1.GCO");
+        assert_eq!(names.name("my drawing (v2).nc"), "MYDRAW_1.NC");
+        assert_eq!(names.name("my drawing (v3).nc"), "MYDRAW_2.NC");
+        assert_eq!(names.name("éé.gcode"), "FILE.GCO");
+        assert_eq!(names.name(".gcode"), "GCODE");
+        assert_eq!(names.name("program"), "PROGRAM");
+    }
+}
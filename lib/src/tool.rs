@@ -0,0 +1,136 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::ConversionConfig;
+#[cfg(test)]
+use crate::Material;
+
+/// What a [`Tool`] cuts with
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ToolKind {
+    /// A router bit with a flat end, as wide as its diameter at any depth
+    #[default]
+    EndMill,
+    /// A router bit with a pointed end, which cuts wider the deeper it goes, up to its diameter
+    VBit,
+    /// A laser, as wide as its spot
+    Laser,
+}
+
+/// A tool in [`ConversionConfig::tools`], with what it can do, so the passes for cutting into
+/// [`ConversionConfig::material`] can be worked out instead of counted by hand
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Tool {
+    /// Used to pick the tool, with [`ConversionConfig::tool`] or [`crate::LayerOverride::tool`]
+    pub name: String,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub kind: ToolKind,
+    /// Width of the cut in millimeters: the diameter of a bit (at its widest for a V-bit), or of
+    /// the spot of a laser
+    pub diameter: f64,
+    /// Angle of the tip of a V-bit in degrees, from one side to the other
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub angle: f64,
+    /// Deepest the tool cuts in one pass in millimeters, or how far into the material one pass of
+    /// a laser burns
+    pub max_depth_of_cut: f64,
+    /// Fastest feedrate in millimeters / minute the tool cuts at, 0 for no limit
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub max_feedrate: f64,
+}
+
+impl Tool {
+    /// Fewest passes that cut `thickness` millimeters deep without going deeper than
+    /// [`Self::max_depth_of_cut`] in any of them
+    pub fn passes(&self, thickness: f64) -> usize {
+        if thickness <= 0. || self.max_depth_of_cut <= 0. {
+            return 1;
+        }
+        // Leeway for rounding error, so a thickness that is a whole number of cuts isn't one more
+        ((thickness / self.max_depth_of_cut) - 1e-9).ceil().max(1.) as usize
+    }
+
+    /// Width of the cut in millimeters at `depth`, which only changes for a V-bit
+    pub fn cut_width(&self, depth: f64) -> f64 {
+        match self.kind {
+            ToolKind::VBit if depth > 0. && self.angle > 0. => {
+                let half_angle = (self.angle / 2.).to_radians();
+                (2. * depth * half_angle.tan()).min(self.diameter)
+            }
+            _ => self.diameter,
+        }
+    }
+
+    /// The config for cutting with the tool: enough passes for [`ConversionConfig::depth`], if
+    /// there is a material, and feedrates no faster than [`Self::max_feedrate`]
+    pub(crate) fn apply(&self, config: &ConversionConfig) -> ConversionConfig {
+        let mut config = config.clone();
+        let depth = config.depth();
+        if depth > 0. {
+            config.passes = self.passes(depth);
+        }
+        if self.max_feedrate > 0. {
+            config.feedrate = config.feedrate.min(self.max_feedrate);
+            for feedrate in &mut config.pass_feedrate {
+                *feedrate = feedrate.min(self.max_feedrate);
+            }
+            if config.finish_feedrate > 0. {
+                config.finish_feedrate = config.finish_feedrate.min(self.max_feedrate);
+            }
+        }
+        config
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn passes_cut_through_the_material_in_even_steps() {
+        let tool = Tool {
+            name: "1/8\" end mill".to_string(),
+            kind: ToolKind::EndMill,
+            diameter: 3.175,
+            angle: 0.,
+            max_depth_of_cut: 1.5,
+            max_feedrate: 1000.,
+        };
+        assert_eq!(tool.passes(6.), 4);
+        assert_eq!(tool.passes(6.1), 5);
+        assert_eq!(tool.passes(0.), 1);
+
+        let config = ConversionConfig {
+            tools: vec![tool.clone()],
+            tool: Some(tool.name.clone()),
+            material: Material {
+                thickness: 6.,
+                ..Default::default()
+            },
+            feedrate: 2000.,
+            pass_feedrate: vec![500., 3000.],
+            ..Default::default()
+        };
+        let config = config.tool().unwrap().apply(&config);
+        assert_eq!(config.passes, 4);
+        assert_eq!(config.feedrate, 1000.);
+        assert_eq!(config.pass_feedrate, [500., 1000.]);
+        assert_eq!(
+            (1..=4)
+                .map(|pass| config.pass_depth(pass))
+                .collect::<Vec<_>>(),
+            [1.5, 3., 4.5, 6.]
+        );
+        assert_eq!(config.cut_width(), 3.175);
+
+        let v_bit = Tool {
+            kind: ToolKind::VBit,
+            angle: 90.,
+            ..tool
+        };
+        assert!((v_bit.cut_width(1.) - 2.).abs() < 1e-9);
+        assert_eq!(v_bit.cut_width(5.), 3.175);
+    }
+}
@@ -0,0 +1,223 @@
+use std::fmt::Debug;
+
+use lyon_geom::euclid::default::Box2D;
+pub use svg2gcode_geometry::{Segment, SourceElement, Toolpath};
+
+pub use self::tile::{tile, Tile};
+use crate::ConversionConfig;
+
+mod tile;
+
+/// A transformation over all toolpaths that runs after SVG conversion and before g-code is emitted.
+///
+/// Implement this to inject custom behavior (i.e. lead-ins, proprietary optimizations)
+/// without modifying the conversion itself. See [`PassRegistry`].
+pub trait ToolpathPass: Debug {
+    /// Toolpaths are given in the order they will be drawn
+    fn apply(&self, toolpaths: Vec<Toolpath>, config: &ConversionConfig) -> Vec<Toolpath>;
+}
+
+/// An ordered collection of [`ToolpathPass`]es to run on each conversion
+#[derive(Debug, Default)]
+pub struct PassRegistry {
+    passes: Vec<Box<dyn ToolpathPass>>,
+}
+
+impl PassRegistry {
+    /// Add a pass, which will run after all previously registered passes
+    pub fn register(&mut self, pass: impl ToolpathPass + 'static) -> &mut Self {
+        self.passes.push(Box::new(pass));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.passes.is_empty()
+    }
+
+    /// Run every pass in registration order
+    pub fn apply(&self, toolpaths: Vec<Toolpath>, config: &ConversionConfig) -> Vec<Toolpath> {
+        self.passes
+            .iter()
+            .fold(toolpaths, |toolpaths, pass| pass.apply(toolpaths, config))
+    }
+}
+
+/// Keeps only the toolpaths that lie entirely inside a rectangle in millimeters, i.e. to redo a
+/// failed area of a larger job
+#[derive(Debug, Clone, PartialEq)]
+pub struct Region(pub Box2D<f64>);
+
+impl ToolpathPass for Region {
+    fn apply(&self, toolpaths: Vec<Toolpath>, config: &ConversionConfig) -> Vec<Toolpath> {
+        let mut kept: Vec<Toolpath> = Vec::with_capacity(toolpaths.len());
+        // A pause before a toolpath that is left out still separates the toolpaths around it
+        let mut pause = false;
+        for mut toolpath in toolpaths {
+            pause |= toolpath.pause;
+            if toolpath
+                .flattened(config.tolerance)
+                .into_iter()
+                .all(|p| self.0.contains_inclusive(p))
+            {
+                toolpath.pause = pause && !kept.is_empty();
+                pause = false;
+                kept.push(toolpath);
+            }
+        }
+        kept
+    }
+}
+
+/// Smallest rectangle containing all of the toolpaths, or [`None`] if there are none
+pub fn bounding_box(toolpaths: &[Toolpath], tolerance: f64) -> Option<Box2D<f64>> {
+    toolpaths
+        .iter()
+        .flat_map(|toolpath| toolpath.flattened(tolerance))
+        .fold(None, |bounds: Option<Box2D<f64>>, p| {
+            Some(match bounds {
+                None => Box2D::new(p, p),
+                Some(bounds) => Box2D::new(bounds.min.min(p), bounds.max.max(p)),
+            })
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use g_code::emit::Token;
+
+    use super::*;
+    use crate::{
+        place, svg2program_with_passes, svg2toolpaths, toolpaths2program, ConversionOptions,
+        LayerOverrides, Machine, SupportedFunctionality,
+    };
+
+    const SVG: &str = r#"<svg xmlns="http://www.w3.org/2000/svg" width="10mm" height="10mm" viewBox="0 0 10 10">
+        <g stroke="red">
+            <path d="M 1 1 L 9 1 L 9 9 Z"/>
+        </g>
+        <path id="blue" stroke="blue" d="M 2 2 L 3 3"/>
+    </svg>"#;
+
+    #[derive(Debug)]
+    struct Reverse;
+
+    impl ToolpathPass for Reverse {
+        fn apply(&self, mut toolpaths: Vec<Toolpath>, _config: &ConversionConfig) -> Vec<Toolpath> {
+            toolpaths.reverse();
+            toolpaths
+        }
+    }
+
+    #[derive(Debug)]
+    struct Discard;
+
+    impl ToolpathPass for Discard {
+        fn apply(&self, _toolpaths: Vec<Toolpath>, _config: &ConversionConfig) -> Vec<Toolpath> {
+            vec![]
+        }
+    }
+
+    #[derive(Debug)]
+    struct OnlyStroke(&'static str);
+
+    impl ToolpathPass for OnlyStroke {
+        fn apply(&self, toolpaths: Vec<Toolpath>, _config: &ConversionConfig) -> Vec<Toolpath> {
+            toolpaths
+                .into_iter()
+                .filter(|toolpath| {
+                    toolpath
+                        .source
+                        .as_ref()
+                        .and_then(|source| source.attribute("stroke"))
+                        == Some(self.0)
+                })
+                .collect()
+        }
+    }
+
+    fn machine() -> Machine<'static> {
+        Machine::new(SupportedFunctionality::default(), None, None, None, None)
+    }
+
+    fn convert(passes: &PassRegistry) -> Vec<Token<'static>> {
+        let document = roxmltree::Document::parse(SVG).unwrap();
+        svg2program_with_passes(
+            &document,
+            &ConversionConfig::default(),
+            ConversionOptions::default(),
+            machine(),
+            passes,
+        )
+    }
+
+    fn rapid_moves(program: &[Token]) -> usize {
+        program
+            .iter()
+            .filter(|token| matches!(token, Token::Field(f) if f.letters == "G" && f.value.as_f64() == Some(0.)))
+            .count()
+    }
+
+    #[test]
+    fn source_element_inherits_presentation_attributes() {
+        let mut only_red = PassRegistry::default();
+        only_red.register(OnlyStroke("red"));
+        assert_eq!(rapid_moves(&convert(&only_red)), 1);
+
+        let mut only_blue = PassRegistry::default();
+        only_blue.register(OnlyStroke("blue"));
+        let only_blue = convert(&only_blue);
+        assert_eq!(rapid_moves(&only_blue), 1);
+        assert!(only_blue.iter().any(
+            |token| matches!(token, Token::Comment { inner, .. } if inner.contains("path#blue"))
+        ));
+    }
+
+    #[test]
+    fn region_keeps_toolpaths_inside_it() {
+        let mut region = PassRegistry::default();
+        region.register(Region(Box2D::new(
+            lyon_geom::point(1.5, 6.5),
+            lyon_geom::point(3.5, 8.5),
+        )));
+        let program = convert(&region);
+        assert_eq!(rapid_moves(&program), 1);
+        assert!(program.iter().any(
+            |token| matches!(token, Token::Comment { inner, .. } if inner.contains("path#blue"))
+        ));
+    }
+
+    #[test]
+    fn registered_passes_run_in_order() {
+        let unchanged = convert(&PassRegistry::default());
+        assert_eq!(rapid_moves(&unchanged), 2);
+
+        let mut reversed = PassRegistry::default();
+        reversed.register(Reverse);
+        let reversed = convert(&reversed);
+        assert_eq!(rapid_moves(&reversed), 2);
+        assert_ne!(reversed, unchanged);
+
+        let mut discarded = PassRegistry::default();
+        discarded.register(Reverse).register(Discard);
+        assert_eq!(rapid_moves(&convert(&discarded)), 0);
+    }
+
+    #[test]
+    fn stages_compose_into_program() {
+        let document = roxmltree::Document::parse(SVG).unwrap();
+        let config = ConversionConfig::default();
+        let options = ConversionOptions::default();
+
+        let placement = place(&document, &config, &options);
+        let toolpaths = svg2toolpaths(&document, &config, options, placement);
+        assert_eq!(toolpaths.toolpaths.len(), 2);
+        // The y-axis points up in millimeters
+        let from = toolpaths.toolpaths[0].from;
+        assert!((from - lyon_geom::point(1., 9.)).length() < 1e-9);
+
+        assert_eq!(
+            toolpaths2program(toolpaths, &config, &LayerOverrides::new(), machine()),
+            convert(&PassRegistry::default())
+        );
+    }
+}
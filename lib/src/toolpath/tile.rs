@@ -0,0 +1,270 @@
+#[cfg(feature = "raster")]
+use lyon_geom::euclid::default::Transform2D;
+use lyon_geom::{euclid::default::Box2D, point, vector, Point};
+use svg2gcode_geometry::clip_to_rect;
+
+use super::{bounding_box, Toolpath};
+#[cfg(feature = "raster")]
+use crate::RasterImage;
+
+/// Longest arm of a registration mark in millimeters
+const MAX_MARK_ARM: f64 = 5.;
+
+/// A section of artwork that fits on the bed, to be drawn as its own program
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tile {
+    /// Counting from 0 at the left
+    pub column: usize,
+    pub columns: usize,
+    /// Counting from 0 at the bottom
+    pub row: usize,
+    pub rows: usize,
+    /// Part of the artwork covered by the tile, in millimeters
+    pub bounds: Box2D<f64>,
+    /// Centers of the registration marks, which are shared with neighboring tiles
+    pub marks: Vec<Point<f64>>,
+    /// The artwork inside the tile followed by its registration marks
+    pub toolpaths: Vec<Toolpath>,
+    /// The parts of the images inside the tile
+    #[cfg(feature = "raster")]
+    pub rasters: Vec<RasterImage>,
+}
+
+impl Tile {
+    /// How to line up the material for this tile, i.e. for comments at the start of its program
+    pub fn instructions(&self) -> Vec<String> {
+        let mut instructions = vec![format!(
+            "tile {} of {} (column {} of {}, row {} of {}) covers x {:.1} to {:.1} mm, y {:.1} to {:.1} mm of the artwork",
+            self.row * self.columns + self.column + 1,
+            self.columns * self.rows,
+            self.column + 1,
+            self.columns,
+            self.row + 1,
+            self.rows,
+            self.bounds.min.x,
+            self.bounds.max.x,
+            self.bounds.min.y,
+            self.bounds.max.y,
+        )];
+        if !self.marks.is_empty() {
+            instructions.push(format!(
+                "align the registration marks at {} with those cut by neighboring tiles",
+                self.marks
+                    .iter()
+                    .map(|mark| format!("({:.1}, {:.1})", mark.x, mark.y))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+        instructions
+    }
+}
+
+/// Split toolpaths that are larger than the bed into tiles that overlap by `overlap` millimeters,
+/// with registration marks in the overlaps.
+///
+/// Each tile is moved so it starts where the artwork did. Returns a single tile with all of the
+/// toolpaths if they already fit on the bed.
+pub fn tile(
+    toolpaths: &[Toolpath],
+    #[cfg(feature = "raster")] rasters: &[RasterImage],
+    bed_size: [f64; 2],
+    overlap: f64,
+    tolerance: f64,
+) -> Vec<Tile> {
+    let artwork = bounding_box(toolpaths, tolerance);
+    #[cfg(feature = "raster")]
+    let artwork = rasters
+        .iter()
+        .map(RasterImage::bounding_box)
+        .fold(artwork, |artwork, raster| {
+            Some(artwork.map_or(raster, |artwork| artwork.union(&raster)))
+        });
+    let Some(artwork) = artwork else {
+        return vec![];
+    };
+    let size = artwork.size();
+    // Always make progress, even if the overlap is as large as the bed
+    let [step_x, step_y] = bed_size.map(|bed| (bed - overlap).max(bed / 2.));
+    let [columns, rows] = [
+        (size.width, bed_size[0], step_x),
+        (size.height, bed_size[1], step_y),
+    ]
+    .map(|(size, bed, step)| {
+        if size <= bed {
+            1
+        } else {
+            ((size - (bed - step)) / step).ceil() as usize
+        }
+    });
+    let overlap = [bed_size[0] - step_x, bed_size[1] - step_y];
+    let arm = (overlap[0].min(overlap[1]) / 2.).min(MAX_MARK_ARM);
+
+    (0..rows)
+        .flat_map(|row| (0..columns).map(move |column| (column, row)))
+        .map(|(column, row)| {
+            let min = artwork.min + vector(column as f64 * step_x, row as f64 * step_y);
+            let bounds = Box2D::new(min, min + vector(bed_size[0], bed_size[1]));
+            let to_tile = artwork.min - bounds.min;
+
+            let mut tile_toolpaths = toolpaths
+                .iter()
+                .flat_map(|toolpath| clip_to_rect(toolpath, &bounds, tolerance))
+                .map(|toolpath| toolpath.translated(to_tile))
+                .collect::<Vec<_>>();
+            #[cfg(feature = "raster")]
+            let tile_rasters = rasters
+                .iter()
+                .filter_map(|raster| raster.clipped(&bounds))
+                .map(|raster| raster.transformed(&Transform2D::translation(to_tile.x, to_tile.y)))
+                .collect();
+
+            // Marks go in the middle of the overlaps, at each corner shared with another tile
+            let inset = [overlap[0] / 2., overlap[1] / 2.];
+            let marks = if arm > 0. {
+                [(false, false), (true, false), (false, true), (true, true)]
+                    .into_iter()
+                    .filter(|(right, top)| {
+                        let horizontal_neighbor = if *right {
+                            column + 1 < columns
+                        } else {
+                            column > 0
+                        };
+                        let vertical_neighbor = if *top { row + 1 < rows } else { row > 0 };
+                        horizontal_neighbor || vertical_neighbor
+                    })
+                    .map(|(right, top)| {
+                        point(
+                            if right {
+                                bounds.max.x - inset[0]
+                            } else {
+                                bounds.min.x + inset[0]
+                            },
+                            if top {
+                                bounds.max.y - inset[1]
+                            } else {
+                                bounds.min.y + inset[1]
+                            },
+                        ) + to_tile
+                    })
+                    .collect::<Vec<_>>()
+            } else {
+                vec![]
+            };
+            for mark in &marks {
+                let mut horizontal =
+                    Toolpath::from_polyline(&[*mark - vector(arm, 0.), *mark + vector(arm, 0.)])
+                        .expect("mark is not empty");
+                horizontal.comments.push("registration mark".to_string());
+                tile_toolpaths.push(horizontal);
+                tile_toolpaths.extend(Toolpath::from_polyline(&[
+                    *mark - vector(0., arm),
+                    *mark + vector(0., arm),
+                ]));
+            }
+
+            Tile {
+                column,
+                columns,
+                row,
+                rows,
+                bounds,
+                marks,
+                toolpaths: tile_toolpaths,
+                #[cfg(feature = "raster")]
+                rasters: tile_rasters,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn artwork_is_split_into_overlapping_tiles() {
+        // 250mm wide and 80mm tall
+        let toolpaths = [
+            Toolpath::from_polyline(&[point(0., 0.), point(250., 0.)]).unwrap(),
+            Toolpath::from_polyline(&[point(0., 80.), point(10., 80.)]).unwrap(),
+        ];
+
+        let fits = tile(
+            &toolpaths,
+            #[cfg(feature = "raster")]
+            &[],
+            [300., 300.],
+            10.,
+            0.1,
+        );
+        assert_eq!(fits.len(), 1);
+        assert_eq!(fits[0].toolpaths, toolpaths);
+        assert!(fits[0].marks.is_empty());
+
+        let tiles = tile(
+            &toolpaths,
+            #[cfg(feature = "raster")]
+            &[],
+            [100., 100.],
+            10.,
+            0.1,
+        );
+        // Steps of 90mm: 0 to 100, 90 to 190, 180 to 280
+        assert_eq!(tiles.len(), 3);
+        assert_eq!(
+            tiles
+                .iter()
+                .map(|tile| tile.bounds.min.x)
+                .collect::<Vec<_>>(),
+            [0., 90., 180.]
+        );
+        // Each tile starts where the artwork did
+        assert_eq!(
+            tiles[1].toolpaths[0].flattened(0.1),
+            [point(0., 0.), point(100., 0.)]
+        );
+        assert_eq!(tiles[2].toolpaths[0].to(), point(70., 0.));
+
+        // Marks are shared with the neighbors on either side
+        assert_eq!(tiles[0].marks, [point(95., 5.), point(95., 95.)]);
+        assert_eq!(
+            tiles[1].marks,
+            [
+                point(5., 5.),
+                point(95., 5.),
+                point(5., 95.),
+                point(95., 95.)
+            ]
+        );
+        assert_eq!(
+            tiles[0].marks[0] + vector(tiles[0].bounds.min.x, 0.),
+            tiles[1].marks[0] + vector(tiles[1].bounds.min.x, 0.)
+        );
+        // Two lines per mark
+        assert_eq!(tiles[2].toolpaths.len(), 1 + 2 * 2);
+        assert!(tiles[1].instructions()[0].starts_with("tile 2 of 3 (column 2 of 3, row 1 of 1)"));
+    }
+
+    #[test]
+    #[cfg(feature = "raster")]
+    fn images_are_split_along_with_the_toolpaths() {
+        use crate::GrayImage;
+
+        // 150mm wide, black on the left half and gray on the right
+        let raster = RasterImage {
+            image: GrayImage::from_netpbm(b"P2 2 1 2\n0 1\n").unwrap(),
+            transform: Transform2D::scale(75., 10.),
+        };
+
+        let tiles = tile(&[], &[raster], [100., 100.], 0., 0.1);
+        assert_eq!(tiles.len(), 2);
+        assert_eq!(tiles[0].rasters[0].image.pixels, [0., 1.]);
+        assert_eq!(tiles[1].rasters[0].image.pixels, [0.5]);
+        // Moved to start where the artwork did, like the toolpaths
+        assert_eq!(
+            tiles[1].rasters[0].bounding_box(),
+            Box2D::new(point(-25., 0.), point(50., 10.))
+        );
+    }
+}
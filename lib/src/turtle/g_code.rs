@@ -1,12 +1,15 @@
 use std::borrow::Cow;
 use std::fmt::Debug;
 
-use ::g_code::{command, emit::Token};
+use ::g_code::{
+    command,
+    emit::{Field, Token, Value},
+};
 use lyon_geom::{CubicBezierSegment, Point, QuadraticBezierSegment, SvgArc};
+use svg2gcode_geometry::{ArcOrLineSegment, FlattenWithArcs};
 
 use super::Turtle;
-use crate::arc::{ArcOrLineSegment, FlattenWithArcs};
-use crate::machine::Machine;
+use crate::{calibration::BacklashCompensation, machine::Machine};
 
 /// Maps path segments into g-code operations
 #[derive(Debug)]
@@ -15,22 +18,115 @@ pub struct GCodeTurtle<'input> {
     pub tolerance: f64,
     pub feedrate: f64,
     pub program: Vec<Token<'input>>,
+    /// Curves are drawn as lines when compensating for backlash, since arcs reverse gradually
+    pub backlash: Option<BacklashCompensation>,
+    pub stay_down: Option<StayDown>,
+    /// Tool power to set with an `S` word when cutting, see [`crate::StrokePower`]
+    pub power: Option<f64>,
+    /// Points of the path about to be cut, for the tool to ramp down along, see
+    /// [`Machine::plunge`]
+    pub path_ahead: Vec<Point<f64>>,
+}
+
+/// Keeps the tool down when moving to a path that starts close to where the last one ended, see
+/// [`crate::ConversionConfig::stay_down_gap`]
+#[derive(Debug, Clone)]
+pub struct StayDown {
+    max_gap: f64,
+    max_travel: f64,
+    /// Where the last cut ended, if the tool is still down
+    down_at: Option<Point<f64>>,
+    /// Length of the gaps moved over since the tool was last lifted
+    travel: f64,
+}
+
+impl StayDown {
+    /// Returns [`None`] if the tool is always lifted, and a `max_travel` of 0 is no limit
+    pub fn new(max_gap: f64, max_travel: f64) -> Option<Self> {
+        (max_gap > 0.).then_some(Self {
+            max_gap,
+            max_travel: if max_travel > 0. {
+                max_travel
+            } else {
+                f64::INFINITY
+            },
+            down_at: None,
+            travel: 0.,
+        })
+    }
+
+    /// Whether the tool can stay down while moving to `to`, counting the gap if it can
+    fn move_to(&mut self, to: Point<f64>) -> bool {
+        let Some(from) = self.down_at else {
+            return false;
+        };
+        let gap = (to - from).length();
+        if gap <= self.max_gap && self.travel + gap <= self.max_travel {
+            self.travel += gap;
+            self.down_at = Some(to);
+            true
+        } else {
+            self.lift();
+            false
+        }
+    }
+
+    fn cut_to(&mut self, to: Point<f64>) {
+        self.down_at = Some(to);
+    }
+
+    fn lift(&mut self) {
+        self.down_at = None;
+        self.travel = 0.;
+    }
 }
 
 impl<'input> GCodeTurtle<'input> {
+    /// Whether curves can be drawn with G2/G3
+    fn circular_interpolation_enabled(&self) -> bool {
+        self.machine
+            .supported_functionality()
+            .circular_interpolation
+            && self.backlash.is_none()
+    }
+
     fn circular_interpolation(&self, svg_arc: SvgArc<f64>) -> Vec<Token<'input>> {
         debug_assert!((svg_arc.radii.x.abs() - svg_arc.radii.y.abs()).abs() < f64::EPSILON);
+        let to = self.machine.snap_to_steps(svg_arc.to);
+        if self.machine.supported_functionality().arc_center_offsets && !svg_arc.flags.large_arc {
+            // Measured from where the machine is, which may be a little off of the arc's start
+            let center = svg_arc.to_arc().center - self.machine.snap_to_steps(svg_arc.from);
+            return if svg_arc.flags.sweep {
+                command!(CounterclockwiseCircularInterpolation {
+                    X: to.x,
+                    Y: to.y,
+                    I: center.x,
+                    J: center.y,
+                    F: self.feedrate,
+                })
+                .into_token_vec()
+            } else {
+                command!(ClockwiseCircularInterpolation {
+                    X: to.x,
+                    Y: to.y,
+                    I: center.x,
+                    J: center.y,
+                    F: self.feedrate,
+                })
+                .into_token_vec()
+            };
+        }
         match (svg_arc.flags.large_arc, svg_arc.flags.sweep) {
             (false, true) => command!(CounterclockwiseCircularInterpolation {
-                X: svg_arc.to.x,
-                Y: svg_arc.to.y,
+                X: to.x,
+                Y: to.y,
                 R: svg_arc.radii.x,
                 F: self.feedrate,
             })
             .into_token_vec(),
             (false, false) => command!(ClockwiseCircularInterpolation {
-                X: svg_arc.to.x,
-                Y: svg_arc.to.y,
+                X: to.x,
+                Y: to.y,
                 R: svg_arc.radii.x,
                 F: self.feedrate,
             })
@@ -44,15 +140,142 @@ impl<'input> GCodeTurtle<'input> {
         }
     }
 
+    /// Append a move, leaving out its feedrate if the machine is already moving at it, and setting
+    /// the tool power on the same line if it changed
+    fn push_move(&mut self, tokens: Vec<Token<'input>>) {
+        for token in tokens {
+            if matches!(&token, Token::Field(field) if field.letters == "F")
+                && !self.machine.feedrate(self.feedrate)
+            {
+                continue;
+            }
+            self.program.push(token);
+        }
+        if let Some(power) = self.power.filter(|power| self.machine.power(*power)) {
+            self.program.push(Token::Field(Field {
+                letters: Cow::Borrowed("S"),
+                value: Value::Float(power),
+            }));
+        }
+    }
+
     fn tool_on(&mut self) {
-        self.program.extend(self.machine.tool_on());
+        // The power is set with each move instead
+        let set_power = self.power.is_some();
+        self.program.extend(self.machine.tool_on().filter(|token| {
+            !(set_power && matches!(token, Token::Field(field) if field.letters == "S"))
+        }));
         self.program.extend(self.machine.absolute());
+        let path_ahead = std::mem::take(&mut self.path_ahead);
+        self.program
+            .extend(self.machine.plunge(self.feedrate, &path_ahead));
     }
 
     fn tool_off(&mut self) {
+        if let Some(stay_down) = &mut self.stay_down {
+            stay_down.lift();
+        }
+        self.program.extend(self.machine.retract());
         self.program.extend(self.machine.tool_off());
         self.program.extend(self.machine.absolute());
     }
+
+    /// Turn the tool on and off with these sequences from now on, see
+    /// [`Machine::set_tool_sequences`], turning it off with the ones used so far if they change
+    pub fn set_tool_sequences(&mut self, sequences: [Option<Vec<Token<'static>>>; 2]) {
+        if *self.machine.tool_sequences() != sequences {
+            self.tool_off();
+            self.machine.set_tool_sequences(sequences);
+        }
+    }
+
+    /// Wait in place with the tool in its current state (i.e. to burn a dot)
+    pub fn dwell(&mut self, seconds: f64) {
+        self.program.extend(self.machine.dwell(seconds));
+    }
+
+    /// Turn the tool on and wait in place for it to pierce the material before cutting
+    pub fn pierce(&mut self, seconds: f64) {
+        self.tool_on();
+        self.dwell(seconds);
+    }
+
+    /// Start a pass over all paths, counting from 1
+    pub fn pass_begin(&mut self, pass: usize, passes: usize, power: Option<f64>) {
+        self.program
+            .extend(self.machine.pass_begin(pass, passes, power));
+    }
+
+    pub fn pass_end(&mut self) {
+        self.program.extend(self.machine.pass_end());
+    }
+
+    /// Start a path within a pass, counting from 1
+    pub fn path_begin(&mut self, path: usize, paths: usize) {
+        self.program.extend(self.machine.path_begin(path, paths));
+    }
+
+    pub fn path_end(&mut self) {
+        self.program.extend(self.machine.path_end());
+    }
+
+    /// Same as [`Turtle::line_to`], also moving the Z axis to `z` along the way (i.e. for a focus
+    /// ramp)
+    pub fn line_z_to(&mut self, to: Point<f64>, z: Option<f64>) {
+        self.tool_on();
+        if let Some(stay_down) = &mut self.stay_down {
+            stay_down.cut_to(to);
+        }
+        let to = match &mut self.backlash {
+            Some(backlash) => {
+                let (slack, to) = backlash.move_to(to);
+                if let Some(slack) = slack {
+                    let slack = self.machine.snap_to_steps(slack);
+                    self.push_move(
+                        command!(LinearInterpolation {
+                            X: slack.x,
+                            Y: slack.y,
+                            F: self.feedrate,
+                        })
+                        .into_token_vec(),
+                    );
+                }
+                to
+            }
+            None => to,
+        };
+        let to = self.machine.snap_to_steps(to);
+        if let Some(z) = z {
+            self.machine.moved_z(z);
+        }
+        let tokens = match z {
+            Some(z) => command!(LinearInterpolation {
+                X: to.x,
+                Y: to.y,
+                Z: z,
+                F: self.feedrate,
+            }),
+            None => command!(LinearInterpolation {
+                X: to.x,
+                Y: to.y,
+                F: self.feedrate,
+            }),
+        };
+        self.push_move(tokens.into_token_vec());
+    }
+
+    /// Make wherever the machine is the origin (G92 X0 Y0), so the program runs from there, until
+    /// it [`Turtle::end`]s
+    pub fn zero_here(&mut self) {
+        self.program.extend(self.machine.zero_here());
+    }
+
+    /// Move the Z axis to `z` with a rapid move, i.e. to the height of the next cut
+    pub fn move_z_to(&mut self, z: f64) {
+        self.machine.moved_z(z);
+        self.program
+            .append(&mut command!(RapidPositioning { Z: z }).into_token_vec());
+    }
 }
 
 impl<'input> Turtle for GCodeTurtle<'input> {
@@ -60,13 +283,16 @@ impl<'input> Turtle for GCodeTurtle<'input> {
         self.program
             .append(&mut command!(UnitsMillimeters {}).into_token_vec());
         self.program.extend(self.machine.absolute());
+        self.program.extend(self.machine.home());
         self.program.extend(self.machine.program_begin());
         self.program.extend(self.machine.absolute());
     }
 
     fn end(&mut self) {
+        self.program.extend(self.machine.retract());
         self.program.extend(self.machine.tool_off());
         self.program.extend(self.machine.absolute());
+        self.program.extend(self.machine.clear_zero());
         self.program.extend(self.machine.program_end());
     }
 
@@ -77,22 +303,38 @@ impl<'input> Turtle for GCodeTurtle<'input> {
         });
     }
 
+    /// Stop until the operator resumes the program (M0), with the tool off
+    fn pause(&mut self) {
+        self.tool_off();
+        self.program.push(Token::Field(Field {
+            letters: Cow::Borrowed("M"),
+            value: Value::Integer(0),
+        }));
+    }
+
     fn move_to(&mut self, to: Point<f64>) {
+        // The tool cuts across the gap while it stays down, so it goes at the feedrate of the cut
+        if self
+            .stay_down
+            .as_mut()
+            .is_some_and(|stay_down| stay_down.move_to(to))
+        {
+            self.line_to(to);
+            return;
+        }
         self.tool_off();
+        // Rapid moves take up the slack on the way
+        let to = match &mut self.backlash {
+            Some(backlash) => backlash.move_to(to).1,
+            None => to,
+        };
+        let to = self.machine.snap_to_steps(to);
         self.program
             .append(&mut command!(RapidPositioning { X: to.x, Y: to.y }).into_token_vec());
     }
 
     fn line_to(&mut self, to: Point<f64>) {
-        self.tool_on();
-        self.program.append(
-            &mut command!(LinearInterpolation {
-                X: to.x,
-                Y: to.y,
-                F: self.feedrate,
-            })
-            .into_token_vec(),
-        );
+        self.line_z_to(to, None);
     }
 
     fn arc(&mut self, svg_arc: SvgArc<f64>) {
@@ -103,17 +345,11 @@ impl<'input> Turtle for GCodeTurtle<'input> {
 
         self.tool_on();
 
-        if self
-            .machine
-            .supported_functionality()
-            .circular_interpolation
-        {
+        if self.circular_interpolation_enabled() {
             FlattenWithArcs::flattened(&svg_arc, self.tolerance)
                 .into_iter()
                 .for_each(|segment| match segment {
-                    ArcOrLineSegment::Arc(arc) => {
-                        self.program.append(&mut self.circular_interpolation(arc))
-                    }
+                    ArcOrLineSegment::Arc(arc) => self.push_move(self.circular_interpolation(arc)),
                     ArcOrLineSegment::Line(line) => {
                         self.line_to(line.to);
                     }
@@ -124,28 +360,28 @@ impl<'input> Turtle for GCodeTurtle<'input> {
                 .flattened(self.tolerance)
                 .for_each(|point| self.line_to(point));
         };
+        if let Some(stay_down) = &mut self.stay_down {
+            stay_down.cut_to(svg_arc.to);
+        }
     }
 
     fn cubic_bezier(&mut self, cbs: CubicBezierSegment<f64>) {
         self.tool_on();
 
-        if self
-            .machine
-            .supported_functionality()
-            .circular_interpolation
-        {
+        if self.circular_interpolation_enabled() {
             FlattenWithArcs::<f64>::flattened(&cbs, self.tolerance)
                 .into_iter()
                 .for_each(|segment| match segment {
-                    ArcOrLineSegment::Arc(arc) => {
-                        self.program.append(&mut self.circular_interpolation(arc))
-                    }
+                    ArcOrLineSegment::Arc(arc) => self.push_move(self.circular_interpolation(arc)),
                     ArcOrLineSegment::Line(line) => self.line_to(line.to),
                 });
         } else {
             cbs.flattened(self.tolerance)
                 .for_each(|point| self.line_to(point));
         };
+        if let Some(stay_down) = &mut self.stay_down {
+            stay_down.cut_to(cbs.to);
+        }
     }
 
     fn quadratic_bezier(&mut self, qbs: QuadraticBezierSegment<f64>) {
@@ -0,0 +1,152 @@
+use svg2gcode::{Barcode, BarcodeFill, QrErrorCorrection, Symbology};
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+use yewdux::use_dispatch;
+
+use crate::{
+    state::{AppState, Svg},
+    ui::{Button, ButtonStyle, Checkbox, Input, InputType},
+};
+
+/// Generates a QR code or Code 128 barcode and adds it as an SVG
+#[function_component(BarcodeForm)]
+pub fn barcode_form() -> Html {
+    let app_dispatch = use_dispatch::<AppState>();
+
+    let text = use_state(String::new);
+    let text_oninput = {
+        let text = text.clone();
+        Callback::from(move |event: InputEvent| {
+            text.set(event.target_unchecked_into::<HtmlInputElement>().value());
+        })
+    };
+
+    let parse_millimeters = |value: String| -> Option<Result<f64, String>> {
+        (!value.is_empty()).then(|| match value.parse::<f64>() {
+            Ok(mm) if mm > 0. => Ok(mm),
+            Ok(_) => Err("must be positive".to_string()),
+            Err(err) => Err(err.to_string()),
+        })
+    };
+    let width = use_state(|| Option::<Result<f64, String>>::None);
+    let width_oninput = {
+        let width = width.clone();
+        Callback::from(move |event: InputEvent| {
+            width.set(parse_millimeters(
+                event.target_unchecked_into::<HtmlInputElement>().value(),
+            ));
+        })
+    };
+    let hatch = use_state(|| Option::<Result<f64, String>>::None);
+    let hatch_oninput = {
+        let hatch = hatch.clone();
+        Callback::from(move |event: InputEvent| {
+            hatch.set(parse_millimeters(
+                event.target_unchecked_into::<HtmlInputElement>().value(),
+            ));
+        })
+    };
+
+    let code128 = use_state(|| false);
+    let code128_onchange = {
+        let code128 = code128.clone();
+        Callback::from(move |_| code128.set(!*code128))
+    };
+
+    let generated = use_state(|| Option::<Result<(), String>>::None);
+    let add_onclick = {
+        let text = text.clone();
+        let width = width.clone();
+        let hatch = hatch.clone();
+        let code128 = code128.clone();
+        let generated = generated.clone();
+        app_dispatch.reduce_mut_callback(move |app| {
+            let Some(Ok(width)) = *width else {
+                return;
+            };
+            let barcode = Barcode {
+                text: (*text).clone(),
+                symbology: if *code128 {
+                    Symbology::Code128 { height: width / 4. }
+                } else {
+                    Symbology::QrCode(QrErrorCorrection::default())
+                },
+                width,
+                fill: match *hatch {
+                    Some(Ok(spacing)) => BarcodeFill::Hatch { spacing },
+                    _ => BarcodeFill::Outline,
+                },
+            };
+            match barcode.to_svg() {
+                Ok(content) => {
+                    app.svgs.push(Svg {
+                        content,
+                        filename: format!("{}.svg", if *code128 { "barcode" } else { "qr_code" }),
+                        dimensions: [None; 2],
+                        scale: 1.0,
+                        offset: [0.0, 0.0],
+                        rotation: 0.0,
+                        skip_ids: vec![],
+                        region: None,
+                        locked: false,
+                        selected: false,
+                    });
+                    generated.set(Some(Ok(())));
+                }
+                Err(err) => generated.set(Some(Err(format!("Error generating barcode: {err}")))),
+            }
+        })
+    };
+
+    let disabled = text.is_empty()
+        || !matches!(*width, Some(Ok(_)))
+        || hatch.as_ref().is_some_and(|hatch| hatch.is_err());
+
+    html! {
+        <>
+            <Input<String, String>
+                label="Generate a barcode for text"
+                desc="Encoded as a QR code unless Code 128 is selected below"
+                r#type={InputType::Text}
+                placeholder="SN-0001"
+                oninput={text_oninput}
+                parsed={(*generated).clone().map(|res| res.map(|_| (*text).clone()))}
+                button={html_nested!(
+                    <Button
+                        style={ButtonStyle::Primary}
+                        title="Add"
+                        input_group=true
+                        disabled={disabled}
+                        onclick={add_onclick}
+                    />
+                )}
+            />
+            <div class="columns">
+                <div class="column col-6">
+                    <Input<f64, String>
+                        label="Barcode width (mm)"
+                        desc="Excluding the quiet zone around it"
+                        r#type={InputType::Text}
+                        oninput={width_oninput}
+                        parsed={(*width).clone()}
+                    />
+                </div>
+                <div class="column col-6">
+                    <Input<f64, String>
+                        label="Hatch spacing (mm)"
+                        desc="Fill dark modules with lines this far apart (i.e. the tool width), else only outline them"
+                        r#type={InputType::Text}
+                        oninput={hatch_oninput}
+                        parsed={(*hatch).clone()}
+                    />
+                </div>
+            </div>
+            <Checkbox
+                label="Code 128"
+                desc="Generate a linear barcode a quarter as tall as it is wide"
+                checked={*code128}
+                onchange={code128_onchange}
+            />
+        </>
+    }
+}
@@ -1,7 +1,10 @@
 use codespan_reporting::term::{emit, termcolor::NoColor, Config};
-use g_code::parse::{into_diagnostic, snippet_parser};
+use g_code::parse::{ast::Snippet, into_diagnostic, snippet_parser};
 use gloo_timers::callback::Timeout;
 use paste::paste;
+#[cfg(feature = "script")]
+use svg2gcode::Script;
+use svg2gcode::{unexpected_mode, SnippetTemplate, UnexpectedMode};
 use web_sys::HtmlInputElement;
 use yew::prelude::*;
 use yewdux::functional::{use_store, use_store_value};
@@ -17,6 +20,8 @@ macro_rules! gcode_input {
         $desc: literal,
         $form_accessor: expr $(=> $form_idx: literal)?,
         $app_accessor: expr $(=> $app_idx: literal)?,
+        $parser: expr,
+        $check: expr,
     })*) => {
         $(
             paste! {
@@ -31,8 +36,11 @@ macro_rules! gcode_input {
                         let timeout = timeout.clone();
                         form_dispatch.reduce_mut_callback_with(move |state, event: InputEvent| {
                             let value = event.target_unchecked_into::<HtmlInputElement>().value();
-                            let res = Some(match snippet_parser(&value) {
-                                Ok(_) => Ok(value),
+                            let res = Some(match $parser(&value) {
+                                Ok(parsed) => match ($check)(&parsed) {
+                                    Some(mode) => Err(format!("This sequence {mode}")),
+                                    None => Ok(value),
+                                },
                                 Err(err) => {
                                     let mut buf = NoColor::new(vec![]);
                                     let config = Config::default();
@@ -46,7 +54,7 @@ macro_rules! gcode_input {
                                     Err(String::from_utf8_lossy(buf.get_ref().as_slice()).to_string())
                                 }
                             }).filter(|res| {
-                                !res.as_ref().ok().map_or(false, |value| value.is_empty())
+                                !res.as_ref().ok().is_some_and(|value| value.is_empty())
                             });
 
                             let timeout_inner = timeout.clone();
@@ -71,30 +79,137 @@ macro_rules! gcode_input {
     };
 }
 
+fn snippet_mode(snippet: &Snippet) -> Option<UnexpectedMode> {
+    unexpected_mode(&snippet.iter_emit_tokens().collect::<Vec<_>>())
+}
+
 gcode_input! {
     ToolOnSequence {
         "Tool On Sequence",
         "G-Code for turning on the tool",
         tool_on_sequence,
         settings.machine.tool_on_sequence,
+        snippet_parser,
+        snippet_mode,
     }
     ToolOffSequence {
         "Tool Off Sequence",
         "G-Code for turning off the tool",
         tool_off_sequence,
         settings.machine.tool_off_sequence,
+        snippet_parser,
+        snippet_mode,
     }
     BeginSequence {
         "Program Begin Sequence",
         "G-Code for initializing the machine at the beginning of the program",
         begin_sequence,
         settings.machine.begin_sequence,
+        snippet_parser,
+        snippet_mode,
     }
     EndSequence {
         "Program End Sequence",
         "G-Code for stopping/idling the machine at the end of the program",
         end_sequence,
         settings.machine.end_sequence,
+        snippet_parser,
+        // Nothing runs after it
+        |_: &Snippet| None::<UnexpectedMode>,
+    }
+    PreHomeSequence {
+        "Pre-Home Sequence",
+        "G-Code run right before homing. This and the sequences below may use {pass}, {passes}, {path}, and {paths}",
+        pre_home_sequence,
+        settings.machine.pre_home_sequence,
+        SnippetTemplate::parse,
+        SnippetTemplate::unexpected_mode,
+    }
+    HomeSequence {
+        "Home Sequence",
+        "G-Code for homing the machine, after units are set and before the program begin sequence",
+        home_sequence,
+        settings.machine.home_sequence,
+        SnippetTemplate::parse,
+        SnippetTemplate::unexpected_mode,
+    }
+    PostHomeSequence {
+        "Post-Home Sequence",
+        "G-Code run right after homing",
+        post_home_sequence,
+        settings.machine.post_home_sequence,
+        SnippetTemplate::parse,
+        SnippetTemplate::unexpected_mode,
+    }
+    PassBeginSequence {
+        "Pass Begin Sequence",
+        "G-Code run before each pass over all paths",
+        pass_begin_sequence,
+        settings.machine.pass_begin_sequence,
+        SnippetTemplate::parse,
+        SnippetTemplate::unexpected_mode,
+    }
+    PassEndSequence {
+        "Pass End Sequence",
+        "G-Code run after each pass over all paths",
+        pass_end_sequence,
+        settings.machine.pass_end_sequence,
+        SnippetTemplate::parse,
+        SnippetTemplate::unexpected_mode,
+    }
+    PathBeginSequence {
+        "Path Begin Sequence",
+        "G-Code run before moving to the start of each path",
+        path_begin_sequence,
+        settings.machine.path_begin_sequence,
+        SnippetTemplate::parse,
+        SnippetTemplate::unexpected_mode,
+    }
+    PathEndSequence {
+        "Path End Sequence",
+        "G-Code run after the last cut of each path, before the tool is turned off",
+        path_end_sequence,
+        settings.machine.path_end_sequence,
+        SnippetTemplate::parse,
+        SnippetTemplate::unexpected_mode,
+    }
+}
+
+/// Editor for the Rhai script run on each path, checked as it is typed like the sequences
+#[cfg(feature = "script")]
+#[function_component(ScriptInput)]
+pub fn script_input() -> Html {
+    const VALIDATION_TIMEOUT: u32 = 350;
+    let app_state = use_store_value::<AppState>();
+    let (form_state, form_dispatch) = use_store::<FormState>();
+
+    let timeout = use_state::<Option<Timeout>, _>(|| None);
+    let oninput = {
+        let timeout = timeout.clone();
+        form_dispatch.reduce_mut_callback_with(move |state, event: InputEvent| {
+            let value = event.target_unchecked_into::<HtmlInputElement>().value();
+            let res = (!value.trim().is_empty()).then(|| match Script::new(&value) {
+                Ok(_) => Ok(value),
+                Err(err) => Err(err.to_string()),
+            });
+
+            let timeout_inner = timeout.clone();
+            timeout.set(Some(Timeout::new(VALIDATION_TIMEOUT, move || {
+                timeout_inner.set(None);
+            })));
+            state.script = res;
+        })
+    };
+    html! {
+        <FormGroup success={form_state.script.as_ref().map(Result::is_ok)}>
+            <TextArea<String, String> label="Script"
+                desc="Rhai script whose fn path() runs on each path as this, to leave it out (return false), change its feedrate, power, or passes, add comments, or set its order"
+                default={app_state.settings.conversion.script.clone()}
+                parsed={form_state.script.clone().filter(|_| timeout.is_none())}
+                oninput={oninput}
+                rows={8}
+            />
+        </FormGroup>
     }
 }
 
@@ -1,10 +1,11 @@
 use paste::paste;
-use std::num::ParseFloatError;
+use std::convert::Infallible;
+use std::num::{ParseFloatError, ParseIntError};
 use yew::prelude::*;
 use yewdux::functional::{use_store, use_store_value};
 
 use crate::{
-    state::{AppState, FormState},
+    state::{parse_positive, AppState, FormState, NumberError},
     ui::*,
 };
 
@@ -41,6 +42,200 @@ macro_rules! form_input {
             }
         )*
     };
+    // Version for comma-separated lists (pass schedules)
+    ($($name: ident list {
+        $label: literal,
+        $desc: literal,
+        $form_accessor: expr,
+        $app_accessor: expr,
+    })*) => {
+        $(
+            paste! {
+                #[function_component([<$name Input>])]
+                pub fn [<$name:snake:lower _input>]() -> Html {
+                    let app_state = use_store_value::<AppState>();
+                    let (form_state, form_dispatch) = use_store::<FormState>();
+                    let oninput = form_dispatch.reduce_mut_callback_with(|state, event: InputEvent| {
+                        let value = event.target_unchecked_into::<web_sys::HtmlInputElement>().value();
+                        state.$form_accessor = value
+                            .split(',')
+                            .map(str::trim)
+                            .filter(|value| !value.is_empty())
+                            .map(str::parse::<f64>)
+                            .collect();
+                    });
+                    let join = |values: &Vec<f64>| values.iter().map(f64::to_string).collect::<Vec<_>>().join(", ");
+                    html! {
+                        <FormGroup success={form_state.$form_accessor.is_ok()}>
+                            <Input<String, ParseFloatError> label=$label desc=$desc
+                                default={join(&app_state.$app_accessor)}
+                                parsed={form_state.$form_accessor.as_ref().map(join).map_err(Clone::clone)}
+                                oninput={oninput}
+                            />
+                        </FormGroup>
+                    }
+                }
+            }
+        )*
+    };
+    // Version for comma-separated lists of values that must be above 0 (pass feedrates)
+    ($($name: ident positive list {
+        $label: literal,
+        $desc: literal,
+        $form_accessor: expr,
+        $app_accessor: expr,
+    })*) => {
+        $(
+            paste! {
+                #[function_component([<$name Input>])]
+                pub fn [<$name:snake:lower _input>]() -> Html {
+                    let app_state = use_store_value::<AppState>();
+                    let (form_state, form_dispatch) = use_store::<FormState>();
+                    let oninput = form_dispatch.reduce_mut_callback_with(|state, event: InputEvent| {
+                        let value = event.target_unchecked_into::<web_sys::HtmlInputElement>().value();
+                        state.$form_accessor = value
+                            .split(',')
+                            .map(str::trim)
+                            .filter(|value| !value.is_empty())
+                            .map(parse_positive)
+                            .collect();
+                    });
+                    let join = |values: &Vec<f64>| values.iter().map(f64::to_string).collect::<Vec<_>>().join(", ");
+                    html! {
+                        <FormGroup success={form_state.$form_accessor.is_ok()}>
+                            <Input<String, NumberError> label=$label desc=$desc
+                                default={join(&app_state.$app_accessor)}
+                                parsed={form_state.$form_accessor.as_ref().map(join).map_err(Clone::clone)}
+                                oninput={oninput}
+                            />
+                        </FormGroup>
+                    }
+                }
+            }
+        )*
+    };
+    // Version for values that must be above 0 (feedrates)
+    ($($name: ident positive {
+        $label: literal,
+        $desc: literal,
+        $form_accessor: expr,
+        $app_accessor: expr,
+    })*) => {
+        $(
+            paste! {
+                #[function_component([<$name Input>])]
+                pub fn [<$name:snake:lower _input>]() -> Html {
+                    let app_state = use_store_value::<AppState>();
+                    let (form_state, form_dispatch) = use_store::<FormState>();
+                    let oninput = form_dispatch.reduce_mut_callback_with(|state, event: InputEvent| {
+                        let value = event.target_unchecked_into::<web_sys::HtmlInputElement>().value();
+                        state.$form_accessor = parse_positive(&value);
+                    });
+                    html! {
+                        <FormGroup success={form_state.$form_accessor.is_ok()}>
+                            <Input<f64, NumberError> label=$label desc=$desc
+                                default={app_state.$app_accessor}
+                                parsed={form_state.$form_accessor.clone()}
+                                oninput={oninput}
+                            />
+                        </FormGroup>
+                    }
+                }
+            }
+        )*
+    };
+    // Version for whole numbers (passes, tabs)
+    ($($name: ident integer {
+        $label: literal,
+        $desc: literal,
+        $form_accessor: expr,
+        $app_accessor: expr,
+    })*) => {
+        $(
+            paste! {
+                #[function_component([<$name Input>])]
+                pub fn [<$name:snake:lower _input>]() -> Html {
+                    let app_state = use_store_value::<AppState>();
+                    let (form_state, form_dispatch) = use_store::<FormState>();
+                    let oninput = form_dispatch.reduce_mut_callback_with(|state, event: InputEvent| {
+                        let value = event.target_unchecked_into::<web_sys::HtmlInputElement>().value();
+                        state.$form_accessor = value.parse::<usize>();
+                    });
+                    html! {
+                        <FormGroup success={form_state.$form_accessor.is_ok()}>
+                            <Input<usize, ParseIntError> label=$label desc=$desc
+                                default={app_state.$app_accessor}
+                                parsed={form_state.$form_accessor.clone()}
+                                oninput={oninput}
+                            />
+                        </FormGroup>
+                    }
+                }
+            }
+        )*
+    };
+    // Version for fields of optional settings (i.e. fill), which show their defaults while unset
+    ($($name: ident in $section: ident {
+        $label: literal,
+        $desc: literal,
+        $form_accessor: ident,
+        $section_field: ident,
+    })*) => {
+        $(
+            paste! {
+                #[function_component([<$name Input>])]
+                pub fn [<$name:snake:lower _input>]() -> Html {
+                    let app_state = use_store_value::<AppState>();
+                    let (form_state, form_dispatch) = use_store::<FormState>();
+                    let oninput = form_dispatch.reduce_mut_callback_with(|state, event: InputEvent| {
+                        let value = event.target_unchecked_into::<web_sys::HtmlInputElement>().value();
+                        state.$form_accessor = value.parse::<f64>();
+                    });
+                    let section = app_state.settings.conversion.$section.clone().unwrap_or_default();
+                    html! {
+                        <FormGroup success={form_state.$form_accessor.is_ok()}>
+                            <Input<f64, ParseFloatError> label=$label desc=$desc
+                                default={section.$section_field}
+                                parsed={form_state.$form_accessor.clone()}
+                                oninput={oninput}
+                            />
+                        </FormGroup>
+                    }
+                }
+            }
+        )*
+    };
+    // Version for fields of optional settings that must be above 0 (fill spacing)
+    ($($name: ident positive in $section: ident {
+        $label: literal,
+        $desc: literal,
+        $form_accessor: ident,
+        $section_field: ident,
+    })*) => {
+        $(
+            paste! {
+                #[function_component([<$name Input>])]
+                pub fn [<$name:snake:lower _input>]() -> Html {
+                    let app_state = use_store_value::<AppState>();
+                    let (form_state, form_dispatch) = use_store::<FormState>();
+                    let oninput = form_dispatch.reduce_mut_callback_with(|state, event: InputEvent| {
+                        let value = event.target_unchecked_into::<web_sys::HtmlInputElement>().value();
+                        state.$form_accessor = parse_positive(&value);
+                    });
+                    let section = app_state.settings.conversion.$section.clone().unwrap_or_default();
+                    html! {
+                        <FormGroup success={form_state.$form_accessor.is_ok()}>
+                            <Input<f64, NumberError> label=$label desc=$desc
+                                default={section.$section_field}
+                                parsed={form_state.$form_accessor.clone()}
+                                oninput={oninput}
+                            />
+                        </FormGroup>
+                    }
+                }
+            }
+        )*
+    };
     // Version for required fields (tolerance, feedrate, dpi, bed_size)
     ($($name: ident {
         $label: literal,
@@ -77,16 +272,10 @@ macro_rules! form_input {
 form_input! {
     Tolerance {
         "Tolerance",
-        "Curve interpolation tolerance (mm)",
+        "Greatest distance the lines a curve is cut as may stray from it, so large curves get more lines and small ones fewer (mm)",
         tolerance,
         settings.conversion.tolerance,
     }
-    Feedrate {
-        "Feedrate",
-        "Machine feedrate (mm/min)",
-        feedrate,
-        settings.conversion.feedrate,
-    }
     Dpi {
         "Dots per Inch",
         "Used for scaling visual units (pixels, points, picas, etc.)",
@@ -105,6 +294,228 @@ form_input! {
         bed_size => 1,
         settings.conversion.bed_size => 1,
     }
+    BacklashX {
+        "Backlash X",
+        "Play in the x-axis to take up when it reverses, if the firmware does not (mm)",
+        backlash => 0,
+        settings.machine.backlash => 0,
+    }
+    BacklashY {
+        "Backlash Y",
+        "Play in the y-axis to take up when it reverses, if the firmware does not (mm)",
+        backlash => 1,
+        settings.machine.backlash => 1,
+    }
+    StepSizeX {
+        "Step Size X",
+        "Distance the x-axis moves per motor step, coordinates are rounded onto it (mm)",
+        step_size => 0,
+        settings.machine.step_size => 0,
+    }
+    StepSizeY {
+        "Step Size Y",
+        "Distance the y-axis moves per motor step, coordinates are rounded onto it (mm)",
+        step_size => 1,
+        settings.machine.step_size => 1,
+    }
+    TabWidth {
+        "Tab Width",
+        "Length of each tab (mm)",
+        tab_width,
+        settings.conversion.tab_width,
+    }
+    PerforationCut {
+        "Perforation Cut",
+        "Perforate closed paths with cuts of this length so parts stay attached until popped out, 0 to disable (mm)",
+        perforation_cut,
+        settings.conversion.perforation_cut,
+    }
+    PerforationGap {
+        "Perforation Gap",
+        "Length of the uncut gap after each perforation cut (mm)",
+        perforation_gap,
+        settings.conversion.perforation_gap,
+    }
+    HealTolerance {
+        "Heal Tolerance",
+        "Merge points within this distance of each other and join paths that then touch, to clean up messy artwork, 0 to disable (mm)",
+        heal_tolerance,
+        settings.conversion.heal_tolerance,
+    }
+    CloseTolerance {
+        "Close Tolerance",
+        "Close paths that end within this distance of where they started, as CAD exports often leave them, 0 to disable (mm)",
+        close_tolerance,
+        settings.conversion.close_tolerance,
+    }
+    OverlapTolerance {
+        "Overlap Tolerance",
+        "Remove the parts of lines that an earlier line already covers, to within this distance, so edges that shapes share are only cut once, 0 to disable (mm)",
+        overlap_tolerance,
+        settings.conversion.overlap_tolerance,
+    }
+    TravelZ {
+        "Travel Z",
+        "Height to lift the Z axis to before every rapid move, like the safe height of a router or pen up for a plotter (mm)",
+        travel_z,
+        settings.machine.travel_z.unwrap_or_default(),
+    }
+    CutZ {
+        "Cut Z",
+        "Height to plunge the Z axis to for cutting, like pen down for a plotter, or each pass goes down to its depth when there is a material (mm)",
+        cut_z,
+        settings.machine.cut_z,
+    }
+    ZFeedrate {
+        "Z Feedrate",
+        "Feedrate to plunge at, 0 for the feedrate of the cut (mm/min)",
+        z_feedrate,
+        settings.machine.z_feedrate,
+    }
+    RampAngle {
+        "Ramp Angle",
+        "Steepest angle from the surface to ramp down into the material at, which small end mills last longer at, 0 for 3 (degrees)",
+        ramp_angle,
+        settings.machine.ramp_angle,
+    }
+    HelixRadius {
+        "Helix Radius",
+        "Radius of the circles to spiral down in, a little under that of the bit so the middle is cut too, inside closed paths, which others are ramped into instead (mm)",
+        helix_radius,
+        settings.machine.helix_radius,
+    }
+    MaterialThickness {
+        "Material Thickness",
+        "Thickness of the material for the tool to cut through, in as many passes as it takes instead of the passes above, each deeper by the same amount, available in sequences as {depth} and {z}, 0 to disable (mm)",
+        material_thickness,
+        settings.conversion.material.thickness,
+    }
+    SurfaceZ {
+        "Surface Z",
+        "Z coordinate of the top of the material, where depths are measured from (mm)",
+        surface_z,
+        settings.conversion.material.surface_z,
+    }
+    Overcut {
+        "Overcut",
+        "How far to cut past the bottom of the material, so parts come out cleanly (mm)",
+        overcut,
+        settings.conversion.material.overcut,
+    }
+    EngraveDepth {
+        "Engrave Depth",
+        "Depth to engrave into the material instead of cutting through it, 0 to cut through (mm)",
+        engrave_depth,
+        settings.conversion.engrave_depth,
+    }
+    KerfWidth {
+        "Kerf Width",
+        "Width of the cut, like the kerf of a laser or the diameter of a router bit, to make up for by moving closed paths over by half of it, 0 to disable or to use the width of the tool (mm)",
+        kerf_width,
+        settings.conversion.kerf_width,
+    }
+    FinishAllowance {
+        "Finish Allowance",
+        "Material to leave on the side of closed paths that is cut on, for a finishing pass after the others to take off at full depth along the exact contour, for cleaner edges, 0 to disable (mm)",
+        finish_allowance,
+        settings.conversion.finish_allowance,
+    }
+    FinishFeedrate {
+        "Finish Feedrate",
+        "Feedrate of the finishing pass, 0 for that of the last pass (mm/min)",
+        finish_feedrate,
+        settings.conversion.finish_feedrate,
+    }
+    FinishPower {
+        "Finish Power",
+        "Tool power (i.e. spindle speed) of the finishing pass, 0 for that of the last pass",
+        finish_power,
+        settings.conversion.finish_power,
+    }
+    LeadIn {
+        "Lead In",
+        "Length of a move onto the start of each closed path, tangent to it, so the tool is turned on beside the part instead of leaving a mark on it, 0 to disable (mm)",
+        lead_in,
+        settings.conversion.lead_in,
+    }
+    LeadOut {
+        "Lead Out",
+        "Length of a move off of the end of each closed path, 0 to disable (mm)",
+        lead_out,
+        settings.conversion.lead_out,
+    }
+    PierceDwell {
+        "Pierce Dwell",
+        "Wait with the tool on before the first cutting move of each path, so it pierces the material before moving, 0 to disable (s)",
+        pierce_dwell,
+        settings.conversion.pierce_dwell,
+    }
+    JoinGap {
+        "Join Gap",
+        "Keep the tool on and draw through to the next path if it starts within this distance of the previous one, 0 to disable (mm)",
+        join_gap,
+        settings.conversion.join_gap,
+    }
+    StayDownGap {
+        "Stay Down Gap",
+        "Don't lift the tool when moving to a path that starts within this distance of the previous one, 0 to disable (mm)",
+        stay_down_gap,
+        settings.conversion.stay_down_gap,
+    }
+    StayDownTravel {
+        "Stay Down Travel",
+        "Lift the tool anyway after staying down over this much distance in a row, 0 for no limit (mm)",
+        stay_down_travel,
+        settings.conversion.stay_down_travel,
+    }
+    HourlyRate {
+        "Hourly Rate",
+        "Cost of running the machine for an hour, for estimates",
+        hourly_rate,
+        settings.cost.hourly_rate,
+    }
+    MaterialCost {
+        "Material Cost",
+        "Cost of a square meter of material, charged for the bounding box of the job",
+        material_cost,
+        settings.cost.material_cost,
+    }
+    Power {
+        "Power",
+        "Power drawn by the machine while running, for estimating electricity use (W)",
+        power,
+        settings.cost.power,
+    }
+    Acceleration {
+        "Acceleration",
+        "How quickly the machine speeds up and slows down, for estimates, 0 to ignore (mm/s²)",
+        acceleration,
+        settings.cost.acceleration,
+    }
+}
+
+form_input! {
+    Feedrate positive {
+        "Feedrate",
+        "Machine feedrate (mm/min)",
+        feedrate,
+        settings.conversion.feedrate,
+    }
+    RapidFeedrate positive {
+        "Rapid Feedrate",
+        "Feedrate of moves between paths, for estimates (mm/min)",
+        rapid_feedrate,
+        settings.cost.rapid_feedrate,
+    }
+}
+
+form_input! {
+    PassFeedrate positive list {
+        "Pass Feedrate",
+        "Comma-separated feedrate of each pass (mm/min), instead of the feedrate above. The last value is used for remaining passes",
+        pass_feedrate,
+        settings.conversion.pass_feedrate,
+    }
 }
 
 form_input! {
@@ -121,3 +532,100 @@ form_input! {
         settings.conversion.origin => 1,
     }
 }
+
+form_input! {
+    PassPower list {
+        "Pass Power",
+        "Comma-separated tool power of each pass, available in sequences as {power}. The last value is used for remaining passes",
+        pass_power,
+        settings.conversion.pass_power,
+    }
+}
+
+form_input! {
+    FillSpacing positive in fill {
+        "Fill Spacing",
+        "Distance between fill lines, rings, spiral turns, or dots (mm)",
+        fill_spacing,
+        spacing,
+    }
+}
+
+form_input! {
+    FillAngle in fill {
+        "Fill Angle",
+        "Angle of fill lines counterclockwise from the x-axis (degrees)",
+        fill_angle,
+        angle,
+    }
+}
+
+form_input! {
+    StrokePowerMin in stroke_power {
+        "Least Stroke Power",
+        "Tool power for a white or transparent stroke",
+        stroke_power_min,
+        min,
+    }
+    StrokePowerMax in stroke_power {
+        "Most Stroke Power",
+        "Tool power for a black or opaque stroke, and for shapes without one",
+        stroke_power_max,
+        max,
+    }
+}
+
+form_input! {
+    Passes integer {
+        "Passes",
+        "Number of times to draw all paths (i.e. to cut through thicker material)",
+        passes,
+        settings.conversion.passes,
+    }
+    TabCount integer {
+        "Tab Count",
+        "Number of uncut gaps left along each closed path to keep parts attached, 0 to disable",
+        tab_count,
+        settings.conversion.tab_count,
+    }
+    TabPasses integer {
+        "Tab Passes",
+        "Only leave tabs on this many of the final passes, earlier passes cut the full path",
+        tab_passes,
+        settings.conversion.tab_passes,
+    }
+    MaxLineLength integer {
+        "Max Line Length",
+        "Longest line the controller accepts, long lines are split up to fit, 0 for no limit",
+        max_line_length,
+        settings.machine.max_line_length,
+    }
+}
+
+/// Layer names and stroke colors of paths to cut last, as a comma-separated list
+#[function_component(CutLastInput)]
+pub fn cut_last_input() -> Html {
+    let app_state = use_store_value::<AppState>();
+    let (form_state, form_dispatch) = use_store::<FormState>();
+    let oninput = form_dispatch.reduce_mut_callback_with(|state, event: InputEvent| {
+        let value = event
+            .target_unchecked_into::<web_sys::HtmlInputElement>()
+            .value();
+        state.cut_last = value
+            .split(',')
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(str::to_string)
+            .collect();
+    });
+    html! {
+        <FormGroup>
+            <Input<String, Infallible> label="Cut Last"
+                desc="Comma-separated layer names or stroke colors of paths to always cut after all others, like the outline that frees a part from the sheet"
+                default={app_state.settings.conversion.cut_last.join(", ")}
+                parsed={Ok(form_state.cut_last.join(", "))}
+                oninput={oninput}
+            />
+        </FormGroup>
+    }
+}
@@ -0,0 +1,208 @@
+use std::collections::BTreeMap;
+
+use svg2gcode::{parse_layer_sequence, CutDirection, LayerOverride};
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+use yewdux::functional::use_store;
+
+use crate::{
+    forms::CUT_DIRECTIONS,
+    state::{parse_non_negative, parse_positive, AppState},
+    ui::{FormGroup, Icon, IconName},
+};
+
+/// Read a cell, which is the same as for the rest of the SVG when empty
+fn optional<T, E: ToString>(
+    value: &str,
+    parse: impl Fn(&str) -> Result<T, E>,
+) -> Result<Option<T>, String> {
+    if value.is_empty() {
+        Ok(None)
+    } else {
+        parse(value).map(Some).map_err(|err| err.to_string())
+    }
+}
+
+/// 0 passes would leave the layer out of the program
+fn parse_passes(value: &str) -> Result<usize, String> {
+    match value.parse() {
+        Ok(0) => Err("must be above 0".to_string()),
+        Ok(passes) => Ok(passes),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+fn parse_cut_direction(value: &str) -> Result<CutDirection, String> {
+    CUT_DIRECTIONS
+        .iter()
+        .find(|(_, label)| label.eq_ignore_ascii_case(value))
+        .map(|(direction, _)| *direction)
+        .ok_or_else(|| {
+            let labels = CUT_DIRECTIONS.map(|(_, label)| label);
+            format!("must be one of {}", labels.join(", "))
+        })
+}
+
+fn parse_sequence(value: &str) -> Result<String, String> {
+    parse_layer_sequence(value)
+        .map(|_| value.to_string())
+        .map_err(|err| err.to_string())
+}
+
+/// Settings of each layer that is drawn differently from the rest of the SVG, i.e. an "engrave"
+/// layer and a "cut" layer in one SVG, see [`svg2gcode::ConversionOptions::layer_overrides`]
+///
+/// Changes are saved as they are made, and empty cells are the same as for the rest of the SVG.
+/// Cells that can't be read are marked and left as they were saved.
+#[function_component(LayerOverridesTable)]
+pub fn layer_overrides_table() -> Html {
+    let (app_state, app_dispatch) = use_store::<AppState>();
+    let name = use_state(String::new);
+    // By layer and column
+    let errors = use_state(BTreeMap::<(String, &'static str), String>::new);
+
+    let name_oninput = {
+        let name = name.clone();
+        Callback::from(move |event: InputEvent| {
+            name.set(event.target_unchecked_into::<HtmlInputElement>().value());
+        })
+    };
+
+    let add_onclick = {
+        let name = name.clone();
+        app_dispatch.reduce_mut_callback(move |app| {
+            app.layer_overrides
+                .entry(name.trim().to_string())
+                .or_default();
+        })
+    };
+
+    let cell = |layer: &str,
+                column: &'static str,
+                value: String,
+                set: fn(&mut LayerOverride, &str) -> Result<(), String>| {
+        let key = (layer.to_string(), column);
+        let error = errors.contains_key(&key);
+        let onchange = {
+            let errors = errors.clone();
+            let app_dispatch = app_dispatch.clone();
+            Callback::from(move |event: Event| {
+                let value = event.target_unchecked_into::<HtmlInputElement>().value();
+                let mut result = Ok(());
+                app_dispatch.reduce_mut(|app| {
+                    if let Some(layer) = app.layer_overrides.get_mut(&key.0) {
+                        result = set(layer, value.trim());
+                    }
+                });
+                let mut updated = (*errors).clone();
+                match result {
+                    Ok(()) => updated.remove(&key),
+                    Err(err) => updated.insert(key.clone(), err),
+                };
+                errors.set(updated);
+            })
+        };
+        html! {
+            <td>
+                <input
+                    type="text"
+                    class={classes!("form-input", "input-sm", if error { Some("is-error") } else { None })}
+                    {value}
+                    {onchange}
+                />
+            </td>
+        }
+    };
+    let cut_direction = |value: Option<CutDirection>| {
+        CUT_DIRECTIONS
+            .iter()
+            .find(|(direction, _)| Some(*direction) == value)
+            .map_or(String::new(), |(_, label)| label.to_string())
+    };
+
+    html! {
+        <FormGroup success={(!errors.is_empty()).then_some(false)}>
+            <label class="form-label">{ "Layers" }</label>
+            {
+                if app_state.layer_overrides.is_empty() {
+                    html!()
+                } else {
+                    html! {
+                        <table class="table">
+                            <thead>
+                                <tr>
+                                    <th>{ "Layer" }</th>
+                                    <th>{ "Feedrate" }</th>
+                                    <th>{ "Power" }</th>
+                                    <th>{ "Passes" }</th>
+                                    <th>{ "Tool" }</th>
+                                    <th>{ "Engrave Depth" }</th>
+                                    <th>{ "Cut Direction" }</th>
+                                    <th>{ "Tool On Sequence" }</th>
+                                    <th>{ "Tool Off Sequence" }</th>
+                                    <th></th>
+                                </tr>
+                            </thead>
+                            <tbody>
+                                {
+                                    for app_state.layer_overrides.iter().map(|(layer, settings)| {
+                                        let remove_onclick = {
+                                            let layer = layer.clone();
+                                            let errors = errors.clone();
+                                            app_dispatch.reduce_mut_callback(move |app| {
+                                                app.layer_overrides.remove(&layer);
+                                                let mut updated = (*errors).clone();
+                                                updated.retain(|(name, _), _| *name != layer);
+                                                errors.set(updated);
+                                            })
+                                        };
+                                        html! {
+                                            <tr>
+                                                <td>{ layer }</td>
+                                                { cell(layer, "Feedrate", settings.feedrate.map_or(String::new(), |f| f.to_string()), |layer, value| { layer.feedrate = optional(value, parse_positive)?; Ok(()) }) }
+                                                { cell(layer, "Power", settings.power.map_or(String::new(), |p| p.to_string()), |layer, value| { layer.power = optional(value, parse_non_negative)?; Ok(()) }) }
+                                                { cell(layer, "Passes", settings.passes.map_or(String::new(), |p| p.to_string()), |layer, value| { layer.passes = optional(value, parse_passes)?; Ok(()) }) }
+                                                { cell(layer, "Tool", settings.tool.clone().unwrap_or_default(), |layer, value| { layer.tool = (!value.is_empty()).then(|| value.to_string()); Ok(()) }) }
+                                                { cell(layer, "Engrave Depth", settings.engrave_depth.map_or(String::new(), |d| d.to_string()), |layer, value| { layer.engrave_depth = optional(value, parse_non_negative)?; Ok(()) }) }
+                                                { cell(layer, "Cut Direction", cut_direction(settings.cut_direction), |layer, value| { layer.cut_direction = optional(value, parse_cut_direction)?; Ok(()) }) }
+                                                { cell(layer, "Tool On Sequence", settings.tool_on_sequence.clone().unwrap_or_default(), |layer, value| { layer.tool_on_sequence = optional(value, parse_sequence)?; Ok(()) }) }
+                                                { cell(layer, "Tool Off Sequence", settings.tool_off_sequence.clone().unwrap_or_default(), |layer, value| { layer.tool_off_sequence = optional(value, parse_sequence)?; Ok(()) }) }
+                                                <td>
+                                                    <button class="btn btn-sm" title="Remove" onclick={remove_onclick}>
+                                                        <Icon name={IconName::Delete} />
+                                                    </button>
+                                                </td>
+                                            </tr>
+                                        }
+                                    })
+                                }
+                            </tbody>
+                        </table>
+                    }
+                }
+            }
+            <div class="input-group">
+                <input
+                    type="text"
+                    class="form-input"
+                    placeholder="Inkscape layer label or outermost group id (i.e. cut)"
+                    value={(*name).clone()}
+                    oninput={name_oninput}
+                />
+                <button
+                    class="btn input-group-btn"
+                    disabled={name.trim().is_empty()}
+                    onclick={add_onclick}
+                >
+                    { "Add layer" }
+                </button>
+            </div>
+            {
+                for errors.iter().map(|((layer, column), err)| html! {
+                    <pre class="form-input-hint">{ format!("{layer} {column}: {err}") }</pre>
+                })
+            }
+            <p class="form-input-hint">{ "Draw layers with their own feedrate, power, passes, tool (by name, from the tool library), engrave depth (0 to cut through), cut direction (as drawn, climb, or conventional), and tool sequences, each in all of its passes before the next. Changes are saved right away, and empty cells are the same as for the rest of the SVG." }</p>
+        </FormGroup>
+    }
+}
@@ -1,3 +1,4 @@
+use g_code::emit::{FormatOptions, Token};
 use gloo_file::{
     callbacks::{read_as_bytes, FileReader},
     futures::read_as_text,
@@ -5,30 +6,168 @@ use gloo_file::{
 use js_sys::TypeError;
 use roxmltree::{Document, ParsingOptions};
 use std::{convert::TryInto, path::Path};
-use svg2gcode::{Settings, Version};
+use svg2gcode::{
+    lint_sequences, write_gcode, CalibrationPattern, CalibrationTest, CutDirection, Dialect, Fill,
+    FillPattern, FocusTest, HalftonePattern, KerfSide, LeadShape, MaterialTest, OriginMode,
+    PaintFilter, PathOrdering, Plunge, PowerSource, Preset, Settings, StrokeOutline, StrokePower,
+    Version,
+};
 use wasm_bindgen::JsCast;
 use wasm_bindgen_futures::JsFuture;
-use web_sys::{window, Event, FileList, HtmlElement, HtmlInputElement, Response};
+use web_sys::{
+    window, Event, FileList, HtmlElement, HtmlInputElement, HtmlSelectElement, Response,
+};
 use yew::prelude::*;
-use yewdux::{functional::use_store, use_dispatch};
+use yewdux::{
+    functional::{use_store, use_store_value},
+    use_dispatch,
+};
 
 use crate::{
-    state::{AppState, FormState, Svg},
+    state::{AppState, FormState, SavedPreset, Svg},
     ui::{
         Button, ButtonStyle, Checkbox, FileUpload, FormGroup, HyperlinkButton, Icon, IconName,
         Input, InputType, Modal,
     },
 };
 
+/// Generates barcodes to add as SVGs
+#[cfg(feature = "barcode")]
+mod barcode;
 mod editors;
 mod inputs;
+mod layers;
+mod tools;
 
+#[cfg(feature = "barcode")]
+use barcode::*;
 use editors::*;
 use inputs::*;
+use layers::*;
+use tools::*;
+
+/// Built-in machines to set up, in the order they are listed before the saved presets
+const PRESETS: [(Preset, &str); 5] = [
+    (Preset::Shapeoko, "Shapeoko 3/4 (router)"),
+    (Preset::XCarve, "X-Carve 1000 mm (router)"),
+    (Preset::OrturLaser, "Ortur Laser Master 2/3"),
+    (Preset::AxiDraw, "AxiDraw V3 (g-code firmware)"),
+    (Preset::GrblPlotter, "Grbl pen plotter (servo)"),
+];
+
+/// Choices for [`svg2gcode::MachineConfig::dialect`], in the order they are listed
+const DIALECTS: [(Option<Dialect>, &str); 5] = [
+    (None, "Custom"),
+    (Some(Dialect::Grbl), "Grbl (laser mode, $32=1)"),
+    (Some(Dialect::Marlin), "Marlin (laser on fan output)"),
+    (Some(Dialect::LinuxCnc), "LinuxCNC"),
+    (Some(Dialect::Klipper), "Klipper (laser on fan output)"),
+];
+
+/// Choices for [`svg2gcode::ConversionConfig::paint_filter`], in the order they are listed
+const PAINT_FILTERS: [(PaintFilter, &str); 4] = [
+    (PaintFilter::All, "All shapes"),
+    (PaintFilter::Stroked, "Stroked shapes"),
+    (PaintFilter::Filled, "Filled shapes"),
+    (PaintFilter::StrokedOrFilled, "Stroked or filled shapes"),
+];
+
+/// Choices for [`svg2gcode::ConversionOptions::path_ordering`], in the order they are listed
+const PATH_ORDERINGS: [(PathOrdering, &str); 3] = [
+    (PathOrdering::Document, "As in the SVG"),
+    (PathOrdering::NearestNeighbor, "Nearest first"),
+    (PathOrdering::Optimized, "Optimized"),
+];
+
+/// Choices for [`svg2gcode::ConversionConfig::stroke_outline`], in the order they are listed
+const STROKE_OUTLINES: [(StrokeOutline, &str); 3] = [
+    (StrokeOutline::Centerline, "Along the middle"),
+    (StrokeOutline::Outline, "Around both sides"),
+    (StrokeOutline::Filled, "Around and filled between"),
+];
+
+/// Choices for [`svg2gcode::ConversionConfig::origin_mode`], in the order they are listed
+const ORIGIN_MODES: [(OriginMode, &str); 4] = [
+    (OriginMode::BottomLeft, "Bottom left corner"),
+    (OriginMode::TopLeft, "Top left corner"),
+    (OriginMode::Center, "Center"),
+    (
+        OriginMode::CurrentPosition,
+        "Current position of the machine",
+    ),
+];
+
+/// Choices for [`svg2gcode::ConversionConfig::cut_direction`], in the order they are listed
+const CUT_DIRECTIONS: [(CutDirection, &str); 3] = [
+    (CutDirection::AsDrawn, "As drawn"),
+    (CutDirection::Climb, "Climb"),
+    (CutDirection::Conventional, "Conventional"),
+];
+
+/// Choices for [`svg2gcode::ConversionConfig::kerf_side`], in the order they are listed
+const KERF_SIDES: [(KerfSide, &str); 2] = [
+    (KerfSide::Outside, "Outside of shapes, inside of holes"),
+    (KerfSide::Inside, "Inside of shapes, outside of holes"),
+];
+
+/// Choices for [`CalibrationTest::pattern`], in the order they are listed
+const CALIBRATION_PATTERNS: [(CalibrationPattern, &str); 4] = [
+    (
+        CalibrationPattern::CircleDiamondSquare,
+        "Circle, diamond, and square",
+    ),
+    (CalibrationPattern::AxisScale, "Squares for axis scale"),
+    (CalibrationPattern::Backlash, "Backlash ticks"),
+    (CalibrationPattern::KerfComb, "Kerf comb"),
+];
+
+/// Choices for [`svg2gcode::ConversionConfig::lead_shape`], in the order they are listed
+const LEAD_SHAPES: [(LeadShape, &str); 2] = [
+    (LeadShape::Line, "Line"),
+    (LeadShape::Arc, "Arc, with the length as its radius"),
+];
+
+/// Choices for [`svg2gcode::MachineConfig::plunge`], in the order they are listed
+const PLUNGES: [(Plunge, &str); 3] = [
+    (Plunge::Straight, "Straight down"),
+    (Plunge::Ramp, "Ramp along the path"),
+    (Plunge::Helix, "Helix, spiraling down"),
+];
+
+/// Choices for [`svg2gcode::ConversionConfig::stroke_power`], in the order they are listed
+const POWER_SOURCES: [(Option<PowerSource>, &str); 3] = [
+    (None, "Same power for every path"),
+    (
+        Some(PowerSource::Luminance),
+        "More power for darker strokes",
+    ),
+    (
+        Some(PowerSource::Opacity),
+        "More power for more opaque strokes",
+    ),
+];
+
+/// Choices for [`svg2gcode::ConversionConfig::fill`], in the order they are listed
+const FILL_PATTERNS: [(Option<FillPattern>, &str); 8] = [
+    (None, "No fill, outlines only"),
+    (Some(FillPattern::Lines), "Lines"),
+    (Some(FillPattern::Crosshatch), "Crosshatch"),
+    (Some(FillPattern::Concentric), "Concentric"),
+    (Some(FillPattern::Spiral), "Spiral"),
+    (Some(FillPattern::Stipple), "Stipple"),
+    (
+        Some(FillPattern::Halftone(HalftonePattern::Dots)),
+        "Halftone dots",
+    ),
+    (
+        Some(FillPattern::Halftone(HalftonePattern::Lines)),
+        "Halftone lines",
+    ),
+];
 
 #[function_component(SettingsForm)]
 pub fn settings_form() -> Html {
-    let app_dispatch = use_dispatch::<AppState>();
+    let (app_state, app_dispatch) = use_store::<AppState>();
     let (form_state, form_dispatch) = use_store::<FormState>();
 
     let disabled = form_state.tolerance.is_err()
@@ -37,7 +176,7 @@ pub fn settings_form() -> Html {
         || form_state
             .origin
             .iter()
-            .all(|opt| opt.as_ref().map_or(false, |r| r.is_err()))
+            .all(|opt| opt.as_ref().is_some_and(|r| r.is_err()))
         || form_state
             .tool_on_sequence
             .as_ref()
@@ -53,11 +192,68 @@ pub fn settings_form() -> Html {
             .as_ref()
             .map(Result::is_err)
             .unwrap_or(false)
-        || form_state
-            .end_sequence
-            .as_ref()
-            .map(Result::is_err)
-            .unwrap_or(false);
+        || form_state.passes.is_err()
+        || form_state.pass_power.is_err()
+        || form_state.pass_feedrate.is_err()
+        || !form_state.tool_errors.is_empty()
+        || form_state.material_thickness.is_err()
+        || form_state.travel_z.is_err()
+        || form_state.cut_z.is_err()
+        || form_state.z_feedrate.is_err()
+        || form_state.ramp_angle.is_err()
+        || form_state.helix_radius.is_err()
+        || form_state.surface_z.is_err()
+        || form_state.overcut.is_err()
+        || form_state.engrave_depth.is_err()
+        || form_state.tab_count.is_err()
+        || form_state.tab_width.is_err()
+        || form_state.tab_passes.is_err()
+        || form_state.heal_tolerance.is_err()
+        || form_state.close_tolerance.is_err()
+        || form_state.overlap_tolerance.is_err()
+        || form_state.kerf_width.is_err()
+        || form_state.finish_allowance.is_err()
+        || form_state.finish_feedrate.is_err()
+        || form_state.finish_power.is_err()
+        || form_state.lead_in.is_err()
+        || form_state.lead_out.is_err()
+        || form_state.pierce_dwell.is_err()
+        || form_state.join_gap.is_err()
+        || form_state.fill_spacing.is_err()
+        || form_state.fill_angle.is_err()
+        || form_state.stroke_power_min.is_err()
+        || form_state.stroke_power_max.is_err()
+        || form_state.stay_down_gap.is_err()
+        || form_state.stay_down_travel.is_err()
+        || form_state.step_size.iter().any(Result::is_err)
+        || form_state.max_line_length.is_err()
+        || form_state.rapid_feedrate.is_err()
+        || form_state.hourly_rate.is_err()
+        || form_state.material_cost.is_err()
+        || form_state.power.is_err()
+        || form_state.acceleration.is_err()
+        || [
+            &form_state.end_sequence,
+            &form_state.pre_home_sequence,
+            &form_state.home_sequence,
+            &form_state.post_home_sequence,
+            &form_state.pass_begin_sequence,
+            &form_state.pass_end_sequence,
+            &form_state.path_begin_sequence,
+            &form_state.path_end_sequence,
+        ]
+        .iter()
+        .any(|sequence| sequence.as_ref().is_some_and(Result::is_err))
+        || form_state.script.as_ref().is_some_and(Result::is_err);
+
+    #[cfg(feature = "script")]
+    let script_editor = html! {
+        <div class="column col-12">
+            <ScriptInput/>
+        </div>
+    };
+    #[cfg(not(feature = "script"))]
+    let script_editor = html! {};
 
     let close_ref = use_node_ref();
 
@@ -69,6 +265,89 @@ pub fn settings_form() -> Html {
                 event.target_unchecked_into::<HtmlInputElement>().checked();
         });
 
+    let on_arc_center_offsets_change =
+        form_dispatch.reduce_mut_callback_with(|form, event: Event| {
+            form.arc_center_offsets = event.target_unchecked_into::<HtmlInputElement>().checked();
+        });
+
+    // Saved right away like an import, so the sequence editors show what the dialect set up
+    let on_dialect_change = {
+        let form_dispatch = form_dispatch.clone();
+        app_dispatch.reduce_mut_callback_with(move |app, event: Event| {
+            let index = event
+                .target_unchecked_into::<HtmlSelectElement>()
+                .selected_index();
+            let Some((dialect, _)) = DIALECTS.get(index as usize) else {
+                return;
+            };
+            let machine = &mut app.settings.machine;
+            match dialect {
+                Some(dialect) => dialect.apply(machine),
+                None => machine.dialect = None,
+            }
+            let hydrated: FormState = (&app.settings).into();
+            form_dispatch.reduce_mut(|form| {
+                form.dialect = hydrated.dialect;
+                form.circular_interpolation = hydrated.circular_interpolation;
+                form.arc_center_offsets = hydrated.arc_center_offsets;
+                form.tool_on_sequence = hydrated.tool_on_sequence;
+                form.tool_off_sequence = hydrated.tool_off_sequence;
+                form.end_sequence = hydrated.end_sequence;
+            });
+        })
+    };
+
+    // Saved right away like an import, so the form shows what the preset set up
+    let on_preset_change = {
+        let form_dispatch = form_dispatch.clone();
+        app_dispatch.reduce_mut_callback_with(move |app, event: Event| {
+            let select = event.target_unchecked_into::<HtmlSelectElement>();
+            // The first option only prompts to choose, and is shown again so the same preset can
+            // be chosen twice
+            let index = select.selected_index() as usize;
+            select.set_selected_index(0);
+            if let Some((preset, _)) = index.checked_sub(1).and_then(|i| PRESETS.get(i)) {
+                preset.apply(&mut app.settings);
+            } else if let Some(saved) = index
+                .checked_sub(1 + PRESETS.len())
+                .and_then(|i| app.presets.get(i))
+            {
+                app.settings = saved.settings.clone();
+            } else {
+                return;
+            }
+            let hydrated = FormState::from(&app.settings);
+            form_dispatch.reduce_mut(|form| *form = hydrated);
+        })
+    };
+
+    let preset_name = use_state(String::new);
+    let preset_name_oninput = {
+        let preset_name = preset_name.clone();
+        Callback::from(move |event: InputEvent| {
+            preset_name.set(event.target_unchecked_into::<HtmlInputElement>().value());
+        })
+    };
+
+    let save_preset_onclick = {
+        let preset_name = preset_name.clone();
+        let form_state = form_state.clone();
+        app_dispatch.reduce_mut_callback(move |app| {
+            let Ok(settings) = form_state.as_ref().try_into() else {
+                return;
+            };
+            let preset = SavedPreset {
+                name: preset_name.trim().to_string(),
+                settings,
+            };
+            // Saving under the name of an existing preset replaces it
+            match app.presets.iter_mut().find(|p| p.name == preset.name) {
+                Some(existing) => *existing = preset,
+                None => app.presets.push(preset),
+            }
+        })
+    };
+
     let on_checksums_change = form_dispatch.reduce_mut_callback_with(|form, event: Event| {
         form.checksums = event.target_unchecked_into::<HtmlInputElement>().checked();
     });
@@ -83,6 +362,147 @@ pub fn settings_form() -> Html {
                 event.target_unchecked_into::<HtmlInputElement>().checked();
         });
 
+    let on_z_moves_change = form_dispatch.reduce_mut_callback_with(|form, event: Event| {
+        form.z_moves = event.target_unchecked_into::<HtmlInputElement>().checked();
+    });
+
+    let on_plunge_change = form_dispatch.reduce_mut_callback_with(|form, event: Event| {
+        let index = event
+            .target_unchecked_into::<HtmlSelectElement>()
+            .selected_index();
+        if let Some((plunge, _)) = PLUNGES.get(index as usize) {
+            form.plunge = *plunge;
+        }
+    });
+
+    let on_short_file_names_change =
+        form_dispatch.reduce_mut_callback_with(|form, event: Event| {
+            form.short_file_names = event.target_unchecked_into::<HtmlInputElement>().checked();
+        });
+
+    let on_number_paths_change = form_dispatch.reduce_mut_callback_with(|form, event: Event| {
+        form.number_paths = event.target_unchecked_into::<HtmlInputElement>().checked();
+    });
+
+    let on_estimate_header_change = form_dispatch.reduce_mut_callback_with(|form, event: Event| {
+        form.estimate_header = event.target_unchecked_into::<HtmlInputElement>().checked();
+    });
+
+    let on_sender_markers_change = form_dispatch.reduce_mut_callback_with(|form, event: Event| {
+        form.sender_markers = event.target_unchecked_into::<HtmlInputElement>().checked();
+    });
+
+    let on_origin_mode_change = form_dispatch.reduce_mut_callback_with(|form, event: Event| {
+        let index = event
+            .target_unchecked_into::<HtmlSelectElement>()
+            .selected_index();
+        if let Some((mode, _)) = ORIGIN_MODES.get(index as usize) {
+            form.origin_mode = *mode;
+        }
+    });
+
+    let on_invert_y_change = form_dispatch.reduce_mut_callback_with(|form, event: Event| {
+        form.invert_y = event.target_unchecked_into::<HtmlInputElement>().checked();
+    });
+
+    let on_tool_change = form_dispatch.reduce_mut_callback_with(|form, event: Event| {
+        let index = event
+            .target_unchecked_into::<HtmlSelectElement>()
+            .selected_index();
+        // The first option is cutting without a tool
+        form.tool = usize::try_from(index - 1)
+            .ok()
+            .and_then(|index| form.tools.get(index))
+            .map(|tool| tool.name.clone());
+    });
+
+    let on_kerf_side_change = form_dispatch.reduce_mut_callback_with(|form, event: Event| {
+        let index = event
+            .target_unchecked_into::<HtmlSelectElement>()
+            .selected_index();
+        if let Some((side, _)) = KERF_SIDES.get(index as usize) {
+            form.kerf_side = *side;
+        }
+    });
+
+    let on_cut_direction_change = form_dispatch.reduce_mut_callback_with(|form, event: Event| {
+        let index = event
+            .target_unchecked_into::<HtmlSelectElement>()
+            .selected_index();
+        if let Some((direction, _)) = CUT_DIRECTIONS.get(index as usize) {
+            form.cut_direction = *direction;
+        }
+    });
+
+    let on_lead_shape_change = form_dispatch.reduce_mut_callback_with(|form, event: Event| {
+        let index = event
+            .target_unchecked_into::<HtmlSelectElement>()
+            .selected_index();
+        if let Some((shape, _)) = LEAD_SHAPES.get(index as usize) {
+            form.lead_shape = *shape;
+        }
+    });
+
+    let on_stroke_outline_change = form_dispatch.reduce_mut_callback_with(|form, event: Event| {
+        let index = event
+            .target_unchecked_into::<HtmlSelectElement>()
+            .selected_index();
+        if let Some((outline, _)) = STROKE_OUTLINES.get(index as usize) {
+            form.stroke_outline = *outline;
+        }
+    });
+
+    let on_paint_filter_change = form_dispatch.reduce_mut_callback_with(|form, event: Event| {
+        let index = event
+            .target_unchecked_into::<HtmlSelectElement>()
+            .selected_index();
+        if let Some((filter, _)) = PAINT_FILTERS.get(index as usize) {
+            form.paint_filter = *filter;
+        }
+    });
+
+    let on_fill_pattern_change = form_dispatch.reduce_mut_callback_with(|form, event: Event| {
+        let index = event
+            .target_unchecked_into::<HtmlSelectElement>()
+            .selected_index();
+        if let Some((pattern, _)) = FILL_PATTERNS.get(index as usize) {
+            form.fill = pattern.map(|pattern| Fill {
+                pattern,
+                ..form.fill.clone().unwrap_or_default()
+            });
+        }
+    });
+
+    let on_power_source_change = form_dispatch.reduce_mut_callback_with(|form, event: Event| {
+        let index = event
+            .target_unchecked_into::<HtmlSelectElement>()
+            .selected_index();
+        if let Some((source, _)) = POWER_SOURCES.get(index as usize) {
+            form.stroke_power = source.map(|source| StrokePower {
+                source,
+                ..form.stroke_power.clone().unwrap_or_default()
+            });
+        }
+    });
+
+    // Not one of the settings, so it is saved right away like the layer overrides
+    let on_path_ordering_change = app_dispatch.reduce_mut_callback_with(|app, event: Event| {
+        let index = event
+            .target_unchecked_into::<HtmlSelectElement>()
+            .selected_index();
+        if let Some((ordering, _)) = PATH_ORDERINGS.get(index as usize) {
+            app.path_ordering = *ordering;
+        }
+    });
+
+    let on_registration_marks_change =
+        form_dispatch.reduce_mut_callback_with(|form, event: Event| {
+            form.registration_marks = event
+                .target_unchecked_into::<HtmlInputElement>()
+                .checked()
+                .then(Default::default);
+        });
+
     let save_onclick = {
         let close_ref = close_ref.clone();
         let form_state = form_state.clone();
@@ -98,6 +518,11 @@ pub fn settings_form() -> Html {
         })
     };
 
+    // Sequences that parse can still have mistakes worth pointing out before generating
+    let sequence_warnings = TryInto::<Settings>::try_into(&*form_state)
+        .map(|settings| lint_sequences(&settings.machine))
+        .unwrap_or_default();
+
     html! {
         <Modal
             id="settings"
@@ -119,6 +544,56 @@ pub fn settings_form() -> Html {
             }
             body={html!(
                 <div class="columns">
+                    <div class="column col-12">
+                        <FormGroup>
+                            <label class="form-label" for="preset">{ "Machine" }</label>
+                            <select id="preset" class="form-select" onchange={on_preset_change}>
+                                <option selected=true>{ "Choose a machine to set up" }</option>
+                                {
+                                    for PRESETS.iter().map(|(_, label)| html! {
+                                        <option>{ *label }</option>
+                                    })
+                                }
+                                {
+                                    for app_state.presets.iter().map(|preset| html! {
+                                        <option>{ &preset.name }</option>
+                                    })
+                                }
+                            </select>
+                            <p class="form-input-hint">{ "Set the bed size, tool sequences, firmware, and feedrates for a machine, replacing them. Saved presets replace all of the settings." }</p>
+                        </FormGroup>
+                        <FormGroup>
+                            {
+                                for app_state.presets.iter().enumerate().map(|(p, preset)| {
+                                    let remove_onclick = app_dispatch.reduce_mut_callback(move |app| {
+                                        app.presets.remove(p);
+                                    });
+                                    html! {
+                                        <span class="chip">
+                                            { &preset.name }
+                                            <a class="btn btn-clear" aria-label="Remove" role="button" onclick={remove_onclick}></a>
+                                        </span>
+                                    }
+                                })
+                            }
+                            <div class="input-group">
+                                <input
+                                    type="text"
+                                    class="form-input"
+                                    placeholder="Name (i.e. shop laser)"
+                                    value={(*preset_name).clone()}
+                                    oninput={preset_name_oninput}
+                                />
+                                <button
+                                    class="btn input-group-btn"
+                                    disabled={disabled || preset_name.trim().is_empty()}
+                                    onclick={save_preset_onclick}
+                                >
+                                    { "Save as preset" }
+                                </button>
+                            </div>
+                        </FormGroup>
+                    </div>
                     <div class="column col-6 col-sm-12">
                         <ToleranceInput/>
                     </div>
@@ -131,12 +606,126 @@ pub fn settings_form() -> Html {
                     <div class="column col-6 col-sm-12">
                         <OriginYInput/>
                     </div>
+                    <div class="column col-12">
+                        <FormGroup>
+                            <label class="form-label" for="origin_mode">{ "Place at origin" }</label>
+                            <select id="origin_mode" class="form-select" onchange={on_origin_mode_change}>
+                                {
+                                    for ORIGIN_MODES.iter().map(|(mode, label)| html! {
+                                        <option selected={form_state.origin_mode == *mode}>{ *label }</option>
+                                    })
+                                }
+                            </select>
+                            <p class="form-input-hint">{ "Which point of the artwork goes at the origin. The current position starts the program with G92 X0 Y0, so it runs from wherever the machine is, and clears it with G92.1 at the end." }</p>
+                        </FormGroup>
+                        <FormGroup>
+                            <Checkbox
+                                label="Invert Y axis"
+                                desc="For machines where Y points toward the front of the bed, like with the origin at the back left. Place the top left corner at the origin to keep the artwork on the bed."
+                                checked={form_state.invert_y}
+                                onchange={on_invert_y_change}
+                            />
+                        </FormGroup>
+                    </div>
                     <div class="column col-6 col-sm-12">
                         <BedWidthInput/>
                     </div>
                     <div class="column col-6 col-sm-12">
                         <BedHeightInput/>
                     </div>
+                    <div class="column col-6 col-sm-12">
+                        <BacklashXInput/>
+                    </div>
+                    <div class="column col-6 col-sm-12">
+                        <BacklashYInput/>
+                    </div>
+                    <div class="column col-6 col-sm-12">
+                        <StepSizeXInput/>
+                    </div>
+                    <div class="column col-6 col-sm-12">
+                        <StepSizeYInput/>
+                    </div>
+                    <div class="column col-6 col-sm-12">
+                        <MaxLineLengthInput/>
+                    </div>
+                    <div class="column col-12">
+                        <FormGroup>
+                            <Checkbox
+                                label="Move the Z axis"
+                                desc="Lift the tool before every rapid move and plunge it to cut, instead of leaving it to the tool on and off sequences, i.e. for a router or drag knife"
+                                checked={form_state.z_moves}
+                                onchange={on_z_moves_change}
+                            />
+                        </FormGroup>
+                    </div>
+                    {
+                        if form_state.z_moves {
+                            html! {
+                                <>
+                                    <div class="column col-4 col-sm-12">
+                                        <TravelZInput/>
+                                    </div>
+                                    <div class="column col-4 col-sm-12">
+                                        <CutZInput/>
+                                    </div>
+                                    <div class="column col-4 col-sm-12">
+                                        <ZFeedrateInput/>
+                                    </div>
+                                    <div class="column col-4 col-sm-12">
+                                        <FormGroup>
+                                            <label class="form-label" for="plunge">{ "Plunge" }</label>
+                                            <select id="plunge" class="form-select" onchange={on_plunge_change}>
+                                                {
+                                                    for PLUNGES.iter().map(|(plunge, label)| html! {
+                                                        <option selected={form_state.plunge == *plunge}>{ *label }</option>
+                                                    })
+                                                }
+                                            </select>
+                                        </FormGroup>
+                                    </div>
+                                    {
+                                        if form_state.plunge == Plunge::Straight {
+                                            html!()
+                                        } else {
+                                            html! {
+                                                <>
+                                                    <div class="column col-4 col-sm-12">
+                                                        <RampAngleInput/>
+                                                    </div>
+                                                    {
+                                                        if form_state.plunge == Plunge::Helix {
+                                                            html! {
+                                                                <div class="column col-4 col-sm-12">
+                                                                    <HelixRadiusInput/>
+                                                                </div>
+                                                            }
+                                                        } else {
+                                                            html!()
+                                                        }
+                                                    }
+                                                </>
+                                            }
+                                        }
+                                    }
+                                </>
+                            }
+                        } else {
+                            html!()
+                        }
+                    }
+                    <div class="column col-12">
+                        <FormGroup>
+                            <label class="form-label" for="dialect">{ "Firmware" }</label>
+                            <select id="dialect" class="form-select" onchange={on_dialect_change}>
+                                {
+                                    for DIALECTS.iter().map(|(dialect, label)| html! {
+                                        <option selected={form_state.dialect == *dialect}>{ *label }</option>
+                                    })
+                                }
+                            </select>
+                            <p class="form-input-hint">{ "Set up the tool sequences and arcs for the firmware your machine runs, replacing them" }</p>
+                        </FormGroup>
+                    </div>
                     <div class="column col-12">
                         <FormGroup>
                             <Checkbox
@@ -147,6 +736,16 @@ pub fn settings_form() -> Html {
                             />
                         </FormGroup>
                     </div>
+                    <div class="column col-12">
+                        <FormGroup>
+                            <Checkbox
+                                label="Arc centers as offsets (I/J)"
+                                desc="With circular interpolation, write the center of arcs instead of their radius (R), which Grbl recommends"
+                                checked={form_state.arc_center_offsets}
+                                onchange={on_arc_center_offsets_change}
+                            />
+                        </FormGroup>
+                    </div>
                     <div class="column col-12">
                         <DpiInput/>
                     </div>
@@ -162,6 +761,308 @@ pub fn settings_form() -> Html {
                     <div class="column col-12">
                         <EndSequenceInput/>
                     </div>
+                    <div class="column col-12">
+                        <PassesInput/>
+                    </div>
+                    <div class="column col-6 col-sm-12">
+                        <PassPowerInput/>
+                    </div>
+                    <div class="column col-6 col-sm-12">
+                        <PassFeedrateInput/>
+                    </div>
+                    <div class="column col-12">
+                        <LayerOverridesTable/>
+                    </div>
+                    <div class="column col-12">
+                        <ToolLibraryTable/>
+                    </div>
+                    <div class="column col-6 col-sm-12">
+                        <FormGroup>
+                            <label class="form-label" for="tool">{ "Tool" }</label>
+                            <select id="tool" class="form-select" onchange={on_tool_change}>
+                                <option selected={form_state.tool.is_none()}>{ "None" }</option>
+                                {
+                                    for form_state.tools.iter().map(|tool| html! {
+                                        <option selected={form_state.tool.as_ref() == Some(&tool.name)}>{ &tool.name }</option>
+                                    })
+                                }
+                            </select>
+                        </FormGroup>
+                    </div>
+                    <div class="column col-6 col-sm-12">
+                        <MaterialThicknessInput/>
+                    </div>
+                    <div class="column col-4 col-sm-12">
+                        <SurfaceZInput/>
+                    </div>
+                    <div class="column col-4 col-sm-12">
+                        <OvercutInput/>
+                    </div>
+                    <div class="column col-4 col-sm-12">
+                        <EngraveDepthInput/>
+                    </div>
+                    <div class="column col-4 col-sm-12">
+                        <TabCountInput/>
+                    </div>
+                    <div class="column col-4 col-sm-12">
+                        <TabWidthInput/>
+                    </div>
+                    <div class="column col-4 col-sm-12">
+                        <TabPassesInput/>
+                    </div>
+                    <div class="column col-6 col-sm-12">
+                        <PerforationCutInput/>
+                    </div>
+                    <div class="column col-6 col-sm-12">
+                        <PerforationGapInput/>
+                    </div>
+                    <div class="column col-12">
+                        <HealToleranceInput/>
+                    </div>
+                    <div class="column col-12">
+                        <CloseToleranceInput/>
+                    </div>
+                    <div class="column col-12">
+                        <OverlapToleranceInput/>
+                    </div>
+                    <div class="column col-6 col-sm-12">
+                        <KerfWidthInput/>
+                    </div>
+                    <div class="column col-6 col-sm-12">
+                        <FormGroup>
+                            <label class="form-label" for="kerf_side">{ "Kerf Side" }</label>
+                            <select id="kerf_side" class="form-select" onchange={on_kerf_side_change}>
+                                {
+                                    for KERF_SIDES.iter().map(|(side, label)| html! {
+                                        <option selected={form_state.kerf_side == *side}>{ *label }</option>
+                                    })
+                                }
+                            </select>
+                        </FormGroup>
+                    </div>
+                    <div class="column col-6 col-sm-12">
+                        <FormGroup>
+                            <label class="form-label" for="cut_direction">{ "Cut Direction" }</label>
+                            <select id="cut_direction" class="form-select" onchange={on_cut_direction_change}>
+                                {
+                                    for CUT_DIRECTIONS.iter().map(|(direction, label)| html! {
+                                        <option selected={form_state.cut_direction == *direction}>{ *label }</option>
+                                    })
+                                }
+                            </select>
+                            <p class="form-input-hint">{ "Which way to go around shapes when milling with a spindle turning clockwise, so the part is on the same side of the tool in every cut" }</p>
+                        </FormGroup>
+                    </div>
+                    <div class="column col-4 col-sm-12">
+                        <FinishAllowanceInput/>
+                    </div>
+                    <div class="column col-4 col-sm-12">
+                        <FinishFeedrateInput/>
+                    </div>
+                    <div class="column col-4 col-sm-12">
+                        <FinishPowerInput/>
+                    </div>
+                    <div class="column col-6 col-sm-12">
+                        <LeadInInput/>
+                    </div>
+                    <div class="column col-6 col-sm-12">
+                        <LeadOutInput/>
+                    </div>
+                    <div class="column col-6 col-sm-12">
+                        <FormGroup>
+                            <label class="form-label" for="lead_shape">{ "Lead Shape" }</label>
+                            <select id="lead_shape" class="form-select" onchange={on_lead_shape_change}>
+                                {
+                                    for LEAD_SHAPES.iter().map(|(shape, label)| html! {
+                                        <option selected={form_state.lead_shape == *shape}>{ *label }</option>
+                                    })
+                                }
+                            </select>
+                        </FormGroup>
+                    </div>
+                    <div class="column col-6 col-sm-12">
+                        <PierceDwellInput/>
+                    </div>
+                    <div class="column col-12">
+                        <JoinGapInput/>
+                    </div>
+                    <div class="column col-6 col-sm-12">
+                        <StayDownGapInput/>
+                    </div>
+                    <div class="column col-6 col-sm-12">
+                        <StayDownTravelInput/>
+                    </div>
+                    <div class="column col-12">
+                        <FormGroup>
+                            <Checkbox
+                                label="Number paths"
+                                desc="Add a comment before each path with its position in the cut order"
+                                checked={form_state.number_paths}
+                                onchange={on_number_paths_change}
+                            />
+                        </FormGroup>
+                        <FormGroup>
+                            <Checkbox
+                                label="Sender markers"
+                                desc="Add ;LAYER: and ;PROGRESS: comments so senders like LaserGRBL or OctoPrint can show progress and resume at a pass"
+                                checked={form_state.sender_markers}
+                                onchange={on_sender_markers_change}
+                            />
+                        </FormGroup>
+                        <FormGroup>
+                            <label class="form-label" for="paint_filter">{ "Shapes to draw" }</label>
+                            <select id="paint_filter" class="form-select" onchange={on_paint_filter_change}>
+                                {
+                                    for PAINT_FILTERS.iter().map(|(filter, label)| html! {
+                                        <option selected={form_state.paint_filter == *filter}>{ *label }</option>
+                                    })
+                                }
+                            </select>
+                        </FormGroup>
+                        <FormGroup>
+                            <label class="form-label" for="stroke_outline">{ "Cut strokes" }</label>
+                            <select id="stroke_outline" class="form-select" onchange={on_stroke_outline_change}>
+                                {
+                                    for STROKE_OUTLINES.iter().map(|(outline, label)| html! {
+                                        <option selected={form_state.stroke_outline == *outline}>{ *label }</option>
+                                    })
+                                }
+                            </select>
+                            <p class="form-input-hint">{ "Strokes wider than the cut can be cut around at the width they are drawn, and filled between with the fill pattern" }</p>
+                        </FormGroup>
+                        <CutLastInput/>
+                        <FormGroup>
+                            <label class="form-label" for="path_ordering">{ "Path order" }</label>
+                            <select id="path_ordering" class="form-select" onchange={on_path_ordering_change}>
+                                {
+                                    for PATH_ORDERINGS.iter().map(|(ordering, label)| html! {
+                                        <option selected={app_state.path_ordering == *ordering}>{ *label }</option>
+                                    })
+                                }
+                            </select>
+                            <p class="form-input-hint">{ "Draw each path after the one that ends closest to it, optimized further by reversing and moving runs of paths, to cut down on travel" }</p>
+                        </FormGroup>
+                        <FormGroup>
+                            <Checkbox
+                                label="Registration marks"
+                                desc="Draw crosses at the corners of the artwork before the first pass, for lining up the material"
+                                checked={form_state.registration_marks.is_some()}
+                                onchange={on_registration_marks_change}
+                            />
+                        </FormGroup>
+                    </div>
+                    <div class="column col-12">
+                        <FormGroup>
+                            <label class="form-label" for="fill_pattern">{ "Fill" }</label>
+                            <select id="fill_pattern" class="form-select" onchange={on_fill_pattern_change}>
+                                {
+                                    for FILL_PATTERNS.iter().map(|(pattern, label)| html! {
+                                        <option selected={form_state.fill.as_ref().map(|fill| fill.pattern) == *pattern}>{ *label }</option>
+                                    })
+                                }
+                            </select>
+                            <p class="form-input-hint">{ "Draw the inside of shapes with a fill before their outline" }</p>
+                        </FormGroup>
+                    </div>
+                    {
+                        if form_state.fill.is_some() {
+                            html! {
+                                <>
+                                    <div class="column col-6 col-sm-12">
+                                        <FillSpacingInput/>
+                                    </div>
+                                    <div class="column col-6 col-sm-12">
+                                        <FillAngleInput/>
+                                    </div>
+                                </>
+                            }
+                        } else {
+                            html!()
+                        }
+                    }
+                    <div class="column col-12">
+                        <FormGroup>
+                            <label class="form-label" for="power_source">{ "Stroke power" }</label>
+                            <select id="power_source" class="form-select" onchange={on_power_source_change}>
+                                {
+                                    for POWER_SOURCES.iter().map(|(source, label)| html! {
+                                        <option selected={form_state.stroke_power.as_ref().map(|stroke_power| stroke_power.source) == *source}>{ *label }</option>
+                                    })
+                                }
+                            </select>
+                            <p class="form-input-hint">{ "Set the power of each path from its stroke with an S word, i.e. to engrave shades with a laser" }</p>
+                        </FormGroup>
+                    </div>
+                    {
+                        if form_state.stroke_power.is_some() {
+                            html! {
+                                <>
+                                    <div class="column col-6 col-sm-12">
+                                        <StrokePowerMinInput/>
+                                    </div>
+                                    <div class="column col-6 col-sm-12">
+                                        <StrokePowerMaxInput/>
+                                    </div>
+                                </>
+                            }
+                        } else {
+                            html!()
+                        }
+                    }
+                    <div class="column col-6 col-sm-12">
+                        <RapidFeedrateInput/>
+                    </div>
+                    <div class="column col-6 col-sm-12">
+                        <PowerInput/>
+                    </div>
+                    <div class="column col-6 col-sm-12">
+                        <HourlyRateInput/>
+                    </div>
+                    <div class="column col-6 col-sm-12">
+                        <MaterialCostInput/>
+                    </div>
+                    <div class="column col-6 col-sm-12">
+                        <AccelerationInput/>
+                    </div>
+                    <div class="column col-6 col-sm-12">
+                        <FormGroup>
+                            <Checkbox
+                                label="Estimate header"
+                                desc="Start the program with comments stating how long it takes and how far it cuts and travels"
+                                checked={form_state.estimate_header}
+                                onchange={on_estimate_header_change}
+                            />
+                        </FormGroup>
+                    </div>
+                    <div class="column col-12">
+                        <PreHomeSequenceInput/>
+                    </div>
+                    <div class="column col-12">
+                        <HomeSequenceInput/>
+                    </div>
+                    <div class="column col-12">
+                        <PostHomeSequenceInput/>
+                    </div>
+                    <div class="column col-12">
+                        <PassBeginSequenceInput/>
+                    </div>
+                    <div class="column col-12">
+                        <PassEndSequenceInput/>
+                    </div>
+                    <div class="column col-12">
+                        <PathBeginSequenceInput/>
+                    </div>
+                    <div class="column col-12">
+                        <PathEndSequenceInput/>
+                    </div>
+                    <div class="column col-12">
+                        {
+                            for sequence_warnings.iter().map(|warning| html! {
+                                <p class="text-warning">{ format!("Check your sequences: {warning}") }</p>
+                            })
+                        }
+                    </div>
+                    { script_editor }
                     <div class="column col-6 col-sm-12">
                         <FormGroup>
                             <Checkbox
@@ -192,6 +1093,16 @@ pub fn settings_form() -> Html {
                             />
                         </FormGroup>
                     </div>
+                    <div class="column col-6 col-sm-12">
+                        <FormGroup>
+                            <Checkbox
+                                label="Short file names"
+                                desc="Name files like DRAWING.GCO, so the SD card reader of the controller can open them"
+                                checked={form_state.short_file_names}
+                                onchange={on_short_file_names_change}
+                            />
+                        </FormGroup>
+                    </div>
                 </div>
             )}
             footer={
@@ -294,6 +1205,16 @@ pub fn import_export_modal() -> Html {
                                             Version::latest(),
                                             env!("CARGO_PKG_REPOSITORY")
                                     ))
+                                } else if let Some(line_interval) = settings
+                                    .conversion
+                                    .raster
+                                    .as_ref()
+                                    .map(|raster| raster.line_interval)
+                                    .filter(|interval| *interval <= 0. || interval.is_nan())
+                                {
+                                    Err(format!(
+                                        "The raster line interval in your settings must be above 0 mm, not {line_interval}"
+                                    ))
                                 } else {
                                     Ok(settings)
                                 })
@@ -349,7 +1270,7 @@ pub fn import_export_modal() -> Html {
                                 button={html_nested!(
                                     <Button
                                         style={ButtonStyle::Primary}
-                                        disabled={import_state.as_ref().map_or(true, |r| r.is_err()) || import_reading.is_some()}
+                                        disabled={import_state.as_ref().is_none_or(|r| r.is_err()) || import_reading.is_some()}
                                         title="Save"
                                         onclick={import_save_onclick}
                                         input_group=true
@@ -427,6 +1348,11 @@ pub fn svg_form() -> Html {
                                         dimensions: [None; 2],
                                         scale: 1.0,
                                         offset: [0.0, 0.0],
+                                        rotation: 0.0,
+                                        skip_ids: vec![],
+                                        region: None,
+                                        locked: false,
+                                        selected: false,
                                     })
                                 }
                             }),
@@ -519,6 +1445,11 @@ pub fn svg_form() -> Html {
                                     dimensions: [None; 2],
                                     scale: 1.0,
                                     offset: [0.0, 0.0],
+                                    rotation: 0.0,
+                                    skip_ids: vec![],
+                                    region: None,
+                                    locked: false,
+                                    selected: false,
                                 });
                             });
                         };
@@ -535,6 +1466,16 @@ pub fn svg_form() -> Html {
         })
     };
 
+    #[cfg(feature = "barcode")]
+    let barcode_form = html! {
+        <>
+            <div class="divider text-center" data-content="OR"/>
+            <BarcodeForm/>
+        </>
+    };
+    #[cfg(not(feature = "barcode"))]
+    let barcode_form = html! {};
+
     html! {
         <FormGroup success={file_upload_res.as_ref().map(Result::is_ok).or_else(|| url_input_parsed.as_ref().map(Result::is_ok))}>
             <FileUpload<(), String>
@@ -561,6 +1502,402 @@ pub fn svg_form() -> Html {
                 )}
                 parsed={(*url_input_parsed).clone()}
             />
+            { barcode_form }
         </FormGroup>
     }
 }
+
+/// Downloads a program that draws a grid across ranges of power and feedrate, for dialing in the
+/// settings for a new material
+#[function_component(MaterialTestForm)]
+pub fn material_test_form() -> Html {
+    let app_store = use_store_value::<AppState>();
+    let default = MaterialTest::default();
+
+    let parse_number = |value: String| -> Result<f64, String> {
+        value.parse::<f64>().map_err(|err| err.to_string())
+    };
+    let parse_count = |value: String| -> Result<usize, String> {
+        match value.parse::<usize>() {
+            Ok(0) => Err("must be at least 1".to_string()),
+            Ok(count) => Ok(count),
+            Err(err) => Err(err.to_string()),
+        }
+    };
+    let power = [
+        use_state(|| Ok(default.power[0])),
+        use_state(|| Ok(default.power[1])),
+    ];
+    let feedrate = [
+        use_state(|| Ok(default.feedrate[0])),
+        use_state(|| Ok(default.feedrate[1])),
+    ];
+    let columns = use_state(|| Ok(default.columns));
+    let rows = use_state(|| Ok(default.rows));
+    let oninput = |state: &UseStateHandle<Result<f64, String>>| {
+        let state = state.clone();
+        Callback::from(move |event: InputEvent| {
+            state.set(parse_number(
+                event.target_unchecked_into::<HtmlInputElement>().value(),
+            ));
+        })
+    };
+    let count_oninput = |state: &UseStateHandle<Result<usize, String>>| {
+        let state = state.clone();
+        Callback::from(move |event: InputEvent| {
+            state.set(parse_count(
+                event.target_unchecked_into::<HtmlInputElement>().value(),
+            ));
+        })
+    };
+
+    let values = (|| {
+        Some(MaterialTest {
+            power: [power[0].as_ref().ok()?, power[1].as_ref().ok()?].map(|power| *power),
+            feedrate: [feedrate[0].as_ref().ok()?, feedrate[1].as_ref().ok()?]
+                .map(|feedrate| *feedrate),
+            columns: *columns.as_ref().ok()?,
+            rows: *rows.as_ref().ok()?,
+            ..MaterialTest::default()
+        })
+    })();
+
+    let uses_power = crate::machine(&app_store.settings.machine).uses_power();
+
+    let download_onclick = {
+        let material_test = values.clone();
+        Callback::from(move |_| {
+            let Some(material_test) = &material_test else {
+                return;
+            };
+            let settings = &app_store.settings;
+            let program =
+                material_test.to_program(&settings.conversion, crate::machine(&settings.machine));
+            download_program(&program, settings, "material_test.gcode");
+        })
+    };
+
+    html! {
+        <details class="form-group">
+            <summary>{ "Material test" }</summary>
+            <p class="text-small">
+                { "Draws a grid with power going up from left to right and feedrate from bottom to top, each cell as its own pass. Use {power} in the pass begin sequence (i.e. M3 S{power})." }
+            </p>
+            {
+                if uses_power {
+                    html!()
+                } else {
+                    html! {
+                        <p class="text-small text-warning">
+                            { "No pass or path sequence uses {power} yet, so every column would be drawn at the same power." }
+                        </p>
+                    }
+                }
+            }
+            <div class="columns">
+                <div class="column col-6">
+                    <Input<f64, String>
+                        label="Lowest power"
+                        r#type={InputType::Text}
+                        default={default.power[0]}
+                        oninput={oninput(&power[0])}
+                        parsed={Some((*power[0]).clone())}
+                    />
+                </div>
+                <div class="column col-6">
+                    <Input<f64, String>
+                        label="Highest power"
+                        r#type={InputType::Text}
+                        default={default.power[1]}
+                        oninput={oninput(&power[1])}
+                        parsed={Some((*power[1]).clone())}
+                    />
+                </div>
+                <div class="column col-6">
+                    <Input<f64, String>
+                        label="Lowest feedrate (mm/min)"
+                        r#type={InputType::Text}
+                        default={default.feedrate[0]}
+                        oninput={oninput(&feedrate[0])}
+                        parsed={Some((*feedrate[0]).clone())}
+                    />
+                </div>
+                <div class="column col-6">
+                    <Input<f64, String>
+                        label="Highest feedrate (mm/min)"
+                        r#type={InputType::Text}
+                        default={default.feedrate[1]}
+                        oninput={oninput(&feedrate[1])}
+                        parsed={Some((*feedrate[1]).clone())}
+                    />
+                </div>
+                <div class="column col-6">
+                    <Input<usize, String>
+                        label="Power steps"
+                        r#type={InputType::Text}
+                        default={default.columns}
+                        oninput={count_oninput(&columns)}
+                        parsed={Some((*columns).clone())}
+                    />
+                </div>
+                <div class="column col-6">
+                    <Input<usize, String>
+                        label="Feedrate steps"
+                        r#type={InputType::Text}
+                        default={default.rows}
+                        oninput={count_oninput(&rows)}
+                        parsed={Some((*rows).clone())}
+                    />
+                </div>
+            </div>
+            <Button
+                title="Download material test"
+                style={ButtonStyle::Default}
+                icon={html_nested!(<Icon name={IconName::Download} />)}
+                disabled={values.is_none()}
+                onclick={download_onclick}
+            />
+        </details>
+    }
+}
+
+/// Downloads a program that cuts a line while moving the Z axis across a range, for finding the
+/// height at which a laser is in focus
+#[function_component(FocusTestForm)]
+pub fn focus_test_form() -> Html {
+    let app_store = use_store_value::<AppState>();
+    let default = FocusTest::default();
+
+    let parse_number = |value: String| -> Result<f64, String> {
+        value.parse::<f64>().map_err(|err| err.to_string())
+    };
+    let z = [
+        use_state(|| Ok(default.z[0])),
+        use_state(|| Ok(default.z[1])),
+    ];
+    let length = use_state(|| Ok(default.length));
+    let tick_spacing = use_state(|| Ok(default.tick_spacing));
+    let oninput = |state: &UseStateHandle<Result<f64, String>>| {
+        let state = state.clone();
+        Callback::from(move |event: InputEvent| {
+            state.set(parse_number(
+                event.target_unchecked_into::<HtmlInputElement>().value(),
+            ));
+        })
+    };
+
+    let values = (|| {
+        Some(FocusTest {
+            z: [*z[0].as_ref().ok()?, *z[1].as_ref().ok()?],
+            length: *length.as_ref().ok()?,
+            tick_spacing: *tick_spacing.as_ref().ok()?,
+            ..FocusTest::default()
+        })
+    })();
+
+    let download_onclick = {
+        let focus_test = values.clone();
+        Callback::from(move |_| {
+            let Some(focus_test) = &focus_test else {
+                return;
+            };
+            let settings = &app_store.settings;
+            let program =
+                focus_test.to_program(&settings.conversion, crate::machine(&settings.machine));
+            download_program(&program, settings, "focus_test.gcode");
+        })
+    };
+
+    html! {
+        <details class="form-group">
+            <summary>{ "Focus test" }</summary>
+            <p class="text-small">
+                { "Cuts a line from the origin while moving the Z axis across a range, with a tick below it at each height. The thinnest part of the line is in focus. The Z axis rapidly moves to the start height, so keep the whole range clear of the material." }
+            </p>
+            <div class="columns">
+                <div class="column col-6">
+                    <Input<f64, String>
+                        label="Start height (mm)"
+                        r#type={InputType::Text}
+                        default={default.z[0]}
+                        oninput={oninput(&z[0])}
+                        parsed={Some((*z[0]).clone())}
+                    />
+                </div>
+                <div class="column col-6">
+                    <Input<f64, String>
+                        label="End height (mm)"
+                        r#type={InputType::Text}
+                        default={default.z[1]}
+                        oninput={oninput(&z[1])}
+                        parsed={Some((*z[1]).clone())}
+                    />
+                </div>
+                <div class="column col-6">
+                    <Input<f64, String>
+                        label="Line length (mm)"
+                        r#type={InputType::Text}
+                        default={default.length}
+                        oninput={oninput(&length)}
+                        parsed={Some((*length).clone())}
+                    />
+                </div>
+                <div class="column col-6">
+                    <Input<f64, String>
+                        label="Height between ticks (mm)"
+                        desc="0 to leave out the ticks"
+                        r#type={InputType::Text}
+                        default={default.tick_spacing}
+                        oninput={oninput(&tick_spacing)}
+                        parsed={Some((*tick_spacing).clone())}
+                    />
+                </div>
+            </div>
+            <Button
+                title="Download focus test"
+                style={ButtonStyle::Default}
+                icon={html_nested!(<Icon name={IconName::Download} />)}
+                disabled={values.is_none()}
+                onclick={download_onclick}
+            />
+        </details>
+    }
+}
+
+/// Downloads a program that draws a pattern for calibrating the machine, with the same settings
+/// as a conversion
+#[function_component(CalibrationTestForm)]
+pub fn calibration_test_form() -> Html {
+    let app_store = use_store_value::<AppState>();
+    let default = CalibrationTest::default();
+
+    let pattern = use_state(|| default.pattern);
+    let size = use_state(|| Ok(default.size));
+    let count = use_state(|| Ok(default.count));
+    let pattern_onchange = {
+        let pattern = pattern.clone();
+        Callback::from(move |event: Event| {
+            let index = event
+                .target_unchecked_into::<HtmlSelectElement>()
+                .selected_index();
+            if let Some((choice, _)) = CALIBRATION_PATTERNS.get(index as usize) {
+                pattern.set(*choice);
+            }
+        })
+    };
+    let size_oninput = {
+        let size = size.clone();
+        Callback::from(move |event: InputEvent| {
+            let value = event.target_unchecked_into::<HtmlInputElement>().value();
+            size.set(value.parse::<f64>().map_err(|err| err.to_string()));
+        })
+    };
+    let count_oninput = {
+        let count = count.clone();
+        Callback::from(move |event: InputEvent| {
+            let value = event.target_unchecked_into::<HtmlInputElement>().value();
+            count.set(match value.parse::<usize>() {
+                Ok(0) => Err("must be at least 1".to_string()),
+                Ok(count) => Ok(count),
+                Err(err) => Err(err.to_string()),
+            });
+        })
+    };
+
+    let values = (|| {
+        Some(CalibrationTest {
+            pattern: *pattern,
+            size: *size.as_ref().ok()?,
+            count: *count.as_ref().ok()?,
+            ..CalibrationTest::default()
+        })
+    })();
+
+    let download_onclick = {
+        let calibration_test = values.clone();
+        Callback::from(move |_| {
+            let Some(calibration_test) = &calibration_test else {
+                return;
+            };
+            let settings = &app_store.settings;
+            let program = calibration_test
+                .to_program(&settings.conversion, crate::machine(&settings.machine));
+            download_program(&program, settings, "calibration.gcode");
+        })
+    };
+
+    let description = match *pattern {
+        CalibrationPattern::AxisScale => "Draws squares a quarter, half, and all of the size wide from the origin. Measure them to see how far each axis really moves.",
+        CalibrationPattern::CircleDiamondSquare => "Draws a square, a diamond touching the middle of its sides, and a circle inside the diamond. Uneven sides or a lopsided circle show axes that aren't square or don't move together smoothly.",
+        CalibrationPattern::Backlash => "Draws two rows of ticks along each axis, one moving forwards and the other backwards. How far apart the rows are is the backlash of the axis.",
+        CalibrationPattern::KerfComb => "Cuts a square into strips. Push them together and measure across them: the kerf is how much less than the size that is, divided by the number of strips.",
+    };
+
+    html! {
+        <details class="form-group">
+            <summary>{ "Calibration" }</summary>
+            <p class="text-small">{ description }</p>
+            <div class="columns">
+                <div class="column col-12">
+                    <FormGroup>
+                        <label class="form-label" for="calibration_pattern">{ "Pattern" }</label>
+                        <select id="calibration_pattern" class="form-select" onchange={pattern_onchange}>
+                            {
+                                for CALIBRATION_PATTERNS.iter().map(|(choice, label)| html! {
+                                    <option selected={*pattern == *choice}>{ *label }</option>
+                                })
+                            }
+                        </select>
+                    </FormGroup>
+                </div>
+                <div class="column col-6">
+                    <Input<f64, String>
+                        label="Size (mm)"
+                        r#type={InputType::Text}
+                        default={default.size}
+                        oninput={size_oninput}
+                        parsed={Some((*size).clone())}
+                    />
+                </div>
+                if matches!(*pattern, CalibrationPattern::Backlash | CalibrationPattern::KerfComb) {
+                    <div class="column col-6">
+                        <Input<usize, String>
+                            label={if *pattern == CalibrationPattern::Backlash { "Ticks in each row" } else { "Strips" }}
+                            r#type={InputType::Text}
+                            default={default.count}
+                            oninput={count_oninput}
+                            parsed={Some((*count).clone())}
+                        />
+                    </div>
+                }
+            </div>
+            <Button
+                title="Download calibration pattern"
+                style={ButtonStyle::Default}
+                icon={html_nested!(<Icon name={IconName::Download} />)}
+                disabled={values.is_none()}
+                onclick={download_onclick}
+            />
+        </details>
+    }
+}
+
+/// Write a generated test program with the postprocessing settings and download it
+fn download_program(program: &[Token], settings: &Settings, filename: &str) {
+    let mut gcode = vec![];
+    write_gcode(
+        program,
+        &FormatOptions {
+            checksums: settings.postprocess.checksums,
+            line_numbers: settings.postprocess.line_numbers,
+            newline_before_comment: settings.postprocess.newline_before_comment,
+            ..Default::default()
+        },
+        settings.postprocess.precision,
+        settings.machine.max_line_length,
+        settings.machine.dialect.is_some_and(Dialect::modal_motion),
+        &mut gcode,
+    )
+    .unwrap();
+    crate::util::prompt_download(filename, gcode);
+}
@@ -0,0 +1,177 @@
+use svg2gcode::{Tool, ToolKind};
+use web_sys::{HtmlInputElement, HtmlSelectElement};
+use yew::prelude::*;
+use yewdux::functional::use_store;
+
+use crate::{
+    state::{parse_non_negative, parse_positive, FormState, NumberError},
+    ui::{FormGroup, Icon, IconName},
+};
+
+/// Choices for [`svg2gcode::Tool::kind`], in the order they are listed
+const TOOL_KINDS: [(ToolKind, &str); 3] = [
+    (ToolKind::EndMill, "End mill"),
+    (ToolKind::VBit, "V-bit"),
+    (ToolKind::Laser, "Laser"),
+];
+
+/// Tools to cut with, picked by name for the whole SVG or for a layer, see
+/// [`svg2gcode::ConversionConfig::tools`]
+///
+/// Like the rest of the form, changes are saved with the settings.
+#[function_component(ToolLibraryTable)]
+pub fn tool_library_table() -> Html {
+    let (form_state, form_dispatch) = use_store::<FormState>();
+    let name = use_state(String::new);
+
+    let name_oninput = {
+        let name = name.clone();
+        Callback::from(move |event: InputEvent| {
+            name.set(event.target_unchecked_into::<HtmlInputElement>().value());
+        })
+    };
+
+    let taken = form_state.tools.iter().any(|tool| tool.name == name.trim());
+    let add_onclick = {
+        let name = name.clone();
+        form_dispatch.reduce_mut_callback(move |form| {
+            form.tools.push(Tool {
+                name: name.trim().to_string(),
+                kind: ToolKind::default(),
+                diameter: 3.175,
+                angle: 0.,
+                max_depth_of_cut: 1.,
+                max_feedrate: 0.,
+            });
+        })
+    };
+
+    let cell = |index: usize,
+                column: &'static str,
+                value: f64,
+                parse: fn(&str) -> Result<f64, NumberError>,
+                set: fn(&mut Tool, f64)| {
+        let Some(name) = form_state.tools.get(index).map(|tool| tool.name.clone()) else {
+            return html!();
+        };
+        let error = form_state.tool_errors.contains_key(&(name.clone(), column));
+        let onchange = form_dispatch.reduce_mut_callback_with(move |form, event: Event| {
+            let value = event.target_unchecked_into::<HtmlInputElement>().value();
+            let Some(tool) = form.tools.get_mut(index) else {
+                return;
+            };
+            match parse(value.trim()) {
+                Ok(value) => {
+                    set(tool, value);
+                    form.tool_errors.remove(&(name.clone(), column));
+                }
+                Err(err) => {
+                    form.tool_errors.insert((name.clone(), column), err);
+                }
+            }
+        });
+        html! {
+            <td>
+                <input
+                    type="number"
+                    class={classes!("form-input", "input-sm", if error { Some("is-error") } else { None })}
+                    value={value.to_string()}
+                    {onchange}
+                />
+            </td>
+        }
+    };
+
+    html! {
+        <FormGroup success={(!form_state.tool_errors.is_empty()).then_some(false)}>
+            <label class="form-label">{ "Tool Library" }</label>
+            {
+                if form_state.tools.is_empty() {
+                    html!()
+                } else {
+                    html! {
+                        <table class="table">
+                            <thead>
+                                <tr>
+                                    <th>{ "Name" }</th>
+                                    <th>{ "Kind" }</th>
+                                    <th>{ "Diameter" }</th>
+                                    <th>{ "Angle" }</th>
+                                    <th>{ "Max Depth of Cut" }</th>
+                                    <th>{ "Max Feedrate" }</th>
+                                    <th></th>
+                                </tr>
+                            </thead>
+                            <tbody>
+                                {
+                                    for form_state.tools.iter().enumerate().map(|(index, tool)| {
+                                        let kind_onchange = form_dispatch.reduce_mut_callback_with(move |form, event: Event| {
+                                            let selected = event
+                                                .target_unchecked_into::<HtmlSelectElement>()
+                                                .selected_index();
+                                            if let (Some(tool), Some((kind, _))) = (form.tools.get_mut(index), TOOL_KINDS.get(selected as usize)) {
+                                                tool.kind = *kind;
+                                            }
+                                        });
+                                        let remove_onclick = form_dispatch.reduce_mut_callback(move |form| {
+                                            let removed = form.tools.remove(index);
+                                            form.tool_errors.retain(|(name, _), _| *name != removed.name);
+                                            if form.tool.as_ref() == Some(&removed.name) {
+                                                form.tool = None;
+                                            }
+                                        });
+                                        html! {
+                                            <tr>
+                                                <td>{ &tool.name }</td>
+                                                <td>
+                                                    <select class="form-select select-sm" onchange={kind_onchange}>
+                                                        {
+                                                            for TOOL_KINDS.iter().map(|(kind, label)| html! {
+                                                                <option selected={tool.kind == *kind}>{ *label }</option>
+                                                            })
+                                                        }
+                                                    </select>
+                                                </td>
+                                                { cell(index, "Diameter", tool.diameter, parse_positive, |tool, value| tool.diameter = value) }
+                                                { cell(index, "Angle", tool.angle, parse_non_negative, |tool, value| tool.angle = value) }
+                                                { cell(index, "Max Depth of Cut", tool.max_depth_of_cut, parse_non_negative, |tool, value| tool.max_depth_of_cut = value) }
+                                                { cell(index, "Max Feedrate", tool.max_feedrate, parse_non_negative, |tool, value| tool.max_feedrate = value) }
+                                                <td>
+                                                    <button class="btn btn-sm" title="Remove" onclick={remove_onclick}>
+                                                        <Icon name={IconName::Delete} />
+                                                    </button>
+                                                </td>
+                                            </tr>
+                                        }
+                                    })
+                                }
+                            </tbody>
+                        </table>
+                    }
+                }
+            }
+            <div class="input-group">
+                <input
+                    type="text"
+                    class="form-input"
+                    placeholder="Tool name (i.e. 1/8\" end mill)"
+                    value={(*name).clone()}
+                    oninput={name_oninput}
+                />
+                <button
+                    class="btn input-group-btn"
+                    disabled={name.trim().is_empty() || taken}
+                    onclick={add_onclick}
+                >
+                    { "Add tool" }
+                </button>
+            </div>
+            {
+                for form_state.tool_errors.iter().map(|((name, column), err)| html! {
+                    <pre class="form-input-hint">{ format!("{name} {column}: {err}") }</pre>
+                })
+            }
+            <p class="form-input-hint">{ "Tools to pick below or for a layer, with their diameter and V-bit angle (mm and degrees), and the deepest (mm) and fastest (mm/min, 0 for no limit) they cut. With a material thickness, passes are worked out from the depth of cut, and without a kerf width, the width of the cut is made up for." }</p>
+        </FormGroup>
+    }
+}
@@ -0,0 +1,51 @@
+use std::sync::Mutex;
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use web_sys::console;
+
+/// Messages of warnings logged by svg2gcode, for manifests
+static WARNING_MESSAGES: Mutex<Vec<String>> = Mutex::new(vec![]);
+
+/// Logs to the browser console, keeping the warnings of svg2gcode as they go by
+struct KeepingLogger;
+
+impl Log for KeepingLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= Level::Info
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let message = format!("{} {}: {}", record.level(), record.target(), record.args());
+        match record.level() {
+            Level::Error => console::error_1(&message.into()),
+            Level::Warn => {
+                if record.target().starts_with("svg2gcode::") {
+                    if let Ok(mut messages) = WARNING_MESSAGES.lock() {
+                        messages.push(record.args().to_string());
+                    }
+                }
+                console::warn_1(&message.into());
+            }
+            _ => console::log_1(&message.into()),
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Log to the browser console, keeping the warnings for [`take_warnings`]
+pub fn init_logger() {
+    log::set_max_level(LevelFilter::Info);
+    log::set_logger(&KeepingLogger).expect("logger is only set once");
+}
+
+/// Messages of the warnings logged by svg2gcode since this was last called
+pub fn take_warnings() -> Vec<String> {
+    WARNING_MESSAGES
+        .lock()
+        .map(|mut messages| std::mem::take(&mut *messages))
+        .unwrap_or_default()
+}
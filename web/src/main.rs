@@ -1,34 +1,41 @@
-use std::{
-    io::Cursor,
-    path::{Path, PathBuf},
-};
+use std::{io, path::Path};
 
 use g_code::{
-    emit::{format_gcode_fmt, format_gcode_io, FormatOptions},
+    emit::{FormatOptions, Token},
     parse::snippet_parser,
 };
 use js_sys::Date;
-use log::Level;
+use log::{error, warn};
 use roxmltree::{Document, ParsingOptions};
-use svg2gcode::{svg2program, ConversionOptions, Machine};
+use svg2gcode::{
+    lint_sequences, place, svg2program_with_passes, svg2toolpaths, toolpaths2perimeter_check,
+    toolpaths2program, write_gcode, ConversionConfig, ConversionOptions, Dialect, Hooks,
+    JobEstimate, LayerOverrides, Machine, MachineConfig, Manifest, ManifestEntry, ManifestSource,
+    PassRegistry, Region, Settings, ShortNames, Toolpaths,
+};
 use yew::prelude::*;
 
 mod forms;
+mod logger;
+mod serial;
 mod state;
 mod ui;
 mod util;
+mod zip;
 
 use forms::*;
+use logger::{init_logger, take_warnings};
 use state::*;
 use ui::*;
 use util::*;
 use yewdux::{prelude::use_store, use_dispatch, YewduxRoot};
-use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+use zip::ZipWriter;
 
 #[function_component(App)]
 fn app() -> Html {
     let generating = use_state_eq(|| false);
     let generating_setter = generating.setter();
+    let lines_written = use_state_eq(|| 0usize);
 
     let form_dispatch = use_dispatch::<FormState>();
     let (app_store, app_dispatch) = use_store::<AppState>();
@@ -50,144 +57,30 @@ fn app() -> Html {
     }
 
     let generate_disabled = *generating || app_store.svgs.is_empty();
+    // Index of the SVG card being dragged to a new place in the list
+    let dragged = use_state_eq(|| None::<usize>);
+    let combine_onchange = app_dispatch.reduce_mut_callback_with(|app, event: Event| {
+        app.combine = event
+            .target_unchecked_into::<web_sys::HtmlInputElement>()
+            .checked();
+    });
     let generate_onclick = {
         let app_store = app_store.clone();
+        let lines_written_setter = lines_written.setter();
         Callback::from(move |_| {
             generating_setter.set(true);
-            let mut zip = ZipWriter::new(Cursor::new(vec![]));
-            let opts = FileOptions::default().compression_method(CompressionMethod::Stored);
-
-            if app_store.svgs.len() > 1 {
-                zip.add_directory("svg2gcode_output", opts).unwrap();
-            }
-
-            for svg in app_store.svgs.iter() {
-                let options = ConversionOptions {
-                    dimensions: svg.dimensions,
-                };
-
-                // Apply scale by adjusting DPI (higher DPI = smaller output, so divide by scale)
-                let mut scaled_conversion_config = app_store.settings.conversion.clone();
-                scaled_conversion_config.dpi = scaled_conversion_config.dpi / svg.scale;
-
-                // Apply offset
-                scaled_conversion_config.origin = [
-                    Some(svg.offset[0]),
-                    Some(svg.offset[1]),
-                ];
-
-                let machine = Machine::new(
-                    app_store.settings.machine.supported_functionality.clone(),
-                    app_store
-                        .settings
-                        .machine
-                        .tool_on_sequence
-                        .as_deref()
-                        .map(snippet_parser)
-                        .transpose()
-                        .unwrap(),
-                    app_store
-                        .settings
-                        .machine
-                        .tool_off_sequence
-                        .as_deref()
-                        .map(snippet_parser)
-                        .transpose()
-                        .unwrap(),
-                    app_store
-                        .settings
-                        .machine
-                        .begin_sequence
-                        .as_deref()
-                        .map(snippet_parser)
-                        .transpose()
-                        .unwrap(),
-                    app_store
-                        .settings
-                        .machine
-                        .end_sequence
-                        .as_deref()
-                        .map(snippet_parser)
-                        .transpose()
-                        .unwrap(),
-                );
-                let document = Document::parse_with_options(
-                    svg.content.as_str(),
-                    ParsingOptions {
-                        allow_dtd: true,
-                        ..Default::default()
-                    },
-                )
-                .unwrap();
-
-                let program =
-                    svg2program(&document, &scaled_conversion_config, options, machine);
-
-                let filepath = if app_store.svgs.len() > 1 {
-                    PathBuf::from("svg2gcode_output")
-                        .join(Path::new(svg.filename.as_str()).with_extension("gcode"))
-                } else {
-                    Path::new(svg.filename.as_str()).with_extension("gcode")
-                };
-
-                match app_store.svgs.len() {
-                    0 => unreachable!(),
-                    1 => {
-                        let gcode = {
-                            let mut acc = String::new();
-                            format_gcode_fmt(
-                                &program,
-                                FormatOptions {
-                                    checksums: app_store.settings.postprocess.checksums,
-                                    line_numbers: app_store.settings.postprocess.line_numbers,
-                                    newline_before_comment: app_store
-                                        .settings
-                                        .postprocess
-                                        .newline_before_comment,
-                                    ..Default::default()
-                                },
-                                &mut acc,
-                            )
-                            .unwrap();
-                            acc
-                        };
-                        prompt_download(filepath, gcode.as_bytes());
-                    }
-                    _multiple => {
-                        zip.start_file(filepath.to_string_lossy(), opts).unwrap();
-
-                        format_gcode_io(
-                            &program,
-                            FormatOptions {
-                                checksums: app_store.settings.postprocess.checksums,
-                                line_numbers: app_store.settings.postprocess.line_numbers,
-                                newline_before_comment: app_store
-                                    .settings
-                                    .postprocess
-                                    .newline_before_comment,
-                                ..Default::default()
-                            },
-                            &mut zip,
-                        )
-                        .unwrap();
-                    }
+            lines_written_setter.set(0);
+            let app_store = app_store.clone();
+            let generating_setter = generating_setter.clone();
+            let lines_written_setter = lines_written_setter.clone();
+            // Formatting yields to the browser now and then, so the line count can be shown
+            wasm_bindgen_futures::spawn_local(async move {
+                if let Err(err) = generate(&app_store, &lines_written_setter).await {
+                    error!("Could not generate g-code: {err}");
                 }
-            }
 
-            if app_store.svgs.len() > 1 {
-                zip.set_comment(format!(
-                    "Created with svg2gcode: https://sameer.github.io/svg2gcode/\n{}",
-                    env!("CARGO_PKG_DESCRIPTION")
-                ));
-                let output = zip.finish().unwrap();
-                let date = Date::new_0().to_iso_string();
-                prompt_download(
-                    format!("svg2gcode_bulk_download_{date}.zip"),
-                    output.get_ref(),
-                );
-            }
-
-            generating_setter.set(false);
+                generating_setter.set(false);
+            });
         })
     };
 
@@ -214,6 +107,7 @@ fn app() -> Html {
                         disabled={generate_disabled}
                         onclick={generate_onclick}
                     />
+                    <LayoutSheetButton/>
                     <HyperlinkButton
                         title="Settings"
                         style={ButtonStyle::Default}
@@ -221,6 +115,25 @@ fn app() -> Html {
                         href="#settings"
                     />
                 </ButtonGroup>
+                if app_store.svgs.len() > 1 {
+                    <label class="form-checkbox">
+                        <input type="checkbox" checked={app_store.combine} onchange={combine_onchange} />
+                        <i class="form-icon"></i>
+                        { "Combine into one program, cutting the SVGs in the order they are listed" }
+                    </label>
+                }
+                {
+                    if *generating {
+                        html!{ <p class="text-small">{ format!("{} lines of G-Code written", *lines_written) }</p> }
+                    } else {
+                        html!{}
+                    }
+                }
+                <MaterialTestForm/>
+                <FocusTestForm/>
+                <CalibrationTestForm/>
+                <SummaryBar/>
+                <GroupTools/>
                 <div class={classes!("card-container", "columns")}>
                     {
                         for app_store.svgs.iter().enumerate().map(|(i, svg)| {
@@ -234,6 +147,123 @@ fn app() -> Html {
                                 app.svgs.remove(i);
                             });
 
+                            let perimeter_check_onclick = {
+                                let app_store = app_store.clone();
+                                Callback::from(move |_| {
+                                    let svg = &app_store.svgs[i];
+                                    let Ok(document) = Document::parse_with_options(
+                                        svg.content.as_str(),
+                                        ParsingOptions {
+                                            allow_dtd: true,
+                                            ..Default::default()
+                                        },
+                                    ) else {
+                                        return;
+                                    };
+                                    // Same scale and offset as when generating
+                                    let mut scaled_conversion_config = app_store.settings.conversion.clone();
+                                    scaled_conversion_config.dpi /= svg.scale;
+                                    scaled_conversion_config.origin = [Some(svg.offset[0]), Some(svg.offset[1])];
+                                    scaled_conversion_config.rotation = svg.rotation;
+                                    let options = ConversionOptions {
+                                        dimensions: svg.dimensions,
+                                        skip_ids: svg.skip_ids.clone(),
+                                        ..Default::default()
+                                    };
+                                    let placement = place(&document, &scaled_conversion_config, &options);
+                                    let mut toolpaths = svg2toolpaths(&document, &scaled_conversion_config, options, placement);
+                                    toolpaths.toolpaths = PassRegistry::default().apply(toolpaths.toolpaths, &scaled_conversion_config);
+                                    let program = toolpaths2perimeter_check(
+                                        &toolpaths,
+                                        &scaled_conversion_config,
+                                        machine(&app_store.settings.machine),
+                                    );
+
+                                    let mut gcode = vec![];
+                                    write_gcode(
+                                        &program,
+                                        &FormatOptions {
+                                            checksums: app_store.settings.postprocess.checksums,
+                                            line_numbers: app_store.settings.postprocess.line_numbers,
+                                            newline_before_comment: app_store.settings.postprocess.newline_before_comment,
+                                            ..Default::default()
+                                        },
+                                        app_store.settings.postprocess.precision,
+                                        app_store.settings.machine.max_line_length,
+                                        app_store.settings.machine.dialect.is_some_and(Dialect::modal_motion),
+                                        &mut gcode,
+                                    )
+                                    .unwrap();
+                                    let stem = Path::new(svg.filename.as_str()).with_extension("");
+                                    prompt_download(
+                                        format!("{}_perimeter_check.gcode", stem.display()),
+                                        gcode,
+                                    );
+                                })
+                            };
+
+                            let recut_onclick = {
+                                let app_store = app_store.clone();
+                                Callback::from(move |_| {
+                                    let svg = &app_store.svgs[i];
+                                    let Some(region) = svg.region else {
+                                        return;
+                                    };
+                                    let Ok(document) = Document::parse_with_options(
+                                        svg.content.as_str(),
+                                        ParsingOptions {
+                                            allow_dtd: true,
+                                            ..Default::default()
+                                        },
+                                    ) else {
+                                        return;
+                                    };
+                                    // Same scale and offset as when generating, so the paths are cut where they were
+                                    let mut scaled_conversion_config = app_store.settings.conversion.clone();
+                                    scaled_conversion_config.dpi /= svg.scale;
+                                    scaled_conversion_config.origin = [Some(svg.offset[0]), Some(svg.offset[1])];
+                                    scaled_conversion_config.rotation = svg.rotation;
+                                    let options = ConversionOptions {
+                                        dimensions: svg.dimensions,
+                                        skip_ids: svg.skip_ids.clone(),
+                                        layer_overrides: app_store.layer_overrides.clone(),
+                                        path_ordering: app_store.path_ordering,
+                                        ..Default::default()
+                                    };
+                                    let mut passes = PassRegistry::default();
+                                    passes.register(Region(region));
+                                    let program = svg2program_with_passes(
+                                        &document,
+                                        &scaled_conversion_config,
+                                        options,
+                                        machine(&app_store.settings.machine),
+                                        &passes,
+                                    );
+
+                                    let mut gcode = vec![];
+                                    write_gcode(
+                                        &program,
+                                        &FormatOptions {
+                                            checksums: app_store.settings.postprocess.checksums,
+                                            line_numbers: app_store.settings.postprocess.line_numbers,
+                                            newline_before_comment: app_store.settings.postprocess.newline_before_comment,
+                                            ..Default::default()
+                                        },
+                                        app_store.settings.postprocess.precision,
+                                        app_store.settings.machine.max_line_length,
+                                        app_store.settings.machine.dialect.is_some_and(Dialect::modal_motion),
+                                        &mut gcode,
+                                    )
+                                    .unwrap();
+                                    let stem = Path::new(svg.filename.as_str()).with_extension("");
+                                    prompt_download(format!("{}_recut.gcode", stem.display()), gcode);
+                                })
+                            };
+
+                            let on_region_change = app_dispatch.reduce_mut_callback_with(move |app, region: Option<_>| {
+                                app.svgs[i].region = region;
+                            });
+
                             let scale_oninput = app_dispatch.reduce_mut_callback_with(move |app, event: InputEvent| {
                                 let value = event.target_unchecked_into::<web_sys::HtmlInputElement>().value();
                                 if let Ok(scale) = value.parse::<f64>() {
@@ -243,20 +273,97 @@ fn app() -> Html {
                                 }
                             });
 
+                            let rotation_oninput = app_dispatch.reduce_mut_callback_with(move |app, event: InputEvent| {
+                                let value = event.target_unchecked_into::<web_sys::HtmlInputElement>().value();
+                                if let Ok(rotation) = value.parse::<f64>() {
+                                    app.svgs[i].rotation = rotation;
+                                    app.svgs[i].region = None;
+                                }
+                            });
+
                             let on_offset_change = app_dispatch.reduce_mut_callback_with(move |app, offset: [f64; 2]| {
-                                app.svgs[i].offset = offset;
+                                let delta = [offset[0] - app.svgs[i].offset[0], offset[1] - app.svgs[i].offset[1]];
+                                // Selected SVGs are dragged together
+                                let together = app.svgs[i].selected;
+                                for (j, svg) in app.svgs.iter_mut().enumerate() {
+                                    if j == i || (together && svg.selected && !svg.locked) {
+                                        svg.offset = [svg.offset[0] + delta[0], svg.offset[1] + delta[1]];
+                                        // The region covered other paths before they moved
+                                        svg.region = None;
+                                    }
+                                }
+                            });
+
+                            let select_toggle = app_dispatch.reduce_mut_callback(move |app| {
+                                app.svgs[i].selected = !app.svgs[i].selected;
+                            });
+
+                            let on_element_toggle = app_dispatch.reduce_mut_callback_with(move |app, (id, checked): (String, bool)| {
+                                let skip_ids = &mut app.svgs[i].skip_ids;
+                                if checked {
+                                    skip_ids.retain(|skipped| *skipped != id);
+                                } else if !skip_ids.contains(&id) {
+                                    skip_ids.push(id);
+                                }
+                            });
+
+                            let lock_onclick = app_dispatch.reduce_mut_callback(move |app| {
+                                app.svgs[i].locked = !app.svgs[i].locked;
                             });
 
+                            let drag_ondragstart = {
+                                let dragged = dragged.clone();
+                                Callback::from(move |_: DragEvent| dragged.set(Some(i)))
+                            };
+                            let drop_ondragover = Callback::from(|event: DragEvent| event.prevent_default());
+                            let drop_ondrop = {
+                                let dragged = dragged.clone();
+                                let app_dispatch = app_dispatch.clone();
+                                Callback::from(move |event: DragEvent| {
+                                    event.prevent_default();
+                                    if let Some(from) = *dragged {
+                                        app_dispatch.reduce_mut(|app| {
+                                            let svg = app.svgs.remove(from);
+                                            app.svgs.insert(i, svg);
+                                        });
+                                    }
+                                    dragged.set(None);
+                                })
+                            };
+
                             let body = html!{
                                 <div>
+                                    <span class="chip" draggable="true" style="cursor: move;" ondragstart={drag_ondragstart}>
+                                        { "⠿ Drag to reorder" }
+                                    </span>
+                                    <span
+                                        class={classes!("chip", svg.locked.then_some("active"))}
+                                        style="cursor: pointer;"
+                                        title="Keep the offset, scale, and rotation as they are"
+                                        onclick={lock_onclick}
+                                    >
+                                        { if svg.locked { "🔒 Locked" } else { "🔓 Unlocked" } }
+                                    </span>
+                                    <label class="form-checkbox form-inline">
+                                        <input type="checkbox" checked={svg.selected} onchange={select_toggle.reform(|_: Event| ())} />
+                                        <i class="form-icon"></i>
+                                        { "Select" }
+                                    </label>
                                     <SvgPreview
                                         svg_content={svg_content.clone()}
                                         scale={svg_scale}
                                         filename={svg_filename.clone()}
                                         dimensions={svg_dimensions}
                                         offset={svg_offset}
+                                        rotation={svg.rotation}
                                         on_offset_change={on_offset_change}
+                                        region={svg.region}
+                                        on_region_change={on_region_change}
+                                        locked={svg.locked}
+                                        selected={svg.selected}
+                                        on_select_toggle={select_toggle.clone()}
                                     />
+                                    <GcodePreview svg_index={i} />
                                     <div class="form-group" style="margin-top: 10px;">
                                         <label class="form-label">{"Scale:"}</label>
                                         <input
@@ -265,29 +372,85 @@ fn app() -> Html {
                                             step="0.1"
                                             min="0.1"
                                             value={svg_scale.to_string()}
+                                            disabled={svg.locked}
                                             oninput={scale_oninput}
                                             style="width: 100%;"
                                         />
                                     </div>
+                                    <div class="form-group">
+                                        <label class="form-label">{"Rotation (degrees counterclockwise):"}</label>
+                                        <input
+                                            type="number"
+                                            class="form-input"
+                                            step="90"
+                                            value={svg.rotation.to_string()}
+                                            disabled={svg.locked}
+                                            oninput={rotation_oninput}
+                                            style="width: 100%;"
+                                        />
+                                    </div>
+                                    if !svg.locked {
+                                        <FixturePicker svg_index={i} />
+                                        <PositionButton svg_index={i} />
+                                    }
+                                    <FrameButtons svg_index={i} />
+                                    <SendButtons svg_index={i} />
+                                    <ElementTree
+                                        svg_content={svg_content.clone()}
+                                        skip_ids={svg.skip_ids.clone()}
+                                        on_toggle={on_element_toggle}
+                                    />
                                 </div>
                             };
 
                             let footer = html!{
-                                <Button
-                                    title="Remove"
-                                    style={ButtonStyle::Primary}
-                                    icon={
-                                        html_nested!(
-                                            <Icon name={IconName::Delete} />
-                                        )
-                                    }
-                                    onclick={remove_svg_onclick}
-                                />
+                                <>
+                                    <EstimateBadge svg_index={i} />
+                                    <ButtonGroup>
+                                        <Button
+                                            title="Perimeter check"
+                                            style={ButtonStyle::Default}
+                                            icon={
+                                                html_nested!(
+                                                    <Icon name={IconName::Download} />
+                                                )
+                                            }
+                                            onclick={perimeter_check_onclick}
+                                        />
+                                        if svg.region.is_some() {
+                                            <Button
+                                                title="Re-cut region"
+                                                style={ButtonStyle::Default}
+                                                icon={
+                                                    html_nested!(
+                                                        <Icon name={IconName::Download} />
+                                                    )
+                                                }
+                                                onclick={recut_onclick}
+                                            />
+                                        }
+                                        <Button
+                                            title="Remove"
+                                            style={ButtonStyle::Primary}
+                                            icon={
+                                                html_nested!(
+                                                    <Icon name={IconName::Delete} />
+                                                )
+                                            }
+                                            onclick={remove_svg_onclick}
+                                        />
+                                    </ButtonGroup>
+                                </>
                             };
                             html!{
-                                <div class={classes!("column", "col-6", "col-xs-12")}>
+                                <div
+                                    id={format!("svg-{i}")}
+                                    class={classes!("column", "col-6", "col-xs-12")}
+                                    ondragover={drop_ondragover}
+                                    ondrop={drop_ondrop}
+                                >
                                     <Card
-                                        title={svg.filename.clone()}
+                                        title={format!("{}. {}", i + 1, svg.filename)}
                                         body={body}
                                         footer={footer}
                                     />
@@ -312,6 +475,292 @@ fn app() -> Html {
     }
 }
 
+/// Convert the SVGs and prompt to download the programs, combined into one, in a ZIP, or one by one,
+/// showing the lines written so far
+async fn generate(
+    app_store: &AppState,
+    lines_written_setter: &UseStateSetter<usize>,
+) -> io::Result<()> {
+    let mut zip = ZipWriter::new(BlobWriter::default());
+    let format_options = FormatOptions {
+        checksums: app_store.settings.postprocess.checksums,
+        line_numbers: app_store.settings.postprocess.line_numbers,
+        newline_before_comment: app_store.settings.postprocess.newline_before_comment,
+        ..Default::default()
+    };
+    let mut lines_written = 0;
+
+    // Combined into one program, cutting the SVGs in the order they are listed
+    let combine = app_store.combine && app_store.svgs.len() > 1;
+    let zipped = !combine && app_store.svgs.len() > 1;
+    let mut combined = Toolpaths::default();
+
+    // Describes each program in the ZIP, with the warnings about the machine's sequences
+    // and those logged while converting its SVG
+    let mut manifest = vec![];
+    let sequence_warnings = lint_sequences(&app_store.settings.machine)
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+    let short_file_names = app_store.settings.postprocess.short_file_names;
+    let mut short_names = short_file_names.then(ShortNames::default);
+    let mut output_name = move |name: String| match &mut short_names {
+        Some(short_names) => short_names.name(&name),
+        None => name,
+    };
+    // Short names go at the root of the ZIP, to copy them straight onto an SD card
+    let directory = if short_file_names {
+        ""
+    } else {
+        "svg2gcode_output/"
+    };
+    if zipped && !directory.is_empty() {
+        zip.add_directory(directory, "")?;
+    }
+
+    for (i, svg) in app_store.svgs.iter().enumerate() {
+        take_warnings();
+        let options = ConversionOptions {
+            dimensions: svg.dimensions,
+            skip_ids: svg.skip_ids.clone(),
+            layer_overrides: app_store.layer_overrides.clone(),
+            path_ordering: app_store.path_ordering,
+            ..Default::default()
+        };
+
+        // Apply scale by adjusting DPI (higher DPI = smaller output, so divide by scale)
+        let mut scaled_conversion_config = app_store.settings.conversion.clone();
+        scaled_conversion_config.dpi /= svg.scale;
+
+        // Apply offset
+        scaled_conversion_config.origin = [Some(svg.offset[0]), Some(svg.offset[1])];
+        scaled_conversion_config.rotation = svg.rotation;
+
+        let document = Document::parse_with_options(
+            svg.content.as_str(),
+            ParsingOptions {
+                allow_dtd: true,
+                ..Default::default()
+            },
+        )
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+        let placement = place(&document, &scaled_conversion_config, &options);
+        let mut toolpaths = svg2toolpaths(&document, &scaled_conversion_config, options, placement);
+
+        if combine {
+            if let Some(first) = toolpaths.toolpaths.first_mut() {
+                first.comments.insert(
+                    0,
+                    format!("{} ({} of {})", svg.filename, i + 1, app_store.svgs.len()),
+                );
+            }
+            combined.toolpaths.append(&mut toolpaths.toolpaths);
+            combined.comments.append(&mut toolpaths.comments);
+            continue;
+        }
+        // Numbered in the order they are listed, so they sort the same way
+        let filename = output_name(
+            Path::new(&format!("{:02}_{}", i + 1, svg.filename))
+                .with_extension("gcode")
+                .to_string_lossy()
+                .into_owned(),
+        );
+        // What the SVG was converted with, for the manifest and the comment of its file
+        let settings = Settings {
+            conversion: scaled_conversion_config.clone(),
+            ..app_store.settings.clone()
+        };
+        let settings_json = serde_json::to_string(&settings)?;
+        let program = program(
+            toolpaths,
+            &scaled_conversion_config,
+            &app_store.layer_overrides,
+            &app_store.settings,
+        );
+        if zipped {
+            manifest.push(ManifestEntry {
+                source: Some(ManifestSource::new(
+                    svg.filename.as_str(),
+                    svg.content.as_bytes(),
+                )),
+                warnings: sequence_warnings
+                    .iter()
+                    .cloned()
+                    .chain(take_warnings())
+                    .collect(),
+                ..ManifestEntry::new(filename.as_str(), &program, settings)
+            });
+        }
+
+        if zipped {
+            // Too long for a comment, but still in the manifest
+            let comment = if settings_json.len() > zip::MAX_COMMENT_LENGTH {
+                warn!("The settings of {filename} are too long to go in its comment in the ZIP");
+                ""
+            } else {
+                &settings_json
+            };
+            zip.start_file(format!("{directory}{filename}"), comment)?;
+
+            let mut w = LineCounter {
+                inner: &mut zip,
+                lines: lines_written,
+            };
+            format_gcode_progressively(
+                &program,
+                format_options.clone(),
+                app_store.settings.postprocess.precision,
+                app_store.settings.machine.max_line_length,
+                app_store
+                    .settings
+                    .machine
+                    .dialect
+                    .is_some_and(Dialect::modal_motion),
+                &mut w,
+                |lines| lines_written_setter.set(lines),
+            )
+            .await?;
+            lines_written = w.lines;
+        } else {
+            let filepath = output_name(
+                Path::new(svg.filename.as_str())
+                    .with_extension("gcode")
+                    .to_string_lossy()
+                    .into_owned(),
+            );
+            download_program(
+                &program,
+                &app_store.settings,
+                &format_options,
+                filepath,
+                lines_written_setter,
+            )
+            .await?;
+        }
+    }
+
+    let date = Date::new_0().to_iso_string();
+    if combine {
+        let program = program(
+            combined,
+            &app_store.settings.conversion,
+            &app_store.layer_overrides,
+            &app_store.settings,
+        );
+        download_program(
+            &program,
+            &app_store.settings,
+            &format_options,
+            output_name(format!("svg2gcode_combined_{date}.gcode")),
+            lines_written_setter,
+        )
+        .await?;
+    } else if zipped {
+        let manifest_name = output_name("manifest.json".to_string());
+        zip.start_file(format!("{directory}{manifest_name}"), "")?;
+        serde_json::to_writer_pretty(&mut zip, &Manifest::new(manifest))?;
+        let output = zip.finish(&format!(
+            "Created with svg2gcode: https://sameer.github.io/svg2gcode/\n{}",
+            env!("CARGO_PKG_DESCRIPTION")
+        ))?;
+        prompt_download_blob(
+            format!("svg2gcode_bulk_download_{date}.zip"),
+            &output.into_blob("application/zip"),
+        );
+    }
+    Ok(())
+}
+
+/// Emit g-code for the toolpaths of one or more SVGs, starting with the
+/// [`JobEstimate::header`] if it is enabled
+fn program<'a>(
+    toolpaths: Toolpaths,
+    config: &ConversionConfig,
+    layer_overrides: &LayerOverrides,
+    settings: &'a Settings,
+) -> Vec<Token<'a>> {
+    let mut program = toolpaths2program(
+        toolpaths,
+        config,
+        layer_overrides,
+        machine(&settings.machine),
+    );
+    if settings.cost.header {
+        // Left out when the job can't be estimated, i.e. for a feedrate of 0
+        if let Ok(estimate) =
+            JobEstimate::from_program(&program, settings.machine.dialect, &settings.cost)
+        {
+            program.splice(0..0, estimate.header());
+        }
+    }
+    program
+}
+
+/// Format a program and prompt to download it, showing the lines written so far
+async fn download_program(
+    program: &[Token<'_>],
+    settings: &Settings,
+    format_options: &FormatOptions,
+    filepath: impl AsRef<Path>,
+    lines_written_setter: &UseStateSetter<usize>,
+) -> io::Result<()> {
+    let mut w = LineCounter {
+        inner: BlobWriter::default(),
+        lines: 0,
+    };
+    format_gcode_progressively(
+        program,
+        format_options.clone(),
+        settings.postprocess.precision,
+        settings.machine.max_line_length,
+        settings.machine.dialect.is_some_and(Dialect::modal_motion),
+        &mut w,
+        |lines| lines_written_setter.set(lines),
+    )
+    .await?;
+    prompt_download_blob(filepath, &w.inner.into_blob("text/plain"));
+    Ok(())
+}
+
+/// Set up the machine as configured, once the settings have been validated by the form
+fn machine(config: &MachineConfig) -> Machine<'_> {
+    Machine::new(
+        config.supported_functionality.clone(),
+        config
+            .tool_on_sequence
+            .as_deref()
+            .map(snippet_parser)
+            .transpose()
+            .unwrap(),
+        config
+            .tool_off_sequence
+            .as_deref()
+            .map(snippet_parser)
+            .transpose()
+            .unwrap(),
+        config
+            .begin_sequence
+            .as_deref()
+            .map(snippet_parser)
+            .transpose()
+            .unwrap(),
+        config
+            .end_sequence
+            .as_deref()
+            .map(snippet_parser)
+            .transpose()
+            .unwrap(),
+    )
+    .with_hooks(Hooks::from_config(config).unwrap())
+    .with_calibration(config.calibration.clone())
+    .with_backlash(config.backlash)
+    .with_step_size(config.step_size)
+    .with_z_moves(config.travel_z, config.cut_z, config.z_feedrate)
+    .with_plunge(config.plunge, config.ramp_angle, config.helix_radius)
+    .with_dialect(config.dialect)
+}
+
 #[function_component(AppContainer)]
 fn app_container() -> Html {
     html! {
@@ -322,6 +771,6 @@ fn app_container() -> Html {
 }
 
 fn main() {
-    wasm_logger::init(wasm_logger::Config::new(Level::Info));
+    init_logger();
     yew::Renderer::<AppContainer>::new().render();
 }
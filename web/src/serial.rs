@@ -0,0 +1,448 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use js_sys::{Array, Function, Object, Promise, Reflect, Uint8Array};
+use svg2gcode::parse_position_report;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::window;
+
+/// Most Grbl and Marlin boards talk at this rate over USB
+const BAUD_RATE: u32 = 115_200;
+
+/// Milliseconds to wait for the machine to answer
+const REPLY_TIMEOUT: i32 = 2000;
+
+/// Grbl's status report query, followed by Marlin's, so either firmware answers with a position
+const POSITION_QUERY: &[u8] = b"?\nM114\n";
+
+/// Grbl's real-time command to stop jogging, which also drops the jogs that are queued
+const JOG_CANCEL: u8 = 0x85;
+
+/// Grbl's real-time feed hold, which slows down to a stop without losing its position
+const FEED_HOLD: u8 = b'!';
+
+/// Grbl's real-time cycle start, which carries on after a [`FEED_HOLD`], turning the tool back on
+/// first if it was turned off with [`SPINDLE_STOP`]
+const CYCLE_START: u8 = b'~';
+
+/// Grbl's real-time toggle of the tool while held, which turns it off until the cycle starts again
+const SPINDLE_STOP: u8 = 0x9E;
+
+/// Grbl's real-time soft reset, which stops it right away with the tool off
+const SOFT_RESET: u8 = 0x18;
+
+/// Milliseconds between checks for [`pause_stream`] and [`stop_stream`] while streaming
+const POLL_INTERVAL: i32 = 100;
+
+/// Times to ask the machine if it is ready before streaming, since many boards restart when the
+/// port is opened and take a moment to boot
+const HANDSHAKE_TRIES: usize = 3;
+
+/// Set by [`cancel_jog`], so [`jog`] stops sending the rest
+static JOG_CANCELLED: AtomicBool = AtomicBool::new(false);
+
+/// Set by [`pause_stream`], so [`stream`] holds off on sending more lines
+static STREAM_PAUSED: AtomicBool = AtomicBool::new(false);
+
+/// Set by [`stop_stream`], so [`stream`] stops the machine and sends no more lines
+static STREAM_STOPPED: AtomicBool = AtomicBool::new(false);
+
+/// Ask the machine connected over [WebSerial](https://developer.mozilla.org/en-US/docs/Web/API/Web_Serial_API)
+/// where its tool is in work coordinates, i.e. after jogging it to where the artwork should start
+///
+/// The user is asked to pick the port the first time. WebSerial is not in `web-sys`'s stable
+/// bindings, so it is called through [`Reflect`].
+pub async fn read_position() -> Result<[f64; 2], String> {
+    let port = open_port().await?;
+    let position = query_position(&port).await;
+    call(&port, "close", &[]).await?;
+    position
+}
+
+/// Send Grbl jog commands (`$J=`) one at a time, i.e. to frame the job, each once the machine
+/// accepted the last
+///
+/// The port is left open, so [`cancel_jog`] can stop the machine without opening it again, which
+/// resets some controllers.
+pub async fn jog(commands: &[String]) -> Result<(), String> {
+    let port = open_port().await?;
+    let mut reader = LineReader::new(&port);
+    JOG_CANCELLED.store(false, Ordering::Relaxed);
+    for command in commands {
+        if JOG_CANCELLED.load(Ordering::Relaxed) {
+            break;
+        }
+        match send_line(&port, &mut reader, command).await? {
+            Some(reply) if reply.is_ok() => {}
+            Some(reply) => return Err(format!("The machine rejected {command:?}: {}", reply.line)),
+            None => return Err("The machine did not answer, is it running Grbl?".to_string()),
+        }
+    }
+    Ok(())
+}
+
+/// Stop jogging right away, without resetting the controller
+pub async fn cancel_jog() -> Result<(), String> {
+    JOG_CANCELLED.store(true, Ordering::Relaxed);
+    let port = open_port().await?;
+    write(&port, &[JOG_CANCEL]).await
+}
+
+/// Stream a program to the machine one line at a time, sending each once the machine accepted the
+/// last with `ok`, and calling `on_progress` with the number of lines accepted so far
+///
+/// Stops at the first line the machine rejects. Grbl is held in place with the tool off while
+/// paused with [`pause_stream`], other firmware only finishes the moves it was already sent. When
+/// stopped with [`stop_stream`], the machine is stopped and then sent `tool_off` (i.e. the tool off
+/// sequence), since only Grbl turns the tool off by itself.
+pub async fn stream(
+    lines: &[String],
+    tool_off: Option<&str>,
+    mut on_progress: impl FnMut(usize),
+) -> Result<(), String> {
+    let port = open_port().await?;
+    STREAM_PAUSED.store(false, Ordering::Relaxed);
+    STREAM_STOPPED.store(false, Ordering::Relaxed);
+    let mut reader = LineReader::new(&port);
+    let firmware = handshake(&port, &mut reader).await?;
+    let streamed = stream_lines(&port, &mut reader, firmware, lines, &mut on_progress).await;
+    if STREAM_STOPPED.load(Ordering::Relaxed) {
+        stop(&port, &mut reader, firmware, tool_off).await?;
+    }
+    streamed
+}
+
+/// How the machine is paused and stopped, which depends on its firmware
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Firmware {
+    /// Takes real-time commands, see [`FEED_HOLD`]
+    Grbl,
+    /// Marlin or any other firmware that answers lines with `ok`, which is stopped with Marlin's
+    /// quick stop (M410)
+    Other,
+}
+
+/// Wait until the machine is ready and find out what firmware it runs
+///
+/// Many boards restart when the port is opened, so whatever they print while starting up is read
+/// first. Grbl answers `$I` with its version, others answer it with `ok` or an error without one.
+async fn handshake(port: &JsValue, reader: &mut LineReader) -> Result<Firmware, String> {
+    let mut grbl = false;
+    while let Some(line) = reader.line(REPLY_TIMEOUT).await? {
+        grbl |= line.starts_with("Grbl");
+    }
+    for _ in 0..HANDSHAKE_TRIES {
+        write(port, b"$I\n").await?;
+        while let Some(line) = reader.line(REPLY_TIMEOUT).await? {
+            grbl |= line.starts_with("Grbl") || line.starts_with("[VER:");
+            if Reply::is_reply(&line) {
+                return Ok(if grbl {
+                    Firmware::Grbl
+                } else {
+                    Firmware::Other
+                });
+            }
+        }
+    }
+    Err("The machine did not answer, is it running Grbl or Marlin?".to_string())
+}
+
+async fn stream_lines(
+    port: &JsValue,
+    reader: &mut LineReader,
+    firmware: Firmware,
+    lines: &[String],
+    on_progress: &mut impl FnMut(usize),
+) -> Result<(), String> {
+    let mut held = false;
+    for (i, line) in lines.iter().enumerate() {
+        while follow_pause(port, firmware, &mut held).await? {
+            if STREAM_STOPPED.load(Ordering::Relaxed) {
+                return Ok(());
+            }
+            JsFuture::from(delay(POLL_INTERVAL))
+                .await
+                .map_err(js_error)?;
+        }
+        if STREAM_STOPPED.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        write(port, format!("{line}\n").as_bytes()).await?;
+        // Long moves are only accepted once there is room for them in the machine's buffer
+        let reply = loop {
+            if STREAM_STOPPED.load(Ordering::Relaxed) {
+                return Ok(());
+            }
+            follow_pause(port, firmware, &mut held).await?;
+            if let Some(reply) = reader.reply(POLL_INTERVAL).await? {
+                break reply;
+            }
+        };
+        if !reply.is_ok() {
+            return Err(format!(
+                "The machine rejected line {} ({line:?}): {}",
+                i + 1,
+                reply.line
+            ));
+        }
+        on_progress(i + 1);
+    }
+    Ok(())
+}
+
+/// Hold Grbl or let it carry on when [`pause_stream`] was called since the last time, returning
+/// whether the stream is paused
+async fn follow_pause(port: &JsValue, firmware: Firmware, held: &mut bool) -> Result<bool, String> {
+    let paused = STREAM_PAUSED.load(Ordering::Relaxed);
+    if paused != *held && firmware == Firmware::Grbl {
+        if paused {
+            write(port, &[FEED_HOLD]).await?;
+            // The tool can only be turned off once Grbl is holding
+            JsFuture::from(delay(POLL_INTERVAL))
+                .await
+                .map_err(js_error)?;
+            write(port, &[SPINDLE_STOP]).await?;
+        } else {
+            write(port, &[CYCLE_START]).await?;
+        }
+    }
+    *held = paused;
+    Ok(paused)
+}
+
+/// Stop the machine right away, then send it `tool_off`
+async fn stop(
+    port: &JsValue,
+    reader: &mut LineReader,
+    firmware: Firmware,
+    tool_off: Option<&str>,
+) -> Result<(), String> {
+    match firmware {
+        Firmware::Grbl => {
+            write(port, &[SOFT_RESET]).await?;
+            // Grbl greets again once it restarted, and locks itself if it was moving, since it
+            // may have lost its position
+            while let Some(line) = reader.line(REPLY_TIMEOUT).await? {
+                if line.starts_with("Grbl") {
+                    break;
+                }
+            }
+            send_line(port, reader, "$X").await?;
+        }
+        Firmware::Other => {
+            send_line(port, reader, "M410").await?;
+        }
+    }
+    let tool_off = tool_off.unwrap_or_default();
+    for line in tool_off
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+    {
+        match send_line(port, reader, line).await? {
+            Some(reply) if reply.is_ok() => {}
+            Some(reply) => {
+                return Err(format!(
+                    "The machine rejected {line:?} while turning the tool off: {}",
+                    reply.line
+                ))
+            }
+            None => return Err("The machine did not answer while stopping".to_string()),
+        }
+    }
+    Ok(())
+}
+
+/// Hold off on sending more lines of the program being streamed, or carry on
+///
+/// The machine still finishes the moves it was already sent.
+pub fn pause_stream(paused: bool) {
+    STREAM_PAUSED.store(paused, Ordering::Relaxed);
+}
+
+/// Stop the machine and the program being streamed to it
+pub fn stop_stream() {
+    STREAM_STOPPED.store(true, Ordering::Relaxed);
+}
+
+/// The port the user picked before, or the one they pick now, opened if it isn't yet
+async fn open_port() -> Result<JsValue, String> {
+    let serial = get(&get(&window().unwrap(), "navigator")?, "serial")?;
+    if serial.is_undefined() {
+        return Err("This browser does not support WebSerial".to_string());
+    }
+    let ports: Array = call(&serial, "getPorts", &[]).await?.unchecked_into();
+    let port = if ports.length() > 0 {
+        ports.get(0)
+    } else {
+        call(&serial, "requestPort", &[]).await?
+    };
+
+    // Still open if a read was cut short, or after jogging
+    if get(&port, "readable")?.is_null() {
+        let options = Object::new();
+        Reflect::set(&options, &"baudRate".into(), &BAUD_RATE.into()).map_err(js_error)?;
+        call(&port, "open", &[options.into()]).await?;
+    }
+    Ok(port)
+}
+
+async fn query_position(port: &JsValue) -> Result<[f64; 2], String> {
+    let mut reader = LineReader::new(port);
+    write(port, POSITION_QUERY).await?;
+    let mut received = String::new();
+    while let Some(line) = reader.line(REPLY_TIMEOUT).await? {
+        if let Some(position) = parse_position_report(&line) {
+            return Ok(position);
+        }
+        received.push_str(&line);
+        received.push('\n');
+    }
+    Err(format!(
+        "The machine did not report its position: {received:?}"
+    ))
+}
+
+async fn write(port: &JsValue, data: &[u8]) -> Result<(), String> {
+    let writer = invoke(&get(port, "writable")?, "getWriter", &[])?;
+    let written = call(&writer, "write", &[Uint8Array::from(data).into()]).await;
+    invoke(&writer, "releaseLock", &[])?;
+    written.map(|_| ())
+}
+
+/// Send a line and wait for the machine to answer it, or `None` if it didn't
+async fn send_line(
+    port: &JsValue,
+    reader: &mut LineReader,
+    line: &str,
+) -> Result<Option<Reply>, String> {
+    write(port, format!("{line}\n").as_bytes()).await?;
+    reader.reply(REPLY_TIMEOUT).await
+}
+
+/// The machine's answer to a line it was sent
+struct Reply {
+    line: String,
+}
+
+impl Reply {
+    /// Whether the line is an answer to a line sent to the machine, as opposed to anything else it
+    /// sends (i.e. Marlin's `echo:` and `busy:`)
+    fn is_reply(line: &str) -> bool {
+        ["ok", "error", "Error", "ALARM", "!!"]
+            .iter()
+            .any(|reply| line.starts_with(reply))
+    }
+
+    /// Whether the line was accepted, as opposed to an `error` from Grbl or an `Error` or alarm
+    /// from Marlin
+    fn is_ok(&self) -> bool {
+        self.line.starts_with("ok")
+    }
+}
+
+/// Reads what the machine sends a line at a time, keeping the start of a line that is still on
+/// its way
+///
+/// The port's reader is only locked while waiting for a line, so the port can be read elsewhere
+/// (i.e. for the position) in between.
+struct LineReader {
+    port: JsValue,
+    received: String,
+}
+
+impl LineReader {
+    fn new(port: &JsValue) -> Self {
+        Self {
+            port: port.clone(),
+            received: String::new(),
+        }
+    }
+
+    /// The next line the machine sends, or `None` if it didn't come within `timeout` milliseconds
+    async fn line(&mut self, timeout: i32) -> Result<Option<String>, String> {
+        loop {
+            if let Some(end) = self.received.find('\n') {
+                let line = self.received[..end].trim().to_string();
+                self.received.drain(..=end);
+                if !line.is_empty() {
+                    return Ok(Some(line));
+                }
+                continue;
+            }
+            let reader = invoke(&get(&self.port, "readable")?, "getReader", &[])?;
+            let read = Promise::resolve(&invoke(&reader, "read", &[])?);
+            let raced = JsFuture::from(Promise::race(&Array::of2(&read, &delay(timeout)))).await;
+            // A read that is still waiting is turned down by letting go, without taking anything
+            // the machine sends after
+            invoke(&reader, "releaseLock", &[])?;
+            let mut chunk = raced.map_err(js_error)?;
+            // The timeout won the race, unless the read finished right after
+            let timed_out = chunk.is_undefined();
+            if timed_out {
+                match JsFuture::from(read).await {
+                    Ok(read) => chunk = read,
+                    Err(_) => return Ok(None),
+                }
+            }
+            if get(&chunk, "done")?.is_truthy() {
+                return Err("The machine disconnected".to_string());
+            }
+            self.received.push_str(&String::from_utf8_lossy(
+                &get(&chunk, "value")?
+                    .unchecked_into::<Uint8Array>()
+                    .to_vec(),
+            ));
+            if timed_out && !self.received.contains('\n') {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// The next answer to a line, skipping anything else the machine sends, or `None` if none
+    /// came within `timeout` milliseconds of the last line
+    async fn reply(&mut self, timeout: i32) -> Result<Option<Reply>, String> {
+        while let Some(line) = self.line(timeout).await? {
+            if Reply::is_reply(&line) {
+                return Ok(Some(Reply { line }));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Resolves with nothing after `ms` milliseconds
+fn delay(ms: i32) -> Promise {
+    Promise::new(&mut |resolve, _| {
+        window()
+            .unwrap()
+            .set_timeout_with_callback_and_timeout_and_arguments_0(&resolve, ms)
+            .unwrap();
+    })
+}
+
+fn get(target: &JsValue, key: &str) -> Result<JsValue, String> {
+    Reflect::get(target, &key.into()).map_err(js_error)
+}
+
+fn invoke(target: &JsValue, method: &str, args: &[JsValue]) -> Result<JsValue, String> {
+    let function: Function = get(target, method)?
+        .dyn_into()
+        .map_err(|_| format!("{method} is not a function"))?;
+    function
+        .apply(target, &args.iter().collect::<Array>())
+        .map_err(js_error)
+}
+
+/// Call an asynchronous method and wait for what it resolves to
+async fn call(target: &JsValue, method: &str, args: &[JsValue]) -> Result<JsValue, String> {
+    let promise = Promise::resolve(&invoke(target, method, args)?);
+    JsFuture::from(promise).await.map_err(js_error)
+}
+
+fn js_error(error: JsValue) -> String {
+    error
+        .as_string()
+        .or_else(|| Reflect::get(&error, &"message".into()).ok()?.as_string())
+        .unwrap_or_else(|| format!("{error:?}"))
+}
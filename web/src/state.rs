@@ -1,7 +1,15 @@
+use lyon_geom::euclid::default::Box2D;
 use serde::{Deserialize, Serialize};
-use std::{convert::TryInto, num::ParseFloatError};
+use std::{
+    collections::BTreeMap,
+    convert::TryInto,
+    num::{ParseFloatError, ParseIntError},
+};
 use svg2gcode::{
-    ConversionConfig, MachineConfig, PostprocessConfig, Settings, SupportedFunctionality, Version,
+    Alignment, Calibration, ConversionConfig, CostConfig, CutDirection, Dialect, Fill, KerfSide,
+    LayerOverrides, LeadShape, MachineConfig, Material, OrderImprovements, OriginMode, PaintFilter,
+    PathOrdering, Plunge, PostprocessConfig, RasterConfig, RegistrationMarks, Settings,
+    StrokeOutline, StrokePower, SupportedFunctionality, Tool, Version,
 };
 use svgtypes::Length;
 use thiserror::Error;
@@ -11,18 +19,116 @@ use yewdux::store::Store;
 #[store]
 pub struct FormState {
     pub tolerance: Result<f64, ParseFloatError>,
-    pub feedrate: Result<f64, ParseFloatError>,
+    pub feedrate: Result<f64, NumberError>,
     pub origin: [Option<Result<f64, ParseFloatError>>; 2],
+    /// Not editable in the form, since each SVG is turned on its own card
+    pub rotation: f64,
+    pub origin_mode: OriginMode,
+    pub invert_y: bool,
+    pub dialect: Option<Dialect>,
     pub circular_interpolation: bool,
+    pub arc_center_offsets: bool,
     pub dpi: Result<f64, ParseFloatError>,
     pub tool_on_sequence: Option<Result<String, String>>,
     pub tool_off_sequence: Option<Result<String, String>>,
     pub begin_sequence: Option<Result<String, String>>,
     pub end_sequence: Option<Result<String, String>>,
+    pub pre_home_sequence: Option<Result<String, String>>,
+    pub home_sequence: Option<Result<String, String>>,
+    pub post_home_sequence: Option<Result<String, String>>,
+    pub pass_begin_sequence: Option<Result<String, String>>,
+    pub pass_end_sequence: Option<Result<String, String>>,
+    pub path_begin_sequence: Option<Result<String, String>>,
+    pub path_end_sequence: Option<Result<String, String>>,
+    /// Not editable in the form, kept so saving doesn't discard imported settings
+    pub calibration: Option<Calibration>,
+    pub backlash: [Result<f64, ParseFloatError>; 2],
+    pub step_size: [Result<f64, ParseFloatError>; 2],
+    pub max_line_length: Result<usize, ParseIntError>,
+    /// Whether the Z axis is moved, to [`Self::travel_z`] and [`Self::cut_z`]
+    pub z_moves: bool,
+    pub travel_z: Result<f64, ParseFloatError>,
+    pub cut_z: Result<f64, ParseFloatError>,
+    pub z_feedrate: Result<f64, ParseFloatError>,
+    pub plunge: Plunge,
+    pub ramp_angle: Result<f64, ParseFloatError>,
+    pub helix_radius: Result<f64, ParseFloatError>,
     pub checksums: bool,
     pub line_numbers: bool,
     pub newline_before_comment: bool,
+    /// Not editable in the form, kept so saving doesn't discard imported settings
+    pub precision: Option<usize>,
+    pub short_file_names: bool,
     pub bed_size: [Result<f64, ParseFloatError>; 2],
+    pub passes: Result<usize, ParseIntError>,
+    pub pass_power: Result<Vec<f64>, ParseFloatError>,
+    pub pass_feedrate: Result<Vec<f64>, NumberError>,
+    pub tools: Vec<Tool>,
+    /// Numbers entered in [`Self::tools`] that can't be used, by the name of the tool and the
+    /// column, which keep the last value that could
+    pub tool_errors: BTreeMap<(String, &'static str), NumberError>,
+    pub tool: Option<String>,
+    pub material_thickness: Result<f64, ParseFloatError>,
+    pub surface_z: Result<f64, ParseFloatError>,
+    pub overcut: Result<f64, ParseFloatError>,
+    pub engrave_depth: Result<f64, ParseFloatError>,
+    pub tab_count: Result<usize, ParseIntError>,
+    pub tab_width: Result<f64, ParseFloatError>,
+    pub tab_passes: Result<usize, ParseIntError>,
+    pub perforation_cut: Result<f64, ParseFloatError>,
+    pub perforation_gap: Result<f64, ParseFloatError>,
+    pub heal_tolerance: Result<f64, ParseFloatError>,
+    pub close_tolerance: Result<f64, ParseFloatError>,
+    pub overlap_tolerance: Result<f64, ParseFloatError>,
+    pub kerf_width: Result<f64, ParseFloatError>,
+    pub kerf_side: KerfSide,
+    pub cut_direction: CutDirection,
+    pub finish_allowance: Result<f64, ParseFloatError>,
+    pub finish_feedrate: Result<f64, ParseFloatError>,
+    pub finish_power: Result<f64, ParseFloatError>,
+    pub lead_in: Result<f64, ParseFloatError>,
+    pub lead_out: Result<f64, ParseFloatError>,
+    pub lead_shape: LeadShape,
+    pub pierce_dwell: Result<f64, ParseFloatError>,
+    pub join_gap: Result<f64, ParseFloatError>,
+    pub stay_down_gap: Result<f64, ParseFloatError>,
+    pub stay_down_travel: Result<f64, ParseFloatError>,
+    pub number_paths: bool,
+    pub sender_markers: bool,
+    /// Not editable in the form, kept so saving doesn't discard imported settings
+    pub font_substitutions: BTreeMap<String, String>,
+    /// Not editable in the form, kept so saving doesn't discard imported settings
+    pub language: String,
+    /// Only toggled in the form, where enabling them uses the default marks
+    pub registration_marks: Option<RegistrationMarks>,
+    /// Not editable in the form, since the reference points are measured for each job
+    pub alignment: Option<Alignment>,
+    /// Not editable in the form, since images are only read with the raster feature, which the
+    /// web interface is built without
+    pub raster: Option<RasterConfig>,
+    /// Only the pattern is picked with this, the spacing and angle are edited on their own
+    pub fill: Option<Fill>,
+    pub fill_spacing: Result<f64, NumberError>,
+    pub fill_angle: Result<f64, ParseFloatError>,
+    /// Only the source is picked with this, the power range is edited on its own
+    pub stroke_power: Option<StrokePower>,
+    pub stroke_power_min: Result<f64, ParseFloatError>,
+    pub stroke_power_max: Result<f64, ParseFloatError>,
+    pub paint_filter: PaintFilter,
+    pub stroke_outline: StrokeOutline,
+    pub cut_last: Vec<String>,
+    /// Not editable in the form, kept so saving doesn't discard imported settings
+    pub order_time_budget: f64,
+    /// Not editable in the form, kept so saving doesn't discard imported settings
+    pub order_improvements: OrderImprovements,
+    pub rapid_feedrate: Result<f64, NumberError>,
+    pub hourly_rate: Result<f64, ParseFloatError>,
+    pub material_cost: Result<f64, ParseFloatError>,
+    pub power: Result<f64, ParseFloatError>,
+    pub acceleration: Result<f64, ParseFloatError>,
+    pub estimate_header: bool,
+    /// Script run on each path, or why it can't be compiled
+    pub script: Option<Result<String, String>>,
 }
 
 impl Default for FormState {
@@ -37,42 +143,185 @@ impl From<&Settings> for FormState {
         Self {
             tolerance: Ok(settings.conversion.tolerance),
             feedrate: Ok(settings.conversion.feedrate),
+            dialect: settings.machine.dialect,
             circular_interpolation: settings
                 .machine
                 .supported_functionality
                 .circular_interpolation,
+            arc_center_offsets: settings.machine.supported_functionality.arc_center_offsets,
             origin: [
                 settings.conversion.origin[0].map(Ok),
                 settings.conversion.origin[1].map(Ok),
             ],
+            rotation: settings.conversion.rotation,
+            origin_mode: settings.conversion.origin_mode,
+            invert_y: settings.conversion.invert_y,
             dpi: Ok(settings.conversion.dpi),
             tool_on_sequence: settings.machine.tool_on_sequence.clone().map(Ok),
             tool_off_sequence: settings.machine.tool_off_sequence.clone().map(Ok),
             begin_sequence: settings.machine.begin_sequence.clone().map(Ok),
             end_sequence: settings.machine.end_sequence.clone().map(Ok),
+            pre_home_sequence: settings.machine.pre_home_sequence.clone().map(Ok),
+            home_sequence: settings.machine.home_sequence.clone().map(Ok),
+            post_home_sequence: settings.machine.post_home_sequence.clone().map(Ok),
+            pass_begin_sequence: settings.machine.pass_begin_sequence.clone().map(Ok),
+            pass_end_sequence: settings.machine.pass_end_sequence.clone().map(Ok),
+            path_begin_sequence: settings.machine.path_begin_sequence.clone().map(Ok),
+            path_end_sequence: settings.machine.path_end_sequence.clone().map(Ok),
+            calibration: settings.machine.calibration.clone(),
+            backlash: [
+                Ok(settings.machine.backlash[0]),
+                Ok(settings.machine.backlash[1]),
+            ],
+            step_size: [
+                Ok(settings.machine.step_size[0]),
+                Ok(settings.machine.step_size[1]),
+            ],
+            max_line_length: Ok(settings.machine.max_line_length),
+            z_moves: settings.machine.travel_z.is_some(),
+            travel_z: Ok(settings.machine.travel_z.unwrap_or_default()),
+            cut_z: Ok(settings.machine.cut_z),
+            z_feedrate: Ok(settings.machine.z_feedrate),
+            plunge: settings.machine.plunge,
+            ramp_angle: Ok(settings.machine.ramp_angle),
+            helix_radius: Ok(settings.machine.helix_radius),
             checksums: settings.postprocess.checksums,
             line_numbers: settings.postprocess.line_numbers,
             newline_before_comment: settings.postprocess.newline_before_comment,
+            precision: settings.postprocess.precision,
+            short_file_names: settings.postprocess.short_file_names,
             bed_size: [
                 Ok(settings.conversion.bed_size[0]),
                 Ok(settings.conversion.bed_size[1]),
             ],
+            passes: Ok(settings.conversion.passes),
+            pass_power: Ok(settings.conversion.pass_power.clone()),
+            pass_feedrate: Ok(settings.conversion.pass_feedrate.clone()),
+            tools: settings.conversion.tools.clone(),
+            tool_errors: BTreeMap::new(),
+            tool: settings.conversion.tool.clone(),
+            material_thickness: Ok(settings.conversion.material.thickness),
+            surface_z: Ok(settings.conversion.material.surface_z),
+            overcut: Ok(settings.conversion.material.overcut),
+            engrave_depth: Ok(settings.conversion.engrave_depth),
+            tab_count: Ok(settings.conversion.tab_count),
+            tab_width: Ok(settings.conversion.tab_width),
+            tab_passes: Ok(settings.conversion.tab_passes),
+            perforation_cut: Ok(settings.conversion.perforation_cut),
+            perforation_gap: Ok(settings.conversion.perforation_gap),
+            heal_tolerance: Ok(settings.conversion.heal_tolerance),
+            close_tolerance: Ok(settings.conversion.close_tolerance),
+            overlap_tolerance: Ok(settings.conversion.overlap_tolerance),
+            kerf_width: Ok(settings.conversion.kerf_width),
+            kerf_side: settings.conversion.kerf_side,
+            cut_direction: settings.conversion.cut_direction,
+            finish_allowance: Ok(settings.conversion.finish_allowance),
+            finish_feedrate: Ok(settings.conversion.finish_feedrate),
+            finish_power: Ok(settings.conversion.finish_power),
+            lead_in: Ok(settings.conversion.lead_in),
+            lead_out: Ok(settings.conversion.lead_out),
+            lead_shape: settings.conversion.lead_shape,
+            pierce_dwell: Ok(settings.conversion.pierce_dwell),
+            join_gap: Ok(settings.conversion.join_gap),
+            stay_down_gap: Ok(settings.conversion.stay_down_gap),
+            stay_down_travel: Ok(settings.conversion.stay_down_travel),
+            number_paths: settings.conversion.number_paths,
+            sender_markers: settings.conversion.sender_markers,
+            font_substitutions: settings.conversion.font_substitutions.clone(),
+            language: settings.conversion.language.clone(),
+            registration_marks: settings.conversion.registration_marks.clone(),
+            alignment: settings.conversion.alignment.clone(),
+            raster: settings.conversion.raster.clone(),
+            fill: settings.conversion.fill.clone(),
+            fill_spacing: Ok(settings
+                .conversion
+                .fill
+                .as_ref()
+                .map_or(Fill::default().spacing, |fill| fill.spacing)),
+            fill_angle: Ok(settings
+                .conversion
+                .fill
+                .as_ref()
+                .map_or(Fill::default().angle, |fill| fill.angle)),
+            stroke_power: settings.conversion.stroke_power.clone(),
+            stroke_power_min: Ok(settings
+                .conversion
+                .stroke_power
+                .as_ref()
+                .map_or(StrokePower::default().min, |stroke_power| stroke_power.min)),
+            stroke_power_max: Ok(settings
+                .conversion
+                .stroke_power
+                .as_ref()
+                .map_or(StrokePower::default().max, |stroke_power| stroke_power.max)),
+            paint_filter: settings.conversion.paint_filter,
+            stroke_outline: settings.conversion.stroke_outline,
+            cut_last: settings.conversion.cut_last.clone(),
+            order_time_budget: settings.conversion.order_time_budget,
+            order_improvements: settings.conversion.order_improvements,
+            rapid_feedrate: Ok(settings.cost.rapid_feedrate),
+            hourly_rate: Ok(settings.cost.hourly_rate),
+            material_cost: Ok(settings.cost.material_cost),
+            power: Ok(settings.cost.power),
+            acceleration: Ok(settings.cost.acceleration),
+            estimate_header: settings.cost.header,
+            script: settings.conversion.script.clone().map(Ok),
         }
     }
 }
 
+/// A number entered in the form that can't be used
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum NumberError {
+    #[error(transparent)]
+    Float(#[from] ParseFloatError),
+    #[error("must be above 0")]
+    NotPositive,
+    #[error("can't be below 0")]
+    Negative,
+}
+
+/// Parse a number that has to be above 0, like a feedrate, which would never finish at 0
+pub fn parse_positive(value: &str) -> Result<f64, NumberError> {
+    let parsed = value.parse::<f64>()?;
+    if parsed > 0. {
+        Ok(parsed)
+    } else {
+        Err(NumberError::NotPositive)
+    }
+}
+
+/// Parse a number that can't be below 0, like a limit that is off at 0
+pub fn parse_non_negative(value: &str) -> Result<f64, NumberError> {
+    let parsed = value.parse::<f64>()?;
+    if parsed >= 0. {
+        Ok(parsed)
+    } else {
+        Err(NumberError::Negative)
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum FormStateConversionError {
     #[error(transparent)]
     Float(#[from] ParseFloatError),
+    #[error(transparent)]
+    Number(#[from] NumberError),
+    #[error(transparent)]
+    Int(#[from] ParseIntError),
     #[error("could not parse gcode: {0}")]
     GCode(String),
+    #[error("could not parse script: {0}")]
+    Script(String),
 }
 
-impl<'a> TryInto<Settings> for &'a FormState {
+impl TryInto<Settings> for &FormState {
     type Error = FormStateConversionError;
 
     fn try_into(self) -> Result<Settings, Self::Error> {
+        if let Some(error) = self.tool_errors.values().next() {
+            return Err(error.clone().into());
+        }
         Ok(Settings {
             conversion: ConversionConfig {
                 tolerance: self.tolerance.clone()?,
@@ -82,15 +331,85 @@ impl<'a> TryInto<Settings> for &'a FormState {
                     self.origin[0].clone().transpose()?,
                     self.origin[1].clone().transpose()?,
                 ],
+                rotation: self.rotation,
+                origin_mode: self.origin_mode,
+                invert_y: self.invert_y,
 		extra_attribute_name: None,
                 bed_size: [
                     self.bed_size[0].clone()?,
                     self.bed_size[1].clone()?,
                 ],
+                passes: self.passes.clone()?,
+                pass_power: self.pass_power.clone()?,
+                pass_feedrate: self.pass_feedrate.clone()?,
+                tools: self.tools.clone(),
+                tool: self.tool.clone(),
+                material: Material {
+                    thickness: self.material_thickness.clone()?,
+                    surface_z: self.surface_z.clone()?,
+                    overcut: self.overcut.clone()?,
+                },
+                engrave_depth: self.engrave_depth.clone()?,
+                tab_count: self.tab_count.clone()?,
+                tab_width: self.tab_width.clone()?,
+                tab_passes: self.tab_passes.clone()?,
+                perforation_cut: self.perforation_cut.clone()?,
+                perforation_gap: self.perforation_gap.clone()?,
+                heal_tolerance: self.heal_tolerance.clone()?,
+                close_tolerance: self.close_tolerance.clone()?,
+                overlap_tolerance: self.overlap_tolerance.clone()?,
+                kerf_width: self.kerf_width.clone()?,
+                kerf_side: self.kerf_side,
+                cut_direction: self.cut_direction,
+                finish_allowance: self.finish_allowance.clone()?,
+                finish_feedrate: self.finish_feedrate.clone()?,
+                finish_power: self.finish_power.clone()?,
+                lead_in: self.lead_in.clone()?,
+                lead_out: self.lead_out.clone()?,
+                lead_shape: self.lead_shape,
+                pierce_dwell: self.pierce_dwell.clone()?,
+                join_gap: self.join_gap.clone()?,
+                stay_down_gap: self.stay_down_gap.clone()?,
+                stay_down_travel: self.stay_down_travel.clone()?,
+                number_paths: self.number_paths,
+                sender_markers: self.sender_markers,
+                font_substitutions: self.font_substitutions.clone(),
+                language: self.language.clone(),
+                registration_marks: self.registration_marks.clone(),
+                alignment: self.alignment.clone(),
+                raster: self.raster.clone(),
+                fill: match &self.fill {
+                    Some(fill) => Some(Fill {
+                        spacing: self.fill_spacing.clone()?,
+                        angle: self.fill_angle.clone()?,
+                        ..fill.clone()
+                    }),
+                    None => None,
+                },
+                stroke_power: match &self.stroke_power {
+                    Some(stroke_power) => Some(StrokePower {
+                        min: self.stroke_power_min.clone()?,
+                        max: self.stroke_power_max.clone()?,
+                        ..stroke_power.clone()
+                    }),
+                    None => None,
+                },
+                paint_filter: self.paint_filter,
+                stroke_outline: self.stroke_outline,
+                cut_last: self.cut_last.clone(),
+                order_time_budget: self.order_time_budget,
+                order_improvements: self.order_improvements,
+                script: self
+                    .script
+                    .clone()
+                    .transpose()
+                    .map_err(FormStateConversionError::Script)?,
             },
             machine: MachineConfig {
+                dialect: self.dialect,
                 supported_functionality: SupportedFunctionality {
                     circular_interpolation: self.circular_interpolation,
+                    arc_center_offsets: self.arc_center_offsets,
                 },
                 tool_on_sequence: self
                     .tool_on_sequence
@@ -112,11 +431,70 @@ impl<'a> TryInto<Settings> for &'a FormState {
                     .clone()
                     .transpose()
                     .map_err(FormStateConversionError::GCode)?,
+                pre_home_sequence: self
+                    .pre_home_sequence
+                    .clone()
+                    .transpose()
+                    .map_err(FormStateConversionError::GCode)?,
+                home_sequence: self
+                    .home_sequence
+                    .clone()
+                    .transpose()
+                    .map_err(FormStateConversionError::GCode)?,
+                post_home_sequence: self
+                    .post_home_sequence
+                    .clone()
+                    .transpose()
+                    .map_err(FormStateConversionError::GCode)?,
+                pass_begin_sequence: self
+                    .pass_begin_sequence
+                    .clone()
+                    .transpose()
+                    .map_err(FormStateConversionError::GCode)?,
+                pass_end_sequence: self
+                    .pass_end_sequence
+                    .clone()
+                    .transpose()
+                    .map_err(FormStateConversionError::GCode)?,
+                path_begin_sequence: self
+                    .path_begin_sequence
+                    .clone()
+                    .transpose()
+                    .map_err(FormStateConversionError::GCode)?,
+                path_end_sequence: self
+                    .path_end_sequence
+                    .clone()
+                    .transpose()
+                    .map_err(FormStateConversionError::GCode)?,
+                calibration: self.calibration.clone(),
+                backlash: [self.backlash[0].clone()?, self.backlash[1].clone()?],
+                step_size: [self.step_size[0].clone()?, self.step_size[1].clone()?],
+                max_line_length: self.max_line_length.clone()?,
+                travel_z: if self.z_moves {
+                    Some(self.travel_z.clone()?)
+                } else {
+                    None
+                },
+                cut_z: self.cut_z.clone()?,
+                z_feedrate: self.z_feedrate.clone()?,
+                plunge: self.plunge,
+                ramp_angle: self.ramp_angle.clone()?,
+                helix_radius: self.helix_radius.clone()?,
             },
             postprocess: PostprocessConfig {
                 checksums: self.checksums,
                 line_numbers: self.line_numbers,
                 newline_before_comment: self.newline_before_comment,
+                precision: self.precision,
+                short_file_names: self.short_file_names,
+            },
+            cost: CostConfig {
+                rapid_feedrate: self.rapid_feedrate.clone()?,
+                hourly_rate: self.hourly_rate.clone()?,
+                material_cost: self.material_cost.clone()?,
+                power: self.power.clone()?,
+                acceleration: self.acceleration.clone()?,
+                header: self.estimate_header,
             },
             version: Version::latest(),
         })
@@ -131,6 +509,39 @@ pub struct AppState {
     pub settings: Settings,
     #[serde(skip)]
     pub svgs: Vec<Svg>,
+    #[serde(default)]
+    pub fixtures: Vec<Fixture>,
+    /// Settings saved under a name to switch between machines, listed after the built-in
+    /// [`svg2gcode::Preset`]s
+    #[serde(default)]
+    pub presets: Vec<SavedPreset>,
+    /// Generate one program cutting all SVGs in the order they are listed, instead of one per SVG
+    #[serde(default)]
+    pub combine: bool,
+    /// Settings of layers drawn differently from the rest, see
+    /// [`svg2gcode::ConversionOptions::layer_overrides`]
+    #[serde(default)]
+    pub layer_overrides: LayerOverrides,
+    /// Same as [`svg2gcode::ConversionOptions::path_ordering`]
+    #[serde(default)]
+    pub path_ordering: PathOrdering,
+}
+
+/// A named place on the bed to put artwork, i.e. a slot of a jig for repeat jobs
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Fixture {
+    pub name: String,
+    /// Same as [`Svg::offset`]
+    pub offset: [f64; 2],
+    /// Same as [`Svg::rotation`]
+    pub rotation: f64,
+}
+
+/// All of the settings for a machine, saved under a name
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SavedPreset {
+    pub name: String,
+    pub settings: Settings,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -140,6 +551,16 @@ pub struct Svg {
     pub dimensions: [Option<Length>; 2],
     pub scale: f64,
     pub offset: [f64; 2],
+    /// Degrees to turn the SVG counterclockwise, see [`svg2gcode::ConversionConfig::rotation`]
+    pub rotation: f64,
+    /// Ids of the elements unchecked in its element tree, see [`svg2gcode::ConversionOptions::skip_ids`]
+    pub skip_ids: Vec<String>,
+    /// Area selected on the preview to re-cut, see [`svg2gcode::Region`]
+    pub region: Option<Box2D<f64>>,
+    /// Keep the offset, scale, and rotation as they are, so dragging on the preview can't move it
+    pub locked: bool,
+    /// Moved and aligned along with the other selected SVGs
+    pub selected: bool,
 }
 
 impl Default for AppState {
@@ -148,6 +569,11 @@ impl Default for AppState {
             first_visit: true,
             settings: Settings::default(),
             svgs: vec![],
+            fixtures: vec![],
+            presets: vec![],
+            combine: false,
+            layer_overrides: LayerOverrides::new(),
+            path_ordering: PathOrdering::Document,
         }
     }
 }
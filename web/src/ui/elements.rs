@@ -0,0 +1,105 @@
+use roxmltree::{Document, Node, ParsingOptions};
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+
+use super::{Icon, IconName};
+
+/// Containers whose elements are never drawn on their own, so there is nothing to select in them
+const UNDRAWN_TAG_NAMES: [&str; 6] = ["defs", "symbol", "marker", "clipPath", "mask", "pattern"];
+
+#[derive(Properties, PartialEq, Clone)]
+pub struct ElementTreeProps {
+    pub svg_content: String,
+    pub skip_ids: Vec<String>,
+    /// Called with the id of a checkbox's element and whether it is now checked
+    pub on_toggle: Callback<(String, bool)>,
+}
+
+/// An element with an id, along with the elements with ids inside it
+#[derive(PartialEq)]
+struct Element {
+    id: String,
+    tag_name: String,
+    children: Vec<Element>,
+}
+
+/// Elements without an id can't be selected, so the ones inside them take their place
+fn elements(node: Node) -> Vec<Element> {
+    node.children()
+        .filter(|child| child.is_element() && !UNDRAWN_TAG_NAMES.contains(&child.tag_name().name()))
+        .flat_map(|child| {
+            let children = elements(child);
+            match child.attribute("id") {
+                Some(id) => vec![Element {
+                    id: id.to_string(),
+                    tag_name: child.tag_name().name().to_string(),
+                    children,
+                }],
+                None => children,
+            }
+        })
+        .collect()
+}
+
+/// Checkboxes for the elements of an SVG with an id, to leave some of them out of the g-code
+#[function_component(ElementTree)]
+pub fn element_tree(props: &ElementTreeProps) -> Html {
+    let elements = use_memo(props.svg_content.clone(), |svg_content| {
+        Document::parse_with_options(
+            svg_content,
+            ParsingOptions {
+                allow_dtd: true,
+                ..Default::default()
+            },
+        )
+        .map(|document| elements(document.root()))
+        .unwrap_or_default()
+    });
+
+    if elements.is_empty() {
+        return html!();
+    }
+
+    html! {
+        <details class="form-group">
+            <summary>{ "Elements" }</summary>
+            { element_list(&elements, props, false) }
+        </details>
+    }
+}
+
+/// Elements inside an unchecked one are left out with it, so they are shown disabled
+fn element_list(elements: &[Element], props: &ElementTreeProps, disabled: bool) -> Html {
+    html! {
+        <ul style="list-style: none; margin: 0 0 0 1em;">
+            {
+                for elements.iter().map(|element| {
+                    let checked = !props.skip_ids.contains(&element.id);
+                    let onchange = {
+                        let id = element.id.clone();
+                        props.on_toggle.reform(move |event: Event| {
+                            (id.clone(), event.target_unchecked_into::<HtmlInputElement>().checked())
+                        })
+                    };
+                    html! {
+                        <li>
+                            <label class="form-checkbox">
+                                <input type="checkbox" {onchange} {checked} {disabled} />
+                                <Icon form={true} name={IconName::None} />
+                                { &element.id }
+                                <span class="text-gray">{ format!(" <{}>", element.tag_name) }</span>
+                            </label>
+                            {
+                                if element.children.is_empty() {
+                                    html!()
+                                } else {
+                                    element_list(&element.children, props, disabled || !checked)
+                                }
+                            }
+                        </li>
+                    }
+                })
+            }
+        </ul>
+    }
+}
@@ -0,0 +1,182 @@
+use lyon_geom::Transform;
+use roxmltree::{Document, ParsingOptions};
+use svg2gcode::{
+    place, svg2toolpaths, toolpaths2program, ConversionConfig, ConversionOptions, CostConfig,
+    EstimateError, HoursMinutesSeconds, JobEstimate, LayerOverrides, MachineConfig, PathOrdering,
+    Toolpath, Toolpaths,
+};
+use svgtypes::Length;
+use yew::prelude::*;
+use yewdux::functional::use_store_value;
+
+use crate::state::{AppState, FormState};
+
+/// Convert an SVG into toolpaths, or [`None`] if it can't be parsed
+pub fn convert(
+    content: &str,
+    dimensions: [Option<Length>; 2],
+    skip_ids: &[String],
+    config: &ConversionConfig,
+    layer_overrides: &LayerOverrides,
+    path_ordering: PathOrdering,
+) -> Option<Vec<Toolpath>> {
+    let document = Document::parse_with_options(
+        content,
+        ParsingOptions {
+            allow_dtd: true,
+            ..Default::default()
+        },
+    )
+    .ok()?;
+    let options = ConversionOptions {
+        dimensions,
+        skip_ids: skip_ids.to_vec(),
+        layer_overrides: layer_overrides.clone(),
+        path_ordering,
+        ..Default::default()
+    };
+    let placement = place(&document, config, &options);
+    Some(svg2toolpaths(&document, config, options, placement).toolpaths)
+}
+
+/// The saved settings, with the feedrates and passes as they are being edited, for estimates
+///
+/// Settings that don't parse yet are left as they were saved.
+pub fn live_settings(
+    app_state: &AppState,
+    form_state: &FormState,
+) -> (ConversionConfig, CostConfig) {
+    let conversion = &app_state.settings.conversion;
+    let config = ConversionConfig {
+        feedrate: form_state.feedrate.clone().unwrap_or(conversion.feedrate),
+        passes: form_state.passes.clone().unwrap_or(conversion.passes),
+        pass_feedrate: form_state
+            .pass_feedrate
+            .clone()
+            .unwrap_or_else(|_| conversion.pass_feedrate.clone()),
+        ..conversion.clone()
+    };
+    let mut cost = app_state.settings.cost.clone();
+    cost.rapid_feedrate = form_state
+        .rapid_feedrate
+        .clone()
+        .unwrap_or(cost.rapid_feedrate);
+    (config, cost)
+}
+
+/// Estimate the job of the toolpaths from the program they are emitted as, with the passes and
+/// feedrates of each layer
+pub fn estimate(
+    toolpaths: &[Toolpath],
+    config: &ConversionConfig,
+    layer_overrides: &LayerOverrides,
+    cost: &CostConfig,
+    machine: &MachineConfig,
+) -> Result<JobEstimate, EstimateError> {
+    let toolpaths = Toolpaths {
+        toolpaths: toolpaths.to_vec(),
+        ..Default::default()
+    };
+    let program = toolpaths2program(toolpaths, config, layer_overrides, crate::machine(machine));
+    JobEstimate::from_program(&program, machine.dialect, cost)
+}
+
+#[derive(Properties, PartialEq, Clone)]
+pub struct EstimateBadgeProps {
+    pub svg_index: usize,
+}
+
+/// Estimated time of the job for an SVG, which follows the feedrate, passes, and scale as they
+/// are edited, before the settings are saved
+///
+/// The SVG is only converted again when something else changes. Filled SVGs are also converted
+/// again when scaled, since the fill spacing doesn't scale with them.
+#[function_component(EstimateBadge)]
+pub fn estimate_badge(props: &EstimateBadgeProps) -> Html {
+    let app_store = use_store_value::<AppState>();
+    let form_state = use_store_value::<FormState>();
+    let svg = &app_store.svgs[props.svg_index];
+    let conversion = &app_store.settings.conversion;
+    let converted_scale = if conversion.fill.is_some() {
+        svg.scale
+    } else {
+        1.
+    };
+
+    let toolpaths = use_memo(
+        (
+            svg.content.clone(),
+            svg.dimensions,
+            svg.skip_ids.clone(),
+            svg.rotation,
+            converted_scale,
+            // Only the settings that change the paths, not how fast they are drawn
+            ConversionConfig {
+                feedrate: 0.,
+                passes: 1,
+                pass_feedrate: vec![],
+                ..conversion.clone()
+            },
+            app_store.layer_overrides.clone(),
+            app_store.path_ordering,
+        ),
+        |(
+            content,
+            dimensions,
+            skip_ids,
+            rotation,
+            scale,
+            config,
+            layer_overrides,
+            path_ordering,
+        )| {
+            let config = ConversionConfig {
+                dpi: config.dpi / scale,
+                rotation: *rotation,
+                ..config.clone()
+            };
+            convert(
+                content,
+                *dimensions,
+                skip_ids,
+                &config,
+                layer_overrides,
+                *path_ordering,
+            )
+        },
+    );
+
+    let Some(toolpaths) = toolpaths.as_ref() else {
+        return html!();
+    };
+    let scale = svg.scale / converted_scale;
+    let scaled = toolpaths
+        .iter()
+        .map(|toolpath| toolpath.transformed(&Transform::scale(scale, scale)))
+        .collect::<Vec<_>>();
+    let (config, cost) = live_settings(&app_store, &form_state);
+    let estimate = match estimate(
+        &scaled,
+        &config,
+        &app_store.layer_overrides,
+        &cost,
+        &app_store.settings.machine,
+    ) {
+        Ok(estimate) => estimate,
+        Err(err) => {
+            return html! {
+                <p class="text-small text-error">{ format!("Can't estimate the job: {err}") }</p>
+            }
+        }
+    };
+
+    html! {
+        <p class="text-small">
+            <span class="label label-rounded label-primary">
+                { format!("≈ {}", HoursMinutesSeconds(estimate.duration)) }
+            </span>
+            { " " }
+            { estimate.to_string() }
+        </p>
+    }
+}
@@ -0,0 +1,90 @@
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+use yewdux::functional::use_store;
+
+use crate::state::{AppState, Fixture};
+
+#[derive(Properties, PartialEq, Clone)]
+pub struct FixturePickerProps {
+    pub svg_index: usize,
+}
+
+/// Places an SVG at one of the saved fixtures with a click, or saves where it is as a new one
+#[function_component(FixturePicker)]
+pub fn fixture_picker(props: &FixturePickerProps) -> Html {
+    let (app_state, app_dispatch) = use_store::<AppState>();
+    let name = use_state(String::new);
+    let i = props.svg_index;
+
+    let name_oninput = {
+        let name = name.clone();
+        Callback::from(move |event: InputEvent| {
+            name.set(event.target_unchecked_into::<HtmlInputElement>().value());
+        })
+    };
+
+    let save_onclick = {
+        let name = name.clone();
+        app_dispatch.reduce_mut_callback(move |app| {
+            let fixture = Fixture {
+                name: name.trim().to_string(),
+                offset: app.svgs[i].offset,
+                rotation: app.svgs[i].rotation,
+            };
+            // Saving under the name of an existing fixture moves it
+            match app.fixtures.iter_mut().find(|f| f.name == fixture.name) {
+                Some(existing) => *existing = fixture,
+                None => app.fixtures.push(fixture),
+            }
+        })
+    };
+
+    html! {
+        <div class="form-group">
+            <label class="form-label">{ "Fixtures:" }</label>
+            {
+                for app_state.fixtures.iter().enumerate().map(|(f, fixture)| {
+                    let apply_onclick = {
+                        let fixture = fixture.clone();
+                        app_dispatch.reduce_mut_callback(move |app| {
+                            let svg = &mut app.svgs[i];
+                            svg.offset = fixture.offset;
+                            svg.rotation = fixture.rotation;
+                            svg.region = None;
+                        })
+                    };
+                    let remove_onclick = app_dispatch.reduce_mut_callback_with(move |app, event: MouseEvent| {
+                        event.stop_propagation();
+                        app.fixtures.remove(f);
+                    });
+                    html! {
+                        <span
+                            class="chip c-hand"
+                            title={format!("X={:.1} Y={:.1} mm, turned {}°", fixture.offset[0], fixture.offset[1], fixture.rotation)}
+                            onclick={apply_onclick}
+                        >
+                            { &fixture.name }
+                            <a class="btn btn-clear" aria-label="Remove" role="button" onclick={remove_onclick}></a>
+                        </span>
+                    }
+                })
+            }
+            <div class="input-group">
+                <input
+                    type="text"
+                    class="form-input"
+                    placeholder="Name (i.e. jig slot A)"
+                    value={(*name).clone()}
+                    oninput={name_oninput}
+                />
+                <button
+                    class="btn input-group-btn"
+                    disabled={name.trim().is_empty()}
+                    onclick={save_onclick}
+                >
+                    { "Save placement" }
+                </button>
+            </div>
+        </div>
+    }
+}
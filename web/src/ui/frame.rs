@@ -0,0 +1,103 @@
+use roxmltree::{Document, ParsingOptions};
+use svg2gcode::{place, svg2toolpaths, toolpaths2frame_jogs, ConversionOptions, PassRegistry};
+use yew::prelude::*;
+use yewdux::functional::use_store_value;
+
+use super::{Button, ButtonGroup, ButtonStyle};
+use crate::{
+    serial::{cancel_jog, jog},
+    state::AppState,
+};
+
+#[derive(Properties, PartialEq, Clone)]
+pub struct FrameButtonsProps {
+    pub svg_index: usize,
+}
+
+/// Traces the bounding box of an SVG on the machine connected over WebSerial with Grbl jogs, which
+/// can be stopped right away
+#[function_component(FrameButtons)]
+pub fn frame_buttons(props: &FrameButtonsProps) -> Html {
+    let app_store = use_store_value::<AppState>();
+    let error = use_state_eq(|| None::<String>);
+    let i = props.svg_index;
+
+    let frame_onclick = {
+        let error = error.clone();
+        Callback::from(move |_| {
+            let svg = &app_store.svgs[i];
+            let Ok(document) = Document::parse_with_options(
+                svg.content.as_str(),
+                ParsingOptions {
+                    allow_dtd: true,
+                    ..Default::default()
+                },
+            ) else {
+                return;
+            };
+            // Same scale and offset as when generating
+            let mut scaled_conversion_config = app_store.settings.conversion.clone();
+            scaled_conversion_config.dpi /= svg.scale;
+            scaled_conversion_config.origin = [Some(svg.offset[0]), Some(svg.offset[1])];
+            scaled_conversion_config.rotation = svg.rotation;
+            let options = ConversionOptions {
+                dimensions: svg.dimensions,
+                skip_ids: svg.skip_ids.clone(),
+                ..Default::default()
+            };
+            let placement = place(&document, &scaled_conversion_config, &options);
+            let mut toolpaths =
+                svg2toolpaths(&document, &scaled_conversion_config, options, placement);
+            toolpaths.toolpaths =
+                PassRegistry::default().apply(toolpaths.toolpaths, &scaled_conversion_config);
+            let jogs = toolpaths2frame_jogs(
+                &toolpaths,
+                &scaled_conversion_config,
+                &crate::machine(&app_store.settings.machine),
+                app_store.settings.cost.rapid_feedrate,
+            );
+
+            let error = error.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                error.set(jog(&jogs).await.err());
+            });
+        })
+    };
+
+    let stop_onclick = {
+        let error = error.clone();
+        Callback::from(move |_| {
+            let error = error.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                error.set(cancel_jog().await.err());
+            });
+        })
+    };
+
+    html! {
+        <div class="form-group">
+            <ButtonGroup>
+                <Button
+                    title="Frame"
+                    style={ButtonStyle::Default}
+                    onclick={frame_onclick}
+                />
+                <Button
+                    title="Stop"
+                    style={ButtonStyle::Default}
+                    onclick={stop_onclick}
+                />
+            </ButtonGroup>
+            <p class="form-input-hint">
+                { "Traces the outline of the SVG with Grbl jogs over USB, which Stop cancels right away" }
+            </p>
+            {
+                if let Some(error) = &*error {
+                    html! { <p class="text-error">{ error }</p> }
+                } else {
+                    html!()
+                }
+            }
+        </div>
+    }
+}
@@ -0,0 +1,169 @@
+use std::fmt::Write;
+
+use lyon_geom::Point;
+use roxmltree::{Document, ParsingOptions};
+use svg2gcode::{
+    follow_program, svg2program, ConversionConfig, ConversionOptions, ProgramStep, Segment,
+};
+use yew::prelude::*;
+use yewdux::functional::use_store_value;
+
+use crate::state::AppState;
+
+#[derive(Properties, PartialEq, Clone)]
+pub struct GcodePreviewProps {
+    pub svg_index: usize,
+}
+
+/// Moves of a program as SVG path data, in machine coordinates
+#[derive(Debug, Default, PartialEq)]
+struct ProgramPaths {
+    /// Moves with the tool on
+    cuts: String,
+    /// Moves with the tool off, i.e. rapid moves (G0)
+    rapids: String,
+    /// Where the first cut starts
+    start: Option<Point<f64>>,
+}
+
+impl ProgramPaths {
+    /// Draw the moves of a program, see [`follow_program`]
+    fn new(steps: Vec<ProgramStep>) -> Self {
+        let mut paths = Self::default();
+        let moves = steps.into_iter().filter_map(|step| match step {
+            ProgramStep::Move(program_move) => Some((program_move.cuts, program_move.segment?)),
+            ProgramStep::Dwell { .. } => None,
+        });
+        for (cuts, segment) in moves {
+            let (from, to) = (segment.from(), segment.to());
+            if cuts {
+                paths.start.get_or_insert(from);
+            }
+            let d = if cuts {
+                &mut paths.cuts
+            } else {
+                &mut paths.rapids
+            };
+            let _ = write!(d, "M{} {}", from.x, from.y);
+            match segment {
+                // Arcs are drawn the same way around in the y-up machine coordinates as on the
+                // machine
+                Segment::Arc(arc) => {
+                    let _ = write!(
+                        d,
+                        "A{r} {r} 0 {large} {sweep} {} {}",
+                        to.x,
+                        to.y,
+                        r = arc.radii.x,
+                        large = u8::from(arc.flags.large_arc),
+                        sweep = u8::from(arc.flags.sweep),
+                    );
+                }
+                _ => {
+                    let _ = write!(d, "L{} {}", to.x, to.y);
+                }
+            }
+        }
+        paths
+    }
+}
+
+/// The program generated for an SVG drawn on the bed, with cuts in blue and moves between them in
+/// red, so the order, origin, and any flipping of the machine coordinates can be checked before
+/// downloading it
+///
+/// The program is only generated while the preview is shown.
+#[function_component(GcodePreview)]
+pub fn gcode_preview(props: &GcodePreviewProps) -> Html {
+    let app_state = use_store_value::<AppState>();
+    let shown = use_state_eq(|| false);
+    let svg = &app_state.svgs[props.svg_index];
+    let [bed_width, bed_height] = app_state.settings.conversion.bed_size;
+
+    let paths = use_memo(
+        (
+            *shown,
+            svg.clone(),
+            app_state.settings.conversion.clone(),
+            app_state.settings.machine.clone(),
+            app_state.layer_overrides.clone(),
+            app_state.path_ordering,
+        ),
+        |(shown, svg, conversion, machine, layer_overrides, path_ordering)| {
+            if !shown {
+                return None;
+            }
+            let document = Document::parse_with_options(
+                svg.content.as_str(),
+                ParsingOptions {
+                    allow_dtd: true,
+                    ..Default::default()
+                },
+            )
+            .ok()?;
+            // Same scale and offset as when generating
+            let config = ConversionConfig {
+                dpi: conversion.dpi / svg.scale,
+                origin: [Some(svg.offset[0]), Some(svg.offset[1])],
+                rotation: svg.rotation,
+                ..conversion.clone()
+            };
+            let options = ConversionOptions {
+                dimensions: svg.dimensions,
+                skip_ids: svg.skip_ids.clone(),
+                layer_overrides: layer_overrides.clone(),
+                path_ordering: *path_ordering,
+                ..Default::default()
+            };
+            let program = svg2program(&document, &config, options, crate::machine(machine));
+            Some(ProgramPaths::new(follow_program(&program, machine.dialect)))
+        },
+    );
+
+    let onchange = {
+        let shown = shown.clone();
+        Callback::from(move |event: Event| {
+            shown.set(
+                event
+                    .target_unchecked_into::<web_sys::HtmlInputElement>()
+                    .checked(),
+            );
+        })
+    };
+
+    html! {
+        <div class="form-group">
+            <label class="form-checkbox">
+                <input type="checkbox" checked={*shown} {onchange} />
+                <i class="form-icon"></i>
+                { "Preview G-Code" }
+            </label>
+            if let Some(paths) = paths.as_ref() {
+                <svg
+                    xmlns="http://www.w3.org/2000/svg"
+                    viewBox={format!("0 0 {bed_width} {bed_height}")}
+                    style="width: 100%; aspect-ratio: 1; border: 1px solid #ccc; background: white;"
+                >
+                    // The machine's y-axis points up from the bottom of the bed
+                    <g transform={format!("translate(0 {bed_height}) scale(1 -1)")}>
+                        <rect
+                            width={bed_width.to_string()}
+                            height={bed_height.to_string()}
+                            fill="none"
+                            stroke="#333"
+                            stroke-width="1"
+                        />
+                        <path d={paths.rapids.clone()} fill="none" stroke="#f44336" stroke-width="0.3" stroke-dasharray="2,2" />
+                        <path d={paths.cuts.clone()} fill="none" stroke="#5755d9" stroke-width="0.5" />
+                        if let Some(start) = paths.start {
+                            <circle cx={start.x.to_string()} cy={start.y.to_string()} r="2" fill="#4caf50" />
+                        }
+                    </g>
+                </svg>
+                <p class="form-input-hint">
+                    { "Cuts in blue, moves between them in red dashes, starting at the green dot" }
+                </p>
+            }
+        </div>
+    }
+}
@@ -0,0 +1,215 @@
+use svg2gcode::{bounding_box, ConversionConfig, PathOrdering};
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+use yewdux::functional::use_store;
+
+use super::estimate::convert;
+use crate::state::AppState;
+
+/// Where a selected SVG is cut on the bed, in millimeters
+struct Extent {
+    index: usize,
+    /// Bottom left corner of its paths, moved by the arrangements
+    offset: [f64; 2],
+    /// Where the corner was, which the SVG is moved on from
+    placed: [f64; 2],
+    size: [f64; 2],
+}
+
+/// Extents of the selected SVGs, converted as when generating since the offset is where the
+/// origin mode puts its paths, not the document
+///
+/// SVGs that can't be read are left out.
+fn selected_extents(app: &AppState) -> Vec<Extent> {
+    app.svgs
+        .iter()
+        .enumerate()
+        .filter(|(_, svg)| svg.selected)
+        .filter_map(|(index, svg)| {
+            let config = ConversionConfig {
+                dpi: app.settings.conversion.dpi / svg.scale,
+                origin: [Some(svg.offset[0]), Some(svg.offset[1])],
+                rotation: svg.rotation,
+                ..app.settings.conversion.clone()
+            };
+            let toolpaths = convert(
+                &svg.content,
+                svg.dimensions,
+                &svg.skip_ids,
+                &config,
+                &app.layer_overrides,
+                // Only the bounds are used, which don't depend on the order
+                PathOrdering::Document,
+            )?;
+            let (corner, size) = bounding_box(&toolpaths, config.tolerance)
+                .map_or((svg.offset, [0., 0.]), |bounds| {
+                    (bounds.min.to_array(), bounds.size().to_array())
+                });
+            Some(Extent {
+                index,
+                offset: corner,
+                placed: corner,
+                size,
+            })
+        })
+        .collect()
+}
+
+/// Move each SVG as far as its extent was moved, leaving out locked ones
+fn place(app: &mut AppState, extents: &[Extent]) {
+    for extent in extents {
+        let svg = &mut app.svgs[extent.index];
+        let delta = [0, 1].map(|axis| extent.offset[axis] - extent.placed[axis]);
+        if !svg.locked && delta != [0., 0.] {
+            svg.offset = [svg.offset[0] + delta[0], svg.offset[1] + delta[1]];
+            svg.region = None;
+        }
+    }
+}
+
+/// Lines up the left edges of extents with the leftmost one
+fn align_left(extents: &mut [Extent]) {
+    let left = extents
+        .iter()
+        .map(|e| e.offset[0])
+        .fold(f64::INFINITY, f64::min);
+    extents.iter_mut().for_each(|e| e.offset[0] = left);
+}
+
+/// Lines up the bottom edges of extents with the lowest one
+fn align_bottom(extents: &mut [Extent]) {
+    let bottom = extents
+        .iter()
+        .map(|e| e.offset[1])
+        .fold(f64::INFINITY, f64::min);
+    extents.iter_mut().for_each(|e| e.offset[1] = bottom);
+}
+
+/// Spaces the centers of extents evenly from left to right, keeping the outermost ones in place
+fn distribute_horizontally(extents: &mut [Extent]) {
+    let center = |e: &Extent| e.offset[0] + e.size[0] / 2.;
+    extents.sort_by(|a, b| center(a).total_cmp(&center(b)));
+    let (Some(first), Some(last)) = (extents.first(), extents.last()) else {
+        return;
+    };
+    let (first, step) = (
+        center(first),
+        (center(last) - center(first)) / (extents.len() - 1).max(1) as f64,
+    );
+    for (i, e) in extents.iter_mut().enumerate() {
+        e.offset[0] = first + step * i as f64 - e.size[0] / 2.;
+    }
+}
+
+/// Leaves the same gap between each extent and the next from left to right, keeping the left
+/// edge of the first and the right edge of the last in place
+fn space_evenly(extents: &mut [Extent]) {
+    extents.sort_by(|a, b| a.offset[0].total_cmp(&b.offset[0]));
+    let (Some(first), Some(last)) = (extents.first(), extents.last()) else {
+        return;
+    };
+    let left = first.offset[0];
+    let span = last.offset[0] + last.size[0] - left;
+    let widths = extents.iter().map(|e| e.size[0]).sum::<f64>();
+    let gap = (span - widths) / (extents.len() - 1).max(1) as f64;
+    let mut x = left;
+    for e in extents.iter_mut() {
+        e.offset[0] = x;
+        x += e.size[0] + gap;
+    }
+}
+
+/// Moves and aligns the SVGs selected on their cards together, i.e. to line up parts on a sheet
+///
+/// Locked SVGs are measured like the others, but stay where they are.
+#[function_component(GroupTools)]
+pub fn group_tools() -> Html {
+    let (app_state, app_dispatch) = use_store::<AppState>();
+    let move_by = use_state(|| [String::new(), String::new()]);
+
+    let selected = app_state.svgs.iter().filter(|svg| svg.selected).count();
+    if selected < 2 {
+        return html!();
+    }
+
+    let arrange = |arrange: fn(&mut [Extent])| {
+        app_dispatch.reduce_mut_callback(move |app| {
+            let mut extents = selected_extents(app);
+            arrange(&mut extents);
+            place(app, &extents);
+        })
+    };
+
+    let move_oninput = |axis: usize| {
+        let move_by = move_by.clone();
+        Callback::from(move |event: InputEvent| {
+            let mut value = (*move_by).clone();
+            value[axis] = event.target_unchecked_into::<HtmlInputElement>().value();
+            move_by.set(value);
+        })
+    };
+    let delta = move_by
+        .iter()
+        .map(|d| {
+            if d.trim().is_empty() {
+                Ok(0.)
+            } else {
+                d.trim().parse::<f64>()
+            }
+        })
+        .collect::<Result<Vec<_>, _>>();
+    let move_onclick = {
+        let delta = delta.clone().unwrap_or_default();
+        app_dispatch.reduce_mut_callback(move |app| {
+            for svg in app
+                .svgs
+                .iter_mut()
+                .filter(|svg| svg.selected && !svg.locked)
+            {
+                svg.offset = [svg.offset[0] + delta[0], svg.offset[1] + delta[1]];
+                svg.region = None;
+            }
+        })
+    };
+
+    let clear_onclick = app_dispatch.reduce_mut_callback(|app| {
+        app.svgs.iter_mut().for_each(|svg| svg.selected = false);
+    });
+
+    html! {
+        <div class="card" style="margin-bottom: 10px;">
+            <div class="card-body">
+                <p>{ format!("{selected} SVGs selected, drag one of them to move them all") }</p>
+                <div class="btn-group">
+                    <button class="btn" onclick={arrange(align_left)}>{ "Align left" }</button>
+                    <button class="btn" onclick={arrange(align_bottom)}>{ "Align bottom" }</button>
+                    <button class="btn" onclick={arrange(distribute_horizontally)}>
+                        { "Distribute horizontally" }
+                    </button>
+                    <button class="btn" onclick={arrange(space_evenly)}>{ "Space evenly" }</button>
+                    <button class="btn" onclick={clear_onclick}>{ "Clear selection" }</button>
+                </div>
+                <div class="input-group" style="margin-top: 10px;">
+                    <span class="input-group-addon">{ "Move by X" }</span>
+                    <input
+                        type="number"
+                        class="form-input"
+                        value={move_by[0].clone()}
+                        oninput={move_oninput(0)}
+                    />
+                    <span class="input-group-addon">{ "Y" }</span>
+                    <input
+                        type="number"
+                        class="form-input"
+                        value={move_by[1].clone()}
+                        oninput={move_oninput(1)}
+                    />
+                    <span class="input-group-addon">{ "mm" }</span>
+                    <button class="btn input-group-btn" disabled={delta.is_err()} onclick={move_onclick}>
+                        { "Move" }
+                    </button>
+                </div>
+            </div>
+        </div>
+    }
+}
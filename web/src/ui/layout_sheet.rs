@@ -0,0 +1,124 @@
+use std::fmt::Write;
+
+use js_sys::Date;
+use svg2gcode::{ConversionConfig, PathOrdering};
+use yew::prelude::*;
+use yewdux::functional::use_store_value;
+
+use super::{estimate::convert, Button, ButtonStyle, Icon, IconName};
+use crate::{state::AppState, util::prompt_download};
+
+/// Side of the square drawn to check that the sheet was printed at full size, in millimeters
+const SCALE_CHECK: f64 = 50.;
+
+/// Outlines of every SVG where it will be cut on the bed, with the machine origin and the corner
+/// each SVG is placed by marked, as an SVG sized in millimeters so it prints at full size
+///
+/// SVGs that can't be read are left out.
+fn layout_sheet(app: &AppState) -> String {
+    let [width, height] = app.settings.conversion.bed_size;
+    let mut sheet = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}mm" height="{height}mm" viewBox="0 0 {width} {height}">"#
+    );
+    // The machine's y-axis points up from the bottom of the bed, unlike the sheet's
+    let _ = write!(
+        sheet,
+        r##"<g transform="translate(0 {height}) scale(1 -1)" fill="none" stroke="#000" stroke-width="0.2"><rect width="{width}" height="{height}" stroke="#999"/>"##
+    );
+    let mut labels = String::new();
+    for svg in &app.svgs {
+        let config = ConversionConfig {
+            dpi: app.settings.conversion.dpi / svg.scale,
+            origin: [Some(svg.offset[0]), Some(svg.offset[1])],
+            rotation: svg.rotation,
+            ..app.settings.conversion.clone()
+        };
+        let Some(toolpaths) = convert(
+            &svg.content,
+            svg.dimensions,
+            &svg.skip_ids,
+            &config,
+            &app.layer_overrides,
+            // Only the bounds are used, which don't depend on the order
+            PathOrdering::Document,
+        ) else {
+            continue;
+        };
+        let mut d = String::new();
+        for points in toolpaths
+            .iter()
+            .map(|toolpath| toolpath.flattened(config.tolerance))
+        {
+            for (i, p) in points.iter().enumerate() {
+                let _ = write!(d, "{}{} {}", if i == 0 { 'M' } else { 'L' }, p.x, p.y);
+            }
+        }
+        let [x, y] = svg.offset;
+        let _ = write!(
+            sheet,
+            r##"<path d="{d}"/><path d="M{} {y}h6M{x} {}v6" stroke="#5755d9" stroke-width="0.4"/>"##,
+            x - 3.,
+            y - 3.,
+        );
+        let _ = write!(
+            labels,
+            r#"<text x="{}" y="{}" font-size="3">{}</text>"#,
+            x + 1.,
+            height - y - 1.,
+            escape(&svg.filename)
+        );
+    }
+    // The machine origin, and a square in the far corner to measure on the printed sheet
+    let _ = write!(
+        sheet,
+        r##"<circle r="3" stroke="#f44336" stroke-width="0.4"/><path d="M-5 0h10M0 -5v10" stroke="#f44336" stroke-width="0.4"/><rect x="{}" y="{}" width="{SCALE_CHECK}" height="{SCALE_CHECK}" stroke="#999" stroke-dasharray="2 2"/></g>"##,
+        width - SCALE_CHECK - 5.,
+        height - SCALE_CHECK - 5.,
+    );
+    let _ = write!(
+        sheet,
+        r#"{labels}<text x="4" y="{}" font-size="3">origin</text><text x="{}" y="{}" font-size="3">{SCALE_CHECK} mm square, print at 100% scale</text></svg>"#,
+        height - 4.,
+        width - SCALE_CHECK - 5.,
+        SCALE_CHECK + 9.,
+    );
+    sheet
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Downloads a full size drawing of the bed layout to print and tape onto the bed or the material,
+/// so where each SVG will be cut can be checked before cutting
+#[function_component(LayoutSheetButton)]
+pub fn layout_sheet_button() -> Html {
+    let app_state = use_store_value::<AppState>();
+
+    let onclick = {
+        let app_state = app_state.clone();
+        Callback::from(move |_| {
+            let date = Date::new_0().to_iso_string();
+            prompt_download(
+                format!("svg2gcode_layout_{date}.svg"),
+                layout_sheet(&app_state),
+            );
+        })
+    };
+
+    html! {
+        <Button
+            title="Layout Sheet"
+            style={ButtonStyle::Default}
+            icon={
+                html_nested! (
+                    <Icon name={IconName::Download} />
+                )
+            }
+            disabled={app_state.svgs.is_empty()}
+            {onclick}
+        />
+    }
+}
@@ -6,19 +6,41 @@ use yew::{
     AttrValue, Callback, Children, Html, NodeRef, Properties, TargetCast, ToHtml,
 };
 
+mod elements;
+mod estimate;
+mod fixtures;
+mod frame;
+mod gcode_preview;
+mod group;
+mod layout_sheet;
+mod position;
 mod preview;
+mod send;
+mod summary;
+pub use elements::*;
+pub use estimate::*;
+pub use fixtures::*;
+pub use frame::*;
+pub use gcode_preview::*;
+pub use group::*;
+pub use layout_sheet::*;
+pub use position::*;
 pub use preview::*;
+pub use send::*;
+pub use summary::*;
 
 macro_rules! css_class_enum {
-    ($name: ident $(($prefix: literal))? {
+    ($(#[$meta: meta])* $name: ident $(($prefix: literal))? {
         $(
-            $variant: ident => $class: literal
+            $(#[$variant_meta: meta])* $variant: ident => $class: literal
         ),*
     }) => {
         #[derive(PartialEq, Eq, Clone, Copy)]
+        $(#[$meta])*
         pub enum $name {
             $(
                 #[allow(dead_code)]
+                $(#[$variant_meta])*
                 $variant
             ),*
         }
@@ -117,7 +139,7 @@ where
     let prop_oninput = props.oninput.clone();
     // Wrap callback to determine when user performed an edit
     let oninput = Callback::from(move |event: InputEvent| {
-        if !event.data().map_or(false, |d| d == "ignore") {
+        if event.data().is_none_or(|d| d != "ignore") {
             user_edited.set(true);
         }
         prop_oninput.emit(event);
@@ -257,56 +279,6 @@ where
     }
 }
 
-#[derive(Properties, PartialEq, Clone)]
-pub struct SelectProps {
-    #[prop_or_default]
-    pub children: Children,
-    #[prop_or(false)]
-    pub disabled: bool,
-    #[prop_or(false)]
-    pub multiple: bool,
-}
-
-#[function_component(Select)]
-pub fn select(props: &SelectProps) -> Html {
-    html! {
-        <select class={classes!("form-select")}>{ for props.children.iter() }</select>
-    }
-}
-
-#[derive(Properties, PartialEq, Clone)]
-pub struct OptionProps {
-    #[prop_or_default]
-    pub children: Children,
-    #[prop_or(false)]
-    pub selected: bool,
-    #[prop_or(false)]
-    pub disabled: bool,
-    pub value: Option<&'static str>,
-}
-
-#[function_component(Opt)]
-pub fn option(props: &OptionProps) -> Html {
-    html! {
-        <option value={props.value}>{ for props.children.iter() }</option>
-    }
-}
-
-#[derive(Properties, PartialEq, Clone)]
-pub struct InputGroupProps {
-    #[prop_or_default]
-    pub children: Children,
-}
-
-#[function_component(InputGroup)]
-pub fn input_group(props: &InputGroupProps) -> Html {
-    html! {
-        <div class="input-group">
-            { for props.children.iter() }
-        </div>
-    }
-}
-
 #[derive(Properties, PartialEq, Clone)]
 pub struct FormGroupProps {
     #[prop_or_default]
@@ -396,7 +368,7 @@ where
     let prop_oninput = props.oninput.clone();
     // Wrap callback to determine when user performed an edit
     let oninput = Callback::from(move |event: InputEvent| {
-        if !event.data().map_or(false, |d| d == "ignore") {
+        if event.data().is_none_or(|d| d != "ignore") {
             user_edited.set(true);
         }
         prop_oninput.emit(event);
@@ -439,7 +411,9 @@ where
 }
 
 css_class_enum! {
+    #[derive(Default)]
     ButtonStyle("btn") {
+        #[default]
         Default => "",
         Primary => "primary",
         Link => "link",
@@ -448,12 +422,6 @@ css_class_enum! {
     }
 }
 
-impl Default for ButtonStyle {
-    fn default() -> Self {
-        Self::Default
-    }
-}
-
 #[derive(Properties, PartialEq, Clone)]
 pub struct ButtonProps {
     #[prop_or_default]
@@ -606,19 +574,15 @@ pub struct ModalProps {
 }
 
 css_class_enum! {
+    #[derive(Default)]
     ModalSize("modal") {
         Small => "sm",
         Large => "lg",
+        #[default]
         Default => ""
     }
 }
 
-impl Default for ModalSize {
-    fn default() -> Self {
-        Self::Default
-    }
-}
-
 #[function_component(Modal)]
 pub fn modal(props: &ModalProps) -> Html {
     html! {
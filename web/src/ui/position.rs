@@ -0,0 +1,65 @@
+use yew::prelude::*;
+use yewdux::functional::use_dispatch;
+
+use super::{Button, ButtonStyle};
+use crate::{serial::read_position, state::AppState};
+
+#[derive(Properties, PartialEq, Clone)]
+pub struct PositionButtonProps {
+    pub svg_index: usize,
+}
+
+/// Moves an SVG to where the tool of the machine connected over WebSerial was jogged
+#[function_component(PositionButton)]
+pub fn position_button(props: &PositionButtonProps) -> Html {
+    let app_dispatch = use_dispatch::<AppState>();
+    let reading = use_state_eq(|| false);
+    let error = use_state_eq(|| None::<String>);
+    let i = props.svg_index;
+
+    let onclick = {
+        let reading = reading.clone();
+        let error = error.clone();
+        Callback::from(move |_| {
+            let reading = reading.clone();
+            let error = error.clone();
+            let app_dispatch = app_dispatch.clone();
+            reading.set(true);
+            wasm_bindgen_futures::spawn_local(async move {
+                match read_position().await {
+                    Ok(position) => {
+                        app_dispatch.reduce_mut(|app| {
+                            app.svgs[i].offset = position;
+                            app.svgs[i].region = None;
+                        });
+                        error.set(None);
+                    }
+                    Err(err) => error.set(Some(err)),
+                }
+                reading.set(false);
+            });
+        })
+    };
+
+    html! {
+        <div class="form-group">
+            <Button
+                title="Use current position"
+                style={ButtonStyle::Default}
+                loading={*reading}
+                disabled={*reading}
+                onclick={onclick}
+            />
+            <p class="form-input-hint">
+                { "Reads where the machine was jogged to over USB, and starts the SVG there" }
+            </p>
+            {
+                if let Some(error) = &*error {
+                    html! { <p class="text-error">{ error }</p> }
+                } else {
+                    html!()
+                }
+            }
+        </div>
+    }
+}
@@ -1,10 +1,14 @@
 use std::str::FromStr;
+
 use base64::Engine;
+use lyon_geom::{euclid::default::Box2D, point};
 use roxmltree::Document;
+use svg2gcode::OriginMode;
 use svgtypes::Length;
 use wasm_bindgen::JsCast;
 use yew::prelude::*;
 use yewdux::functional::use_store_value;
+
 use crate::state::AppState;
 
 #[derive(Properties, PartialEq, Clone)]
@@ -14,7 +18,24 @@ pub struct PreviewProps {
     pub filename: String,
     pub dimensions: [Option<Length>; 2],
     pub offset: [f64; 2],
+    /// Degrees counterclockwise, see [`svg2gcode::ConversionConfig::rotation`]
+    #[prop_or_default]
+    pub rotation: f64,
     pub on_offset_change: Callback<[f64; 2]>,
+    /// Area to re-cut in g-code coordinates, see [`svg2gcode::Region`]
+    #[prop_or_default]
+    pub region: Option<Box2D<f64>>,
+    #[prop_or_default]
+    pub on_region_change: Callback<Option<Box2D<f64>>>,
+    /// Don't move the SVG when dragging, only select regions
+    #[prop_or_default]
+    pub locked: bool,
+    /// Whether the SVG is selected to move along with others
+    #[prop_or_default]
+    pub selected: bool,
+    /// Called on Ctrl-click (or Cmd-click), to select or deselect the SVG
+    #[prop_or_default]
+    pub on_select_toggle: Callback<()>,
 }
 
 // Parse SVG size from viewBox or width/height attributes
@@ -118,6 +139,8 @@ pub fn svg_preview(props: &PreviewProps) -> Html {
 
     let is_dragging = use_state(|| false);
     let drag_start = use_state(|| None::<(f64, f64)>);
+    // Corners of the region being selected, on the preview
+    let selecting = use_state(|| None::<((f64, f64), (f64, f64))>);
 
     // Parse SVG dimensions in mm
     let svg_dimensions = parse_svg_dimensions(&props.svg_content, props.dimensions);
@@ -142,16 +165,78 @@ pub fn svg_preview(props: &PreviewProps) -> Html {
         (0.0, 0.0, "Unknown size".to_string())
     };
 
+    // The SVG is drawn as is and then turned, so everything else uses the box it is turned within
+    let (image_width, image_height) = (scaled_width, scaled_height);
+    let (sin, cos) = props.rotation.to_radians().sin_cos();
+    let scaled_width = (image_width * cos).abs() + (image_height * sin).abs();
+    let scaled_height = (image_width * sin).abs() + (image_height * cos).abs();
+
+    // The origin is where the bottom left corner of the outline box is when the offset is its top
+    // left corner, and the box is moved so the point the origin mode picks lands there, as in the
+    // g-code
+    let origin_mode = app_state.settings.conversion.origin_mode;
+    let origin_marker = (props.offset[0], props.offset[1] + scaled_height);
+    let anchor_shift = match origin_mode {
+        OriginMode::BottomLeft | OriginMode::CurrentPosition => (0.0, 0.0),
+        OriginMode::TopLeft => (0.0, scaled_height),
+        OriginMode::Center => (-scaled_width / 2.0, scaled_height / 2.0),
+    };
+    let (left, top) = (
+        props.offset[0] + anchor_shift.0,
+        props.offset[1] + anchor_shift.1,
+    );
+
+    // Counterclockwise on the bed is a negative angle on the preview, since its y-axis points down
+    let image_transform = format!(
+        "translate({} {}) rotate({} {} {})",
+        left + (scaled_width - image_width) / 2.0,
+        top + (scaled_height - image_height) / 2.0,
+        -props.rotation,
+        image_width / 2.0,
+        image_height / 2.0,
+    );
+
     // Check if SVG fits on bed
     let fits_on_bed = scaled_width <= bed_width && scaled_height <= bed_height;
     let warning_color = if fits_on_bed { "#4caf50" } else { "#f44336" };
 
+    // The axes of the g-code are drawn from the origin
+    let axis_length = 10.0;
+    let y_axis_end = if app_state.settings.conversion.invert_y {
+        origin_marker.1 + axis_length
+    } else {
+        origin_marker.1 - axis_length
+    };
+
+    // The preview's y-axis points down from the top of the SVG, and the g-code's points up from its bottom
+    let flip_y = 2.0 * props.offset[1] + scaled_height;
+    let region_rect = match *selecting {
+        Some(((x1, y1), (x2, y2))) => {
+            Some((x1.min(x2), y1.min(y2), (x2 - x1).abs(), (y2 - y1).abs()))
+        }
+        None => props.region.map(|region| {
+            (
+                region.min.x,
+                flip_y - region.max.y,
+                region.width(),
+                region.height(),
+            )
+        }),
+    };
+
     let onmousedown = {
         let is_dragging = is_dragging.clone();
         let drag_start = drag_start.clone();
+        let selecting = selecting.clone();
         let offset = props.offset;
+        let locked = props.locked;
+        let on_select_toggle = props.on_select_toggle.clone();
         Callback::from(move |e: MouseEvent| {
             e.prevent_default();
+            if e.ctrl_key() || e.meta_key() {
+                on_select_toggle.emit(());
+                return;
+            }
             if let Some(target) = e.current_target() {
                 let element: web_sys::Element = target.dyn_into().unwrap();
                 let rect = element.get_bounding_client_rect();
@@ -160,8 +245,13 @@ pub fn svg_preview(props: &PreviewProps) -> Html {
                 let x = ((e.client_x() as f64 - rect.left()) / rect.width()) * bed_width;
                 let y = ((e.client_y() as f64 - rect.top()) / rect.height()) * bed_height;
 
-                drag_start.set(Some((x - offset[0], y - offset[1])));
-                is_dragging.set(true);
+                // Shift-drag selects a region to re-cut instead of moving the SVG
+                if e.shift_key() {
+                    selecting.set(Some(((x, y), (x, y))));
+                } else if !locked {
+                    drag_start.set(Some((x - offset[0], y - offset[1])));
+                    is_dragging.set(true);
+                }
             }
         })
     };
@@ -169,9 +259,20 @@ pub fn svg_preview(props: &PreviewProps) -> Html {
     let onmousemove = {
         let is_dragging = is_dragging.clone();
         let drag_start = drag_start.clone();
+        let selecting = selecting.clone();
         let on_offset_change = props.on_offset_change.clone();
         Callback::from(move |e: MouseEvent| {
-            if *is_dragging {
+            if let Some((from, _)) = *selecting {
+                if let Some(target) = e.current_target() {
+                    let element: web_sys::Element = target.dyn_into().unwrap();
+                    let rect = element.get_bounding_client_rect();
+
+                    let x = ((e.client_x() as f64 - rect.left()) / rect.width()) * bed_width;
+                    let y = ((e.client_y() as f64 - rect.top()) / rect.height()) * bed_height;
+
+                    selecting.set(Some((from, (x, y))));
+                }
+            } else if *is_dragging {
                 e.prevent_default();
                 if let Some((start_x, start_y)) = *drag_start {
                     if let Some(target) = e.current_target() {
@@ -181,8 +282,13 @@ pub fn svg_preview(props: &PreviewProps) -> Html {
                         let x = ((e.client_x() as f64 - rect.left()) / rect.width()) * bed_width;
                         let y = ((e.client_y() as f64 - rect.top()) / rect.height()) * bed_height;
 
-                        let new_offset_x = (x - start_x).max(0.0).min(bed_width - scaled_width);
-                        let new_offset_y = (y - start_y).max(0.0).min(bed_height - scaled_height);
+                        // Kept so the outline box stays on the bed
+                        let new_offset_x = (x - start_x)
+                            .max(-anchor_shift.0)
+                            .min(bed_width - scaled_width - anchor_shift.0);
+                        let new_offset_y = (y - start_y)
+                            .max(-anchor_shift.1)
+                            .min(bed_height - scaled_height - anchor_shift.1);
 
                         on_offset_change.emit([new_offset_x, new_offset_y]);
                     }
@@ -193,15 +299,25 @@ pub fn svg_preview(props: &PreviewProps) -> Html {
 
     let onmouseup = {
         let is_dragging = is_dragging.clone();
+        let selecting = selecting.clone();
+        let on_region_change = props.on_region_change.clone();
         Callback::from(move |_: MouseEvent| {
             is_dragging.set(false);
+            if let Some(((x1, y1), (x2, y2))) = *selecting {
+                let region = Box2D::from_points([point(x1, flip_y - y1), point(x2, flip_y - y2)]);
+                // A click without dragging clears the region
+                on_region_change.emit((!region.is_empty()).then_some(region));
+                selecting.set(None);
+            }
         })
     };
 
     let onmouseleave = {
         let is_dragging = is_dragging.clone();
+        let selecting = selecting.clone();
         Callback::from(move |_: MouseEvent| {
             is_dragging.set(false);
+            selecting.set(None);
         })
     };
 
@@ -210,7 +326,7 @@ pub fn svg_preview(props: &PreviewProps) -> Html {
             <svg
                 xmlns="http://www.w3.org/2000/svg"
                 viewBox={view_box.clone()}
-                style={format!("width: 100%; height: 100%; border: 1px solid #ccc; background: white; cursor: {};", if *is_dragging { "grabbing" } else { "grab" })}
+                style={format!("width: 100%; height: 100%; border: {}; background: white; cursor: {};", if props.selected { "2px solid #5755d9" } else { "1px solid #ccc" }, if props.locked { "default" } else if *is_dragging { "grabbing" } else { "grab" })}
                 onmousedown={onmousedown}
                 onmousemove={onmousemove}
                 onmouseup={onmouseup}
@@ -261,17 +377,16 @@ pub fn svg_preview(props: &PreviewProps) -> Html {
                 if svg_dimensions.is_some() {
                     <image
                         href={format!("data:image/svg+xml;base64,{}", svg_base64)}
-                        x={props.offset[0].to_string()}
-                        y={props.offset[1].to_string()}
-                        width={scaled_width.to_string()}
-                        height={scaled_height.to_string()}
+                        transform={image_transform}
+                        width={image_width.to_string()}
+                        height={image_height.to_string()}
                         preserveAspectRatio="xMinYMin meet"
                     />
 
                     // Draw outline box around SVG area
                     <rect
-                        x={props.offset[0].to_string()}
-                        y={props.offset[1].to_string()}
+                        x={left.to_string()}
+                        y={top.to_string()}
                         width={scaled_width.to_string()}
                         height={scaled_height.to_string()}
                         fill="none"
@@ -279,6 +394,38 @@ pub fn svg_preview(props: &PreviewProps) -> Html {
                         stroke-width="1"
                         stroke-dasharray="5,5"
                     />
+
+                    // Axis marker at the origin
+                    <g class="origin-marker" stroke="#5755d9" stroke-width="0.8" fill="#5755d9" font-size="4">
+                        <line
+                            x1={origin_marker.0.to_string()}
+                            y1={origin_marker.1.to_string()}
+                            x2={(origin_marker.0 + axis_length).to_string()}
+                            y2={origin_marker.1.to_string()}
+                        />
+                        <line
+                            x1={origin_marker.0.to_string()}
+                            y1={origin_marker.1.to_string()}
+                            x2={origin_marker.0.to_string()}
+                            y2={y_axis_end.to_string()}
+                        />
+                        <circle cx={origin_marker.0.to_string()} cy={origin_marker.1.to_string()} r="1.2" />
+                        <text x={(origin_marker.0 + axis_length + 1.0).to_string()} y={(origin_marker.1 + 1.5).to_string()} stroke="none">{"X"}</text>
+                        <text x={(origin_marker.0 - 1.5).to_string()} y={(y_axis_end + if y_axis_end > origin_marker.1 { 4.0 } else { -1.0 }).to_string()} stroke="none">{"Y"}</text>
+                    </g>
+                }
+
+                // Region to re-cut
+                if let Some((x, y, width, height)) = region_rect {
+                    <rect
+                        x={x.to_string()}
+                        y={y.to_string()}
+                        width={width.to_string()}
+                        height={height.to_string()}
+                        fill="rgba(87, 85, 217, 0.15)"
+                        stroke="#5755d9"
+                        stroke-width="0.5"
+                    />
                 }
             </svg>
             <div style={format!("position: absolute; bottom: 5px; right: 5px; font-size: 10px; background: rgba(255,255,255,0.9); padding: 3px 6px; border-left: 3px solid {};", warning_color)}>
@@ -286,6 +433,14 @@ pub fn svg_preview(props: &PreviewProps) -> Html {
                 <div><strong>{format!("SVG: {}", dimensions_info)}</strong></div>
                 <div>{format!("Scale: {:.2}x", props.scale)}</div>
                 <div>{format!("Offset: X={:.1} Y={:.1} mm", props.offset[0], props.offset[1])}</div>
+                if origin_mode == OriginMode::CurrentPosition {
+                    <div>{"Origin: current position of the machine"}</div>
+                }
+                <div>{"Shift-drag to select an area to re-cut"}</div>
+                <div>{"Ctrl-click to select it with others"}</div>
+                if props.locked {
+                    <div>{"🔒 Locked in place"}</div>
+                }
                 if !fits_on_bed && svg_dimensions.is_some() {
                     <div style="color: #f44336;"><strong>{"⚠ Too large for bed!"}</strong></div>
                 }
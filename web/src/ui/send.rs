@@ -0,0 +1,174 @@
+use g_code::emit::{FormatOptions, Token};
+use roxmltree::{Document, ParsingOptions};
+use svg2gcode::{place, svg2toolpaths, write_gcode, ConversionOptions, Dialect};
+use yew::prelude::*;
+use yewdux::functional::use_store_value;
+
+use super::{Button, ButtonGroup, ButtonStyle};
+use crate::{
+    serial::{pause_stream, stop_stream, stream},
+    state::AppState,
+};
+
+#[derive(Properties, PartialEq, Clone)]
+pub struct SendButtonsProps {
+    pub svg_index: usize,
+}
+
+/// Lines of the program for an SVG as they are sent to the machine, without comments
+fn program_lines(app: &AppState, i: usize) -> Option<Vec<String>> {
+    let svg = &app.svgs[i];
+    let document = Document::parse_with_options(
+        svg.content.as_str(),
+        ParsingOptions {
+            allow_dtd: true,
+            ..Default::default()
+        },
+    )
+    .ok()?;
+    // Same scale and offset as when generating
+    let mut scaled_conversion_config = app.settings.conversion.clone();
+    scaled_conversion_config.dpi /= svg.scale;
+    scaled_conversion_config.origin = [Some(svg.offset[0]), Some(svg.offset[1])];
+    scaled_conversion_config.rotation = svg.rotation;
+    let options = ConversionOptions {
+        dimensions: svg.dimensions,
+        skip_ids: svg.skip_ids.clone(),
+        layer_overrides: app.layer_overrides.clone(),
+        path_ordering: app.path_ordering,
+        ..Default::default()
+    };
+    let placement = place(&document, &scaled_conversion_config, &options);
+    let toolpaths = svg2toolpaths(&document, &scaled_conversion_config, options, placement);
+    let program = crate::program(
+        toolpaths,
+        &scaled_conversion_config,
+        &app.layer_overrides,
+        &app.settings,
+    )
+    .into_iter()
+    .filter(|token| !matches!(token, Token::Comment { .. }))
+    .collect::<Vec<_>>();
+
+    let mut gcode = vec![];
+    write_gcode(
+        &program,
+        &FormatOptions {
+            checksums: app.settings.postprocess.checksums,
+            line_numbers: app.settings.postprocess.line_numbers,
+            ..Default::default()
+        },
+        app.settings.postprocess.precision,
+        app.settings.machine.max_line_length,
+        app.settings
+            .machine
+            .dialect
+            .is_some_and(Dialect::modal_motion),
+        &mut gcode,
+    )
+    .ok()?;
+    Some(
+        String::from_utf8_lossy(&gcode)
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect(),
+    )
+}
+
+/// Streams the program for an SVG to the machine connected over WebSerial, showing how much of it
+/// was sent, so no separate sender is needed
+#[function_component(SendButtons)]
+pub fn send_buttons(props: &SendButtonsProps) -> Html {
+    let app_store = use_store_value::<AppState>();
+    // Lines accepted by the machine and lines in the program, while it is being sent
+    let progress = use_state_eq(|| None::<(usize, usize)>);
+    let paused = use_state_eq(|| false);
+    let error = use_state_eq(|| None::<String>);
+    let i = props.svg_index;
+
+    let send_onclick = {
+        let progress = progress.clone();
+        let paused = paused.clone();
+        let error = error.clone();
+        Callback::from(move |_| {
+            let Some(lines) = program_lines(&app_store, i) else {
+                error.set(Some("The SVG could not be converted".to_string()));
+                return;
+            };
+            let tool_off = app_store.settings.machine.tool_off_sequence.clone();
+            let progress = progress.clone();
+            let error = error.clone();
+            paused.set(false);
+            error.set(None);
+            progress.set(Some((0, lines.len())));
+            wasm_bindgen_futures::spawn_local(async move {
+                let sent = stream(&lines, tool_off.as_deref(), |sent| {
+                    progress.set(Some((sent, lines.len())));
+                })
+                .await;
+                progress.set(None);
+                error.set(sent.err());
+            });
+        })
+    };
+
+    let pause_onclick = {
+        let paused = paused.clone();
+        Callback::from(move |_| {
+            pause_stream(!*paused);
+            paused.set(!*paused);
+        })
+    };
+
+    let stop_onclick = Callback::from(|_| stop_stream());
+
+    html! {
+        <div class="form-group">
+            <ButtonGroup>
+                <Button
+                    title="Send"
+                    style={ButtonStyle::Default}
+                    disabled={progress.is_some()}
+                    onclick={send_onclick}
+                />
+                <Button
+                    title={if *paused { "Resume" } else { "Pause" }}
+                    style={ButtonStyle::Default}
+                    disabled={progress.is_none()}
+                    onclick={pause_onclick}
+                />
+                <Button
+                    title="Stop"
+                    style={ButtonStyle::Default}
+                    disabled={progress.is_none()}
+                    onclick={stop_onclick}
+                />
+            </ButtonGroup>
+            {
+                if let Some((sent, total)) = *progress {
+                    html! {
+                        <>
+                            <progress class="progress" value={sent.to_string()} max={total.to_string()}></progress>
+                            <p class="form-input-hint">{ format!("{sent} of {total} lines sent") }</p>
+                        </>
+                    }
+                } else {
+                    html! {
+                        <p class="form-input-hint">
+                            { "Sends the program to a Grbl or Marlin machine over USB, waiting for it to accept each line. Pause holds off on the next lines, and holds Grbl in place with the tool off. Stop halts the machine and turns the tool off." }
+                        </p>
+                    }
+                }
+            }
+            {
+                if let Some(error) = &*error {
+                    html! { <p class="text-error">{ error }</p> }
+                } else {
+                    html!()
+                }
+            }
+        </div>
+    }
+}
@@ -0,0 +1,229 @@
+use std::time::Duration;
+
+use svg2gcode::{
+    ConversionConfig, CostConfig, HoursMinutesSeconds, LayerOverrides, MachineConfig, Toolpath,
+};
+use yew::prelude::*;
+use yewdux::functional::use_store_value;
+
+use super::estimate::{convert, estimate, live_settings};
+use crate::state::{AppState, FormState};
+
+/// What the summary shows, added up over the SVGs
+struct Totals {
+    duration: Duration,
+    cutting_distance: f64,
+    paths: usize,
+    fits_bed: bool,
+    /// Index of the SVG each warning is about, and the warning
+    warnings: Vec<(usize, String)>,
+}
+
+impl Totals {
+    fn new(
+        filenames: &[String],
+        toolpaths: &[Option<Vec<Toolpath>>],
+        config: &ConversionConfig,
+        layer_overrides: &LayerOverrides,
+        cost: &CostConfig,
+        machine: &MachineConfig,
+    ) -> Self {
+        let bed_size = config.bed_size;
+        let mut totals = Self {
+            duration: Duration::ZERO,
+            cutting_distance: 0.,
+            paths: 0,
+            fits_bed: true,
+            warnings: vec![],
+        };
+        for (i, (filename, toolpaths)) in filenames.iter().zip(toolpaths).enumerate() {
+            let Some(toolpaths) = toolpaths else {
+                totals
+                    .warnings
+                    .push((i, format!("{filename} could not be read")));
+                continue;
+            };
+            if toolpaths.is_empty() {
+                totals
+                    .warnings
+                    .push((i, format!("{filename} has nothing to draw")));
+            }
+            match estimate(toolpaths, config, layer_overrides, cost, machine) {
+                Ok(estimate) => {
+                    totals.duration += estimate.duration;
+                    totals.cutting_distance += estimate.cutting_distance;
+                }
+                Err(err) => totals
+                    .warnings
+                    .push((i, format!("{filename} can't be estimated: {err}"))),
+            }
+            totals.paths += toolpaths.len();
+
+            let fits = toolpaths
+                .iter()
+                .flat_map(|toolpath| toolpath.flattened(config.tolerance))
+                .all(|p| {
+                    (-config.tolerance..=bed_size[0] + config.tolerance).contains(&p.x)
+                        && (-config.tolerance..=bed_size[1] + config.tolerance).contains(&p.y)
+                });
+            totals.fits_bed &= fits;
+            if !fits {
+                totals.warnings.push((
+                    i,
+                    format!(
+                        "{filename} goes past the edges of the {} x {} mm bed",
+                        bed_size[0], bed_size[1]
+                    ),
+                ));
+            }
+        }
+        totals
+    }
+}
+
+/// Totals over all SVGs that will be generated, kept in view at the top of the page while
+/// scrolling through their cards
+///
+/// SVGs that can't be parsed, draw nothing, can't be estimated, or go past the edges of the bed
+/// are counted as warnings, which link to the card of each SVG. The SVGs are only converted again
+/// when something their paths depend on changes, not when one is selected or locked, and the
+/// totals only when those paths or the settings change.
+#[function_component(SummaryBar)]
+pub fn summary_bar() -> Html {
+    let app_store = use_store_value::<AppState>();
+    let form_state = use_store_value::<FormState>();
+
+    // Placed as when generating, so the edges of the bed can be checked
+    let config = ConversionConfig {
+        feedrate: 0.,
+        passes: 1,
+        pass_feedrate: vec![],
+        ..app_store.settings.conversion.clone()
+    };
+    let inputs = app_store
+        .svgs
+        .iter()
+        .map(|svg| {
+            (
+                svg.content.clone(),
+                svg.dimensions,
+                svg.skip_ids.clone(),
+                ConversionConfig {
+                    dpi: config.dpi / svg.scale,
+                    origin: [Some(svg.offset[0]), Some(svg.offset[1])],
+                    rotation: svg.rotation,
+                    ..config.clone()
+                },
+            )
+        })
+        .collect::<Vec<_>>();
+    let toolpaths = use_memo(
+        (
+            inputs.clone(),
+            app_store.layer_overrides.clone(),
+            app_store.path_ordering,
+        ),
+        |(inputs, layer_overrides, path_ordering)| {
+            inputs
+                .iter()
+                .map(|(content, dimensions, skip_ids, config)| {
+                    convert(
+                        content,
+                        *dimensions,
+                        skip_ids,
+                        config,
+                        layer_overrides,
+                        *path_ordering,
+                    )
+                })
+                .collect::<Vec<_>>()
+        },
+    );
+
+    let (config, cost) = live_settings(&app_store, &form_state);
+    let filenames = app_store
+        .svgs
+        .iter()
+        .map(|svg| svg.filename.clone())
+        .collect::<Vec<_>>();
+    // The toolpaths only change along with the inputs they were converted from
+    let totals = use_memo(
+        (
+            inputs,
+            filenames,
+            config,
+            app_store.layer_overrides.clone(),
+            cost,
+            app_store.settings.machine.clone(),
+        ),
+        move |(_, filenames, config, layer_overrides, cost, machine)| {
+            Totals::new(
+                filenames,
+                &toolpaths,
+                config,
+                layer_overrides,
+                cost,
+                machine,
+            )
+        },
+    );
+
+    if app_store.svgs.is_empty() {
+        return html!();
+    }
+    let Totals {
+        duration,
+        cutting_distance,
+        paths,
+        fits_bed,
+        warnings,
+    } = &*totals;
+
+    html! {
+        <div class="card" style="position: sticky; top: 0; z-index: 10; margin-bottom: 10px;">
+            <div class="card-body">
+                <span class="label label-rounded label-primary">
+                    { format!("≈ {}", HoursMinutesSeconds(*duration)) }
+                </span>
+                { format!(
+                    " {:.0} mm cut over {} paths in {} {}, ",
+                    cutting_distance,
+                    paths,
+                    app_store.svgs.len(),
+                    if app_store.svgs.len() == 1 { "file" } else { "files" },
+                ) }
+                {
+                    if *fits_bed {
+                        html! { <span class="text-success">{ "fits the bed" }</span> }
+                    } else {
+                        html! { <span class="text-error">{ "doesn't fit the bed" }</span> }
+                    }
+                }
+                {
+                    if warnings.is_empty() {
+                        html!()
+                    } else {
+                        html! {
+                            <details>
+                                <summary class="text-warning">
+                                    { format!(
+                                        "{} {}",
+                                        warnings.len(),
+                                        if warnings.len() == 1 { "warning" } else { "warnings" },
+                                    ) }
+                                </summary>
+                                <ul>
+                                    {
+                                        for warnings.iter().map(|(i, warning)| html! {
+                                            <li><a href={format!("#svg-{i}")}>{ warning }</a></li>
+                                        })
+                                    }
+                                </ul>
+                            </details>
+                        }
+                    }
+                }
+            </div>
+        </div>
+    }
+}
@@ -1,18 +1,214 @@
 use base64::Engine;
-use std::path::Path;
+use g_code::emit::{FormatOptions, Token};
+use gloo_timers::future::TimeoutFuture;
+use js_sys::{Array, Uint8Array};
+use std::{
+    io::{self, Seek, SeekFrom, Write},
+    path::Path,
+};
+use svg2gcode::write_gcode;
 use wasm_bindgen::JsCast;
-use web_sys::{window, HtmlElement};
+use web_sys::{window, Blob, BlobPropertyBag, HtmlElement, Url};
+
+/// Bytes kept in WebAssembly memory before they are moved into JavaScript memory
+const CHUNK_SIZE: usize = 1 << 20;
+
+/// Tokens formatted between updates of the progress shown while generating
+const BATCH_TOKENS: usize = 50_000;
 
 pub fn prompt_download(path: impl AsRef<Path>, content: impl AsRef<[u8]>) {
+    let mut href = "data:text/plain;base64,".to_string();
+    base64::engine::general_purpose::STANDARD_NO_PAD.encode_string(content, &mut href);
+    click_download_link(path, &href);
+}
+
+/// Same as [`prompt_download`], without copying the content into a data URL
+pub fn prompt_download_blob(path: impl AsRef<Path>, blob: &Blob) {
+    let href = Url::create_object_url_with_blob(blob).unwrap();
+    click_download_link(path, &href);
+    Url::revoke_object_url(&href).unwrap();
+}
+
+fn click_download_link(path: impl AsRef<Path>, href: &str) {
     let window = window().unwrap();
     let document = window.document().unwrap();
     let hyperlink = document.create_element("a").unwrap();
 
-    let mut href = "data:text/plain;base64,".to_string();
-    base64::engine::general_purpose::STANDARD_NO_PAD.encode_string(content, &mut href);
-    hyperlink.set_attribute("href", &href).unwrap();
+    hyperlink.set_attribute("href", href).unwrap();
     hyperlink
         .set_attribute("download", &path.as_ref().display().to_string())
         .unwrap();
     hyperlink.unchecked_into::<HtmlElement>().click();
 }
+
+/// Collects output a chunk at a time in JavaScript memory, so huge programs don't run the
+/// WebAssembly memory out, and builds a [`Blob`] from the chunks at the end
+///
+/// Bytes that were already written can be overwritten after seeking back, which
+/// [`crate::zip::ZipWriter`] does to fill in the size of each file.
+#[derive(Default)]
+pub struct BlobWriter {
+    /// Moved into JavaScript memory, with where each starts
+    chunks: Vec<(u64, Uint8Array)>,
+    /// Not moved into a chunk yet
+    tail: Vec<u8>,
+    position: u64,
+}
+
+impl BlobWriter {
+    fn len(&self) -> u64 {
+        self.tail_start() + self.tail.len() as u64
+    }
+
+    fn tail_start(&self) -> u64 {
+        self.chunks
+            .last()
+            .map_or(0, |(start, chunk)| start + chunk.length() as u64)
+    }
+
+    pub fn into_blob(mut self, mime_type: &str) -> Blob {
+        self.flush_tail();
+        let parts = self
+            .chunks
+            .into_iter()
+            .map(|(_, chunk)| chunk)
+            .collect::<Array>();
+        let options = BlobPropertyBag::new();
+        options.set_type(mime_type);
+        Blob::new_with_u8_array_sequence_and_options(&parts, &options).unwrap()
+    }
+
+    fn flush_tail(&mut self) {
+        if !self.tail.is_empty() {
+            let start = self.tail_start();
+            self.chunks
+                .push((start, Uint8Array::from(self.tail.as_slice())));
+            self.tail.clear();
+        }
+    }
+}
+
+impl Write for BlobWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let tail_start = self.tail_start();
+        if self.position < tail_start {
+            // Overwrite part of a chunk, up to its end
+            let i = self
+                .chunks
+                .partition_point(|(start, _)| *start <= self.position)
+                - 1;
+            let (start, chunk) = &self.chunks[i];
+            let offset = (self.position - start) as u32;
+            let written = buf.len().min((chunk.length() - offset) as usize);
+            chunk
+                .subarray(offset, offset + written as u32)
+                .copy_from(&buf[..written]);
+            self.position += written as u64;
+            return Ok(written);
+        }
+
+        let offset = (self.position - tail_start) as usize;
+        let overwritten = buf.len().min(self.tail.len() - offset);
+        self.tail[offset..offset + overwritten].copy_from_slice(&buf[..overwritten]);
+        self.tail.extend_from_slice(&buf[overwritten..]);
+        self.position += buf.len() as u64;
+        if self.tail.len() >= CHUNK_SIZE && self.position == self.len() {
+            self.flush_tail();
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for BlobWriter {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.len().checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+        match position.filter(|position| *position <= self.len()) {
+            Some(position) => {
+                self.position = position;
+                Ok(position)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "cannot seek outside of what was written",
+            )),
+        }
+    }
+}
+
+/// Counts the lines written through it
+pub struct LineCounter<W> {
+    pub inner: W,
+    pub lines: usize,
+}
+
+impl<W: Write> Write for LineCounter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.lines += buf[..written].iter().filter(|b| **b == b'\n').count();
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Same as [`write_gcode`], but a batch of tokens at a time, yielding to the browser in
+/// between so it can show how many lines have been written
+pub async fn format_gcode_progressively<W: Write>(
+    program: &[Token<'_>],
+    options: FormatOptions,
+    precision: Option<usize>,
+    max_line_length: usize,
+    modal_motion: bool,
+    w: &mut LineCounter<W>,
+    on_progress: impl Fn(usize),
+) -> io::Result<()> {
+    // Line numbers would restart with each batch
+    if options.line_numbers {
+        return write_gcode(
+            program,
+            &options,
+            precision,
+            max_line_length,
+            modal_motion,
+            w,
+        );
+    }
+    let mut rest = program;
+    while !rest.is_empty() {
+        // Split before a command, which always starts a new line
+        let end = rest
+            .iter()
+            .enumerate()
+            .skip(BATCH_TOKENS)
+            .find(|(_, token)| match token {
+                Token::Field(field) => {
+                    matches!(field.letters.to_ascii_uppercase().as_str(), "G" | "M" | "D")
+                }
+                _ => false,
+            })
+            .map_or(rest.len(), |(i, _)| i);
+        // The first move of each batch keeps its G word, which is still the same program
+        write_gcode(
+            &rest[..end],
+            &options,
+            precision,
+            max_line_length,
+            modal_motion,
+            &mut *w,
+        )?;
+        rest = &rest[end..];
+        on_progress(w.lines);
+        TimeoutFuture::new(0).await;
+    }
+    Ok(())
+}
@@ -0,0 +1,264 @@
+use std::io::{self, Seek, SeekFrom, Write};
+
+const LOCAL_HEADER_SIGNATURE: u32 = 0x04034b50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x02014b50;
+const END_OF_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x06054b50;
+/// Version 2.0, needed for directories, made on Unix so the permissions are read
+const VERSION_NEEDED: u16 = 20;
+const VERSION_MADE_BY: u16 = 3 << 8 | VERSION_NEEDED;
+/// Names and comments are UTF-8
+const UTF8_FLAG: u16 = 1 << 11;
+/// Midnight on 1980-01-01, the earliest time a ZIP can hold, in MS-DOS format
+const DOS_TIME: u16 = 0;
+const DOS_DATE: u16 = 1 << 5 | 1;
+/// Where the CRC-32 and sizes are in a local header, filled in once the file is written
+const LOCAL_HEADER_CRC_OFFSET: u64 = 14;
+/// Longest name or comment a file can have, in bytes
+pub const MAX_COMMENT_LENGTH: usize = u16::MAX as usize;
+
+/// A file or directory in the central directory
+struct Entry {
+    name: String,
+    comment: String,
+    header_start: u32,
+    crc32: u32,
+    size: u32,
+}
+
+impl Entry {
+    fn is_directory(&self) -> bool {
+        self.name.ends_with('/')
+    }
+}
+
+/// Writes a ZIP archive of uncompressed files, with a comment for each
+///
+/// Every file has the same timestamp and files are kept in the order they are added, so archives
+/// of the same programs are the same byte for byte and can be diffed. Like other writers that
+/// don't support ZIP64, each file and the whole archive must be under 4 GiB.
+pub struct ZipWriter<W> {
+    inner: W,
+    entries: Vec<Entry>,
+    /// Of the file being written
+    hasher: crc32fast::Hasher,
+    size: u64,
+}
+
+impl<W: Write + Seek> ZipWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            entries: vec![],
+            hasher: crc32fast::Hasher::new(),
+            size: 0,
+        }
+    }
+
+    /// Add a directory, which has a name ending in `/`
+    pub fn add_directory(&mut self, name: impl Into<String>, comment: &str) -> io::Result<()> {
+        let mut name = name.into();
+        if !name.ends_with('/') {
+            name.push('/');
+        }
+        self.start_entry(name, comment)
+    }
+
+    /// Start a file that is written to until the next one is started, or the archive is finished
+    pub fn start_file(&mut self, name: impl Into<String>, comment: &str) -> io::Result<()> {
+        self.start_entry(name.into(), comment)
+    }
+
+    fn start_entry(&mut self, name: String, comment: &str) -> io::Result<()> {
+        self.finish_entry()?;
+        if name.len() > MAX_COMMENT_LENGTH || comment.len() > MAX_COMMENT_LENGTH {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "ZIP file names and comments must be under 64 KiB",
+            ));
+        }
+        let header_start = position(&mut self.inner)?;
+        let mut header = vec![];
+        header.extend(LOCAL_HEADER_SIGNATURE.to_le_bytes());
+        header.extend(VERSION_NEEDED.to_le_bytes());
+        header.extend(UTF8_FLAG.to_le_bytes());
+        // Stored, without compression
+        header.extend(0u16.to_le_bytes());
+        header.extend(DOS_TIME.to_le_bytes());
+        header.extend(DOS_DATE.to_le_bytes());
+        // CRC-32, compressed size, and uncompressed size
+        header.extend([0; 12]);
+        header.extend((name.len() as u16).to_le_bytes());
+        // Extra field length
+        header.extend(0u16.to_le_bytes());
+        header.extend(name.as_bytes());
+        self.inner.write_all(&header)?;
+
+        self.entries.push(Entry {
+            name,
+            comment: comment.to_string(),
+            header_start,
+            crc32: 0,
+            size: 0,
+        });
+        self.hasher = crc32fast::Hasher::new();
+        self.size = 0;
+        Ok(())
+    }
+
+    /// Fill in the CRC-32 and size of the last file in its local header
+    fn finish_entry(&mut self) -> io::Result<()> {
+        let Some(entry) = self
+            .entries
+            .last_mut()
+            .filter(|entry| !entry.is_directory())
+        else {
+            return Ok(());
+        };
+        entry.crc32 = self.hasher.clone().finalize();
+        entry.size = u32::try_from(self.size).map_err(|_| too_large())?;
+        let mut fields = vec![];
+        fields.extend(entry.crc32.to_le_bytes());
+        fields.extend(entry.size.to_le_bytes());
+        fields.extend(entry.size.to_le_bytes());
+        self.inner.seek(SeekFrom::Start(
+            u64::from(entry.header_start) + LOCAL_HEADER_CRC_OFFSET,
+        ))?;
+        self.inner.write_all(&fields)?;
+        self.inner.seek(SeekFrom::End(0))?;
+        Ok(())
+    }
+
+    /// Write the central directory with a `comment` for the whole archive
+    pub fn finish(mut self, comment: &str) -> io::Result<W> {
+        self.finish_entry()?;
+        let directory_start = position(&mut self.inner)?;
+        for entry in &self.entries {
+            // Unix permissions, and the MS-DOS directory attribute for directories
+            let attributes: u32 = if entry.is_directory() {
+                0o40755 << 16 | 0x10
+            } else {
+                0o100644 << 16
+            };
+            let mut header = vec![];
+            header.extend(CENTRAL_HEADER_SIGNATURE.to_le_bytes());
+            header.extend(VERSION_MADE_BY.to_le_bytes());
+            header.extend(VERSION_NEEDED.to_le_bytes());
+            header.extend(UTF8_FLAG.to_le_bytes());
+            header.extend(0u16.to_le_bytes());
+            header.extend(DOS_TIME.to_le_bytes());
+            header.extend(DOS_DATE.to_le_bytes());
+            header.extend(entry.crc32.to_le_bytes());
+            header.extend(entry.size.to_le_bytes());
+            header.extend(entry.size.to_le_bytes());
+            header.extend((entry.name.len() as u16).to_le_bytes());
+            // Extra field length
+            header.extend(0u16.to_le_bytes());
+            header.extend((entry.comment.len() as u16).to_le_bytes());
+            // Disk number and internal attributes
+            header.extend([0; 4]);
+            header.extend(attributes.to_le_bytes());
+            header.extend(entry.header_start.to_le_bytes());
+            header.extend(entry.name.as_bytes());
+            header.extend(entry.comment.as_bytes());
+            self.inner.write_all(&header)?;
+        }
+        let directory_size = position(&mut self.inner)? - directory_start;
+        let entries = u16::try_from(self.entries.len()).map_err(|_| too_large())?;
+        let comment = &comment.as_bytes()[..comment.len().min(MAX_COMMENT_LENGTH)];
+
+        let mut end = vec![];
+        end.extend(END_OF_CENTRAL_DIRECTORY_SIGNATURE.to_le_bytes());
+        // Number of this disk and the one the central directory starts on
+        end.extend([0; 4]);
+        end.extend(entries.to_le_bytes());
+        end.extend(entries.to_le_bytes());
+        end.extend(directory_size.to_le_bytes());
+        end.extend(directory_start.to_le_bytes());
+        end.extend((comment.len() as u16).to_le_bytes());
+        end.extend(comment);
+        self.inner.write_all(&end)?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for ZipWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Where the writer is, which has to fit in the 32 bits that ZIPs without ZIP64 have for it
+fn position(inner: &mut impl Seek) -> io::Result<u32> {
+    u32::try_from(inner.stream_position()?).map_err(|_| too_large())
+}
+
+fn too_large() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "ZIPs over 4 GiB or with over 65535 files are not supported",
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Cursor, Read};
+
+    use super::*;
+
+    fn archive() -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(vec![]));
+        zip.add_directory("output", "").unwrap();
+        zip.start_file("output/a.gcode", "{\"dpi\":96}").unwrap();
+        zip.write_all(b"G0 X1\nM2\n").unwrap();
+        zip.start_file("output/b.gcode", "").unwrap();
+        zip.write_all(&b"G1 X2 Y3\n".repeat(1000)).unwrap();
+        zip.finish("made by a test").unwrap().into_inner()
+    }
+
+    #[test]
+    fn archives_are_read_back_by_another_reader() {
+        let bytes = archive();
+        let mut archive = ::zip::ZipArchive::new(Cursor::new(&bytes)).unwrap();
+        assert_eq!(archive.comment(), b"made by a test");
+        assert_eq!(archive.len(), 3);
+        assert!(archive.by_index(0).unwrap().is_dir());
+        for (i, name, comment, contents) in [
+            (1, "output/a.gcode", "{\"dpi\":96}", b"G0 X1\nM2\n".to_vec()),
+            (2, "output/b.gcode", "", b"G1 X2 Y3\n".repeat(1000)),
+        ] {
+            let mut file = archive.by_index(i).unwrap();
+            assert_eq!(file.name(), name);
+            assert_eq!(file.comment(), comment);
+            // The local header has the same CRC-32 and sizes as the central directory
+            let local = file.header_start() as usize + LOCAL_HEADER_CRC_OFFSET as usize;
+            let crc32 = file.crc32();
+            let size = file.size() as u32;
+            assert_eq!(bytes[local..local + 4], crc32.to_le_bytes());
+            assert_eq!(bytes[local + 4..local + 8], size.to_le_bytes());
+            assert_eq!(bytes[local + 8..local + 12], size.to_le_bytes());
+            // Reading to the end checks the CRC-32
+            let mut read = vec![];
+            file.read_to_end(&mut read).unwrap();
+            assert_eq!(read, contents);
+        }
+    }
+
+    #[test]
+    fn archives_of_the_same_files_are_the_same() {
+        assert_eq!(archive(), archive());
+    }
+
+    #[test]
+    fn comments_over_64_kib_are_rejected() {
+        let mut zip = ZipWriter::new(Cursor::new(vec![]));
+        let comment = "x".repeat(MAX_COMMENT_LENGTH + 1);
+        assert!(zip.start_file("a.gcode", &comment).is_err());
+    }
+}